@@ -3,16 +3,42 @@
 //! Every security-relevant event (connections, authorization decisions,
 //! sessions, pairing) is appended as a single JSON line to an audit log
 //! file.  The log uses `tokio::sync::Mutex` to serialize writes and
-//! `tokio::fs::OpenOptions` in append mode for crash safety.
-
-use std::path::PathBuf;
-
-use anyhow::{Context, Result};
+//! `tokio::fs::OpenOptions` in append mode for crash safety.  Each append
+//! also holds an exclusive `flock` on the file, since CLI commands (`pair`,
+//! `peers`) append to the same log as a running server.  On startup a
+//! partially written trailing line (e.g. from a crash mid-append) is
+//! truncated so subsequent appends start on a clean line.
+//!
+//! Optionally, entries can be linked into a SHA-256 hash chain: each entry
+//! carries the hash of the previous raw JSON line in `prev_hash`, so editing
+//! or deleting any entry breaks the chain for every entry after it.  The
+//! previous hash is re-read from the file under the lock on every append,
+//! so entries written by another process are chained correctly.  Use
+//! [`AuditLog::verify_chain`] to detect tampering.
+
+use std::io::SeekFrom;
+use std::net::SocketAddr;
+use std::os::fd::{AsRawFd, RawFd};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
-use tokio::io::AsyncWriteExt;
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
 use tokio::sync::Mutex;
-use tracing::debug;
+use tokio::time::Duration;
+use tracing::{debug, warn};
+
+/// Chunk size used when scanning backwards for the start of the last line.
+/// Audit entries are well under 1 KB, so one chunk nearly always suffices.
+const TAIL_SCAN_BYTES: u64 = 64 * 1024;
+
+/// Pause between attempts to take the file lock held by another process.
+const LOCK_RETRY: Duration = Duration::from_millis(5);
+
+/// `prev_hash` of the first chained entry in an otherwise empty log.
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
 
 // ---------------------------------------------------------------------------
 // AuditEventType
@@ -32,14 +58,24 @@ pub enum AuditEventType {
     SessionDenied,
     /// A test session completed (normally or via timeout).
     SessionCompleted,
+    /// A running test session was forcibly stopped via `CancelTest`.
+    SessionCancelled,
     /// Pairing mode was enabled on this reflector.
     PairingEnabled,
     /// A new peer was enrolled via the pairing flow.
     PeerPaired,
+    /// A peer was added to the authorized set by an operator.
+    PeerAdded,
     /// A peer was removed from the authorized set.
     PeerRemoved,
     /// The reflector's Ed25519 identity was rotated.
     IdentityRotated,
+    /// Maintenance mode was switched on or off.
+    MaintenanceModeChanged,
+    /// Per-peer usage totals for a UTC day, written at rollover.
+    DailyUsageSummary,
+    /// The reflector shut itself down (idle auto-shutdown).
+    ServerShutdown,
 }
 
 // ---------------------------------------------------------------------------
@@ -60,6 +96,9 @@ pub struct AuditEntry {
     pub peer_id: Option<String>,
     /// This reflector's own endpoint ID.
     pub endpoint_id: String,
+    /// Remote socket address of the connection, for connection-level events.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_addr: Option<SocketAddr>,
     /// Test type (e.g. "udp_echo", "throughput"), if applicable.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub test_type: Option<String>,
@@ -81,6 +120,9 @@ pub struct AuditEntry {
     /// Duration of the session in seconds, if applicable.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub duration_sec: Option<f64>,
+    /// Hex SHA-256 of the previous raw log line (hash chaining only).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prev_hash: Option<String>,
 }
 
 impl AuditEntry {
@@ -92,6 +134,7 @@ impl AuditEntry {
             event_type,
             peer_id: None,
             endpoint_id: endpoint_id.into(),
+            source_addr: None,
             test_type: None,
             test_id: None,
             params: None,
@@ -99,6 +142,7 @@ impl AuditEntry {
             reason: None,
             bytes_transferred: None,
             duration_sec: None,
+            prev_hash: None,
         }
     }
 
@@ -108,6 +152,12 @@ impl AuditEntry {
         self
     }
 
+    /// Builder-style setter for `source_addr`.
+    pub fn with_source_addr(mut self, addr: SocketAddr) -> Self {
+        self.source_addr = Some(addr);
+        self
+    }
+
     /// Builder-style setter for `test_type`.
     pub fn with_test_type(mut self, test_type: impl Into<String>) -> Self {
         self.test_type = Some(test_type.into());
@@ -161,12 +211,31 @@ impl AuditEntry {
 /// to share across async tasks.
 pub struct AuditLog {
     path: PathBuf,
-    writer: Mutex<tokio::fs::File>,
+    writer: Mutex<AuditWriter>,
+}
+
+/// Mutable writer state guarded by the log's mutex.
+struct AuditWriter {
+    file: tokio::fs::File,
+    /// Whether entries carry the previous line's hash in `prev_hash`.
+    hash_chain: bool,
 }
 
 impl AuditLog {
     /// Open (or create) the audit log file at `path` in append mode.
+    ///
+    /// If the existing file ends with an incomplete JSON line, the trailing
+    /// bytes are truncated (with a warning) before the file is opened.
     pub async fn new(path: PathBuf) -> Result<Self> {
+        Self::open(path, false).await
+    }
+
+    /// Open (or create) the audit log, optionally with hash chaining.
+    ///
+    /// With `hash_chain` enabled, each entry chains from the hash of the
+    /// last line in the file at the time it is written (or [`GENESIS_HASH`]
+    /// for an empty log).
+    pub async fn open(path: PathBuf, hash_chain: bool) -> Result<Self> {
         // Ensure the parent directory exists.
         if let Some(parent) = path.parent() {
             tokio::fs::create_dir_all(parent)
@@ -174,6 +243,8 @@ impl AuditLog {
                 .with_context(|| format!("failed to create audit log directory: {}", parent.display()))?;
         }
 
+        recover_trailing_line(&path).await?;
+
         let file = tokio::fs::OpenOptions::new()
             .create(true)
             .append(true)
@@ -181,26 +252,45 @@ impl AuditLog {
             .await
             .with_context(|| format!("failed to open audit log: {}", path.display()))?;
 
-        debug!(path = %path.display(), "audit log opened");
+        debug!(path = %path.display(), hash_chain = hash_chain, "audit log opened");
 
         Ok(Self {
             path,
-            writer: Mutex::new(file),
+            writer: Mutex::new(AuditWriter { file, hash_chain }),
         })
     }
 
     /// Append a single audit entry as a JSON line.
-    pub async fn log(&self, entry: AuditEntry) -> Result<()> {
+    ///
+    /// When hash chaining is enabled, `prev_hash` is overwritten with the
+    /// hash of the last line in the file, read under the file lock.
+    pub async fn log(&self, mut entry: AuditEntry) -> Result<()> {
+        let mut writer = self.writer.lock().await;
+        let _lock = FileLock::acquire(&writer.file)
+            .await
+            .with_context(|| format!("failed to lock audit log: {}", self.path.display()))?;
+
+        entry.prev_hash = if writer.hash_chain {
+            Some(
+                read_last_line(&self.path)
+                    .await?
+                    .map(|line| hash_line(&line))
+                    .unwrap_or_else(|| GENESIS_HASH.to_string()),
+            )
+        } else {
+            None
+        };
         let mut line = serde_json::to_string(&entry)
             .context("failed to serialize audit entry")?;
         line.push('\n');
 
-        let mut writer = self.writer.lock().await;
         writer
+            .file
             .write_all(line.as_bytes())
             .await
             .with_context(|| format!("failed to write to audit log: {}", self.path.display()))?;
         writer
+            .file
             .flush()
             .await
             .with_context(|| format!("failed to flush audit log: {}", self.path.display()))?;
@@ -208,12 +298,211 @@ impl AuditLog {
         Ok(())
     }
 
+    /// Verify the hash chain over the whole log file.
+    ///
+    /// Entries written before chaining was enabled (no `prev_hash`) are
+    /// accepted only if they precede every chained entry.  Returns an error
+    /// naming the first line where the chain is broken.
+    pub async fn verify_chain(&self) -> Result<()> {
+        // Hold the writer lock so we never observe a half-written line.
+        let _writer = self.writer.lock().await;
+        let content = tokio::fs::read(&self.path)
+            .await
+            .with_context(|| format!("failed to read audit log: {}", self.path.display()))?;
+
+        let mut prev_line: Option<&[u8]> = None;
+        let mut chained = false;
+
+        for (idx, line) in content.split(|&b| b == b'\n').enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+            let line_no = idx + 1;
+            let entry: AuditEntry = serde_json::from_slice(line)
+                .with_context(|| format!("audit log line {} is not a valid entry", line_no))?;
+
+            match entry.prev_hash {
+                Some(ref recorded) => {
+                    let expected = prev_line
+                        .map(hash_line)
+                        .unwrap_or_else(|| GENESIS_HASH.to_string());
+                    if *recorded != expected {
+                        bail!("audit hash chain broken at line {}", line_no);
+                    }
+                    chained = true;
+                }
+                None if chained => {
+                    bail!("audit log line {} is missing prev_hash after chained entries", line_no);
+                }
+                None => {}
+            }
+
+            prev_line = Some(line);
+        }
+
+        Ok(())
+    }
+
     /// Return the path of the audit log file.
     pub fn path(&self) -> &PathBuf {
         &self.path
     }
 }
 
+/// Verify that the last line of the audit log is valid JSON.
+///
+/// A crash during `write_all` can leave a partial record at the end of the
+/// file.  That tail is truncated back to the last newline so the next append
+/// starts a fresh line.  A complete record that is merely missing its
+/// terminating newline gets one appended instead.
+///
+/// Runs under the file lock so it never truncates another process's append
+/// in progress.
+async fn recover_trailing_line(path: &Path) -> Result<()> {
+    let mut file = match tokio::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .await
+    {
+        Ok(f) => f,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => {
+            return Err(e)
+                .with_context(|| format!("failed to open audit log for integrity check: {}", path.display()))
+        }
+    };
+    let _lock = FileLock::acquire(&file)
+        .await
+        .with_context(|| format!("failed to lock audit log: {}", path.display()))?;
+
+    let len = file
+        .metadata()
+        .await
+        .with_context(|| format!("failed to stat audit log: {}", path.display()))?
+        .len();
+    if len == 0 {
+        return Ok(());
+    }
+
+    // Locate the last line (ignoring a single terminating newline).
+    file.seek(SeekFrom::Start(len - 1)).await?;
+    let terminated = file.read_u8().await? == b'\n';
+    let body_end = if terminated { len - 1 } else { len };
+    let line_start = line_start_before(&mut file, body_end)
+        .await
+        .with_context(|| format!("failed to scan audit log tail: {}", path.display()))?;
+
+    let last_line = read_range(&mut file, line_start, body_end).await?;
+    if serde_json::from_slice::<serde_json::Value>(&last_line).is_ok() {
+        if !terminated {
+            file.seek(SeekFrom::End(0)).await?;
+            file.write_all(b"\n").await?;
+            file.flush().await?;
+        }
+        return Ok(());
+    }
+
+    warn!(
+        path = %path.display(),
+        discarded_bytes = len - line_start,
+        "audit log ends with an incomplete entry, truncating"
+    );
+    file.set_len(line_start)
+        .await
+        .with_context(|| format!("failed to truncate audit log: {}", path.display()))?;
+    Ok(())
+}
+
+/// The last complete line of the log, if any. Callers hold the file lock,
+/// so the file always ends with a newline.
+async fn read_last_line(path: &Path) -> Result<Option<Vec<u8>>> {
+    let mut file = match tokio::fs::File::open(path).await {
+        Ok(f) => f,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(e).with_context(|| format!("failed to open audit log: {}", path.display()))
+        }
+    };
+    let len = file.metadata().await?.len();
+    if len == 0 {
+        return Ok(None);
+    }
+    let end = len - 1;
+    let start = line_start_before(&mut file, end)
+        .await
+        .with_context(|| format!("failed to scan audit log tail: {}", path.display()))?;
+    if start == end {
+        return Ok(None);
+    }
+    Ok(Some(read_range(&mut file, start, end).await?))
+}
+
+/// Exclusive advisory `flock` on an open file, released on drop.
+///
+/// Borrows only the descriptor, so it must not outlive the file it locks.
+struct FileLock(RawFd);
+
+impl FileLock {
+    /// Take the lock, yielding to the runtime while another process holds it.
+    async fn acquire(file: &tokio::fs::File) -> std::io::Result<Self> {
+        let fd = file.as_raw_fd();
+        loop {
+            // SAFETY: `fd` is a valid open descriptor borrowed from `file`.
+            if unsafe { libc::flock(fd, libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+                return Ok(Self(fd));
+            }
+            let err = std::io::Error::last_os_error();
+            if err.kind() != std::io::ErrorKind::WouldBlock {
+                return Err(err);
+            }
+            tokio::time::sleep(LOCK_RETRY).await;
+        }
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        // SAFETY: the descriptor is still open; see the type's docs.
+        unsafe {
+            libc::flock(self.0, libc::LOCK_UN);
+        }
+    }
+}
+
+/// Offset of the first byte after the last newline before `end`, or 0.
+///
+/// Scans backwards in [`TAIL_SCAN_BYTES`] chunks, so a line of any length
+/// is found rather than silently restarting the hash chain.
+async fn line_start_before(file: &mut tokio::fs::File, end: u64) -> Result<u64> {
+    let mut chunk_end = end;
+    while chunk_end > 0 {
+        let chunk_start = chunk_end.saturating_sub(TAIL_SCAN_BYTES);
+        let chunk = read_range(file, chunk_start, chunk_end).await?;
+        if let Some(i) = chunk.iter().rposition(|&b| b == b'\n') {
+            return Ok(chunk_start + i as u64 + 1);
+        }
+        chunk_end = chunk_start;
+    }
+    Ok(0)
+}
+
+/// Read the bytes in `start..end`.
+async fn read_range(file: &mut tokio::fs::File, start: u64, end: u64) -> Result<Vec<u8>> {
+    file.seek(SeekFrom::Start(start)).await?;
+    let mut buf = vec![0u8; (end - start) as usize];
+    file.read_exact(&mut buf).await.context("failed to read audit log")?;
+    Ok(buf)
+}
+
+/// Hex-encoded SHA-256 of a raw log line (without its trailing newline).
+fn hash_line(line: &[u8]) -> String {
+    Sha256::digest(line)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
 // ---------------------------------------------------------------------------
 // Tests
 // ---------------------------------------------------------------------------
@@ -318,6 +607,20 @@ mod tests {
         assert_eq!(parsed.peer_id.as_deref(), Some("PP-NEW-PEER-1111-B"));
     }
 
+    #[test]
+    fn test_source_addr_is_structured() {
+        let addr: SocketAddr = "192.0.2.10:51234".parse().unwrap();
+        let entry = AuditEntry::new(AuditEventType::ConnectionAccepted, "PP-SELF-1234-5678-A")
+            .with_source_addr(addr);
+
+        let value = serde_json::to_value(&entry).unwrap();
+        assert_eq!(value["source_addr"], "192.0.2.10:51234");
+
+        let parsed: AuditEntry = serde_json::from_value(value).unwrap();
+        assert_eq!(parsed.source_addr, Some(addr));
+        assert_eq!(parsed.source_addr.map(|a| a.ip()), Some(addr.ip()));
+    }
+
     #[tokio::test]
     async fn test_none_fields_omitted_in_json() {
         let entry = AuditEntry::new(AuditEventType::IdentityRotated, "PP-SELF-1234-5678-A");
@@ -325,6 +628,7 @@ mod tests {
 
         // Fields set to None with skip_serializing_if should be absent.
         assert!(!json.contains("\"peer_id\""));
+        assert!(!json.contains("\"source_addr\""));
         assert!(!json.contains("\"test_type\""));
         assert!(!json.contains("\"test_id\""));
         assert!(!json.contains("\"params\""));
@@ -347,10 +651,13 @@ mod tests {
             AuditEventType::SessionGranted,
             AuditEventType::SessionDenied,
             AuditEventType::SessionCompleted,
+            AuditEventType::SessionCancelled,
             AuditEventType::PairingEnabled,
             AuditEventType::PeerPaired,
+            AuditEventType::PeerAdded,
             AuditEventType::PeerRemoved,
             AuditEventType::IdentityRotated,
+            AuditEventType::MaintenanceModeChanged,
         ];
 
         for t in types {
@@ -391,4 +698,175 @@ mod tests {
         assert_eq!(p1.event_type, AuditEventType::ConnectionAccepted);
         assert_eq!(p2.event_type, AuditEventType::ConnectionDenied);
     }
+
+    #[tokio::test]
+    async fn test_truncated_trailing_line_is_recovered() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("audit.jsonl");
+
+        // One complete entry followed by a partial write from a "crash".
+        let complete = serde_json::to_string(
+            &AuditEntry::new(AuditEventType::ConnectionAccepted, "PP-SELF-0000-0000-X"),
+        )
+        .unwrap();
+        let content = format!("{}\n{{\"timestamp\":\"2025-06-15T12:", complete);
+        tokio::fs::write(&path, content).await.unwrap();
+
+        let log = AuditLog::new(path.clone()).await.unwrap();
+        let entry = AuditEntry::new(AuditEventType::ConnectionDenied, "PP-SELF-0000-0000-X");
+        log.log(entry).await.unwrap();
+
+        let content = tokio::fs::read_to_string(&path).await.unwrap();
+        let lines: Vec<&str> = content.trim().split('\n').collect();
+        assert_eq!(lines.len(), 2, "partial line should have been discarded");
+
+        let p1: AuditEntry = serde_json::from_str(lines[0]).unwrap();
+        let p2: AuditEntry = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(p1.event_type, AuditEventType::ConnectionAccepted);
+        assert_eq!(p2.event_type, AuditEventType::ConnectionDenied);
+    }
+
+    #[tokio::test]
+    async fn test_unterminated_complete_line_is_kept() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("audit.jsonl");
+
+        let complete = serde_json::to_string(
+            &AuditEntry::new(AuditEventType::PeerPaired, "PP-SELF-0000-0000-X"),
+        )
+        .unwrap();
+        tokio::fs::write(&path, &complete).await.unwrap();
+
+        let log = AuditLog::new(path.clone()).await.unwrap();
+        log.log(AuditEntry::new(AuditEventType::PeerRemoved, "PP-SELF-0000-0000-X"))
+            .await
+            .unwrap();
+
+        let content = tokio::fs::read_to_string(&path).await.unwrap();
+        let lines: Vec<&str> = content.trim().split('\n').collect();
+        assert_eq!(lines.len(), 2);
+        let p1: AuditEntry = serde_json::from_str(lines[0]).unwrap();
+        let p2: AuditEntry = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(p1.event_type, AuditEventType::PeerPaired);
+        assert_eq!(p2.event_type, AuditEventType::PeerRemoved);
+    }
+
+    #[tokio::test]
+    async fn test_hash_chain_verifies() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("audit.jsonl");
+
+        let log = AuditLog::open(path.clone(), true).await.unwrap();
+        for _ in 0..3 {
+            log.log(AuditEntry::new(AuditEventType::ConnectionAccepted, "PP-SELF-0000-0000-X"))
+                .await
+                .unwrap();
+        }
+        log.verify_chain().await.unwrap();
+
+        let content = tokio::fs::read_to_string(&path).await.unwrap();
+        let first: AuditEntry = serde_json::from_str(content.lines().next().unwrap()).unwrap();
+        assert_eq!(first.prev_hash.as_deref(), Some(GENESIS_HASH));
+    }
+
+    #[tokio::test]
+    async fn test_hash_chain_survives_reopen() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("audit.jsonl");
+
+        {
+            let log = AuditLog::open(path.clone(), true).await.unwrap();
+            log.log(AuditEntry::new(AuditEventType::PeerPaired, "PP-SELF-0000-0000-X"))
+                .await
+                .unwrap();
+        }
+
+        let log = AuditLog::open(path.clone(), true).await.unwrap();
+        log.log(AuditEntry::new(AuditEventType::PeerRemoved, "PP-SELF-0000-0000-X"))
+            .await
+            .unwrap();
+        log.verify_chain().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_hash_chain_shared_between_writers() {
+        // A server and a CLI command each hold the log open and interleave.
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let server = AuditLog::open(path.clone(), true).await.unwrap();
+        let cli = AuditLog::open(path.clone(), true).await.unwrap();
+
+        for log in [&server, &cli, &server, &cli] {
+            log.log(AuditEntry::new(AuditEventType::PeerAdded, "PP-SELF-0000-0000-X"))
+                .await
+                .unwrap();
+        }
+        server.verify_chain().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_hash_chain_resumes_after_oversized_line() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("audit.jsonl");
+
+        {
+            let log = AuditLog::open(path.clone(), true).await.unwrap();
+            log.log(AuditEntry::new(AuditEventType::PeerPaired, "PP-SELF-0000-0000-X"))
+                .await
+                .unwrap();
+            let long_reason = "x".repeat(3 * TAIL_SCAN_BYTES as usize);
+            log.log(
+                AuditEntry::new(AuditEventType::SessionDenied, "PP-SELF-0000-0000-X")
+                    .with_reason(long_reason),
+            )
+            .await
+            .unwrap();
+        }
+
+        let log = AuditLog::open(path.clone(), true).await.unwrap();
+        log.log(AuditEntry::new(AuditEventType::PeerRemoved, "PP-SELF-0000-0000-X"))
+            .await
+            .unwrap();
+        log.verify_chain().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_hash_chain_detects_tampering() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("audit.jsonl");
+
+        let log = AuditLog::open(path.clone(), true).await.unwrap();
+        log.log(
+            AuditEntry::new(AuditEventType::ConnectionDenied, "PP-SELF-0000-0000-X")
+                .with_peer_id("PP-EVIL-PEER-0000-Z"),
+        )
+        .await
+        .unwrap();
+        log.log(AuditEntry::new(AuditEventType::ConnectionAccepted, "PP-SELF-0000-0000-X"))
+            .await
+            .unwrap();
+
+        // Rewrite the first entry's peer ID.
+        let content = tokio::fs::read_to_string(&path).await.unwrap();
+        let tampered = content.replace("PP-EVIL-PEER-0000-Z", "PP-GOOD-PEER-0000-Z");
+        tokio::fs::write(&path, tampered).await.unwrap();
+
+        let err = log.verify_chain().await.unwrap_err();
+        assert!(err.to_string().contains("line 2"), "unexpected error: {}", err);
+    }
+
+    #[tokio::test]
+    async fn test_unchained_log_verifies() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("audit.jsonl");
+
+        let log = AuditLog::new(path.clone()).await.unwrap();
+        log.log(AuditEntry::new(AuditEventType::PairingEnabled, "PP-SELF-0000-0000-X"))
+            .await
+            .unwrap();
+
+        let content = tokio::fs::read_to_string(&path).await.unwrap();
+        assert!(!content.contains("prev_hash"));
+        log.verify_chain().await.unwrap();
+    }
 }
@@ -4,10 +4,11 @@
 //! pairing flow for enrolling new peers.  All mutable state is protected
 //! by async-aware locks so the gate is safe to share across tasks.
 
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
 
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
 use chrono::Utc;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
@@ -17,6 +18,10 @@ use tracing::{debug, info, warn};
 use crate::config::AccessConfig;
 use crate::peer::{AuthorizedPeers, PeerId};
 
+/// File, next to the identity key, through which the `pair` command hands the
+/// active pairing code to the running server (they are separate processes).
+pub const PAIRING_FILE: &str = "pairing.json";
+
 /// Charset for 8-digit pairing codes: uppercase alphanumeric, no ambiguous chars (0/O, 1/I/L).
 const PAIRING_CHARSET: &[u8] = b"ABCDEFGHJKMNPQRSTUVWXYZ23456789";
 
@@ -68,6 +73,8 @@ struct PairingState {
     active_token: Option<PairingToken>,
     /// Parsed expiry for fast comparison.
     expiry: Option<chrono::DateTime<Utc>>,
+    /// Last consumed code, so a stale pairing file can't revive it.
+    consumed: Option<String>,
 }
 
 impl PairingState {
@@ -75,6 +82,7 @@ impl PairingState {
         Self {
             active_token: None,
             expiry: None,
+            consumed: None,
         }
     }
 
@@ -97,7 +105,7 @@ impl PairingState {
 
     /// Consume the token so it cannot be reused.
     fn consume(&mut self) {
-        self.active_token = None;
+        self.consumed = self.active_token.take().map(|t| t.token);
         self.expiry = None;
     }
 }
@@ -115,6 +123,12 @@ pub struct AuthGate {
     peers: Arc<RwLock<AuthorizedPeers>>,
     pairing: Arc<Mutex<PairingState>>,
     pairing_enabled: bool,
+    /// Shared pairing file, see [`PAIRING_FILE`].
+    pairing_file: Option<PathBuf>,
+    /// Peers from `access.authorized_peers`, always allowed.
+    config_peers: Vec<PeerId>,
+    /// Persistent peer store, see [`crate::peer::PEERS_FILE`].
+    peers_file: Option<PathBuf>,
 }
 
 impl AuthGate {
@@ -123,9 +137,14 @@ impl AuthGate {
     /// Pre-authorized peer IDs listed in the config are loaded into the
     /// initial allow-list.
     pub fn new(config: &AccessConfig) -> Self {
+        let config_peers: Vec<PeerId> = config
+            .authorized_peers
+            .iter()
+            .map(|id| PeerId::new(id.clone()))
+            .collect();
         let mut authorized = AuthorizedPeers::new();
-        for id_str in &config.authorized_peers {
-            authorized.add(PeerId::new(id_str.clone()));
+        for peer in &config_peers {
+            authorized.add(peer.clone());
         }
         info!(
             count = authorized.len(),
@@ -136,6 +155,98 @@ impl AuthGate {
             peers: Arc::new(RwLock::new(authorized)),
             pairing: Arc::new(Mutex::new(PairingState::new())),
             pairing_enabled: config.pairing_enabled,
+            pairing_file: None,
+            config_peers,
+            peers_file: None,
+        }
+    }
+
+    /// Keep peers in the store at `path`: its peers are allowed alongside
+    /// the configured ones and peers enrolled by pairing are saved to it.
+    /// Edits made by the `peers` command apply on [`reload_peers`]
+    /// (SIGHUP).
+    ///
+    /// [`reload_peers`]: Self::reload_peers
+    pub fn with_peers_file(mut self, path: PathBuf) -> Self {
+        match AuthorizedPeers::load(&path) {
+            Ok(stored) => {
+                let mut authorized = stored;
+                for peer in &self.config_peers {
+                    authorized.add(peer.clone());
+                }
+                info!(count = authorized.len(), "loaded peer store");
+                self.peers = Arc::new(RwLock::new(authorized));
+            }
+            Err(e) => warn!(error = %e, "peer store unreadable; using configured peers only"),
+        }
+        self.peers_file = Some(path);
+        self
+    }
+
+    /// Re-read the peer store. The allowed set becomes the configured peers
+    /// plus the store's. A missing store leaves the current set untouched
+    /// rather than dropping peers paired since start-up; an unreadable one
+    /// is an error. Returns the number of allowed peers.
+    pub async fn reload_peers(&self) -> Result<usize> {
+        let Some(path) = &self.peers_file else {
+            return Ok(self.peers.read().await.len());
+        };
+        if !path.exists() {
+            let count = self.peers.read().await.len();
+            warn!(path = %path.display(), count, "peer store missing; keeping current peers");
+            return Ok(count);
+        }
+        let mut authorized = AuthorizedPeers::load(path)?;
+        for peer in &self.config_peers {
+            authorized.add(peer.clone());
+        }
+        let count = authorized.len();
+        *self.peers.write().await = authorized;
+        Ok(count)
+    }
+
+    /// Add `peer_id` to the peer store, if there is one.
+    fn persist_peer(&self, peer_id: &PeerId) -> Result<()> {
+        let Some(path) = &self.peers_file else {
+            return Ok(());
+        };
+        let mut stored = AuthorizedPeers::load(path)?;
+        stored.add(peer_id.clone());
+        stored.save(path)
+    }
+
+    /// Share pairing state through the file at `path`: codes enabled here are
+    /// written to it, and codes written by another process (the `pair`
+    /// command) are picked up by this one. The file is removed once the code
+    /// is used.
+    pub fn with_pairing_file(mut self, path: PathBuf) -> Self {
+        self.pairing_file = Some(path);
+        self
+    }
+
+    /// Adopt a code another process published to the pairing file.
+    fn load_pairing_file(&self, state: &mut PairingState) {
+        let Some(path) = &self.pairing_file else {
+            return;
+        };
+        let Ok(raw) = std::fs::read_to_string(path) else {
+            return;
+        };
+        let token: PairingToken = match serde_json::from_str(&raw) {
+            Ok(t) => t,
+            Err(e) => {
+                warn!(path = %path.display(), error = %e, "ignoring unreadable pairing file");
+                return;
+            }
+        };
+        let Ok(expiry) = chrono::DateTime::parse_from_rfc3339(&token.expires_at) else {
+            return;
+        };
+        let current = state.active_token.as_ref().map(|t| t.token.as_str());
+        if current != Some(token.token.as_str()) && state.consumed.as_ref() != Some(&token.token) {
+            info!(expires_at = %token.expires_at, "pairing code loaded from pairing file");
+            state.expiry = Some(expiry.with_timezone(&Utc));
+            state.active_token = Some(token);
         }
     }
 
@@ -151,7 +262,8 @@ impl AuthGate {
         // Not in the allow-list.  If pairing mode is configured and active,
         // signal that the caller should attempt pairing.
         if self.pairing_enabled {
-            let state = self.pairing.lock().await;
+            let mut state = self.pairing.lock().await;
+            self.load_pairing_file(&mut state);
             if state.is_active() {
                 debug!(peer = %peer_id, "peer unknown but pairing mode active");
                 return AuthDecision::PairingRequired;
@@ -184,6 +296,12 @@ impl AuthGate {
         let mut state = self.pairing.lock().await;
         state.active_token = Some(token.clone());
         state.expiry = Some(expiry);
+        state.consumed = None;
+        if let Some(path) = &self.pairing_file {
+            if let Err(e) = write_pairing_file(path, &token) {
+                warn!(error = %e, "failed to publish pairing code");
+            }
+        }
 
         info!(
             expires_at = %expiry.to_rfc3339(),
@@ -199,6 +317,7 @@ impl AuthGate {
     /// authorized set and the token is consumed (one-time use).
     pub async fn try_pair(&self, peer_id: &PeerId, token: &str) -> Result<()> {
         let mut state = self.pairing.lock().await;
+        self.load_pairing_file(&mut state);
 
         if !state.validate_token(token) {
             bail!("invalid or expired pairing token");
@@ -207,10 +326,21 @@ impl AuthGate {
         // Consume the token so it cannot be reused.
         state.consume();
         drop(state);
+        if let Some(path) = &self.pairing_file {
+            if let Err(e) = std::fs::remove_file(path) {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    warn!(path = %path.display(), error = %e, "failed to remove used pairing file");
+                }
+            }
+        }
 
-        // Add the peer to the authorized set.
+        // Add the peer to the authorized set, and keep it across restarts.
         let mut peers = self.peers.write().await;
         peers.add(peer_id.clone());
+        drop(peers);
+        if let Err(e) = self.persist_peer(peer_id) {
+            warn!(peer = %peer_id, error = %e, "paired peer not saved; it will need to re-pair after a restart");
+        }
 
         info!(peer = %peer_id, "peer paired successfully");
         Ok(())
@@ -241,6 +371,24 @@ impl AuthGate {
     }
 }
 
+/// Write `token` to the pairing file with mode 0600 (it is a credential).
+fn write_pairing_file(path: &Path, token: &PairingToken) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create directory {}", parent.display()))?;
+    }
+    std::fs::write(path, serde_json::to_vec(token)?)
+        .with_context(|| format!("failed to write pairing file {}", path.display()))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+            .with_context(|| format!("failed to set permissions on {}", path.display()))?;
+    }
+    Ok(())
+}
+
 // ---------------------------------------------------------------------------
 // Tests
 // ---------------------------------------------------------------------------
@@ -253,6 +401,7 @@ mod tests {
         AccessConfig {
             pairing_enabled: pairing,
             authorized_peers: peers.into_iter().map(String::from).collect(),
+            admin_peers: Vec::new(),
         }
     }
 
@@ -436,4 +585,82 @@ mod tests {
         let result = gate.try_pair(&peer, &token.token).await;
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_pairing_code_shared_through_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(PAIRING_FILE);
+        let config = make_config(vec![], true);
+
+        // The `pair` command and the server are separate gates.
+        let cli = AuthGate::new(&config).with_pairing_file(path.clone());
+        let server = AuthGate::new(&config).with_pairing_file(path.clone());
+
+        let token = cli.enable_pairing(Duration::from_secs(300)).await;
+        assert!(path.exists());
+
+        let peer = PeerId::new("PP-NEWP-EEEE-RRRR-2");
+        assert_eq!(server.check(&peer).await, AuthDecision::PairingRequired);
+        server.try_pair(&peer, &token.token).await.unwrap();
+        assert_eq!(server.check(&peer).await, AuthDecision::Allowed);
+
+        // Used: the file is gone and the code can't be replayed.
+        assert!(!path.exists());
+        let other = PeerId::new("PP-ANOT-HERR-PEER-3");
+        assert!(server.try_pair(&other, &token.token).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_peers_file_edits_applied_on_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(crate::peer::PEERS_FILE);
+        let stored_at_start = PeerId::new("PP-SSSS-TTTT-UUUU-3");
+        let mut stored = AuthorizedPeers::new();
+        stored.add(stored_at_start.clone());
+        stored.save(&path).unwrap();
+
+        let config = make_config(vec!["PP-AAAA-BBBB-CCCC-0"], true);
+        let gate = AuthGate::new(&config).with_peers_file(path.clone());
+        assert_eq!(gate.check(&stored_at_start).await, AuthDecision::Allowed);
+
+        // Added by the `peers` command while the server runs: applied on reload.
+        let added = PeerId::new("PP-XXXX-YYYY-ZZZZ-1");
+        let mut stored = AuthorizedPeers::load(&path).unwrap();
+        stored.add(added.clone());
+        stored.save(&path).unwrap();
+        assert!(matches!(gate.check(&added).await, AuthDecision::Denied(_)));
+        assert_eq!(gate.reload_peers().await.unwrap(), 3);
+        assert_eq!(gate.check(&added).await, AuthDecision::Allowed);
+
+        // Paired peers are saved to the store.
+        let token = gate.enable_pairing(Duration::from_secs(300)).await;
+        let paired = PeerId::new("PP-NEWP-EEEE-RRRR-2");
+        gate.try_pair(&paired, &token.token).await.unwrap();
+        assert!(AuthorizedPeers::load(&path).unwrap().is_authorized(&paired));
+
+        // Removed from the store: denied again, configured peers still allowed.
+        let mut stored = AuthorizedPeers::load(&path).unwrap();
+        stored.remove(&added);
+        stored.save(&path).unwrap();
+        gate.reload_peers().await.unwrap();
+        assert!(matches!(gate.check(&added).await, AuthDecision::Denied(_)));
+        assert_eq!(gate.check(&paired).await, AuthDecision::Allowed);
+        assert_eq!(gate.check(&PeerId::new("PP-AAAA-BBBB-CCCC-0")).await, AuthDecision::Allowed);
+    }
+
+    #[tokio::test]
+    async fn test_missing_peers_file_keeps_paired_peers() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(crate::peer::PEERS_FILE);
+        let config = make_config(vec![], true);
+        let gate = AuthGate::new(&config).with_peers_file(path.clone());
+
+        let token = gate.enable_pairing(Duration::from_secs(300)).await;
+        let paired = PeerId::new("PP-NEWP-EEEE-RRRR-2");
+        gate.try_pair(&paired, &token.token).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(gate.reload_peers().await.unwrap(), 1);
+        assert_eq!(gate.check(&paired).await, AuthDecision::Allowed);
+    }
 }
@@ -10,6 +10,9 @@ use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use tracing::{debug, info, warn};
 
+use crate::identity::EndpointId;
+use crate::tls::ALPN_PP_LINK;
+
 // ---------------------------------------------------------------------------
 // Top-level config
 // ---------------------------------------------------------------------------
@@ -29,6 +32,10 @@ pub struct ReflectorConfig {
     pub iperf3: Iperf3Config,
     #[serde(default)]
     pub logging: LoggingConfig,
+    #[serde(default)]
+    pub selftest: SelfTestConfig,
+    #[serde(default)]
+    pub directory: DirectoryConfig,
 }
 
 impl Default for ReflectorConfig {
@@ -40,6 +47,8 @@ impl Default for ReflectorConfig {
             quotas: QuotaConfig::default(),
             iperf3: Iperf3Config::default(),
             logging: LoggingConfig::default(),
+            selftest: SelfTestConfig::default(),
+            directory: DirectoryConfig::default(),
         }
     }
 }
@@ -49,8 +58,12 @@ impl ReflectorConfig {
     pub fn load(path: &Path) -> Result<Self> {
         let content = std::fs::read_to_string(path)
             .with_context(|| format!("failed to read config file: {}", path.display()))?;
-        let config: Self = toml::from_str(&content)
+        let mut config: Self = toml::from_str(&content)
             .with_context(|| format!("failed to parse config file: {}", path.display()))?;
+        config
+            .access
+            .normalize_peer_ids()
+            .with_context(|| format!("invalid config file: {}", path.display()))?;
         info!(path = %path.display(), "loaded reflector configuration");
         Ok(config)
     }
@@ -124,8 +137,11 @@ impl Default for IdentityConfig {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct NetworkConfig {
-    /// Address and port for the QUIC / TLS control plane listener.
+    /// Address and port for the QUIC / TLS control plane listener. TCP and
+    /// QUIC (UDP) share the same port number.
     pub listen_address: String,
+    /// Control-plane transport(s) to listen on.
+    pub transport: ControlTransport,
     /// ALPN protocol identifier negotiated during the TLS handshake.
     pub alpn: String,
     /// Data-plane transport mode.
@@ -139,24 +155,53 @@ pub struct NetworkConfig {
     pub deployment_mode: String,
     /// Address and port for the HTTP health check listener.
     pub listen_address_health: String,
+    /// Shut down after this many seconds with no connections and no active
+    /// sessions. `0` (default) runs until stopped.
+    pub idle_shutdown_sec: u64,
 }
 
 impl Default for NetworkConfig {
     fn default() -> Self {
         Self {
             listen_address: "0.0.0.0:4000".to_string(),
-            alpn: "pp-link/1".to_string(),
+            transport: ControlTransport::Tcp,
+            alpn: ALPN_PP_LINK.to_string(),
             mode: DataPlaneMode::Tunneled,
             data_port_range_start: 5201,
             data_port_range_end: 5299,
             deployment_mode: "auto".to_string(),
             listen_address_health: "0.0.0.0:7301".to_string(),
+            idle_shutdown_sec: 0,
         }
     }
 }
 
+/// Transport carrying the control plane. Either way the peer speaks the
+/// same length-prefixed `LinkMessage` framing; over QUIC it uses the first
+/// bidirectional stream of the connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ControlTransport {
+    /// mTLS over TCP (default).
+    Tcp,
+    /// mTLS over QUIC; copes better with lossy or high-latency paths.
+    Quic,
+    /// Listen on both.
+    Both,
+}
+
+impl ControlTransport {
+    pub fn tcp(self) -> bool {
+        matches!(self, Self::Tcp | Self::Both)
+    }
+
+    pub fn quic(self) -> bool {
+        matches!(self, Self::Quic | Self::Both)
+    }
+}
+
 /// How the data plane (iperf3 / UDP echo) traffic is transported.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum DataPlaneMode {
     /// All data flows inside the mTLS tunnel (default, firewall-friendly).
@@ -166,6 +211,16 @@ pub enum DataPlaneMode {
     DirectEphemeral,
 }
 
+impl DataPlaneMode {
+    /// Wire name, as sent in `SessionGrant.mode`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DataPlaneMode::Tunneled => "tunneled",
+            DataPlaneMode::DirectEphemeral => "direct_ephemeral",
+        }
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Access
 // ---------------------------------------------------------------------------
@@ -178,6 +233,26 @@ pub struct AccessConfig {
     pub pairing_enabled: bool,
     /// List of pre-authorized peer endpoint IDs (e.g. `PP-XXXX-...`).
     pub authorized_peers: Vec<String>,
+    /// Peer endpoint IDs allowed to call admin RPCs (e.g. `get_usage_summary`).
+    pub admin_peers: Vec<String>,
+}
+
+impl AccessConfig {
+    /// Validate the configured endpoint IDs and rewrite them in canonical
+    /// form, so a typo fails at startup instead of never matching a peer.
+    pub fn normalize_peer_ids(&mut self) -> Result<()> {
+        for (key, ids) in [
+            ("authorized_peers", &mut self.authorized_peers),
+            ("admin_peers", &mut self.admin_peers),
+        ] {
+            for (i, id) in ids.iter_mut().enumerate() {
+                let parsed = EndpointId::parse(id)
+                    .with_context(|| format!("access.{key}[{i}] {id:?} is not a valid endpoint ID"))?;
+                *id = parsed.to_string();
+            }
+        }
+        Ok(())
+    }
 }
 
 impl Default for AccessConfig {
@@ -185,6 +260,7 @@ impl Default for AccessConfig {
         Self {
             pairing_enabled: false,
             authorized_peers: Vec::new(),
+            admin_peers: Vec::new(),
         }
     }
 }
@@ -199,6 +275,9 @@ impl Default for AccessConfig {
 pub struct QuotaConfig {
     /// Maximum duration for a single test session (seconds).
     pub max_test_duration_sec: u64,
+    /// Shortest test a peer may request (seconds); shorter requests are
+    /// denied as `invalid_params`.
+    pub min_test_duration_sec: u64,
     /// Maximum number of tests running concurrently on this reflector.
     pub max_concurrent_tests: u32,
     /// Per-peer rate limit: tests allowed per rolling hour.
@@ -217,6 +296,7 @@ impl Default for QuotaConfig {
     fn default() -> Self {
         Self {
             max_test_duration_sec: 60,
+            min_test_duration_sec: 1,
             max_concurrent_tests: 1,
             max_tests_per_hour_per_peer: 10,
             max_bytes_per_day_per_peer: 5_000_000_000,
@@ -265,6 +345,11 @@ pub struct LoggingConfig {
     pub level: String,
     /// Path to the append-only JSON-lines audit log.
     pub audit_log_path: PathBuf,
+    /// Link audit entries into a SHA-256 hash chain for tamper evidence.
+    /// Costs one hash per write; off by default.
+    pub audit_hash_chain: bool,
+    /// Write a per-peer usage summary entry to the audit log at UTC rollover.
+    pub audit_daily_summary: bool,
 }
 
 impl Default for LoggingConfig {
@@ -272,6 +357,55 @@ impl Default for LoggingConfig {
         Self {
             level: "info".to_string(),
             audit_log_path: PathBuf::from("/var/lib/reflector/audit.jsonl"),
+            audit_hash_chain: false,
+            audit_daily_summary: false,
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Self-test
+// ---------------------------------------------------------------------------
+
+/// Host readiness self-test configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SelfTestConfig {
+    /// Sustained throughput (Mbps) this reflector is expected to serve.
+    /// Memory, loopback, NIC, and READY-verdict thresholds scale from it.
+    pub target_mbps: u32,
+}
+
+impl Default for SelfTestConfig {
+    fn default() -> Self {
+        Self { target_mbps: 1000 }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Directory
+// ---------------------------------------------------------------------------
+
+/// Optional self-registration with a reflector directory.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DirectoryConfig {
+    /// Directory endpoint to POST signed heartbeats to. Empty disables
+    /// publishing.
+    pub url: String,
+    /// Seconds between heartbeats.
+    pub heartbeat_interval_sec: u64,
+    /// Address (`host:port`) to publish. Empty publishes `network.listen_address`,
+    /// with a wildcard host replaced by the egress interface's address.
+    pub advertise_address: String,
+}
+
+impl Default for DirectoryConfig {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            heartbeat_interval_sec: 300,
+            advertise_address: String::new(),
         }
     }
 }
@@ -296,7 +430,9 @@ mod tests {
 
         // Network
         assert_eq!(cfg.network.listen_address, "0.0.0.0:4000");
+        assert_eq!(cfg.network.transport, ControlTransport::Tcp);
         assert_eq!(cfg.network.alpn, "pp-link/1");
+        assert_eq!(cfg.network.idle_shutdown_sec, 0);
         assert!(matches!(cfg.network.mode, DataPlaneMode::Tunneled));
         assert_eq!(cfg.network.data_port_range_start, 5201);
         assert_eq!(cfg.network.data_port_range_end, 5299);
@@ -305,9 +441,11 @@ mod tests {
         // Access
         assert!(!cfg.access.pairing_enabled);
         assert!(cfg.access.authorized_peers.is_empty());
+        assert!(cfg.access.admin_peers.is_empty());
 
         // Quotas
         assert_eq!(cfg.quotas.max_test_duration_sec, 60);
+        assert_eq!(cfg.quotas.min_test_duration_sec, 1);
         assert_eq!(cfg.quotas.max_concurrent_tests, 1);
         assert_eq!(cfg.quotas.max_tests_per_hour_per_peer, 10);
         assert_eq!(cfg.quotas.max_bytes_per_day_per_peer, 5_000_000_000);
@@ -326,6 +464,11 @@ mod tests {
             cfg.logging.audit_log_path,
             PathBuf::from("/var/lib/reflector/audit.jsonl")
         );
+        assert!(!cfg.logging.audit_hash_chain);
+        assert!(!cfg.logging.audit_daily_summary);
+
+        // Self-test
+        assert_eq!(cfg.selftest.target_mbps, 1000);
     }
 
     #[test]
@@ -336,6 +479,7 @@ private_key_path = "/opt/reflector/my.key"
 
 [network]
 listen_address = "127.0.0.1:5000"
+transport = "both"
 alpn = "pp-link/1"
 mode = "direct_ephemeral"
 data_port_range_start = 6000
@@ -344,6 +488,7 @@ data_port_range_end = 6100
 [access]
 pairing_enabled = true
 authorized_peers = ["PP-AAAA-BBBB-CCCC-0", "PP-DDDD-EEEE-FFFF-1"]
+admin_peers = ["PP-AAAA-BBBB-CCCC-0"]
 
 [quotas]
 max_test_duration_sec = 120
@@ -362,18 +507,31 @@ max_streams = 16
 [logging]
 level = "debug"
 audit_log_path = "/var/log/reflector/audit.jsonl"
+audit_hash_chain = true
+audit_daily_summary = true
+
+[selftest]
+target_mbps = 10000
+
+[directory]
+url = "https://directory.example.net/v1/heartbeat"
+heartbeat_interval_sec = 120
+advertise_address = "reflector.example.net:4000"
 "#;
 
         let cfg: ReflectorConfig = toml::from_str(toml_str).unwrap();
 
         assert_eq!(cfg.identity.private_key_path, PathBuf::from("/opt/reflector/my.key"));
         assert_eq!(cfg.network.listen_address, "127.0.0.1:5000");
+        assert_eq!(cfg.network.transport, ControlTransport::Both);
+        assert!(cfg.network.transport.tcp() && cfg.network.transport.quic());
         assert!(matches!(cfg.network.mode, DataPlaneMode::DirectEphemeral));
         assert_eq!(cfg.network.data_port_range_start, 6000);
         assert_eq!(cfg.network.data_port_range_end, 6100);
         assert!(cfg.access.pairing_enabled);
         assert_eq!(cfg.access.authorized_peers.len(), 2);
         assert_eq!(cfg.access.authorized_peers[0], "PP-AAAA-BBBB-CCCC-0");
+        assert_eq!(cfg.access.admin_peers, vec!["PP-AAAA-BBBB-CCCC-0".to_string()]);
         assert_eq!(cfg.quotas.max_test_duration_sec, 120);
         assert_eq!(cfg.quotas.max_concurrent_tests, 4);
         assert_eq!(cfg.quotas.max_tests_per_hour_per_peer, 20);
@@ -389,6 +547,12 @@ audit_log_path = "/var/log/reflector/audit.jsonl"
             cfg.logging.audit_log_path,
             PathBuf::from("/var/log/reflector/audit.jsonl")
         );
+        assert!(cfg.logging.audit_hash_chain);
+        assert!(cfg.logging.audit_daily_summary);
+        assert_eq!(cfg.selftest.target_mbps, 10_000);
+        assert_eq!(cfg.directory.url, "https://directory.example.net/v1/heartbeat");
+        assert_eq!(cfg.directory.heartbeat_interval_sec, 120);
+        assert_eq!(cfg.directory.advertise_address, "reflector.example.net:4000");
     }
 
     #[test]
@@ -447,6 +611,24 @@ listen_address = "0.0.0.0:9999"
         assert_eq!(cfg.network.listen_address, "0.0.0.0:9999");
     }
 
+    #[test]
+    fn test_load_rejects_mistyped_peer_id() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("reflector.toml");
+        let id = crate::identity::Identity::generate().endpoint_id().to_string();
+
+        // Lowercase is fine and comes back canonical.
+        std::fs::write(&path, format!("[access]\nauthorized_peers = [{:?}]\n", id.to_lowercase())).unwrap();
+        let cfg = ReflectorConfig::load(&path).unwrap();
+        assert_eq!(cfg.access.authorized_peers, vec![id.clone()]);
+
+        // A truncated ID fails the load and names the entry.
+        let truncated = &id[..id.len() - 2];
+        std::fs::write(&path, format!("[access]\nadmin_peers = [{:?}]\n", truncated)).unwrap();
+        let err = format!("{:#}", ReflectorConfig::load(&path).unwrap_err());
+        assert!(err.contains("access.admin_peers[0]"), "{err}");
+    }
+
     #[test]
     fn test_load_missing_file_errors() {
         let result = ReflectorConfig::load(Path::new("/nonexistent/path/reflector.toml"));
@@ -0,0 +1,288 @@
+//! Self-registration heartbeat to an optional reflector directory.
+//!
+//! When `directory.url` is set, the reflector periodically POSTs its current
+//! address and network position to the directory so Paramedics can look it
+//! up by endpoint ID.  Each heartbeat is signed with the reflector's Ed25519
+//! identity key; since the endpoint ID *is* the public key (Crockford Base32
+//! with a check digit), the directory can verify authenticity without a CA by
+//! checking that `public_key` matches `endpoint_id` and the signature matches
+//! `payload`.
+//!
+//! Wire format (JSON body):
+//!
+//! ```text
+//! {
+//!   "payload":    "<heartbeat JSON, exactly the bytes that were signed>",
+//!   "public_key": "<32-byte Ed25519 public key, hex>",
+//!   "signature":  "<64-byte Ed25519 signature over payload, hex>"
+//! }
+//! ```
+//!
+//! The payload travels as a string so the directory verifies the exact bytes
+//! that were signed, with no JSON canonicalization on either side.  It carries
+//! a timestamp so stale heartbeats can be rejected.
+//!
+//! Failures to reach the directory are logged and retried with backoff; they
+//! never affect the server.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use ed25519_dalek::{Signer, SigningKey};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::config::DirectoryConfig;
+use crate::network::PositionReport;
+
+/// Public addresses used only to ask the kernel which local address routes
+/// out (a UDP `connect` sends nothing).
+const EGRESS_PROBE_V4: IpAddr = IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1));
+const EGRESS_PROBE_V6: IpAddr = IpAddr::V6(Ipv6Addr::new(0x2606, 0x4700, 0x4700, 0, 0, 0, 0, 0x1111));
+
+/// Timeout for a single heartbeat POST.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// First retry delay after a failed heartbeat; doubles up to the interval.
+const RETRY_BASE: Duration = Duration::from_secs(15);
+
+/// What the reflector publishes about itself.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Heartbeat {
+    pub endpoint_id: String,
+    /// Address Paramedics should connect to (`host:port`).
+    pub address: String,
+    /// `"wan"`, `"lan"`, `"hybrid"`, or `"unknown"`.
+    pub network_position: String,
+    /// `"ipv4"`, `"ipv6"`, or `"dual_stack"` when known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reachable_via: Option<String>,
+    /// RFC 3339 time the heartbeat was produced.
+    pub timestamp: String,
+}
+
+/// A heartbeat as POSTed to the directory.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedHeartbeat {
+    pub payload: String,
+    pub public_key: String,
+    pub signature: String,
+}
+
+impl Heartbeat {
+    /// Build a heartbeat for `address` stamped with the current time.
+    pub fn new(endpoint_id: &str, address: &str, position: Option<PositionReport>) -> Self {
+        Self {
+            endpoint_id: endpoint_id.to_string(),
+            address: address.to_string(),
+            network_position: position
+                .map(|p| p.position.as_str())
+                .unwrap_or("unknown")
+                .to_string(),
+            reachable_via: position
+                .and_then(|p| p.reachable_via)
+                .map(|f| f.as_str().to_string()),
+            timestamp: Utc::now().to_rfc3339(),
+        }
+    }
+
+    /// Serialize and sign with the reflector's identity key.
+    pub fn sign(&self, key: &SigningKey) -> Result<SignedHeartbeat> {
+        let payload = serde_json::to_string(self).context("failed to serialize heartbeat")?;
+        let signature = key.sign(payload.as_bytes());
+        Ok(SignedHeartbeat {
+            payload,
+            public_key: to_hex(key.verifying_key().as_bytes()),
+            signature: to_hex(&signature.to_bytes()),
+        })
+    }
+}
+
+/// Publish heartbeats to the configured directory until the process exits.
+///
+/// Returns immediately if no directory URL is configured.
+pub async fn run_heartbeat(
+    config: DirectoryConfig,
+    key: SigningKey,
+    endpoint_id: String,
+    address: String,
+    position: Option<PositionReport>,
+) {
+    if config.url.is_empty() {
+        return;
+    }
+    let url = config.url;
+    let client = match reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build() {
+        Ok(c) => c,
+        Err(e) => {
+            warn!(error = %e, "failed to build directory HTTP client, heartbeat disabled");
+            return;
+        }
+    };
+    let interval = Duration::from_secs(config.heartbeat_interval_sec.max(1));
+    info!(url = %url, address = %address, interval_sec = interval.as_secs(), "publishing heartbeats to directory");
+
+    let mut failures = 0u32;
+    loop {
+        let heartbeat = Heartbeat::new(&endpoint_id, &address, position);
+        let delay = match post(&client, &url, &heartbeat, &key).await {
+            Ok(()) => {
+                if failures > 0 {
+                    info!(url = %url, "directory reachable again");
+                }
+                debug!(url = %url, "heartbeat published");
+                failures = 0;
+                interval
+            }
+            Err(e) => {
+                failures = failures.saturating_add(1);
+                let delay = retry_delay(failures, interval);
+                warn!(
+                    url = %url,
+                    failures = failures,
+                    retry_in_sec = delay.as_secs(),
+                    "directory heartbeat failed: {:#}", e
+                );
+                delay
+            }
+        };
+        tokio::time::sleep(delay).await;
+    }
+}
+
+async fn post(
+    client: &reqwest::Client,
+    url: &str,
+    heartbeat: &Heartbeat,
+    key: &SigningKey,
+) -> Result<()> {
+    let body = heartbeat.sign(key)?;
+    client
+        .post(url)
+        .json(&body)
+        .send()
+        .await
+        .context("request failed")?
+        .error_for_status()
+        .context("directory rejected heartbeat")?;
+    Ok(())
+}
+
+/// The `host:port` to publish: `directory.advertise_address` when set,
+/// otherwise `listen`. A wildcard listen address (`0.0.0.0`, `::`) is not
+/// dialable, so its host is replaced by the address of the interface that
+/// routes to the internet. Errors when that can't be determined.
+pub fn advertise_address(directory: &DirectoryConfig, listen: &str) -> Result<String> {
+    if !directory.advertise_address.is_empty() {
+        return Ok(directory.advertise_address.clone());
+    }
+    let listen: SocketAddr = listen
+        .parse()
+        .with_context(|| format!("cannot publish listen address {}", listen))?;
+    if !listen.ip().is_unspecified() {
+        return Ok(listen.to_string());
+    }
+    let ip = egress_ip(listen.ip())
+        .context("listening on all interfaces and no egress address found; set directory.advertise_address")?;
+    Ok(SocketAddr::new(ip, listen.port()).to_string())
+}
+
+/// Local address of the interface that routes to the internet, in the
+/// family of `family`.
+fn egress_ip(family: IpAddr) -> Result<IpAddr> {
+    let (bind, probe): (IpAddr, IpAddr) = match family {
+        IpAddr::V4(_) => (Ipv4Addr::UNSPECIFIED.into(), EGRESS_PROBE_V4),
+        IpAddr::V6(_) => (Ipv6Addr::UNSPECIFIED.into(), EGRESS_PROBE_V6),
+    };
+    let socket = UdpSocket::bind((bind, 0))?;
+    socket.connect((probe, 53))?;
+    Ok(socket.local_addr()?.ip())
+}
+
+/// Backoff after `failures` consecutive failures, capped at the interval.
+fn retry_delay(failures: u32, interval: Duration) -> Duration {
+    RETRY_BASE
+        .saturating_mul(1 << failures.saturating_sub(1).min(16))
+        .min(interval)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::Identity;
+    use crate::network::{NetworkPosition, ReachableFamily};
+    use ed25519_dalek::{Signature, Verifier};
+
+    fn from_hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_advertise_address() {
+        let mut directory = DirectoryConfig::default();
+        assert_eq!(advertise_address(&directory, "192.0.2.7:4000").unwrap(), "192.0.2.7:4000");
+
+        // A wildcard listen address is never published as-is.
+        if let Ok(derived) = advertise_address(&directory, "0.0.0.0:4000") {
+            let addr: SocketAddr = derived.parse().unwrap();
+            assert!(!addr.ip().is_unspecified());
+            assert_eq!(addr.port(), 4000);
+        }
+
+        directory.advertise_address = "reflector.example.net:4000".into();
+        assert_eq!(
+            advertise_address(&directory, "0.0.0.0:4000").unwrap(),
+            "reflector.example.net:4000"
+        );
+    }
+
+    #[test]
+    fn test_signed_heartbeat_verifies() {
+        let identity = Identity::generate();
+        let endpoint_id = identity.endpoint_id().to_string();
+        let position = PositionReport {
+            position: NetworkPosition::WanFacing,
+            reachable_via: Some(ReachableFamily::Ipv6),
+        };
+        let hb = Heartbeat::new(&endpoint_id, "[2001:db8::5]:4000", Some(position));
+        let signed = hb.sign(identity.signing_key()).unwrap();
+
+        let decoded: Heartbeat = serde_json::from_str(&signed.payload).unwrap();
+        assert_eq!(decoded, hb);
+        assert_eq!(decoded.network_position, "wan");
+        assert_eq!(decoded.reachable_via.as_deref(), Some("ipv6"));
+
+        let pk: [u8; 32] = from_hex(&signed.public_key).try_into().unwrap();
+        assert_eq!(
+            crate::identity::EndpointId::from_public_key_bytes(&pk).to_string(),
+            endpoint_id
+        );
+        let key = ed25519_dalek::VerifyingKey::from_bytes(&pk).unwrap();
+        let sig: [u8; 64] = from_hex(&signed.signature).try_into().unwrap();
+        let sig = Signature::from_bytes(&sig);
+        assert!(key.verify(signed.payload.as_bytes(), &sig).is_ok());
+
+        // Any change to the payload breaks the signature.
+        let tampered = signed.payload.replace("4000", "4001");
+        assert!(key.verify(tampered.as_bytes(), &sig).is_err());
+    }
+
+    #[test]
+    fn test_retry_delay_backs_off_to_interval() {
+        let interval = Duration::from_secs(300);
+        assert_eq!(retry_delay(1, interval), Duration::from_secs(15));
+        assert_eq!(retry_delay(2, interval), Duration::from_secs(30));
+        assert_eq!(retry_delay(3, interval), Duration::from_secs(60));
+        assert_eq!(retry_delay(10, interval), interval);
+        assert_eq!(retry_delay(u32::MAX, interval), interval);
+    }
+}
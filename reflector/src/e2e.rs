@@ -0,0 +1,437 @@
+//! End-to-end tests against an in-process reflector.
+//!
+//! Each test starts a real [`ReflectorServer`] on loopback with its state in
+//! a temp directory, then drives it as a Paramedic would: mTLS with a fresh
+//! client identity, `Hello`, pairing, a session request, the data plane, and
+//! `SessionClose`. The audit log is checked afterwards. This covers the
+//! wiring between TLS, auth, sessions, engines and auditing that the
+//! per-module tests stub out. The Paramedic's own client is covered against
+//! the built binary by the root crate's `tests/reflector_client_e2e.rs`.
+
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use rustls_pki_types::ServerName;
+use tempfile::TempDir;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::task::JoinHandle;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::TlsConnector;
+
+use crate::audit::{AuditEntry, AuditEventType};
+use crate::auth::{PairingToken, PAIRING_FILE};
+use crate::cert::generate_self_signed_cert;
+use crate::config::{DataPlaneMode, ReflectorConfig};
+use crate::identity::Identity;
+use crate::peer::PeerId;
+use crate::rpc::*;
+use crate::server::ReflectorServer;
+use crate::tls::{build_client_config, ALPN_PP_LINK};
+
+/// Pairing code published to the reflector before the client connects.
+const PAIRING_CODE: &str = "E2EPAIR1";
+
+/// How long to wait for the server to start accepting connections.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+// ---------------------------------------------------------------------------
+// Harness
+// ---------------------------------------------------------------------------
+
+/// A reflector running in-process, with pairing open.
+struct TestReflector {
+    addr: SocketAddr,
+    dir: TempDir,
+    task: JoinHandle<Result<()>>,
+}
+
+impl TestReflector {
+    /// Start a reflector with the given data-plane mode. Its control port
+    /// and its single data port are picked by the OS, so tests can run in
+    /// parallel.
+    async fn start(mode: DataPlaneMode) -> Result<Self> {
+        let dir = tempfile::tempdir()?;
+        let port = free_port().await?;
+        let data_port = free_port().await?;
+
+        let mut config = ReflectorConfig::default();
+        config.identity.private_key_path = dir.path().join("identity.key");
+        config.network.listen_address = format!("127.0.0.1:{}", port);
+        config.network.mode = mode;
+        config.network.data_port_range_start = data_port;
+        config.network.data_port_range_end = data_port;
+        config.access.pairing_enabled = true;
+        config.logging.audit_log_path = dir.path().join("audit.jsonl");
+
+        // Published the way the `pair` command does it.
+        let token = PairingToken {
+            token: PAIRING_CODE.to_string(),
+            expires_at: (chrono::Utc::now() + chrono::Duration::minutes(5)).to_rfc3339(),
+        };
+        std::fs::write(dir.path().join(PAIRING_FILE), serde_json::to_vec(&token)?)?;
+
+        let server = ReflectorServer::new(config).await?;
+        let task = tokio::spawn(async move { server.run().await });
+        Ok(Self {
+            addr: SocketAddr::from((Ipv4Addr::LOCALHOST, port)),
+            dir,
+            task,
+        })
+    }
+
+    /// Audit entries about `peer_id`, in log order.
+    fn audit_for(&self, peer_id: &str) -> Vec<AuditEntry> {
+        let raw = std::fs::read_to_string(self.dir.path().join("audit.jsonl")).unwrap();
+        raw.lines()
+            .map(|line| serde_json::from_str::<AuditEntry>(line).unwrap())
+            .filter(|e| e.peer_id.as_deref() == Some(peer_id))
+            .collect()
+    }
+}
+
+impl Drop for TestReflector {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// A Paramedic-side control connection with its own ephemeral identity.
+struct TestClient {
+    stream: TlsStream<TcpStream>,
+    peer_id: String,
+    next_request: u32,
+}
+
+impl TestClient {
+    /// Connect over mTLS, retrying until the server is listening.
+    async fn connect(addr: SocketAddr) -> Result<Self> {
+        let identity = Identity::generate();
+        let (cert_der, key_der) = generate_self_signed_cert(&identity)?;
+        let peer_id = PeerId::from_cert(&cert_der)?.to_string();
+        let tls = TlsConnector::from(Arc::new(build_client_config(
+            cert_der,
+            key_der,
+            ALPN_PP_LINK,
+        )?));
+
+        let started = tokio::time::Instant::now();
+        let tcp = loop {
+            match TcpStream::connect(addr).await {
+                Ok(s) => break s,
+                Err(e) if started.elapsed() > STARTUP_TIMEOUT => {
+                    return Err(e).context("reflector never started listening")
+                }
+                Err(_) => tokio::time::sleep(Duration::from_millis(50)).await,
+            }
+        };
+        let stream = tls
+            .connect(ServerName::try_from("reflector".to_string())?, tcp)
+            .await
+            .context("TLS handshake with reflector failed")?;
+
+        Ok(Self {
+            stream,
+            peer_id,
+            next_request: 0,
+        })
+    }
+
+    /// Send one request and wait for its response.
+    async fn call(&mut self, payload: MessagePayload) -> Result<MessagePayload> {
+        self.next_request += 1;
+        let request_id = format!("e2e-{}", self.next_request);
+        let json = serde_json::to_vec(&LinkMessage {
+            request_id: request_id.clone(),
+            payload,
+        })?;
+        self.stream
+            .write_all(&(json.len() as u32).to_be_bytes())
+            .await?;
+        self.stream.write_all(&json).await?;
+        self.stream.flush().await?;
+
+        let mut len = [0u8; 4];
+        self.stream.read_exact(&mut len).await?;
+        let mut frame = vec![0u8; u32::from_be_bytes(len) as usize];
+        self.stream.read_exact(&mut frame).await?;
+        let response: LinkMessage = serde_json::from_slice(&frame)?;
+        if response.request_id != request_id {
+            bail!("response {} to request {}", response.request_id, request_id);
+        }
+        Ok(response.payload)
+    }
+
+    /// Negotiate the newest protocol version and pair with the published code.
+    async fn hello_and_pair(&mut self) -> Result<()> {
+        let hello = Hello {
+            version: "1.2".into(),
+            versions: vec!["1.0".into(), "1.1".into(), "1.2".into()],
+            features: vec!["throughput".into(), "udp_echo".into()],
+        };
+        match self.call(MessagePayload::Hello(hello)).await? {
+            MessagePayload::ServerHello(sh) => assert_eq!(sh.version, "1.2"),
+            other => bail!("expected ServerHello, got {:?}", other),
+        }
+        match self
+            .call(MessagePayload::PairRequest(PairRequest {
+                token: PAIRING_CODE.into(),
+            }))
+            .await?
+        {
+            MessagePayload::PairResponse(pr) if pr.success => Ok(()),
+            other => bail!("pairing failed: {:?}", other),
+        }
+    }
+
+    async fn request(&mut self, test_type: TestType, params: TestParams) -> Result<SessionGrant> {
+        match self
+            .call(MessagePayload::SessionRequest(SessionRequest {
+                test_type,
+                params,
+            }))
+            .await?
+        {
+            MessagePayload::SessionGrant(grant) => Ok(grant),
+            other => bail!("expected SessionGrant, got {:?}", other),
+        }
+    }
+
+    async fn close(&mut self, test_id: &str) -> Result<SessionSummary> {
+        match self
+            .call(MessagePayload::SessionClose(SessionClose {
+                test_id: test_id.to_string(),
+                want_summary: true,
+            }))
+            .await?
+        {
+            MessagePayload::SessionSummary(summary) => Ok(summary),
+            other => bail!("expected SessionSummary, got {:?}", other),
+        }
+    }
+}
+
+/// Local port that forwards each connection to the gate on `gate_port`,
+/// writing the session token first, as the Paramedic's relay does.
+async fn token_relay(gate_port: u16, token: String) -> Result<u16> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+    let port = listener.local_addr()?.port();
+    tokio::spawn(async move {
+        while let Ok((mut local, _)) = listener.accept().await {
+            let token = token.clone();
+            tokio::spawn(async move {
+                let mut gate = TcpStream::connect((Ipv4Addr::LOCALHOST, gate_port)).await?;
+                gate.write_all(token.as_bytes()).await?;
+                tokio::io::copy_bidirectional(&mut local, &mut gate).await?;
+                Ok::<_, std::io::Error>(())
+            });
+        }
+    });
+    Ok(port)
+}
+
+/// A loopback port that was free a moment ago.
+async fn free_port() -> Result<u16> {
+    Ok(TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+        .await?
+        .local_addr()?
+        .port())
+}
+
+/// Whether `entries` holds `event`, optionally for `test_id`: set as the
+/// field, or as the `test_id=` reason some entries carry instead.
+fn has_event(entries: &[AuditEntry], event: AuditEventType, test_id: Option<&str>) -> bool {
+    entries.iter().any(|e| {
+        e.event_type == event
+            && test_id.is_none_or(|id| {
+                e.test_id.as_deref() == Some(id)
+                    || e.reason.as_deref() == Some(format!("test_id={}", id).as_str())
+            })
+    })
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+#[ignore = "needs iperf3 installed"]
+async fn test_e2e_throughput_session() {
+    let reflector = TestReflector::start(DataPlaneMode::DirectEphemeral)
+        .await
+        .unwrap();
+    let mut client = TestClient::connect(reflector.addr).await.unwrap();
+    client.hello_and_pair().await.unwrap();
+
+    let grant = client
+        .request(
+            TestType::Throughput,
+            TestParams {
+                duration_sec: 2,
+                protocol: Some("tcp".into()),
+                streams: Some(2),
+                reverse: Some(false),
+                mtu_probe: None,
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(grant.mode, "direct_ephemeral");
+    assert!(grant.token_preamble);
+    assert_eq!(grant.streams, Some(2));
+
+    // Give iperf3 a moment to bind behind the gate.
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    let relay = token_relay(grant.port, grant.token.clone()).await.unwrap();
+    let out = tokio::process::Command::new("iperf3")
+        .args([
+            "-c",
+            "127.0.0.1",
+            "-p",
+            &relay.to_string(),
+            "-t",
+            "2",
+            "-P",
+            "2",
+            "-J",
+        ])
+        .output()
+        .await
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "iperf3 client failed: {}",
+        String::from_utf8_lossy(&out.stdout)
+    );
+    let report: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    let client_mbps = report["end"]["sum_received"]["bits_per_second"]
+        .as_f64()
+        .unwrap()
+        / 1_000_000.0;
+    assert!(
+        client_mbps > 1.0,
+        "implausible loopback rate: {} Mbps",
+        client_mbps
+    );
+
+    let summary = client.close(&grant.test_id).await.unwrap();
+    assert_eq!(summary.test_id, grant.test_id);
+    assert_eq!(summary.outcome, SessionOutcome::Completed);
+    assert!(summary.bytes_transferred > 0);
+    assert_eq!(summary.streams_mbps.len(), 2);
+    // Nothing is lost on loopback, so both ends should roughly agree.
+    let ratio = summary.throughput_mbps() / client_mbps;
+    assert!(
+        (0.5..2.0).contains(&ratio),
+        "reflector/client ratio {}",
+        ratio
+    );
+
+    let audit = reflector.audit_for(&client.peer_id);
+    assert!(audit
+        .iter()
+        .any(|e| e.event_type == AuditEventType::ConnectionAccepted
+            && e.reason.as_deref() == Some("pairing completed")));
+    assert!(has_event(
+        &audit,
+        AuditEventType::SessionGranted,
+        Some(&grant.test_id)
+    ));
+    let completed = audit
+        .iter()
+        .find(|e| {
+            e.event_type == AuditEventType::SessionCompleted
+                && e.test_id.as_deref() == Some(grant.test_id.as_str())
+        })
+        .expect("no session_completed entry for the test");
+    assert_eq!(completed.bytes_transferred, Some(summary.bytes_transferred));
+}
+
+#[tokio::test]
+async fn test_e2e_udp_echo_session() {
+    let reflector = TestReflector::start(DataPlaneMode::Tunneled)
+        .await
+        .unwrap();
+    let mut client = TestClient::connect(reflector.addr).await.unwrap();
+    client.hello_and_pair().await.unwrap();
+
+    let grant = client
+        .request(
+            TestType::UdpEcho,
+            TestParams {
+                duration_sec: 2,
+                protocol: Some("udp".into()),
+                streams: None,
+                reverse: None,
+                mtu_probe: None,
+            },
+        )
+        .await
+        .unwrap();
+
+    let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    socket
+        .connect((Ipv4Addr::LOCALHOST, grant.port))
+        .await
+        .unwrap();
+    let mut buf = [0u8; 64];
+    for seq in 0u8..10 {
+        let probe = [seq; 32];
+        socket.send(&probe).await.unwrap();
+        let len = tokio::time::timeout(Duration::from_secs(1), socket.recv(&mut buf))
+            .await
+            .expect("no echo within 1s")
+            .unwrap();
+        assert_eq!(&buf[..len], &probe);
+    }
+
+    let summary = client.close(&grant.test_id).await.unwrap();
+    // Received and echoed: 10 probes of 32 bytes each way.
+    assert_eq!(summary.bytes_transferred, 10 * 32 * 2);
+    assert!(summary.streams_mbps.is_empty());
+
+    let audit = reflector.audit_for(&client.peer_id);
+    assert!(has_event(
+        &audit,
+        AuditEventType::SessionGranted,
+        Some(&grant.test_id)
+    ));
+    assert!(has_event(
+        &audit,
+        AuditEventType::SessionCompleted,
+        Some(&grant.test_id)
+    ));
+}
+
+#[tokio::test]
+async fn test_e2e_unpaired_peer_cannot_request_sessions() {
+    let reflector = TestReflector::start(DataPlaneMode::Tunneled)
+        .await
+        .unwrap();
+    let mut client = TestClient::connect(reflector.addr).await.unwrap();
+
+    let reply = client
+        .call(MessagePayload::SessionRequest(SessionRequest {
+            test_type: TestType::UdpEcho,
+            params: TestParams {
+                duration_sec: 2,
+                protocol: None,
+                streams: None,
+                reverse: None,
+                mtu_probe: None,
+            },
+        }))
+        .await
+        .unwrap();
+    match reply {
+        MessagePayload::Error(e) => assert_eq!(e.code, 403),
+        other => panic!("expected 403, got {:?}", other),
+    }
+    assert!(!has_event(
+        &reflector.audit_for(&client.peer_id),
+        AuditEventType::SessionGranted,
+        None
+    ));
+}
@@ -0,0 +1,168 @@
+//! Session-token gate for the direct-ephemeral data plane.
+//!
+//! In direct-ephemeral mode the granted port is reachable from the network,
+//! and iperf3 itself has no notion of our session token.  The gate listens on
+//! the granted port instead of iperf3 (which binds loopback only) and requires
+//! every connection to open with the session's `SessionGrant.token` bytes.
+//! Verified connections are spliced to the engine; anything else is dropped
+//! before a single byte reaches iperf3.
+//!
+//! Tunneled sessions do not need the gate: their channels only exist inside
+//! the peer's authenticated control connection.
+
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use tokio::io::AsyncReadExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+/// How long a new connection has to present the token.
+pub const TOKEN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Bind the gate's listener on `addr`.  Bound before the engine starts, so
+/// the granted port is held from the moment it is chosen.
+pub async fn bind(addr: SocketAddr) -> Result<TcpListener> {
+    TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("failed to bind data-plane gate on {}", addr))
+}
+
+/// Forward connections on `listener` that present `token` to the engine at
+/// `upstream`.  The gate stops accepting after `lifetime` (the engine's own
+/// deadline); already-forwarded connections run to completion.
+pub fn spawn(
+    listener: TcpListener,
+    upstream: SocketAddr,
+    token: String,
+    lifetime: Duration,
+) -> JoinHandle<()> {
+    let bind = listener
+        .local_addr()
+        .unwrap_or_else(|_| SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)));
+    tokio::spawn(async move {
+        let deadline = tokio::time::sleep(lifetime);
+        tokio::pin!(deadline);
+        loop {
+            tokio::select! {
+                _ = &mut deadline => {
+                    debug!(%bind, "data-plane gate closed");
+                    return;
+                }
+                accepted = listener.accept() => {
+                    let (stream, remote) = match accepted {
+                        Ok(conn) => conn,
+                        Err(e) => {
+                            warn!(%bind, error = %e, "data-plane gate accept failed");
+                            continue;
+                        }
+                    };
+                    let token = token.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = forward(stream, upstream, &token).await {
+                            warn!(%remote, "data-plane connection rejected: {:#}", e);
+                        }
+                    });
+                }
+            }
+        }
+    })
+}
+
+/// Reserve a free loopback port for the engine behind the gate.
+///
+/// iperf3 binds its port itself, so the port cannot be handed over as a
+/// listener.  The returned listener holds the port until the caller drops
+/// it right before starting the engine, which keeps the window in which
+/// another local process could take it as short as possible.
+pub async fn reserve_loopback_port() -> Result<TcpListener> {
+    TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+        .await
+        .context("failed to reserve a loopback port")
+}
+
+/// Check the token preamble on `stream`, then splice it to `upstream`.
+async fn forward(mut stream: TcpStream, upstream: SocketAddr, token: &str) -> Result<()> {
+    let mut presented = vec![0u8; token.len()];
+    tokio::time::timeout(TOKEN_TIMEOUT, stream.read_exact(&mut presented))
+        .await
+        .context("no session token within the timeout")?
+        .context("connection closed before the session token")?;
+    if !tokens_match(&presented, token.as_bytes()) {
+        anyhow::bail!("invalid session token");
+    }
+
+    let mut engine = TcpStream::connect(upstream)
+        .await
+        .context("engine is not accepting connections")?;
+    let _ = tokio::io::copy_bidirectional(&mut stream, &mut engine).await;
+    Ok(())
+}
+
+/// Compare tokens without short-circuiting on the first differing byte.
+fn tokens_match(presented: &[u8], expected: &[u8]) -> bool {
+    presented.len() == expected.len()
+        && presented
+            .iter()
+            .zip(expected)
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+
+    /// Upstream that echoes whatever it receives.
+    async fn echo_upstream() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut s, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let (mut r, mut w) = s.split();
+                    let _ = tokio::io::copy(&mut r, &mut w).await;
+                });
+            }
+        });
+        addr
+    }
+
+    async fn gate(token: &str) -> SocketAddr {
+        let listener = bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        spawn(listener, echo_upstream().await, token.to_string(), Duration::from_secs(30));
+        addr
+    }
+
+    #[test]
+    fn test_tokens_match() {
+        assert!(tokens_match(b"secret", b"secret"));
+        assert!(!tokens_match(b"secreT", b"secret"));
+        assert!(!tokens_match(b"secre", b"secret"));
+    }
+
+    #[tokio::test]
+    async fn test_valid_token_is_forwarded() {
+        let addr = gate("tok-123").await;
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        client.write_all(b"tok-123hello").await.unwrap();
+        let mut buf = [0u8; 5];
+        client.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_wrong_token_is_dropped() {
+        let addr = gate("tok-123").await;
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        client.write_all(b"tok-999hello").await.unwrap();
+        let mut buf = [0u8; 5];
+        // The gate closes the connection without forwarding anything.
+        let n = client.read(&mut buf).await.unwrap_or(0);
+        assert_eq!(n, 0);
+    }
+}
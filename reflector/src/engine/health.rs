@@ -1,13 +1,29 @@
 //! Health check endpoint for the PacketParamedic Reflector.
 //!
 //! Provides a simple HTTP `GET /health` endpoint that returns the reflector's
-//! status, version, and current system load.
+//! status, version, current system load, and connection setup latencies.
 
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use axum::extract::State;
+use axum::http::StatusCode;
 use axum::response::IntoResponse;
 use axum::{routing::get, Json, Router};
 use serde_json::json;
 use sysinfo::System;
 
+use crate::metrics::LatencyMetrics;
+
+/// State shared with the health handler.
+#[derive(Clone)]
+pub struct HealthState {
+    /// Maintenance-mode flag, shared with the session manager.
+    pub maintenance: Arc<AtomicBool>,
+    /// Handshake and session setup latencies, shared with the server.
+    pub latency: Arc<LatencyMetrics>,
+}
+
 // ---------------------------------------------------------------------------
 // Health handler
 // ---------------------------------------------------------------------------
@@ -15,25 +31,45 @@ use sysinfo::System;
 /// Axum handler for `GET /health`.
 ///
 /// Returns a JSON object with:
-/// - `status`: always `"ok"` if the server is running
+/// - `status`: `"ok"`, or `"maintenance"` while the reflector is draining
 /// - `version`: the crate version from `Cargo.toml`
 /// - `load`: 1-minute system load average
-pub async fn health_handler() -> impl IntoResponse {
+/// - `latency`: p50/p95 of TLS handshake and session setup times (`null`
+///   until a sample exists)
+///
+/// Responds with `503 Service Unavailable` in maintenance mode so that load
+/// balancers route new tests elsewhere.
+pub async fn health_handler(State(state): State<HealthState>) -> impl IntoResponse {
     let load = System::load_average();
+    let in_maintenance = state.maintenance.load(Ordering::SeqCst);
+
+    let (code, status) = if in_maintenance {
+        (StatusCode::SERVICE_UNAVAILABLE, "maintenance")
+    } else {
+        (StatusCode::OK, "ok")
+    };
 
-    Json(json!({
-        "status": "ok",
-        "version": env!("CARGO_PKG_VERSION"),
-        "load": load.one,
-    }))
+    (
+        code,
+        Json(json!({
+            "status": status,
+            "version": env!("CARGO_PKG_VERSION"),
+            "load": load.one,
+            "latency": state.latency.snapshot(),
+        })),
+    )
 }
 
 /// Build an Axum router with the health endpoint.
 ///
 /// Mount this router on a separate HTTP listener (e.g. port 7301) so that
-/// monitoring systems can probe the reflector without TLS/mTLS.
-pub fn build_health_router() -> Router {
-    Router::new().route("/health", get(health_handler))
+/// monitoring systems can probe the reflector without TLS/mTLS. The
+/// `maintenance` flag is shared with the session manager and `latency`
+/// with the server.
+pub fn build_health_router(maintenance: Arc<AtomicBool>, latency: Arc<LatencyMetrics>) -> Router {
+    Router::new()
+        .route("/health", get(health_handler))
+        .with_state(HealthState { maintenance, latency })
 }
 
 // ---------------------------------------------------------------------------
@@ -49,7 +85,9 @@ mod tests {
 
     #[tokio::test]
     async fn test_health_endpoint() {
-        let app = build_health_router();
+        let latency = Arc::new(LatencyMetrics::new());
+        latency.record_handshake(std::time::Duration::from_millis(12));
+        let app = build_health_router(Arc::new(AtomicBool::new(false)), latency);
 
         let request = Request::builder()
             .uri("/health")
@@ -68,5 +106,29 @@ mod tests {
         assert_eq!(json["status"], "ok");
         assert!(json["version"].is_string());
         assert!(json["load"].is_number());
+        assert_eq!(json["latency"]["handshake"]["samples"], 1);
+        assert_eq!(json["latency"]["handshake"]["p95_ms"], 12.0);
+        assert!(json["latency"]["session_setup"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_health_endpoint_maintenance() {
+        let app = build_health_router(Arc::new(AtomicBool::new(true)), Arc::default());
+
+        let request = Request::builder()
+            .uri("/health")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let body = axum::body::to_bytes(response.into_body(), 10_000)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(json["status"], "maintenance");
     }
 }
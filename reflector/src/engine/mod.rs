@@ -4,11 +4,14 @@
 //! stopped independently.  The [`TestHandle`] provides a shutdown channel for
 //! graceful termination, and [`EngineResult`] captures the outcome.
 
+pub mod gate;
 pub mod health;
 pub mod path_meta;
 pub mod throughput;
 pub mod udp_echo;
 
+use crate::rpc::{SessionOutcome, SessionSummary};
+
 // ---------------------------------------------------------------------------
 // TestHandle
 // ---------------------------------------------------------------------------
@@ -39,6 +42,9 @@ pub enum EngineResult {
         bytes_transferred: u64,
         /// Wall-clock duration in seconds.
         duration_sec: f64,
+        /// Per-stream throughput in Mbps, for engines that run parallel
+        /// streams; empty otherwise.
+        streams_mbps: Vec<f64>,
     },
     /// The test was terminated because it exceeded its time limit.
     TimedOut {
@@ -46,7 +52,48 @@ pub enum EngineResult {
         bytes_transferred: u64,
         /// Wall-clock duration in seconds (should be close to the limit).
         duration_sec: f64,
+        /// Per-stream throughput in Mbps, as for `Completed`.
+        streams_mbps: Vec<f64>,
     },
     /// The test failed with an error.
     Error(String),
 }
+
+impl EngineResult {
+    /// Wire-format summary of this result for `test_id`.
+    pub fn to_summary(&self, test_id: &str) -> SessionSummary {
+        let (outcome, bytes_transferred, duration_sec, streams_mbps, error) = match self {
+            EngineResult::Completed {
+                bytes_transferred,
+                duration_sec,
+                streams_mbps,
+            } => (
+                SessionOutcome::Completed,
+                *bytes_transferred,
+                *duration_sec,
+                streams_mbps.clone(),
+                None,
+            ),
+            EngineResult::TimedOut {
+                bytes_transferred,
+                duration_sec,
+                streams_mbps,
+            } => (
+                SessionOutcome::TimedOut,
+                *bytes_transferred,
+                *duration_sec,
+                streams_mbps.clone(),
+                None,
+            ),
+            EngineResult::Error(e) => (SessionOutcome::Error, 0, 0.0, Vec::new(), Some(e.clone())),
+        };
+        SessionSummary {
+            test_id: test_id.to_string(),
+            outcome,
+            bytes_transferred,
+            duration_sec,
+            streams_mbps,
+            error,
+        }
+    }
+}
@@ -1,12 +1,15 @@
 //! iperf3 server spawner engine for throughput tests.
 //!
-//! Spawns an `iperf3 -s --one-off` child process on a free port within a
-//! configured range.  The child is monitored and killed on shutdown signal
-//! or timeout.
+//! Spawns an `iperf3 -s --one-off --json` child process on a free port within
+//! a configured range.  The child is monitored and killed on shutdown signal
+//! or timeout, and its JSON report supplies the bytes transferred and the
+//! measured test duration.
 
+use std::net::IpAddr;
 use std::time::Duration;
 
 use anyhow::{Context, Result};
+use tokio::io::AsyncReadExt;
 use tokio::process::Command;
 use tokio::task::JoinHandle;
 use tracing::{debug, info, warn};
@@ -31,6 +34,8 @@ pub struct ThroughputEngine {
     port_range_start: u16,
     /// End of the ephemeral port range (inclusive).
     port_range_end: u16,
+    /// Address iperf3 binds to; all interfaces when `None`.
+    bind_address: Option<IpAddr>,
 }
 
 impl ThroughputEngine {
@@ -40,9 +45,16 @@ impl ThroughputEngine {
             iperf3_path: config.path.clone(),
             port_range_start: port_range.0,
             port_range_end: port_range.1,
+            bind_address: None,
         }
     }
 
+    /// Bind iperf3 to `addr` only (loopback for tunneled sessions).
+    pub fn with_bind_address(mut self, addr: IpAddr) -> Self {
+        self.bind_address = Some(addr);
+        self
+    }
+
     /// Get the configured port range.
     pub fn port_range(&self) -> (u16, u16) {
         (self.port_range_start, self.port_range_end)
@@ -98,13 +110,18 @@ impl ThroughputEngine {
             "starting iperf3 server"
         );
 
-        let mut child = Command::new(&self.iperf3_path)
-            .arg("-s")
+        let mut cmd = Command::new(&self.iperf3_path);
+        cmd.arg("-s")
             .arg("-p")
             .arg(port.to_string())
             .arg("--one-off")
+            .arg("--json");
+        if let Some(addr) = self.bind_address {
+            cmd.arg("-B").arg(addr.to_string());
+        }
+        let mut child = cmd
             .kill_on_drop(true)
-            .stdout(std::process::Stdio::inherit())
+            .stdout(std::process::Stdio::piped())
             .stderr(std::process::Stdio::inherit())
             .spawn()
             .with_context(|| {
@@ -116,6 +133,16 @@ impl ThroughputEngine {
 
         let child_pid = child.id();
 
+        // Drain stdout concurrently so a large report never blocks iperf3.
+        let stdout = child.stdout.take();
+        let report = tokio::spawn(async move {
+            let mut buf = Vec::new();
+            if let Some(mut out) = stdout {
+                let _ = out.read_to_end(&mut buf).await;
+            }
+            buf
+        });
+
         let task_test_id = test_id.clone();
         let handle = tokio::spawn(async move {
             let start = tokio::time::Instant::now();
@@ -129,20 +156,24 @@ impl ThroughputEngine {
                 _ = &mut shutdown_rx => {
                     debug!(test_id = task_test_id.as_str(), "shutdown signal received, terminating iperf3");
                     terminate_child(&mut child).await;
-                    let elapsed = start.elapsed().as_secs_f64();
+                    let (bytes_transferred, duration_sec, streams_mbps) =
+                        report_totals(report, start.elapsed().as_secs_f64()).await;
                     EngineResult::Completed {
-                        bytes_transferred: 0, // iperf3 output parsing not yet implemented
-                        duration_sec: elapsed,
+                        bytes_transferred,
+                        duration_sec,
+                        streams_mbps,
                     }
                 }
 
                 _ = &mut timeout => {
                     warn!(test_id = task_test_id.as_str(), "iperf3 test timed out, terminating");
                     terminate_child(&mut child).await;
-                    let elapsed = start.elapsed().as_secs_f64();
+                    let (bytes_transferred, duration_sec, streams_mbps) =
+                        report_totals(report, start.elapsed().as_secs_f64()).await;
                     EngineResult::TimedOut {
-                        bytes_transferred: 0,
-                        duration_sec: elapsed,
+                        bytes_transferred,
+                        duration_sec,
+                        streams_mbps,
                     }
                 }
 
@@ -157,9 +188,12 @@ impl ThroughputEngine {
                                 "iperf3 exited"
                             );
                             if exit.success() {
+                                let (bytes_transferred, duration_sec, streams_mbps) =
+                                    report_totals(report, elapsed).await;
                                 EngineResult::Completed {
-                                    bytes_transferred: 0,
-                                    duration_sec: elapsed,
+                                    bytes_transferred,
+                                    duration_sec,
+                                    streams_mbps,
                                 }
                             } else {
                                 EngineResult::Error(format!(
@@ -194,6 +228,56 @@ impl ThroughputEngine {
     }
 }
 
+/// Wait for the stdout reader and extract `(bytes, seconds, streams_mbps)`
+/// from the report, falling back to zero bytes over `elapsed` without one.
+async fn report_totals(report: JoinHandle<Vec<u8>>, elapsed: f64) -> (u64, f64, Vec<f64>) {
+    let buf = report.await.unwrap_or_default();
+    let (bytes, seconds) = parse_report(&buf).unwrap_or((0, elapsed));
+    (bytes, seconds, parse_streams(&buf))
+}
+
+/// Bytes moved and test seconds according to an iperf3 `--json` server report.
+///
+/// TCP reports carry `end.sum_sent` / `end.sum_received`, UDP reports carry
+/// `end.sum`; the largest is taken so it reflects what the sender pushed in
+/// either direction. The report's own seconds exclude the time iperf3 sat
+/// waiting for the client. Returns `None` if the output is not a complete
+/// report (e.g. the child was killed before writing it).
+fn parse_report(output: &[u8]) -> Option<(u64, f64)> {
+    let json: serde_json::Value = serde_json::from_slice(output).ok()?;
+    let end = json.get("end")?;
+    ["sum_sent", "sum_received", "sum"]
+        .iter()
+        .filter_map(|k| {
+            let sum = end.get(*k)?;
+            Some((sum.get("bytes")?.as_u64()?, sum.get("seconds")?.as_f64()?))
+        })
+        .max_by_key(|(bytes, _)| *bytes)
+}
+
+/// Per-stream throughput in Mbps from an iperf3 `--json` server report.
+///
+/// Each `end.streams[]` entry carries a `receiver` and `sender` side for TCP
+/// or a single `udp` side; the receiver is preferred as the figure the data
+/// actually arrived at. Empty if the report is missing or incomplete.
+fn parse_streams(output: &[u8]) -> Vec<f64> {
+    let Ok(json) = serde_json::from_slice::<serde_json::Value>(output) else {
+        return Vec::new();
+    };
+    let Some(streams) = json.pointer("/end/streams").and_then(|s| s.as_array()) else {
+        return Vec::new();
+    };
+    streams
+        .iter()
+        .filter_map(|stream| {
+            ["receiver", "sender", "udp"]
+                .iter()
+                .find_map(|side| stream.get(*side)?.get("bits_per_second")?.as_f64())
+        })
+        .map(|bps| bps / 1_000_000.0)
+        .collect()
+}
+
 /// Gracefully terminate a child process.
 ///
 /// Sends SIGTERM first, waits up to 5 seconds, then sends SIGKILL if the
@@ -235,6 +319,38 @@ async fn terminate_child(child: &mut tokio::process::Child) {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_report_tcp() {
+        let report = br#"{"start":{},"end":{
+            "sum_sent":{"bytes":1250000000,"seconds":10.0},
+            "sum_received":{"bytes":1187500000,"seconds":10.0}}}"#;
+        assert_eq!(parse_report(report), Some((1_250_000_000, 10.0)));
+    }
+
+    #[test]
+    fn test_parse_streams() {
+        let report = br#"{"end":{"streams":[
+            {"sender":{"bits_per_second":3.2e8},"receiver":{"bits_per_second":3.1e8}},
+            {"sender":{"bits_per_second":1.0e7}},
+            {"udp":{"bits_per_second":2.5e7}}]}}"#;
+        assert_eq!(parse_streams(report), vec![310.0, 10.0, 25.0]);
+        assert!(parse_streams(b"").is_empty());
+        assert!(parse_streams(br#"{"end":{}}"#).is_empty());
+    }
+
+    #[test]
+    fn test_parse_report_udp() {
+        let report = br#"{"end":{"sum":{"bytes":65536,"seconds":1.0}}}"#;
+        assert_eq!(parse_report(report), Some((65536, 1.0)));
+    }
+
+    #[test]
+    fn test_parse_report_incomplete() {
+        assert_eq!(parse_report(b""), None);
+        assert_eq!(parse_report(br#"{"start":{}"#), None);
+        assert_eq!(parse_report(br#"{"start":{}}"#), None);
+    }
+
     #[tokio::test]
     async fn test_find_free_port() {
         let config = Iperf3Config {
@@ -1,9 +1,18 @@
 //! Built-in UDP echo reflector engine.
 //!
-//! Listens on a UDP socket and echoes every received datagram back to the
-//! sender.  Tracks bytes transferred and enforces an optional packet rate
+//! Listens on a UDP socket and echoes datagrams from the session's peer back
+//! to it.  Tracks bytes transferred and enforces an optional packet rate
 //! limit and a maximum duration.
+//!
+//! Datagrams from any address other than the peer's are dropped unanswered;
+//! otherwise a spoofed source would turn the reflector into an open UDP
+//! amplifier aimed at a third party.
+//!
+//! For path-MTU probing (a client ramping datagram sizes) the engine can set
+//! the don't-fragment bit on its echoes, so an echo too large for the return
+//! path is dropped instead of silently fragmented.
 
+use std::net::IpAddr;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
@@ -22,7 +31,7 @@ use super::{EngineResult, TestHandle};
 
 /// UDP echo reflector engine.
 ///
-/// Binds a UDP socket and echoes every received packet back to the sender,
+/// Binds a UDP socket and echoes every packet from the peer back to it,
 /// tracking total bytes transferred.
 pub struct UdpEchoEngine;
 
@@ -37,31 +46,42 @@ impl UdpEchoEngine {
     ///
     /// * `port` - UDP port to bind on `0.0.0.0`. Use `0` for an
     ///   OS-assigned ephemeral port.
+    /// * `peer` - IP of the authenticated control connection; the only
+    ///   source echoed.
     /// * `duration` - Maximum duration before the session auto-closes.
     /// * `max_packet_rate` - Maximum packets per second. `0` means unlimited.
+    /// * `dont_fragment` - Send echoes with DF set (path-MTU probing).
     pub async fn start(
         port: u16,
+        peer: IpAddr,
         duration: Duration,
         max_packet_rate: u32,
+        dont_fragment: bool,
     ) -> Result<(TestHandle, JoinHandle<EngineResult>)> {
         let bind_addr = format!("0.0.0.0:{}", port);
         let socket = UdpSocket::bind(&bind_addr)
             .await
             .with_context(|| format!("failed to bind UDP socket on {}", bind_addr))?;
+        if dont_fragment {
+            set_dont_fragment(&socket).context("failed to set don't-fragment on echo socket")?;
+        }
 
         let actual_port = socket
             .local_addr()
             .context("failed to get local address")?
             .port();
 
+        let peer = peer.to_canonical();
         let test_id = Uuid::new_v4().to_string();
         let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
 
         info!(
             test_id = test_id.as_str(),
             port = actual_port,
+            peer = %peer,
             duration_sec = duration.as_secs(),
             max_packet_rate = max_packet_rate,
+            dont_fragment = dont_fragment,
             "starting UDP echo engine"
         );
 
@@ -96,6 +116,15 @@ impl UdpEchoEngine {
                     result = socket.recv_from(&mut buf) => {
                         match result {
                             Ok((len, addr)) => {
+                                if addr.ip().to_canonical() != peer {
+                                    debug!(
+                                        test_id = task_test_id.as_str(),
+                                        source = %addr,
+                                        "dropping datagram from non-peer source"
+                                    );
+                                    continue;
+                                }
+
                                 // Rate limiting.
                                 if max_packet_rate > 0 {
                                     let now = tokio::time::Instant::now();
@@ -112,6 +141,16 @@ impl UdpEchoEngine {
 
                                 // Echo the packet back.
                                 if let Err(e) = socket.send_to(&buf[..len], addr).await {
+                                    // With DF set, an echo larger than the known
+                                    // path MTU is the expected outcome of a ramp.
+                                    if dont_fragment && e.raw_os_error() == Some(libc::EMSGSIZE) {
+                                        debug!(
+                                            test_id = task_test_id.as_str(),
+                                            len,
+                                            "echo exceeds path MTU"
+                                        );
+                                        continue;
+                                    }
                                     warn!(
                                         test_id = task_test_id.as_str(),
                                         error = %e,
@@ -149,11 +188,13 @@ impl UdpEchoEngine {
                 EngineResult::TimedOut {
                     bytes_transferred: total_bytes,
                     duration_sec: elapsed,
+                    streams_mbps: Vec::new(),
                 }
             } else {
                 EngineResult::Completed {
                     bytes_transferred: total_bytes,
                     duration_sec: elapsed,
+                    streams_mbps: Vec::new(),
                 }
             }
         });
@@ -168,6 +209,38 @@ impl UdpEchoEngine {
     }
 }
 
+/// Set `IP_PMTUDISC_DO` so datagrams leave with DF set and are never
+/// fragmented locally.
+#[cfg(target_os = "linux")]
+fn set_dont_fragment(socket: &UdpSocket) -> std::io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let value: libc::c_int = libc::IP_PMTUDISC_DO;
+    // SAFETY: `value` outlives the call and its size is passed alongside it.
+    let ret = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::IPPROTO_IP,
+            libc::IP_MTU_DISCOVER,
+            &value as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of_val(&value) as libc::socklen_t,
+        )
+    };
+    if ret == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_dont_fragment(_socket: &UdpSocket) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "don't-fragment is only supported on Linux",
+    ))
+}
+
 // ---------------------------------------------------------------------------
 // Tests
 // ---------------------------------------------------------------------------
@@ -175,12 +248,15 @@ impl UdpEchoEngine {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::net::Ipv4Addr;
     use tokio::net::UdpSocket;
 
+    const LOOPBACK: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
     #[tokio::test]
     async fn test_udp_echo_round_trip() {
         // Start the echo engine on an ephemeral port.
-        let (handle, task) = UdpEchoEngine::start(0, Duration::from_secs(5), 0)
+        let (handle, task) = UdpEchoEngine::start(0, LOOPBACK, Duration::from_secs(5), 0, false)
             .await
             .expect("should start echo engine");
 
@@ -229,7 +305,7 @@ mod tests {
     #[tokio::test]
     async fn test_udp_echo_timeout() {
         // Start with a very short duration.
-        let (handle, task) = UdpEchoEngine::start(0, Duration::from_millis(100), 0)
+        let (handle, task) = UdpEchoEngine::start(0, LOOPBACK, Duration::from_millis(100), 0, false)
             .await
             .expect("should start echo engine");
 
@@ -261,7 +337,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_udp_echo_rate_limit() {
-        let (handle, _task) = UdpEchoEngine::start(0, Duration::from_secs(5), 2)
+        let (handle, _task) = UdpEchoEngine::start(0, LOOPBACK, Duration::from_secs(5), 2, false)
             .await
             .expect("should start echo engine");
 
@@ -285,4 +361,59 @@ mod tests {
         // Shut down.
         let _ = handle.shutdown_tx.send(());
     }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_udp_echo_dont_fragment() {
+        let (handle, _task) = UdpEchoEngine::start(0, LOOPBACK, Duration::from_secs(5), 0, true)
+            .await
+            .expect("should start echo engine with DF");
+
+        // Loopback MTU is far above the largest UDP payload, so a 1472-byte
+        // echo still comes back.
+        let client = UdpSocket::bind("127.0.0.1:0").await.expect("client bind");
+        let payload = vec![0xAB; 1472];
+        client
+            .send_to(&payload, format!("127.0.0.1:{}", handle.port))
+            .await
+            .expect("send");
+        let mut buf = [0u8; 2048];
+        let (len, _) = tokio::time::timeout(Duration::from_secs(2), client.recv_from(&mut buf))
+            .await
+            .expect("timeout waiting for echo")
+            .expect("recv");
+        assert_eq!(&buf[..len], &payload[..]);
+
+        let _ = handle.shutdown_tx.send(());
+    }
+
+    #[tokio::test]
+    async fn test_udp_echo_ignores_other_sources() {
+        let peer = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2));
+        let (handle, _task) = UdpEchoEngine::start(0, peer, Duration::from_secs(5), 0, false)
+            .await
+            .expect("should start echo engine");
+        let target = format!("127.0.0.1:{}", handle.port);
+        let mut buf = [0u8; 64];
+
+        // A spoofed (or any other) source gets nothing back.
+        let stranger = UdpSocket::bind("127.0.0.1:0").await.expect("stranger bind");
+        stranger.send_to(b"reflect me", &target).await.expect("send");
+        assert!(
+            tokio::time::timeout(Duration::from_millis(300), stranger.recv_from(&mut buf))
+                .await
+                .is_err(),
+            "echoed a datagram from a non-peer source"
+        );
+
+        let client = UdpSocket::bind("127.0.0.2:0").await.expect("peer bind");
+        client.send_to(b"ping", &target).await.expect("send");
+        let (len, _) = tokio::time::timeout(Duration::from_secs(2), client.recv_from(&mut buf))
+            .await
+            .expect("timeout waiting for echo")
+            .expect("recv");
+        assert_eq!(&buf[..len], b"ping");
+
+        let _ = handle.shutdown_tx.send(());
+    }
 }
@@ -0,0 +1,261 @@
+//! Firewall check for `direct_ephemeral` data-plane mode.
+//!
+//! In direct mode iperf3 listens on ports from `data_port_range_start..=end`,
+//! and peers connect to them directly. A host firewall that does not let that
+//! range through is the most common reason direct mode "doesn't work". This
+//! module checks, from the host itself:
+//!
+//! 1. that the ports can be bound (nothing else is squatting on them), and
+//! 2. whether the local nftables / iptables rules accept inbound TCP to them.
+//!
+//! Rule analysis is a heuristic: it looks at input-chain default policies and
+//! `dport` accept rules, and does not evaluate rule order, source filters or
+//! upstream (router / cloud) firewalls.
+
+use std::net::TcpListener;
+use std::process::Command;
+
+use serde::Serialize;
+
+/// What the rules say about the data port range.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "verdict", rename_all = "snake_case")]
+pub enum RuleVerdict {
+    /// Input policy accepts by default.
+    OpenByPolicy,
+    /// Explicit accept rules cover the whole range.
+    Allowed,
+    /// Accept rules cover part of the range; `uncovered` ports would be dropped.
+    Partial { uncovered: usize, first_uncovered: u16 },
+    /// Input policy drops and no accept rule covers the range.
+    Blocked,
+    /// The ruleset could not be read (tool missing, needs root, ...).
+    Unknown { reason: String },
+}
+
+impl RuleVerdict {
+    pub fn is_problem(&self) -> bool {
+        matches!(self, Self::Partial { .. } | Self::Blocked)
+    }
+}
+
+/// Result of `reflector firewall check`.
+#[derive(Debug, Clone, Serialize)]
+pub struct FirewallReport {
+    pub range_start: u16,
+    pub range_end: u16,
+    /// Ports in the range that could not be bound locally.
+    pub ports_in_use: Vec<u16>,
+    pub nftables: RuleVerdict,
+    pub iptables: RuleVerdict,
+}
+
+impl FirewallReport {
+    /// Whether anything found would stop peers reaching the data ports.
+    pub fn blocked(&self) -> bool {
+        self.nftables.is_problem() || self.iptables.is_problem()
+    }
+}
+
+/// Run all checks for the inclusive range `start..=end`.
+pub fn check(start: u16, end: u16) -> FirewallReport {
+    FirewallReport {
+        range_start: start,
+        range_end: end,
+        ports_in_use: ports_in_use(start, end),
+        nftables: match run_tool("nft", &["list", "ruleset"]) {
+            Ok(out) => analyze_nft(&out, start, end),
+            Err(reason) => RuleVerdict::Unknown { reason },
+        },
+        iptables: match run_tool("iptables-save", &[]) {
+            Ok(out) => analyze_iptables(&out, start, end),
+            Err(reason) => RuleVerdict::Unknown { reason },
+        },
+    }
+}
+
+fn ports_in_use(start: u16, end: u16) -> Vec<u16> {
+    (start..=end)
+        .filter(|port| TcpListener::bind(("0.0.0.0", *port)).is_err())
+        .collect()
+}
+
+fn run_tool(program: &str, args: &[&str]) -> Result<String, String> {
+    let out = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| format!("{} not available: {}", program, e))?;
+    if !out.status.success() {
+        return Err(format!(
+            "{} failed (root required?): {}",
+            program,
+            String::from_utf8_lossy(&out.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+}
+
+/// Analyze `nft list ruleset` output.
+pub fn analyze_nft(ruleset: &str, start: u16, end: u16) -> RuleVerdict {
+    let mut drop_policy = false;
+    let mut any_input_chain = false;
+    let mut covered = vec![false; end.saturating_sub(start) as usize + 1];
+
+    for line in ruleset.lines().map(str::trim) {
+        if line.contains("hook input") {
+            any_input_chain = true;
+            if line.contains("policy drop") || line.contains("policy reject") {
+                drop_policy = true;
+            }
+            continue;
+        }
+        // Accept rules in any chain count: firewalld/ufw jump into sub-chains.
+        let Some(idx) = line.find("dport") else { continue };
+        if !line.ends_with("accept") || !(line.contains("tcp") || line.contains("th dport")) {
+            continue;
+        }
+        let spec = &line[idx + "dport".len()..];
+        let spec = match spec.trim_start().strip_prefix('{') {
+            Some(set) => set.split('}').next().unwrap_or_default(),
+            None => spec.split_whitespace().next().unwrap_or_default(),
+        };
+        for part in spec.split(',') {
+            mark(&mut covered, start, parse_range(part.trim(), '-'));
+        }
+    }
+
+    verdict(any_input_chain && drop_policy, &covered, start)
+}
+
+/// Analyze `iptables-save` output.
+pub fn analyze_iptables(rules: &str, start: u16, end: u16) -> RuleVerdict {
+    let mut drop_policy = false;
+    let mut covered = vec![false; end.saturating_sub(start) as usize + 1];
+
+    for line in rules.lines().map(str::trim) {
+        if line.starts_with(":INPUT DROP") || line.starts_with(":INPUT REJECT") {
+            drop_policy = true;
+            continue;
+        }
+        if !line.starts_with("-A ") || !line.contains("-j ACCEPT") || !line.contains("-p tcp") {
+            continue;
+        }
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        for pair in tokens.windows(2) {
+            if pair[0] == "--dport" || pair[0] == "--dports" {
+                for part in pair[1].split(',') {
+                    mark(&mut covered, start, parse_range(part, ':'));
+                }
+            }
+        }
+    }
+
+    verdict(drop_policy, &covered, start)
+}
+
+/// Parse "5201" or "5201<sep>5299".
+fn parse_range(s: &str, sep: char) -> Option<(u16, u16)> {
+    match s.split_once(sep) {
+        Some((a, b)) => Some((a.trim().parse().ok()?, b.trim().parse().ok()?)),
+        None => {
+            let p = s.trim().parse().ok()?;
+            Some((p, p))
+        }
+    }
+}
+
+fn mark(covered: &mut [bool], start: u16, range: Option<(u16, u16)>) {
+    let Some((lo, hi)) = range else { return };
+    for (i, slot) in covered.iter_mut().enumerate() {
+        if (lo as usize..=hi as usize).contains(&(start as usize + i)) {
+            *slot = true;
+        }
+    }
+}
+
+fn verdict(drop_policy: bool, covered: &[bool], start: u16) -> RuleVerdict {
+    if !drop_policy {
+        return RuleVerdict::OpenByPolicy;
+    }
+    match covered.iter().position(|c| !c) {
+        None => RuleVerdict::Allowed,
+        Some(i) if covered.iter().any(|c| *c) => RuleVerdict::Partial {
+            uncovered: covered.iter().filter(|c| !**c).count(),
+            first_uncovered: start + i as u16,
+        },
+        Some(_) => RuleVerdict::Blocked,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NFT_OPEN: &str = "\
+table inet filter {
+\tchain input {
+\t\ttype filter hook input priority filter; policy drop;
+\t\tct state established,related accept
+\t\ttcp dport { 22, 4000, 5201-5299 } accept
+\t}
+}
+";
+
+    #[test]
+    fn test_nft_accept_rule_covers_range() {
+        assert_eq!(analyze_nft(NFT_OPEN, 5201, 5299), RuleVerdict::Allowed);
+    }
+
+    #[test]
+    fn test_nft_partial_and_blocked() {
+        let partial = NFT_OPEN.replace("5201-5299", "5201-5210");
+        assert_eq!(
+            analyze_nft(&partial, 5201, 5299),
+            RuleVerdict::Partial { uncovered: 89, first_uncovered: 5211 }
+        );
+
+        let blocked = NFT_OPEN.replace(", 5201-5299", "");
+        assert_eq!(analyze_nft(&blocked, 5201, 5299), RuleVerdict::Blocked);
+    }
+
+    #[test]
+    fn test_nft_accept_policy_or_no_ruleset() {
+        let open = NFT_OPEN.replace("policy drop", "policy accept");
+        assert_eq!(analyze_nft(&open, 5201, 5299), RuleVerdict::OpenByPolicy);
+        assert_eq!(analyze_nft("", 5201, 5299), RuleVerdict::OpenByPolicy);
+    }
+
+    #[test]
+    fn test_iptables_ufw_style() {
+        let rules = "\
+*filter
+:INPUT DROP [0:0]
+:FORWARD DROP [0:0]
+:ufw-user-input - [0:0]
+-A INPUT -j ufw-user-input
+-A ufw-user-input -p tcp -m tcp --dport 22 -j ACCEPT
+-A ufw-user-input -p tcp -m multiport --dports 4000,5201:5299 -j ACCEPT
+COMMIT
+";
+        assert_eq!(analyze_iptables(rules, 5201, 5299), RuleVerdict::Allowed);
+        assert_eq!(
+            analyze_iptables(&rules.replace("5201:5299", "5201:5298"), 5201, 5299),
+            RuleVerdict::Partial { uncovered: 1, first_uncovered: 5299 }
+        );
+        assert_eq!(
+            analyze_iptables(&rules.replace(",5201:5299", ""), 5201, 5299),
+            RuleVerdict::Blocked
+        );
+        assert_eq!(
+            analyze_iptables(":INPUT ACCEPT [0:0]\n", 5201, 5299),
+            RuleVerdict::OpenByPolicy
+        );
+    }
+
+    #[test]
+    fn test_ports_in_use_detects_bound_port() {
+        let listener = TcpListener::bind("0.0.0.0:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert_eq!(ports_in_use(port, port), vec![port]);
+    }
+}
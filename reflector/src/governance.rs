@@ -2,17 +2,29 @@
 //!
 //! The [`GovernanceEngine`] enforces per-peer rate limits, byte quotas,
 //! cooldown periods, and test-type restrictions.  All state is held behind a
-//! `tokio::sync::RwLock` for safe concurrent access from async tasks.
+//! `tokio::sync::RwLock` for safe concurrent access from async tasks.  The
+//! daily counters can be persisted (see [`crate::stats`]) so they survive a
+//! restart.
 
 use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
 
 use chrono::{DateTime, Utc};
-use tokio::sync::RwLock;
+use tokio::sync::{Mutex, RwLock};
 use tokio::time::Instant;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use crate::config::QuotaConfig;
-use crate::rpc::{DenyReason, TestType};
+use crate::rpc::{DenyReason, PeerUsage, TestType, UsageSummary};
+use crate::stats::{self, DailyUsage};
+
+/// UTC midnight starting the day that contains `now`.
+pub fn utc_day_start(now: DateTime<Utc>) -> DateTime<Utc> {
+    now.date_naive()
+        .and_hms_opt(0, 0, 0)
+        .expect("midnight is valid")
+        .and_utc()
+}
 
 // ---------------------------------------------------------------------------
 // GovernanceEngine
@@ -24,6 +36,8 @@ struct GovernanceInner {
     peer_test_counts: HashMap<String, VecDeque<Instant>>,
     /// Bytes transferred today per peer (for daily quota).
     peer_bytes_today: HashMap<String, u64>,
+    /// Tests started today per peer (for usage reporting).
+    peer_tests_today: HashMap<String, u32>,
     /// Timestamp of the last test per peer (for cooldown enforcement).
     peer_last_test: HashMap<String, Instant>,
     /// UTC start of the current day (for daily reset).
@@ -37,26 +51,78 @@ struct GovernanceInner {
 pub struct GovernanceEngine {
     inner: RwLock<GovernanceInner>,
     config: QuotaConfig,
+    /// Where the daily counters are persisted, if anywhere.
+    usage_file: Option<PathBuf>,
+    /// Serializes writes of the usage file, so concurrent saves neither
+    /// share its temp file nor land out of order.
+    persist_lock: Mutex<()>,
 }
 
 impl GovernanceEngine {
     /// Create a new governance engine with the given configuration.
     pub fn new(config: QuotaConfig) -> Self {
-        let now = Utc::now();
-        let day_start = now
-            .date_naive()
-            .and_hms_opt(0, 0, 0)
-            .expect("midnight is valid")
-            .and_utc();
+        let day_start = utc_day_start(Utc::now());
 
         Self {
             inner: RwLock::new(GovernanceInner {
                 peer_test_counts: HashMap::new(),
                 peer_bytes_today: HashMap::new(),
+                peer_tests_today: HashMap::new(),
                 peer_last_test: HashMap::new(),
                 day_start,
             }),
             config,
+            usage_file: None,
+            persist_lock: Mutex::new(()),
+        }
+    }
+
+    /// Persist the daily counters to `path` and restore today's from it.
+    ///
+    /// Counters saved on an earlier UTC day are discarded, matching the
+    /// rollover in [`reset_daily_if_needed`](Self::reset_daily_if_needed).
+    pub fn with_usage_file(mut self, path: PathBuf) -> Self {
+        if let Some(saved) = stats::load(&path) {
+            let inner = self.inner.get_mut();
+            if saved.day_start == inner.day_start {
+                info!(
+                    path = %path.display(),
+                    peers = saved.peer_bytes.len().max(saved.peer_tests.len()),
+                    "restored today's usage counters"
+                );
+                inner.peer_bytes_today = saved.peer_bytes;
+                inner.peer_tests_today = saved.peer_tests;
+            } else {
+                info!(
+                    saved_day = saved.day_start.to_rfc3339().as_str(),
+                    "discarding usage counters from an earlier day"
+                );
+            }
+        }
+        self.usage_file = Some(path);
+        self
+    }
+
+    /// Write the daily counters to the usage file, if one is configured.
+    async fn persist(&self) {
+        let Some(path) = &self.usage_file else {
+            return;
+        };
+        let _writing = self.persist_lock.lock().await;
+        let usage = {
+            let inner = self.inner.read().await;
+            DailyUsage {
+                day_start: inner.day_start,
+                peer_bytes: inner.peer_bytes_today.clone(),
+                peer_tests: inner.peer_tests_today.clone(),
+            }
+        };
+        let target = path.clone();
+        let saved = tokio::task::spawn_blocking(move || stats::save(&target, &usage))
+            .await
+            .unwrap_or_else(|e| Err(anyhow::anyhow!("usage save task failed: {e}")));
+        if let Err(e) = saved {
+            warn!(path = %path.display(), error = %e, "failed to persist usage counters");
         }
     }
 
@@ -135,62 +201,115 @@ impl GovernanceEngine {
     ///
     /// Updates the sliding window and last-test timestamp.
     pub async fn record_test_start(&self, peer_id: &str) {
-        let mut inner = self.inner.write().await;
-        let now = Instant::now();
-
-        // Update sliding window.
-        let timestamps = inner
-            .peer_test_counts
-            .entry(peer_id.to_string())
-            .or_default();
-        timestamps.push_back(now);
-
-        // Prune entries older than 1 hour.
-        let one_hour_ago = Instant::now() - std::time::Duration::from_secs(3600);
-        while timestamps.front().is_some_and(|&t| t < one_hour_ago) {
-            timestamps.pop_front();
-        }
+        {
+            let mut inner = self.inner.write().await;
+            let now = Instant::now();
 
-        // Update last-test timestamp.
-        inner
-            .peer_last_test
-            .insert(peer_id.to_string(), now);
+            // Update sliding window.
+            let timestamps = inner
+                .peer_test_counts
+                .entry(peer_id.to_string())
+                .or_default();
+            timestamps.push_back(now);
 
-        debug!(peer_id = peer_id, "recorded test start");
+            // Prune entries older than 1 hour.
+            let one_hour_ago = Instant::now() - std::time::Duration::from_secs(3600);
+            while timestamps.front().is_some_and(|&t| t < one_hour_ago) {
+                timestamps.pop_front();
+            }
+
+            // Update last-test timestamp.
+            inner
+                .peer_last_test
+                .insert(peer_id.to_string(), now);
+
+            *inner
+                .peer_tests_today
+                .entry(peer_id.to_string())
+                .or_insert(0) += 1;
+
+            debug!(peer_id = peer_id, "recorded test start");
+        }
+        self.persist().await;
     }
 
     /// Record bytes transferred for a peer (adds to their daily total).
     pub async fn record_bytes(&self, peer_id: &str, bytes: u64) {
-        let mut inner = self.inner.write().await;
-        let entry = inner
-            .peer_bytes_today
-            .entry(peer_id.to_string())
-            .or_insert(0);
-        *entry += bytes;
-        debug!(peer_id = peer_id, bytes = bytes, total = *entry, "recorded bytes");
+        {
+            let mut inner = self.inner.write().await;
+            let entry = inner
+                .peer_bytes_today
+                .entry(peer_id.to_string())
+                .or_insert(0);
+            *entry += bytes;
+            debug!(peer_id = peer_id, bytes = bytes, total = *entry, "recorded bytes");
+        }
+        self.persist().await;
     }
 
-    /// Reset daily byte counters if a new UTC day has started.
+    /// Per-peer bytes and test counts for the current UTC day.
     ///
+    /// Only completed transfers are counted; see
+    /// [`SessionManager::usage_summary`](crate::session::SessionManager::usage_summary)
+    /// for totals including running sessions.
+    pub async fn usage_today(&self) -> UsageSummary {
+        let inner = self.inner.read().await;
+        summarize(&inner)
+    }
+
+    /// Reset daily counters if a new UTC day has started.
+    ///
+    /// Returns the summary of the day that just ended, if a reset happened.
     /// Should be called periodically from a background task.
-    pub async fn reset_daily_if_needed(&self) {
-        let now = Utc::now();
-        let today_start = now
-            .date_naive()
-            .and_hms_opt(0, 0, 0)
-            .expect("midnight is valid")
-            .and_utc();
-
-        let mut inner = self.inner.write().await;
-        if today_start > inner.day_start {
+    pub async fn reset_daily_if_needed(&self) -> Option<UsageSummary> {
+        let today_start = utc_day_start(Utc::now());
+
+        let ended = {
+            let mut inner = self.inner.write().await;
+            if today_start <= inner.day_start {
+                return None;
+            }
             info!(
                 old_day = inner.day_start.to_rfc3339().as_str(),
                 new_day = today_start.to_rfc3339().as_str(),
                 "resetting daily governance counters"
             );
+            let ended = summarize(&inner);
             inner.peer_bytes_today.clear();
+            inner.peer_tests_today.clear();
             inner.day_start = today_start;
-        }
+            ended
+        };
+        self.persist().await;
+        Some(ended)
+    }
+}
+
+/// Build a usage summary from the daily counters.
+fn summarize(inner: &GovernanceInner) -> UsageSummary {
+    let mut peer_ids: Vec<&String> = inner
+        .peer_bytes_today
+        .keys()
+        .chain(inner.peer_tests_today.keys())
+        .collect();
+    peer_ids.sort();
+    peer_ids.dedup();
+
+    let peers: Vec<PeerUsage> = peer_ids
+        .into_iter()
+        .map(|peer_id| PeerUsage {
+            peer_id: peer_id.clone(),
+            bytes_today: inner.peer_bytes_today.get(peer_id).copied().unwrap_or(0),
+            tests_today: inner.peer_tests_today.get(peer_id).copied().unwrap_or(0),
+            active_tests: 0,
+        })
+        .collect();
+
+    UsageSummary {
+        day_start: inner.day_start.to_rfc3339(),
+        total_bytes: peers.iter().map(|p| p.bytes_today).sum(),
+        total_tests: peers.iter().map(|p| p.tests_today).sum(),
+        peers,
     }
 }
 
@@ -206,6 +325,7 @@ mod tests {
         QuotaConfig {
             max_concurrent_tests: 1,
             max_test_duration_sec: 60,
+            min_test_duration_sec: 1,
             max_tests_per_hour_per_peer: 10,
             max_bytes_per_day_per_peer: 1_000_000,
             cooldown_sec: 2,
@@ -336,10 +456,117 @@ mod tests {
             inner.day_start = Utc::now() - chrono::Duration::days(2);
         }
 
-        engine.reset_daily_if_needed().await;
+        let ended = engine.reset_daily_if_needed().await.expect("day rolled over");
+        assert_eq!(ended.total_bytes, 1000);
 
         // After reset, peer should be allowed again.
         let r2 = engine.check_allowed("peer-g", &TestType::UdpEcho).await;
         assert!(r2.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_usage_today_per_peer() {
+        let config = QuotaConfig {
+            cooldown_sec: 0,
+            ..default_config()
+        };
+        let engine = GovernanceEngine::new(config);
+
+        engine.record_test_start("peer-b").await;
+        engine.record_test_start("peer-a").await;
+        engine.record_test_start("peer-a").await;
+        engine.record_bytes("peer-a", 300).await;
+        engine.record_bytes("peer-b", 200).await;
+
+        let usage = engine.usage_today().await;
+        assert_eq!(usage.total_bytes, 500);
+        assert_eq!(usage.total_tests, 3);
+        assert_eq!(usage.peers.len(), 2);
+        assert_eq!(usage.peers[0].peer_id, "peer-a");
+        assert_eq!(usage.peers[0].bytes_today, 300);
+        assert_eq!(usage.peers[0].tests_today, 2);
+        assert_eq!(usage.peers[1].tests_today, 1);
+
+        // No rollover on the same day.
+        assert!(engine.reset_daily_if_needed().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_usage_survives_restart() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join(stats::USAGE_FILE);
+        let config = QuotaConfig {
+            max_bytes_per_day_per_peer: 1000,
+            cooldown_sec: 0,
+            ..default_config()
+        };
+
+        let engine = GovernanceEngine::new(config.clone()).with_usage_file(path.clone());
+        engine.record_test_start("peer-a").await;
+        engine.record_bytes("peer-a", 1000).await;
+        drop(engine);
+
+        let restarted = GovernanceEngine::new(config).with_usage_file(path);
+        let usage = restarted.usage_today().await;
+        assert_eq!(usage.total_bytes, 1000);
+        assert_eq!(usage.total_tests, 1);
+        // The restored total still counts against the quota.
+        assert_eq!(
+            restarted.check_allowed("peer-a", &TestType::UdpEcho).await,
+            Err(DenyReason::QuotaExceeded)
+        );
+    }
+
+    /// Concurrent saves are serialized, so the file ends with the final
+    /// counts and no temp file is left behind.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_persists_keep_last_counts() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join(stats::USAGE_FILE);
+        let engine = std::sync::Arc::new(
+            GovernanceEngine::new(default_config()).with_usage_file(path.clone()),
+        );
+
+        let tasks: Vec<_> = (0..32)
+            .map(|_| {
+                let engine = engine.clone();
+                tokio::spawn(async move { engine.record_bytes("peer-a", 10).await })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        assert_eq!(stats::load(&path).unwrap().peer_bytes["peer-a"], 320);
+        assert!(!path.with_extension("json.tmp").exists());
+    }
+
+    #[tokio::test]
+    async fn test_usage_from_earlier_day_discarded() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join(stats::USAGE_FILE);
+        stats::save(
+            &path,
+            &DailyUsage {
+                day_start: utc_day_start(Utc::now()) - chrono::Duration::days(1),
+                peer_bytes: HashMap::from([("peer-a".to_string(), 500)]),
+                peer_tests: HashMap::from([("peer-a".to_string(), 2)]),
+            },
+        )
+        .unwrap();
+
+        let engine = GovernanceEngine::new(default_config()).with_usage_file(path.clone());
+        assert_eq!(engine.usage_today().await.total_bytes, 0);
+
+        // A rollover rewrites the file with the empty new day.
+        engine.record_bytes("peer-b", 10).await;
+        {
+            let mut inner = engine.inner.write().await;
+            inner.day_start -= chrono::Duration::days(1);
+        }
+        assert!(engine.reset_daily_if_needed().await.is_some());
+        let saved = stats::load(&path).unwrap();
+        assert_eq!(saved.day_start, utc_day_start(Utc::now()));
+        assert!(saved.peer_bytes.is_empty());
+    }
 }
@@ -12,6 +12,7 @@ use anyhow::{Context, Result};
 use ed25519_dalek::{SigningKey, VerifyingKey};
 use rand::rngs::OsRng;
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
 use tracing::{debug, info};
 use zeroize::Zeroize;
 
@@ -148,6 +149,63 @@ fn crockford_luhn_validate(encoded_with_check: &str) -> bool {
 // EndpointId
 // ---------------------------------------------------------------------------
 
+/// Number of 4-character Crockford groups kept in a short fingerprint.
+///
+/// Three groups are 60 bits of the public key, plus the full ID's check
+/// digit (5 bits that depend on the whole key). Accidental collisions only
+/// become likely around 2^32 (~4 billion) endpoints, but an attacker who can
+/// grind ~2^65 keys could forge a matching fingerprint, so fingerprints are
+/// for eyeball comparison and never replace the full ID.
+pub const FINGERPRINT_GROUPS: usize = 3;
+
+/// Crockford characters encoding a 32-byte public key (256 bits, 5 per char).
+const ENDPOINT_ID_PAYLOAD_CHARS: usize = 52;
+
+/// Reasons a user-supplied endpoint ID is rejected by [`EndpointId::parse`].
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum EndpointIdError {
+    #[error("endpoint ID is empty")]
+    Empty,
+
+    #[error("endpoint ID must start with \"PP-\"")]
+    MissingPrefix,
+
+    #[error("{0:?} is not a Crockford Base32 character (U is never used)")]
+    InvalidChar(char),
+
+    #[error("endpoint ID has {got} characters after \"PP-\", expected {expected}; is part of it missing?")]
+    WrongLength { got: usize, expected: usize },
+
+    #[error("check digit does not match; a character is probably mistyped")]
+    CheckDigit,
+}
+
+/// Group Crockford `encoded` characters into `PP-XXXX-...-C`.
+fn format_endpoint_id(encoded: &str, check: char) -> String {
+    let mut formatted = String::from("PP");
+    for chunk in encoded.as_bytes().chunks(4) {
+        formatted.push('-');
+        for &b in chunk {
+            formatted.push(b as char);
+        }
+    }
+    formatted.push('-');
+    formatted.push(check);
+    formatted
+}
+
+/// Truncate a formatted endpoint ID to its fingerprint.
+fn short_fingerprint(id: &str) -> String {
+    let parts: Vec<&str> = id.split('-').collect();
+    if parts.len() <= FINGERPRINT_GROUPS + 2 {
+        return id.to_string();
+    }
+    let mut out = parts[..=FINGERPRINT_GROUPS].join("-");
+    out.push('-');
+    out.push_str(parts[parts.len() - 1]);
+    out
+}
+
 /// A human-readable identifier for a PacketParamedic endpoint, derived from
 /// the Ed25519 public key.
 ///
@@ -161,19 +219,46 @@ impl EndpointId {
     pub fn from_public_key_bytes(pk_bytes: &[u8; 32]) -> Self {
         let encoded = crockford_encode(pk_bytes);
         let check = crockford_luhn_check_char(&encoded);
+        EndpointId(format_endpoint_id(&encoded, check))
+    }
 
-        // Group into chunks of 4, then append check digit.
-        let mut formatted = String::from("PP");
-        for chunk in encoded.as_bytes().chunks(4) {
-            formatted.push('-');
-            for &b in chunk {
-                formatted.push(b as char);
-            }
+    /// Parse a user-typed endpoint ID into its canonical form.
+    ///
+    /// Case, whitespace and dash placement are ignored, and the Crockford
+    /// look-alikes `O`, `I` and `L` are read as `0`, `1` and `1`. The input
+    /// must carry the `PP-` prefix, a full-length key and a matching check
+    /// digit.
+    pub fn parse(input: &str) -> std::result::Result<Self, EndpointIdError> {
+        let compact: String = input
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != '-')
+            .map(|c| c.to_ascii_uppercase())
+            .collect();
+        if compact.is_empty() {
+            return Err(EndpointIdError::Empty);
+        }
+        let body = compact
+            .strip_prefix("PP")
+            .ok_or(EndpointIdError::MissingPrefix)?;
+
+        let mut symbols = String::with_capacity(body.len());
+        for c in body.chars() {
+            let value = crockford_value(c).ok_or(EndpointIdError::InvalidChar(c))?;
+            symbols.push(CROCKFORD_ALPHABET[value] as char);
+        }
+        if symbols.len() != ENDPOINT_ID_PAYLOAD_CHARS + 1 {
+            return Err(EndpointIdError::WrongLength {
+                got: symbols.len(),
+                expected: ENDPOINT_ID_PAYLOAD_CHARS + 1,
+            });
+        }
+        if !crockford_luhn_validate(&symbols) {
+            return Err(EndpointIdError::CheckDigit);
         }
-        formatted.push('-');
-        formatted.push(check);
 
-        EndpointId(formatted)
+        let (payload, check) = symbols.split_at(ENDPOINT_ID_PAYLOAD_CHARS);
+        let check = check.chars().next().unwrap_or('0');
+        Ok(EndpointId(format_endpoint_id(payload, check)))
     }
 
     /// Return the raw string representation.
@@ -181,6 +266,16 @@ impl EndpointId {
         &self.0
     }
 
+    /// Short fingerprint for humans to compare: the first
+    /// [`FINGERPRINT_GROUPS`] groups plus the check digit, e.g.
+    /// `PP-5R6Q-2M1K-9D3F-C`.
+    ///
+    /// Only for display and verbal comparison; the full ID stays the
+    /// canonical identifier everywhere it is stored or checked.
+    pub fn short_fingerprint(&self) -> String {
+        short_fingerprint(&self.0)
+    }
+
     /// Validate the Luhn check digit of this endpoint ID.
     pub fn validate(&self) -> bool {
         // Strip the "PP-" prefix, remove dashes, then validate.
@@ -310,6 +405,56 @@ mod tests {
     use super::*;
     use tempfile::TempDir;
 
+    #[test]
+    fn test_parse_endpoint_id() {
+        let id = Identity::generate().endpoint_id();
+        let s = id.to_string();
+        assert_eq!(EndpointId::parse(&s).unwrap(), id);
+
+        // Formatting is not significant.
+        let sloppy = format!("  {}  ", s.to_lowercase().replace('-', " "));
+        assert_eq!(EndpointId::parse(&sloppy).unwrap(), id);
+        let undashed = format!("PP-{}", s[3..].replace('-', ""));
+        assert_eq!(EndpointId::parse(&undashed).unwrap(), id);
+
+        // Look-alikes read as the digits they stand for.
+        let zeroed = EndpointId::from_public_key_bytes(&[0u8; 32]).to_string();
+        assert_eq!(EndpointId::parse(&zeroed.replace('0', "O")).unwrap().as_str(), zeroed);
+    }
+
+    #[test]
+    fn test_parse_endpoint_id_typos() {
+        // PP-0410-6105-...-T: a fixed key keeps the typo cases deterministic.
+        let key: [u8; 32] = std::array::from_fn(|i| i as u8 + 1);
+        let s = EndpointId::from_public_key_bytes(&key).to_string();
+        assert!(s.starts_with("PP-0410-"));
+
+        assert_eq!(EndpointId::parse("  "), Err(EndpointIdError::Empty));
+        assert_eq!(EndpointId::parse(&s[3..]), Err(EndpointIdError::MissingPrefix));
+        assert_eq!(
+            EndpointId::parse(&format!("{}U", &s[..s.len() - 1])),
+            Err(EndpointIdError::InvalidChar('U'))
+        );
+
+        // Truncated paste, and the short fingerprint used as if it were the ID.
+        assert!(matches!(
+            EndpointId::parse(&s[..s.len() - 5]),
+            Err(EndpointIdError::WrongLength { expected: 53, .. })
+        ));
+        let fingerprint = EndpointId::parse(&s).unwrap().short_fingerprint();
+        assert!(matches!(EndpointId::parse(&fingerprint), Err(EndpointIdError::WrongLength { .. })));
+
+        // One mistyped character.
+        let mut chars: Vec<char> = s.chars().collect();
+        chars[5] = if chars[5] == 'X' { 'Y' } else { 'X' };
+        let typo: String = chars.into_iter().collect();
+        assert_eq!(EndpointId::parse(&typo), Err(EndpointIdError::CheckDigit));
+
+        // Two adjacent characters swapped.
+        let swapped = s.replacen("0410", "0140", 1);
+        assert_eq!(EndpointId::parse(&swapped), Err(EndpointIdError::CheckDigit));
+    }
+
     #[test]
     fn test_generate_and_endpoint_id_format() {
         let id = Identity::generate();
@@ -461,4 +606,22 @@ mod tests {
         let deserialized: EndpointId = serde_json::from_str(&json).unwrap();
         assert_eq!(eid, deserialized);
     }
+
+    #[test]
+    fn test_short_fingerprint() {
+        let eid = Identity::generate().endpoint_id();
+        let full = eid.to_string();
+        let fp = eid.short_fingerprint();
+
+        let parts: Vec<&str> = fp.split('-').collect();
+        assert_eq!(parts.len(), 1 + FINGERPRINT_GROUPS + 1);
+        assert_eq!(parts[0], "PP");
+        assert!(full.starts_with(&parts[..=FINGERPRINT_GROUPS].join("-")));
+        // Ends with the full ID's check digit.
+        assert_eq!(full.rsplit('-').next(), parts.last().copied());
+        assert!(fp.len() < full.len());
+
+        // Already-short strings are returned unchanged.
+        assert_eq!(short_fingerprint("PP-ABCD-1"), "PP-ABCD-1");
+    }
 }
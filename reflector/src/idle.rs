@@ -0,0 +1,117 @@
+//! Idle auto-shutdown for ephemeral reflectors.
+//!
+//! A cloud reflector started for one round of tests can set
+//! `network.idle_shutdown_sec`; once it has had no open connections and no
+//! active sessions for that long, [`wait_for_idle`] returns and the server
+//! shuts down.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::session::SessionManager;
+
+/// Longest gap between idle checks.
+const MAX_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Tracks open control connections and when the reflector was last busy.
+#[derive(Debug)]
+pub struct IdleMonitor {
+    connections: AtomicUsize,
+    last_active: Mutex<Instant>,
+}
+
+impl IdleMonitor {
+    pub fn new() -> Self {
+        Self {
+            connections: AtomicUsize::new(0),
+            last_active: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Count a connection as open until the returned guard is dropped.
+    pub fn connection(self: &Arc<Self>) -> ConnectionGuard {
+        self.connections.fetch_add(1, Ordering::SeqCst);
+        self.touch();
+        ConnectionGuard(Arc::clone(self))
+    }
+
+    /// Mark the reflector as busy now.
+    pub fn touch(&self) {
+        *self.last_active.lock().unwrap() = Instant::now();
+    }
+
+    /// How long the reflector has had no open connections (zero while one
+    /// is open).
+    pub fn idle_for(&self) -> Duration {
+        if self.connections.load(Ordering::SeqCst) > 0 {
+            return Duration::ZERO;
+        }
+        self.last_active.lock().unwrap().elapsed()
+    }
+}
+
+/// Keeps a connection counted by its [`IdleMonitor`].
+pub struct ConnectionGuard(Arc<IdleMonitor>);
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.0.touch();
+        self.0.connections.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Resolve once the reflector has had no connections and no active
+/// sessions for `limit`.
+pub async fn wait_for_idle(monitor: &IdleMonitor, session_manager: &SessionManager, limit: Duration) {
+    let interval = (limit / 4).clamp(Duration::from_millis(100), MAX_CHECK_INTERVAL);
+    loop {
+        tokio::time::sleep(interval).await;
+        // A session can outlive its control connection (direct-ephemeral).
+        if session_manager.active_count().await > 0 {
+            monitor.touch();
+        } else if monitor.idle_for() >= limit {
+            return;
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::QuotaConfig;
+    use crate::governance::GovernanceEngine;
+
+    #[tokio::test]
+    async fn test_connections_hold_off_idle() {
+        let monitor = Arc::new(IdleMonitor::new());
+        let guard = monitor.connection();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(monitor.idle_for(), Duration::ZERO);
+
+        drop(guard);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let idle = monitor.idle_for();
+        assert!(idle >= Duration::from_millis(50) && idle < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_idle() {
+        let config = QuotaConfig::default();
+        let governance = Arc::new(GovernanceEngine::new(config.clone()));
+        let manager = SessionManager::new(config, governance, "PP-TEST-0000".into());
+        let monitor = IdleMonitor::new();
+
+        let started = Instant::now();
+        wait_for_idle(&monitor, &manager, Duration::from_millis(300)).await;
+        let waited = started.elapsed();
+        assert!(waited >= Duration::from_millis(300));
+        assert!(waited < Duration::from_secs(2));
+    }
+}
@@ -8,16 +8,26 @@ mod audit;
 mod auth;
 mod cert;
 mod config;
+mod directory;
+#[cfg(test)]
+mod e2e;
 mod engine;
+mod firewall;
 mod governance;
 mod identity;
+mod idle;
+mod metrics;
 mod network;
 mod peer;
+mod quic;
 mod rpc;
 mod selftest;
 mod server;
 mod session;
+mod stats;
 mod tls;
+mod tunnel;
+mod version;
 mod wire;
 
 use std::path::PathBuf;
@@ -63,7 +73,8 @@ enum Commands {
         ttl: String,
 
         /// Accept a code generated by the other side (bidirectional pairing).
-        /// If omitted, a new code is generated locally.
+        /// If omitted, a new code is generated locally. The generating side
+        /// owns the code's expiry: pass the `--ttl` it displays.
         #[arg(long)]
         code: Option<String>,
     },
@@ -77,6 +88,18 @@ enum Commands {
     /// Print the endpoint ID
     ShowId,
 
+    /// Firewall diagnostics for direct_ephemeral data-plane mode
+    Firewall {
+        #[command(subcommand)]
+        action: FirewallAction,
+    },
+
+    /// Manage the persistent allow-list of paired peers
+    Peers {
+        #[command(subcommand)]
+        action: PeersAction,
+    },
+
     /// Run hardware self-test (checks if host can push 1 Gbps)
     SelfTest {
         /// Output results as JSON instead of human-readable table
@@ -85,6 +108,47 @@ enum Commands {
     },
 }
 
+#[derive(Subcommand)]
+enum FirewallAction {
+    /// Check that the data port range is bindable and allowed by local rules
+    Check {
+        /// Output results as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum PeersAction {
+    /// List authorized peers (stored and from the config file)
+    List,
+    /// Authorize a peer by endpoint ID
+    Add {
+        /// Endpoint ID, as printed by `reflector show-id` on the peer
+        endpoint_id: String,
+    },
+    /// Revoke a stored peer
+    Remove {
+        /// Endpoint ID of the peer to revoke
+        endpoint_id: String,
+    },
+    /// Write the stored peers as JSON
+    Export {
+        /// Write to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Add peers from a JSON file written by `peers export`
+    Import {
+        /// Peers file to import
+        file: PathBuf,
+
+        /// Replace the stored peers instead of merging
+        #[arg(long)]
+        replace: bool,
+    },
+}
+
 // ---------------------------------------------------------------------------
 // Main
 // ---------------------------------------------------------------------------
@@ -111,6 +175,8 @@ async fn main() {
         Commands::RotateIdentity => cmd_rotate_identity(config),
         Commands::Status => cmd_status(config),
         Commands::ShowId => cmd_show_id(config),
+        Commands::Firewall { action: FirewallAction::Check { json } } => cmd_firewall_check(config, json),
+        Commands::Peers { action } => cmd_peers(config, action).await,
         Commands::SelfTest { json } => cmd_self_test(config, json).await,
     };
 
@@ -204,13 +270,21 @@ async fn cmd_serve(mut config: ReflectorConfig, bind_override: Option<String>) -
         config.network.listen_address = addr;
     }
 
-    // Spawn HTTP health check server.
     let health_addr = config.network.listen_address_health.clone();
+
+    let server = ReflectorServer::new(config)
+        .await
+        .context("failed to initialize reflector server")?;
+
+    // Spawn HTTP health check server. It shares the maintenance flag so that
+    // load balancers see 503 while the reflector is draining.
+    let maintenance = server.maintenance_flag();
+    let latency = server.latency_metrics();
     tokio::spawn(async move {
         info!(address = %health_addr, "starting health check listener");
         match tokio::net::TcpListener::bind(&health_addr).await {
             Ok(listener) => {
-                let app = engine::health::build_health_router();
+                let app = engine::health::build_health_router(maintenance, latency);
                 if let Err(e) = axum::serve(listener, app).await {
                     error!(error = %e, "health server failed");
                 }
@@ -221,10 +295,6 @@ async fn cmd_serve(mut config: ReflectorConfig, bind_override: Option<String>) -
         }
     });
 
-    let server = ReflectorServer::new(config)
-        .await
-        .context("failed to initialize reflector server")?;
-
     server.run().await
 }
 
@@ -233,6 +303,10 @@ async fn cmd_serve(mut config: ReflectorConfig, bind_override: Option<String>) -
 /// Supports bidirectional pairing:
 /// - Without `--code`: generates a new 8-character code (reflector-initiated)
 /// - With `--code ABCD1234`: accepts a code from the other side (appliance-initiated)
+///
+/// The code is handed to the running server through the pairing file next to
+/// the identity key. The command then counts down until the code is used,
+/// expires, or is cancelled with Ctrl-C.
 async fn cmd_pair(config: ReflectorConfig, ttl: String, code: Option<String>) -> Result<()> {
     let identity_dir = config
         .identity
@@ -249,7 +323,12 @@ async fn cmd_pair(config: ReflectorConfig, ttl: String, code: Option<String>) ->
     let ttl_secs = parse_duration_str(&ttl)
         .context("failed to parse TTL duration")?;
 
-    let auth_gate = auth::AuthGate::new(&config.access);
+    if !config.access.pairing_enabled {
+        warn!("access.pairing_enabled is false; the server will not offer pairing to new peers");
+    }
+
+    let pairing_file = identity_dir.join(auth::PAIRING_FILE);
+    let auth_gate = auth::AuthGate::new(&config.access).with_pairing_file(pairing_file.clone());
 
     let is_external_code = code.is_some();
     let token = match code {
@@ -272,13 +351,42 @@ async fn cmd_pair(config: ReflectorConfig, ttl: String, code: Option<String>) ->
                 .await
         }
     };
+    if !pairing_file.exists() {
+        anyhow::bail!(
+            "failed to hand the pairing code to the server via {}",
+            pairing_file.display()
+        );
+    }
+
+    // The code itself stays out of the audit log.
+    let audit_log = audit::AuditLog::open(
+        config.logging.audit_log_path.clone(),
+        config.logging.audit_hash_chain,
+    )
+    .await
+    .context("failed to open audit log")?;
+    audit_log
+        .log(
+            audit::AuditEntry::new(audit::AuditEventType::PairingEnabled, endpoint_id.to_string())
+                .with_reason(if is_external_code {
+                    "code supplied by peer"
+                } else {
+                    "code generated locally"
+                })
+                .with_params(serde_json::json!({
+                    "ttl_sec": ttl_secs,
+                    "expires_at": token.expires_at,
+                })),
+        )
+        .await?;
+    info!(code = %token.token, expires_at = %token.expires_at, "pairing mode enabled");
 
     println!();
     println!("  Pairing Mode Enabled");
     println!("  ====================");
     println!("  Endpoint ID    : {}", endpoint_id);
+    println!("  Fingerprint    : {}", endpoint_id.short_fingerprint());
     println!("  Pairing Code   : {}", token.token);
-    println!("  Expires In     : {}", ttl);
     println!();
     if is_external_code {
         println!("  Waiting for peer to connect with this code.");
@@ -287,9 +395,45 @@ async fn cmd_pair(config: ReflectorConfig, ttl: String, code: Option<String>) ->
         println!("  Share this code with the peer (appliance or reflector).");
         println!("  The peer enters the code to establish a long-term pairing.");
     }
+    println!("  Press Ctrl-C to cancel.");
     println!();
 
-    Ok(())
+    wait_for_pairing(&pairing_file, std::time::Duration::from_secs(ttl_secs)).await
+}
+
+/// Count down until the server uses the code (and removes the pairing file),
+/// the code expires, or the operator cancels.
+async fn wait_for_pairing(pairing_file: &std::path::Path, ttl: std::time::Duration) -> Result<()> {
+    use std::io::Write;
+
+    let deadline = tokio::time::Instant::now() + ttl;
+    let mut tick = tokio::time::interval(std::time::Duration::from_secs(1));
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+
+    loop {
+        tokio::select! {
+            _ = tick.tick() => {}
+            _ = &mut ctrl_c => {
+                let _ = std::fs::remove_file(pairing_file);
+                println!("\n  Pairing cancelled; the code is no longer valid.");
+                return Ok(());
+            }
+        }
+
+        if !pairing_file.exists() {
+            println!("\r  Peer paired.                    ");
+            return Ok(());
+        }
+        let left = deadline.saturating_duration_since(tokio::time::Instant::now());
+        if left.is_zero() {
+            let _ = std::fs::remove_file(pairing_file);
+            println!("\r  Pairing code expired.           ");
+            return Ok(());
+        }
+        print!("\r  Expires In     : {}   ", format_countdown(left.as_secs()));
+        let _ = std::io::stdout().flush();
+    }
 }
 
 /// `rotate-identity` -- Generate a new Ed25519 keypair.
@@ -350,15 +494,19 @@ fn cmd_status(config: ReflectorConfig) -> Result<()> {
     let identity = Identity::load(&identity_path)
         .context("failed to load identity")?;
 
-    let net_position = network::resolve_position(&config.network.deployment_mode);
+    let net_position = network::resolve_report(&config.network.deployment_mode);
 
     println!();
     println!("  Reflector Status");
     println!("  ================");
     println!("  Endpoint ID   : {}", identity.endpoint_id());
+    println!("  Fingerprint   : {}", identity.endpoint_id().short_fingerprint());
     println!("  Bind Address  : {}", config.network.listen_address);
     println!("  Mode          : {:?}", config.network.mode);
-    println!("  Network       : {}", net_position);
+    match net_position.reachable_via {
+        Some(family) => println!("  Network       : {} (reachable via {})", net_position.position, family),
+        None => println!("  Network       : {}", net_position.position),
+    }
     println!("  Data Dir      : {}", identity_dir.display());
     println!();
 
@@ -387,6 +535,142 @@ fn cmd_show_id(config: ReflectorConfig) -> Result<()> {
     Ok(())
 }
 
+/// `peers` -- Edit the peer store (`peers.json` next to the identity key).
+///
+/// A running server applies the changes when it receives SIGHUP
+/// (`systemctl reload reflector`), without a restart.
+async fn cmd_peers(config: ReflectorConfig, action: PeersAction) -> Result<()> {
+    let identity_dir = config
+        .identity
+        .private_key_path
+        .parent()
+        .unwrap_or(std::path::Path::new("/var/lib/reflector"));
+    let peers_file = identity_dir.join(peer::PEERS_FILE);
+    let mut stored = peer::AuthorizedPeers::load(&peers_file)?;
+
+    let changes: Vec<(audit::AuditEventType, peer::PeerId)> = match action {
+        PeersAction::List => {
+            println!();
+            println!("  Authorized Peers");
+            println!("  ================");
+            for peer in stored.sorted() {
+                println!("  {}", peer);
+            }
+            for id in &config.access.authorized_peers {
+                println!("  {}  (config file)", id);
+            }
+            if stored.is_empty() && config.access.authorized_peers.is_empty() {
+                println!("  (none)");
+            }
+            println!();
+            return Ok(());
+        }
+        PeersAction::Export { output } => {
+            let json = serde_json::to_string_pretty(&serde_json::json!({ "peers": stored.sorted() }))?;
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, json + "\n")
+                        .with_context(|| format!("failed to write {}", path.display()))?;
+                    println!("  Exported {} peer(s) to {}", stored.len(), path.display());
+                }
+                None => println!("{json}"),
+            }
+            return Ok(());
+        }
+        PeersAction::Add { endpoint_id } => {
+            let peer_id = peer::PeerId::validated(&endpoint_id)?;
+            if stored.is_authorized(&peer_id) {
+                println!("  {} is already authorized", peer_id);
+                return Ok(());
+            }
+            stored.add(peer_id.clone());
+            vec![(audit::AuditEventType::PeerAdded, peer_id)]
+        }
+        PeersAction::Remove { endpoint_id } => {
+            // Canonical form if valid; anything else is matched as typed.
+            let peer_id = peer::PeerId::validated(&endpoint_id)
+                .unwrap_or_else(|_| peer::PeerId::new(endpoint_id.trim()));
+            if !stored.remove(&peer_id) {
+                if config.access.authorized_peers.iter().any(|id| id == peer_id.as_str()) {
+                    anyhow::bail!("{} is listed in the config file (access.authorized_peers); remove it there", peer_id);
+                }
+                anyhow::bail!("{} is not a stored peer", peer_id);
+            }
+            vec![(audit::AuditEventType::PeerRemoved, peer_id)]
+        }
+        PeersAction::Import { file, replace } => {
+            let raw = std::fs::read_to_string(&file)
+                .with_context(|| format!("failed to read {}", file.display()))?;
+            let imported: ImportFile = serde_json::from_str(&raw)
+                .with_context(|| format!("failed to parse peers file {}", file.display()))?;
+            // All or nothing: one bad ID rejects the whole file.
+            let imported = imported
+                .peers
+                .iter()
+                .map(|id| peer::PeerId::validated(id))
+                .collect::<Result<Vec<_>, _>>()
+                .with_context(|| format!("nothing imported from {}", file.display()))?;
+
+            let mut changes = Vec::new();
+            if replace {
+                for peer_id in stored.sorted() {
+                    if !imported.contains(peer_id) {
+                        changes.push((audit::AuditEventType::PeerRemoved, peer_id.clone()));
+                    }
+                }
+                for (_, peer_id) in &changes {
+                    stored.remove(peer_id);
+                }
+            }
+            for peer_id in imported {
+                if !stored.is_authorized(&peer_id) {
+                    stored.add(peer_id.clone());
+                    changes.push((audit::AuditEventType::PeerAdded, peer_id));
+                }
+            }
+            changes
+        }
+    };
+
+    if changes.is_empty() {
+        println!("  No changes; the peer store is up to date.");
+        return Ok(());
+    }
+    stored.save(&peers_file)?;
+
+    let endpoint_id = Identity::load_or_generate(&identity_dir.join("identity.key"))
+        .context("failed to load identity")?
+        .endpoint_id()
+        .to_string();
+    let audit_log = audit::AuditLog::open(
+        config.logging.audit_log_path.clone(),
+        config.logging.audit_hash_chain,
+    )
+    .await
+    .context("failed to open audit log")?;
+    for (event, peer_id) in &changes {
+        audit_log
+            .log(
+                audit::AuditEntry::new(event.clone(), endpoint_id.clone())
+                    .with_peer_id(peer_id.as_str())
+                    .with_reason("peers command"),
+            )
+            .await?;
+        let verb = if *event == audit::AuditEventType::PeerAdded { "Added" } else { "Removed" };
+        println!("  {verb} {peer_id}");
+    }
+    println!("  {} peer(s) stored in {}", stored.len(), peers_file.display());
+    println!("  Send SIGHUP to a running server to apply (systemctl reload reflector).");
+
+    Ok(())
+}
+
+/// A file written by `peers export`.
+#[derive(serde::Deserialize)]
+struct ImportFile {
+    peers: Vec<String>,
+}
+
 /// `self-test` -- Run hardware self-test to check 1 Gbps readiness.
 async fn cmd_self_test(config: ReflectorConfig, json: bool) -> Result<()> {
     let report = selftest::run(&config).await;
@@ -407,10 +691,83 @@ async fn cmd_self_test(config: ReflectorConfig, json: bool) -> Result<()> {
     Ok(())
 }
 
+/// `firewall check` -- Diagnose whether direct-mode data ports are reachable.
+fn cmd_firewall_check(config: ReflectorConfig, json: bool) -> Result<()> {
+    let start = config.network.data_port_range_start;
+    let end = config.network.data_port_range_end;
+    if end < start {
+        anyhow::bail!("invalid data port range {}-{}", start, end);
+    }
+    let report = firewall::check(start, end);
+
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&report).context("failed to serialize firewall report")?
+        );
+    } else {
+        let describe = |v: &firewall::RuleVerdict| match v {
+            firewall::RuleVerdict::OpenByPolicy => "OK     input policy accepts".to_string(),
+            firewall::RuleVerdict::Allowed => "OK     accept rule covers the range".to_string(),
+            firewall::RuleVerdict::Partial { uncovered, first_uncovered } => format!(
+                "WARN   {} port(s) not allowed, first {}",
+                uncovered, first_uncovered
+            ),
+            firewall::RuleVerdict::Blocked => "FAIL   input policy drops and no rule allows the range".to_string(),
+            firewall::RuleVerdict::Unknown { reason } => format!("SKIP   {}", reason),
+        };
+
+        println!();
+        println!("  Reflector Firewall Check");
+        println!("  ========================");
+        println!("  Data ports  : {}-{} (tcp)", start, end);
+        println!("  Mode        : {:?}", config.network.mode);
+        println!();
+        if report.ports_in_use.is_empty() {
+            println!("  Local bind  : OK     all ports free");
+        } else {
+            println!(
+                "  Local bind  : WARN   {} port(s) already in use: {:?}",
+                report.ports_in_use.len(),
+                report.ports_in_use
+            );
+        }
+        println!("  nftables    : {}", describe(&report.nftables));
+        println!("  iptables    : {}", describe(&report.iptables));
+        println!();
+        if matches!(config.network.mode, config::DataPlaneMode::Tunneled) {
+            println!("  Note: mode is tunneled; the data port range is only needed for direct_ephemeral.");
+        }
+        if report.blocked() {
+            println!("  Peers will likely fail to reach the data ports. Allow inbound TCP {}-{},", start, end);
+            println!("  e.g. `nft add rule inet filter input tcp dport {}-{} accept`", start, end);
+            println!("  or `ufw allow {}:{}/tcp`.", start, end);
+        }
+        println!("  Router / cloud firewalls are not visible from here; verify from outside with");
+        println!("  `nc -zv <public-ip> {}` while a test session is running.", start);
+        println!();
+    }
+
+    if report.blocked() {
+        std::process::exit(2);
+    }
+    Ok(())
+}
+
 // ---------------------------------------------------------------------------
 // Helpers
 // ---------------------------------------------------------------------------
 
+/// `m:ss` (or `h:mm:ss`) for the pairing countdown.
+fn format_countdown(secs: u64) -> String {
+    let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if h > 0 {
+        format!("{}:{:02}:{:02}", h, m, s)
+    } else {
+        format!("{}:{:02}", m, s)
+    }
+}
+
 /// Parse a human-readable duration string like "10m", "1h", "30s" into seconds.
 fn parse_duration_str(s: &str) -> Result<u64> {
     let s = s.trim();
@@ -518,6 +875,19 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_cli_parse_peers() {
+        let cli = Cli::try_parse_from(["reflector", "peers", "import", "peers.json", "--replace"]).unwrap();
+        match cli.command {
+            Commands::Peers { action: PeersAction::Import { file, replace } } => {
+                assert_eq!(file, PathBuf::from("peers.json"));
+                assert!(replace);
+            }
+            _ => panic!("expected Peers Import"),
+        }
+        assert!(Cli::try_parse_from(["reflector", "peers", "add"]).is_err());
+    }
+
     #[test]
     fn test_cli_parse_pair() {
         let cli = Cli::try_parse_from(["reflector", "pair"]).unwrap();
@@ -542,6 +912,22 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_cli_parse_pair_with_seconds_ttl() {
+        let cli = Cli::try_parse_from(["reflector", "pair", "--ttl", "300"]).unwrap();
+        match cli.command {
+            Commands::Pair { ttl, .. } => assert_eq!(parse_duration_str(&ttl).unwrap(), 300),
+            _ => panic!("expected Pair"),
+        }
+    }
+
+    #[test]
+    fn test_format_countdown() {
+        assert_eq!(format_countdown(299), "4:59");
+        assert_eq!(format_countdown(5), "0:05");
+        assert_eq!(format_countdown(3725), "1:02:05");
+    }
+
     #[test]
     fn test_cli_parse_pair_with_code() {
         let cli = Cli::try_parse_from(["reflector", "pair", "--code", "ABCD1234"]).unwrap();
@@ -0,0 +1,132 @@
+//! Connection setup latency metrics for the PacketParamedic Reflector.
+//!
+//! Records how long each TLS handshake took and how long each
+//! `SessionRequest` took to turn into a `SessionGrant`. Under load the two
+//! tell operators whether crypto or port allocation / engine start-up is the
+//! bottleneck. The most recent samples are kept in a fixed-size window and
+//! reported as p50/p95 on the health endpoint.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Serialize;
+
+/// Samples kept per metric; older ones fall out of the percentiles.
+pub const WINDOW: usize = 1024;
+
+/// Aggregates over the current window of one metric, in milliseconds.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LatencySummary {
+    pub samples: usize,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+}
+
+/// Snapshot of all setup latency metrics.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LatencySnapshot {
+    /// TLS (or QUIC) handshake duration.
+    pub handshake: Option<LatencySummary>,
+    /// `SessionRequest` received to `SessionGrant` sent.
+    pub session_setup: Option<LatencySummary>,
+}
+
+/// Rolling windows of setup latencies, shared by all connections.
+#[derive(Debug, Default)]
+pub struct LatencyMetrics {
+    handshake: Mutex<VecDeque<Duration>>,
+    session_setup: Mutex<VecDeque<Duration>>,
+}
+
+impl LatencyMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a completed handshake.
+    pub fn record_handshake(&self, elapsed: Duration) {
+        push(&self.handshake, elapsed);
+    }
+
+    /// Record a granted session's setup time.
+    pub fn record_session_setup(&self, elapsed: Duration) {
+        push(&self.session_setup, elapsed);
+    }
+
+    /// Current p50/p95 of each metric (`None` until it has a sample).
+    pub fn snapshot(&self) -> LatencySnapshot {
+        LatencySnapshot {
+            handshake: summarize(&self.handshake),
+            session_setup: summarize(&self.session_setup),
+        }
+    }
+}
+
+fn push(window: &Mutex<VecDeque<Duration>>, elapsed: Duration) {
+    let mut window = window.lock().unwrap();
+    if window.len() == WINDOW {
+        window.pop_front();
+    }
+    window.push_back(elapsed);
+}
+
+fn summarize(window: &Mutex<VecDeque<Duration>>) -> Option<LatencySummary> {
+    let mut sorted: Vec<Duration> = window.lock().unwrap().iter().copied().collect();
+    if sorted.is_empty() {
+        return None;
+    }
+    sorted.sort_unstable();
+    // Nearest-rank percentile.
+    let pct = |p: usize| {
+        let rank = (sorted.len() * p).div_ceil(100).max(1);
+        sorted[rank - 1].as_secs_f64() * 1000.0
+    };
+    Some(LatencySummary {
+        samples: sorted.len(),
+        p50_ms: pct(50),
+        p95_ms: pct(95),
+    })
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentiles() {
+        let metrics = LatencyMetrics::new();
+        assert_eq!(metrics.snapshot().handshake, None);
+
+        for ms in 1..=100 {
+            metrics.record_handshake(Duration::from_millis(ms));
+        }
+        metrics.record_session_setup(Duration::from_millis(7));
+
+        let snap = metrics.snapshot();
+        let handshake = snap.handshake.unwrap();
+        assert_eq!(handshake.samples, 100);
+        assert_eq!(handshake.p50_ms, 50.0);
+        assert_eq!(handshake.p95_ms, 95.0);
+        let setup = snap.session_setup.unwrap();
+        assert_eq!((setup.samples, setup.p50_ms, setup.p95_ms), (1, 7.0, 7.0));
+    }
+
+    #[test]
+    fn test_window_drops_oldest() {
+        let metrics = LatencyMetrics::new();
+        for _ in 0..WINDOW {
+            metrics.record_session_setup(Duration::from_secs(10));
+        }
+        for _ in 0..WINDOW {
+            metrics.record_session_setup(Duration::from_millis(1));
+        }
+        let setup = metrics.snapshot().session_setup.unwrap();
+        assert_eq!(setup.samples, WINDOW);
+        assert_eq!(setup.p95_ms, 1.0);
+    }
+}
@@ -4,6 +4,13 @@
 //! reflector is WAN-facing (has at least one public IP), LAN-only (all IPs are
 //! private/link-local/CGNAT), or a hybrid of both.
 //!
+//! Either address family counts: a host behind CGNAT on IPv4 with a public
+//! IPv6 address is WAN-facing over IPv6, which dual-stack Paramedics can use.
+//! [`PositionReport::reachable_via`] records which families are public.
+//! Link-local addresses never make a host hybrid (every IPv6 interface has
+//! one), and neither does a CGNAT address next to a public one: it is the
+//! IPv4 side of the same uplink, not a separate LAN.
+//!
 //! IP classification follows:
 //! - RFC 1918: 10.0.0.0/8, 172.16.0.0/12, 192.168.0.0/16  → Private
 //! - RFC 6598: 100.64.0.0/10 (CGNAT)                        → Cgnat
@@ -66,6 +73,50 @@ impl std::fmt::Display for NetworkPosition {
     }
 }
 
+/// Address families over which the reflector has a public address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReachableFamily {
+    Ipv4,
+    Ipv6,
+    DualStack,
+}
+
+impl ReachableFamily {
+    /// Short string for wire protocol and display.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Ipv4 => "ipv4",
+            Self::Ipv6 => "ipv6",
+            Self::DualStack => "dual_stack",
+        }
+    }
+
+    fn from_flags(v4: bool, v6: bool) -> Option<Self> {
+        match (v4, v6) {
+            (true, true) => Some(Self::DualStack),
+            (true, false) => Some(Self::Ipv4),
+            (false, true) => Some(Self::Ipv6),
+            (false, false) => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ReachableFamily {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A network position together with the families it is reachable over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PositionReport {
+    pub position: NetworkPosition,
+    /// `None` when no public address was found, or when the position was set
+    /// manually in the config.
+    pub reachable_via: Option<ReachableFamily>,
+}
+
 // ---------------------------------------------------------------------------
 // IpClass
 // ---------------------------------------------------------------------------
@@ -299,20 +350,45 @@ fn get_interface_addrs(name: &str) -> Option<Vec<IpAddr>> {
     }
 }
 
-/// Detect the network position of this host based on its interfaces.
-pub fn detect_network_position() -> NetworkPosition {
+/// Classify a set of interface addresses into a network position.
+pub fn classify_position(interfaces: &[InterfaceInfo]) -> PositionReport {
+    let public_v4 = interfaces
+        .iter()
+        .any(|i| i.class == IpClass::Public && i.ip.is_ipv4());
+    let public_v6 = interfaces
+        .iter()
+        .any(|i| i.class == IpClass::Public && i.ip.is_ipv6());
+    let has_lan = interfaces.iter().any(|i| i.class == IpClass::Private);
+    let has_non_public = interfaces
+        .iter()
+        .any(|i| matches!(i.class, IpClass::Private | IpClass::Cgnat | IpClass::LinkLocal));
+
+    let reachable_via = ReachableFamily::from_flags(public_v4, public_v6);
+    let position = match (reachable_via.is_some(), has_lan, has_non_public) {
+        (true, true, _) => NetworkPosition::Hybrid,
+        (true, false, _) => NetworkPosition::WanFacing,
+        (false, _, true) => NetworkPosition::LanOnly,
+        (false, _, false) => NetworkPosition::Unknown,
+    };
+    PositionReport {
+        position,
+        reachable_via,
+    }
+}
+
+/// Detect the network position of this host based on its interfaces, and
+/// the address families it is publicly reachable over.
+pub fn detect_position_report() -> PositionReport {
     let interfaces = enumerate_interfaces();
 
     if interfaces.is_empty() {
         info!("no non-loopback interfaces found");
-        return NetworkPosition::Unknown;
+        return PositionReport {
+            position: NetworkPosition::Unknown,
+            reachable_via: None,
+        };
     }
 
-    let has_public = interfaces.iter().any(|i| i.class == IpClass::Public);
-    let has_private = interfaces
-        .iter()
-        .any(|i| matches!(i.class, IpClass::Private | IpClass::Cgnat | IpClass::LinkLocal));
-
     for iface in &interfaces {
         debug!(
             name = %iface.name,
@@ -322,30 +398,33 @@ pub fn detect_network_position() -> NetworkPosition {
         );
     }
 
-    let position = match (has_public, has_private) {
-        (true, true) => NetworkPosition::Hybrid,
-        (true, false) => NetworkPosition::WanFacing,
-        (false, true) => NetworkPosition::LanOnly,
-        (false, false) => NetworkPosition::Unknown,
-    };
-
-    info!(position = %position, interfaces = interfaces.len(), "network position detected");
-    position
+    let report = classify_position(&interfaces);
+    info!(
+        position = %report.position,
+        reachable_via = report.reachable_via.map(|f| f.as_str()).unwrap_or("none"),
+        interfaces = interfaces.len(),
+        "network position detected"
+    );
+    report
 }
 
 /// Resolve the effective network position from config or auto-detection.
 ///
 /// If `deployment_mode` is `"auto"`, runs detection. Otherwise parses the
-/// string as a position name.
-pub fn resolve_position(deployment_mode: &str) -> NetworkPosition {
+/// string as a position name, which carries no reachable families.
+pub fn resolve_report(deployment_mode: &str) -> PositionReport {
+    let manual = |position| PositionReport {
+        position,
+        reachable_via: None,
+    };
     match deployment_mode.to_lowercase().as_str() {
-        "auto" | "" => detect_network_position(),
-        "wan" => NetworkPosition::WanFacing,
-        "lan" => NetworkPosition::LanOnly,
-        "hybrid" => NetworkPosition::Hybrid,
+        "auto" | "" => detect_position_report(),
+        "wan" => manual(NetworkPosition::WanFacing),
+        "lan" => manual(NetworkPosition::LanOnly),
+        "hybrid" => manual(NetworkPosition::Hybrid),
         _ => {
             info!(mode = deployment_mode, "unknown deployment mode, falling back to auto-detect");
-            detect_network_position()
+            detect_position_report()
         }
     }
 }
@@ -451,17 +530,17 @@ mod tests {
 
     #[test]
     fn test_resolve_position_manual() {
-        assert_eq!(resolve_position("wan"), NetworkPosition::WanFacing);
-        assert_eq!(resolve_position("lan"), NetworkPosition::LanOnly);
-        assert_eq!(resolve_position("hybrid"), NetworkPosition::Hybrid);
-        assert_eq!(resolve_position("WAN"), NetworkPosition::WanFacing);
-        assert_eq!(resolve_position("LAN"), NetworkPosition::LanOnly);
+        assert_eq!(resolve_report("wan").position, NetworkPosition::WanFacing);
+        assert_eq!(resolve_report("lan").position, NetworkPosition::LanOnly);
+        assert_eq!(resolve_report("hybrid").position, NetworkPosition::Hybrid);
+        assert_eq!(resolve_report("WAN").position, NetworkPosition::WanFacing);
+        assert_eq!(resolve_report("LAN").position, NetworkPosition::LanOnly);
     }
 
     #[test]
     fn test_resolve_position_auto() {
         // Auto should return some valid position (depends on host).
-        let pos = resolve_position("auto");
+        let pos = resolve_report("auto").position;
         assert!(matches!(
             pos,
             NetworkPosition::WanFacing
@@ -479,9 +558,60 @@ mod tests {
         assert_eq!(NetworkPosition::Unknown.as_str(), "unknown");
     }
 
+    fn iface(ip: &str) -> InterfaceInfo {
+        let ip: IpAddr = ip.parse().unwrap();
+        InterfaceInfo {
+            name: "eth0".into(),
+            ip,
+            class: classify_ip(&ip),
+        }
+    }
+
+    #[test]
+    fn test_cgnat_v4_with_public_v6_is_wan_over_v6() {
+        let report = classify_position(&[
+            iface("100.72.10.5"),
+            iface("2001:db8:1::5"),
+            iface("fe80::1"),
+        ]);
+        assert_eq!(report.position, NetworkPosition::WanFacing);
+        assert_eq!(report.reachable_via, Some(ReachableFamily::Ipv6));
+    }
+
+    #[test]
+    fn test_classify_position() {
+        // CGNAT only: not reachable from outside.
+        let report = classify_position(&[iface("100.72.10.5"), iface("fe80::1")]);
+        assert_eq!(report.position, NetworkPosition::LanOnly);
+        assert_eq!(report.reachable_via, None);
+
+        // Public v4 plus the usual IPv6 link-local is still WAN-facing.
+        let report = classify_position(&[iface("203.0.113.7"), iface("fe80::1")]);
+        assert_eq!(report.position, NetworkPosition::WanFacing);
+        assert_eq!(report.reachable_via, Some(ReachableFamily::Ipv4));
+
+        // A real LAN next to a public address is hybrid.
+        let report = classify_position(&[
+            iface("203.0.113.7"),
+            iface("2001:db8:1::5"),
+            iface("192.168.1.2"),
+        ]);
+        assert_eq!(report.position, NetworkPosition::Hybrid);
+        assert_eq!(report.reachable_via, Some(ReachableFamily::DualStack));
+
+        assert_eq!(classify_position(&[]).position, NetworkPosition::Unknown);
+    }
+
+    #[test]
+    fn test_manual_position_has_no_family() {
+        let report = resolve_report("wan");
+        assert_eq!(report.position, NetworkPosition::WanFacing);
+        assert_eq!(report.reachable_via, None);
+    }
+
     #[test]
     fn test_detect_returns_valid_position() {
-        let pos = detect_network_position();
+        let pos = detect_position_report().position;
         // Just verify it doesn't panic and returns a valid variant.
         let _ = pos.as_str();
     }
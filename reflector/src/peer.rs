@@ -7,11 +7,18 @@
 
 use std::collections::HashSet;
 use std::fmt;
+use std::path::Path;
 
+use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use crate::cert;
+use crate::identity::{EndpointId, EndpointIdError};
+
+/// File, next to the identity key, holding peers enrolled by pairing or the
+/// `peers` command (on top of `access.authorized_peers` in the config).
+pub const PEERS_FILE: &str = "peers.json";
 
 // ---------------------------------------------------------------------------
 // Errors
@@ -28,6 +35,10 @@ pub enum PeerError {
     /// The peer is not in the authorized peers set.
     #[error("peer {0} is not authorized")]
     Unauthorized(PeerId),
+
+    /// A user-supplied endpoint ID failed validation.
+    #[error("invalid endpoint ID {0:?}: {1}")]
+    InvalidEndpointId(String, EndpointIdError),
 }
 
 // ---------------------------------------------------------------------------
@@ -59,6 +70,14 @@ impl PeerId {
         Ok(PeerId(id))
     }
 
+    /// Build a `PeerId` from a user-supplied endpoint ID, in canonical
+    /// form; see [`EndpointId::parse`].
+    pub fn validated(id: &str) -> Result<Self, PeerError> {
+        EndpointId::parse(id)
+            .map(|id| PeerId(id.to_string()))
+            .map_err(|e| PeerError::InvalidEndpointId(id.trim().to_string(), e))
+    }
+
     /// Return the inner endpoint ID string.
     pub fn as_str(&self) -> &str {
         &self.0
@@ -133,6 +152,36 @@ impl AuthorizedPeers {
     pub fn iter(&self) -> impl Iterator<Item = &PeerId> {
         self.peers.iter()
     }
+
+    /// Peer IDs in sorted order, for stable listings and files.
+    pub fn sorted(&self) -> Vec<&PeerId> {
+        let mut peers: Vec<&PeerId> = self.peers.iter().collect();
+        peers.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        peers
+    }
+
+    /// Load a peers file (`{"peers": [...]}`); a missing file is empty.
+    pub fn load(path: &Path) -> Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(raw) => serde_json::from_str(&raw)
+                .with_context(|| format!("failed to parse peers file {}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::new()),
+            Err(e) => Err(e).with_context(|| format!("failed to read peers file {}", path.display())),
+        }
+    }
+
+    /// Write the set to `path`, sorted, replacing it atomically so a running
+    /// server never reads a half-written file.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create directory {}", parent.display()))?;
+        }
+        let json = serde_json::to_vec_pretty(&serde_json::json!({ "peers": self.sorted() }))?;
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, json).with_context(|| format!("failed to write {}", tmp.display()))?;
+        std::fs::rename(&tmp, path).with_context(|| format!("failed to replace {}", path.display()))
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -226,6 +275,34 @@ mod tests {
         assert_ne!(a, c);
     }
 
+    #[test]
+    fn test_peer_id_validated() {
+        let id = Identity::generate().endpoint_id().to_string();
+        assert_eq!(PeerId::validated(&format!(" {id} ")).unwrap().as_str(), id);
+        assert_eq!(PeerId::validated(&id.to_lowercase()).unwrap().as_str(), id);
+        assert!(matches!(
+            PeerId::validated("PP-AAAA-BBBB-CCCC-0"),
+            Err(PeerError::InvalidEndpointId(_, EndpointIdError::WrongLength { .. }))
+        ));
+    }
+
+    #[test]
+    fn test_peers_file_roundtrip() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join(PEERS_FILE);
+        assert!(AuthorizedPeers::load(&path).unwrap().is_empty());
+
+        let mut auth = AuthorizedPeers::new();
+        auth.add(PeerId::new("PP-DDDD-EEEE-FFFF-1"));
+        auth.add(PeerId::new("PP-AAAA-BBBB-CCCC-0"));
+        auth.save(&path).unwrap();
+
+        let raw = std::fs::read_to_string(&path).unwrap();
+        assert!(raw.find("PP-AAAA").unwrap() < raw.find("PP-DDDD").unwrap());
+        let loaded = AuthorizedPeers::load(&path).unwrap();
+        assert_eq!(loaded.sorted(), auth.sorted());
+    }
+
     #[test]
     fn test_peer_id_from_conversions() {
         let from_string: PeerId = String::from("PP-1234-5678-9ABC-D").into();
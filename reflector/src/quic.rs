@@ -0,0 +1,122 @@
+//! QUIC control-plane transport.
+//!
+//! Carries the same length-prefixed [`LinkMessage`](crate::rpc::LinkMessage)
+//! framing as the TCP listener, on the first bidirectional stream of each
+//! connection. The TLS side is the TCP listener's mTLS 1.3 config unchanged
+//! (certificate, client-cert requirement, ALPN). 0-RTT stays disabled:
+//! session requests are not replay-safe.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use quinn::crypto::rustls::{HandshakeData, QuicServerConfig};
+use rustls_pki_types::CertificateDer;
+
+/// A silent connection is dropped after this long.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Keep-alive interval, well inside [`IDLE_TIMEOUT`], so the control
+/// connection survives a long test with no control traffic.
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// A QUIC bidirectional stream as a single `AsyncRead + AsyncWrite`.
+pub type QuicStream = tokio::io::Join<quinn::RecvStream, quinn::SendStream>;
+
+/// Bind a QUIC server endpoint on `addr` (UDP) using `tls`.
+pub fn server_endpoint(tls: rustls::ServerConfig, addr: SocketAddr) -> Result<quinn::Endpoint> {
+    let crypto = QuicServerConfig::try_from(tls).context("TLS config is not usable for QUIC")?;
+    let mut server = quinn::ServerConfig::with_crypto(Arc::new(crypto));
+
+    let mut transport = quinn::TransportConfig::default();
+    transport.max_idle_timeout(Some(IDLE_TIMEOUT.try_into()?));
+    transport.keep_alive_interval(Some(KEEP_ALIVE_INTERVAL));
+    // One control stream per connection; nothing else is accepted.
+    transport.max_concurrent_bidi_streams(1u32.into());
+    transport.max_concurrent_uni_streams(0u32.into());
+    server.transport_config(Arc::new(transport));
+
+    quinn::Endpoint::server(server, addr)
+        .with_context(|| format!("failed to bind QUIC endpoint on {}", addr))
+}
+
+/// Accept the control stream the client opens after the handshake.
+pub async fn accept_control_stream(conn: &quinn::Connection) -> Result<QuicStream> {
+    let (send, recv) = conn.accept_bi().await.context("no control stream opened")?;
+    Ok(tokio::io::join(recv, send))
+}
+
+/// Negotiated ALPN and the client's certificate chain.
+pub fn peer_handshake(
+    conn: &quinn::Connection,
+) -> (Option<Vec<u8>>, Option<Vec<CertificateDer<'static>>>) {
+    let alpn = conn
+        .handshake_data()
+        .and_then(|d| d.downcast::<HandshakeData>().ok())
+        .and_then(|d| d.protocol);
+    let certs = conn
+        .peer_identity()
+        .and_then(|i| i.downcast::<Vec<CertificateDer<'static>>>().ok())
+        .map(|c| *c);
+    (alpn, certs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tls::{build_client_config, build_server_config, ALPN_PP_LINK};
+    use quinn::crypto::rustls::QuicClientConfig;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn client_endpoint(tls: rustls::ClientConfig, bind: SocketAddr) -> Result<quinn::Endpoint> {
+        let crypto = QuicClientConfig::try_from(tls)?;
+        let mut endpoint = quinn::Endpoint::client(bind)?;
+        endpoint.set_default_client_config(quinn::ClientConfig::new(Arc::new(crypto)));
+        Ok(endpoint)
+    }
+
+    fn generate_test_cert() -> (Vec<u8>, Vec<u8>) {
+        let key_pair = rcgen::KeyPair::generate_for(&rcgen::PKCS_ED25519).unwrap();
+        let params = rcgen::CertificateParams::new(vec!["localhost".to_string()]).unwrap();
+        let cert = params.self_signed(&key_pair).unwrap();
+        (cert.der().to_vec(), key_pair.serialize_der().to_vec())
+    }
+
+    #[tokio::test]
+    async fn test_quic_control_stream_roundtrip() {
+        let (server_cert, server_key) = generate_test_cert();
+        let (client_cert, client_key) = generate_test_cert();
+        let server_tls = build_server_config(server_cert, server_key, ALPN_PP_LINK).unwrap();
+        let client_tls = build_client_config(client_cert, client_key, ALPN_PP_LINK).unwrap();
+
+        let server = server_endpoint(server_tls, "127.0.0.1:0".parse().unwrap()).unwrap();
+        let server_addr = server.local_addr().unwrap();
+        let client = client_endpoint(client_tls, "127.0.0.1:0".parse().unwrap()).unwrap();
+
+        let accept = tokio::spawn(async move {
+            let conn = server.accept().await.unwrap().await.unwrap();
+            let (alpn, certs) = peer_handshake(&conn);
+            let mut stream = accept_control_stream(&conn).await.unwrap();
+            let mut buf = [0u8; 4];
+            stream.read_exact(&mut buf).await.unwrap();
+            stream.write_all(b"pong").await.unwrap();
+            stream.flush().await.unwrap();
+            // Keep the connection open until the client has read the reply.
+            let _ = stream.read(&mut buf).await;
+            (alpn, certs.map(|c| c.len()), buf)
+        });
+
+        let conn = client.connect(server_addr, "reflector").unwrap().await.unwrap();
+        let (mut send, mut recv) = conn.open_bi().await.unwrap();
+        send.write_all(b"ping").await.unwrap();
+        let mut reply = [0u8; 4];
+        recv.read_exact(&mut reply).await.unwrap();
+        assert_eq!(&reply, b"pong");
+        send.finish().unwrap();
+
+        let (alpn, cert_count, _) = accept.await.unwrap();
+        assert_eq!(alpn.as_deref(), Some(ALPN_PP_LINK.as_bytes()));
+        assert_eq!(cert_count, Some(1));
+    }
+}
@@ -35,6 +35,10 @@ pub enum MessagePayload {
     SessionGrant(SessionGrant),
     SessionDeny(SessionDeny),
     SessionClose(SessionClose),
+    SessionSummary(SessionSummary),
+
+    // -- Data-plane tunnel --
+    TunnelOpen(TunnelOpen),
 
     // -- Status --
     GetStatus,
@@ -48,6 +52,16 @@ pub enum MessagePayload {
     GetPathMeta,
     PathMeta(PathMeta),
 
+    // -- Admin --
+    GetUsageSummary,
+    UsageSummary(UsageSummary),
+    /// Abort a running test (protocol 1.2).
+    CancelTest(CancelTest),
+
+    // -- Keepalive (protocol 1.1) --
+    Ping,
+    Pong,
+
     // -- Generic --
     Ok,
     Error(ErrorResponse),
@@ -62,6 +76,10 @@ pub enum MessagePayload {
 pub struct Hello {
     /// Protocol version, e.g. `"1.0"`.
     pub version: String,
+    /// Every protocol version the client speaks; the server picks the
+    /// highest one it shares. Older clients send only `version`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub versions: Vec<String>,
     /// Capabilities the client supports, e.g. `["throughput", "udp_echo", "path_meta"]`.
     pub features: Vec<String>,
 }
@@ -69,8 +87,11 @@ pub struct Hello {
 /// Sent by the server in response to [`Hello`].
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServerHello {
-    /// Protocol version, e.g. `"1.0"`.
+    /// Protocol version negotiated for this connection, e.g. `"1.0"`.
     pub version: String,
+    /// Every protocol version the server speaks.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub versions: Vec<String>,
     /// Capabilities the server supports.
     pub features: Vec<String>,
     /// Summary of the server's rate-limiting and resource policies.
@@ -78,6 +99,14 @@ pub struct ServerHello {
     /// Network position of this reflector (`"wan"`, `"lan"`, `"hybrid"`, or `"unknown"`).
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub network_position: Option<String>,
+    /// Address families with a public address (`"ipv4"`, `"ipv6"`, or
+    /// `"dual_stack"`); a `"wan"` reflector behind IPv4 CGNAT reports `"ipv6"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reachable_via: Option<String>,
+    /// This reflector's endpoint ID, so a client can confirm it reached the
+    /// intended reflector before pairing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint_id: Option<String>,
 }
 
 /// Summary of server-side resource policies communicated during the handshake.
@@ -125,6 +154,9 @@ pub struct TestParams {
     pub streams: Option<u32>,
     /// Whether to run in reverse mode (throughput tests only).
     pub reverse: Option<bool>,
+    /// Set don't-fragment on echoes for a path-MTU size ramp (UDP echo only).
+    #[serde(default)]
+    pub mtu_probe: Option<bool>,
 }
 
 /// Server grants a test session.
@@ -134,12 +166,24 @@ pub struct SessionGrant {
     pub test_id: String,
     /// `"tunneled"` or `"direct_ephemeral"`.
     pub mode: String,
-    /// Data-plane port to connect to.
+    /// Data-plane port to connect to (`0` when tunneled).
     pub port: u16,
     /// One-time auth cookie for the data channel.
     pub token: String,
     /// ISO 8601 expiration timestamp for this grant.
     pub expires_at: String,
+    /// Every data connection to `port` must open with the `token` bytes
+    /// (direct-ephemeral mode); connections that don't are dropped.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub token_preamble: bool,
+    /// Duration the session was granted, after clamping to the reflector's
+    /// maximum (seconds).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_sec: Option<u64>,
+    /// Parallel streams to run (throughput only): the requested count, or
+    /// the reflector's default when none was asked for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub streams: Option<u32>,
 }
 
 /// Server denies a test session.
@@ -170,6 +214,79 @@ pub enum DenyReason {
 pub struct SessionClose {
     /// The test that should be torn down.
     pub test_id: String,
+    /// Reply with a [`SessionSummary`] instead of `Ok` when the engine
+    /// result is available.
+    #[serde(default)]
+    pub want_summary: bool,
+}
+
+/// Abort a running test. Peers may cancel their own tests; admin peers
+/// (`access.admin_peers`) may cancel any.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CancelTest {
+    /// The test to stop.
+    pub test_id: String,
+}
+
+/// How a test engine run ended.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionOutcome {
+    Completed,
+    TimedOut,
+    Error,
+}
+
+/// The reflector's own view of a finished test session.
+///
+/// Comparing this with the client's goodput exposes loss on the path: a
+/// reflector that sent 950 Mbps to a client that saw 900 Mbps lost the
+/// difference somewhere in between.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionSummary {
+    /// The test this summary describes.
+    pub test_id: String,
+    /// How the engine run ended.
+    pub outcome: SessionOutcome,
+    /// Bytes the reflector sent or received on the data plane.
+    pub bytes_transferred: u64,
+    /// Test duration in seconds, from the iperf3 report when available.
+    pub duration_sec: f64,
+    /// Throughput of each parallel stream in Mbps, as the reflector saw it.
+    /// Empty for single-stream engines and for reflectors that predate the
+    /// field; it is optional on the wire, not tied to a protocol version.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub streams_mbps: Vec<f64>,
+    /// Engine error message, for `outcome == error`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl SessionSummary {
+    /// Average data-plane rate seen by the reflector, in Mbps.
+    pub fn throughput_mbps(&self) -> f64 {
+        if self.duration_sec <= 0.0 {
+            return 0.0;
+        }
+        self.bytes_transferred as f64 * 8.0 / self.duration_sec / 1_000_000.0
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Data-plane tunnel
+// ---------------------------------------------------------------------------
+
+/// Client announces a new data connection for a `tunneled` session.
+///
+/// The reflector connects the channel to the session's iperf3 server and
+/// replies with `Ok` or `Error`; the channel's bytes then travel as binary
+/// data frames (see [`crate::tunnel`]).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TunnelOpen {
+    /// Session the connection belongs to.
+    pub test_id: String,
+    /// Client-chosen channel ID, unique per control connection.
+    pub channel: u32,
 }
 
 // ---------------------------------------------------------------------------
@@ -235,6 +352,36 @@ pub struct ActiveTestInfo {
     pub remaining_sec: u64,
 }
 
+// ---------------------------------------------------------------------------
+// Usage accounting (admin)
+// ---------------------------------------------------------------------------
+
+/// Bytes served and tests run per peer for one UTC day.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageSummary {
+    /// ISO 8601 start of the UTC day this summary covers.
+    pub day_start: String,
+    /// Sum of `bytes_today` over all peers.
+    pub total_bytes: u64,
+    /// Sum of `tests_today` over all peers.
+    pub total_tests: u32,
+    /// Per-peer breakdown, sorted by peer ID.
+    pub peers: Vec<PeerUsage>,
+}
+
+/// Usage counters for a single peer.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PeerUsage {
+    /// Peer endpoint ID.
+    pub peer_id: String,
+    /// Bytes transferred today, including sessions still running.
+    pub bytes_today: u64,
+    /// Tests started today.
+    pub tests_today: u32,
+    /// Sessions currently running for this peer.
+    pub active_tests: u32,
+}
+
 // ---------------------------------------------------------------------------
 // Path meta
 // ---------------------------------------------------------------------------
@@ -297,6 +444,7 @@ mod tests {
             request_id: "req-001".into(),
             payload: MessagePayload::Hello(Hello {
                 version: "1.0".into(),
+                versions: vec!["1.0".into()],
                 features: vec![
                     "throughput".into(),
                     "udp_echo".into(),
@@ -316,12 +464,23 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_hello_without_versions() {
+        // Clients predating version negotiation only send `version`.
+        let json = r#"{"type":"hello","version":"1.0","features":[]}"#;
+        match serde_json::from_str::<MessagePayload>(json).unwrap() {
+            MessagePayload::Hello(h) => assert!(h.versions.is_empty()),
+            other => panic!("expected Hello, got {:?}", other),
+        }
+    }
+
     #[test]
     fn test_server_hello_round_trip() {
         let msg = LinkMessage {
             request_id: "req-002".into(),
             payload: MessagePayload::ServerHello(ServerHello {
                 version: "1.0".into(),
+                versions: vec!["1.0".into()],
                 features: vec!["throughput".into()],
                 policy_summary: PolicySummary {
                     max_test_duration_sec: 60,
@@ -330,6 +489,8 @@ mod tests {
                     allowed_test_types: vec!["throughput".into()],
                 },
                 network_position: Some("wan".into()),
+                reachable_via: Some("ipv6".into()),
+                endpoint_id: Some("PP-5R6Q-2M1K-9D3F-C3".into()),
             }),
         };
         let (json, decoded) = round_trip(&msg);
@@ -338,6 +499,8 @@ mod tests {
             MessagePayload::ServerHello(sh) => {
                 assert_eq!(sh.policy_summary.max_test_duration_sec, 60);
                 assert_eq!(sh.policy_summary.max_concurrent_tests, 1);
+                assert_eq!(sh.endpoint_id.as_deref(), Some("PP-5R6Q-2M1K-9D3F-C3"));
+                assert_eq!(sh.reachable_via.as_deref(), Some("ipv6"));
             }
             other => panic!("expected ServerHello, got {:?}", other),
         }
@@ -354,6 +517,7 @@ mod tests {
                     protocol: Some("tcp".into()),
                     streams: Some(4),
                     reverse: Some(false),
+                    mtu_probe: None,
                 },
             }),
         };
@@ -381,6 +545,9 @@ mod tests {
                 port: 5201,
                 token: "abc123secret".into(),
                 expires_at: "2025-06-15T12:00:00Z".into(),
+                token_preamble: true,
+                duration_sec: Some(30),
+                streams: Some(4),
             }),
         };
         let (json, decoded) = round_trip(&msg);
@@ -389,6 +556,9 @@ mod tests {
             MessagePayload::SessionGrant(sg) => {
                 assert_eq!(sg.port, 5201);
                 assert_eq!(sg.mode, "tunneled");
+                assert!(sg.token_preamble);
+                assert_eq!(sg.duration_sec, Some(30));
+                assert_eq!(sg.streams, Some(4));
             }
             other => panic!("expected SessionGrant, got {:?}", other),
         }
@@ -422,6 +592,7 @@ mod tests {
             request_id: "req-006".into(),
             payload: MessagePayload::SessionClose(SessionClose {
                 test_id: "test-42".into(),
+                want_summary: false,
             }),
         };
         let (json, decoded) = round_trip(&msg);
@@ -432,6 +603,62 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_tunnel_open_round_trip() {
+        let msg = LinkMessage {
+            request_id: "req-007".into(),
+            payload: MessagePayload::TunnelOpen(TunnelOpen {
+                test_id: "test-42".into(),
+                channel: 3,
+            }),
+        };
+        let (json, decoded) = round_trip(&msg);
+        assert!(json.contains(r#""type": "tunnel_open""#));
+        match &decoded.payload {
+            MessagePayload::TunnelOpen(t) => {
+                assert_eq!(t.test_id, "test-42");
+                assert_eq!(t.channel, 3);
+            }
+            other => panic!("expected TunnelOpen, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_session_close_without_want_summary_defaults_false() {
+        let json = r#"{"request_id":"r","payload":{"type":"session_close","test_id":"t"}}"#;
+        let msg: LinkMessage = serde_json::from_str(json).unwrap();
+        match msg.payload {
+            MessagePayload::SessionClose(sc) => assert!(!sc.want_summary),
+            other => panic!("expected SessionClose, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_session_summary_round_trip() {
+        let msg = LinkMessage {
+            request_id: "req-006b".into(),
+            payload: MessagePayload::SessionSummary(SessionSummary {
+                test_id: "test-42".into(),
+                outcome: SessionOutcome::Completed,
+                bytes_transferred: 1_187_500_000,
+                duration_sec: 10.0,
+                streams_mbps: Vec::new(),
+                error: None,
+            }),
+        };
+        let (json, decoded) = round_trip(&msg);
+        assert!(json.contains(r#""type": "session_summary""#));
+        assert!(json.contains(r#""outcome": "completed""#));
+        assert!(!json.contains("error"));
+        match &decoded.payload {
+            MessagePayload::SessionSummary(s) => {
+                assert_eq!(s.bytes_transferred, 1_187_500_000);
+                assert!((s.throughput_mbps() - 950.0).abs() < 1e-9);
+            }
+            other => panic!("expected SessionSummary, got {:?}", other),
+        }
+    }
+
     #[test]
     fn test_get_status_round_trip() {
         let msg = LinkMessage {
@@ -476,6 +703,40 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_ping_pong_round_trip() {
+        for payload in [MessagePayload::Ping, MessagePayload::Pong] {
+            let msg = LinkMessage {
+                request_id: "req-ka".into(),
+                payload,
+            };
+            let json = serde_json::to_string(&msg).unwrap();
+            let decoded: LinkMessage = serde_json::from_str(&json).unwrap();
+            assert_eq!(decoded.request_id, "req-ka");
+            assert_eq!(
+                std::mem::discriminant(&decoded.payload),
+                std::mem::discriminant(&msg.payload)
+            );
+        }
+        assert!(serde_json::to_string(&MessagePayload::Ping).unwrap().contains(r#""type":"ping""#));
+    }
+
+    #[test]
+    fn test_cancel_test_round_trip() {
+        let msg = LinkMessage {
+            request_id: "req-cancel".into(),
+            payload: MessagePayload::CancelTest(CancelTest {
+                test_id: "test-42".into(),
+            }),
+        };
+        let (json, decoded) = round_trip(&msg);
+        assert!(json.contains(r#""type": "cancel_test""#));
+        match decoded.payload {
+            MessagePayload::CancelTest(c) => assert_eq!(c.test_id, "test-42"),
+            other => panic!("expected CancelTest, got {:?}", other),
+        }
+    }
+
     #[test]
     fn test_get_path_meta_round_trip() {
         let msg = LinkMessage {
@@ -609,6 +870,7 @@ mod tests {
                     protocol: None,
                     streams: None,
                     reverse: None,
+                    mtu_probe: Some(true),
                 },
             }),
         };
@@ -619,6 +881,7 @@ mod tests {
             MessagePayload::SessionRequest(sr) => {
                 assert_eq!(sr.test_type, TestType::UdpEcho);
                 assert!(sr.params.protocol.is_none());
+                assert_eq!(sr.params.mtu_probe, Some(true));
             }
             other => panic!("expected SessionRequest, got {:?}", other),
         }
@@ -700,4 +963,14 @@ mod tests {
             other => panic!("expected SessionDeny, got {:?}", other),
         }
     }
+
+    #[test]
+    fn test_test_params_without_mtu_probe() {
+        // Peers that predate size ramps omit the field entirely.
+        let params: TestParams = serde_json::from_str(
+            r#"{"duration_sec": 10, "protocol": null, "streams": null, "reverse": null}"#,
+        )
+        .unwrap();
+        assert_eq!(params.mtu_probe, None);
+    }
 }
@@ -1,16 +1,17 @@
 //! Hardware and environment self-test for the PacketParamedic Reflector.
 //!
 //! Validates that the host running the reflector has the CPU, memory, network,
-//! and tooling needed to saturate a 1 Gbps link during throughput tests.
-//! Produces a structured report with pass/warn/fail verdicts and remediation
-//! guidance.
+//! and tooling needed to saturate its target link rate (`[selftest]
+//! target_mbps`, 1 Gbps by default) during throughput tests.  Produces a
+//! structured report with pass/warn/fail verdicts and remediation guidance.
 
 use std::collections::HashMap;
 use std::path::Path;
 use std::time::{Duration, Instant};
 
+use rand::Rng;
 use serde::Serialize;
-use tracing::info;
+use tracing::{debug, info};
 
 use crate::config::ReflectorConfig;
 
@@ -63,13 +64,85 @@ impl std::fmt::Display for TestStatus {
     }
 }
 
+// ---------------------------------------------------------------------------
+// Thresholds
+// ---------------------------------------------------------------------------
+
+/// Pass/warn thresholds scaled from the configured target rate.
+///
+/// Every threshold is linear in `target_mbps`, so the default (1000) matches
+/// the original 1 Gbps expectations:
+///
+/// | Threshold      | Formula                            | @ 1000 |
+/// |----------------|------------------------------------|--------|
+/// | memory pass    | 512 MB per Gbps, at least 128 MB   | 512 MB |
+/// | memory warn    | half of memory pass                | 256 MB |
+/// | loopback pass  | `target_mbps`                      | 1000   |
+/// | loopback warn  | half of `target_mbps`              | 500    |
+/// | NIC link speed | `target_mbps`                      | 1000   |
+/// | READY verdict  | 94% of `target_mbps` (TCP goodput) | 940    |
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Thresholds {
+    /// Target sustained throughput in Mbps.
+    pub target_mbps: u32,
+    /// Available memory (MB) required to pass.
+    pub memory_pass_mb: u64,
+    /// Available memory (MB) below which the check fails.
+    pub memory_warn_mb: u64,
+    /// Loopback throughput (Mbps) required to pass.
+    pub loopback_pass_mbps: f64,
+    /// Loopback throughput (Mbps) below which the check fails.
+    pub loopback_warn_mbps: f64,
+    /// Minimum NIC link speed (Mbps).
+    pub nic_min_mbps: i32,
+    /// Estimated throughput (Mbps) required for an unqualified READY verdict.
+    pub ready_mbps: u32,
+}
+
+impl Thresholds {
+    /// Derive all thresholds from a target rate in Mbps.
+    pub fn from_target(target_mbps: u32) -> Self {
+        let target_mbps = target_mbps.max(1);
+        let memory_pass_mb = (512 * target_mbps as u64 / 1000).max(128);
+        Self {
+            target_mbps,
+            memory_pass_mb,
+            memory_warn_mb: memory_pass_mb / 2,
+            loopback_pass_mbps: target_mbps as f64,
+            loopback_warn_mbps: target_mbps as f64 / 2.0,
+            nic_min_mbps: target_mbps.min(i32::MAX as u32) as i32,
+            ready_mbps: (target_mbps as u64 * 94 / 100) as u32,
+        }
+    }
+
+    /// Human-readable target rate, e.g. "1 Gbps", "2.5 Gbps", "100 Mbps".
+    pub fn target_label(&self) -> String {
+        format_rate(self.target_mbps)
+    }
+}
+
+/// Format a rate in Mbps as a short label.
+fn format_rate(mbps: u32) -> String {
+    if mbps >= 1000 && mbps % 1000 == 0 {
+        format!("{} Gbps", mbps / 1000)
+    } else if mbps >= 1000 {
+        format!("{:.1} Gbps", mbps as f64 / 1000.0)
+    } else {
+        format!("{} Mbps", mbps)
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Main entry point
 // ---------------------------------------------------------------------------
 
 /// Run the full self-test suite and return a structured report.
 pub async fn run(config: &ReflectorConfig) -> SelfTestReport {
-    info!("self-test: checking host readiness for 1 Gbps reflector operation");
+    let thresholds = Thresholds::from_target(config.selftest.target_mbps);
+    info!(
+        "self-test: checking host readiness for {} reflector operation",
+        thresholds.target_label()
+    );
 
     let mut results = Vec::new();
 
@@ -80,16 +153,16 @@ pub async fn run(config: &ReflectorConfig) -> SelfTestReport {
     results.push(check_cpu_features());
 
     // 3. Memory
-    results.push(check_memory());
+    results.push(check_memory(&thresholds));
 
     // 4. Network interfaces
-    results.extend(check_network_interfaces());
+    results.extend(check_network_interfaces(&thresholds));
 
     // 5. iperf3 availability
     results.push(check_iperf3(&config.iperf3.path));
 
     // 6. Loopback throughput (iperf3 self-test)
-    results.push(check_loopback_throughput(&config.iperf3.path).await);
+    results.push(check_loopback_throughput(&config.iperf3.path, &thresholds).await);
 
     // 7. Disk I/O (audit log write speed)
     results.push(check_disk_io().await);
@@ -106,9 +179,9 @@ pub async fn run(config: &ReflectorConfig) -> SelfTestReport {
     info!("self-test complete: {} checks run", results.len());
 
     // Derive capabilities and verdict.
-    let capabilities = derive_capabilities(&results);
-    let estimated_max_mbps = estimate_max_throughput(&results);
-    let verdict = derive_verdict(&results, estimated_max_mbps);
+    let capabilities = derive_capabilities(&results, &thresholds);
+    let estimated_max_mbps = estimate_max_throughput(&results, &thresholds);
+    let verdict = derive_verdict(&results, estimated_max_mbps, &thresholds);
 
     SelfTestReport {
         results,
@@ -229,24 +302,32 @@ fn check_cpu_features() -> ComponentResult {
 }
 
 /// Check available memory. 1 Gbps iperf3 with 4 streams needs ~128 MB buffers.
-fn check_memory() -> ComponentResult {
+fn check_memory(thresholds: &Thresholds) -> ComponentResult {
     let sys = sysinfo::System::new_all();
     let total_mb = sys.total_memory() / (1024 * 1024);
     let available_mb = sys.available_memory() / (1024 * 1024);
 
     // iperf3 with 4 streams at 1 Gbps needs ~128 MB of socket buffers.
-    // Reflector itself uses ~20 MB. Total comfortable minimum: 512 MB available.
-    let (status, remediation) = if available_mb >= 512 {
+    // Reflector itself uses ~20 MB. Comfortable minimum: 512 MB available per
+    // Gbps of target rate.
+    let target = thresholds.target_label();
+    let (status, remediation) = if available_mb >= thresholds.memory_pass_mb {
         (TestStatus::Pass, None)
-    } else if available_mb >= 256 {
+    } else if available_mb >= thresholds.memory_warn_mb {
         (
             TestStatus::Warning,
-            Some("Low available memory. 1 Gbps tests with multiple streams may be constrained.".into()),
+            Some(format!(
+                "Low available memory. {} tests with multiple streams may be constrained.",
+                target
+            )),
         )
     } else {
         (
             TestStatus::Fail,
-            Some("Insufficient memory for 1 Gbps throughput testing. Need 512 MB+ available.".into()),
+            Some(format!(
+                "Insufficient memory for {} throughput testing. Need {} MB+ available.",
+                target, thresholds.memory_pass_mb
+            )),
         )
     };
 
@@ -259,8 +340,8 @@ fn check_memory() -> ComponentResult {
     }
 }
 
-/// Scan network interfaces for link speed and 1 Gbps capability.
-fn check_network_interfaces() -> Vec<ComponentResult> {
+/// Scan network interfaces for link speed and target-rate capability.
+fn check_network_interfaces(thresholds: &Thresholds) -> Vec<ComponentResult> {
     let mut results = Vec::new();
 
     // Try reading from /sys/class/net (Linux)
@@ -285,8 +366,9 @@ fn check_network_interfaces() -> Vec<ComponentResult> {
         }
     };
 
-    let mut found_1g = false;
+    let mut found_target = false;
     let mut max_speed = 0i32;
+    let target = thresholds.target_label();
 
     for entry in entries.flatten() {
         let iface = entry.file_name().to_string_lossy().to_string();
@@ -317,19 +399,16 @@ fn check_network_interfaces() -> Vec<ComponentResult> {
             max_speed = speed_mbps;
         }
 
-        let (status, remediation) = if speed_mbps >= 10000 {
-            found_1g = true;
-            (TestStatus::Pass, None)
-        } else if speed_mbps >= 2500 {
-            found_1g = true;
-            (TestStatus::Pass, None)
-        } else if speed_mbps >= 1000 {
-            found_1g = true;
+        let (status, remediation) = if speed_mbps >= thresholds.nic_min_mbps {
+            found_target = true;
             (TestStatus::Pass, None)
         } else if speed_mbps >= 100 {
             (
                 TestStatus::Warning,
-                Some("100 Mbps link. Cannot reach 1 Gbps. Upgrade NIC or check cable.".into()),
+                Some(format!(
+                    "{} Mbps link. Cannot reach {}. Upgrade NIC or check cable.",
+                    speed_mbps, target
+                )),
             )
         } else {
             (
@@ -372,12 +451,18 @@ fn check_network_interfaces() -> Vec<ComponentResult> {
         });
     }
 
-    if !found_1g && !results.is_empty() {
+    if !found_target && !results.is_empty() {
         results.push(ComponentResult {
-            component: "Network: 1G Capability".into(),
+            component: format!("Network: {} Capability", target),
             status: TestStatus::Fail,
-            details: format!("No interface with 1000+ Mbps link detected (max: {} Mbps)", max_speed),
-            remediation: Some("Connect a 1 Gbps or faster NIC for full throughput testing.".into()),
+            details: format!(
+                "No interface with {}+ Mbps link detected (max: {} Mbps)",
+                thresholds.nic_min_mbps, max_speed
+            ),
+            remediation: Some(format!(
+                "Connect a {} or faster NIC for full throughput testing.",
+                target
+            )),
             measured: None,
         });
     }
@@ -459,9 +544,9 @@ fn check_iperf3(iperf3_path: &str) -> ComponentResult {
 
 /// Run a 5-second loopback iperf3 test to measure raw TCP throughput capacity.
 ///
-/// This is the key "can we push 1 Gbps?" check. Loopback removes NIC as a
-/// variable and measures CPU + kernel networking stack.
-async fn check_loopback_throughput(iperf3_path: &str) -> ComponentResult {
+/// This is the key "can we push the target rate?" check. Loopback removes NIC
+/// as a variable and measures CPU + kernel networking stack.
+async fn check_loopback_throughput(iperf3_path: &str, thresholds: &Thresholds) -> ComponentResult {
     // Check iperf3 exists first
     if std::process::Command::new(iperf3_path)
         .arg("--version")
@@ -479,32 +564,63 @@ async fn check_loopback_throughput(iperf3_path: &str) -> ComponentResult {
 
     info!("self-test: running 5-second loopback iperf3 throughput test");
 
-    // Start iperf3 server on loopback
-    let server = tokio::process::Command::new(iperf3_path)
-        .args(["-s", "-p", "5199", "--one-off"])
-        .stdout(std::process::Stdio::null())
-        .stderr(std::process::Stdio::null())
-        .spawn();
+    // Start iperf3 server on the first candidate port that binds.
+    let mut server = None;
+    for port in loopback_port_candidates() {
+        if !port_is_free(port) {
+            debug!(port, "self-test: loopback port busy, trying another");
+            continue;
+        }
+
+        let spawned = tokio::process::Command::new(iperf3_path)
+            .args(["-s", "-p", &port.to_string(), "--one-off"])
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn();
 
-    let mut server_child = match server {
-        Ok(child) => child,
-        Err(e) => {
-            return ComponentResult {
-                component: "Loopback Throughput".into(),
-                status: TestStatus::Warning,
-                details: format!("Failed to start iperf3 server: {}", e),
-                remediation: Some("Ensure iperf3 can bind to port 5199.".into()),
-                measured: None,
-            };
+        let mut child = match spawned {
+            Ok(child) => child,
+            Err(e) => {
+                return ComponentResult {
+                    component: "Loopback Throughput".into(),
+                    status: TestStatus::Warning,
+                    details: format!("Failed to start iperf3 server: {}", e),
+                    remediation: Some("Ensure iperf3 is executable by this user.".into()),
+                    measured: None,
+                };
+            }
+        };
+
+        // Give server a moment to bind; if it already exited, someone else
+        // grabbed the port between our probe and iperf3's bind.
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        match child.try_wait() {
+            Ok(None) => {
+                server = Some((port, child));
+                break;
+            }
+            _ => debug!(port, "self-test: iperf3 server exited early, trying another port"),
         }
-    };
+    }
 
-    // Give server a moment to bind
-    tokio::time::sleep(Duration::from_millis(500)).await;
+    let Some((port, mut server_child)) = server else {
+        return ComponentResult {
+            component: "Loopback Throughput".into(),
+            status: TestStatus::Warning,
+            details: format!(
+                "No free loopback port after {} attempts in {}-{}",
+                LOOPBACK_PORT_ATTEMPTS,
+                LOOPBACK_PORT_RANGE.start(),
+                LOOPBACK_PORT_RANGE.end()
+            ),
+            remediation: Some("Free some ephemeral ports or re-run the self-test later.".into()),
+            measured: None,
+        };
+    };
 
     // Run client against loopback
     let client = tokio::process::Command::new(iperf3_path)
-        .args(["-c", "127.0.0.1", "-p", "5199", "-t", "5", "-J", "-P", "4"])
+        .args(["-c", "127.0.0.1", "-p", &port.to_string(), "-t", "5", "-J", "-P", "4"])
         .output()
         .await;
 
@@ -517,19 +633,24 @@ async fn check_loopback_throughput(iperf3_path: &str) -> ComponentResult {
             // Parse iperf3 JSON for sum_received.bits_per_second
             let throughput_mbps = parse_iperf3_throughput(&stdout);
 
-            let (status, remediation) = if throughput_mbps >= 10000.0 {
-                (TestStatus::Pass, None)
-            } else if throughput_mbps >= 1000.0 {
+            let target = thresholds.target_label();
+            let (status, remediation) = if throughput_mbps >= thresholds.loopback_pass_mbps {
                 (TestStatus::Pass, None)
-            } else if throughput_mbps >= 500.0 {
+            } else if throughput_mbps >= thresholds.loopback_warn_mbps {
                 (
                     TestStatus::Warning,
-                    Some("Loopback throughput below 1 Gbps. CPU may bottleneck real tests.".into()),
+                    Some(format!(
+                        "Loopback throughput below {}. CPU may bottleneck real tests.",
+                        target
+                    )),
                 )
             } else {
                 (
                     TestStatus::Fail,
-                    Some("Loopback throughput too low for 1 Gbps testing. Check CPU load and system resources.".into()),
+                    Some(format!(
+                        "Loopback throughput too low for {} testing. Check CPU load and system resources.",
+                        target
+                    )),
                 )
             };
 
@@ -537,8 +658,8 @@ async fn check_loopback_throughput(iperf3_path: &str) -> ComponentResult {
                 component: "Loopback Throughput".into(),
                 status,
                 details: format!(
-                    "Loopback iperf3 (4 streams, 5s): {:.0} Mbps",
-                    throughput_mbps
+                    "Loopback iperf3 (4 streams, 5s, port {}): {:.0} Mbps",
+                    port, throughput_mbps
                 ),
                 remediation,
                 measured: Some(format!("{:.0} Mbps", throughput_mbps)),
@@ -549,8 +670,8 @@ async fn check_loopback_throughput(iperf3_path: &str) -> ComponentResult {
             ComponentResult {
                 component: "Loopback Throughput".into(),
                 status: TestStatus::Warning,
-                details: format!("iperf3 client failed: {}", stderr),
-                remediation: Some("Port 5199 may be in use. Try again after a moment.".into()),
+                details: format!("iperf3 client failed on port {}: {}", port, stderr),
+                remediation: Some("Check that nothing else is using loopback networking and try again.".into()),
                 measured: None,
             }
         }
@@ -564,6 +685,28 @@ async fn check_loopback_throughput(iperf3_path: &str) -> ComponentResult {
     }
 }
 
+/// Ephemeral (IANA dynamic) range scanned for a free loopback iperf3 port.
+const LOOPBACK_PORT_RANGE: std::ops::RangeInclusive<u16> = 49152..=65535;
+
+/// Number of candidate ports tried before the loopback check gives up.
+const LOOPBACK_PORT_ATTEMPTS: usize = 8;
+
+/// Random candidate ports for the loopback iperf3 server.
+///
+/// Randomising avoids colliding with a previous run whose server is still
+/// tearing down when the self-test is invoked repeatedly.
+fn loopback_port_candidates() -> Vec<u16> {
+    let mut rng = rand::thread_rng();
+    (0..LOOPBACK_PORT_ATTEMPTS)
+        .map(|_| rng.gen_range(LOOPBACK_PORT_RANGE))
+        .collect()
+}
+
+/// Whether a TCP port on 127.0.0.1 can currently be bound.
+fn port_is_free(port: u16) -> bool {
+    std::net::TcpListener::bind((std::net::Ipv4Addr::LOCALHOST, port)).is_ok()
+}
+
 /// Parse iperf3 JSON output for total throughput in Mbps.
 fn parse_iperf3_throughput(json_str: &str) -> f64 {
     let parsed: serde_json::Value = match serde_json::from_str(json_str) {
@@ -801,7 +944,7 @@ fn check_ulimits() -> ComponentResult {
 // ---------------------------------------------------------------------------
 
 /// Derive high-level capability flags from component results.
-fn derive_capabilities(results: &[ComponentResult]) -> HashMap<String, bool> {
+fn derive_capabilities(results: &[ComponentResult], thresholds: &Thresholds) -> HashMap<String, bool> {
     let mut caps = HashMap::new();
 
     let get_status = |name: &str| -> TestStatus {
@@ -812,22 +955,24 @@ fn derive_capabilities(results: &[ComponentResult]) -> HashMap<String, bool> {
             .unwrap_or(TestStatus::Skipped)
     };
 
-    // Can push 1 Gbps?
+    let target = thresholds.target_label();
+
+    // Can push the target rate?
     let cpu_ok = get_status("CPU") != TestStatus::Fail;
     let mem_ok = get_status("Memory") != TestStatus::Fail;
     let iperf3_ok = get_status("iperf3") == TestStatus::Pass;
     let loopback_ok = get_status("Loopback") == TestStatus::Pass;
 
     caps.insert(
-        "1 Gbps Throughput Testing".into(),
+        format!("{} Throughput Testing", target),
         cpu_ok && mem_ok && iperf3_ok && loopback_ok,
     );
 
-    // Has NIC >= 1 Gbps?
-    let has_1g_nic = results
+    // Has NIC >= target rate?
+    let has_target_nic = results
         .iter()
         .any(|r| r.component.starts_with("Network:") && r.status == TestStatus::Pass);
-    caps.insert("1 Gbps NIC Detected".into(), has_1g_nic);
+    caps.insert(format!("{} NIC Detected", target), has_target_nic);
 
     // Crypto fast enough for burst mTLS?
     let crypto_ok = get_status("Crypto") != TestStatus::Fail;
@@ -845,7 +990,10 @@ fn derive_capabilities(results: &[ComponentResult]) -> HashMap<String, bool> {
 }
 
 /// Estimate maximum sustainable throughput from test results.
-fn estimate_max_throughput(results: &[ComponentResult]) -> u32 {
+///
+/// The estimate is capped at the configured target rate: a reflector
+/// deliberately sized for 100 Mbps is not expected to exceed it.
+fn estimate_max_throughput(results: &[ComponentResult], thresholds: &Thresholds) -> u32 {
     // Base estimate from loopback test
     let loopback_mbps = results
         .iter()
@@ -877,11 +1025,15 @@ fn estimate_max_throughput(results: &[ComponentResult]) -> u32 {
     };
 
     // Apply 90% efficiency factor (TCP overhead, kernel overhead)
-    (estimated * 0.9) as u32
+    ((estimated * 0.9) as u32).min(thresholds.target_mbps)
 }
 
 /// Derive overall verdict string.
-fn derive_verdict(results: &[ComponentResult], estimated_max_mbps: u32) -> String {
+fn derive_verdict(
+    results: &[ComponentResult],
+    estimated_max_mbps: u32,
+    thresholds: &Thresholds,
+) -> String {
     let fail_count = results.iter().filter(|r| r.status == TestStatus::Fail).count();
     let warn_count = results
         .iter()
@@ -898,8 +1050,12 @@ fn derive_verdict(results: &[ComponentResult], estimated_max_mbps: u32) -> Strin
             "DEGRADED - {} warning(s). Estimated max: {} Mbps",
             warn_count, estimated_max_mbps
         )
-    } else if estimated_max_mbps >= 940 {
-        format!("READY - Host can sustain 1 Gbps. Estimated max: {} Mbps", estimated_max_mbps)
+    } else if estimated_max_mbps >= thresholds.ready_mbps {
+        format!(
+            "READY - Host can sustain {}. Estimated max: {} Mbps",
+            thresholds.target_label(),
+            estimated_max_mbps
+        )
     } else {
         format!(
             "READY (limited) - Estimated max: {} Mbps",
@@ -1006,7 +1162,7 @@ mod tests {
                 measured: None,
             },
         ];
-        let verdict = derive_verdict(&results, 940);
+        let verdict = derive_verdict(&results, 940, &Thresholds::from_target(1000));
         assert!(verdict.starts_with("READY"));
     }
 
@@ -1021,7 +1177,7 @@ mod tests {
                 measured: None,
             },
         ];
-        let verdict = derive_verdict(&results, 100);
+        let verdict = derive_verdict(&results, 100, &Thresholds::from_target(1000));
         assert!(verdict.starts_with("NOT READY"));
     }
 
@@ -1036,7 +1192,7 @@ mod tests {
                 measured: None,
             },
         ];
-        let verdict = derive_verdict(&results, 800);
+        let verdict = derive_verdict(&results, 800, &Thresholds::from_target(1000));
         assert!(verdict.starts_with("DEGRADED"));
     }
 
@@ -1058,9 +1214,75 @@ mod tests {
                 measured: Some("1000 Mbps".into()),
             },
         ];
-        let max = estimate_max_throughput(&results);
+        let max = estimate_max_throughput(&results, &Thresholds::from_target(1000));
         // Should be capped by NIC: 1000 * 0.9 = 900
         assert_eq!(max, 900);
+
+        // A deliberately capped reflector never estimates above its target.
+        let max = estimate_max_throughput(&results, &Thresholds::from_target(100));
+        assert_eq!(max, 100);
+    }
+
+    #[test]
+    fn test_loopback_port_candidates_in_range() {
+        let ports = loopback_port_candidates();
+        assert_eq!(ports.len(), LOOPBACK_PORT_ATTEMPTS);
+        assert!(ports.iter().all(|p| LOOPBACK_PORT_RANGE.contains(p)));
+    }
+
+    #[test]
+    fn test_port_is_free_detects_busy_port() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert!(!port_is_free(port));
+        drop(listener);
+        assert!(port_is_free(port));
+    }
+
+    #[test]
+    fn test_thresholds_default_target() {
+        let t = Thresholds::from_target(1000);
+        assert_eq!(t.memory_pass_mb, 512);
+        assert_eq!(t.memory_warn_mb, 256);
+        assert_eq!(t.loopback_pass_mbps, 1000.0);
+        assert_eq!(t.loopback_warn_mbps, 500.0);
+        assert_eq!(t.nic_min_mbps, 1000);
+        assert_eq!(t.ready_mbps, 940);
+        assert_eq!(t.target_label(), "1 Gbps");
+    }
+
+    #[test]
+    fn test_thresholds_scale_with_target() {
+        let t = Thresholds::from_target(10_000);
+        assert_eq!(t.memory_pass_mb, 5120);
+        assert_eq!(t.nic_min_mbps, 10_000);
+        assert_eq!(t.ready_mbps, 9400);
+        assert_eq!(t.target_label(), "10 Gbps");
+
+        assert_eq!(Thresholds::from_target(2500).target_label(), "2.5 Gbps");
+
+        // Small targets keep a memory floor.
+        let t = Thresholds::from_target(100);
+        assert_eq!(t.memory_pass_mb, 128);
+        assert_eq!(t.memory_warn_mb, 64);
+        assert_eq!(t.ready_mbps, 94);
+        assert_eq!(t.target_label(), "100 Mbps");
+    }
+
+    #[test]
+    fn test_derive_verdict_uses_target() {
+        let results = vec![ComponentResult {
+            component: "CPU".into(),
+            status: TestStatus::Pass,
+            details: "ok".into(),
+            remediation: None,
+            measured: None,
+        }];
+        let verdict = derive_verdict(&results, 95, &Thresholds::from_target(100));
+        assert!(verdict.starts_with("READY - Host can sustain 100 Mbps"));
+
+        let verdict = derive_verdict(&results, 940, &Thresholds::from_target(10_000));
+        assert!(verdict.starts_with("READY (limited)"));
     }
 
     #[test]
@@ -1072,7 +1294,7 @@ mod tests {
 
     #[test]
     fn test_check_memory_returns_result() {
-        let result = check_memory();
+        let result = check_memory(&Thresholds::from_target(1000));
         assert_eq!(result.component, "Memory");
         assert!(result.measured.is_some());
     }
@@ -1161,11 +1383,38 @@ mod tests {
                 measured: None,
             },
         ];
-        let caps = derive_capabilities(&results);
+        let caps = derive_capabilities(&results, &Thresholds::from_target(1000));
         assert_eq!(caps.get("1 Gbps Throughput Testing"), Some(&true));
         assert_eq!(caps.get("1 Gbps NIC Detected"), Some(&true));
         assert_eq!(caps.get("mTLS Performance"), Some(&true));
         assert_eq!(caps.get("Audit Log Performance"), Some(&true));
         assert_eq!(caps.get("Accurate Timestamps"), Some(&true));
     }
+
+    #[test]
+    fn test_report_json_shape() {
+        // Provisioning scripts gate on these fields; keep them stable.
+        let results = vec![ComponentResult {
+            component: "CPU".into(),
+            status: TestStatus::Pass,
+            details: "ok".into(),
+            remediation: None,
+            measured: None,
+        }];
+        let thresholds = Thresholds::from_target(1000);
+        let report = SelfTestReport {
+            capabilities: derive_capabilities(&results, &thresholds),
+            verdict: derive_verdict(&results, 940, &thresholds),
+            estimated_max_mbps: 940,
+            results,
+        };
+
+        let json: serde_json::Value = serde_json::to_value(&report).unwrap();
+        assert!(json["verdict"].as_str().unwrap().starts_with("READY"));
+        assert_eq!(json["estimated_max_mbps"], 940);
+        assert_eq!(json["results"][0]["component"], "CPU");
+        assert_eq!(json["results"][0]["status"], "Pass");
+        assert!(json["results"][0].get("measured").is_none());
+        assert!(json["capabilities"].is_object());
+    }
 }
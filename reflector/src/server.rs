@@ -1,32 +1,49 @@
 //! Main mTLS server for the PacketParamedic Reflector.
 //!
-//! `ReflectorServer` binds a TCP listener, performs TLS handshakes with mutual
+//! `ReflectorServer` binds a TCP listener (and/or a QUIC endpoint, per
+//! `network.transport`), performs TLS handshakes with mutual
 //! certificate authentication, extracts the peer identity from the presented
 //! certificate, enforces authorization via [`AuthGate`], and dispatches
 //! length-prefixed JSON messages according to the Paramedic Link protocol.
 
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::path::Path;
+use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpListener;
+use bytes::Bytes;
+use rustls_pki_types::CertificateDer;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
 use tokio_rustls::TlsAcceptor;
 use tracing::{debug, error, info, warn};
 
 use crate::audit::{AuditEntry, AuditEventType, AuditLog};
-use crate::auth::{AuthDecision, AuthGate};
+use crate::auth::{AuthDecision, AuthGate, PAIRING_FILE};
 use crate::cert::generate_self_signed_cert;
-use crate::config::ReflectorConfig;
+use crate::config::{DataPlaneMode, ReflectorConfig};
+use crate::directory;
+use crate::engine::gate;
 use crate::engine::path_meta::collect_path_meta;
 use crate::engine::throughput::ThroughputEngine;
+use crate::engine::udp_echo::UdpEchoEngine;
 use crate::governance::GovernanceEngine;
 use crate::identity::Identity;
-use crate::peer::PeerId;
+use crate::idle::{self, IdleMonitor};
+use crate::metrics::LatencyMetrics;
+use crate::network;
+use crate::peer::{PeerId, PEERS_FILE};
+use crate::quic;
 use crate::rpc::*;
-use crate::session::SessionManager;
-use crate::tls::build_server_config;
+use crate::session::{CancelError, SessionManager};
+use crate::stats;
+use crate::tls::{build_server_config, check_negotiated_alpn};
+use crate::tunnel::{self, Tunnel};
+use crate::version::{self, ProtocolVersion};
+use crate::wire;
 
 // ---------------------------------------------------------------------------
 // Constants
@@ -38,9 +55,14 @@ const MAX_FRAME_SIZE: usize = 1_048_576;
 /// Interval between session cleanup sweeps.
 const SESSION_CLEANUP_INTERVAL_SECS: u64 = 30;
 
-/// Protocol version advertised by this server.
+/// Protocol version assumed for a connection until its Hello negotiates
+/// one: what clients predating version negotiation speak.
 const PROTOCOL_VERSION: &str = "1.0";
 
+/// Echo rate cap per UDP echo session, so a session can't be turned into a
+/// packet flood.
+const UDP_ECHO_MAX_PACKET_RATE: u32 = 1000;
+
 // ---------------------------------------------------------------------------
 // ReflectorServer
 // ---------------------------------------------------------------------------
@@ -53,11 +75,15 @@ pub struct ReflectorServer {
     config: ReflectorConfig,
     identity: Identity,
     tls_acceptor: TlsAcceptor,
+    /// Same TLS config as `tls_acceptor`, for the QUIC listener.
+    quic_tls: rustls::ServerConfig,
     auth_gate: Arc<AuthGate>,
     session_manager: Arc<SessionManager>,
     governance: Arc<GovernanceEngine>,
     throughput: Arc<ThroughputEngine>,
     audit_log: Arc<AuditLog>,
+    latency: Arc<LatencyMetrics>,
+    idle: Arc<IdleMonitor>,
     start_time: Instant,
 }
 
@@ -89,46 +115,79 @@ impl ReflectorServer {
             .context("failed to generate self-signed certificate")?;
 
         // 3. TLS server config
-        let tls_config = build_server_config(cert_der, key_der)
+        let tls_config = build_server_config(cert_der, key_der, &config.network.alpn)
             .context("failed to build TLS server configuration")?;
+        let quic_tls = tls_config.clone();
         let tls_acceptor = TlsAcceptor::from(Arc::new(tls_config));
 
         // 4. Subsystems
-        let auth_gate = Arc::new(AuthGate::new(&config.access));
-        let governance = Arc::new(GovernanceEngine::new(config.quotas.clone()));
-        let session_manager = Arc::new(SessionManager::new(
-            config.quotas.clone(),
-            governance.clone(),
-            endpoint_id,
-        ));
-        let throughput = Arc::new(ThroughputEngine::new(
+        let auth_gate = Arc::new(
+            AuthGate::new(&config.access)
+                .with_pairing_file(identity_dir.join(PAIRING_FILE))
+                .with_peers_file(identity_dir.join(PEERS_FILE)),
+        );
+        let governance = Arc::new(
+            GovernanceEngine::new(config.quotas.clone())
+                .with_usage_file(identity_dir.join(stats::USAGE_FILE)),
+        );
+        let session_manager = Arc::new(
+            SessionManager::new(config.quotas.clone(), governance.clone(), endpoint_id)
+                .with_iperf3_limits(&config.iperf3)
+                .with_data_plane(config.network.mode)
+                .with_position(network::resolve_report(&config.network.deployment_mode)),
+        );
+        let throughput = ThroughputEngine::new(
             &config.iperf3,
             (config.network.data_port_range_start, config.network.data_port_range_end),
-        ));
+        );
+        // iperf3 is never exposed directly: tunneled channels are bridged to
+        // it, and direct-ephemeral clients reach it through the token gate.
+        let throughput = Arc::new(throughput.with_bind_address(Ipv4Addr::LOCALHOST.into()));
         let audit_log = Arc::new(
-            AuditLog::new(config.logging.audit_log_path.clone())
-                .await
-                .context("failed to initialize audit log")?,
+            AuditLog::open(
+                config.logging.audit_log_path.clone(),
+                config.logging.audit_hash_chain,
+            )
+            .await
+            .context("failed to initialize audit log")?,
         );
+        if config.logging.audit_hash_chain {
+            if let Err(e) = audit_log.verify_chain().await {
+                warn!(error = %e, "audit log hash chain verification failed");
+            }
+        }
 
         Ok(ReflectorServer {
             config,
             identity,
             tls_acceptor,
+            quic_tls,
             auth_gate,
             session_manager,
             governance,
             throughput,
             audit_log,
+            latency: Arc::new(LatencyMetrics::new()),
+            idle: Arc::new(IdleMonitor::new()),
             start_time: Instant::now(),
         })
     }
 
+    /// Shared maintenance-mode flag, for wiring into the health endpoint.
+    pub fn maintenance_flag(&self) -> Arc<AtomicBool> {
+        self.session_manager.maintenance_flag()
+    }
+
+    /// Shared setup latency metrics, for wiring into the health endpoint.
+    pub fn latency_metrics(&self) -> Arc<LatencyMetrics> {
+        Arc::clone(&self.latency)
+    }
+
     /// Run the reflector server, accepting connections in a loop.
     ///
     /// This method does not return under normal operation. It spawns a
     /// background task for periodic session cleanup and then enters the
-    /// TCP accept loop.
+    /// accept loop(s) for the configured transport.
     pub async fn run(&self) -> Result<()> {
         let bind_addr = self.config.network.listen_address.clone();
 
@@ -138,17 +197,37 @@ impl ReflectorServer {
         println!("  ========================");
         println!("  Endpoint ID : {}", self.identity.endpoint_id());
         println!("  Listen      : {}", bind_addr);
+        println!("  Transport   : {:?}", self.config.network.transport);
         println!("  Mode        : {:?}", self.config.network.mode);
         println!();
 
-        let listener = TcpListener::bind(&bind_addr)
-            .await
-            .with_context(|| format!("failed to bind TCP listener on {}", bind_addr))?;
-
-        info!(addr = %bind_addr, "reflector listening");
+        let transport = self.config.network.transport;
+        let listener = if transport.tcp() {
+            let listener = TcpListener::bind(&bind_addr)
+                .await
+                .with_context(|| format!("failed to bind TCP listener on {}", bind_addr))?;
+            info!(addr = %bind_addr, "reflector listening (TCP)");
+            Some(listener)
+        } else {
+            None
+        };
+        let quic_endpoint = if transport.quic() {
+            let addr: SocketAddr = bind_addr
+                .parse()
+                .with_context(|| format!("invalid listen address for QUIC: {}", bind_addr))?;
+            let endpoint = quic::server_endpoint(self.quic_tls.clone(), addr)?;
+            info!(addr = %bind_addr, "reflector listening (QUIC)");
+            Some(endpoint)
+        } else {
+            None
+        };
 
-        // Spawn periodic session cleanup.
+        // Spawn periodic session cleanup and daily governance rollover.
         let session_mgr = Arc::clone(&self.session_manager);
+        let governance = Arc::clone(&self.governance);
+        let audit_log = Arc::clone(&self.audit_log);
+        let endpoint_id = self.identity.endpoint_id().to_string();
+        let daily_summary = self.config.logging.audit_daily_summary;
         tokio::spawn(async move {
             let mut interval = tokio::time::interval(
                 std::time::Duration::from_secs(SESSION_CLEANUP_INTERVAL_SECS),
@@ -156,240 +235,568 @@ impl ReflectorServer {
             loop {
                 interval.tick().await;
                 session_mgr.cleanup_expired().await;
+
+                if let Some(ended) = governance.reset_daily_if_needed().await {
+                    if daily_summary {
+                        let _ = audit_log
+                            .log(
+                                AuditEntry::new(AuditEventType::DailyUsageSummary, &endpoint_id)
+                                    .with_bytes_transferred(ended.total_bytes)
+                                    .with_reason(format!(
+                                        "day {}: {} test(s) from {} peer(s)",
+                                        ended.day_start,
+                                        ended.total_tests,
+                                        ended.peers.len()
+                                    ))
+                                    .with_params(
+                                        serde_json::to_value(&ended.peers).unwrap_or_default(),
+                                    ),
+                            )
+                            .await;
+                    }
+                }
             }
         });
 
-        // Accept loop.
-        loop {
-            let (tcp_stream, peer_addr) = match listener.accept().await {
-                Ok(conn) => conn,
-                Err(e) => {
-                    error!(error = %e, "failed to accept TCP connection");
-                    continue;
+        // Publish signed heartbeats to the directory, if one is configured.
+        if !self.config.directory.url.is_empty() {
+            let directory = &self.config.directory;
+            match directory::advertise_address(directory, &bind_addr) {
+                Ok(address) => {
+                    tokio::spawn(directory::run_heartbeat(
+                        directory.clone(),
+                        self.identity.signing_key().clone(),
+                        self.identity.endpoint_id().to_string(),
+                        address,
+                        self.session_manager.position(),
+                    ));
                 }
-            };
-
-            debug!(peer_addr = %peer_addr, "accepted TCP connection");
+                Err(e) => warn!("not publishing to the directory: {:#}", e),
+            }
+        }
 
-            let tls_acceptor = self.tls_acceptor.clone();
-            let auth_gate = Arc::clone(&self.auth_gate);
-            let session_manager = Arc::clone(&self.session_manager);
-            let throughput = Arc::clone(&self.throughput);
+        // Toggle maintenance mode on SIGUSR1.
+        #[cfg(unix)]
+        {
+            let session_mgr = Arc::clone(&self.session_manager);
             let audit_log = Arc::clone(&self.audit_log);
-            let config = self.config.clone();
             let endpoint_id = self.identity.endpoint_id().to_string();
-
             tokio::spawn(async move {
-                // TLS handshake.
-                let tls_stream = match tls_acceptor.accept(tcp_stream).await {
+                use tokio::signal::unix::{signal, SignalKind};
+
+                let mut sigusr1 = match signal(SignalKind::user_defined1()) {
                     Ok(s) => s,
                     Err(e) => {
-                        warn!(peer_addr = %peer_addr, error = %e, "TLS handshake failed");
+                        warn!(error = %e, "failed to install SIGUSR1 handler, maintenance toggle unavailable");
                         return;
                     }
                 };
 
-                // Extract peer certificate from the TLS session.
-                let (_, server_conn) = tls_stream.get_ref();
-                let peer_certs = server_conn.peer_certificates();
-                let peer_id = match peer_certs.and_then(|certs| certs.first()) {
-                    Some(cert_der) => match PeerId::from_cert(cert_der.as_ref()) {
-                        Ok(id) => id,
-                        Err(e) => {
-                            warn!(
-                                peer_addr = %peer_addr,
-                                error = %e,
-                                "failed to extract peer ID from certificate"
-                            );
-                            return;
-                        }
-                    },
-                    None => {
-                        warn!(peer_addr = %peer_addr, "no peer certificate presented");
+                while sigusr1.recv().await.is_some() {
+                    let enabled = !session_mgr.is_maintenance();
+                    session_mgr.set_maintenance(enabled);
+                    let active = session_mgr.active_count().await;
+                    info!(enabled = enabled, active_sessions = active, "SIGUSR1: maintenance mode toggled");
+                    let _ = audit_log
+                        .log(
+                            AuditEntry::new(AuditEventType::MaintenanceModeChanged, &endpoint_id)
+                                .with_decision(if enabled { "enabled" } else { "disabled" })
+                                .with_reason(format!("SIGUSR1 ({} active session(s))", active)),
+                        )
+                        .await;
+                }
+            });
+        }
+
+        // Re-read the peer store on SIGHUP (`peers` command edits).
+        #[cfg(unix)]
+        {
+            let auth_gate = Arc::clone(&self.auth_gate);
+            tokio::spawn(async move {
+                use tokio::signal::unix::{signal, SignalKind};
+
+                let mut sighup = match signal(SignalKind::hangup()) {
+                    Ok(s) => s,
+                    Err(e) => {
+                        warn!(error = %e, "failed to install SIGHUP handler, peer reload unavailable");
                         return;
                     }
                 };
 
-                debug!(peer_id = %peer_id, peer_addr = %peer_addr, "peer identified");
-
-                // Authorization check -- allow PairingRequired peers through
-                // with restricted access (pairing messages only).
-                let auth_decision = auth_gate.check(&peer_id).await;
-                let pairing_only = match &auth_decision {
-                    AuthDecision::Allowed => false,
-                    AuthDecision::PairingRequired => {
-                        debug!(peer_id = %peer_id, "peer allowed for pairing only");
-                        true
+                while sighup.recv().await.is_some() {
+                    match auth_gate.reload_peers().await {
+                        Ok(count) => info!(count = count, "SIGHUP: authorized peers reloaded"),
+                        Err(e) => warn!(error = %e, "SIGHUP: keeping previous peers; peer store unreadable"),
                     }
-                    AuthDecision::Denied(reason) => {
-                        warn!(peer_id = %peer_id, reason = %reason, "peer not authorized, closing connection");
-                        let _ = audit_log.log(
-                            AuditEntry::new(AuditEventType::ConnectionDenied, &endpoint_id)
-                                .with_peer_id(peer_id.to_string())
-                                .with_reason("peer not in authorized set"),
-                        ).await;
-                        return;
+                }
+            });
+        }
+
+        let ctx = ConnContext {
+            auth_gate: Arc::clone(&self.auth_gate),
+            session_manager: Arc::clone(&self.session_manager),
+            throughput: Arc::clone(&self.throughput),
+            audit_log: Arc::clone(&self.audit_log),
+            latency: Arc::clone(&self.latency),
+            idle: Arc::clone(&self.idle),
+            config: self.config.clone(),
+            endpoint_id: self.identity.endpoint_id().to_string(),
+        };
+
+        let quic_task = match quic_endpoint {
+            Some(endpoint) => {
+                let ctx = ctx.clone();
+                Some(tokio::spawn(async move { quic_accept_loop(endpoint, ctx).await }))
+            }
+            None => None,
+        };
+
+        let serve = async {
+            match listener {
+                Some(listener) => tcp_accept_loop(listener, self.tls_acceptor.clone(), ctx).await,
+                None => {
+                    if let Some(task) = quic_task {
+                        task.await.context("QUIC accept loop panicked")?;
                     }
-                };
+                    Ok(())
+                }
+            }
+        };
 
-                let _ = audit_log.log(
-                    AuditEntry::new(AuditEventType::ConnectionAccepted, &endpoint_id)
-                        .with_peer_id(peer_id.to_string())
-                        .with_reason(format!("from {} (pairing_only={})", peer_addr, pairing_only)),
-                ).await;
-
-                // Handle the connection.
-                if let Err(e) = handle_connection(
-                    tls_stream,
-                    peer_id.clone(),
-                    endpoint_id.clone(),
-                    config,
-                    session_manager,
-                    throughput,
-                    auth_gate.clone(),
-                    audit_log.clone(),
-                    pairing_only,
-                )
-                .await
+        let idle_limit = self.config.network.idle_shutdown_sec;
+        if idle_limit == 0 {
+            return serve.await;
+        }
+        let limit = Duration::from_secs(idle_limit);
+        tokio::select! {
+            res = serve => res,
+            () = idle::wait_for_idle(&self.idle, &self.session_manager, limit) => {
+                info!(idle_shutdown_sec = idle_limit, "idle limit reached, shutting down");
+                // `log` flushes before returning, so the entry is on disk.
+                if let Err(e) = self
+                    .audit_log
+                    .log(
+                        AuditEntry::new(
+                            AuditEventType::ServerShutdown,
+                            self.identity.endpoint_id().to_string(),
+                        )
+                            .with_reason(format!(
+                                "idle for {}s with no connections or active sessions",
+                                idle_limit
+                            )),
+                    )
+                    .await
                 {
-                    debug!(peer_id = %peer_id, error = %e, "connection handler finished with error");
+                    warn!(error = %e, "failed to audit idle shutdown");
                 }
-
-                let _ = audit_log.log(
-                    AuditEntry::new(AuditEventType::SessionCompleted, &endpoint_id)
-                        .with_peer_id(peer_id.to_string())
-                        .with_reason("connection closed"),
-                ).await;
-            });
+                Ok(())
+            }
         }
     }
 }
 
 // ---------------------------------------------------------------------------
-// Connection handler
+// Accept loops
 // ---------------------------------------------------------------------------
 
-/// Handle a single authenticated mTLS connection.
-///
-/// Reads length-prefixed JSON frames from the TLS stream, dispatches each
-/// [`LinkMessage`] based on its payload type, and writes response frames back.
-///
-/// If `pairing_only` is true, only `Hello` and `PairRequest` messages are
-/// accepted -- all other message types are rejected until the peer completes
-/// pairing.
-async fn handle_connection(
-    mut stream: tokio_rustls::server::TlsStream<tokio::net::TcpStream>,
-    peer_id: PeerId,
-    endpoint_id: String,
-    config: ReflectorConfig,
+/// Shared state handed to every connection, whatever its transport.
+#[derive(Clone)]
+struct ConnContext {
+    auth_gate: Arc<AuthGate>,
     session_manager: Arc<SessionManager>,
     throughput: Arc<ThroughputEngine>,
-    auth_gate: Arc<AuthGate>,
     audit_log: Arc<AuditLog>,
-    mut pairing_only: bool,
-) -> Result<()> {
+    latency: Arc<LatencyMetrics>,
+    idle: Arc<IdleMonitor>,
+    config: ReflectorConfig,
+    endpoint_id: String,
+}
+
+/// Accept mTLS-over-TCP connections.
+async fn tcp_accept_loop(listener: TcpListener, tls_acceptor: TlsAcceptor, ctx: ConnContext) -> Result<()> {
     loop {
-        // Read a length-prefixed frame.
-        let msg = match read_frame(&mut stream).await {
-            Ok(Some(m)) => m,
-            Ok(None) => {
-                debug!(peer_id = %peer_id, "connection closed by peer");
-                return Ok(());
-            }
+        let (tcp_stream, peer_addr) = match listener.accept().await {
+            Ok(conn) => conn,
             Err(e) => {
-                debug!(peer_id = %peer_id, error = %e, "error reading frame");
-                return Err(e);
+                error!(error = %e, "failed to accept TCP connection");
+                continue;
             }
         };
 
-        let request_id = msg.request_id.clone();
-        debug!(
-            peer_id = %peer_id,
-            request_id = %request_id,
-            "received message"
-        );
+        debug!(peer_addr = %peer_addr, "accepted TCP connection");
 
-        // Dispatch based on payload type and build a response.
-        let response_payload = match msg.payload {
-            MessagePayload::Hello(hello) => {
-                handle_hello(&hello, &config).await
-            }
+        let tls_acceptor = tls_acceptor.clone();
+        let ctx = ctx.clone();
+        tokio::spawn(async move {
+            // TLS handshake.
+            let started = Instant::now();
+            let tls_stream = match tls_acceptor.accept(tcp_stream).await {
+                Ok(s) => s,
+                Err(e) => {
+                    warn!(peer_addr = %peer_addr, error = %e, "TLS handshake failed");
+                    return;
+                }
+            };
 
-            MessagePayload::PairRequest(req) => {
-                let result = handle_pair_request(
-                    &req,
-                    &peer_id,
-                    &endpoint_id,
-                    &auth_gate,
-                    &audit_log,
-                )
-                .await;
-                // If pairing succeeded, upgrade this connection to full access.
-                if let MessagePayload::PairResponse(ref pr) = result {
-                    if pr.success {
-                        pairing_only = false;
-                    }
+            let (_, server_conn) = tls_stream.get_ref();
+            let alpn = server_conn.alpn_protocol().map(|p| p.to_vec());
+            let peer_certs = server_conn.peer_certificates().map(|c| c.to_vec());
+            serve_peer(tls_stream, alpn, peer_certs, peer_addr, started.elapsed(), ctx).await;
+        });
+    }
+}
+
+/// Accept mTLS-over-QUIC connections; each carries one control stream.
+async fn quic_accept_loop(endpoint: quinn::Endpoint, ctx: ConnContext) {
+    while let Some(incoming) = endpoint.accept().await {
+        let peer_addr = incoming.remote_address();
+        debug!(peer_addr = %peer_addr, "accepted QUIC connection");
+
+        let ctx = ctx.clone();
+        tokio::spawn(async move {
+            let started = Instant::now();
+            let conn = match incoming.await {
+                Ok(c) => c,
+                Err(e) => {
+                    warn!(peer_addr = %peer_addr, error = %e, "QUIC handshake failed");
+                    return;
                 }
-                result
-            }
+            };
+            let handshake = started.elapsed();
+            let (alpn, peer_certs) = quic::peer_handshake(&conn);
+            let stream = match quic::accept_control_stream(&conn).await {
+                Ok(s) => s,
+                Err(e) => {
+                    debug!(peer_addr = %peer_addr, error = %e, "QUIC connection closed before control stream");
+                    return;
+                }
+            };
+            serve_peer(stream, alpn, peer_certs, peer_addr, handshake, ctx).await;
+            conn.close(0u32.into(), b"bye");
+        });
+    }
+}
+
+/// Everything after the TLS handshake: ALPN check, peer identification,
+/// authorization, auditing, and the message loop. `handshake` is how long
+/// the TLS (or QUIC) handshake took.
+async fn serve_peer<S>(
+    stream: S,
+    alpn: Option<Vec<u8>>,
+    peer_certs: Option<Vec<CertificateDer<'static>>>,
+    peer_addr: SocketAddr,
+    handshake: Duration,
+    ctx: ConnContext,
+) where
+    S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    let _connection = ctx.idle.connection();
+    ctx.latency.record_handshake(handshake);
+    let audit_log = ctx.audit_log.clone();
+    let endpoint_id = ctx.endpoint_id.clone();
+
+    // Require the configured ALPN before anything else, so generic
+    // TLS clients and scanners never reach the message loop.
+    if let Err(e) = check_negotiated_alpn(alpn.as_deref(), &ctx.config.network.alpn) {
+        warn!(peer_addr = %peer_addr, error = %e, "ALPN check failed, closing connection");
+        return;
+    }
 
-            _ if pairing_only => {
+    // Extract peer certificate from the TLS session.
+    let peer_id = match peer_certs.as_ref().and_then(|certs| certs.first()) {
+        Some(cert_der) => match PeerId::from_cert(cert_der.as_ref()) {
+            Ok(id) => id,
+            Err(e) => {
                 warn!(
-                    peer_id = %peer_id,
-                    request_id = %request_id,
-                    "pairing-only peer sent non-pairing message"
+                    peer_addr = %peer_addr,
+                    error = %e,
+                    "failed to extract peer ID from certificate"
                 );
-                MessagePayload::Error(ErrorResponse {
-                    code: 403,
-                    message: "pairing required before sending other messages".into(),
-                })
+                return;
             }
+        },
+        None => {
+            warn!(peer_addr = %peer_addr, "no peer certificate presented");
+            return;
+        }
+    };
 
-            MessagePayload::SessionRequest(req) => {
-                handle_session_request(
-                    &req,
-                    &peer_id,
-                    &endpoint_id,
-                    &session_manager,
-                    &throughput,
-                    &audit_log,
-                )
-                .await
-            }
+    debug!(peer_id = %peer_id, peer_addr = %peer_addr, "peer identified");
 
-            MessagePayload::SessionClose(close) => {
-                handle_session_close(&close, &peer_id, &endpoint_id, &session_manager, &audit_log).await
-            }
+    // Authorization check -- allow PairingRequired peers through
+    // with restricted access (pairing messages only).
+    let auth_decision = ctx.auth_gate.check(&peer_id).await;
+    let pairing_only = match &auth_decision {
+        AuthDecision::Allowed => false,
+        AuthDecision::PairingRequired => {
+            debug!(peer_id = %peer_id, "peer allowed for pairing only");
+            true
+        }
+        AuthDecision::Denied(reason) => {
+            warn!(peer_id = %peer_id, reason = %reason, "peer not authorized, closing connection");
+            let _ = audit_log.log(
+                AuditEntry::new(AuditEventType::ConnectionDenied, &endpoint_id)
+                    .with_peer_id(peer_id.to_string())
+                    .with_source_addr(peer_addr)
+                    .with_reason("peer not in authorized set"),
+            ).await;
+            return;
+        }
+    };
+
+    let _ = audit_log.log(
+        AuditEntry::new(AuditEventType::ConnectionAccepted, &endpoint_id)
+            .with_peer_id(peer_id.to_string())
+            .with_source_addr(peer_addr)
+            .with_reason(format!("pairing_only={}", pairing_only))
+            .with_params(serde_json::json!({ "handshake_ms": duration_ms(handshake) })),
+    ).await;
+
+    // Handle the connection.
+    if let Err(e) = handle_connection(
+        stream,
+        peer_id.clone(),
+        peer_addr.ip(),
+        ctx,
+        pairing_only,
+    )
+    .await
+    {
+        debug!(peer_id = %peer_id, error = %e, "connection handler finished with error");
+    }
+
+    let _ = audit_log.log(
+        AuditEntry::new(AuditEventType::SessionCompleted, &endpoint_id)
+            .with_peer_id(peer_id.to_string())
+            .with_source_addr(peer_addr)
+            .with_reason("connection closed"),
+    ).await;
+}
 
-            MessagePayload::GetStatus => {
-                handle_get_status(&session_manager).await
+// ---------------------------------------------------------------------------
+// Connection handler
+// ---------------------------------------------------------------------------
+
+/// Handle a single authenticated mTLS connection (TCP or QUIC stream).
+///
+/// Reads length-prefixed frames from the TLS stream, dispatches each
+/// [`LinkMessage`] based on its payload type, and queues the response for the
+/// writer task. Binary data frames of `tunneled` sessions are routed to their
+/// channel in the connection's [`Tunnel`].
+///
+/// If `pairing_only` is true, only `Hello` and `PairRequest` messages are
+/// accepted -- all other message types are rejected until the peer completes
+/// pairing. `peer_ip` is the connection's source address; UDP sessions only
+/// answer it.
+async fn handle_connection<S>(
+    stream: S,
+    peer_id: PeerId,
+    peer_ip: IpAddr,
+    ctx: ConnContext,
+    mut pairing_only: bool,
+) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    let (mut reader, mut writer) = tokio::io::split(stream);
+
+    // Responses and tunnelled data share the stream, so one task writes.
+    let (outbound, mut outbound_rx) = mpsc::channel::<Bytes>(tunnel::OUTBOUND_QUEUE);
+    let writer_task = tokio::spawn(async move {
+        while let Some(payload) = outbound_rx.recv().await {
+            write_payload(&mut writer, &payload).await?;
+            // Batch whatever else is queued before flushing.
+            while let Ok(payload) = outbound_rx.try_recv() {
+                write_payload(&mut writer, &payload).await?;
             }
+            writer
+                .flush()
+                .await
+                .context("failed to flush stream after writing frame")?;
+        }
+        Ok::<_, anyhow::Error>(())
+    });
+    let mut tunnel = Tunnel::new(outbound.clone());
+    let mut protocol: ProtocolVersion = PROTOCOL_VERSION.parse()?;
 
-            MessagePayload::GetPathMeta => handle_get_path_meta(),
+    let result: Result<()> = async {
+        loop {
+            // Read a length-prefixed frame.
+            let frame = match read_raw_frame(&mut reader).await {
+                Ok(Some(f)) => f,
+                Ok(None) => {
+                    debug!(peer_id = %peer_id, "connection closed by peer");
+                    return Ok(());
+                }
+                Err(e) => {
+                    debug!(peer_id = %peer_id, error = %e, "error reading frame");
+                    return Err(e);
+                }
+            };
 
-            // Messages that are responses (not requests) -- unexpected from a client.
-            _ => {
-                warn!(
-                    peer_id = %peer_id,
-                    request_id = %request_id,
-                    "unexpected message type from client"
-                );
-                MessagePayload::Error(ErrorResponse {
-                    code: 400,
-                    message: "unexpected message type".into(),
-                })
+            if tunnel::is_data_frame(&frame) {
+                let (channel, data) = tunnel::parse_data_frame(frame)?;
+                tunnel.deliver(channel, data).await;
+                continue;
             }
-        };
 
-        let response = LinkMessage {
-            request_id,
-            payload: response_payload,
-        };
+            // Size/depth-checked before serde; a bad frame drops the connection.
+            let msg: LinkMessage = wire::decode_message(frame)
+                .context("failed to deserialize LinkMessage from frame")?;
+            let request_id = msg.request_id.clone();
+            debug!(
+                peer_id = %peer_id,
+                request_id = %request_id,
+                "received message"
+            );
+
+            // Dispatch based on payload type and build a response.
+            let response_payload = match msg.payload {
+                MessagePayload::Hello(hello) => match version::negotiate(&hello, version::SUPPORTED) {
+                    Some(negotiated) => {
+                        protocol = negotiated;
+                        handle_hello(&hello, protocol, &ctx.config, &ctx.endpoint_id, &ctx.session_manager).await
+                    }
+                    None => {
+                        warn!(
+                            peer_id = %peer_id,
+                            client_version = %hello.version,
+                            client_versions = ?hello.versions,
+                            "no protocol version in common with client"
+                        );
+                        MessagePayload::Error(ErrorResponse {
+                            code: 426,
+                            message: format!(
+                                "unsupported protocol version; reflector speaks {}",
+                                supported_versions().join(", ")
+                            ),
+                        })
+                    }
+                },
+
+                ref payload if protocol < version::minimum_for(payload) => {
+                    MessagePayload::Error(ErrorResponse {
+                        code: 400,
+                        message: format!(
+                            "message requires protocol {}; connection negotiated {}",
+                            version::minimum_for(payload),
+                            protocol
+                        ),
+                    })
+                }
+
+                // Keepalive: answered on any connection, never audited.
+                MessagePayload::Ping => MessagePayload::Pong,
+
+                MessagePayload::PairRequest(req) => {
+                    let result = handle_pair_request(
+                        &req,
+                        &peer_id,
+                        &ctx.endpoint_id,
+                        &ctx.auth_gate,
+                        &ctx.audit_log,
+                    )
+                    .await;
+                    // If pairing succeeded, upgrade this connection to full access.
+                    if let MessagePayload::PairResponse(ref pr) = result {
+                        if pr.success {
+                            pairing_only = false;
+                        }
+                    }
+                    result
+                }
+
+                _ if pairing_only => {
+                    warn!(
+                        peer_id = %peer_id,
+                        request_id = %request_id,
+                        "pairing-only peer sent non-pairing message"
+                    );
+                    MessagePayload::Error(ErrorResponse {
+                        code: 403,
+                        message: "pairing required before sending other messages".into(),
+                    })
+                }
 
-        if let Err(e) = write_frame(&mut stream, &response).await {
+                MessagePayload::SessionRequest(req) => {
+                    handle_session_request(&req, &peer_id, peer_ip, &ctx).await
+                }
+
+                MessagePayload::SessionClose(close) => {
+                    tunnel.close_test(&close.test_id);
+                    handle_session_close(
+                        &close,
+                        &peer_id,
+                        &ctx.endpoint_id,
+                        &ctx.session_manager,
+                        &ctx.audit_log,
+                    )
+                    .await
+                }
+
+                MessagePayload::TunnelOpen(open) => {
+                    handle_tunnel_open(&open, &peer_id, &ctx.session_manager, &mut tunnel).await
+                }
+
+                MessagePayload::GetStatus => {
+                    handle_get_status(&ctx.session_manager).await
+                }
+
+                MessagePayload::GetPathMeta => handle_get_path_meta(),
+
+                MessagePayload::GetUsageSummary => {
+                    handle_get_usage_summary(&peer_id, &ctx.config, &ctx.session_manager).await
+                }
+
+                MessagePayload::CancelTest(cancel) => {
+                    tunnel.close_test(&cancel.test_id);
+                    handle_cancel_test(
+                        &cancel,
+                        &peer_id,
+                        &ctx.endpoint_id,
+                        &ctx.config,
+                        &ctx.session_manager,
+                        &ctx.audit_log,
+                    )
+                    .await
+                }
+
+                // Messages that are responses (not requests) -- unexpected from a client.
+                _ => {
+                    warn!(
+                        peer_id = %peer_id,
+                        request_id = %request_id,
+                        "unexpected message type from client"
+                    );
+                    MessagePayload::Error(ErrorResponse {
+                        code: 400,
+                        message: "unexpected message type".into(),
+                    })
+                }
+            };
+
+            let response = LinkMessage {
+                request_id,
+                payload: response_payload,
+            };
+
+            if outbound.send(encode_frame(&response)?).await.is_err() {
+                // The writer failed; its error is reported below.
+                return Ok(());
+            }
+        }
+    }
+    .await;
+
+    // Stop the channel tasks and let the writer drain what is queued.
+    drop(tunnel);
+    drop(outbound);
+    match writer_task.await {
+        Ok(Err(e)) => {
             debug!(peer_id = %peer_id, error = %e, "error writing response frame");
-            return Err(e);
+            result.and(Err(e))
         }
+        _ => result,
     }
 }
 
@@ -397,15 +804,19 @@ async fn handle_connection(
 // Message handlers
 // ---------------------------------------------------------------------------
 
-/// Handle a `Hello` message: respond with `ServerHello` including capabilities
-/// and policy summary.
+/// Handle a `Hello` message: respond with `ServerHello` including capabilities,
+/// policy summary and our endpoint ID.
 async fn handle_hello(
     hello: &Hello,
+    protocol: ProtocolVersion,
     config: &ReflectorConfig,
+    endpoint_id: &str,
+    session_manager: &SessionManager,
 ) -> MessagePayload {
     debug!(
         client_version = %hello.version,
         client_features = ?hello.features,
+        negotiated_version = %protocol,
         "handling Hello"
     );
 
@@ -424,8 +835,10 @@ async fn handle_hello(
         allowed_test_types,
     };
 
+    let position = session_manager.position();
     MessagePayload::ServerHello(ServerHello {
-        version: PROTOCOL_VERSION.into(),
+        version: protocol.to_string(),
+        versions: supported_versions(),
         features: vec![
             "throughput".into(),
             "udp_echo".into(),
@@ -433,10 +846,19 @@ async fn handle_hello(
             "pairing".into(),
         ],
         policy_summary: policy,
-        network_position: None, // populated at startup if network detection is available
+        network_position: position.map(|p| p.position.as_str().to_string()),
+        reachable_via: position
+            .and_then(|p| p.reachable_via)
+            .map(|f| f.as_str().to_string()),
+        endpoint_id: Some(endpoint_id.to_string()),
     })
 }
 
+/// Every protocol version this reflector speaks, as sent in `ServerHello`.
+fn supported_versions() -> Vec<String> {
+    version::SUPPORTED.iter().map(|v| v.to_string()).collect()
+}
+
 /// Handle a `PairRequest` message: validate the pairing code and add the peer.
 async fn handle_pair_request(
     req: &PairRequest,
@@ -486,11 +908,18 @@ async fn handle_pair_request(
 async fn handle_session_request(
     req: &SessionRequest,
     peer_id: &PeerId,
-    endpoint_id: &str,
-    session_manager: &SessionManager,
-    throughput: &ThroughputEngine,
-    audit_log: &AuditLog,
+    peer_ip: IpAddr,
+    ctx: &ConnContext,
 ) -> MessagePayload {
+    let ConnContext {
+        session_manager,
+        throughput,
+        audit_log,
+        latency,
+        endpoint_id,
+        ..
+    } = ctx;
+    let started = Instant::now();
     let peer_id_str = peer_id.to_string();
 
     // Request the session (session manager checks governance internally).
@@ -501,65 +930,76 @@ async fn handle_session_request(
         Ok(mut grant) => {
             // If this is a throughput test, start the engine.
             if req.test_type == TestType::Throughput {
-                // Determine port and start iperf3.
+                // Determine port and start iperf3, for the granted duration.
                 let duration = std::time::Duration::from_secs(
-                    req.params.duration_sec.min(60) // Safety cap, though session manager handles policy
+                    grant.duration_sec.unwrap_or(req.params.duration_sec),
                 );
 
-                // Find a free port using internal state to avoid race conditions.
-                let allocated = session_manager.get_allocated_ports().await;
-                let (start, end) = throughput.port_range();
-                let mut port = 0;
-                
-                for p in start..=end {
-                    if !allocated.contains(&p) {
-                        port = p;
-                        break;
-                    }
-                }
-                
-                if port == 0 {
-                     return MessagePayload::SessionDeny(SessionDeny {
-                         reason: DenyReason::ResourceExhausted,
-                         message: "no ports available".into(),
-                         retry_after_sec: Some(10),
-                     });
-                }
+                // Take the next free port in the range; ports just released by
+                // other sessions sit out a cooldown (TIME_WAIT).
+                let Some(port) = session_manager
+                    .allocate_port(&grant.test_id, throughput.port_range())
+                    .await
+                else {
+                    return MessagePayload::SessionDeny(SessionDeny {
+                        reason: DenyReason::ResourceExhausted,
+                        message: "no ports available".into(),
+                        retry_after_sec: Some(10),
+                    });
+                };
 
                 // Add buffer to duration so server outlives client slightly.
                 let server_duration = duration + std::time::Duration::from_secs(5);
 
-                match throughput.start(port, server_duration).await {
+                // Direct-ephemeral clients connect to the token gate on the
+                // granted port; iperf3 then listens on a private loopback port.
+                // The gate's listener is bound before iperf3 starts so the
+                // granted port can't be taken in between.
+                let direct = session_manager.data_plane() == DataPlaneMode::DirectEphemeral;
+                let (engine_port, gate_listener) = if direct {
+                    let reserved = async {
+                        let gate_listener = gate::bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, port))).await?;
+                        let engine = gate::reserve_loopback_port().await?;
+                        anyhow::Ok((engine.local_addr()?.port(), gate_listener))
+                    }
+                    .await;
+                    match reserved {
+                        Ok((engine_port, listener)) => (engine_port, Some(listener)),
+                        Err(e) => {
+                            error!(error = %e, "failed to open data port");
+                            let _ = session_manager.close_session(&grant.test_id).await;
+                            return MessagePayload::SessionDeny(SessionDeny {
+                                reason: DenyReason::ResourceExhausted,
+                                message: format!("failed to open data port: {}", e),
+                                retry_after_sec: Some(10),
+                            });
+                        }
+                    }
+                } else {
+                    (port, None)
+                };
+
+                match throughput.start(engine_port, server_duration).await {
                    Ok((handle, result_rx)) => {
-                       // Update grant with actual port.
-                       grant.port = port;
+                       // Update grant with actual port. Tunneled clients never
+                       // connect to it; their channels are bridged to it here.
+                       if let Some(listener) = gate_listener {
+                           let upstream = SocketAddr::from((Ipv4Addr::LOCALHOST, engine_port));
+                           gate::spawn(listener, upstream, grant.token.clone(), server_duration);
+                           grant.port = port;
+                           grant.token_preamble = true;
+                       }
                        
-                       // Attach handle to session manager for lifecycle management.
+                       // Attach handle and result to the session manager for
+                       // lifecycle management; the result is collected on close.
                        session_manager.attach_test_handle(&grant.test_id, handle).await;
-                       session_manager.set_session_port(&grant.test_id, port).await;
-
-                       // Spawn a background task to await the result and log it.
-                       let audit_log = audit_log.clone(); // Arc clone
-                       let endpoint_id = endpoint_id.to_string();
-                       let test_id = grant.test_id.clone();
-                       let peer_id_str = peer_id_str.clone();
-
-                       tokio::spawn(async move {
-                           match result_rx.await {
-                               Ok(res) => {
-                                   debug!(test_id = %test_id, result = ?res, "engine task completed");
-                               },
-                               Err(e) => {
-                                   warn!(test_id = %test_id, error = %e, "engine task join error");
-                               }
-                           }
-                       });
+                       session_manager.attach_engine_result(&grant.test_id, result_rx).await;
                    },
                    Err(e) => {
                        error!(error = %e, "failed to start throughput engine");
-                       // If engine fails, we should technically return a Deny, or Error.
-                       // But avoiding complex rollback logic here, client will just fail to connect.
-                       // Maybe better to return Deny?
+                       // Drop the session so its port goes into cooldown now
+                       // rather than staying held until the session expires.
+                       let _ = session_manager.close_session(&grant.test_id).await;
                        return MessagePayload::SessionDeny(SessionDeny {
                             reason: DenyReason::ResourceExhausted,
                             message: format!("failed to start engine: {}", e),
@@ -569,11 +1009,22 @@ async fn handle_session_request(
                 }
             }
 
+            if req.test_type == TestType::UdpEcho {
+                if let Err(deny) =
+                    start_udp_echo(req, peer_ip, &mut grant, session_manager, throughput.port_range()).await
+                {
+                    return MessagePayload::SessionDeny(deny);
+                }
+            }
+
+            let setup = started.elapsed();
+            latency.record_session_setup(setup);
             let _ = audit_log
                 .log(
                     AuditEntry::new(AuditEventType::SessionGranted, endpoint_id)
                         .with_peer_id(&peer_id_str)
-                        .with_reason(format!("test_id={}", grant.test_id)),
+                        .with_reason(format!("test_id={}", grant.test_id))
+                        .with_params(serde_json::json!({ "setup_ms": duration_ms(setup) })),
                 )
                 .await;
             MessagePayload::SessionGrant(grant)
@@ -591,7 +1042,54 @@ async fn handle_session_request(
     }
 }
 
+/// Milliseconds, to microsecond precision, for audit entries.
+fn duration_ms(d: Duration) -> f64 {
+    d.as_micros() as f64 / 1000.0
+}
+
+/// Start the UDP echo engine for a granted `UdpEcho` session.
+///
+/// UDP cannot ride the TCP control connection, so even in tunneled mode the
+/// echo listens on a port from the data range and the grant carries it. It
+/// only answers `peer_ip`, the control connection's source.
+async fn start_udp_echo(
+    req: &SessionRequest,
+    peer_ip: IpAddr,
+    grant: &mut SessionGrant,
+    session_manager: &SessionManager,
+    port_range: (u16, u16),
+) -> Result<(), SessionDeny> {
+    let exhausted = |message: String| SessionDeny {
+        reason: DenyReason::ResourceExhausted,
+        message,
+        retry_after_sec: Some(10),
+    };
+    let Some(port) = session_manager.allocate_port(&grant.test_id, port_range).await else {
+        return Err(exhausted("no ports available".into()));
+    };
+
+    let duration =
+        std::time::Duration::from_secs(grant.duration_sec.unwrap_or(req.params.duration_sec) + 5);
+    let dont_fragment = req.params.mtu_probe.unwrap_or(false);
+    match UdpEchoEngine::start(port, peer_ip, duration, UDP_ECHO_MAX_PACKET_RATE, dont_fragment).await {
+        Ok((handle, result_rx)) => {
+            grant.port = handle.port;
+            session_manager.attach_test_handle(&grant.test_id, handle).await;
+            session_manager.attach_engine_result(&grant.test_id, result_rx).await;
+            Ok(())
+        }
+        Err(e) => {
+            error!(error = %e, "failed to start UDP echo engine");
+            let _ = session_manager.close_session(&grant.test_id).await;
+            Err(exhausted(format!("failed to start engine: {}", e)))
+        }
+    }
+}
+
 /// Handle a `SessionClose`: tear down the referenced test session.
+///
+/// Replies with the engine's [`SessionSummary`] when the client asked for it
+/// and one is available, otherwise with `Ok`.
 async fn handle_session_close(
     close: &SessionClose,
     peer_id: &PeerId,
@@ -599,15 +1097,78 @@ async fn handle_session_close(
     session_manager: &SessionManager,
     audit_log: &AuditLog,
 ) -> MessagePayload {
-    let _ = session_manager.close_session(&close.test_id).await;
-    let _ = audit_log
-        .log(
-            AuditEntry::new(AuditEventType::SessionCompleted, endpoint_id)
-                .with_peer_id(peer_id.to_string())
-                .with_reason(format!("test_id={}", close.test_id)),
-        )
-        .await;
-    MessagePayload::Ok
+    let summary = session_manager
+        .close_session(&close.test_id)
+        .await
+        .ok()
+        .flatten();
+
+    let mut entry = AuditEntry::new(AuditEventType::SessionCompleted, endpoint_id)
+        .with_peer_id(peer_id.to_string())
+        .with_test_id(&close.test_id)
+        .with_reason(format!("test_id={}", close.test_id));
+    if let Some(s) = &summary {
+        info!(
+            test_id = %close.test_id,
+            outcome = ?s.outcome,
+            throughput_mbps = s.throughput_mbps(),
+            "session closed"
+        );
+        entry = entry
+            .with_bytes_transferred(s.bytes_transferred)
+            .with_duration_sec(s.duration_sec);
+    }
+    let _ = audit_log.log(entry).await;
+
+    match summary {
+        Some(s) if close.want_summary => MessagePayload::SessionSummary(s),
+        _ => MessagePayload::Ok,
+    }
+}
+
+/// Handle a `TunnelOpen`: bridge a new channel to the session's iperf3
+/// server on loopback.
+async fn handle_tunnel_open(
+    open: &TunnelOpen,
+    peer_id: &PeerId,
+    session_manager: &SessionManager,
+    tunnel: &mut Tunnel,
+) -> MessagePayload {
+    if session_manager.data_plane() != DataPlaneMode::Tunneled {
+        return MessagePayload::Error(ErrorResponse {
+            code: 400,
+            message: "data plane is not tunneled on this reflector".into(),
+        });
+    }
+    let Some(port) = session_manager
+        .session_port(&open.test_id, &peer_id.to_string())
+        .await
+    else {
+        return MessagePayload::Error(ErrorResponse {
+            code: 404,
+            message: format!("no active session {}", open.test_id),
+        });
+    };
+    if tunnel.contains(open.channel) {
+        return MessagePayload::Error(ErrorResponse {
+            code: 409,
+            message: format!("channel {} is already open", open.channel),
+        });
+    }
+
+    match TcpStream::connect((Ipv4Addr::LOCALHOST, port)).await {
+        Ok(local) => {
+            tunnel.open(open.channel, &open.test_id, local);
+            MessagePayload::Ok
+        }
+        Err(e) => {
+            warn!(test_id = %open.test_id, port, error = %e, "failed to reach engine for tunnel channel");
+            MessagePayload::Error(ErrorResponse {
+                code: 502,
+                message: format!("data plane unavailable: {}", e),
+            })
+        }
+    }
 }
 
 /// Handle a `GetStatus` request: build and return a status snapshot.
@@ -618,6 +1179,73 @@ async fn handle_get_status(
     MessagePayload::StatusSnapshot(status)
 }
 
+/// Handle a `GetUsageSummary` request: per-peer bytes and tests for today.
+///
+/// Admin-only: the caller must be listed in `access.admin_peers`.
+async fn handle_get_usage_summary(
+    peer_id: &PeerId,
+    config: &ReflectorConfig,
+    session_manager: &SessionManager,
+) -> MessagePayload {
+    let caller = peer_id.to_string();
+    if !config.access.admin_peers.contains(&caller) {
+        warn!(peer_id = %peer_id, "non-admin peer requested usage summary");
+        return MessagePayload::Error(ErrorResponse {
+            code: 403,
+            message: "usage summary requires an admin peer".into(),
+        });
+    }
+
+    MessagePayload::UsageSummary(session_manager.usage_summary().await)
+}
+
+/// Handle a `CancelTest`: stop a running test before its client closes it.
+///
+/// Peers may cancel their own tests; admin peers may cancel any. The
+/// forced cancellation is audited with the caller and the session owner.
+async fn handle_cancel_test(
+    cancel: &CancelTest,
+    peer_id: &PeerId,
+    endpoint_id: &str,
+    config: &ReflectorConfig,
+    session_manager: &SessionManager,
+    audit_log: &AuditLog,
+) -> MessagePayload {
+    let caller = peer_id.to_string();
+    let is_admin = config.access.admin_peers.contains(&caller);
+
+    let cancelled = match session_manager
+        .cancel_session(&cancel.test_id, &caller, is_admin)
+        .await
+    {
+        Ok(c) => c,
+        Err(e) => {
+            warn!(peer_id = %peer_id, test_id = %cancel.test_id, error = %e, "cancel_test refused");
+            let code = match e {
+                CancelError::NotFound(_) => 404,
+                CancelError::NotOwner(_) => 403,
+            };
+            return MessagePayload::Error(ErrorResponse {
+                code,
+                message: e.to_string(),
+            });
+        }
+    };
+
+    let mut entry = AuditEntry::new(AuditEventType::SessionCancelled, endpoint_id)
+        .with_peer_id(&caller)
+        .with_test_id(&cancel.test_id)
+        .with_reason(format!("cancelled by {}; owner {}", caller, cancelled.owner));
+    if let Some(s) = &cancelled.summary {
+        entry = entry
+            .with_bytes_transferred(s.bytes_transferred)
+            .with_duration_sec(s.duration_sec);
+    }
+    let _ = audit_log.log(entry).await;
+
+    MessagePayload::Ok
+}
+
 /// Handle a `GetPathMeta` request: collect system and path metadata.
 fn handle_get_path_meta() -> MessagePayload {
     let meta = collect_path_meta();
@@ -628,12 +1256,13 @@ fn handle_get_path_meta() -> MessagePayload {
 // Frame I/O helpers
 // ---------------------------------------------------------------------------
 
-/// Read a single length-prefixed JSON frame from the stream.
+/// Read the payload of a single length-prefixed frame (JSON message or
+/// tunnel data frame).
 ///
-/// Returns `Ok(None)` on clean EOF, `Ok(Some(msg))` on success, or an error.
-async fn read_frame<S>(stream: &mut S) -> Result<Option<LinkMessage>>
+/// Returns `Ok(None)` on clean EOF.
+async fn read_raw_frame<S>(stream: &mut S) -> Result<Option<Bytes>>
 where
-    S: AsyncReadExt + Unpin,
+    S: AsyncRead + Unpin,
 {
     // Read 4-byte big-endian length prefix.
     let mut len_buf = [0u8; 4];
@@ -660,17 +1289,11 @@ where
         .await
         .context("failed to read frame payload")?;
 
-    let msg: LinkMessage =
-        serde_json::from_slice(&payload).context("failed to deserialize LinkMessage from frame")?;
-
-    Ok(Some(msg))
+    Ok(Some(Bytes::from(payload)))
 }
 
-/// Write a single length-prefixed JSON frame to the stream.
-async fn write_frame<S>(stream: &mut S, msg: &LinkMessage) -> Result<()>
-where
-    S: AsyncWriteExt + Unpin,
-{
+/// Serialize a message into a frame payload.
+fn encode_frame(msg: &LinkMessage) -> Result<Bytes> {
     let json = serde_json::to_vec(msg).context("failed to serialize response to JSON")?;
 
     if json.len() > MAX_FRAME_SIZE {
@@ -680,23 +1303,25 @@ where
             MAX_FRAME_SIZE
         );
     }
+    Ok(Bytes::from(json))
+}
 
-    let len = json.len() as u32;
+/// Write a length prefix and `payload`, without flushing.
+async fn write_payload<S>(stream: &mut S, payload: &[u8]) -> Result<()>
+where
+    S: AsyncWrite + Unpin,
+{
+    let len = payload.len() as u32;
     stream
         .write_all(&len.to_be_bytes())
         .await
         .context("failed to write frame length prefix")?;
 
     stream
-        .write_all(&json)
+        .write_all(payload)
         .await
         .context("failed to write frame payload")?;
 
-    stream
-        .flush()
-        .await
-        .context("failed to flush stream after writing frame")?;
-
     Ok(())
 }
 
@@ -712,6 +1337,8 @@ mod tests {
     #[test]
     fn test_protocol_version() {
         assert_eq!(PROTOCOL_VERSION, "1.0");
+        let assumed: ProtocolVersion = PROTOCOL_VERSION.parse().unwrap();
+        assert!(version::SUPPORTED.contains(&assumed));
     }
 
     /// Verify frame size constant.
@@ -765,4 +1392,129 @@ mod tests {
         let decoded: LinkMessage = serde_json::from_slice(&buf[4..]).unwrap();
         assert_eq!(decoded.request_id, "test-001");
     }
+
+    /// Only peers listed in `access.admin_peers` get the usage summary.
+    #[tokio::test]
+    async fn test_usage_summary_requires_admin_peer() {
+        let mut config = ReflectorConfig::default();
+        config.access.admin_peers = vec!["PP-AAAA-BBBB-CCCC-0".into()];
+        let governance = Arc::new(GovernanceEngine::new(config.quotas.clone()));
+        let manager = SessionManager::new(config.quotas.clone(), governance, "PP-TEST-0000".into());
+
+        let outsider = PeerId::new("PP-XXXX-YYYY-ZZZZ-1");
+        match handle_get_usage_summary(&outsider, &config, &manager).await {
+            MessagePayload::Error(e) => assert_eq!(e.code, 403),
+            other => panic!("expected 403, got {other:?}"),
+        }
+
+        let admin = PeerId::new("PP-AAAA-BBBB-CCCC-0");
+        assert!(matches!(
+            handle_get_usage_summary(&admin, &config, &manager).await,
+            MessagePayload::UsageSummary(_)
+        ));
+    }
+
+    /// Data frames and JSON frames share the framing and are told apart by
+    /// their first byte.
+    #[tokio::test]
+    async fn test_raw_frames_carry_data_and_messages() {
+        let (mut a, mut b) = tokio::io::duplex(4096);
+        let msg = LinkMessage {
+            request_id: "test-002".into(),
+            payload: MessagePayload::Ok,
+        };
+        write_payload(&mut a, &encode_frame(&msg).unwrap()).await.unwrap();
+        write_payload(&mut a, &tunnel::data_frame(9, b"xyz")).await.unwrap();
+        drop(a);
+
+        let first = read_raw_frame(&mut b).await.unwrap().unwrap();
+        assert!(!tunnel::is_data_frame(&first));
+        let decoded: LinkMessage = serde_json::from_slice(&first).unwrap();
+        assert_eq!(decoded.request_id, "test-002");
+
+        let second = read_raw_frame(&mut b).await.unwrap().unwrap();
+        let (channel, data) = tunnel::parse_data_frame(second).unwrap();
+        assert_eq!(channel, 9);
+        assert_eq!(&data[..], b"xyz");
+
+        assert!(read_raw_frame(&mut b).await.unwrap().is_none());
+    }
+
+    /// Run `handle_connection` on one end of an in-memory stream, as a
+    /// pairing-only peer, and return the other end plus the handler task.
+    async fn spawn_connection(
+        dir: &std::path::Path,
+    ) -> (tokio::io::DuplexStream, tokio::task::JoinHandle<Result<()>>) {
+        let config = ReflectorConfig::default();
+        let governance = Arc::new(GovernanceEngine::new(config.quotas.clone()));
+        let session_manager = Arc::new(SessionManager::new(
+            config.quotas.clone(),
+            governance,
+            "PP-TEST-0000".into(),
+        ));
+        let ctx = ConnContext {
+            auth_gate: Arc::new(AuthGate::new(&config.access)),
+            session_manager,
+            throughput: Arc::new(ThroughputEngine::new(&config.iperf3, (5300, 5310))),
+            audit_log: Arc::new(AuditLog::open(dir.join("audit.log"), false).await.unwrap()),
+            latency: Arc::new(LatencyMetrics::new()),
+            idle: Arc::new(IdleMonitor::new()),
+            config,
+            endpoint_id: "PP-TEST-0000".into(),
+        };
+        let (client, server) = tokio::io::duplex(MAX_FRAME_SIZE + 64);
+        let handler = tokio::spawn(handle_connection(
+            server,
+            PeerId::new("PP-XXXX-YYYY-ZZZZ-1"),
+            Ipv4Addr::LOCALHOST.into(),
+            ctx,
+            true,
+        ));
+        (client, handler)
+    }
+
+    /// Pathological control frames close the connection with an error
+    /// instead of reaching serde or hanging the handler.
+    #[tokio::test]
+    async fn test_connection_drops_pathological_frames() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = format!("{}{}", "[".repeat(100_000), "]".repeat(100_000));
+        let huge = format!(
+            r#"{{"request_id":"x","payload":{{"type":"ping","pad":"{}"}}}}"#,
+            "a".repeat(wire::MAX_CONTROL_FRAME_SIZE)
+        );
+        for frame in [nested.into_bytes(), huge.into_bytes()] {
+            let (mut client, handler) = spawn_connection(dir.path()).await;
+
+            // The connection works before the bad frame arrives.
+            let ping = LinkMessage {
+                request_id: "ping-1".into(),
+                payload: MessagePayload::Ping,
+            };
+            write_payload(&mut client, &encode_frame(&ping).unwrap()).await.unwrap();
+            let reply = read_raw_frame(&mut client).await.unwrap().unwrap();
+            let reply: LinkMessage = serde_json::from_slice(&reply).unwrap();
+            assert_eq!(reply.request_id, "ping-1");
+
+            write_payload(&mut client, &frame).await.unwrap();
+            let res = tokio::time::timeout(Duration::from_secs(5), handler)
+                .await
+                .expect("handler hung on a pathological frame")
+                .unwrap();
+            assert!(res.is_err());
+            assert!(read_raw_frame(&mut client).await.unwrap().is_none());
+        }
+
+        // An oversized length prefix is refused before the payload is read.
+        let (mut client, handler) = spawn_connection(dir.path()).await;
+        client
+            .write_all(&((MAX_FRAME_SIZE as u32) + 1).to_be_bytes())
+            .await
+            .unwrap();
+        let res = tokio::time::timeout(Duration::from_secs(5), handler)
+            .await
+            .expect("handler waited for an oversized payload")
+            .unwrap();
+        assert!(res.is_err());
+    }
 }
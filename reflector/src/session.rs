@@ -4,21 +4,62 @@
 //! governance checks, and provides session lifecycle management.
 
 use std::collections::HashMap;
-use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 use anyhow::Result;
 use chrono::{DateTime, Duration, Utc};
+use thiserror::Error;
 use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
 use tracing::{debug, info, warn};
 use uuid::Uuid;
 
-use crate::config::QuotaConfig;
+use crate::config::{DataPlaneMode, Iperf3Config, QuotaConfig};
+use crate::network::PositionReport;
 use crate::governance::GovernanceEngine;
 use crate::rpc::{
-    ActiveTestInfo, DenyReason, SessionDeny, SessionGrant, StatusSnapshot, TestParams, TestType,
+    ActiveTestInfo, DenyReason, PeerUsage, SessionDeny, SessionGrant, SessionSummary,
+    StatusSnapshot, TestParams, TestType, UsageSummary,
 };
-use crate::engine::TestHandle;
+use crate::engine::{EngineResult, TestHandle};
+
+/// How long `close_session` waits for an engine to finish on its own before
+/// signalling shutdown. iperf3 `--one-off` exits right after the client does,
+/// so this only matters when the client closes mid-test.
+const ENGINE_SETTLE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(3);
+
+/// How long a released data port is kept out of rotation. Covers the
+/// kernel's TIME_WAIT (60 s on Linux), during which re-binding the port
+/// intermittently fails.
+pub const PORT_COOLDOWN: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Data-port rotation state: recently released ports and where the next
+/// search starts, so ports are handed out round-robin rather than lowest-first.
+#[derive(Debug, Default)]
+struct PortState {
+    released: HashMap<u16, Instant>,
+    cursor: Option<u16>,
+}
+
+/// Why a [`SessionManager::cancel_session`] request was refused.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CancelError {
+    #[error("no active session {0}")]
+    NotFound(String),
+    #[error("session {0} belongs to another peer")]
+    NotOwner(String),
+}
+
+/// A session torn down by [`SessionManager::cancel_session`].
+#[derive(Debug)]
+pub struct CancelledSession {
+    /// Peer that requested the session.
+    pub owner: String,
+    /// The engine's summary, if it produced one.
+    pub summary: Option<SessionSummary>,
+}
 
 // ---------------------------------------------------------------------------
 // ActiveSession
@@ -44,6 +85,8 @@ pub struct ActiveSession {
     pub child_pid: Option<u32>,
     /// Handle to the running engine, ensuring cleanup on drop.
     pub test_handle: Option<TestHandle>,
+    /// Task resolving to the engine's result, awaited on close.
+    pub engine_result: Option<JoinHandle<EngineResult>>,
 }
 
 impl ActiveSession {
@@ -83,6 +126,19 @@ pub struct SessionManager {
     started_at: DateTime<Utc>,
     /// Reflector endpoint ID (for status snapshots).
     endpoint_id: String,
+    /// Maintenance mode flag. While set, new sessions are refused but active
+    /// sessions are left to finish.
+    maintenance: Arc<AtomicBool>,
+    /// How granted sessions carry their data plane.
+    data_plane: DataPlaneMode,
+    /// Network position resolved at startup (for hellos and status snapshots).
+    position: Option<PositionReport>,
+    /// Data ports cooling down after release.
+    ports: Mutex<PortState>,
+    /// Streams granted to throughput sessions that don't ask for a count.
+    default_streams: u32,
+    /// Most parallel streams a throughput session may request.
+    max_streams: u32,
 }
 
 impl SessionManager {
@@ -98,7 +154,62 @@ impl SessionManager {
             config,
             started_at: Utc::now(),
             endpoint_id,
+            maintenance: Arc::new(AtomicBool::new(false)),
+            data_plane: DataPlaneMode::Tunneled,
+            position: None,
+            ports: Mutex::new(PortState::default()),
+            default_streams: Iperf3Config::default().default_streams,
+            max_streams: Iperf3Config::default().max_streams,
+        }
+    }
+
+    /// Set the default and maximum stream counts from the iperf3
+    /// configuration.
+    pub fn with_iperf3_limits(mut self, iperf3: &Iperf3Config) -> Self {
+        self.default_streams = iperf3.default_streams.clamp(1, iperf3.max_streams.max(1));
+        self.max_streams = iperf3.max_streams;
+        self
+    }
+
+    /// Set the data-plane mode reported in session grants.
+    pub fn with_data_plane(mut self, mode: DataPlaneMode) -> Self {
+        self.data_plane = mode;
+        self
+    }
+
+    /// The data-plane mode granted sessions use.
+    pub fn data_plane(&self) -> DataPlaneMode {
+        self.data_plane
+    }
+
+    /// Set the network position reported to peers.
+    pub fn with_position(mut self, report: PositionReport) -> Self {
+        self.position = Some(report);
+        self
+    }
+
+    /// The network position reported to peers, if resolved.
+    pub fn position(&self) -> Option<PositionReport> {
+        self.position
+    }
+
+    /// Enable or disable maintenance mode. Returns the previous state.
+    pub fn set_maintenance(&self, enabled: bool) -> bool {
+        let previous = self.maintenance.swap(enabled, Ordering::SeqCst);
+        if previous != enabled {
+            info!(enabled = enabled, "maintenance mode changed");
         }
+        previous
+    }
+
+    /// Whether maintenance mode is currently active.
+    pub fn is_maintenance(&self) -> bool {
+        self.maintenance.load(Ordering::SeqCst)
+    }
+
+    /// Shared handle to the maintenance flag (for the health endpoint).
+    pub fn maintenance_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.maintenance)
     }
 
     /// Request a new test session for a peer.
@@ -112,7 +223,20 @@ impl SessionManager {
         test_type: TestType,
         params: &TestParams,
     ) -> Result<SessionGrant, SessionDeny> {
-        // 1. Check max concurrent sessions.
+        // 0. Refuse new work while draining for maintenance.
+        if self.is_maintenance() {
+            info!(peer_id = peer_id, "session denied: maintenance mode");
+            return Err(SessionDeny {
+                reason: DenyReason::Busy,
+                message: "reflector is in maintenance mode".to_string(),
+                retry_after_sec: Some(300),
+            });
+        }
+
+        // 1. Reject malformed parameters before any limits are consumed.
+        let effective = self.effective_params(peer_id, &test_type, params)?;
+
+        // 2. Check max concurrent sessions.
         {
             let sessions = self.sessions.read().await;
             if sessions.len() as u32 >= self.config.max_concurrent_tests {
@@ -133,7 +257,7 @@ impl SessionManager {
             }
         }
 
-        // 2. Check governance rules (rate limit, cooldown, quota, test type).
+        // 3. Check governance rules (rate limit, cooldown, quota, test type).
         if let Err(reason) = self.governance.check_allowed(peer_id, &test_type).await {
             let (message, retry_after) = match &reason {
                 DenyReason::RateLimited => (
@@ -158,12 +282,10 @@ impl SessionManager {
             });
         }
 
-        // 3. Clamp duration to max.
-        let duration_sec = params
-            .duration_sec
-            .min(self.config.max_test_duration_sec);
+        // 4. Run for the effective (clamped) duration.
+        let duration_sec = effective.duration_sec;
 
-        // 4. Generate session identifiers.
+        // 5. Generate session identifiers.
         let test_id = Uuid::new_v4().to_string();
         let token = Uuid::new_v4().to_string();
         let now = Utc::now();
@@ -171,14 +293,14 @@ impl SessionManager {
             + Duration::seconds(duration_sec as i64)
             + Duration::seconds(5); // grace period
 
-        // 5. Assign a port. For now use a placeholder; the engine will provide
+        // 6. Assign a port. For now use a placeholder; the engine will provide
         //    the actual port during start-up.
         let port = 0_u16; // will be updated by the engine layer
 
-        // 6. Record the test start in governance.
+        // 7. Record the test start in governance.
         self.governance.record_test_start(peer_id).await;
 
-        // 7. Insert the active session.
+        // 8. Insert the active session.
         let session = ActiveSession {
             test_id: test_id.clone(),
             test_type: test_type.clone(),
@@ -189,6 +311,7 @@ impl SessionManager {
             bytes_transferred: AtomicU64::new(0),
             child_pid: None,
             test_handle: None,
+            engine_result: None,
         };
 
         {
@@ -201,59 +324,201 @@ impl SessionManager {
             peer_id = peer_id,
             test_type = ?test_type,
             duration_sec = duration_sec,
+            streams = ?effective.streams,
             "session granted"
         );
 
         Ok(SessionGrant {
             test_id,
-            mode: "direct_ephemeral".to_string(),
+            mode: self.data_plane.as_str().to_string(),
             port,
             token,
             expires_at: expires_at.to_rfc3339(),
+            token_preamble: false,
+            duration_sec: Some(duration_sec),
+            streams: effective.streams,
         })
     }
 
+    /// The parameters a session will actually run with, or a deny for
+    /// requests no engine should run.
+    ///
+    /// Every test needs at least `min_test_duration_sec` and is shortened to
+    /// `max_test_duration_sec`. Throughput tests get `iperf3.default_streams`
+    /// unless they ask for a count, which must be 1 to `iperf3.max_streams`;
+    /// other tests carry no stream count.
+    fn effective_params(
+        &self,
+        peer_id: &str,
+        test_type: &TestType,
+        params: &TestParams,
+    ) -> Result<TestParams, SessionDeny> {
+        let invalid = |message: String| {
+            info!(peer_id = peer_id, reason = %message, "session denied: invalid params");
+            SessionDeny {
+                reason: DenyReason::InvalidParams,
+                message,
+                retry_after_sec: None,
+            }
+        };
+
+        let min = self.config.min_test_duration_sec;
+        if params.duration_sec < min {
+            return Err(invalid(format!(
+                "duration_sec {} is below the minimum of {}s",
+                params.duration_sec, min
+            )));
+        }
+        let mut effective = params.clone();
+        effective.duration_sec = params.duration_sec.min(self.config.max_test_duration_sec);
+
+        effective.streams = match test_type {
+            TestType::Throughput => match params.streams {
+                None => Some(self.default_streams),
+                Some(0) => return Err(invalid("streams must be at least 1".to_string())),
+                Some(streams) if streams > self.max_streams => {
+                    return Err(invalid(format!(
+                        "{} streams requested, at most {} allowed",
+                        streams, self.max_streams
+                    )));
+                }
+                Some(streams) => Some(streams),
+            },
+            TestType::UdpEcho => None,
+        };
+        Ok(effective)
+    }
+
     /// Close and remove an active session.
-    pub async fn close_session(&self, test_id: &str) -> Result<()> {
-        let mut sessions = self.sessions.write().await;
-        if let Some(session) = sessions.remove(test_id) {
-            let bytes = session.bytes_transferred.load(Ordering::Relaxed);
-            info!(
-                test_id = test_id,
-                peer_id = session.peer_id.as_str(),
-                bytes_transferred = bytes,
-                "session closed"
-            );
-            // Record final bytes in governance.
-            self.governance
-                .record_bytes(&session.peer_id, bytes)
-                .await;
-        } else {
+    ///
+    /// If an engine is attached, waits for its result (signalling shutdown
+    /// after [`ENGINE_SETTLE_TIMEOUT`]) and returns the reflector-side summary.
+    pub async fn close_session(&self, test_id: &str) -> Result<Option<SessionSummary>> {
+        let removed = self.sessions.write().await.remove(test_id);
+        let Some(mut session) = removed else {
             warn!(test_id = test_id, "attempted to close unknown session");
+            return Ok(None);
+        };
+        self.release_port(session.port);
+
+        let summary = match session.engine_result.take() {
+            Some(mut result) => {
+                let outcome = match tokio::time::timeout(ENGINE_SETTLE_TIMEOUT, &mut result).await {
+                    Ok(joined) => joined,
+                    Err(_) => {
+                        // Dropping the handle's sender stops the engine.
+                        drop(session.test_handle.take());
+                        result.await
+                    }
+                };
+                match outcome {
+                    Ok(res) => Some(res.to_summary(test_id)),
+                    Err(e) => {
+                        warn!(test_id = test_id, error = %e, "engine task join error");
+                        None
+                    }
+                }
+            }
+            None => None,
+        };
+
+        let bytes = session
+            .bytes_transferred
+            .load(Ordering::Relaxed)
+            .max(summary.as_ref().map_or(0, |s| s.bytes_transferred));
+        info!(
+            test_id = test_id,
+            peer_id = session.peer_id.as_str(),
+            bytes_transferred = bytes,
+            "session closed"
+        );
+        // Record final bytes in governance.
+        self.governance
+            .record_bytes(&session.peer_id, bytes)
+            .await;
+        Ok(summary)
+    }
+
+    /// Forcibly stop a running session on behalf of `caller`.
+    ///
+    /// Only the owning peer may cancel unless `is_admin` is set. The engine
+    /// is signalled to shut down right away rather than given
+    /// [`ENGINE_SETTLE_TIMEOUT`] to finish, then the session is closed as
+    /// in [`close_session`](Self::close_session).
+    pub async fn cancel_session(
+        &self,
+        test_id: &str,
+        caller: &str,
+        is_admin: bool,
+    ) -> Result<CancelledSession, CancelError> {
+        let (owner, handle) = {
+            let mut sessions = self.sessions.write().await;
+            let session = sessions
+                .get_mut(test_id)
+                .ok_or_else(|| CancelError::NotFound(test_id.to_string()))?;
+            if session.peer_id != caller && !is_admin {
+                return Err(CancelError::NotOwner(test_id.to_string()));
+            }
+            (session.peer_id.clone(), session.test_handle.take())
+        };
+        if let Some(handle) = handle {
+            let _ = handle.shutdown_tx.send(());
         }
-        Ok(())
+        info!(test_id = test_id, owner = owner.as_str(), caller = caller, "cancelling session");
+
+        let summary = self.close_session(test_id).await.ok().flatten();
+        Ok(CancelledSession { owner, summary })
     }
 
     /// Get a snapshot of the reflector's current status.
+    ///
+    /// The daily totals come from [`usage_summary`](Self::usage_summary), so
+    /// they include running sessions and survive restarts when governance
+    /// persists its counters.
     pub async fn get_status(&self) -> StatusSnapshot {
+        let usage = self.usage_summary().await;
         let sessions = self.sessions.read().await;
         let uptime = (Utc::now() - self.started_at).num_seconds().max(0) as u64;
 
         let active_test = sessions.values().next().map(|s| s.to_info());
 
-        let bytes_today: u64 = sessions
-            .values()
-            .map(|s| s.bytes_transferred.load(Ordering::Relaxed))
-            .sum();
-
         StatusSnapshot {
             endpoint_id: self.endpoint_id.clone(),
             uptime_sec: uptime,
             active_test,
-            tests_today: 0, // TODO: track completed tests count
-            bytes_today,
-            network_position: None, // populated by server layer
+            tests_today: usage.total_tests,
+            bytes_today: usage.total_bytes,
+            network_position: self.position.map(|p| p.position.as_str().to_string()),
+        }
+    }
+
+    /// Per-peer usage for the current UTC day.
+    ///
+    /// Combines the governance daily counters (completed sessions) with bytes
+    /// from sessions that are still running.
+    pub async fn usage_summary(&self) -> UsageSummary {
+        let mut summary = self.governance.usage_today().await;
+        let sessions = self.sessions.read().await;
+
+        for session in sessions.values() {
+            let bytes = session.bytes_transferred.load(Ordering::Relaxed);
+            match summary.peers.iter_mut().find(|p| p.peer_id == session.peer_id) {
+                Some(peer) => {
+                    peer.bytes_today += bytes;
+                    peer.active_tests += 1;
+                }
+                None => summary.peers.push(PeerUsage {
+                    peer_id: session.peer_id.clone(),
+                    bytes_today: bytes,
+                    tests_today: 0,
+                    active_tests: 1,
+                }),
+            }
+            summary.total_bytes += bytes;
         }
+
+        summary.peers.sort_by(|a, b| a.peer_id.cmp(&b.peer_id));
+        summary
     }
 
     /// Remove all sessions that have exceeded their expiry time.
@@ -270,6 +535,7 @@ impl SessionManager {
 
         for test_id in &expired {
             if let Some(session) = sessions.remove(test_id) {
+                self.release_port(session.port);
                 let bytes = session.bytes_transferred.load(Ordering::Relaxed);
                 warn!(
                     test_id = test_id.as_str(),
@@ -277,6 +543,9 @@ impl SessionManager {
                     bytes_transferred = bytes,
                     "expired session cleaned up"
                 );
+                self.governance
+                    .record_bytes(&session.peer_id, bytes)
+                    .await;
             }
         }
 
@@ -295,14 +564,6 @@ impl SessionManager {
         }
     }
 
-    /// Update the port for an active session (called after engine starts).
-    pub async fn set_session_port(&self, test_id: &str, port: u16) {
-        let mut sessions = self.sessions.write().await;
-        if let Some(session) = sessions.get_mut(test_id) {
-            session.port = port;
-        }
-    }
-
     /// Set the child PID for an active session.
     pub async fn set_child_pid(&self, test_id: &str, pid: u32) {
         let mut sessions = self.sessions.write().await;
@@ -319,14 +580,70 @@ impl SessionManager {
         }
     }
 
+    /// Attach the engine's result task to an active session.
+    pub async fn attach_engine_result(&self, test_id: &str, result: JoinHandle<EngineResult>) {
+        let mut sessions = self.sessions.write().await;
+        if let Some(session) = sessions.get_mut(test_id) {
+            session.engine_result = Some(result);
+        }
+    }
+
     /// Get the number of currently active sessions.
     pub async fn active_count(&self) -> usize {
         self.sessions.read().await.len()
     }
 
-    /// Get a list of all ports currently assigned to active sessions.
-    pub async fn get_allocated_ports(&self) -> Vec<u16> {
-        self.sessions.read().await.values().map(|s| s.port).collect()
+    /// Data port of `test_id` if it is active, owned by `peer_id`, and a
+    /// port has been allocated for its engine.
+    pub async fn session_port(&self, test_id: &str, peer_id: &str) -> Option<u16> {
+        let sessions = self.sessions.read().await;
+        sessions
+            .get(test_id)
+            .filter(|s| s.peer_id == peer_id && s.port != 0)
+            .map(|s| s.port)
+    }
+
+    /// Assign a free data port from `range` (inclusive) to `test_id`.
+    ///
+    /// Skips ports held by active sessions and ports released less than
+    /// [`PORT_COOLDOWN`] ago, and continues after the last port handed out so
+    /// the range is cycled through. Returns `None` if every port is busy or
+    /// cooling down, or the session is gone.
+    pub async fn allocate_port(&self, test_id: &str, range: (u16, u16)) -> Option<u16> {
+        let (start, end) = range;
+        if start == 0 || start > end {
+            return None;
+        }
+        // Hold the sessions lock across the search so concurrent requests
+        // cannot pick the same port.
+        let mut sessions = self.sessions.write().await;
+        let in_use: std::collections::HashSet<u16> = sessions.values().map(|s| s.port).collect();
+
+        let mut ports = self.ports.lock().unwrap();
+        ports
+            .released
+            .retain(|_, released| released.elapsed() < PORT_COOLDOWN);
+
+        let first = match ports.cursor {
+            Some(c) if (start..=end).contains(&c) => c,
+            _ => start,
+        };
+        let port = (first..=end)
+            .chain(start..first)
+            .find(|p| !in_use.contains(p) && !ports.released.contains_key(p))?;
+
+        let session = sessions.get_mut(test_id)?;
+        session.port = port;
+        ports.cursor = Some(if port == end { start } else { port + 1 });
+        debug!(test_id = test_id, port = port, "data port allocated");
+        Some(port)
+    }
+
+    /// Put a session's data port into cooldown.
+    fn release_port(&self, port: u16) {
+        if port != 0 {
+            self.ports.lock().unwrap().released.insert(port, Instant::now());
+        }
     }
 }
 
@@ -342,6 +659,7 @@ mod tests {
         QuotaConfig {
             max_concurrent_tests: 1,
             max_test_duration_sec: 30,
+            min_test_duration_sec: 1,
             max_tests_per_hour_per_peer: 10,
             max_bytes_per_day_per_peer: 10_000_000_000,
             cooldown_sec: 0,
@@ -356,6 +674,7 @@ mod tests {
             protocol: None,
             streams: None,
             reverse: None,
+            mtu_probe: None,
         }
     }
 
@@ -375,9 +694,98 @@ mod tests {
         let grant = result.unwrap();
         assert!(!grant.test_id.is_empty());
         assert!(!grant.token.is_empty());
+        assert_eq!(grant.mode, "tunneled");
+    }
+
+    #[tokio::test]
+    async fn test_grant_mode_follows_data_plane() {
+        let mgr = make_manager().with_data_plane(DataPlaneMode::DirectEphemeral);
+        let grant = mgr
+            .request_session("peer-1", TestType::Throughput, &test_params())
+            .await
+            .unwrap();
         assert_eq!(grant.mode, "direct_ephemeral");
     }
 
+    #[tokio::test]
+    async fn test_session_port_requires_owner_and_engine() {
+        let mgr = make_manager();
+        let grant = mgr
+            .request_session("peer-1", TestType::Throughput, &test_params())
+            .await
+            .unwrap();
+        // No engine port yet.
+        assert_eq!(mgr.session_port(&grant.test_id, "peer-1").await, None);
+
+        assert_eq!(mgr.allocate_port(&grant.test_id, (5201, 5201)).await, Some(5201));
+        assert_eq!(mgr.session_port(&grant.test_id, "peer-1").await, Some(5201));
+        assert_eq!(mgr.session_port(&grant.test_id, "peer-2").await, None);
+        assert_eq!(mgr.session_port("no-such-test", "peer-1").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_request_session_duration_floor() {
+        for (duration_sec, allowed) in [(0, false), (1, true)] {
+            let mgr = make_manager();
+            let params = TestParams { duration_sec, ..test_params() };
+            let result = mgr.request_session("peer-1", TestType::UdpEcho, &params).await;
+            match result {
+                Ok(_) => assert!(allowed, "duration {duration_sec} should be denied"),
+                Err(deny) => {
+                    assert!(!allowed, "duration {duration_sec} should be granted");
+                    assert_eq!(deny.reason, DenyReason::InvalidParams);
+                    assert_eq!(mgr.active_count().await, 0);
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_request_session_stream_limit() {
+        let iperf3 = Iperf3Config { max_streams: 8, ..Iperf3Config::default() };
+        for (streams, allowed) in [(8, true), (9, false), (1000, false)] {
+            let mgr = make_manager().with_iperf3_limits(&iperf3);
+            let params = TestParams { streams: Some(streams), ..test_params() };
+            let result = mgr.request_session("peer-1", TestType::Throughput, &params).await;
+            assert_eq!(result.is_ok(), allowed, "{streams} streams");
+            if let Err(deny) = result {
+                assert_eq!(deny.reason, DenyReason::InvalidParams);
+            }
+        }
+
+        // Streams mean nothing to UDP echo.
+        let mgr = make_manager().with_iperf3_limits(&iperf3);
+        let params = TestParams { streams: Some(1000), ..test_params() };
+        assert!(mgr.request_session("peer-1", TestType::UdpEcho, &params).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_grant_reflects_effective_params() {
+        let iperf3 = Iperf3Config { default_streams: 4, max_streams: 8, ..Iperf3Config::default() };
+
+        // No count: the default. Over-long: clamped to max_test_duration_sec.
+        let mgr = make_manager().with_iperf3_limits(&iperf3);
+        let params = TestParams { duration_sec: 300, ..test_params() };
+        let grant = mgr.request_session("peer-1", TestType::Throughput, &params).await.unwrap();
+        assert_eq!(grant.streams, Some(4));
+        assert_eq!(grant.duration_sec, Some(30));
+
+        let mgr = make_manager().with_iperf3_limits(&iperf3);
+        let params = TestParams { streams: Some(2), ..test_params() };
+        let grant = mgr.request_session("peer-1", TestType::Throughput, &params).await.unwrap();
+        assert_eq!(grant.streams, Some(2));
+        assert_eq!(grant.duration_sec, Some(10));
+
+        let mgr = make_manager().with_iperf3_limits(&iperf3);
+        let params = TestParams { streams: Some(0), ..test_params() };
+        let deny = mgr.request_session("peer-1", TestType::Throughput, &params).await.unwrap_err();
+        assert_eq!(deny.reason, DenyReason::InvalidParams);
+
+        let mgr = make_manager().with_iperf3_limits(&iperf3);
+        let grant = mgr.request_session("peer-1", TestType::UdpEcho, &test_params()).await.unwrap();
+        assert_eq!(grant.streams, None);
+    }
+
     #[tokio::test]
     async fn test_request_session_busy() {
         let mgr = make_manager();
@@ -409,6 +817,90 @@ mod tests {
         assert_eq!(mgr.active_count().await, 0);
     }
 
+    #[tokio::test]
+    async fn test_close_session_returns_engine_summary() {
+        let mgr = make_manager();
+        let grant = mgr
+            .request_session("peer-1", TestType::Throughput, &test_params())
+            .await
+            .unwrap();
+
+        let result = tokio::spawn(async {
+            EngineResult::Completed {
+                bytes_transferred: 1_250_000_000,
+                duration_sec: 10.0,
+                streams_mbps: Vec::new(),
+            }
+        });
+        mgr.attach_engine_result(&grant.test_id, result).await;
+
+        let summary = mgr
+            .close_session(&grant.test_id)
+            .await
+            .unwrap()
+            .expect("engine summary");
+        assert_eq!(summary.test_id, grant.test_id);
+        assert_eq!(summary.outcome, crate::rpc::SessionOutcome::Completed);
+        assert_eq!(summary.bytes_transferred, 1_250_000_000);
+        assert!((summary.throughput_mbps() - 1000.0).abs() < 1e-9);
+
+        // Engine bytes count toward the peer's daily quota.
+        let usage = mgr.usage_summary().await;
+        assert_eq!(usage.total_bytes, 1_250_000_000);
+    }
+
+    #[tokio::test]
+    async fn test_close_session_without_engine_has_no_summary() {
+        let mgr = make_manager();
+        let grant = mgr
+            .request_session("peer-1", TestType::UdpEcho, &test_params())
+            .await
+            .unwrap();
+        assert!(mgr.close_session(&grant.test_id).await.unwrap().is_none());
+        assert!(mgr.close_session("unknown").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_session_ownership() {
+        let mgr = make_manager();
+        let grant = mgr
+            .request_session("peer-1", TestType::UdpEcho, &test_params())
+            .await
+            .unwrap();
+
+        // An engine that only stops when signalled.
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+        let engine = tokio::spawn(async move {
+            let _ = shutdown_rx.await;
+            EngineResult::Completed {
+                bytes_transferred: 42,
+                duration_sec: 0.5,
+                streams_mbps: Vec::new(),
+            }
+        });
+        let handle = TestHandle { test_id: grant.test_id.clone(), port: 0, shutdown_tx };
+        mgr.attach_test_handle(&grant.test_id, handle).await;
+        mgr.attach_engine_result(&grant.test_id, engine).await;
+
+        assert_eq!(
+            mgr.cancel_session(&grant.test_id, "peer-2", false).await.unwrap_err(),
+            CancelError::NotOwner(grant.test_id.clone())
+        );
+        assert_eq!(mgr.active_count().await, 1);
+
+        let started = Instant::now();
+        let cancelled = mgr.cancel_session(&grant.test_id, "admin", true).await.unwrap();
+        assert!(started.elapsed() < ENGINE_SETTLE_TIMEOUT);
+        assert_eq!(cancelled.owner, "peer-1");
+        assert_eq!(cancelled.summary.unwrap().bytes_transferred, 42);
+        assert_eq!(mgr.active_count().await, 0);
+
+        assert_eq!(
+            mgr.cancel_session(&grant.test_id, "peer-1", false).await.unwrap_err(),
+            CancelError::NotFound(grant.test_id.clone())
+        );
+    }
+
     #[tokio::test]
     async fn test_record_bytes() {
         let mgr = make_manager();
@@ -422,6 +914,13 @@ mod tests {
 
         let status = mgr.get_status().await;
         assert_eq!(status.bytes_today, 3000);
+        assert_eq!(status.tests_today, 1);
+
+        // Closing moves the bytes into governance; the daily total holds.
+        mgr.close_session(&grant.test_id).await.unwrap();
+        let status = mgr.get_status().await;
+        assert_eq!(status.bytes_today, 3000);
+        assert_eq!(status.tests_today, 1);
     }
 
     #[tokio::test]
@@ -448,6 +947,60 @@ mod tests {
         assert_eq!(mgr.active_count().await, 0);
     }
 
+    #[tokio::test]
+    async fn test_maintenance_mode_denies_new_sessions() {
+        let mgr = make_manager();
+        let grant = mgr
+            .request_session("peer-1", TestType::UdpEcho, &test_params())
+            .await
+            .unwrap();
+
+        assert!(!mgr.set_maintenance(true));
+        assert!(mgr.is_maintenance());
+
+        // New sessions are refused with Busy.
+        let deny = mgr
+            .request_session("peer-2", TestType::UdpEcho, &test_params())
+            .await
+            .unwrap_err();
+        assert_eq!(deny.reason, DenyReason::Busy);
+        assert!(deny.message.contains("maintenance"));
+
+        // The active session is left alone.
+        assert_eq!(mgr.active_count().await, 1);
+        mgr.close_session(&grant.test_id).await.unwrap();
+
+        // Leaving maintenance mode accepts sessions again.
+        assert!(mgr.set_maintenance(false));
+        assert!(mgr
+            .request_session("peer-2", TestType::UdpEcho, &test_params())
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_usage_summary_includes_active_sessions() {
+        let mgr = make_manager();
+        let grant = mgr
+            .request_session("peer-1", TestType::UdpEcho, &test_params())
+            .await
+            .unwrap();
+        mgr.record_bytes(&grant.test_id, 4096).await;
+
+        let usage = mgr.usage_summary().await;
+        assert_eq!(usage.total_bytes, 4096);
+        assert_eq!(usage.total_tests, 1);
+        assert_eq!(usage.peers.len(), 1);
+        assert_eq!(usage.peers[0].peer_id, "peer-1");
+        assert_eq!(usage.peers[0].active_tests, 1);
+
+        // Once closed, the bytes move into the governance counters.
+        mgr.close_session(&grant.test_id).await.unwrap();
+        let usage = mgr.usage_summary().await;
+        assert_eq!(usage.total_bytes, 4096);
+        assert_eq!(usage.peers[0].active_tests, 0);
+    }
+
     #[tokio::test]
     async fn test_get_status() {
         let mgr = make_manager();
@@ -455,4 +1008,62 @@ mod tests {
         assert_eq!(status.endpoint_id, "PP-TEST-0000");
         assert!(status.active_test.is_none());
     }
+
+    #[tokio::test]
+    async fn test_ports_cool_down_under_churn() {
+        let mgr = make_manager();
+        let range = (6000, 6002);
+        let mut handed_out = Vec::new();
+
+        // Rapidly open and close sessions: each takes the next port in the
+        // range and nothing is reused while it cools down.
+        for i in 0..3 {
+            let grant = mgr
+                .request_session(&format!("peer-{}", i), TestType::Throughput, &test_params())
+                .await
+                .unwrap();
+            let port = mgr.allocate_port(&grant.test_id, range).await.unwrap();
+            handed_out.push(port);
+            mgr.close_session(&grant.test_id).await.unwrap();
+        }
+        assert_eq!(handed_out, vec![6000, 6001, 6002]);
+
+        let grant = mgr
+            .request_session("peer-3", TestType::Throughput, &test_params())
+            .await
+            .unwrap();
+        assert_eq!(mgr.allocate_port(&grant.test_id, range).await, None);
+
+        // Once the cooldown has passed the rotation resumes at the start.
+        {
+            let mut ports = mgr.ports.lock().unwrap();
+            for released in ports.released.values_mut() {
+                *released = released.checked_sub(PORT_COOLDOWN).unwrap();
+            }
+        }
+        assert_eq!(mgr.allocate_port(&grant.test_id, range).await, Some(6000));
+    }
+
+    #[tokio::test]
+    async fn test_allocate_port_skips_active_sessions() {
+        let config = QuotaConfig {
+            max_concurrent_tests: 2,
+            ..test_config()
+        };
+        let governance = Arc::new(GovernanceEngine::new(config.clone()));
+        let mgr = SessionManager::new(config, governance, "PP-TEST-0000".into());
+
+        let a = mgr
+            .request_session("peer-a", TestType::Throughput, &test_params())
+            .await
+            .unwrap();
+        let b = mgr
+            .request_session("peer-b", TestType::Throughput, &test_params())
+            .await
+            .unwrap();
+        assert_eq!(mgr.allocate_port(&a.test_id, (6000, 6001)).await, Some(6000));
+        assert_eq!(mgr.allocate_port(&b.test_id, (6000, 6001)).await, Some(6001));
+        assert_eq!(mgr.session_port(&b.test_id, "peer-b").await, Some(6001));
+        assert_eq!(mgr.allocate_port("no-such-test", (6000, 6001)).await, None);
+    }
 }
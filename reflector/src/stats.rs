@@ -0,0 +1,87 @@
+//! Persistent daily usage counters for the PacketParamedic Reflector.
+//!
+//! The [`GovernanceEngine`](crate::governance::GovernanceEngine) keeps
+//! per-peer bytes and test counts in memory; this module saves them to a
+//! small JSON file next to the identity key so a restart mid-day does not
+//! zero the day's accounting (or hand every peer a fresh byte quota).
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// File, next to the identity key, holding the current day's counters.
+pub const USAGE_FILE: &str = "usage.json";
+
+/// Per-peer counters for one UTC day, as stored in [`USAGE_FILE`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DailyUsage {
+    /// UTC midnight the counters belong to.
+    pub day_start: DateTime<Utc>,
+    pub peer_bytes: HashMap<String, u64>,
+    pub peer_tests: HashMap<String, u32>,
+}
+
+/// Read the usage file. A missing or unreadable file yields `None` (and a
+/// warning for the latter); accounting then starts from zero.
+pub fn load(path: &Path) -> Option<DailyUsage> {
+    let raw = std::fs::read_to_string(path).ok()?;
+    match serde_json::from_str(&raw) {
+        Ok(usage) => Some(usage),
+        Err(e) => {
+            warn!(path = %path.display(), error = %e, "ignoring unreadable usage file");
+            None
+        }
+    }
+}
+
+/// Write the usage file atomically (temp file + rename), so a crash
+/// mid-write never leaves a truncated file behind.
+pub fn save(path: &Path, usage: &DailyUsage) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create directory {}", parent.display()))?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, serde_json::to_vec(usage)?)
+        .with_context(|| format!("failed to write {}", tmp.display()))?;
+    std::fs::rename(&tmp, path)
+        .with_context(|| format!("failed to replace usage file {}", path.display()))?;
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_load_roundtrip() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("nested").join(USAGE_FILE);
+        let usage = DailyUsage {
+            day_start: Utc::now(),
+            peer_bytes: HashMap::from([("peer-a".to_string(), 1234)]),
+            peer_tests: HashMap::from([("peer-a".to_string(), 3)]),
+        };
+
+        assert!(load(&path).is_none());
+        save(&path, &usage).unwrap();
+        assert_eq!(load(&path), Some(usage));
+        assert!(!path.with_extension("json.tmp").exists());
+    }
+
+    #[test]
+    fn test_corrupt_file_is_ignored() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join(USAGE_FILE);
+        std::fs::write(&path, "{not json").unwrap();
+        assert!(load(&path).is_none());
+    }
+}
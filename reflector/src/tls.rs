@@ -1,6 +1,8 @@
 //! mTLS configuration for the PacketParamedic Reflector.
 //!
-//! Both server and client configs enforce TLS 1.3 only and use the `pp-link/1` ALPN.
+//! Both server and client configs enforce TLS 1.3 only and use the configured ALPN
+//! (`pp-link/1` by default). The server refuses handshakes that offer a different
+//! protocol, and [`check_negotiated_alpn`] rejects clients that offered none.
 //! Certificate verification is intentionally permissive — identity is verified at the
 //! application layer by extracting the peer's public key from the presented certificate.
 
@@ -17,8 +19,8 @@ use rustls::{
     ClientConfig, DigitallySignedStruct, DistinguishedName, Error, ServerConfig, SignatureScheme,
 };
 
-/// ALPN protocol identifier for the Paramedic Link protocol.
-const ALPN_PP_LINK: &[u8] = b"pp-link/1";
+/// Default ALPN protocol identifier for the Paramedic Link protocol.
+pub const ALPN_PP_LINK: &str = "pp-link/1";
 
 // ---------------------------------------------------------------------------
 // Server-side: custom ClientCertVerifier
@@ -175,8 +177,12 @@ impl ServerCertVerifier for AcceptAnyServerCert {
 /// - TLS 1.3 only
 /// - Requires client certificates (mTLS)
 /// - Uses [`AcceptAnyClientCert`] — authorization happens at the app layer
-/// - ALPN: `pp-link/1`
-pub fn build_server_config(cert_der: Vec<u8>, key_der: Vec<u8>) -> Result<rustls::ServerConfig> {
+/// - ALPN: `alpn` only; clients offering other protocols fail the handshake
+pub fn build_server_config(
+    cert_der: Vec<u8>,
+    key_der: Vec<u8>,
+    alpn: &str,
+) -> Result<rustls::ServerConfig> {
     let provider = default_provider();
     let verifier = Arc::new(AcceptAnyClientCert::new(&provider));
 
@@ -190,7 +196,7 @@ pub fn build_server_config(cert_der: Vec<u8>, key_der: Vec<u8>) -> Result<rustls
         .with_single_cert(vec![cert], key)
         .context("failed to configure server certificate")?;
 
-    config.alpn_protocols = vec![ALPN_PP_LINK.to_vec()];
+    config.alpn_protocols = vec![alpn.as_bytes().to_vec()];
 
     Ok(config)
 }
@@ -200,8 +206,12 @@ pub fn build_server_config(cert_der: Vec<u8>, key_der: Vec<u8>) -> Result<rustls
 /// - TLS 1.3 only
 /// - Presents the given client certificate (for mTLS)
 /// - Uses [`AcceptAnyServerCert`] — identity verified via endpoint-ID
-/// - ALPN: `pp-link/1`
-pub fn build_client_config(cert_der: Vec<u8>, key_der: Vec<u8>) -> Result<rustls::ClientConfig> {
+/// - Offers ALPN `alpn`
+pub fn build_client_config(
+    cert_der: Vec<u8>,
+    key_der: Vec<u8>,
+    alpn: &str,
+) -> Result<rustls::ClientConfig> {
     let provider = default_provider();
     let verifier = Arc::new(AcceptAnyServerCert::new(&provider));
 
@@ -216,11 +226,28 @@ pub fn build_client_config(cert_der: Vec<u8>, key_der: Vec<u8>) -> Result<rustls
         .with_client_auth_cert(vec![cert], key)
         .context("failed to configure client certificate")?;
 
-    config.alpn_protocols = vec![ALPN_PP_LINK.to_vec()];
+    config.alpn_protocols = vec![alpn.as_bytes().to_vec()];
 
     Ok(config)
 }
 
+/// Verify that a completed handshake negotiated `expected`.
+///
+/// rustls only rejects *mismatched* ALPN offers; a client that offers no
+/// ALPN at all completes the handshake with `None`. Callers must close such
+/// connections before reading the first frame.
+pub fn check_negotiated_alpn(negotiated: Option<&[u8]>, expected: &str) -> Result<()> {
+    match negotiated {
+        Some(proto) if proto == expected.as_bytes() => Ok(()),
+        Some(proto) => anyhow::bail!(
+            "negotiated ALPN '{}' does not match '{}'",
+            String::from_utf8_lossy(proto),
+            expected
+        ),
+        None => anyhow::bail!("client did not negotiate ALPN '{}'", expected),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -236,15 +263,60 @@ mod tests {
     #[test]
     fn test_build_server_config() {
         let (cert_der, key_der) = generate_test_cert();
-        let config = build_server_config(cert_der, key_der).expect("should build server config");
-        assert_eq!(config.alpn_protocols, vec![ALPN_PP_LINK.to_vec()]);
+        let config = build_server_config(cert_der, key_der, ALPN_PP_LINK)
+            .expect("should build server config");
+        assert_eq!(config.alpn_protocols, vec![ALPN_PP_LINK.as_bytes().to_vec()]);
     }
 
     #[test]
     fn test_build_client_config() {
         let (cert_der, key_der) = generate_test_cert();
-        let config = build_client_config(cert_der, key_der).expect("should build client config");
-        assert_eq!(config.alpn_protocols, vec![ALPN_PP_LINK.to_vec()]);
+        let config = build_client_config(cert_der, key_der, ALPN_PP_LINK)
+            .expect("should build client config");
+        assert_eq!(config.alpn_protocols, vec![ALPN_PP_LINK.as_bytes().to_vec()]);
+    }
+
+    /// Run a TLS handshake over an in-memory pipe and return the ALPN the
+    /// server negotiated, or the server-side handshake error.
+    async fn handshake(client_alpn: Option<&str>) -> Result<Option<Vec<u8>>> {
+        let (server_cert, server_key) = generate_test_cert();
+        let (client_cert, client_key) = generate_test_cert();
+
+        let server_config = build_server_config(server_cert, server_key, ALPN_PP_LINK)?;
+        let mut client_config =
+            build_client_config(client_cert, client_key, client_alpn.unwrap_or(ALPN_PP_LINK))?;
+        if client_alpn.is_none() {
+            client_config.alpn_protocols.clear();
+        }
+
+        let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(server_config));
+        let connector = tokio_rustls::TlsConnector::from(Arc::new(client_config));
+        let (client_io, server_io) = tokio::io::duplex(16 * 1024);
+
+        let server = tokio::spawn(async move { acceptor.accept(server_io).await });
+        let name = ServerName::try_from("reflector").unwrap();
+        let _client = connector.connect(name, client_io).await;
+
+        let stream = server.await.unwrap()?;
+        Ok(stream.get_ref().1.alpn_protocol().map(|p| p.to_vec()))
+    }
+
+    #[tokio::test]
+    async fn test_matching_alpn_accepted() {
+        let negotiated = handshake(Some(ALPN_PP_LINK)).await.expect("handshake");
+        assert!(check_negotiated_alpn(negotiated.as_deref(), ALPN_PP_LINK).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_mismatched_alpn_rejected_at_handshake() {
+        assert!(handshake(Some("h2")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_missing_alpn_rejected_after_handshake() {
+        let negotiated = handshake(None).await.expect("handshake completes without ALPN");
+        assert!(negotiated.is_none());
+        assert!(check_negotiated_alpn(negotiated.as_deref(), ALPN_PP_LINK).is_err());
     }
 
     #[test]
@@ -0,0 +1,220 @@
+//! Data-plane tunnelling over the control connection.
+//!
+//! In `tunneled` mode iperf3 listens on loopback only and its connections are
+//! carried inside the mTLS control connection instead of on their own ports.
+//! The client accepts iperf3's connections on a local port and announces each
+//! one with a `TunnelOpen` message; the reflector bridges it to the session's
+//! iperf3 server. From then on both sides exchange binary data frames that
+//! share the control channel's length prefix:
+//!
+//! ```text
+//! [u32 length][0x00][u32 channel][bytes ...]
+//! ```
+//!
+//! JSON control frames always start with `{`, so the leading `0x00` tells the
+//! two apart. A data frame with no bytes marks end-of-stream for its direction.
+
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+use bytes::{BufMut, Bytes, BytesMut};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::debug;
+
+/// First payload byte of a data frame.
+pub const DATA_FRAME_TAG: u8 = 0x00;
+
+/// Tag byte plus channel ID.
+const HEADER_LEN: usize = 5;
+
+/// Largest chunk read from a local socket into one data frame.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Frames buffered per channel before the connection reader waits.
+const CHANNEL_QUEUE: usize = 32;
+
+/// Frames buffered for the control connection's writer.
+pub const OUTBOUND_QUEUE: usize = 64;
+
+/// Whether a frame payload is a data frame rather than a JSON message.
+pub fn is_data_frame(payload: &[u8]) -> bool {
+    payload.first() == Some(&DATA_FRAME_TAG)
+}
+
+/// Build a data-frame payload (without the length prefix).
+pub fn data_frame(channel: u32, data: &[u8]) -> Bytes {
+    let mut buf = BytesMut::with_capacity(HEADER_LEN + data.len());
+    buf.put_u8(DATA_FRAME_TAG);
+    buf.put_u32(channel);
+    buf.extend_from_slice(data);
+    buf.freeze()
+}
+
+/// Split a data-frame payload into its channel and bytes.
+pub fn parse_data_frame(payload: Bytes) -> Result<(u32, Bytes)> {
+    if payload.len() < HEADER_LEN || !is_data_frame(&payload) {
+        bail!("malformed data frame ({} bytes)", payload.len());
+    }
+    let channel = u32::from_be_bytes([payload[1], payload[2], payload[3], payload[4]]);
+    Ok((channel, payload.slice(HEADER_LEN..)))
+}
+
+struct Channel {
+    test_id: String,
+    /// Bytes from the peer, written to the local socket.
+    tx: mpsc::Sender<Bytes>,
+    tasks: [JoinHandle<()>; 2],
+}
+
+impl Channel {
+    fn abort(&self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+/// The open channels of one control connection.
+///
+/// Bytes read from local sockets are sent to `outbound` as data-frame
+/// payloads; the connection's writer adds the length prefix.
+pub struct Tunnel {
+    outbound: mpsc::Sender<Bytes>,
+    channels: HashMap<u32, Channel>,
+}
+
+impl Tunnel {
+    pub fn new(outbound: mpsc::Sender<Bytes>) -> Self {
+        Self {
+            outbound,
+            channels: HashMap::new(),
+        }
+    }
+
+    pub fn contains(&self, channel: u32) -> bool {
+        self.channels.contains_key(&channel)
+    }
+
+    /// Bridge `local` as `channel` of session `test_id`.
+    pub fn open(&mut self, channel: u32, test_id: &str, local: TcpStream) {
+        let _ = local.set_nodelay(true);
+        let (mut rd, mut wr) = local.into_split();
+        let (tx, mut rx) = mpsc::channel::<Bytes>(CHANNEL_QUEUE);
+
+        let outbound = self.outbound.clone();
+        let upstream = tokio::spawn(async move {
+            let mut buf = vec![0u8; CHUNK_SIZE];
+            loop {
+                let n = rd.read(&mut buf).await.unwrap_or(0);
+                if outbound.send(data_frame(channel, &buf[..n])).await.is_err() || n == 0 {
+                    break;
+                }
+            }
+        });
+        let downstream = tokio::spawn(async move {
+            while let Some(data) = rx.recv().await {
+                if data.is_empty() || wr.write_all(&data).await.is_err() {
+                    break;
+                }
+            }
+            let _ = wr.shutdown().await;
+        });
+
+        debug!(channel, test_id, "tunnel channel opened");
+        let previous = self.channels.insert(
+            channel,
+            Channel {
+                test_id: test_id.to_string(),
+                tx,
+                tasks: [upstream, downstream],
+            },
+        );
+        if let Some(old) = previous {
+            old.abort();
+        }
+    }
+
+    /// Deliver bytes received from the peer for `channel`.
+    ///
+    /// Frames for unknown or finished channels are dropped: they are in
+    /// flight when either side closes.
+    pub async fn deliver(&mut self, channel: u32, data: Bytes) {
+        if let Some(ch) = self.channels.get(&channel) {
+            let _ = ch.tx.send(data).await;
+        }
+    }
+
+    /// Close every channel of session `test_id`.
+    pub fn close_test(&mut self, test_id: &str) {
+        self.channels.retain(|_, ch| {
+            let keep = ch.test_id != test_id;
+            if !keep {
+                ch.abort();
+            }
+            keep
+        });
+    }
+}
+
+impl Drop for Tunnel {
+    fn drop(&mut self) {
+        for ch in self.channels.values() {
+            ch.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_data_frame_round_trip() {
+        let payload = data_frame(7, b"abc");
+        assert!(is_data_frame(&payload));
+        assert!(!is_data_frame(br#"{"request_id":"r"}"#));
+        let (channel, data) = parse_data_frame(payload).unwrap();
+        assert_eq!(channel, 7);
+        assert_eq!(&data[..], b"abc");
+
+        let (_, eof) = parse_data_frame(data_frame(1, b"")).unwrap();
+        assert!(eof.is_empty());
+        assert!(parse_data_frame(Bytes::from_static(&[0, 0, 1])).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_channel_bridges_local_socket() {
+        // A local echo server stands in for iperf3.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut s, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 5];
+            s.read_exact(&mut buf).await.unwrap();
+            s.write_all(&buf).await.unwrap();
+        });
+
+        let (out_tx, mut out_rx) = mpsc::channel(OUTBOUND_QUEUE);
+        let mut tunnel = Tunnel::new(out_tx);
+        tunnel.open(3, "test-1", TcpStream::connect(addr).await.unwrap());
+        tunnel.deliver(3, Bytes::from_static(b"hello")).await;
+
+        let mut echoed = Vec::new();
+        loop {
+            let (channel, data) = parse_data_frame(out_rx.recv().await.unwrap()).unwrap();
+            assert_eq!(channel, 3);
+            if data.is_empty() {
+                break;
+            }
+            echoed.extend_from_slice(&data);
+        }
+        assert_eq!(echoed, b"hello");
+
+        tunnel.close_test("test-1");
+        assert!(!tunnel.contains(3));
+    }
+}
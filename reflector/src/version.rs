@@ -0,0 +1,154 @@
+//! Paramedic Link protocol versions and per-connection negotiation.
+//!
+//! A client lists every version it speaks in its [`Hello`]; the reflector
+//! picks the highest one both sides support and answers with it in the
+//! `ServerHello`. The chosen version is kept for the lifetime of the
+//! connection and consulted when dispatching messages, so a new message
+//! type (or a changed one) can ship behind a version bump without a flag
+//! day: old clients keep negotiating the version they know.
+//!
+//! The ALPN identifier (`pp-link/1`) only carries the major version;
+//! minor versions are negotiated here, in-band.
+
+use std::fmt;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::rpc::{Hello, MessagePayload};
+
+/// A `major.minor` protocol version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ProtocolVersion {
+    pub major: u16,
+    pub minor: u16,
+}
+
+impl ProtocolVersion {
+    pub const V1_0: Self = Self { major: 1, minor: 0 };
+    /// Adds `ping`/`pong` keepalives.
+    pub const V1_1: Self = Self { major: 1, minor: 1 };
+    /// Adds `cancel_test`.
+    pub const V1_2: Self = Self { major: 1, minor: 2 };
+}
+
+/// Versions this reflector speaks, oldest first.
+pub const SUPPORTED: &[ProtocolVersion] =
+    &[ProtocolVersion::V1_0, ProtocolVersion::V1_1, ProtocolVersion::V1_2];
+
+/// Error returned when a version string is not `major.minor`.
+#[derive(Debug, Error)]
+#[error("invalid protocol version {0:?}")]
+pub struct ParseVersionError(String);
+
+impl FromStr for ProtocolVersion {
+    type Err = ParseVersionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseVersionError(s.to_string());
+        let (major, minor) = s.trim().split_once('.').ok_or_else(err)?;
+        Ok(Self {
+            major: major.parse().map_err(|_| err())?,
+            minor: minor.parse().map_err(|_| err())?,
+        })
+    }
+}
+
+impl fmt::Display for ProtocolVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// Pick the highest version in `supported` that the client offered.
+///
+/// The client's offer is `hello.versions` plus `hello.version`; clients
+/// predating negotiation send only the latter. Entries that don't parse
+/// are ignored. `None` means there is no version in common.
+pub fn negotiate(hello: &Hello, supported: &[ProtocolVersion]) -> Option<ProtocolVersion> {
+    hello
+        .versions
+        .iter()
+        .chain(std::iter::once(&hello.version))
+        .filter_map(|v| v.parse::<ProtocolVersion>().ok())
+        .filter(|v| supported.contains(v))
+        .max()
+}
+
+/// Lowest protocol version in which `payload` is a valid request.
+///
+/// Messages added after 1.0 list the version that introduced them here.
+pub fn minimum_for(payload: &MessagePayload) -> ProtocolVersion {
+    match payload {
+        MessagePayload::Ping => ProtocolVersion::V1_1,
+        MessagePayload::CancelTest(_) => ProtocolVersion::V1_2,
+        _ => ProtocolVersion::V1_0,
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hello(version: &str, versions: &[&str]) -> Hello {
+        Hello {
+            version: version.into(),
+            versions: versions.iter().map(|v| v.to_string()).collect(),
+            features: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_parse_and_order() {
+        let v: ProtocolVersion = "1.10".parse().unwrap();
+        assert_eq!(v, ProtocolVersion { major: 1, minor: 10 });
+        assert_eq!(v.to_string(), "1.10");
+        assert!(v > "1.9".parse().unwrap());
+        assert!(v < "2.0".parse().unwrap());
+        assert!("1".parse::<ProtocolVersion>().is_err());
+        assert!("1.x".parse::<ProtocolVersion>().is_err());
+    }
+
+    #[test]
+    fn test_negotiate_picks_highest_common() {
+        let supported = ["1.0".parse().unwrap(), "1.1".parse().unwrap(), "2.0".parse().unwrap()];
+
+        let v = negotiate(&hello("1.0", &["1.0", "1.1", "1.2"]), &supported);
+        assert_eq!(v, Some("1.1".parse().unwrap()));
+
+        // A client predating negotiation only sends `version`.
+        assert_eq!(negotiate(&hello("1.0", &[]), &supported), Some(ProtocolVersion::V1_0));
+
+        assert_eq!(negotiate(&hello("3.0", &["3.0", "bogus"]), &supported), None);
+    }
+
+    #[test]
+    fn test_ping_needs_1_1() {
+        assert_eq!(minimum_for(&MessagePayload::Ping), ProtocolVersion::V1_1);
+        assert_eq!(minimum_for(&MessagePayload::GetStatus), ProtocolVersion::V1_0);
+        assert_eq!(
+            negotiate(&hello("1.0", &["1.0", "1.1"]), SUPPORTED),
+            Some(ProtocolVersion::V1_1)
+        );
+    }
+
+    #[test]
+    fn test_cancel_test_needs_1_2() {
+        let cancel = MessagePayload::CancelTest(crate::rpc::CancelTest { test_id: "t".into() });
+        assert_eq!(minimum_for(&cancel), ProtocolVersion::V1_2);
+        assert_eq!(
+            negotiate(&hello("1.0", &["1.0", "1.1", "1.2"]), SUPPORTED),
+            Some(ProtocolVersion::V1_2)
+        );
+    }
+
+    #[test]
+    fn test_supported_is_sorted() {
+        assert!(SUPPORTED.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(negotiate(&hello("1.0", &[]), SUPPORTED), Some(ProtocolVersion::V1_0));
+    }
+}
@@ -3,6 +3,11 @@
 //! Frames are encoded as a 4-byte big-endian length prefix followed by a JSON payload.
 //! The length field describes only the payload size (not including itself).
 //! Maximum frame size is 1 MB.
+//!
+//! The 1 MB limit exists for tunnelled data frames. JSON control messages are
+//! a few hundred bytes, so [`check_control_frame`] holds them to a much
+//! smaller size and nesting depth before serde sees them: a peer can't make
+//! the parser chew through a megabyte of `[[[[...` or a giant string.
 
 use anyhow::{Context, Result};
 use bytes::{Bytes, BytesMut};
@@ -13,6 +18,13 @@ use tokio_util::codec::LengthDelimitedCodec;
 /// Maximum frame payload size: 1 MB.
 const MAX_FRAME_SIZE: usize = 1_048_576;
 
+/// Maximum size of a JSON control message.
+pub const MAX_CONTROL_FRAME_SIZE: usize = 64 * 1024;
+
+/// Maximum array/object nesting of a JSON control message. Real messages
+/// nest four levels at most.
+pub const MAX_JSON_DEPTH: usize = 16;
+
 /// Length-prefixed frame codec for the Paramedic Link protocol.
 ///
 /// Wraps a [`LengthDelimitedCodec`] configured for:
@@ -79,10 +91,54 @@ pub fn encode_message<T: Serialize>(msg: &T) -> Result<Bytes> {
 ///
 /// This is the inverse of [`encode_message`]. The caller is responsible for
 /// stripping the 4-byte length prefix before passing the payload here.
+///
+/// The payload must pass [`check_control_frame`] first.
 pub fn decode_message<T: DeserializeOwned>(frame: Bytes) -> Result<T> {
+    check_control_frame(&frame)?;
     serde_json::from_slice(&frame).context("failed to deserialize message from JSON frame")
 }
 
+/// Reject a control payload that is too large or nested too deeply.
+///
+/// A single linear pass over the bytes, tracking only whether we are inside
+/// a string, so it costs nothing like a parse and can't recurse.
+pub fn check_control_frame(payload: &[u8]) -> Result<()> {
+    if payload.len() > MAX_CONTROL_FRAME_SIZE {
+        anyhow::bail!(
+            "control message ({} bytes) exceeds maximum ({} bytes)",
+            payload.len(),
+            MAX_CONTROL_FRAME_SIZE
+        );
+    }
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for &b in payload {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if b == b'\\' {
+                escaped = true;
+            } else if b == b'"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match b {
+            b'"' => in_string = true,
+            b'{' | b'[' => {
+                depth += 1;
+                if depth > MAX_JSON_DEPTH {
+                    anyhow::bail!("control message nests deeper than {} levels", MAX_JSON_DEPTH);
+                }
+            }
+            b'}' | b']' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -146,4 +202,48 @@ mod tests {
         let decoded: Empty = decode_message(payload).expect("decode should succeed");
         assert_eq!(original, decoded);
     }
+
+    #[test]
+    fn test_deeply_nested_json_rejected() {
+        let nested = format!("{}{}", "[".repeat(1_000), "]".repeat(1_000));
+        let err = decode_message::<serde_json::Value>(Bytes::from(nested)).unwrap_err();
+        assert!(err.to_string().contains("nests deeper"));
+
+        // Unclosed nesting is caught just the same.
+        let open = format!("{{\"a\":{}", "{\"a\":".repeat(MAX_JSON_DEPTH));
+        assert!(check_control_frame(open.as_bytes()).is_err());
+
+        let ok = format!("{}1{}", "[".repeat(MAX_JSON_DEPTH), "]".repeat(MAX_JSON_DEPTH));
+        assert!(decode_message::<serde_json::Value>(Bytes::from(ok)).is_ok());
+    }
+
+    #[test]
+    fn test_brackets_inside_strings_are_not_nesting() {
+        let msg = TestMessage {
+            greeting: format!("\\\"{}", "[{".repeat(1000)),
+            count: 1,
+        };
+        let payload = serde_json::to_vec(&msg).unwrap();
+        let decoded: TestMessage = decode_message(Bytes::from(payload)).unwrap();
+        assert_eq!(decoded, msg);
+    }
+
+    #[test]
+    fn test_huge_control_message_rejected() {
+        let msg = TestMessage {
+            greeting: "x".repeat(MAX_CONTROL_FRAME_SIZE),
+            count: 0,
+        };
+        // Fits a frame, but not a control message.
+        let framed = encode_message(&msg).unwrap();
+        let err = decode_message::<TestMessage>(framed.slice(4..)).unwrap_err();
+        assert!(err.to_string().contains("exceeds maximum"));
+    }
+
+    #[test]
+    fn test_malformed_json_rejected() {
+        for garbage in [&b"{\"greeting\":"[..], b"\xff\xfe\x00", b"{}}}}", b"null"] {
+            assert!(decode_message::<TestMessage>(Bytes::from_static(garbage)).is_err());
+        }
+    }
 }
@@ -0,0 +1,185 @@
+//! Backend benchmark harness.
+//!
+//! Runs every `AcceleratedOp` on every available `Backend` over the same
+//! synthetic latency dataset, so users on different Pi revisions can see
+//! whether the GPU actually beats NEON for their data sizes.
+
+use crate::accel::ops::{HistogramInput, HistogramOp, StatsInput, StatsOp};
+use crate::accel::{AccelMetadata, AcceleratedOp, AccelerationManager, Backend};
+use serde::Serialize;
+
+/// Result of benchmarking one op on one backend at one dataset size.
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchEntry {
+    pub op: &'static str,
+    pub backend: Backend,
+    pub samples: usize,
+    /// Successful timed iterations.
+    pub iterations: u32,
+    pub min_us: u64,
+    pub mean_us: u64,
+    pub max_us: u64,
+    /// Samples processed per second at the mean latency, in millions.
+    pub msamples_per_sec: f64,
+    /// Set when the backend failed (e.g. shader not compiled); timings are 0.
+    pub error: Option<String>,
+}
+
+/// Full benchmark report, suitable for collecting across a fleet.
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchReport {
+    pub backends: Vec<Backend>,
+    pub iterations: u32,
+    pub entries: Vec<BenchEntry>,
+}
+
+/// Histogram bin count used by the benchmark.
+const BENCH_BINS: usize = 256;
+
+/// Benchmark all ops on all available backends for each dataset size.
+pub fn run(manager: &AccelerationManager, sizes: &[usize], iterations: u32) -> BenchReport {
+    let backends = manager.available_backends();
+    let iterations = iterations.max(1);
+    let mut entries = Vec::new();
+
+    for &size in sizes {
+        let data = synthetic_latencies(size);
+
+        let stats_input = StatsInput { data: data.clone() };
+        for &backend in &backends {
+            entries.push(bench_op(
+                manager,
+                backend,
+                "stats",
+                &StatsOp,
+                &stats_input,
+                size,
+                iterations,
+            ));
+        }
+
+        let hist_input = match HistogramInput::uniform(data, 0.0, 200.0, BENCH_BINS) {
+            Ok(input) => input,
+            Err(_) => continue,
+        };
+        for &backend in &backends {
+            entries.push(bench_op(
+                manager,
+                backend,
+                "histogram",
+                &HistogramOp,
+                &hist_input,
+                size,
+                iterations,
+            ));
+        }
+    }
+
+    BenchReport {
+        backends,
+        iterations,
+        entries,
+    }
+}
+
+/// Time `iterations` runs of `op` on `backend`; stops at the first error.
+fn bench_op<Op, Input, Output>(
+    manager: &AccelerationManager,
+    backend: Backend,
+    name: &'static str,
+    op: &Op,
+    input: &Input,
+    samples: usize,
+    iterations: u32,
+) -> BenchEntry
+where
+    Op: AcceleratedOp<Input, Output>,
+{
+    let mut runs: Vec<AccelMetadata> = Vec::with_capacity(iterations as usize);
+    let mut error = None;
+
+    for _ in 0..iterations {
+        match manager.execute_on(backend, op, input) {
+            Ok((_, meta)) => runs.push(meta),
+            Err(e) => {
+                error = Some(e.to_string());
+                break;
+            }
+        }
+    }
+
+    let durations: Vec<u64> = runs.iter().map(|m| m.duration_us).collect();
+    let mean_us = if durations.is_empty() {
+        0
+    } else {
+        durations.iter().sum::<u64>() / durations.len() as u64
+    };
+    let msamples_per_sec = if mean_us > 0 {
+        samples as f64 / mean_us as f64
+    } else {
+        0.0
+    };
+
+    BenchEntry {
+        op: name,
+        backend,
+        samples,
+        iterations: runs.len() as u32,
+        min_us: durations.iter().copied().min().unwrap_or(0),
+        mean_us,
+        max_us: durations.iter().copied().max().unwrap_or(0),
+        msamples_per_sec,
+        error,
+    }
+}
+
+/// Deterministic latency-like samples (ms): a 5-40 ms base with occasional
+/// spikes, generated from a fixed-seed LCG so every host benchmarks the
+/// same data.
+pub fn synthetic_latencies(n: usize) -> Vec<f32> {
+    let mut state: u32 = 0x5EED_1234;
+    (0..n)
+        .map(|_| {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            let unit = (state >> 8) as f32 / (1u32 << 24) as f32;
+            if state % 100 == 0 {
+                100.0 + unit * 100.0
+            } else {
+                5.0 + unit * 35.0
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_synthetic_latencies_deterministic() {
+        let a = synthetic_latencies(1000);
+        assert_eq!(a, synthetic_latencies(1000));
+        assert!(a.iter().all(|&x| (5.0..=200.0).contains(&x)));
+    }
+
+    #[test]
+    fn test_bench_covers_every_backend_and_op() {
+        let manager = AccelerationManager::new();
+        let report = run(&manager, &[1024, 4096], 2);
+
+        let per_size = report.backends.len() * 2;
+        assert_eq!(report.entries.len(), per_size * 2);
+
+        let scalar: Vec<_> = report
+            .entries
+            .iter()
+            .filter(|e| e.backend == Backend::Scalar)
+            .collect();
+        assert_eq!(scalar.len(), 4);
+        assert!(scalar.iter().all(|e| e.error.is_none() && e.iterations == 2));
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["entries"][0]["op"], "stats");
+        assert_eq!(json["entries"][0]["samples"], 1024);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::accel::ops::StatsOutput;
+use crate::accel::ops::{HistogramOutput, StatsOutput};
 use anyhow::Result;
 
 /// Scalar CPU reference implementation for F32 statistics.
@@ -40,3 +40,63 @@ pub fn stats_f32(data: &[f32]) -> Result<StatsOutput> {
         variance: variance.max(0.0), // Avoid negative float precision errors
     })
 }
+
+/// Scalar CPU reference implementation for histogram binning.
+///
+/// Bin `i` covers `[edges[i], edges[i + 1])`; the last bin also includes the
+/// upper edge. NaN samples are skipped.
+pub fn histogram_f32(data: &[f32], edges: &[f32]) -> Result<HistogramOutput> {
+    let bins = edges.len().saturating_sub(1);
+    anyhow::ensure!(bins > 0, "histogram needs at least one bin");
+
+    let (lo, hi) = (edges[0], edges[bins]);
+    let mut out = HistogramOutput::empty(bins);
+
+    for &val in data {
+        if val.is_nan() {
+            continue;
+        }
+        if val < lo {
+            out.underflow += 1;
+        } else if val > hi {
+            out.overflow += 1;
+        } else {
+            // Index of the last edge <= val, clamped so `hi` lands in the final bin.
+            let idx = edges.partition_point(|&e| e <= val) - 1;
+            out.counts[idx.min(bins - 1)] += 1;
+        }
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_basic_binning() {
+        let edges = [0.0, 10.0, 20.0, 30.0];
+        let data = [0.0, 5.0, 9.999, 10.0, 25.0, 30.0];
+        let out = histogram_f32(&data, &edges).unwrap();
+        assert_eq!(out.counts, vec![3, 1, 2]);
+        assert_eq!(out.underflow, 0);
+        assert_eq!(out.overflow, 0);
+    }
+
+    #[test]
+    fn test_histogram_out_of_range_and_nan() {
+        let edges = [1.0, 2.0];
+        let data = [0.5, 1.5, 2.5, f32::NAN, f32::INFINITY, f32::NEG_INFINITY];
+        let out = histogram_f32(&data, &edges).unwrap();
+        assert_eq!(out.counts, vec![1]);
+        assert_eq!(out.underflow, 2);
+        assert_eq!(out.overflow, 2);
+    }
+
+    #[test]
+    fn test_histogram_rejects_empty_edges() {
+        assert!(histogram_f32(&[1.0], &[]).is_err());
+        assert!(histogram_f32(&[1.0], &[1.0]).is_err());
+    }
+}
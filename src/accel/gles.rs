@@ -1,10 +1,37 @@
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use glow::HasContext;
-use std::rc::Rc;
+use glutin::api::egl::context::{NotCurrentContext, PossiblyCurrentContext};
+use glutin::context::PossiblyCurrentGlContext;
+use std::sync::{Arc, Mutex, MutexGuard};
 
 /// OpenGL ES 3.1 Backend via EGL (Headless)
+///
+/// `new` leaves the EGL context un-bound, so the backend can be built on one
+/// thread and used on another; callers bind it with [`GlesBackend::make_current`]
+/// before issuing GL.
 pub struct GlesBackend {
-    pub gl: Rc<glow::Context>,
+    pub gl: Arc<glow::Context>,
+    /// The EGL context, parked un-bound between dispatches.
+    context: Mutex<Option<NotCurrentContext>>,
+}
+
+/// The GLES context bound to the calling thread; released on drop.
+///
+/// Holds the backend's context lock, so dispatches are serialized.
+pub struct CurrentContext<'a> {
+    slot: MutexGuard<'a, Option<NotCurrentContext>>,
+    current: Option<PossiblyCurrentContext>,
+}
+
+impl Drop for CurrentContext<'_> {
+    fn drop(&mut self) {
+        if let Some(current) = self.current.take() {
+            match current.make_not_current() {
+                Ok(context) => *self.slot = Some(context),
+                Err(e) => tracing::warn!(error = %e, "failed to release GLES context"),
+            }
+        }
+    }
 }
 
 impl GlesBackend {
@@ -38,6 +65,25 @@ impl GlesBackend {
         bail!("GLES initialization requires active EGL display (not present in build env)")
     }
 
+    /// Bind the context to the calling thread (surfaceless; compute needs no
+    /// framebuffer) until the returned guard is dropped.
+    pub fn make_current(&self) -> Result<CurrentContext<'_>> {
+        let mut slot = self
+            .context
+            .lock()
+            .map_err(|_| anyhow!("GLES context lock poisoned"))?;
+        let context = slot
+            .take()
+            .ok_or_else(|| anyhow!("GLES context was lost by an earlier failed bind"))?;
+        let current = context
+            .make_current_surfaceless()
+            .context("failed to make GLES context current")?;
+        Ok(CurrentContext {
+            slot,
+            current: Some(current),
+        })
+    }
+
     /// Compile a compute shader (or fragment shader for GLES < 3.1)
     ///
     /// # Safety
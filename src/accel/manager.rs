@@ -34,13 +34,30 @@ pub trait AcceleratedOp<Input, Output> {
 
 use crate::accel::gles::GlesBackend;
 use crate::accel::vulkan::VulkanBackend;
+use crate::accel::AccelMetadata;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+/// How long a GPU backend may take to initialize before it is treated as hung.
+/// Headless Pis without a GPU context can block inside the driver.
+pub const BACKEND_INIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Outcome of initializing one backend.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct BackendInit {
+    pub backend: Backend,
+    pub available: bool,
+    /// Why the backend is unavailable (init error, timeout, panic).
+    pub reason: Option<String>,
+    pub init_ms: u64,
+}
 
 /// Manager to handle backend selection and dispatch
 pub struct AccelerationManager {
     vulkan: Option<VulkanBackend>,
     gles: Option<GlesBackend>,
-    #[allow(dead_code)]
     neon_available: bool,
+    init: Vec<BackendInit>,
 }
 
 impl Default for AccelerationManager {
@@ -51,27 +68,43 @@ impl Default for AccelerationManager {
 
 impl AccelerationManager {
     pub fn new() -> Self {
+        Self::with_init_timeout(BACKEND_INIT_TIMEOUT)
+    }
+
+    /// Initialize backends, giving each GPU backend at most `timeout`.
+    /// A backend that fails, panics, or times out is marked unavailable and
+    /// ops fall back to NEON/scalar; startup is never blocked or aborted.
+    pub fn with_init_timeout(timeout: Duration) -> Self {
         // Attempt runtime initialization
-        let vulkan = unsafe {
-            match VulkanBackend::new() {
-                Ok(v) => Some(v),
-                Err(e) => {
-                    warn!("Vulkan initialization failed: {}", e);
-                    None
-                }
-            }
-        };
+        let (vulkan, vulkan_init) = timed_init(Backend::Vulkan, || {
+            // SAFETY: VulkanBackend::new only loads libvulkan and creates
+            // handles owned by the returned backend.
+            init_with_timeout("accel-vulkan-init", timeout, || unsafe { VulkanBackend::new() })
+        });
 
-        let gles = match GlesBackend::new() {
-            Ok(g) => Some(g),
-            Err(e) => {
-                warn!("GLES initialization failed: {}", e);
-                None
-            }
-        };
+        let (gles, gles_init) = timed_init(Backend::Gles, || {
+            init_with_timeout("accel-gles-init", timeout, GlesBackend::new)
+        });
 
         let neon_available = true; // Always true on Pi 5 (Cortex-A76)
 
+        let init = vec![
+            vulkan_init,
+            gles_init,
+            BackendInit {
+                backend: Backend::Neon,
+                available: neon_available,
+                reason: None,
+                init_ms: 0,
+            },
+            BackendInit {
+                backend: Backend::Scalar,
+                available: true,
+                reason: None,
+                init_ms: 0,
+            },
+        ];
+
         info!(
             "AccelerationManager initialized. Vulkan: {}, GLES: {}, NEON: {}",
             vulkan.is_some(),
@@ -83,9 +116,15 @@ impl AccelerationManager {
             vulkan,
             gles,
             neon_available,
+            init,
         }
     }
 
+    /// Per-backend initialization outcome, in `Backend` order.
+    pub fn init_report(&self) -> &[BackendInit] {
+        &self.init
+    }
+
     /// Select the best backend for a given operation and payload size
     pub fn select_backend(&self, payload_size_bytes: usize) -> Backend {
         // Simple heuristic:
@@ -116,8 +155,53 @@ impl AccelerationManager {
         self.gles.as_ref()
     }
 
+    /// Backends that initialized on this host, fastest-first.
+    pub fn available_backends(&self) -> Vec<Backend> {
+        let mut backends = Vec::with_capacity(4);
+        if self.vulkan.is_some() {
+            backends.push(Backend::Vulkan);
+        }
+        if self.gles.is_some() {
+            backends.push(Backend::Gles);
+        }
+        if self.neon_available {
+            backends.push(Backend::Neon);
+        }
+        backends.push(Backend::Scalar);
+        backends
+    }
+
+    /// Execute an operation on one specific backend, bypassing selection,
+    /// fallback, and verification. Used for benchmarking.
+    pub fn execute_on<Op, Input, Output>(
+        &self,
+        backend: Backend,
+        op: &Op,
+        input: &Input,
+    ) -> Result<(Output, AccelMetadata)>
+    where
+        Op: AcceleratedOp<Input, Output>,
+    {
+        let start = std::time::Instant::now();
+        let output = match backend {
+            Backend::Vulkan => op.run_vulkan(input, self),
+            Backend::Gles => op.run_gles(input, self),
+            Backend::Neon => op.run_neon(input),
+            Backend::Scalar => op.run_scalar(input),
+        }?;
+
+        Ok((
+            output,
+            AccelMetadata {
+                backend,
+                duration_us: start.elapsed().as_micros() as u64,
+            },
+        ))
+    }
+
     /// Execute an operation using the best available backend.
     /// Includes optional verification against scalar reference (debug mode).
+    #[cfg_attr(not(debug_assertions), allow(unused_assignments))]
     pub fn execute<Op, Input, Output>(
         &self,
         op: &Op,
@@ -128,13 +212,25 @@ impl AccelerationManager {
         Op: AcceleratedOp<Input, Output>,
         Output: PartialEq + std::fmt::Debug,
     {
-        let backend = self.select_backend(payload_size);
+        let mut backend = self.select_backend(payload_size);
         let result = match backend {
             Backend::Vulkan => op.run_vulkan(input, self),
             Backend::Gles => op.run_gles(input, self),
             Backend::Neon => op.run_neon(input),
             Backend::Scalar => op.run_scalar(input),
-        }?;
+        };
+
+        // A GPU path that fails (missing shader, driver error) falls back to NEON
+        // rather than failing the caller.
+        let result = match result {
+            Ok(r) => r,
+            Err(e) if matches!(backend, Backend::Vulkan | Backend::Gles) => {
+                warn!("{:?} backend failed ({}), falling back to NEON", backend, e);
+                backend = Backend::Neon;
+                op.run_neon(input)?
+            }
+            Err(e) => return Err(e),
+        };
 
         // In debug builds, verify against scalar reference
         #[cfg(debug_assertions)]
@@ -157,3 +253,115 @@ impl AccelerationManager {
         Ok(result)
     }
 }
+
+/// Run a backend initializer, recording its outcome and duration.
+fn timed_init<T>(
+    backend: Backend,
+    init: impl FnOnce() -> std::result::Result<T, String>,
+) -> (Option<T>, BackendInit) {
+    let start = Instant::now();
+    let result = init();
+    let init_ms = start.elapsed().as_millis() as u64;
+
+    match result {
+        Ok(b) => (
+            Some(b),
+            BackendInit {
+                backend,
+                available: true,
+                reason: None,
+                init_ms,
+            },
+        ),
+        Err(reason) => {
+            warn!(?backend, %reason, "backend unavailable, falling back to NEON/scalar");
+            (
+                None,
+                BackendInit {
+                    backend,
+                    available: false,
+                    reason: Some(reason),
+                    init_ms,
+                },
+            )
+        }
+    }
+}
+
+/// Run `f` on a named worker thread and wait at most `timeout` for it.
+///
+/// On timeout the worker is left detached; if it ever finishes, its result
+/// is dropped. A panicking worker is reported as an error.
+fn init_with_timeout<T, F>(name: &str, timeout: Duration, f: F) -> std::result::Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel();
+    std::thread::Builder::new()
+        .name(name.to_string())
+        .spawn(move || {
+            let _ = tx.send(f());
+        })
+        .map_err(|e| format!("failed to spawn {}: {}", name, e))?;
+
+    match rx.recv_timeout(timeout) {
+        Ok(Ok(value)) => Ok(value),
+        Ok(Err(e)) => Err(format!("{:#}", e)),
+        Err(RecvTimeoutError::Timeout) => {
+            Err(format!("initialization timed out after {}s", timeout.as_secs_f32()))
+        }
+        Err(RecvTimeoutError::Disconnected) => Err("initialization panicked".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_init_with_timeout_returns_value() {
+        let r = init_with_timeout("t-ok", Duration::from_secs(1), || Ok(42));
+        assert_eq!(r, Ok(42));
+    }
+
+    #[test]
+    fn test_init_with_timeout_reports_error() {
+        let r: std::result::Result<(), String> =
+            init_with_timeout("t-err", Duration::from_secs(1), || anyhow::bail!("no device"));
+        assert_eq!(r, Err("no device".to_string()));
+    }
+
+    #[test]
+    fn test_init_with_timeout_does_not_block_on_hang() {
+        let start = Instant::now();
+        let r: std::result::Result<(), String> =
+            init_with_timeout("t-hang", Duration::from_millis(50), || {
+                std::thread::sleep(Duration::from_secs(5));
+                Ok(())
+            });
+        assert!(r.unwrap_err().contains("timed out"));
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_init_with_timeout_catches_panic() {
+        let r: std::result::Result<(), String> =
+            init_with_timeout("t-panic", Duration::from_secs(1), || panic!("driver crashed"));
+        assert_eq!(r, Err("initialization panicked".to_string()));
+    }
+
+    #[test]
+    fn test_manager_always_has_cpu_fallbacks() {
+        let manager = AccelerationManager::with_init_timeout(Duration::from_millis(500));
+        let report = manager.init_report();
+        assert_eq!(report.len(), 4);
+        assert!(report
+            .iter()
+            .any(|b| b.backend == Backend::Scalar && b.available));
+        for b in report {
+            assert_eq!(b.available, b.reason.is_none());
+        }
+        assert!(manager.available_backends().contains(&Backend::Scalar));
+    }
+}
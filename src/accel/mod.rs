@@ -11,6 +11,7 @@
 //! - `neon_cpu` (ARM NEON)
 //! - `scalar_cpu` (Reference)
 
+pub mod bench;
 pub mod cpu;
 pub mod gles;
 pub mod manager;
@@ -23,7 +24,7 @@ pub mod ops;
 pub use manager::{AcceleratedOp, AccelerationManager, Backend};
 
 /// Metadata recording which acceleration path was used.
-#[derive(Debug, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize)]
 pub struct AccelMetadata {
     pub backend: Backend,
     pub duration_us: u64,
@@ -1,4 +1,4 @@
-use crate::accel::ops::StatsOutput;
+use crate::accel::ops::{HistogramOutput, StatsOutput};
 use anyhow::Result;
 use std::arch::aarch64::*;
 
@@ -84,6 +84,80 @@ pub fn stats_f32(data: &[f32]) -> Result<StatsOutput> {
     }
 }
 
+/// NEON-assisted histogram binning for F32 buffer.
+///
+/// Bin indices are estimated four lanes at a time with `(x - lo) * scale`,
+/// then corrected against the edges with exact comparisons, so the result
+/// matches `cpu::histogram_f32` exactly.
+pub fn histogram_f32(data: &[f32], edges: &[f32]) -> Result<HistogramOutput> {
+    let bins = edges.len().saturating_sub(1);
+    anyhow::ensure!(bins > 0, "histogram needs at least one bin");
+
+    let lo = edges[0];
+    let hi = edges[bins];
+    let scale = if hi > lo { bins as f32 / (hi - lo) } else { 0.0 };
+    let mut out = HistogramOutput::empty(bins);
+
+    let len = data.len();
+    let mut i = 0;
+
+    unsafe {
+        let v_lo = vdupq_n_f32(lo);
+        let v_scale = vdupq_n_f32(scale);
+        let v_last = vdupq_n_u32((bins - 1) as u32);
+        let mut hints = [0u32; 4];
+
+        while i + 4 <= len {
+            let val = vld1q_f32(data.as_ptr().add(i));
+
+            // Float -> u32 conversion saturates: negatives and NaN become 0.
+            let est = vcvtq_u32_f32(vmulq_f32(vsubq_f32(val, v_lo), v_scale));
+            vst1q_u32(hints.as_mut_ptr(), vminq_u32(est, v_last));
+
+            for (lane, &hint) in hints.iter().enumerate() {
+                bin_with_hint(&mut out, edges, *data.get_unchecked(i + lane), hint as usize);
+            }
+            i += 4;
+        }
+    }
+
+    // Remainder: estimate in scalar, same correction.
+    while i < len {
+        let val = data[i];
+        let hint = (((val - lo) * scale) as usize).min(bins - 1);
+        bin_with_hint(&mut out, edges, val, hint);
+        i += 1;
+    }
+
+    Ok(out)
+}
+
+/// Count `val` into `out`, starting the edge search at `hint`.
+fn bin_with_hint(out: &mut HistogramOutput, edges: &[f32], val: f32, hint: usize) {
+    let bins = edges.len() - 1;
+    if val.is_nan() {
+        return;
+    }
+    if val < edges[0] {
+        out.underflow += 1;
+        return;
+    }
+    if val > edges[bins] {
+        out.overflow += 1;
+        return;
+    }
+
+    // Walk to the last edge <= val; the estimate is usually exact or off by one.
+    let mut idx = hint;
+    while idx > 0 && val < edges[idx] {
+        idx -= 1;
+    }
+    while idx + 1 < bins && val >= edges[idx + 1] {
+        idx += 1;
+    }
+    out.counts[idx] += 1;
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -132,4 +206,27 @@ mod tests {
         let epsilon = 1e-5;
         assert!((neon_res.mean - scalar_res.mean).abs() < epsilon);
     }
+
+    #[test]
+    fn test_neon_histogram_matches_scalar() {
+        let input: Vec<f32> = (0..10_003).map(|i| ((i * 7919) % 1000) as f32 * 0.37).collect();
+        let edges: Vec<f32> = (0..=64).map(|i| i as f32 * 5.0).collect();
+        assert_eq!(
+            histogram_f32(&input, &edges).unwrap(),
+            cpu::histogram_f32(&input, &edges).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_neon_histogram_edge_values() {
+        // Exact edges, out-of-range and NaN samples must bin identically.
+        let edges = vec![1.0, 1.5, 2.0, 4.0, 8.0];
+        let input = vec![
+            0.0, 1.0, 1.5, 2.0, 4.0, 8.0, 8.000001, f32::NAN, -3.0, 1.4999999, 3.9999998,
+        ];
+        assert_eq!(
+            histogram_f32(&input, &edges).unwrap(),
+            cpu::histogram_f32(&input, &edges).unwrap()
+        );
+    }
 }
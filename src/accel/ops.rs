@@ -61,3 +61,223 @@ impl AcceleratedOp<StatsInput, StatsOutput> for StatsOp {
         crate::accel::cpu::stats_f32(&input.data)
     }
 }
+
+/// Input for histogram binning over a fixed set of bin edges.
+///
+/// `edges` has `bins + 1` monotonically non-decreasing entries. Bin `i`
+/// covers `[edges[i], edges[i + 1])`, except the last bin, which also
+/// includes `edges[bins]` so the dataset maximum is never counted as overflow.
+#[derive(Debug)]
+pub struct HistogramInput {
+    pub data: Vec<f32>,
+    pub edges: Vec<f32>,
+}
+
+impl HistogramInput {
+    /// Build an input with `bins` equal-width bins spanning `[min, max]`.
+    pub fn uniform(data: Vec<f32>, min: f32, max: f32, bins: usize) -> Result<Self> {
+        anyhow::ensure!(bins > 0, "histogram needs at least one bin");
+        anyhow::ensure!(
+            min.is_finite() && max.is_finite() && max > min,
+            "invalid histogram range [{}, {}]",
+            min,
+            max
+        );
+
+        let width = (max - min) / bins as f32;
+        let mut edges: Vec<f32> = (0..bins).map(|i| min + width * i as f32).collect();
+        edges.push(max);
+        Ok(Self { data, edges })
+    }
+
+    /// Number of bins described by `edges`.
+    pub fn bins(&self) -> usize {
+        self.edges.len().saturating_sub(1)
+    }
+}
+
+/// Output of histogram binning.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct HistogramOutput {
+    /// Per-bin sample counts (`edges.len() - 1` entries).
+    pub counts: Vec<u64>,
+    /// Samples below `edges[0]`.
+    pub underflow: u64,
+    /// Samples above `edges[bins]`.
+    pub overflow: u64,
+}
+
+impl HistogramOutput {
+    /// An all-zero output with `bins` bins.
+    pub fn empty(bins: usize) -> Self {
+        Self {
+            counts: vec![0; bins],
+            underflow: 0,
+            overflow: 0,
+        }
+    }
+}
+
+/// GLSL ES 3.1 compute shader for histogram binning.
+///
+/// Bin lookup is a binary search over the edges using comparisons only, so the
+/// GPU result is bit-for-bit identical to the scalar reference.
+/// `counts` layout: `[underflow, overflow, bin0, bin1, ...]`.
+const HISTOGRAM_GLSL: &str = r#"#version 310 es
+layout(local_size_x = 256) in;
+layout(std430, binding = 0) readonly buffer Samples { float samples[]; };
+layout(std430, binding = 1) readonly buffer Edges { float edges[]; };
+layout(std430, binding = 2) buffer Counts { uint counts[]; };
+uniform uint u_len;
+uniform uint u_bins;
+
+void main() {
+    uint stride = gl_NumWorkGroups.x * gl_WorkGroupSize.x;
+    float lo = edges[0];
+    float hi = edges[u_bins];
+    for (uint i = gl_GlobalInvocationID.x; i < u_len; i += stride) {
+        float x = samples[i];
+        if (x < lo) { atomicAdd(counts[0], 1u); continue; }
+        if (x > hi) { atomicAdd(counts[1], 1u); continue; }
+        uint left = 0u;
+        uint right = u_bins + 1u;
+        while (left < right) {
+            uint mid = (left + right) / 2u;
+            if (edges[mid] <= x) { left = mid + 1u; } else { right = mid; }
+        }
+        atomicAdd(counts[2u + min(left - 1u, u_bins - 1u)], 1u);
+    }
+}
+"#;
+
+/// Work group size declared in `HISTOGRAM_GLSL`.
+const HISTOGRAM_LOCAL_SIZE: usize = 256;
+/// Upper bound on dispatched work groups; the shader grid-strides past it.
+const HISTOGRAM_MAX_GROUPS: usize = 1024;
+
+/// Operation to bin samples into a histogram.
+pub struct HistogramOp;
+
+impl AcceleratedOp<HistogramInput, HistogramOutput> for HistogramOp {
+    fn run_vulkan(
+        &self,
+        _input: &HistogramInput,
+        manager: &AccelerationManager,
+    ) -> Result<HistogramOutput> {
+        let _backend = manager
+            .get_vulkan()
+            .ok_or_else(|| anyhow::anyhow!("Vulkan backend not available"))?;
+
+        // Same shader as the GLES path once compiled to SPIR-V
+        // (TODO: embed shaders/histogram.comp.spv and bind descriptor sets).
+        anyhow::bail!("Vulkan HistogramOp shader not compiled")
+    }
+
+    fn run_gles(
+        &self,
+        input: &HistogramInput,
+        manager: &AccelerationManager,
+    ) -> Result<HistogramOutput> {
+        use glow::HasContext;
+
+        let backend = manager
+            .get_gles()
+            .ok_or_else(|| anyhow::anyhow!("GLES backend not available"))?;
+
+        let bins = input.bins();
+        anyhow::ensure!(bins > 0, "histogram needs at least one bin");
+
+        // NaN comparisons are not reliable in GLSL; drop them on the CPU,
+        // matching the scalar reference which skips NaN samples.
+        let samples: Vec<f32> = input.data.iter().copied().filter(|x| !x.is_nan()).collect();
+        if samples.is_empty() {
+            return Ok(HistogramOutput::empty(bins));
+        }
+        anyhow::ensure!(
+            samples.len() <= u32::MAX as usize,
+            "too many samples for GPU histogram"
+        );
+
+        let sample_bytes: Vec<u8> = samples.iter().flat_map(|x| x.to_ne_bytes()).collect();
+        let edge_bytes: Vec<u8> = input.edges.iter().flat_map(|x| x.to_ne_bytes()).collect();
+        let mut count_bytes = vec![0u8; (bins + 2) * std::mem::size_of::<u32>()];
+        let groups = samples
+            .len()
+            .div_ceil(HISTOGRAM_LOCAL_SIZE)
+            .min(HISTOGRAM_MAX_GROUPS) as u32;
+
+        let _current = backend.make_current()?;
+        // SAFETY: `_current` keeps the GLES 3.1 context bound to this thread and
+        // every buffer below is created, bound, and deleted within this block.
+        unsafe {
+            let gl = &backend.gl;
+            let program = backend.create_compute_program(HISTOGRAM_GLSL)?;
+
+            let mut buffers = Vec::with_capacity(3);
+            for (binding, bytes, usage) in [
+                (0u32, sample_bytes.as_slice(), glow::STATIC_DRAW),
+                (1u32, edge_bytes.as_slice(), glow::STATIC_DRAW),
+                (2u32, count_bytes.as_slice(), glow::DYNAMIC_READ),
+            ] {
+                let buffer = gl.create_buffer().map_err(|e| anyhow::anyhow!(e))?;
+                gl.bind_buffer(glow::SHADER_STORAGE_BUFFER, Some(buffer));
+                gl.buffer_data_u8_slice(glow::SHADER_STORAGE_BUFFER, bytes, usage);
+                gl.bind_buffer_base(glow::SHADER_STORAGE_BUFFER, binding, Some(buffer));
+                buffers.push(buffer);
+            }
+
+            gl.use_program(Some(program));
+            gl.uniform_1_u32(
+                gl.get_uniform_location(program, "u_len").as_ref(),
+                samples.len() as u32,
+            );
+            gl.uniform_1_u32(
+                gl.get_uniform_location(program, "u_bins").as_ref(),
+                bins as u32,
+            );
+            gl.dispatch_compute(groups, 1, 1);
+            gl.memory_barrier(glow::BUFFER_UPDATE_BARRIER_BIT);
+
+            // GLES 3.1 has no glGetBufferSubData; map the counts for reading.
+            gl.bind_buffer(glow::SHADER_STORAGE_BUFFER, Some(buffers[2]));
+            let mapped = gl.map_buffer_range(
+                glow::SHADER_STORAGE_BUFFER,
+                0,
+                count_bytes.len() as i32,
+                glow::MAP_READ_BIT,
+            );
+            let readback = if mapped.is_null() {
+                Err(anyhow::anyhow!("failed to map GLES histogram buffer"))
+            } else {
+                std::ptr::copy_nonoverlapping(mapped, count_bytes.as_mut_ptr(), count_bytes.len());
+                gl.unmap_buffer(glow::SHADER_STORAGE_BUFFER);
+                Ok(())
+            };
+
+            for buffer in buffers {
+                gl.delete_buffer(buffer);
+            }
+            gl.delete_program(program);
+            readback?;
+        }
+
+        let raw: Vec<u64> = count_bytes
+            .chunks_exact(4)
+            .map(|c| u32::from_ne_bytes([c[0], c[1], c[2], c[3]]) as u64)
+            .collect();
+
+        Ok(HistogramOutput {
+            underflow: raw[0],
+            overflow: raw[1],
+            counts: raw[2..].to_vec(),
+        })
+    }
+
+    fn run_neon(&self, input: &HistogramInput) -> Result<HistogramOutput> {
+        crate::accel::neon::histogram_f32(&input.data, &input.edges)
+    }
+
+    fn run_scalar(&self, input: &HistogramInput) -> Result<HistogramOutput> {
+        crate::accel::cpu::histogram_f32(&input.data, &input.edges)
+    }
+}
@@ -66,6 +66,7 @@ impl FeatureAggregator {
              WHERE probe_type = ?1 
              AND (target = ?2 OR target = 'gateway') -- simplistic matching
              AND created_at >= datetime('now', ?3)
+             AND dscp IS NULL
              ORDER BY value ASC",
         )?;
 
@@ -78,6 +79,7 @@ impl FeatureAggregator {
              WHERE probe_type = ?1 
              AND target = ?2
              AND created_at >= datetime('now', ?3)
+             AND dscp IS NULL
              ORDER BY value ASC",
         )?;
 
@@ -102,7 +104,8 @@ impl FeatureAggregator {
             "SELECT value FROM measurements 
              WHERE probe_type = ?1 
              AND target = ?2
-             AND created_at >= datetime('now', ?3)",
+             AND created_at >= datetime('now', ?3)
+             AND dscp IS NULL",
         )?;
         let all_rows = stmt_all.query_map(params![probe_type, target, window_start], |row| {
             row.get::<_, f64>(0)
@@ -152,7 +155,8 @@ impl FeatureAggregator {
         let mut stmt = conn.prepare(
             "SELECT value FROM measurements 
              WHERE probe_type = ?1 
-             AND created_at >= datetime('now', ?2)",
+             AND created_at >= datetime('now', ?2)
+             AND dscp IS NULL",
         )?;
 
         let rows = stmt.query_map(params![probe_type, window_start], |row| {
@@ -0,0 +1,194 @@
+//! Blame-check history.
+//!
+//! A single blame check is a snapshot taken whenever someone notices a
+//! problem, often after it has cleared. Persisting every run lets recurring
+//! faults show up as a pattern ("ISP issue 6 times between 20:00 and 23:00").
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::Serialize;
+
+use crate::probes::BlameReport;
+use crate::storage::Pool;
+
+/// One persisted blame-check run.
+#[derive(Debug, Clone, Serialize)]
+pub struct BlameCheckRecord {
+    pub verdict: String,
+    pub confidence: u8,
+    pub details: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// How often one verdict came up within the window.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct VerdictSummary {
+    pub verdict: String,
+    pub count: usize,
+    pub first: DateTime<Utc>,
+    pub last: DateTime<Utc>,
+    pub avg_confidence: f64,
+}
+
+/// A point where the verdict differed from the previous run.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct VerdictChange {
+    pub at: DateTime<Utc>,
+    pub from: String,
+    pub to: String,
+}
+
+/// Persist a blame-check run to `blame_checks`.
+pub fn save_blame_check(pool: &Pool, report: &BlameReport) -> Result<()> {
+    let conn = pool.get()?;
+    conn.execute(
+        "INSERT INTO blame_checks (verdict, confidence, details_json) VALUES (?1, ?2, ?3)",
+        rusqlite::params![
+            report.verdict,
+            report.confidence,
+            serde_json::to_string(&report.details)?,
+        ],
+    )
+    .context("Failed to save blame check")?;
+    Ok(())
+}
+
+/// Blame-check runs from the last `hours`, oldest first.
+pub fn load_history(pool: &Pool, hours: u32) -> Result<Vec<BlameCheckRecord>> {
+    let conn = pool.get()?;
+    let mut stmt = conn.prepare(
+        "SELECT verdict, confidence, details_json, created_at FROM blame_checks
+         WHERE created_at > datetime('now', ?1)
+         ORDER BY created_at ASC, id ASC",
+    )?;
+    let rows = stmt.query_map([format!("-{} hours", hours)], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, i64>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, String>(3)?,
+        ))
+    })?;
+
+    let mut records = Vec::new();
+    for r in rows {
+        let (verdict, confidence, details_json, created_at) = r?;
+        let created_at = NaiveDateTime::parse_from_str(&created_at, "%Y-%m-%d %H:%M:%S")
+            .with_context(|| format!("Bad blame_checks timestamp: {}", created_at))?
+            .and_utc();
+        records.push(BlameCheckRecord {
+            verdict,
+            confidence: confidence.clamp(0, 100) as u8,
+            details: serde_json::from_str(&details_json).unwrap_or_default(),
+            created_at,
+        });
+    }
+    Ok(records)
+}
+
+/// Per-verdict counts and time spans, most frequent first.
+pub fn summarize(records: &[BlameCheckRecord]) -> Vec<VerdictSummary> {
+    let mut out: Vec<VerdictSummary> = Vec::new();
+    for r in records {
+        match out.iter_mut().find(|s| s.verdict == r.verdict) {
+            Some(s) => {
+                s.avg_confidence =
+                    (s.avg_confidence * s.count as f64 + r.confidence as f64) / (s.count + 1) as f64;
+                s.count += 1;
+                s.first = s.first.min(r.created_at);
+                s.last = s.last.max(r.created_at);
+            }
+            None => out.push(VerdictSummary {
+                verdict: r.verdict.clone(),
+                count: 1,
+                first: r.created_at,
+                last: r.created_at,
+                avg_confidence: r.confidence as f64,
+            }),
+        }
+    }
+    out.sort_by(|a, b| b.count.cmp(&a.count).then(a.first.cmp(&b.first)));
+    out
+}
+
+/// Every run whose verdict differs from the run before it.
+pub fn verdict_changes(records: &[BlameCheckRecord]) -> Vec<VerdictChange> {
+    records
+        .windows(2)
+        .filter(|w| w[0].verdict != w[1].verdict)
+        .map(|w| VerdictChange {
+            at: w[1].created_at,
+            from: w[0].verdict.clone(),
+            to: w[1].verdict.clone(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(verdict: &str, confidence: u8, minute: i64) -> BlameCheckRecord {
+        BlameCheckRecord {
+            verdict: verdict.to_string(),
+            confidence,
+            details: vec![],
+            created_at: DateTime::from_timestamp(1_700_000_000 + minute * 60, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_summarize_and_changes() {
+        let records = vec![
+            record("Healthy", 80, 0),
+            record("ISP / Internet Connection Issue", 70, 10),
+            record("ISP / Internet Connection Issue", 90, 20),
+            record("Healthy", 80, 30),
+            record("ISP / Internet Connection Issue", 80, 40),
+        ];
+
+        let summary = summarize(&records);
+        assert_eq!(summary[0].verdict, "ISP / Internet Connection Issue");
+        assert_eq!(summary[0].count, 3);
+        assert_eq!(summary[0].first, records[1].created_at);
+        assert_eq!(summary[0].last, records[4].created_at);
+        assert_eq!(summary[0].avg_confidence, 80.0);
+        assert_eq!(summary[1].count, 2);
+
+        let changes = verdict_changes(&records);
+        assert_eq!(changes.len(), 3);
+        assert_eq!(changes[0].at, records[1].created_at);
+        assert_eq!(changes[1].to, "Healthy");
+        assert!(verdict_changes(&records[..1]).is_empty());
+    }
+
+    #[test]
+    fn test_save_and_load_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = crate::storage::open_pool(dir.path().join("t.db").to_str().unwrap()).unwrap();
+
+        let report = BlameReport {
+            verdict: "Local Network Issue".to_string(),
+            fault: crate::probes::BlameFault::Local,
+            confidence: 87,
+            details: vec!["Gateway (192.168.1.1) unreachable".to_string()],
+        };
+        save_blame_check(&pool, &report).unwrap();
+        pool.get()
+            .unwrap()
+            .execute(
+                "INSERT INTO blame_checks (verdict, confidence, details_json, created_at)
+                 VALUES ('Healthy', 80, '[]', datetime('now', '-48 hours'))",
+                [],
+            )
+            .unwrap();
+
+        let history = load_history(&pool, 24).unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].verdict, "Local Network Issue");
+        assert_eq!(history[0].confidence, 87);
+        assert_eq!(history[0].details, report.details);
+
+        assert_eq!(load_history(&pool, 72).unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,180 @@
+//! Confidence calibration for blame-check verdicts.
+//!
+//! Confidence is derived from the evidence behind each stage instead of a
+//! fixed number per verdict: how many attempts failed, whether latency sits
+//! inside the learned baseline, and whether a baseline exists at all.
+
+use super::stats::Baseline;
+
+/// Baselines with fewer samples than this are treated as absent, matching
+/// the threshold used by anomaly detection.
+pub const MIN_BASELINE_SAMPLES: u64 = 10;
+
+/// Confidence ceiling for a passing stage with no baseline to compare its
+/// latency against.
+const NO_BASELINE_CAP: f64 = 80.0;
+
+/// What one blame-check stage observed.
+#[derive(Debug, Clone, Default)]
+pub struct StageEvidence {
+    pub attempts: u32,
+    pub successes: u32,
+    /// Mean latency of the successful attempts.
+    pub latency_ms: Option<f64>,
+    /// Learned baseline for this target, if one with enough samples exists.
+    pub baseline: Option<Baseline>,
+    /// The stage was judged against an explicit expectation (e.g. a LAN
+    /// throughput floor), so passing means more than "it answered".
+    pub corroborated: bool,
+}
+
+impl StageEvidence {
+    fn failure_ratio(&self) -> f64 {
+        if self.attempts == 0 {
+            return 0.0;
+        }
+        1.0 - self.successes as f64 / self.attempts as f64
+    }
+
+    /// Baseline usable for comparison (enough samples).
+    fn usable_baseline(&self) -> Option<&Baseline> {
+        self.baseline
+            .as_ref()
+            .filter(|b| b.sample_count >= MIN_BASELINE_SAMPLES)
+    }
+
+    /// How many standard deviations above the baseline mean the latency is.
+    pub fn latency_z(&self) -> Option<f64> {
+        let b = self.usable_baseline()?;
+        let latency = self.latency_ms?;
+        if b.std_dev <= 0.0001 {
+            return None;
+        }
+        Some((latency - b.mean) / b.std_dev)
+    }
+}
+
+/// Confidence (0-100) that a failed stage reflects a real fault.
+///
+/// Grows with the share of failed attempts and with the number of attempts
+/// (one lost packet proves little; three in a row proves a lot). A target
+/// with a healthy history failing now is stronger evidence than one we
+/// know nothing about.
+pub fn failure_confidence(e: &StageEvidence) -> u8 {
+    // 1 attempt -> 0.5, 2 -> 0.75, 3 -> 0.875, ...
+    let certainty = 1.0 - 0.5f64.powi(e.attempts.max(1) as i32);
+    let mut conf = 50.0 + 45.0 * e.failure_ratio() * certainty;
+    if e.usable_baseline().is_some() {
+        conf += 5.0;
+    }
+    conf.round().clamp(0.0, 99.0) as u8
+}
+
+/// Confidence (0-100) that a passing stage is genuinely healthy.
+///
+/// Partial failures and latency above the learned baseline lower it; without
+/// a baseline it is capped, since "answered" is not the same as "normal".
+pub fn health_confidence(e: &StageEvidence) -> u8 {
+    let mut conf = 95.0 * (1.0 - e.failure_ratio());
+    match e.latency_z() {
+        Some(z) if z > 3.0 => conf -= (10.0 + (z - 3.0) * 10.0).min(40.0),
+        Some(_) => {}
+        None if e.usable_baseline().is_none() && !e.corroborated => {
+            conf = conf.min(NO_BASELINE_CAP)
+        }
+        None => {}
+    }
+    conf.round().clamp(0.0, 99.0) as u8
+}
+
+/// Confidence in a fault verdict at `failed`, given the stages that passed
+/// before it. A shaky upstream stage weakens the attribution: if the
+/// gateway was borderline, blaming the ISP is less certain.
+pub fn verdict_confidence(failed: &StageEvidence, passed: &[StageEvidence]) -> u8 {
+    let fail = failure_confidence(failed) as f64;
+    let weakest = passed.iter().map(health_confidence).min().unwrap_or(100) as f64;
+    (fail * (0.5 + 0.5 * weakest / 100.0)).round() as u8
+}
+
+/// Confidence in a "Healthy" verdict: only as strong as the weakest stage.
+pub fn healthy_confidence(passed: &[StageEvidence]) -> u8 {
+    passed.iter().map(health_confidence).min().unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn baseline(mean: f64, std_dev: f64) -> Option<Baseline> {
+        Some(Baseline {
+            mean,
+            std_dev,
+            sample_count: 100,
+            z_score_threshold: 3.0,
+        })
+    }
+
+    fn stage(attempts: u32, successes: u32, latency_ms: Option<f64>, b: Option<Baseline>) -> StageEvidence {
+        StageEvidence {
+            attempts,
+            successes,
+            latency_ms,
+            baseline: b,
+            corroborated: false,
+        }
+    }
+
+    #[test]
+    fn test_failure_confidence_grows_with_evidence() {
+        let one_lost = failure_confidence(&stage(1, 0, None, None));
+        let three_lost = failure_confidence(&stage(3, 0, None, None));
+        let one_of_three = failure_confidence(&stage(3, 1, None, None));
+        let with_history = failure_confidence(&stage(3, 0, None, baseline(2.0, 0.5)));
+
+        assert!(one_lost < three_lost);
+        assert!(one_of_three < three_lost);
+        assert!(three_lost < with_history);
+        assert_eq!(three_lost, 89);
+    }
+
+    #[test]
+    fn test_health_confidence_penalizes_borderline_latency() {
+        let normal = health_confidence(&stage(3, 3, Some(2.1), baseline(2.0, 0.5)));
+        let slow = health_confidence(&stage(3, 3, Some(4.0), baseline(2.0, 0.5)));
+        let unknown = health_confidence(&stage(3, 3, Some(2.1), None));
+
+        assert_eq!(normal, 95);
+        assert!(slow < normal);
+        assert_eq!(unknown, 80);
+
+        let measured = StageEvidence {
+            corroborated: true,
+            ..stage(1, 1, Some(0.8), None)
+        };
+        assert_eq!(health_confidence(&measured), 95);
+    }
+
+    #[test]
+    fn test_small_baseline_is_ignored() {
+        let mut thin = baseline(2.0, 0.5).unwrap();
+        thin.sample_count = 3;
+        let e = stage(3, 3, Some(50.0), Some(thin));
+        assert!(e.latency_z().is_none());
+        assert_eq!(health_confidence(&e), 80);
+    }
+
+    #[test]
+    fn test_verdict_confidence_weighs_upstream_health() {
+        let wan_down = stage(3, 0, None, None);
+        let solid_gw = stage(3, 3, Some(1.0), baseline(1.0, 0.2));
+        let shaky_gw = stage(3, 2, Some(3.0), baseline(1.0, 0.2));
+
+        let strong = verdict_confidence(&wan_down, std::slice::from_ref(&solid_gw));
+        let weak = verdict_confidence(&wan_down, &[shaky_gw]);
+        assert!(weak < strong);
+        assert_eq!(verdict_confidence(&wan_down, &[]), failure_confidence(&wan_down));
+
+        assert_eq!(healthy_confidence(&[solid_gw]), 95);
+        assert_eq!(healthy_confidence(&[]), 0);
+    }
+}
@@ -0,0 +1,161 @@
+//! Latency histograms for reporting over large measurement windows.
+//!
+//! Sorting every sample to read off percentiles is fine for a 5-minute blame
+//! window but not for days of probes. Here the samples are binned through
+//! `accel` (GPU / NEON / scalar) and percentiles are read from the bins.
+
+use crate::accel::ops::{HistogramInput, HistogramOp, StatsInput, StatsOp};
+use crate::accel::AccelerationManager;
+use crate::storage::Pool;
+use anyhow::Result;
+use serde::Serialize;
+
+/// A binned latency distribution for one probe type + target.
+#[derive(Debug, Clone, Serialize)]
+pub struct LatencyHistogram {
+    pub probe_type: String,
+    pub target: String,
+    /// Number of valid (non-negative) samples binned.
+    pub sample_count: u64,
+    pub min_ms: f32,
+    pub max_ms: f32,
+    /// Bin edges in ms (`counts.len() + 1` entries).
+    pub edges_ms: Vec<f32>,
+    pub counts: Vec<u64>,
+}
+
+impl LatencyHistogram {
+    /// Approximate percentile (`p` in `0.0..=100.0`): the upper edge of the
+    /// bin holding the p-th sample. Error is bounded by one bin width.
+    pub fn percentile(&self, p: f64) -> f32 {
+        if self.sample_count == 0 {
+            return 0.0;
+        }
+
+        let rank = ((p.clamp(0.0, 100.0) / 100.0) * self.sample_count as f64).ceil() as u64;
+        let rank = rank.max(1);
+
+        let mut cumulative = 0u64;
+        for (i, &count) in self.counts.iter().enumerate() {
+            cumulative += count;
+            if cumulative >= rank {
+                return self.edges_ms[i + 1];
+            }
+        }
+        self.max_ms
+    }
+}
+
+/// Build a latency histogram from the last `window_hours` of measurements.
+///
+/// Error sentinels (negative values) are excluded, as in `stats`. Hours that
+/// have already been rolled up contribute approximate samples; see
+/// [`super::trend::window_samples`].
+pub fn latency_histogram(
+    pool: &Pool,
+    accel: &AccelerationManager,
+    probe_type: &str,
+    target: &str,
+    window_hours: u32,
+    bins: usize,
+) -> Result<LatencyHistogram> {
+    let values: Vec<f32> = super::trend::window_samples(pool, probe_type, target, window_hours)?
+        .into_iter()
+        .map(|v| v as f32)
+        .collect();
+
+    build_histogram(accel, probe_type, target, values, bins)
+}
+
+/// Bin `values` into `bins` equal-width bins spanning their min..max.
+fn build_histogram(
+    accel: &AccelerationManager,
+    probe_type: &str,
+    target: &str,
+    values: Vec<f32>,
+    bins: usize,
+) -> Result<LatencyHistogram> {
+    let bins = bins.max(1);
+    let payload_size = values.len() * std::mem::size_of::<f32>();
+    let sample_count = values.len() as u64;
+
+    if values.is_empty() {
+        return Ok(LatencyHistogram {
+            probe_type: probe_type.to_string(),
+            target: target.to_string(),
+            sample_count: 0,
+            min_ms: 0.0,
+            max_ms: 0.0,
+            edges_ms: vec![0.0; bins + 1],
+            counts: vec![0; bins],
+        });
+    }
+
+    // 1. Range
+    let stats_input = StatsInput { data: values };
+    let stats = accel.execute(&StatsOp, &stats_input, payload_size)?;
+
+    // A constant series still needs a non-empty range.
+    let max = if stats.max > stats.min {
+        stats.max
+    } else {
+        stats.min + 1.0
+    };
+
+    // 2. Binning
+    let input = HistogramInput::uniform(stats_input.data, stats.min, max, bins)?;
+    let output = accel.execute(&HistogramOp, &input, payload_size)?;
+
+    Ok(LatencyHistogram {
+        probe_type: probe_type.to_string(),
+        target: target.to_string(),
+        sample_count,
+        min_ms: stats.min,
+        max_ms: stats.max,
+        edges_ms: input.edges,
+        counts: output.counts,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_histogram_counts_every_sample() {
+        let accel = AccelerationManager::new();
+        let values: Vec<f32> = (0..10_000).map(|i| (i % 100) as f32).collect();
+        let hist = build_histogram(&accel, "icmp", "8.8.8.8", values, 10).unwrap();
+
+        assert_eq!(hist.sample_count, 10_000);
+        assert_eq!(hist.counts.len(), 10);
+        assert_eq!(hist.edges_ms.len(), 11);
+        assert_eq!(hist.counts.iter().sum::<u64>(), 10_000);
+        assert_eq!(hist.min_ms, 0.0);
+        assert_eq!(hist.max_ms, 99.0);
+    }
+
+    #[test]
+    fn test_percentile_within_one_bin() {
+        let accel = AccelerationManager::new();
+        let values: Vec<f32> = (1..=1000).map(|i| i as f32).collect();
+        let hist = build_histogram(&accel, "icmp", "gw", values, 100).unwrap();
+
+        let width = (hist.max_ms - hist.min_ms) / 100.0;
+        assert!((hist.percentile(50.0) - 500.0).abs() <= width);
+        assert!((hist.percentile(95.0) - 950.0).abs() <= width);
+        assert_eq!(hist.percentile(100.0), hist.max_ms);
+    }
+
+    #[test]
+    fn test_empty_and_constant_series() {
+        let accel = AccelerationManager::new();
+        let hist = build_histogram(&accel, "icmp", "gw", Vec::new(), 8).unwrap();
+        assert_eq!(hist.sample_count, 0);
+        assert_eq!(hist.percentile(99.0), 0.0);
+
+        let hist = build_histogram(&accel, "icmp", "gw", vec![5.0; 50], 8).unwrap();
+        assert_eq!(hist.counts[0], 50);
+        assert_eq!(hist.percentile(50.0), hist.edges_ms[1]);
+    }
+}
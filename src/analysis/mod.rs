@@ -6,3 +6,8 @@ pub mod qos; // Phase 13
 
 pub mod stats; // Phase 8.1
 pub mod correlation; // Phase 8.2
+pub mod histogram;
+pub mod confidence;
+pub mod blame_history;
+pub mod trend;
+pub mod timeseries;
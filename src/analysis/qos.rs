@@ -1,4 +1,6 @@
+use crate::storage::Pool;
 use crate::throughput;
+use crate::throughput::provider::SpeedTestResult;
 use crate::probes::{Probe, icmp::IcmpProbe};
 use std::time::Duration;
 use tokio::time::sleep;
@@ -11,7 +13,41 @@ pub struct QosResult {
     pub loaded_rtt_ms: f64,
     pub bufferbloat_ms: f64,
     pub grade: char,
-    pub download_mbps: Option<f64>, // If we capture it
+    /// Throughput the load phase achieved.
+    pub download_mbps: Option<f64>,
+    pub upload_mbps: Option<f64>,
+    /// The load phase as a normalized speed test, stored alongside.
+    #[serde(skip)]
+    pub speed: Option<SpeedTestResult>,
+}
+
+/// `probe_results.probe_type` for stored latency-under-load runs.
+pub const BUFFERBLOAT_PROBE_TYPE: &str = "bufferbloat";
+
+impl QosResult {
+    /// Persist to `probe_results` as a `bufferbloat` row, plus the load
+    /// phase as a `speed` row with the same timestamp, so the saturation
+    /// detector can pair them.
+    pub fn save(&self, pool: &Pool) -> anyhow::Result<()> {
+        let mut conn = pool.get()?;
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO probe_results (probe_type, target, result_json) VALUES (?1, ?2, ?3)",
+            rusqlite::params![BUFFERBLOAT_PROBE_TYPE, self.target, serde_json::to_string(self)?],
+        )?;
+        if let Some(speed) = &self.speed {
+            tx.execute(
+                "INSERT INTO probe_results (probe_type, target, result_json) VALUES (?1, ?2, ?3)",
+                rusqlite::params![
+                    throughput::provider::SPEED_PROBE_TYPE,
+                    speed.provider_id,
+                    serde_json::to_string(speed)?
+                ],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
 }
 
 pub async fn run_qos_test(target: &str) -> anyhow::Result<QosResult> {
@@ -27,7 +63,7 @@ pub async fn run_qos_test(target: &str) -> anyhow::Result<QosResult> {
     let target_clone = target.to_string();
     
     let pinger_handle = tokio::spawn(async move {
-        let probe = IcmpProbe;
+        let probe = IcmpProbe::default();
         loop {
             // Check if receiver dropped (test done)
             if tx.is_closed() { break; }
@@ -53,20 +89,20 @@ pub async fn run_qos_test(target: &str) -> anyhow::Result<QosResult> {
     // 3. Saturate Link (Download)
     info!("Saturating downstream bandwidth (10s)...");
     // We use iperf3 "wan" mode, 4 streams for max load
-    // Note: run_test returns Result<ThroughputResult> (if I updated it) or just Result<()> (logging only).
-    // Reviewing throughput/mod.rs implies it returns Result<()>.
-    // So we don't get Mbps programmatically easily unless we parse logs or update run_test.
-    // For QoS, we care about the LATENCY impact.
-    let load_start = std::time::Instant::now();
-    let load_result = throughput::run_test("wan", None, "10s", 4).await;
+    // Not recorded on its own: `QosResult::save` keeps the load phase.
+    let load_result = throughput::run_test(None, "wan", None, "10s", 4, throughput::iperf::Protocol::Tcp).await;
     
     // 4. Stop Pinger
     // We abort the task to stop it immediately
     pinger_handle.abort();
     
-    if let Err(e) = load_result {
-        warn!("Throughput test failed: {}. Bufferbloat metric may be invalid.", e);
-    }
+    let speed = match load_result {
+        Ok(results) => SpeedTestResult::from_throughput(results),
+        Err(e) => {
+            warn!("Throughput test failed: {}. Bufferbloat metric may be invalid.", e);
+            None
+        }
+    };
 
     // 5. Collect Loaded Metrics
     let mut loaded_samples = Vec::new();
@@ -99,20 +135,27 @@ pub async fn run_qos_test(target: &str) -> anyhow::Result<QosResult> {
         _ => 'F', // Bad
     };
 
+    let speed = speed.map(|mut s| {
+        s.latency_ms = Some(baseline_rtt);
+        s.bufferbloat_ms = Some(bloat);
+        s
+    });
     Ok(QosResult {
         target: target.to_string(),
         baseline_rtt_ms: baseline_rtt,
         loaded_rtt_ms: loaded_rtt,
         bufferbloat_ms: bloat,
         grade,
-        download_mbps: None, // Not captured yet
+        download_mbps: speed.as_ref().and_then(|s| s.download_mbps),
+        upload_mbps: speed.as_ref().and_then(|s| s.upload_mbps),
+        speed,
     })
 }
 
 async fn measure_rtt_batch(target: &str, count: usize, interval: Duration) -> anyhow::Result<f64> {
     let mut total = 0.0;
     let mut valid = 0;
-    let probe = IcmpProbe;
+    let probe = IcmpProbe::default();
     
     for _ in 0..count {
         if let Ok(m) = probe.run(target, Duration::from_secs(1)).await {
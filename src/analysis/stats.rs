@@ -22,6 +22,22 @@ impl Default for Baseline {
     }
 }
 
+impl Baseline {
+    /// Z-score of `value` against this baseline (0 for a flat baseline).
+    pub fn z_score(&self, value: f64) -> f64 {
+        if self.std_dev > 0.0001 {
+            (value - self.mean) / self.std_dev
+        } else {
+            0.0
+        }
+    }
+
+    /// Whether `value` is no worse than the anomaly threshold.
+    pub fn is_within(&self, value: f64) -> bool {
+        value >= 0.0 && self.z_score(value) <= self.z_score_threshold
+    }
+}
+
 /// Calculate statisical baseline for a given probe type + target over a time window.
 /// Default window is 24 hours.
 pub fn calculate_baseline(pool: &Pool, probe_type: &str, target: &str) -> Result<Baseline> {
@@ -34,6 +50,7 @@ pub fn calculate_baseline(pool: &Pool, probe_type: &str, target: &str) -> Result
          WHERE probe_type = ?1 
          AND target = ?2 
          AND created_at > datetime('now', '-24 hours')
+         AND dscp IS NULL -- DSCP-marked probes measure a QoS class, not the path
          AND value >= 0 -- Exclude error sentinels (-1.0)"
     )?;
 
@@ -88,11 +105,7 @@ pub fn check_for_anomaly(pool: &Pool, probe_type: &str, target: &str, value: f64
     }
 
     // Z-Score calculation
-    let z_score = if baseline.std_dev > 0.0001 {
-        (value - baseline.mean) / baseline.std_dev
-    } else {
-        0.0
-    };
+    let z_score = baseline.z_score(value);
 
     // Anomaly if z_score > threshold AND value is "worse" than mean
     // For Latency: Worse = Higher. (z_score > 3.0)
@@ -188,6 +201,7 @@ mod tests {
                 unit: "ms".to_string(),
                 success: true,
                 timestamp: std::time::SystemTime::now(),
+                dscp: None,
             })?;
         }
         
@@ -199,6 +213,7 @@ mod tests {
              unit: "ms".to_string(),
              success: true,
              timestamp: std::time::SystemTime::now(),
+             dscp: None,
         })?;
 
         let baseline = calculate_baseline(&pool, "icmp", "8.8.8.8")?;
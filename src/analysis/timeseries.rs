@@ -0,0 +1,279 @@
+//! Fixed-step time series for external dashboards (Grafana).
+//!
+//! A metric is a probe type plus what to plot, e.g. `icmp_latency`. Series
+//! are built from raw measurements and, for ranges past raw retention, from
+//! hourly rollups, so a rolled-up hour contributes one point at its start
+//! when the step is finer than an hour.
+
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, bail, Result};
+use rusqlite::params;
+
+use crate::storage::Pool;
+
+/// Probe types that can be plotted.
+pub const PROBE_TYPES: [&str; 4] = ["icmp", "dns", "http", "tcp"];
+
+/// Most points a single series may return.
+pub const MAX_POINTS: i64 = 11_000;
+
+/// What a metric plots per step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricKind {
+    /// Mean of successful samples, in ms.
+    Latency,
+    /// Number of failed probes.
+    Errors,
+}
+
+/// A queryable metric, named `<probe_type>_<kind>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Metric {
+    pub probe_type: &'static str,
+    pub kind: MetricKind,
+}
+
+impl Metric {
+    pub fn parse(name: &str) -> Result<Self> {
+        let (probe, kind) = name
+            .rsplit_once('_')
+            .ok_or_else(|| anyhow!("unknown metric '{}'", name))?;
+        let probe_type = PROBE_TYPES
+            .iter()
+            .copied()
+            .find(|p| *p == probe)
+            .ok_or_else(|| anyhow!("unknown probe type in metric '{}'", name))?;
+        let kind = match kind {
+            "latency" => MetricKind::Latency,
+            "errors" => MetricKind::Errors,
+            _ => bail!(
+                "unknown metric '{}' (expected <probe>_latency or <probe>_errors)",
+                name
+            ),
+        };
+        Ok(Self { probe_type, kind })
+    }
+
+    pub fn name(&self) -> String {
+        let kind = match self.kind {
+            MetricKind::Latency => "latency",
+            MetricKind::Errors => "errors",
+        };
+        format!("{}_{}", self.probe_type, kind)
+    }
+
+    /// Every queryable metric name.
+    pub fn all() -> Vec<String> {
+        PROBE_TYPES
+            .iter()
+            .flat_map(|p| [format!("{}_latency", p), format!("{}_errors", p)])
+            .collect()
+    }
+}
+
+/// Parse a step like `30s`, `5m`, `1h` or `1d` (bare numbers are seconds).
+pub fn parse_step(step: &str) -> Result<i64> {
+    let step = step.trim();
+    let (num, mult) = match step.char_indices().last() {
+        Some((i, 's')) => (&step[..i], 1),
+        Some((i, 'm')) => (&step[..i], 60),
+        Some((i, 'h')) => (&step[..i], 3600),
+        Some((i, 'd')) => (&step[..i], 86_400),
+        _ => (step, 1),
+    };
+    let n: i64 = num
+        .parse()
+        .map_err(|_| anyhow!("invalid step '{}' (e.g. 30s, 5m, 1h)", step))?;
+    if n <= 0 {
+        bail!("step must be positive");
+    }
+    n.checked_mul(mult)
+        .ok_or_else(|| anyhow!("step '{}' is too large", step))
+}
+
+#[derive(Default)]
+struct Bucket {
+    sum: f64,
+    count: u64,
+    errors: u64,
+}
+
+/// `[value, epoch_ms]` points for `metric` on `target` in `[from_ms, to_ms)`,
+/// one per `step_secs` bucket that has data, oldest first.
+pub fn series(
+    pool: &Pool,
+    metric: &Metric,
+    target: &str,
+    from_ms: i64,
+    to_ms: i64,
+    step_secs: i64,
+) -> Result<Vec<(f64, i64)>> {
+    if step_secs <= 0 || to_ms <= from_ms {
+        bail!("empty range or non-positive step");
+    }
+    if (to_ms - from_ms) / 1000 / step_secs > MAX_POINTS {
+        bail!(
+            "range/step would return more than {} points; use a larger step",
+            MAX_POINTS
+        );
+    }
+    let (from, to) = (from_ms.div_euclid(1000), to_ms.div_euclid(1000));
+    let bucket_of = |epoch: i64| epoch - epoch.rem_euclid(step_secs);
+    let mut buckets: BTreeMap<i64, Bucket> = BTreeMap::new();
+    let conn = pool.get()?;
+
+    let mut stmt = conn.prepare(
+        "SELECT CAST(strftime('%s', hour) AS INTEGER), count, error_count, avg
+         FROM measurement_rollups
+         WHERE probe_type = ?1 AND target = ?2
+         AND hour >= datetime(?3, 'unixepoch') AND hour < datetime(?4, 'unixepoch')",
+    )?;
+    let mut rows = stmt.query(params![metric.probe_type, target, from, to])?;
+    while let Some(row) = rows.next()? {
+        let b = buckets.entry(bucket_of(row.get(0)?)).or_default();
+        let count = row.get::<_, i64>(1)? as u64;
+        b.sum += row.get::<_, Option<f64>>(3)?.unwrap_or(0.0) * count as f64;
+        b.count += count;
+        b.errors += row.get::<_, i64>(2)? as u64;
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT CAST(strftime('%s', created_at) AS INTEGER), value FROM measurements
+         WHERE probe_type = ?1 AND target = ?2 AND dscp IS NULL
+         AND datetime(created_at) >= datetime(?3, 'unixepoch')
+         AND datetime(created_at) < datetime(?4, 'unixepoch')",
+    )?;
+    let mut rows = stmt.query(params![metric.probe_type, target, from, to])?;
+    while let Some(row) = rows.next()? {
+        let b = buckets.entry(bucket_of(row.get(0)?)).or_default();
+        let value: f64 = row.get(1)?;
+        if value < 0.0 {
+            b.errors += 1;
+        } else {
+            b.sum += value;
+            b.count += 1;
+        }
+    }
+
+    Ok(buckets
+        .into_iter()
+        .filter_map(|(start, b)| {
+            let value = match metric.kind {
+                MetricKind::Latency if b.count > 0 => b.sum / b.count as f64,
+                MetricKind::Latency => return None,
+                MetricKind::Errors => b.errors as f64,
+            };
+            Some((value, start * 1000))
+        })
+        .collect())
+}
+
+/// `metric:target` names for every series with data, for Grafana's metric
+/// picker.
+pub fn series_names(pool: &Pool) -> Result<Vec<String>> {
+    let conn = pool.get()?;
+    let mut stmt = conn.prepare(
+        "SELECT probe_type, target FROM measurements WHERE dscp IS NULL
+         UNION SELECT probe_type, target FROM measurement_rollups
+         ORDER BY 1, 2",
+    )?;
+    let pairs = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(pairs
+        .into_iter()
+        .filter(|(probe, _)| PROBE_TYPES.contains(&probe.as_str()))
+        .flat_map(|(probe, target)| {
+            [
+                format!("{}_latency:{}", probe, target),
+                format!("{}_errors:{}", probe, target),
+            ]
+        })
+        .collect())
+}
+
+/// Split a `metric:target` series name. The target may itself contain `:`
+/// (IPv6), metric names never do.
+pub fn parse_series_name(name: &str) -> Result<(Metric, String)> {
+    let (metric, target) = name
+        .split_once(':')
+        .ok_or_else(|| anyhow!("expected <metric>:<target>, got '{}'", name))?;
+    Ok((Metric::parse(metric)?, target.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_metric_and_step() {
+        let m = Metric::parse("icmp_latency").unwrap();
+        assert_eq!((m.probe_type, m.kind), ("icmp", MetricKind::Latency));
+        assert_eq!(m.name(), "icmp_latency");
+        assert!(Metric::parse("icmp_jitter").is_err());
+        assert!(Metric::parse("smtp_latency").is_err());
+        assert_eq!(Metric::all().len(), 8);
+
+        assert_eq!(parse_step("5m").unwrap(), 300);
+        assert_eq!(parse_step("1h").unwrap(), 3600);
+        assert_eq!(parse_step("45").unwrap(), 45);
+        assert!(parse_step("0s").is_err());
+        assert!(parse_step("fast").is_err());
+        assert!(parse_step(&format!("{}d", i64::MAX)).is_err());
+
+        let (m, target) = parse_series_name("tcp_errors:2001:db8::1").unwrap();
+        assert_eq!(m.kind, MetricKind::Errors);
+        assert_eq!(target, "2001:db8::1");
+    }
+
+    #[test]
+    fn test_series_buckets_raw_and_rollups() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = crate::storage::open_pool(dir.path().join("t.db").to_str().unwrap()).unwrap();
+        let conn = pool.get().unwrap();
+        // 2000-01-01 00:00:00 UTC
+        let base: i64 = 946_684_800;
+        for (offset, value) in [(0, 10.0), (60, 20.0), (120, -1.0), (400, 30.0)] {
+            conn.execute(
+                "INSERT INTO measurements (probe_type, target, value, unit, created_at)
+                 VALUES ('icmp', 'gw', ?1, 'ms', datetime(?2, 'unixepoch'))",
+                params![value, base + offset],
+            )
+            .unwrap();
+        }
+        conn.execute(
+            "INSERT INTO measurement_rollups
+                (probe_type, target, unit, hour, count, error_count, min, avg, max, p95)
+             VALUES ('icmp', 'gw', 'ms', '2000-01-01 01:00:00', 4, 2, 1, 50, 90, 90)",
+            [],
+        )
+        .unwrap();
+        drop(conn);
+
+        let latency = Metric::parse("icmp_latency").unwrap();
+        let from = base * 1000;
+        let to = (base + 7200) * 1000;
+        let points = series(&pool, &latency, "gw", from, to, 300).unwrap();
+        assert_eq!(
+            points,
+            vec![
+                (15.0, from),
+                (30.0, from + 300_000),
+                (50.0, from + 3_600_000)
+            ]
+        );
+
+        let errors = Metric::parse("icmp_errors").unwrap();
+        let points = series(&pool, &errors, "gw", from, to, 3600).unwrap();
+        assert_eq!(points, vec![(1.0, from), (2.0, from + 3_600_000)]);
+
+        assert!(series(&pool, &latency, "gw", from, to, 0).is_err());
+        assert_eq!(
+            series_names(&pool).unwrap(),
+            vec!["icmp_latency:gw", "icmp_errors:gw"]
+        );
+    }
+}
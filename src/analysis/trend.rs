@@ -0,0 +1,208 @@
+//! Long-range trends across raw measurements and hourly rollups.
+//!
+//! Raw rows only cover the retention window (`storage::rollup`); older hours
+//! live in `measurement_rollups`. Callers ask for a range and get both
+//! stitched together, without needing to know where the cutoff lies.
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use rusqlite::params;
+use serde::Serialize;
+
+use crate::storage::rollup::{HourStats, HOUR_BUCKET};
+use crate::storage::Pool;
+
+/// One hour of a trend.
+#[derive(Debug, Clone, Serialize)]
+pub struct HourlyPoint {
+    /// Start of the hour, `YYYY-MM-DD HH:00:00` (UTC).
+    pub hour: String,
+    #[serde(flatten)]
+    pub stats: HourStats,
+    /// Whether any of this hour came from rollups rather than raw rows.
+    pub rolled_up: bool,
+}
+
+fn rollups_since(
+    pool: &Pool,
+    probe_type: &str,
+    target: &str,
+    hours: u32,
+) -> Result<Vec<(String, HourStats)>> {
+    let conn = pool.get()?;
+    let mut stmt = conn.prepare(
+        "SELECT hour, count, error_count, min, avg, max, p95 FROM measurement_rollups
+         WHERE probe_type = ?1 AND target = ?2
+         AND hour >= strftime('%Y-%m-%d %H:00:00', 'now', ?3)
+         ORDER BY hour",
+    )?;
+    let rows = stmt
+        .query_map(
+            params![probe_type, target, format!("-{} hours", hours)],
+            |row| {
+                Ok((
+                    row.get(0)?,
+                    HourStats {
+                        count: row.get::<_, i64>(1)? as u64,
+                        error_count: row.get::<_, i64>(2)? as u64,
+                        min: row.get(3)?,
+                        avg: row.get(4)?,
+                        max: row.get(5)?,
+                        p95: row.get(6)?,
+                    },
+                ))
+            },
+        )?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(rows)
+}
+
+/// Hourly min/avg/max/p95 for the last `hours`, oldest first: rollups for
+/// hours past raw retention, raw rows aggregated on the fly for the rest.
+pub fn hourly_trend(
+    pool: &Pool,
+    probe_type: &str,
+    target: &str,
+    hours: u32,
+) -> Result<Vec<HourlyPoint>> {
+    let mut points: BTreeMap<String, HourlyPoint> = rollups_since(pool, probe_type, target, hours)?
+        .into_iter()
+        .map(|(hour, stats)| {
+            (
+                hour.clone(),
+                HourlyPoint {
+                    hour,
+                    stats,
+                    rolled_up: true,
+                },
+            )
+        })
+        .collect();
+
+    let conn = pool.get()?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} AS hour, value FROM measurements
+         WHERE probe_type = ?1 AND target = ?2 AND dscp IS NULL
+         AND datetime(created_at) >= strftime('%Y-%m-%d %H:00:00', 'now', ?3)
+         ORDER BY hour, value",
+        HOUR_BUCKET
+    ))?;
+    let rows = stmt
+        .query_map(
+            params![probe_type, target, format!("-{} hours", hours)],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?)),
+        )?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    // Rows are ordered by hour, so each hour is one contiguous run.
+    let mut start = 0;
+    while start < rows.len() {
+        let hour = rows[start].0.clone();
+        let end = start + rows[start..].iter().take_while(|(h, _)| *h == hour).count();
+        let values: Vec<f64> = rows[start..end].iter().map(|(_, v)| *v).collect();
+        start = end;
+        let stats = HourStats::from_sorted(&values);
+        points
+            .entry(hour.clone())
+            .and_modify(|p| p.stats = p.stats.merge(&stats))
+            .or_insert(HourlyPoint {
+                hour,
+                stats,
+                rolled_up: false,
+            });
+    }
+
+    Ok(points.into_values().collect())
+}
+
+/// Valid (non-negative) samples from the last `hours`: raw values, plus
+/// [`HourStats::approximate_samples`] for hours that have been rolled up.
+/// Distributions over old ranges are therefore approximate.
+pub fn window_samples(pool: &Pool, probe_type: &str, target: &str, hours: u32) -> Result<Vec<f64>> {
+    let mut values: Vec<f64> = rollups_since(pool, probe_type, target, hours)?
+        .iter()
+        .flat_map(|(_, stats)| stats.approximate_samples())
+        .collect();
+
+    let conn = pool.get()?;
+    let mut stmt = conn.prepare(
+        "SELECT value FROM measurements
+         WHERE probe_type = ?1 AND target = ?2 AND dscp IS NULL
+         AND datetime(created_at) > datetime('now', ?3)
+         AND value >= 0",
+    )?;
+    let raw = stmt
+        .query_map(
+            params![probe_type, target, format!("-{} hours", hours)],
+            |row| row.get::<_, f64>(0),
+        )?
+        .collect::<rusqlite::Result<Vec<f64>>>()?;
+    values.extend(raw);
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert(pool: &Pool, value: f64, age: &str) {
+        pool.get()
+            .unwrap()
+            .execute(
+                "INSERT INTO measurements (probe_type, target, value, unit, created_at)
+                 VALUES ('icmp', 'gw', ?1, 'ms', datetime('now', ?2))",
+                params![value, age],
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_trend_spans_rollups_and_raw() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = crate::storage::open_pool(dir.path().join("t.db").to_str().unwrap()).unwrap();
+
+        insert(&pool, 10.0, "-10 days");
+        insert(&pool, 30.0, "-10 days");
+        insert(&pool, 5.0, "-1 minutes");
+        insert(&pool, -1.0, "-1 minutes");
+        crate::storage::rollup::rollup_and_prune(&pool, 7).unwrap();
+
+        let trend = hourly_trend(&pool, "icmp", "gw", 24 * 30).unwrap();
+        assert_eq!(trend.len(), 2);
+        assert!(trend[0].rolled_up);
+        assert_eq!(trend[0].stats.avg, Some(20.0));
+        assert!(!trend[1].rolled_up);
+        assert_eq!(trend[1].stats.count, 1);
+        assert_eq!(trend[1].stats.error_count, 1);
+
+        // A window inside raw retention never touches rollups.
+        assert_eq!(hourly_trend(&pool, "icmp", "gw", 24).unwrap().len(), 1);
+
+        let mut samples = window_samples(&pool, "icmp", "gw", 24 * 30).unwrap();
+        samples.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(samples, vec![5.0, 10.0, 30.0]);
+    }
+
+    #[test]
+    fn test_trend_skips_dscp_marked_samples() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = crate::storage::open_pool(dir.path().join("t.db").to_str().unwrap()).unwrap();
+
+        insert(&pool, 5.0, "-1 minutes");
+        pool.get()
+            .unwrap()
+            .execute(
+                "INSERT INTO measurements (probe_type, target, value, unit, dscp)
+                 VALUES ('icmp', 'gw', 50.0, 'ms', 46)",
+                [],
+            )
+            .unwrap();
+
+        let trend = hourly_trend(&pool, "icmp", "gw", 24).unwrap();
+        assert_eq!(trend.len(), 1);
+        assert_eq!(trend[0].stats.count, 1);
+        assert_eq!(trend[0].stats.avg, Some(5.0));
+        assert_eq!(window_samples(&pool, "icmp", "gw", 24).unwrap(), vec![5.0]);
+    }
+}
@@ -0,0 +1,433 @@
+//! Router middleware: request IDs, request logging, bearer auth, CORS, and
+//! request timeouts.
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderName, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+/// Header carrying the request ID, accepted from the client or generated.
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Longest client-supplied request ID we will echo back.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Request ID stored in request extensions by [`request_id`].
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+/// Assign each request an ID and echo it in the `x-request-id` response header.
+///
+/// A well-formed client-supplied ID is kept so callers can correlate their
+/// own logs; anything else is replaced with a fresh UUID.
+pub async fn request_id(mut req: Request, next: Next) -> Response {
+    let id = req
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| is_valid_request_id(v))
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    req.extensions_mut().insert(RequestId(id.clone()));
+    let mut res = next.run(req).await;
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        res.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    res
+}
+
+/// Log method, path, status, and handling time of each request at debug level.
+pub async fn log_requests(req: Request, next: Next) -> Response {
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+    let request_id = req
+        .extensions()
+        .get::<RequestId>()
+        .map(|r| r.0.clone())
+        .unwrap_or_default();
+
+    let start = Instant::now();
+    let res = next.run(req).await;
+
+    tracing::debug!(
+        %request_id,
+        %method,
+        %path,
+        status = res.status().as_u16(),
+        duration_ms = start.elapsed().as_secs_f64() * 1000.0,
+        "api request"
+    );
+    res
+}
+
+/// Routes that stay reachable without a token (liveness checks).
+const AUTH_EXEMPT_PATHS: &[&str] = &["/api/v1/health"];
+
+/// Require `Authorization: Bearer <token>` on `/api/v1` routes.
+///
+/// Missing or wrong tokens get `401` with a `WWW-Authenticate` challenge.
+/// The token itself is never logged.
+pub async fn require_bearer(
+    State(token): State<Arc<str>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let path = req.uri().path();
+    if !path.starts_with("/api/v1") || AUTH_EXEMPT_PATHS.contains(&path) {
+        return next.run(req).await;
+    }
+
+    let presented = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim);
+
+    match presented {
+        Some(t) if constant_time_eq(t.as_bytes(), token.as_bytes()) => next.run(req).await,
+        other => {
+            tracing::warn!(
+                path = %path,
+                token_present = other.is_some(),
+                "rejected unauthenticated API request"
+            );
+            (
+                StatusCode::UNAUTHORIZED,
+                [(header::WWW_AUTHENTICATE, "Bearer")],
+                Json(json!({ "error": "missing or invalid bearer token" })),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Per-route limits on how long a request may take.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RouteTimeouts {
+    /// Everything not listed below.
+    pub default: Duration,
+    /// Routes that run probes or checks on demand (`POST /blame-check`,
+    /// `POST /trace`, `POST /selftest`).
+    pub probe: Duration,
+    /// `/health`, which monitors expect to answer quickly.
+    pub health: Duration,
+}
+
+impl Default for RouteTimeouts {
+    fn default() -> Self {
+        Self {
+            default: Duration::from_secs(30),
+            probe: Duration::from_secs(120),
+            health: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Routes (method, path) that run probes and get [`RouteTimeouts::probe`].
+const PROBE_ROUTES: &[(&str, &str)] = &[
+    ("POST", "/api/v1/blame-check"),
+    ("POST", "/api/v1/trace"),
+    ("POST", "/api/v1/selftest"),
+];
+
+impl RouteTimeouts {
+    /// The limit for a request to `path`.
+    pub fn for_route(&self, method: &axum::http::Method, path: &str) -> Duration {
+        if AUTH_EXEMPT_PATHS.contains(&path) {
+            self.health
+        } else if PROBE_ROUTES.iter().any(|&(m, p)| m == method.as_str() && p == path) {
+            self.probe
+        } else {
+            self.default
+        }
+    }
+}
+
+/// Answer `504` when a request outlives its [`RouteTimeouts`] limit.
+///
+/// Each request carries a [`CancellationToken`] in its extensions. It is
+/// cancelled when the request ends for any reason (finished, timed out, or
+/// the client went away), so handlers can pass it to work that might
+/// otherwise keep running, such as probes.
+pub async fn timeout(
+    State(timeouts): State<RouteTimeouts>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let limit = timeouts.for_route(req.method(), req.uri().path());
+    let path = req.uri().path().to_string();
+    let cancel = CancellationToken::new();
+    req.extensions_mut().insert(cancel.clone());
+    let _cancel_on_drop = cancel.drop_guard();
+
+    match tokio::time::timeout(limit, next.run(req)).await {
+        Ok(res) => res,
+        Err(_) => {
+            tracing::warn!(path = %path, limit_ms = limit.as_millis() as u64, "API request timed out");
+            (
+                StatusCode::GATEWAY_TIMEOUT,
+                Json(json!({ "error": format!("request timed out after {} ms", limit.as_millis()) })),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// How long browsers may cache a preflight response.
+const CORS_MAX_AGE: Duration = Duration::from_secs(600);
+
+/// Build a CORS layer allowing `origins`, or `None` when the list is empty
+/// (same-origin only). `"*"` allows any origin.
+///
+/// Preflight requests are answered by the layer itself, so it must wrap the
+/// bearer-auth layer: browsers never send credentials on a preflight.
+pub fn cors_layer(origins: &[String]) -> Option<CorsLayer> {
+    if origins.is_empty() {
+        return None;
+    }
+
+    let allow_origin = if origins.iter().any(|o| o == "*") {
+        AllowOrigin::any()
+    } else {
+        let values: Vec<HeaderValue> = origins
+            .iter()
+            .filter_map(|o| match HeaderValue::from_str(o.trim_end_matches('/')) {
+                Ok(v) => Some(v),
+                Err(_) => {
+                    tracing::warn!(origin = %o, "ignoring invalid CORS origin");
+                    None
+                }
+            })
+            .collect();
+        if values.is_empty() {
+            return None;
+        }
+        AllowOrigin::list(values)
+    };
+
+    Some(
+        CorsLayer::new()
+            .allow_origin(allow_origin)
+            .allow_methods([
+                axum::http::Method::GET,
+                axum::http::Method::POST,
+                axum::http::Method::DELETE,
+            ])
+            .allow_headers([header::AUTHORIZATION, header::CONTENT_TYPE, REQUEST_ID_HEADER])
+            .expose_headers([REQUEST_ID_HEADER])
+            .max_age(CORS_MAX_AGE),
+    )
+}
+
+/// Compare without short-circuiting on the first differing byte.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LEN
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, routing::get, Router};
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        Router::new()
+            .route("/ping", get(|| async { "pong" }))
+            .layer(axum::middleware::from_fn(log_requests))
+            .layer(axum::middleware::from_fn(request_id))
+    }
+
+    #[tokio::test]
+    async fn test_request_id_generated() {
+        let res = app()
+            .oneshot(Request::builder().uri("/ping").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let id = res.headers().get(&REQUEST_ID_HEADER).unwrap().to_str().unwrap();
+        assert!(uuid::Uuid::parse_str(id).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_request_id_echoed() {
+        let res = app()
+            .oneshot(
+                Request::builder()
+                    .uri("/ping")
+                    .header("x-request-id", "client-abc.123")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.headers()[&REQUEST_ID_HEADER], "client-abc.123");
+    }
+
+    fn authed_app() -> Router {
+        Router::new()
+            .route("/api/v1/health", get(|| async { "ok" }))
+            .route("/api/v1/schedules", get(|| async { "[]" }))
+            .layer(axum::middleware::from_fn_with_state(
+                Arc::<str>::from("s3cret"),
+                require_bearer,
+            ))
+    }
+
+    async fn status_with_auth(uri: &str, auth: Option<&str>) -> StatusCode {
+        let mut req = Request::builder().uri(uri);
+        if let Some(a) = auth {
+            req = req.header(header::AUTHORIZATION, a);
+        }
+        authed_app()
+            .oneshot(req.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn test_bearer_auth() {
+        assert_eq!(status_with_auth("/api/v1/schedules", None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            status_with_auth("/api/v1/schedules", Some("Bearer wrong")).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            status_with_auth("/api/v1/schedules", Some("Basic s3cret")).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            status_with_auth("/api/v1/schedules", Some("Bearer s3cret")).await,
+            StatusCode::OK
+        );
+        // Health stays open for liveness probes.
+        assert_eq!(status_with_auth("/api/v1/health", None).await, StatusCode::OK);
+    }
+
+    async fn preflight(origins: &[&str], origin: &str) -> Response {
+        let origins: Vec<String> = origins.iter().map(|o| o.to_string()).collect();
+        let app = authed_app().layer(cors_layer(&origins).unwrap());
+        app.oneshot(
+            Request::builder()
+                .method("OPTIONS")
+                .uri("/api/v1/schedules")
+                .header(header::ORIGIN, origin)
+                .header(header::ACCESS_CONTROL_REQUEST_METHOD, "DELETE")
+                .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "authorization")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_cors_preflight_allowed_origin() {
+        let res = preflight(&["https://dash.example"], "https://dash.example").await;
+        // Answered by the CORS layer, not rejected by auth.
+        assert!(res.status().is_success());
+        assert_eq!(
+            res.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://dash.example"
+        );
+        let methods = res.headers()[header::ACCESS_CONTROL_ALLOW_METHODS].to_str().unwrap();
+        assert!(methods.contains("DELETE"));
+    }
+
+    #[tokio::test]
+    async fn test_cors_preflight_other_origin() {
+        let res = preflight(&["https://dash.example"], "https://evil.example").await;
+        assert!(res.headers().get(header::ACCESS_CONTROL_ALLOW_ORIGIN).is_none());
+    }
+
+    fn slow_app(cancelled: Arc<std::sync::atomic::AtomicBool>) -> Router {
+        let timeouts = RouteTimeouts {
+            default: Duration::from_millis(50),
+            probe: Duration::from_secs(5),
+            health: Duration::from_millis(50),
+        };
+        Router::new()
+            .route(
+                "/api/v1/schedules",
+                get(move |axum::Extension(cancel): axum::Extension<CancellationToken>| async move {
+                    tokio::spawn(async move {
+                        cancel.cancelled().await;
+                        cancelled.store(true, std::sync::atomic::Ordering::SeqCst);
+                    });
+                    tokio::time::sleep(Duration::from_secs(10)).await;
+                    "done"
+                }),
+            )
+            .route("/api/v1/health", get(|| async { "ok" }))
+            .layer(axum::middleware::from_fn_with_state(timeouts, timeout))
+    }
+
+    #[tokio::test]
+    async fn test_timeout_returns_504_and_cancels() {
+        let cancelled = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let res = slow_app(cancelled.clone())
+            .oneshot(Request::builder().uri("/api/v1/schedules").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::GATEWAY_TIMEOUT);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(cancelled.load(std::sync::atomic::Ordering::SeqCst));
+
+        let res = slow_app(cancelled)
+            .oneshot(Request::builder().uri("/api/v1/health").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_route_timeouts() {
+        use axum::http::Method;
+        let t = RouteTimeouts::default();
+        assert_eq!(t.for_route(&Method::GET, "/api/v1/health"), t.health);
+        assert_eq!(t.for_route(&Method::POST, "/api/v1/blame-check"), t.probe);
+        assert_eq!(t.for_route(&Method::POST, "/api/v1/selftest"), t.probe);
+        assert_eq!(t.for_route(&Method::GET, "/api/v1/trace"), t.default);
+        assert_eq!(t.for_route(&Method::GET, "/api/v1/schedules"), t.default);
+    }
+
+    #[test]
+    fn test_cors_disabled_without_origins() {
+        assert!(cors_layer(&[]).is_none());
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"abc", b"abc"));
+        assert!(!constant_time_eq(b"abc", b"abd"));
+        assert!(!constant_time_eq(b"abc", b"abcd"));
+    }
+
+    #[test]
+    fn test_request_id_validation() {
+        assert!(is_valid_request_id("abc-123_x.y"));
+        assert!(!is_valid_request_id(""));
+        assert!(!is_valid_request_id("has space"));
+        assert!(!is_valid_request_id(&"a".repeat(MAX_REQUEST_ID_LEN + 1)));
+    }
+}
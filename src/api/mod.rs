@@ -1,19 +1,158 @@
 //! API layer -- axum routes, handlers, and middleware.
 
+pub mod middleware;
+pub mod openapi;
 mod routes;
 pub mod state;
 
+use self::middleware::RouteTimeouts;
 use self::state::AppState;
 use axum::Router;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Router options, read from the environment at startup.
+#[derive(Clone, Default)]
+pub struct ApiConfig {
+    /// Log every request (method, path, status, latency) at debug level.
+    /// Off by default so health polls don't flood production logs.
+    pub request_logging: bool,
+    /// Bearer token required on `/api/v1` routes (except `/health`).
+    /// `None` leaves the API open.
+    pub token: Option<String>,
+    /// Browser origins allowed to call the API (CORS). Empty means
+    /// same-origin only; `"*"` allows any origin.
+    pub cors_origins: Vec<String>,
+    /// How long each route may take before the API answers `504`.
+    pub timeouts: RouteTimeouts,
+}
+
+impl ApiConfig {
+    /// Build from `PP_API_*` environment variables.
+    pub fn from_env() -> Self {
+        Self {
+            request_logging: env_flag("PP_API_LOG_REQUESTS"),
+            token: std::env::var("PP_API_TOKEN")
+                .ok()
+                .map(|t| t.trim().to_string())
+                .filter(|t| !t.is_empty()),
+            cors_origins: std::env::var("PP_API_CORS_ORIGINS")
+                .map(|v| parse_origins(&v))
+                .unwrap_or_default(),
+            timeouts: timeouts_from_env(),
+        }
+    }
+}
+
+/// [`RouteTimeouts`] from `PP_API_*TIMEOUT_SECS`; unset or zero keeps the default.
+fn timeouts_from_env() -> RouteTimeouts {
+    let env_secs = |name: &str| {
+        std::env::var(name)
+            .ok()?
+            .trim()
+            .parse::<u64>()
+            .ok()
+            .filter(|&s| s > 0)
+            .map(Duration::from_secs)
+    };
+    let defaults = RouteTimeouts::default();
+    RouteTimeouts {
+        default: env_secs("PP_API_TIMEOUT_SECS").unwrap_or(defaults.default),
+        probe: env_secs("PP_API_PROBE_TIMEOUT_SECS").unwrap_or(defaults.probe),
+        health: env_secs("PP_API_HEALTH_TIMEOUT_SECS").unwrap_or(defaults.health),
+    }
+}
+
+// Hand-written so the token never ends up in logs.
+impl std::fmt::Debug for ApiConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ApiConfig")
+            .field("request_logging", &self.request_logging)
+            .field("token", &self.token.as_ref().map(|_| "<redacted>"))
+            .field("cors_origins", &self.cors_origins)
+            .field("timeouts", &self.timeouts)
+            .finish()
+    }
+}
+
+/// Split a comma-separated origin list, dropping blanks.
+fn parse_origins(raw: &str) -> Vec<String> {
+    raw.split(',')
+        .map(str::trim)
+        .filter(|o| !o.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn env_flag(name: &str) -> bool {
+    std::env::var(name)
+        .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+        .unwrap_or(false)
+}
 
 /// Build the application router with all API routes.
-pub fn router(state: AppState) -> Router {
-    Router::new()
+pub fn router(state: AppState, config: &ApiConfig) -> Router {
+    let mut app = Router::new()
         .nest("/api/v1", routes::api_routes())
         .fallback(fallback)
         .with_state(state)
+        // Innermost, so only handler time counts against the limit.
+        .layer(axum::middleware::from_fn_with_state(
+            config.timeouts,
+            middleware::timeout,
+        ));
+
+    match &config.token {
+        Some(token) => {
+            app = app.layer(axum::middleware::from_fn_with_state(
+                Arc::<str>::from(token.as_str()),
+                middleware::require_bearer,
+            ));
+        }
+        None => tracing::warn!("PP_API_TOKEN not set; API is unauthenticated"),
+    }
+
+    // Outside auth so preflights are answered without a token.
+    if let Some(cors) = middleware::cors_layer(&config.cors_origins) {
+        if config.token.is_some() && config.cors_origins.iter().any(|o| o == "*") {
+            tracing::warn!("CORS allows any origin while bearer auth is enabled; list dashboard origins explicitly");
+        }
+        app = app.layer(cors);
+    }
+
+    if config.request_logging {
+        app = app.layer(axum::middleware::from_fn(middleware::log_requests));
+    }
+
+    // Outermost, so logging and handlers both see the request ID.
+    app.layer(axum::middleware::from_fn(middleware::request_id))
 }
 
 async fn fallback() -> (axum::http::StatusCode, &'static str) {
     (axum::http::StatusCode::NOT_FOUND, "not found")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_origins() {
+        assert_eq!(
+            parse_origins(" https://a.example, ,http://b.local:3000 "),
+            vec!["https://a.example".to_string(), "http://b.local:3000".to_string()]
+        );
+        assert!(parse_origins("").is_empty());
+    }
+
+    #[test]
+    fn test_debug_redacts_token() {
+        let config = ApiConfig {
+            token: Some("s3cret".into()),
+            ..Default::default()
+        };
+        let dbg = format!("{:?}", config);
+        assert!(!dbg.contains("s3cret"));
+        assert!(dbg.contains("<redacted>"));
+    }
+}
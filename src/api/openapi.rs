@@ -0,0 +1,603 @@
+//! OpenAPI 3 description of the `/api/v1` surface.
+//!
+//! Hand-built rather than derived, so it has no macro dependencies. When a
+//! route or DTO in `routes.rs` changes, update the matching entry here; the
+//! tests below fail if a documented route goes missing.
+
+use serde_json::{json, Map, Value};
+
+/// Build the OpenAPI 3.0 document served at `GET /api/v1/openapi.json`.
+pub fn openapi() -> Value {
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "PacketParamedic API",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Appliance-grade network diagnostics for Raspberry Pi 5."
+        },
+        "servers": [{ "url": "/api/v1" }],
+        "paths": paths(),
+        "components": { "schemas": schemas() }
+    })
+}
+
+fn paths() -> Value {
+    let mut paths = Map::new();
+
+    paths.insert(
+        "/health".into(),
+        json!({ "get": op("health", "Liveness check with version", "Health") }),
+    );
+    paths.insert(
+        "/openapi.json".into(),
+        json!({ "get": op_raw("openapi", "This document", json!({ "type": "object" })) }),
+    );
+    paths.insert(
+        "/selftest".into(),
+        json!({
+            "post": {
+                "operationId": "runSelfTest",
+                "summary": "Run the hardware self-test now and store the report",
+                "parameters": [{
+                    "name": "quick", "in": "query", "required": false,
+                    "schema": { "type": "boolean", "default": false }
+                }],
+                "responses": {
+                    "200": response("Self-test report", "Data"),
+                    "500": response("Self-test failure", "Error")
+                }
+            }
+        }),
+    );
+    paths.insert(
+        "/selftest/latest".into(),
+        json!({ "get": op("selfTestLatest", "Last stored hardware self-test report", "NullableData") }),
+    );
+    paths.insert(
+        "/self-test/latest".into(),
+        json!({ "get": op("selfTestLatestAlias", "Alias of /selftest/latest", "NullableData") }),
+    );
+    paths.insert(
+        "/incidents".into(),
+        json!({
+            "get": {
+                "operationId": "listIncidents",
+                "summary": "Incidents active since a point in time (default: the last 7 days)",
+                "parameters": [{
+                    "name": "since", "in": "query", "required": false,
+                    "schema": { "type": "string", "format": "date-time" }
+                }],
+                "responses": { "200": response("Incidents, oldest first", "ListEnvelope") }
+            }
+        }),
+    );
+    paths.insert(
+        "/probes/status".into(),
+        json!({ "get": op("probeStatus", "Running/queued scheduled tests, data budget and thermal history", "ProbeStatus") }),
+    );
+    paths.insert(
+        "/blame-check".into(),
+        json!({
+            "post": {
+                "operationId": "blameCheck",
+                "summary": "Run a blame check now, optionally overriding probe timeouts",
+                "requestBody": body("BlameCheckRequest"),
+                "responses": {
+                    "200": response("Verdict, confidence, and evidence", "BlameCheck"),
+                    "400": response("Invalid retry policy", "Error"),
+                    "500": response("Probe failure", "Error")
+                }
+            }
+        }),
+    );
+    paths.insert(
+        "/speed-test/latest".into(),
+        json!({ "get": op("speedTestLatest", "Most recent speed test", "NullableData") }),
+    );
+    paths.insert(
+        "/speed-test/history".into(),
+        json!({ "get": op("speedTestHistory", "All past speed tests", "ListEnvelope") }),
+    );
+    paths.insert(
+        "/schedules".into(),
+        json!({
+            "get": op("listSchedules", "Configured cron schedules", "ScheduleList"),
+            "post": {
+                "operationId": "createSchedule",
+                "summary": "Add a cron schedule",
+                "requestBody": body("CreateSchedule"),
+                "responses": {
+                    "201": response("Created", "Message"),
+                    "400": response("Invalid name, cron, or test", "Error")
+                }
+            }
+        }),
+    );
+    paths.insert(
+        "/schedules/{name}".into(),
+        json!({
+            "delete": {
+                "operationId": "deleteSchedule",
+                "summary": "Remove a schedule by name",
+                "parameters": [{
+                    "name": "name", "in": "path", "required": true,
+                    "schema": { "type": "string" }
+                }],
+                "responses": {
+                    "200": response("Deleted", "Message"),
+                    "404": response("No such schedule", "Error")
+                }
+            }
+        }),
+    );
+    paths.insert(
+        "/schedules/dry-run".into(),
+        json!({
+            "get": {
+                "operationId": "scheduleDryRun",
+                "summary": "Preview upcoming scheduled runs",
+                "parameters": [{
+                    "name": "hours", "in": "query", "required": true,
+                    "schema": { "type": "integer", "minimum": 0 }
+                }],
+                "responses": { "200": response("Upcoming runs", "DryRun") }
+            }
+        }),
+    );
+    paths.insert(
+        "/trace".into(),
+        json!({
+            "get": op("listTraces", "Last 50 stored traces", "TraceList"),
+            "post": {
+                "operationId": "runTrace",
+                "summary": "Run an MTR trace and store the result",
+                "requestBody": body("TraceRequest"),
+                "responses": {
+                    "200": response("MTR report", "Data"),
+                    "500": response("Trace or storage failure", "Error")
+                }
+            }
+        }),
+    );
+    paths.insert(
+        "/network/interfaces".into(),
+        json!({ "get": op("networkInterfaces", "Detected network interfaces", "Data") }),
+    );
+    paths.insert(
+        "/measurements/trend".into(),
+        json!({
+            "get": {
+                "operationId": "measurementTrend",
+                "summary": "Hourly min/avg/max/p95 across raw measurements and rollups",
+                "parameters": [
+                    {
+                        "name": "probe_type", "in": "query", "required": true,
+                        "schema": { "type": "string" }
+                    },
+                    {
+                        "name": "target", "in": "query", "required": true,
+                        "schema": { "type": "string" }
+                    },
+                    {
+                        "name": "hours", "in": "query", "required": false,
+                        "schema": { "type": "integer", "minimum": 0, "default": 168 }
+                    }
+                ],
+                "responses": {
+                    "200": response("Hourly points, oldest first", "TrendList"),
+                    "500": response("Storage failure", "Error")
+                }
+            }
+        }),
+    );
+
+    paths.insert(
+        "/timeseries".into(),
+        json!({
+            "get": {
+                "operationId": "timeseries",
+                "summary": "One metric as [value, epoch_ms] points (Grafana JSON shape)",
+                "parameters": [
+                    {
+                        "name": "metric", "in": "query", "required": true,
+                        "description": "<probe>_latency or <probe>_errors; probe is icmp, dns, http or tcp",
+                        "schema": { "type": "string" }
+                    },
+                    {
+                        "name": "target", "in": "query", "required": true,
+                        "schema": { "type": "string" }
+                    },
+                    {
+                        "name": "step", "in": "query", "required": false,
+                        "schema": { "type": "string", "default": "5m" }
+                    },
+                    {
+                        "name": "from", "in": "query", "required": false,
+                        "description": "Epoch ms (default: 24 h before `to`)",
+                        "schema": { "type": "integer" }
+                    },
+                    {
+                        "name": "to", "in": "query", "required": false,
+                        "description": "Epoch ms (default: now)",
+                        "schema": { "type": "integer" }
+                    }
+                ],
+                "responses": {
+                    "200": response("Series", "Timeseries"),
+                    "400": response("Unknown metric, bad step, or too many points", "Error")
+                }
+            }
+        }),
+    );
+    paths.insert(
+        "/grafana".into(),
+        json!({
+            "get": {
+                "operationId": "grafanaPing",
+                "summary": "SimpleJSON datasource connection test",
+                "responses": { "200": { "description": "OK" } }
+            }
+        }),
+    );
+    paths.insert(
+        "/grafana/search".into(),
+        json!({
+            "post": {
+                "operationId": "grafanaSearch",
+                "summary": "SimpleJSON search: metric:target series with data",
+                "responses": {
+                    "200": {
+                        "description": "Series names",
+                        "content": { "application/json": { "schema": {
+                            "type": "array", "items": { "type": "string" }
+                        } } }
+                    }
+                }
+            }
+        }),
+    );
+    paths.insert(
+        "/grafana/query".into(),
+        json!({
+            "post": {
+                "operationId": "grafanaQuery",
+                "summary": "SimpleJSON query (bare array, no envelope)",
+                "requestBody": {
+                    "required": true,
+                    "content": { "application/json": { "schema": {
+                        "type": "object", "additionalProperties": true
+                    } } }
+                },
+                "responses": {
+                    "200": {
+                        "description": "One series per target",
+                        "content": { "application/json": { "schema": {
+                            "type": "array", "items": schema_ref("Series")
+                        } } }
+                    },
+                    "400": response("Unknown series name", "Error")
+                }
+            }
+        }),
+    );
+
+    Value::Object(paths)
+}
+
+/// A `GET`-style operation with a single 200 response.
+fn op(id: &str, summary: &str, schema: &str) -> Value {
+    op_raw(id, summary, schema_ref(schema))
+}
+
+fn op_raw(id: &str, summary: &str, schema: Value) -> Value {
+    json!({
+        "operationId": id,
+        "summary": summary,
+        "responses": {
+            "200": {
+                "description": "OK",
+                "content": { "application/json": { "schema": schema } }
+            }
+        }
+    })
+}
+
+fn response(description: &str, schema: &str) -> Value {
+    json!({
+        "description": description,
+        "content": { "application/json": { "schema": schema_ref(schema) } }
+    })
+}
+
+fn body(schema: &str) -> Value {
+    json!({
+        "required": true,
+        "content": { "application/json": { "schema": schema_ref(schema) } }
+    })
+}
+
+fn schema_ref(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+/// Wrap a `data` schema in the `{ "data": ..., "meta": ... }` envelope.
+fn envelope(data: Value) -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "data": data,
+            "meta": { "type": "object", "additionalProperties": true }
+        }
+    })
+}
+
+fn schemas() -> Value {
+    json!({
+        "Error": {
+            "type": "object",
+            "required": ["error"],
+            "properties": { "error": { "type": "string" } }
+        },
+        "Data": envelope(json!({ "type": "object", "additionalProperties": true })),
+        "NullableData": envelope(json!({ "type": "object", "nullable": true })),
+        "ListEnvelope": envelope(json!({ "type": "array", "items": { "type": "object" } })),
+        "Message": envelope(json!({
+            "type": "object",
+            "properties": { "message": { "type": "string" } }
+        })),
+        "Health": envelope(json!({
+            "type": "object",
+            "properties": {
+                "status": { "type": "string", "enum": ["ok", "degraded"], "example": "ok" },
+                "version": { "type": "string" },
+                "storage": schema_ref("StorageStatus")
+            }
+        })),
+        "StorageStatus": {
+            "type": "object",
+            "properties": {
+                "degraded": { "type": "boolean" },
+                "reason": { "type": "string", "enum": ["disk_full", "read_only"] },
+                "dropped_writes": { "type": "integer" },
+                "wal_bytes": { "type": "integer", "nullable": true, "description": "Current size of the SQLite -wal file" }
+            }
+        },
+        "ProbeStatus": envelope(json!({
+            "type": "object",
+            "properties": {
+                "active_probes": { "type": "integer", "description": "Scheduled tests running now" },
+                "jobs": {
+                    "type": "object",
+                    "description": "Scheduled tests running and queued under PP_MAX_CONCURRENT_JOBS",
+                    "properties": {
+                        "running": { "type": "integer" },
+                        "queued": { "type": "integer" },
+                        "limit": { "type": "integer" }
+                    }
+                },
+                "data_budget": schema_ref("DataBudget"),
+                "thermal": schema_ref("ThermalHistory")
+            }
+        })),
+        "ThermalHistory": {
+            "type": "object",
+            "description": "SoC temperature and vcgencmd throttle flags sampled every 30 s over the last hour.",
+            "properties": {
+                "window_minutes": { "type": "integer", "example": 60 },
+                "throttled": { "type": "boolean" },
+                "under_voltage": { "type": "boolean" },
+                "samples": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "timestamp": { "type": "string", "format": "date-time" },
+                            "temp_c": { "type": "number", "nullable": true },
+                            "throttle_mask": { "type": "integer", "nullable": true }
+                        }
+                    }
+                }
+            }
+        },
+        "DataBudget": {
+            "type": "object",
+            "description": "Scheduled throughput data used today (UTC) against PP_DAILY_BW_BUDGET_GB.",
+            "properties": {
+                "day": { "type": "string", "format": "date" },
+                "limit_bytes": { "type": "integer", "nullable": true },
+                "used_bytes": { "type": "integer" },
+                "remaining_bytes": { "type": "integer", "nullable": true },
+                "exhausted": { "type": "boolean" }
+            }
+        },
+        "CreateSchedule": {
+            "type": "object",
+            "required": ["name", "cron", "test"],
+            "properties": {
+                "name": { "type": "string" },
+                "cron": { "type": "string", "example": "0 3 * * *" },
+                "test": { "type": "string", "example": "speed-test-light" },
+                "params": {
+                    "type": "object",
+                    "description": "Test parameters checked against the test type (e.g. target, provider, duration_sec); reflector_throughput / reflector_udp_echo need a paired reflector",
+                    "example": { "reflector": "office" }
+                }
+            }
+        },
+        "Schedule": {
+            "type": "object",
+            "properties": {
+                "name": { "type": "string" },
+                "cron": { "type": "string" },
+                "test": { "type": "string" },
+                "enabled": { "type": "boolean" },
+                "params": { "type": "object", "nullable": true }
+            }
+        },
+        "ScheduleList": envelope(json!({
+            "type": "array",
+            "items": schema_ref("Schedule")
+        })),
+        "DryRun": envelope(json!({
+            "type": "object",
+            "properties": {
+                "upcoming": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "time": { "type": "string", "format": "date-time" },
+                            "name": { "type": "string" },
+                            "test": { "type": "string" },
+                            "estimated_bytes": { "type": "integer" }
+                        }
+                    }
+                },
+                "estimated_usage": {
+                    "type": "object",
+                    "properties": {
+                        "window_bytes": { "type": "integer" },
+                        "daily_bytes": { "type": "integer" },
+                        "monthly_bytes": { "type": "integer" }
+                    }
+                }
+            }
+        })),
+        "BlameCheckRequest": {
+            "type": "object",
+            "properties": {
+                "timeouts": {
+                    "type": "object",
+                    "description": "Per-type timeouts in ms; omitted fields use the configured defaults",
+                    "properties": {
+                        "icmp_ms": { "type": "integer", "example": 1000 },
+                        "dns_ms": { "type": "integer", "example": 2000 },
+                        "http_ms": { "type": "integer", "example": 5000 },
+                        "tcp_ms": { "type": "integer", "example": 2000 }
+                    }
+                },
+                "retry": {
+                    "type": "object",
+                    "description": "Attempts per probe and how many must succeed",
+                    "required": ["attempts", "required_successes"],
+                    "properties": {
+                        "attempts": { "type": "integer", "minimum": 1, "maximum": 10, "example": 3 },
+                        "required_successes": { "type": "integer", "example": 2 }
+                    }
+                }
+            }
+        },
+        "BlameCheck": envelope(json!({
+            "type": "object",
+            "properties": {
+                "verdict": { "type": "string", "example": "Healthy" },
+                "fault": {
+                    "type": "string",
+                    "enum": ["healthy", "local", "isp", "dns", "service", "unknown"],
+                    "example": "healthy"
+                },
+                "confidence": { "type": "integer" },
+                "details": { "type": "array", "items": { "type": "string" } }
+            }
+        })),
+        "TraceRequest": {
+            "type": "object",
+            "required": ["target"],
+            "properties": { "target": { "type": "string", "example": "8.8.8.8" } }
+        },
+        "TraceSummary": {
+            "type": "object",
+            "properties": {
+                "id": { "type": "integer" },
+                "target": { "type": "string" },
+                "hop_count": { "type": "integer" },
+                "max_latency": { "type": "number" },
+                "avg_loss": { "type": "number" },
+                "created_at": { "type": "string" }
+            }
+        },
+        "Series": {
+            "type": "object",
+            "properties": {
+                "target": { "type": "string", "description": "<metric>:<target>" },
+                "datapoints": {
+                    "type": "array",
+                    "description": "[value, epoch_ms] pairs, oldest first",
+                    "items": { "type": "array", "items": { "type": "number" } }
+                }
+            }
+        },
+        "Timeseries": envelope(schema_ref("Series")),
+        "TrendPoint": {
+            "type": "object",
+            "properties": {
+                "hour": { "type": "string", "description": "YYYY-MM-DD HH:00:00 (UTC)" },
+                "count": { "type": "integer" },
+                "error_count": { "type": "integer" },
+                "min": { "type": "number", "nullable": true },
+                "avg": { "type": "number", "nullable": true },
+                "max": { "type": "number", "nullable": true },
+                "p95": { "type": "number", "nullable": true },
+                "rolled_up": { "type": "boolean" }
+            }
+        },
+        "TrendList": envelope(json!({
+            "type": "array",
+            "items": schema_ref("TrendPoint")
+        })),
+        "TraceList": envelope(json!({
+            "type": "array",
+            "items": schema_ref("TraceSummary")
+        }))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_openapi_covers_routes() {
+        let doc = openapi();
+        assert_eq!(doc["openapi"], "3.0.3");
+
+        let paths = doc["paths"].as_object().unwrap();
+        for (path, method) in [
+            ("/health", "get"),
+            ("/schedules", "get"),
+            ("/schedules", "post"),
+            ("/schedules/{name}", "delete"),
+            ("/schedules/dry-run", "get"),
+            ("/trace", "post"),
+            ("/blame-check", "post"),
+            ("/selftest", "post"),
+            ("/selftest/latest", "get"),
+            ("/speed-test/history", "get"),
+            ("/measurements/trend", "get"),
+            ("/timeseries", "get"),
+            ("/grafana", "get"),
+            ("/grafana/search", "post"),
+            ("/grafana/query", "post"),
+            ("/openapi.json", "get"),
+        ] {
+            assert!(
+                paths.get(path).and_then(|p| p.get(method)).is_some(),
+                "{} {} not documented",
+                method,
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn test_openapi_refs_resolve() {
+        let doc = openapi();
+        let schemas = doc["components"]["schemas"].as_object().unwrap();
+        let text = doc.to_string();
+
+        for part in text.split("#/components/schemas/").skip(1) {
+            let name: String = part.chars().take_while(|c| *c != '"').collect();
+            assert!(schemas.contains_key(&name), "dangling $ref to {}", name);
+        }
+    }
+}
@@ -1,19 +1,24 @@
 //! API route definitions.
 
 use axum::{
-    routing::{delete, get},
-    Json, Router,
+    routing::{delete, get, post},
+    Extension, Json, Router,
 };
 use serde_json::{json, Value};
+use tokio_util::sync::CancellationToken;
 
 use crate::api::state::AppState;
 
 pub fn api_routes() -> Router<AppState> {
     Router::new()
         .route("/health", get(health))
+        .route("/openapi.json", get(openapi))
+        .route("/selftest", post(run_self_test))
+        .route("/selftest/latest", get(self_test_latest))
         .route("/self-test/latest", get(self_test_latest))
         .route("/incidents", get(list_incidents))
         .route("/probes/status", get(probe_status))
+        .route("/blame-check", post(blame_check))
         .route("/speed-test/latest", get(speed_test_latest))
         .route("/speed-test/history", get(speed_test_history))
         .route("/schedules", get(list_schedules).post(create_schedule))
@@ -21,13 +26,29 @@ pub fn api_routes() -> Router<AppState> {
         .route("/schedules/dry-run", get(schedule_dry_run))
         .route("/trace", get(list_traces).post(run_trace))
         .route("/network/interfaces", get(network_interfaces))
+        .route("/measurements/trend", get(measurement_trend))
+        .route("/timeseries", get(timeseries))
+        .route("/grafana", get(grafana_ping))
+        .route("/grafana/search", post(grafana_search))
+        .route("/grafana/query", post(grafana_query))
 }
 
-async fn health() -> Json<Value> {
+async fn health(State(state): State<AppState>) -> Json<Value> {
+    let storage = crate::storage::health::status();
+    let status = if storage.degraded { "degraded" } else { "ok" };
+    let mut storage = json!(storage);
+    storage["wal_bytes"] = match crate::storage::wal::wal_size_bytes(&state.pool) {
+        Ok(size) => json!(size),
+        Err(e) => {
+            tracing::debug!("WAL size unavailable: {:#}", e);
+            Value::Null
+        }
+    };
     Json(json!({
         "data": {
-            "status": "ok",
-            "version": env!("CARGO_PKG_VERSION")
+            "status": status,
+            "version": env!("CARGO_PKG_VERSION"),
+            "storage": storage
         },
         "meta": {
             "timestamp": chrono::Utc::now().to_rfc3339(),
@@ -36,16 +57,172 @@ async fn health() -> Json<Value> {
     }))
 }
 
-async fn self_test_latest() -> Json<Value> {
-    Json(json!({ "data": null, "meta": { "message": "no self-test results yet" } }))
+async fn openapi() -> Json<Value> {
+    Json(crate::api::openapi::openapi())
 }
 
-async fn list_incidents() -> Json<Value> {
-    Json(json!({ "data": [], "meta": { "total": 0 } }))
+#[derive(Deserialize)]
+struct SelfTestParams {
+    #[serde(default)]
+    quick: bool,
+}
+
+async fn run_self_test(
+    State(state): State<AppState>,
+    Query(params): Query<SelfTestParams>,
+) -> (StatusCode, Json<Value>) {
+    match crate::selftest::run_with(params.quick).await {
+        Ok(report) => {
+            if let Err(e) = crate::selftest::save_report(&state.pool, &report, params.quick) {
+                tracing::warn!("Failed to record self-test: {:#}", e);
+            }
+            (
+                StatusCode::OK,
+                Json(json!({ "data": report, "meta": { "quick": params.quick } })),
+            )
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": e.to_string() })),
+        ),
+    }
+}
+
+async fn self_test_latest(State(state): State<AppState>) -> (StatusCode, Json<Value>) {
+    match crate::selftest::latest_report(&state.pool) {
+        Ok(Some(stored)) => (
+            StatusCode::OK,
+            Json(json!({
+                "data": stored.report,
+                "meta": { "quick": stored.quick, "created_at": stored.created_at.to_rfc3339() }
+            })),
+        ),
+        Ok(None) => (
+            StatusCode::OK,
+            Json(json!({ "data": null, "meta": { "message": "no self-test results yet" } })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": e.to_string() })),
+        ),
+    }
 }
 
-async fn probe_status() -> Json<Value> {
-    Json(json!({ "data": { "active_probes": 0 } }))
+/// Default look-back of `/incidents`.
+const INCIDENTS_DEFAULT_DAYS: i64 = 7;
+
+#[derive(Deserialize)]
+struct IncidentParams {
+    /// RFC 3339; defaults to a week ago.
+    since: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Incidents active since `since`, oldest first.
+async fn list_incidents(
+    State(state): State<AppState>,
+    Query(params): Query<IncidentParams>,
+) -> (StatusCode, Json<Value>) {
+    let since = params
+        .since
+        .unwrap_or_else(|| chrono::Utc::now() - chrono::Duration::days(INCIDENTS_DEFAULT_DAYS));
+    match crate::storage::list_incidents(&state.pool, since) {
+        Ok(incidents) => (
+            StatusCode::OK,
+            Json(json!({
+                "data": incidents,
+                "meta": { "total": incidents.len(), "since": since.to_rfc3339() }
+            })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": e.to_string() })),
+        ),
+    }
+}
+
+/// Thermal history shown by `/probes/status`.
+const THERMAL_STATUS_MINUTES: i64 = 60;
+
+async fn probe_status(State(state): State<AppState>) -> Json<Value> {
+    let data_budget = match state.scheduler.data_budget().status(&state.pool) {
+        Ok(status) => json!(status),
+        Err(e) => json!({ "error": e.to_string() }),
+    };
+    let thermal = match crate::system::thermal::recent(&state.pool, THERMAL_STATUS_MINUTES) {
+        Ok(samples) => json!({
+            "window_minutes": THERMAL_STATUS_MINUTES,
+            "throttled": samples.iter().any(|s| s.throttled()),
+            "under_voltage": samples.iter().any(|s| s.under_voltage()),
+            "samples": samples,
+        }),
+        Err(e) => json!({ "error": e.to_string() }),
+    };
+    let jobs = state.scheduler.job_queue().counts();
+    Json(json!({
+        "data": {
+            "active_probes": jobs.running,
+            "jobs": jobs,
+            "data_budget": data_budget,
+            "thermal": thermal
+        }
+    }))
+}
+
+#[derive(Deserialize, Default)]
+struct BlameCheckRequest {
+    #[serde(default)]
+    timeouts: crate::probes::TimeoutOverrides,
+    #[serde(default)]
+    retry: Option<crate::probes::ProbeRetry>,
+}
+
+/// Run a blame check now. Per-type timeouts and the retry policy default to
+/// the `PP_PROBE_*` settings and can be overridden per call. The probes stop
+/// when the request times out or the client disconnects.
+async fn blame_check(
+    State(state): State<AppState>,
+    Extension(cancel): Extension<CancellationToken>,
+    Json(req): Json<BlameCheckRequest>,
+) -> (StatusCode, Json<Value>) {
+    let timeouts = crate::probes::ProbeTimeouts::from_env().with_overrides(&req.timeouts);
+    let retry = match req.retry.map(Ok).unwrap_or_else(crate::probes::ProbeRetry::from_env) {
+        Ok(r) => r,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({ "error": e.to_string() }))),
+    };
+    if let Err(e) = retry.validate() {
+        return (StatusCode::BAD_REQUEST, Json(json!({ "error": e.to_string() })));
+    }
+    let lan = crate::probes::lan::LanReflector::discover();
+    match crate::probes::run_blame_check(&timeouts, &retry, Some(&state.pool), lan.as_ref(), &cancel).await {
+        Ok(report) => {
+            if let Err(e) = crate::analysis::blame_history::save_blame_check(&state.pool, &report) {
+                tracing::warn!("Failed to record blame check: {:#}", e);
+            }
+            (
+                StatusCode::OK,
+                Json(json!({
+                    "data": report,
+                    "meta": {
+                        "timeouts_ms": {
+                            "icmp": timeouts.icmp.as_millis() as u64,
+                            "dns": timeouts.dns.as_millis() as u64,
+                            "http": timeouts.http.as_millis() as u64,
+                            "tcp": timeouts.tcp.as_millis() as u64,
+                        },
+                        "retry": {
+                            "attempts": retry.attempts,
+                            "required_successes": retry.required_successes,
+                        },
+                        "lan_reflector": lan.as_ref().map(|l| l.nickname.clone()),
+                    }
+                })),
+            )
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": e.to_string() })),
+        ),
+    }
 }
 
 async fn speed_test_latest() -> Json<Value> {
@@ -69,6 +246,9 @@ struct CreateSchedule {
     name: String,
     cron: String,
     test: String,
+    /// Test parameters, e.g. `{"reflector": "office"}` for reflector tests.
+    #[serde(default)]
+    params: Option<Value>,
 }
 
 #[derive(Serialize)]
@@ -77,6 +257,8 @@ struct ScheduleDto {
     cron: String,
     test: String,
     enabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    params: Option<Value>,
 }
 
 async fn list_schedules(State(state): State<AppState>) -> Json<Value> {
@@ -84,11 +266,12 @@ async fn list_schedules(State(state): State<AppState>) -> Json<Value> {
         Ok(list) => {
             let dtos: Vec<ScheduleDto> = list
                 .into_iter()
-                .map(|(name, cron, test, enabled)| ScheduleDto {
+                .map(|(name, cron, test, enabled, params)| ScheduleDto {
                     name,
                     cron,
                     test,
                     enabled,
+                    params,
                 })
                 .collect();
             Json(json!({ "data": dtos, "meta": { "total": dtos.len() } }))
@@ -105,13 +288,25 @@ async fn create_schedule(
 ) -> (StatusCode, Json<Value>) {
     match state
         .scheduler
-        .add_schedule(&payload.name, &payload.cron, &payload.test)
+        .add_schedule_with_params(&payload.name, &payload.cron, &payload.test, payload.params.as_ref())
         .await
     {
-        Ok(_) => (
-            StatusCode::CREATED,
-            Json(json!({ "data": { "message": "created" } })),
-        ),
+        Ok(_) => {
+            use crate::scheduler::cron::{describe, next_runs};
+            let next: Vec<String> = next_runs(&payload.cron, chrono::Utc::now(), 3)
+                .unwrap_or_default()
+                .iter()
+                .map(|t| t.to_rfc3339())
+                .collect();
+            (
+                StatusCode::CREATED,
+                Json(json!({ "data": {
+                    "message": "created",
+                    "description": describe(&payload.cron).ok(),
+                    "next_runs": next,
+                } })),
+            )
+        }
         Err(e) => (
             StatusCode::BAD_REQUEST,
             Json(json!({ "error": e.to_string() })),
@@ -139,68 +334,203 @@ async fn schedule_dry_run(
     State(state): State<AppState>,
     Query(params): Query<DryRunParams>,
 ) -> Json<Value> {
-    match state.scheduler.preview_next_runs(params.hours).await {
-        Ok(preview) => {
+    match state.scheduler.preview_data_usage(params.hours).await {
+        Ok((preview, usage)) => {
             let runs: Vec<Value> = preview
                 .into_iter()
-                .map(|(time, name, test)| json!({ "time": time, "name": name, "test": test }))
+                .map(|(time, name, test, bytes)| {
+                    json!({ "time": time, "name": name, "test": test, "estimated_bytes": bytes })
+                })
                 .collect();
-            Json(json!({ "data": { "upcoming": runs } }))
+            Json(json!({ "data": { "upcoming": runs, "estimated_usage": usage } }))
         }
         Err(e) => Json(json!({ "error": e.to_string() })),
     }
 }
 
+#[derive(Deserialize)]
+struct TrendParams {
+    probe_type: String,
+    target: String,
+    #[serde(default = "default_trend_hours")]
+    hours: u32,
+}
+
+fn default_trend_hours() -> u32 {
+    24 * 7
+}
+
+/// Hourly trend for one probe type + target, spanning raw measurements and
+/// hourly rollups.
+async fn measurement_trend(
+    State(state): State<AppState>,
+    Query(params): Query<TrendParams>,
+) -> (StatusCode, Json<Value>) {
+    match crate::analysis::trend::hourly_trend(
+        &state.pool,
+        &params.probe_type,
+        &params.target,
+        params.hours,
+    ) {
+        Ok(points) => (
+            StatusCode::OK,
+            Json(json!({
+                "data": points,
+                "meta": {
+                    "total": points.len(),
+                    "raw_retention_days": crate::storage::rollup::retention_days()
+                }
+            })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": e.to_string() })),
+        ),
+    }
+}
+
+#[derive(Deserialize)]
+struct TimeseriesParams {
+    metric: String,
+    target: String,
+    #[serde(default = "default_step")]
+    step: String,
+    /// Epoch ms; defaults to 24 hours before `to`.
+    from: Option<i64>,
+    /// Epoch ms; defaults to now.
+    to: Option<i64>,
+}
+
+fn default_step() -> String {
+    "5m".to_string()
+}
+
+/// One metric as `[value, epoch_ms]` pairs, the shape Grafana's JSON
+/// datasources plot.
+async fn timeseries(
+    State(state): State<AppState>,
+    Query(params): Query<TimeseriesParams>,
+) -> (StatusCode, Json<Value>) {
+    use crate::analysis::timeseries::{parse_step, series, Metric};
+
+    let (metric, step) = match (Metric::parse(&params.metric), parse_step(&params.step)) {
+        (Ok(metric), Ok(step)) => (metric, step),
+        (Err(e), _) | (_, Err(e)) => {
+            return (StatusCode::BAD_REQUEST, Json(json!({ "error": e.to_string() })))
+        }
+    };
+    let to = params
+        .to
+        .unwrap_or_else(|| chrono::Utc::now().timestamp_millis());
+    let from = params.from.unwrap_or(to - 24 * 3_600_000);
+
+    match series(&state.pool, &metric, &params.target, from, to, step) {
+        Ok(points) => (
+            StatusCode::OK,
+            Json(json!({
+                "data": {
+                    "target": format!("{}:{}", metric.name(), params.target),
+                    "datapoints": points
+                },
+                "meta": { "from": from, "to": to, "step_secs": step }
+            })),
+        ),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e.to_string() }))),
+    }
+}
+
+/// SimpleJSON datasource connection test.
+async fn grafana_ping() -> StatusCode {
+    StatusCode::OK
+}
+
+/// SimpleJSON `/search`: every `metric:target` series with data.
+async fn grafana_search(State(state): State<AppState>) -> (StatusCode, Json<Value>) {
+    match crate::analysis::timeseries::series_names(&state.pool) {
+        Ok(names) => (StatusCode::OK, Json(json!(names))),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": e.to_string() })),
+        ),
+    }
+}
+
+#[derive(Deserialize)]
+struct GrafanaRange {
+    from: chrono::DateTime<chrono::Utc>,
+    to: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Deserialize)]
+struct GrafanaTarget {
+    target: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GrafanaQuery {
+    range: GrafanaRange,
+    #[serde(default)]
+    interval_ms: Option<i64>,
+    #[serde(default)]
+    targets: Vec<GrafanaTarget>,
+}
+
+/// SimpleJSON `/query`. Grafana expects a bare array here, so unlike the
+/// rest of the API the response has no `data` envelope.
+async fn grafana_query(
+    State(state): State<AppState>,
+    Json(query): Json<GrafanaQuery>,
+) -> (StatusCode, Json<Value>) {
+    use crate::analysis::timeseries::{parse_series_name, series};
+
+    let from = query.range.from.timestamp_millis();
+    let to = query.range.to.timestamp_millis();
+    // Nothing is measured more often than the scheduler's default 10 s
+    // minimum schedule interval.
+    let step = (query.interval_ms.unwrap_or(60_000) / 1000).max(10);
+
+    let mut out = Vec::with_capacity(query.targets.len());
+    for t in &query.targets {
+        let result = parse_series_name(&t.target)
+            .and_then(|(metric, target)| series(&state.pool, &metric, &target, from, to, step));
+        match result {
+            Ok(points) => out.push(json!({ "target": t.target, "datapoints": points })),
+            Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({ "error": e.to_string() }))),
+        }
+    }
+    (StatusCode::OK, Json(Value::Array(out)))
+}
+
 async fn network_interfaces() -> Json<Value> {
     Json(json!({ "data": { "interfaces": [] } }))
 }
 
 use crate::probes::trace::{self, MtrReport};
-use rusqlite::params;
 
 #[derive(Deserialize)]
 struct TraceRequest {
     target: String,
 }
 
+/// Run a trace now. `mtr` is killed when the request times out or the
+/// client disconnects.
 async fn run_trace(
     State(state): State<AppState>,
+    Extension(cancel): Extension<CancellationToken>,
     Json(payload): Json<TraceRequest>,
 ) -> (StatusCode, Json<Value>) {
-    // 1. Run trace (blocking operation)
-    let target = payload.target.clone();
-    let result = tokio::task::spawn_blocking(move || trace::run_trace(&target)).await;
-
-    match result {
-        Ok(Ok(report)) => {
+    // 1. Run trace
+    match trace::run_trace_cancellable(&payload.target, &cancel).await {
+        Ok(report) => {
             // 2. Persist to DB
-            let conn = match state.pool.get() {
-                Ok(c) => c,
-                Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": "DB pool error" }))),
-            };
-
-            let json_str = serde_json::to_string(&report).unwrap_or_default();
-            
-            // Calculate summary stats
-            let hubs = &report.report.mtr.hubs;
-            let hop_count = hubs.len();
-            let max_lat = hubs.iter().map(|h| h.worst).fold(0.0, f32::max);
-            let avg_loss = if hop_count > 0 {
-                hubs.iter().map(|h| h.loss_percent).sum::<f32>() / hop_count as f32
-            } else { 0.0 };
-
-            if let Err(e) = conn.execute(
-                "INSERT INTO trace_results (target, hop_count, max_latency_ms, avg_loss_percent, result_json) 
-                 VALUES (?1, ?2, ?3, ?4, ?5)",
-                params![report.report.mtr.dst, hop_count, max_lat, avg_loss, json_str],
-            ) {
+            if let Err(e) = trace::save(&state.pool, &report) {
                 return (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": e.to_string() })));
             }
 
             (StatusCode::OK, Json(json!({ "data": report })))
         }
-        Ok(Err(e)) => (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": e.to_string() }))),
-        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": format!("Task join error: {}", e) }))),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": e.to_string() }))),
     }
 }
 
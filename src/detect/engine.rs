@@ -1,103 +1,214 @@
 use crate::storage::Pool;
 use crate::detect::incident::IncidentManager;
-use crate::analysis::stats::check_for_anomaly;
+use crate::detect::EscalationThresholds;
+use crate::analysis::stats::{check_for_anomaly, Anomaly};
 use anyhow::Result;
+use rusqlite::OptionalExtension;
 use tracing::{info, warn};
 
+/// Anomalies on one target measured within this many minutes of each other
+/// are reported as a single incident.
+pub const CORRELATION_WINDOW_MINUTES: i64 = 10;
+
+/// How far back each scan looks for latency-under-load measurements to
+/// pair with speed tests, and for lossy throughput runs; ones already
+/// reported are skipped.
+const SATURATION_LOOKBACK_HOURS: u32 = 24;
+
 pub struct AnomalyEngine {
     pool: Pool,
-    incident_manager: IncidentManager,
 }
 
 impl AnomalyEngine {
     pub fn new(pool: Pool) -> Self {
-        let incident_manager = IncidentManager::new(pool.clone());
-        Self { pool, incident_manager }
+        Self { pool }
     }
 
     /// Run a scan for anomalies.
     /// This iterates over known targets and checks their latest value against the baseline.
     /// Typically called by a cron schedule (e.g. "anomaly-scan").
+    ///
+    /// The whole scan reads and writes SQLite, so it runs as one blocking task.
     pub async fn run_scan(&self) -> Result<()> {
         info!("Running anomaly detection scan");
-        
         let pool = self.pool.clone();
-        
-        // Find all active targets in the last hour
-        let targets: Vec<(String, String)> = tokio::task::spawn_blocking(move || -> Result<Vec<(String, String)>> {
-            let conn = pool.get()?;
-            let mut stmt = conn.prepare(
-                "SELECT DISTINCT probe_type, target FROM measurements 
-                 WHERE created_at > datetime('now', '-1 hour')"
-            )?;
-            let rows = stmt.query_map([], |row| {
-                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
-            })?;
-            
-            let mut res = Vec::new();
-            for r in rows { res.push(r?); }
-            Ok(res)
-        }).await??;
-
-        for (probe_type, target) in targets {
-            self.analyze_target(&probe_type, &target).await?;
+        tokio::task::spawn_blocking(move || scan(&pool)).await?
+    }
+}
+
+fn scan(pool: &Pool) -> Result<()> {
+    let incident_manager = IncidentManager::new(pool.clone());
+
+    // Find all active targets in the last hour
+    let targets: Vec<(String, String)> = {
+        let conn = pool.get()?;
+        let mut stmt = conn.prepare(
+            "SELECT DISTINCT probe_type, target FROM measurements 
+             WHERE created_at > datetime('now', '-1 hour') AND dscp IS NULL"
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+        rows.collect::<rusqlite::Result<_>>()?
+    };
+
+    let mut anomalies = Vec::new();
+    for (probe_type, target) in targets {
+        if let Some(anomaly) = analyze_target(pool, &probe_type, &target)? {
+            warn!(target=%anomaly.target, "Anomaly Detected: {:?}", anomaly);
+            anomalies.push(anomaly);
         }
-        
-        Ok(())
     }
 
-    async fn analyze_target(&self, probe_type: &str, target: &str) -> Result<()> {
-        let pool = self.pool.clone();
-        let pt = probe_type.to_string();
-        let t = target.to_string();
-
-        // Fetch latest measurement
-        let latest: Option<(f64, String)> = tokio::task::spawn_blocking(move || -> Result<Option<(f64, String)>> {
-            let conn = pool.get()?;
-            let mut stmt = conn.prepare(
-                "SELECT value, created_at FROM measurements 
-                 WHERE probe_type = ?1 AND target = ?2 
-                 ORDER BY created_at DESC LIMIT 1"
-            )?;
-            
-            let mut rows = stmt.query_map(rusqlite::params![pt, t], |row| {
-                Ok((row.get::<_, f64>(0)?, row.get::<_, String>(1)?))
-            })?;
-            
-            if let Some(r) = rows.next() {
-                Ok(Some(r?))
-            } else {
-                Ok(None)
-            }
-        }).await??;
-
-        if let Some((val, _ts)) = latest {
-            // Check for anomaly
-            let pool = self.pool.clone();
-            let pt = probe_type.to_string();
-            let t = target.to_string();
-            
-            // Just use the stats engine!
-            let anomaly_opt = tokio::task::spawn_blocking(move || {
-                check_for_anomaly(&pool, &pt, &t, val)
-            }).await??;
-
-            if let Some(anomaly) = anomaly_opt {
-                warn!(target=%anomaly.target, "Anomaly Detected: {:?}", anomaly);
-                self.incident_manager.record_incident(
-                    &format!("{} Anomaly: {}", anomaly.probe_type.to_uppercase(), anomaly.target),
-                    anomaly.severity,
-                    serde_json::json!({
-                        "target": anomaly.target,
-                        "probe_type": anomaly.probe_type,
-                        "value": anomaly.value,
-                        "baseline_mean": anomaly.baseline_mean,
-                        "z_score": anomaly.z_score,
-                        "val_unit": "ms"
-                    })
-                )?;
+    // Concurrent anomalies on one target are one incident, not one per probe type
+    let window = chrono::Duration::minutes(CORRELATION_WINDOW_MINUTES);
+    for group in group_concurrent(anomalies, window) {
+        incident_manager.record_anomalies(&group, CORRELATION_WINDOW_MINUTES)?;
+    }
+
+    // Slow speed tests read against latency under load: saturation or ISP
+    for episode in crate::detect::saturation::find_episodes(pool, SATURATION_LOOKBACK_HOURS)? {
+        warn!(source=%episode.source, direction=?episode.shortfall.direction, "{}", episode.verdict());
+        incident_manager.record_incident(&episode.verdict(), crate::detect::Severity::Warning, episode.evidence())?;
+    }
+
+    // Throughput runs that retransmitted heavily: loss on the path under load
+    for run in crate::detect::tcp_loss::find_lossy_runs(pool, SATURATION_LOOKBACK_HOURS)? {
+        warn!(mode=%run.mode, direction=%run.direction, retransmit_pct=run.retransmit_pct, "{}", run.verdict());
+        incident_manager.record_incident(&run.verdict(), crate::detect::Severity::Warning, run.evidence())?;
+    }
+
+    // Escalate incidents that keep going; each escalation is a fresh alert
+    for e in incident_manager.escalate(&EscalationThresholds::from_env())? {
+        warn!(
+            incident = %e.id,
+            from = ?e.from,
+            to = ?e.to,
+            minutes = e.duration.num_minutes(),
+            "Incident escalated: {}", e.verdict
+        );
+    }
+
+    // Close incidents whose metric has been back within baseline for the recovery period
+    let resolved = incident_manager.resolve_recovered(crate::detect::recovery_minutes())?;
+    if !resolved.is_empty() {
+        info!(count = resolved.len(), "Resolved recovered incidents");
+    }
+
+    Ok(())
+}
+
+fn analyze_target(pool: &Pool, probe_type: &str, target: &str) -> Result<Option<Anomaly>> {
+    // Fetch latest measurement
+    let latest: Option<(f64, String)> = {
+        let conn = pool.get()?;
+        let mut stmt = conn.prepare(
+            "SELECT value, created_at FROM measurements 
+             WHERE probe_type = ?1 AND target = ?2 AND dscp IS NULL
+             ORDER BY created_at DESC LIMIT 1"
+        )?;
+        stmt.query_row(rusqlite::params![probe_type, target], |row| {
+            Ok((row.get::<_, f64>(0)?, row.get::<_, String>(1)?))
+        })
+        .optional()?
+    };
+
+    let Some((val, ts)) = latest else {
+        return Ok(None);
+    };
+
+    // Just use the stats engine!
+    let anomaly = check_for_anomaly(pool, probe_type, target, val)?;
+
+    // Stamp with the sample time so concurrency is judged by when it was measured
+    Ok(anomaly.map(|mut a| {
+        if let Ok(sampled) = chrono::DateTime::parse_from_rfc3339(&ts) {
+            a.timestamp = sampled.with_timezone(&chrono::Utc);
+        }
+        a
+    }))
+}
+
+/// Group anomalies on the same target whose samples fall within `window` of
+/// the group's first, so a target degrading on several probe types at once
+/// yields one group.
+pub fn group_concurrent(mut anomalies: Vec<Anomaly>, window: chrono::Duration) -> Vec<Vec<Anomaly>> {
+    anomalies.sort_by(|a, b| a.target.cmp(&b.target).then(a.timestamp.cmp(&b.timestamp)));
+    let mut groups: Vec<Vec<Anomaly>> = Vec::new();
+    for a in anomalies {
+        match groups.last_mut() {
+            Some(g) if g[0].target == a.target && a.timestamp - g[0].timestamp <= window => g.push(a),
+            _ => groups.push(vec![a]),
+        }
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detect::Severity;
+
+    fn anomaly(probe_type: &str, target: &str, minutes_ago: i64) -> Anomaly {
+        Anomaly {
+            probe_type: probe_type.to_string(),
+            target: target.to_string(),
+            value: 100.0,
+            baseline_mean: 10.0,
+            baseline_std_dev: 2.0,
+            z_score: 45.0,
+            severity: Severity::Critical,
+            timestamp: chrono::Utc::now() - chrono::Duration::minutes(minutes_ago),
+        }
+    }
+
+    #[test]
+    fn test_group_concurrent() {
+        let groups = group_concurrent(
+            vec![
+                anomaly("icmp", "8.8.8.8", 1),
+                anomaly("dns", "1.1.1.1", 2),
+                anomaly("http", "8.8.8.8", 3),
+                anomaly("tcp", "8.8.8.8", 40),
+            ],
+            chrono::Duration::minutes(CORRELATION_WINDOW_MINUTES),
+        );
+        let shape: Vec<Vec<&str>> = groups
+            .iter()
+            .map(|g| g.iter().map(|a| a.probe_type.as_str()).collect())
+            .collect();
+        // The stale TCP sample is not concurrent with the other two.
+        assert_eq!(shape, vec![vec!["dns"], vec!["tcp"], vec!["http", "icmp"]]);
+    }
+
+    #[tokio::test]
+    async fn test_scan_records_latency_spike() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = crate::storage::open_pool(dir.path().join("t.db").to_str().unwrap()).unwrap();
+        {
+            let conn = pool.get().unwrap();
+            for i in 0..20 {
+                conn.execute(
+                    "INSERT INTO measurements (probe_type, target, value, unit, created_at)
+                     VALUES ('icmp', '8.8.8.8', ?1, 'ms', datetime('now', '-30 minutes'))",
+                    [10.0 + f64::from(i % 3)],
+                )
+                .unwrap();
             }
+            conn.execute(
+                "INSERT INTO measurements (probe_type, target, value, unit) VALUES ('icmp', '8.8.8.8', 500.0, 'ms')",
+                [],
+            )
+            .unwrap();
         }
-        Ok(())
+
+        AnomalyEngine::new(pool.clone()).run_scan().await.unwrap();
+
+        let incidents: i64 = pool
+            .get()
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM incidents", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(incidents, 1);
     }
 }
@@ -1,9 +1,70 @@
-use crate::detect::{Incident, Severity};
+use crate::analysis::stats::{calculate_baseline, Anomaly};
+use crate::detect::{EscalationThresholds, Incident, Severity};
+use crate::storage::health::STORAGE_HEALTH;
 use crate::storage::Pool;
 use anyhow::Result;
-use rusqlite::params;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use rusqlite::{params, OptionalExtension};
 use uuid::Uuid;
 
+/// Fewest post-incident samples that can show a metric has recovered.
+const MIN_RECOVERY_SAMPLES: usize = 3;
+
+/// Severity as stored in `incidents.severity` (the `Debug` name).
+pub(crate) fn parse_severity(s: &str) -> Severity {
+    match s {
+        "Critical" => Severity::Critical,
+        "Warning" => Severity::Warning,
+        _ => Severity::Info,
+    }
+}
+
+/// An open incident moved to a higher severity.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Escalation {
+    pub id: Uuid,
+    pub verdict: String,
+    pub from: Severity,
+    pub to: Severity,
+    /// How long the condition had persisted.
+    pub duration: chrono::Duration,
+}
+
+/// `ICMP Anomaly: 8.8.8.8` for one probe type, `Anomaly affecting
+/// HTTP+ICMP: 8.8.8.8` for several.
+fn anomaly_verdict(target: &str, probe_types: &[String]) -> String {
+    let names: Vec<String> = probe_types.iter().map(|p| p.to_uppercase()).collect();
+    match names.as_slice() {
+        [single] => format!("{} Anomaly: {}", single, target),
+        _ => format!("Anomaly affecting {}: {}", names.join("+"), target),
+    }
+}
+
+/// Per-metric entries of an anomaly incident's evidence; a single-metric
+/// incident from before correlation has its fields at the top level.
+fn evidence_metrics(evidence: &serde_json::Value) -> Vec<serde_json::Value> {
+    if let Some(metrics) = evidence.get("metrics").and_then(|m| m.as_array()) {
+        return metrics.clone();
+    }
+    match evidence.get("probe_type") {
+        Some(probe_type) => vec![serde_json::json!({
+            "probe_type": probe_type,
+            "value": evidence.get("value"),
+            "baseline_mean": evidence.get("baseline_mean"),
+            "z_score": evidence.get("z_score"),
+        })],
+        None => Vec::new(),
+    }
+}
+
+/// Parse an incident timestamp: `datetime('now')` format or RFC3339.
+pub(crate) fn parse_timestamp(s: &str) -> DateTime<Utc> {
+    NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S")
+        .map(|t| t.and_utc())
+        .or_else(|_| DateTime::parse_from_rfc3339(s).map(|t| t.with_timezone(&Utc)))
+        .unwrap_or_default()
+}
+
 pub struct IncidentManager {
     pool: Pool,
 }
@@ -24,41 +85,268 @@ impl IncidentManager {
              // Update existing incident
              // We update 'updated_at' to keep it alive
              let uuid = Uuid::parse_str(&existing_id).unwrap_or_default();
-             conn.execute("UPDATE incidents SET updated_at = datetime('now') WHERE id = ?1", params![existing_id])?;
+             STORAGE_HEALTH.absorb(conn.execute("UPDATE incidents SET updated_at = datetime('now') WHERE id = ?1", params![existing_id]))?;
              return Ok(uuid);
         }
 
-        let id = Uuid::new_v4();
-        let severity_str = format!("{:?}", severity); // Info, Warning, Critical
-        let evidence_json = serde_json::to_string(&evidence)?;
+        // Release the connection: save_incident takes its own.
+        drop(stmt);
+        drop(conn);
 
-        conn.execute(
-            "INSERT INTO incidents (id, severity, verdict, evidence_json, status, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, 'Open', datetime('now'), datetime('now'))",
-            params![id.to_string(), severity_str, verdict, evidence_json],
+        let id = Uuid::new_v4();
+        crate::storage::save_incident(
+            &self.pool,
+            &Incident {
+                id,
+                severity,
+                verdict: verdict.to_string(),
+                evidence,
+                created_at: Utc::now(),
+                resolved_at: None,
+            },
         )?;
 
         Ok(id)
     }
 
+    /// Record concurrent anomalies on one target as a single incident with
+    /// per-metric evidence. Folds them into the target's open anomaly
+    /// incident if it was raised within `window_minutes`, so a second probe
+    /// type degrading a little later widens that incident instead of opening
+    /// another.
+    pub fn record_anomalies(&self, anomalies: &[Anomaly], window_minutes: i64) -> Result<Uuid> {
+        let Some(first) = anomalies.first() else {
+            anyhow::bail!("no anomalies to record");
+        };
+        let target = first.target.as_str();
+        let conn = self.pool.get()?;
+
+        let existing: Option<(String, String, String)> = conn
+            .query_row(
+                "SELECT id, severity, evidence_json FROM incidents
+                 WHERE status = 'Open' AND verdict LIKE '%Anomaly%'
+                 AND json_extract(evidence_json, '$.target') = ?1
+                 AND datetime(updated_at) > datetime('now', ?2)
+                 ORDER BY updated_at DESC LIMIT 1",
+                params![target, format!("-{} minutes", window_minutes)],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()?;
+
+        let mut metrics: Vec<serde_json::Value> = Vec::new();
+        let mut severity = Severity::Info;
+        if let Some((_, existing_severity, evidence_str)) = &existing {
+            let evidence: serde_json::Value = serde_json::from_str(evidence_str).unwrap_or_default();
+            metrics = evidence_metrics(&evidence);
+            severity = parse_severity(existing_severity);
+        }
+        for a in anomalies {
+            metrics.retain(|m| m["probe_type"] != a.probe_type.as_str());
+            metrics.push(serde_json::json!({
+                "probe_type": a.probe_type,
+                "value": a.value,
+                "baseline_mean": a.baseline_mean,
+                "z_score": a.z_score,
+                "severity": a.severity,
+                "sampled_at": a.timestamp.to_rfc3339(),
+            }));
+            severity = severity.max(a.severity);
+        }
+        metrics.sort_by(|a, b| a["probe_type"].as_str().cmp(&b["probe_type"].as_str()));
+        let probe_types: Vec<String> = metrics
+            .iter()
+            .filter_map(|m| m["probe_type"].as_str().map(str::to_string))
+            .collect();
+        let verdict = anomaly_verdict(target, &probe_types);
+
+        let Some((id, _, evidence_str)) = existing else {
+            return self.record_incident(
+                &verdict,
+                severity,
+                serde_json::json!({
+                    "target": target,
+                    "probe_types": probe_types,
+                    "metrics": metrics,
+                    "val_unit": "ms",
+                }),
+            );
+        };
+
+        // Keep everything else (e.g. the escalation timeline) as is.
+        let mut evidence: serde_json::Value = serde_json::from_str(&evidence_str).unwrap_or_default();
+        if let Some(obj) = evidence.as_object_mut() {
+            for legacy in ["probe_type", "value", "baseline_mean", "z_score"] {
+                obj.remove(legacy);
+            }
+            obj.insert("probe_types".to_string(), serde_json::json!(probe_types));
+            obj.insert("metrics".to_string(), serde_json::json!(metrics));
+        }
+        STORAGE_HEALTH.absorb(conn.execute(
+            "UPDATE incidents SET verdict = ?2, severity = ?3, evidence_json = ?4, updated_at = datetime('now') WHERE id = ?1",
+            params![id, verdict, format!("{:?}", severity), serde_json::to_string(&evidence)?],
+        ))?;
+        Ok(Uuid::parse_str(&id).unwrap_or_default())
+    }
+
+    /// Close an incident, recording its impact window (onset to now) in the
+    /// evidence so the bundle can report how long it lasted.
     pub fn resolve_incident(&self, incident_id: Uuid) -> Result<()> {
         let conn = self.pool.get()?;
-        conn.execute("UPDATE incidents SET status = 'Resolved', updated_at = datetime('now') WHERE id = ?1", params![incident_id.to_string()])?;
+        let row: Option<(String, String, String)> = conn
+            .query_row(
+                "SELECT evidence_json, created_at, datetime('now') FROM incidents WHERE id = ?1 AND status = 'Open'",
+                params![incident_id.to_string()],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()?;
+        let Some((evidence_str, created_at, now)) = row else {
+            return Ok(());
+        };
+
+        let start = parse_timestamp(&created_at);
+        let end = parse_timestamp(&now);
+        let mut evidence: serde_json::Value = serde_json::from_str(&evidence_str).unwrap_or_default();
+        if let Some(obj) = evidence.as_object_mut() {
+            obj.insert(
+                "impact_window".to_string(),
+                serde_json::json!({
+                    "start": start.to_rfc3339(),
+                    "end": end.to_rfc3339(),
+                    "duration_secs": (end - start).num_seconds(),
+                }),
+            );
+        }
+
+        conn.execute(
+            "UPDATE incidents SET status = 'Resolved', resolved_at = ?2, updated_at = ?2, evidence_json = ?3 WHERE id = ?1",
+            params![incident_id.to_string(), now, serde_json::to_string(&evidence)?],
+        )?;
         Ok(())
     }
 
+    /// Raise the severity of open incidents whose condition has persisted
+    /// (onset to last re-raise) past `thresholds`. Severity only ever goes
+    /// up, so each level is reported once per incident; every step is
+    /// appended to the evidence `timeline`.
+    pub fn escalate(&self, thresholds: &EscalationThresholds) -> Result<Vec<Escalation>> {
+        let conn = self.pool.get()?;
+        let open: Vec<(String, String, String, String, String, String)> = {
+            let mut stmt = conn.prepare(
+                "SELECT id, severity, verdict, evidence_json, created_at, updated_at FROM incidents WHERE status = 'Open'",
+            )?;
+            let rows = stmt.query_map([], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?))
+            })?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+
+        let mut escalations = Vec::new();
+        for (id, severity, verdict, evidence_str, created_at, updated_at) in open {
+            let from = parse_severity(&severity);
+            let duration = parse_timestamp(&updated_at) - parse_timestamp(&created_at);
+            let to = thresholds.severity_for(duration);
+            if to <= from {
+                continue;
+            }
+
+            let mut evidence: serde_json::Value = serde_json::from_str(&evidence_str).unwrap_or_default();
+            if let Some(obj) = evidence.as_object_mut() {
+                let timeline = obj.entry("timeline").or_insert_with(|| serde_json::json!([]));
+                if let Some(events) = timeline.as_array_mut() {
+                    events.push(serde_json::json!({
+                        "at": Utc::now().to_rfc3339(),
+                        "event": "escalated",
+                        "from": from,
+                        "to": to,
+                        "after_secs": duration.num_seconds(),
+                    }));
+                }
+            }
+            STORAGE_HEALTH.absorb(conn.execute(
+                "UPDATE incidents SET severity = ?2, evidence_json = ?3 WHERE id = ?1",
+                params![id, format!("{:?}", to), serde_json::to_string(&evidence)?],
+            ))?;
+            escalations.push(Escalation {
+                id: Uuid::parse_str(&id).unwrap_or_default(),
+                verdict,
+                from,
+                to,
+                duration,
+            });
+        }
+        Ok(escalations)
+    }
+
+    /// Resolve open incidents whose condition has recovered: not re-raised
+    /// for `minutes`, and, for anomaly incidents, every sample of each
+    /// affected metric in the last `minutes` within baseline. Returns the resolved ids.
+    pub fn resolve_recovered(&self, minutes: u32) -> Result<Vec<Uuid>> {
+        let window = format!("-{} minutes", minutes);
+        let candidates: Vec<(String, String)> = {
+            let conn = self.pool.get()?;
+            let mut stmt = conn.prepare(
+                "SELECT id, evidence_json FROM incidents
+                 WHERE status = 'Open' AND datetime(updated_at) <= datetime('now', ?1)",
+            )?;
+            let rows = stmt.query_map([&window], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+
+        let mut resolved = Vec::new();
+        for (id, evidence_str) in candidates {
+            let evidence: serde_json::Value = serde_json::from_str(&evidence_str).unwrap_or_default();
+            let probe_types: Vec<String> = evidence_metrics(&evidence)
+                .iter()
+                .filter_map(|m| m["probe_type"].as_str().map(str::to_string))
+                .collect();
+            let recovered = match evidence.get("target").and_then(|t| t.as_str()) {
+                Some(target) if !probe_types.is_empty() => {
+                    let mut all = true;
+                    for probe_type in &probe_types {
+                        all &= self.metric_recovered(probe_type, target, &window)?;
+                    }
+                    all
+                }
+                // Correlated incidents are re-raised while their anomalies
+                // persist, so going quiet for the window is recovery.
+                _ => true,
+            };
+            if recovered {
+                let uuid = Uuid::parse_str(&id).unwrap_or_default();
+                self.resolve_incident(uuid)?;
+                resolved.push(uuid);
+            }
+        }
+        Ok(resolved)
+    }
+
+    /// Whether `probe_type`/`target` has had at least [`MIN_RECOVERY_SAMPLES`]
+    /// samples since `window` and all of them were within baseline.
+    fn metric_recovered(&self, probe_type: &str, target: &str, window: &str) -> Result<bool> {
+        let values: Vec<f64> = {
+            let conn = self.pool.get()?;
+            let mut stmt = conn.prepare(
+                "SELECT value FROM measurements
+                 WHERE probe_type = ?1 AND target = ?2 AND dscp IS NULL
+                 AND datetime(created_at) > datetime('now', ?3)",
+            )?;
+            let rows = stmt.query_map(params![probe_type, target, window], |row| row.get(0))?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+        if values.len() < MIN_RECOVERY_SAMPLES {
+            return Ok(false);
+        }
+        let baseline = calculate_baseline(&self.pool, probe_type, target)?;
+        Ok(values.iter().all(|v| baseline.is_within(*v)))
+    }
 
     pub fn list_recent(&self, limit: usize) -> Result<Vec<Incident>> {
         let conn = self.pool.get()?;
-        let mut stmt = conn.prepare("SELECT id, severity, verdict, evidence_json, created_at FROM incidents ORDER BY created_at DESC LIMIT ?1")?;
+        let mut stmt = conn.prepare("SELECT id, severity, verdict, evidence_json, created_at, resolved_at FROM incidents ORDER BY created_at DESC LIMIT ?1")?;
         
         let rows = stmt.query_map([limit], |row| {
             let id_str: String = row.get(0)?;
-            let sev_str: String = row.get(1)?;
-            let severity = match sev_str.as_str() {
-                "Critical" => Severity::Critical,
-                "Warning" => Severity::Warning,
-                _ => Severity::Info,
-            };
+            let severity = parse_severity(&row.get::<_, String>(1)?);
             let evidence_str: String = row.get(3)?;
 
             Ok(Incident {
@@ -66,7 +354,8 @@ impl IncidentManager {
                 severity,
                 verdict: row.get(2)?,
                 evidence: serde_json::from_str(&evidence_str).unwrap_or_default(),
-                created_at: chrono::DateTime::parse_from_rfc3339(&row.get::<_, String>(4)?).unwrap_or_default().with_timezone(&chrono::Utc),
+                created_at: parse_timestamp(&row.get::<_, String>(4)?),
+                resolved_at: row.get::<_, Option<String>>(5)?.as_deref().map(parse_timestamp),
             })
         })?;
 
@@ -77,3 +366,175 @@ impl IncidentManager {
         Ok(incidents)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert_measurement(pool: &Pool, value: f64, age: &str) {
+        pool.get()
+            .unwrap()
+            .execute(
+                "INSERT INTO measurements (probe_type, target, value, unit, created_at)
+                 VALUES ('icmp', '8.8.8.8', ?1, 'ms', datetime('now', ?2))",
+                params![value, age],
+            )
+            .unwrap();
+    }
+
+    fn open_incident(pool: &Pool, im: &IncidentManager, verdict: &str, evidence: serde_json::Value) -> Uuid {
+        let id = im.record_incident(verdict, Severity::Warning, evidence).unwrap();
+        // Last raised an hour ago.
+        pool.get()
+            .unwrap()
+            .execute(
+                "UPDATE incidents SET created_at = datetime('now', '-2 hours'), updated_at = datetime('now', '-1 hours') WHERE id = ?1",
+                [id.to_string()],
+            )
+            .unwrap();
+        id
+    }
+
+    #[test]
+    fn test_resolve_recovered() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = crate::storage::open_pool(dir.path().join("t.db").to_str().unwrap()).unwrap();
+        let im = IncidentManager::new(pool.clone());
+
+        // Baseline of ~10 ms, then the last 15 minutes back to normal.
+        for i in 0..20 {
+            insert_measurement(&pool, 10.0 + (i % 3) as f64, &format!("-{} hours", 2 + i));
+        }
+        let latency = open_incident(
+            &pool,
+            &im,
+            "ICMP Anomaly: 8.8.8.8",
+            serde_json::json!({ "probe_type": "icmp", "target": "8.8.8.8" }),
+        );
+        let other = open_incident(
+            &pool,
+            &im,
+            "DNS Anomaly: 1.1.1.1",
+            serde_json::json!({ "probe_type": "dns", "target": "1.1.1.1" }),
+        );
+
+        // Two good samples are not yet a sustained recovery.
+        insert_measurement(&pool, 11.0, "-10 minutes");
+        insert_measurement(&pool, 10.0, "-5 minutes");
+        assert!(im.resolve_recovered(15).unwrap().is_empty());
+
+        insert_measurement(&pool, 12.0, "-1 minutes");
+        assert_eq!(im.resolve_recovered(15).unwrap(), vec![latency]);
+
+        let incidents = im.list_recent(10).unwrap();
+        let resolved = incidents.iter().find(|i| i.id == latency).unwrap();
+        let duration = resolved.impact_duration().unwrap();
+        assert!((duration.num_minutes() - 120).abs() <= 1);
+        assert_eq!(resolved.evidence["impact_window"]["duration_secs"], duration.num_seconds());
+        // No samples for the DNS target, so it stays open.
+        assert!(incidents.iter().find(|i| i.id == other).unwrap().resolved_at.is_none());
+    }
+
+    fn anomaly(probe_type: &str, severity: Severity) -> Anomaly {
+        Anomaly {
+            probe_type: probe_type.to_string(),
+            target: "8.8.8.8".to_string(),
+            value: 120.0,
+            baseline_mean: 12.0,
+            baseline_std_dev: 3.0,
+            z_score: 36.0,
+            severity,
+            timestamp: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_record_anomalies_correlates_probe_types() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = crate::storage::open_pool(dir.path().join("t.db").to_str().unwrap()).unwrap();
+        let im = IncidentManager::new(pool.clone());
+
+        let id = im.record_anomalies(&[anomaly("icmp", Severity::Info)], 10).unwrap();
+        assert_eq!(im.list_recent(10).unwrap()[0].verdict, "ICMP Anomaly: 8.8.8.8");
+
+        // HTTP degrades on the same target shortly after: same incident.
+        let merged = im
+            .record_anomalies(&[anomaly("http", Severity::Warning), anomaly("icmp", Severity::Info)], 10)
+            .unwrap();
+        assert_eq!(merged, id);
+
+        let incidents = im.list_recent(10).unwrap();
+        assert_eq!(incidents.len(), 1);
+        let incident = &incidents[0];
+        assert_eq!(incident.verdict, "Anomaly affecting HTTP+ICMP: 8.8.8.8");
+        assert_eq!(incident.severity, Severity::Warning);
+        assert_eq!(incident.evidence["probe_types"], serde_json::json!(["http", "icmp"]));
+        assert_eq!(incident.evidence["metrics"].as_array().unwrap().len(), 2);
+
+        // Another target stays separate.
+        let mut other = anomaly("icmp", Severity::Info);
+        other.target = "1.1.1.1".to_string();
+        assert_ne!(im.record_anomalies(&[other], 10).unwrap(), id);
+    }
+
+    #[test]
+    fn test_escalate_by_duration() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = crate::storage::open_pool(dir.path().join("t.db").to_str().unwrap()).unwrap();
+        let im = IncidentManager::new(pool.clone());
+        let thresholds = EscalationThresholds::default();
+
+        let id = im
+            .record_incident("ICMP Anomaly: 8.8.8.8", Severity::Info, serde_json::json!({ "target": "8.8.8.8" }))
+            .unwrap();
+        assert!(im.escalate(&thresholds).unwrap().is_empty());
+
+        let set_onset = |age: &str| {
+            pool.get()
+                .unwrap()
+                .execute(
+                    "UPDATE incidents SET created_at = datetime('now', ?2), updated_at = datetime('now') WHERE id = ?1",
+                    params![id.to_string(), age],
+                )
+                .unwrap();
+        };
+
+        set_onset("-45 minutes");
+        let escalated = im.escalate(&thresholds).unwrap();
+        assert_eq!(escalated.len(), 1);
+        assert_eq!((escalated[0].from, escalated[0].to), (Severity::Info, Severity::Warning));
+        // Already at Warning: nothing new to report.
+        assert!(im.escalate(&thresholds).unwrap().is_empty());
+
+        set_onset("-3 hours");
+        let escalated = im.escalate(&thresholds).unwrap();
+        assert_eq!((escalated[0].from, escalated[0].to), (Severity::Warning, Severity::Critical));
+
+        let incident = im.list_recent(1).unwrap().remove(0);
+        assert_eq!(incident.severity, Severity::Critical);
+        let timeline = incident.evidence["timeline"].as_array().unwrap();
+        assert_eq!(timeline.len(), 2);
+        assert_eq!(timeline[1]["to"], "Critical");
+    }
+
+    #[test]
+    fn test_anomalous_sample_keeps_incident_open() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = crate::storage::open_pool(dir.path().join("t.db").to_str().unwrap()).unwrap();
+        let im = IncidentManager::new(pool.clone());
+
+        for i in 0..20 {
+            insert_measurement(&pool, 10.0 + (i % 3) as f64, &format!("-{} hours", 2 + i));
+        }
+        open_incident(
+            &pool,
+            &im,
+            "ICMP Anomaly: 8.8.8.8",
+            serde_json::json!({ "probe_type": "icmp", "target": "8.8.8.8" }),
+        );
+        for (value, age) in [(10.0, "-12 minutes"), (250.0, "-6 minutes"), (11.0, "-1 minutes")] {
+            insert_measurement(&pool, value, age);
+        }
+        assert!(im.resolve_recovered(15).unwrap().is_empty());
+    }
+}
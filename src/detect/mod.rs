@@ -3,6 +3,8 @@
 pub mod anomaly;
 pub mod incident;
 pub mod engine;
+pub mod saturation;
+pub mod tcp_loss;
 
 use thiserror::Error;
 
@@ -12,14 +14,72 @@ pub enum DetectError {
     InsufficientBaseline { needed: usize, have: usize },
 }
 
-/// Severity levels for detected incidents.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+/// Severity levels for detected incidents, least severe first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize)]
 pub enum Severity {
     Info,
     Warning,
     Critical,
 }
 
+/// How long an incident's condition must persist before it escalates.
+#[derive(Debug, Clone, Copy)]
+pub struct EscalationThresholds {
+    pub warning: chrono::Duration,
+    pub critical: chrono::Duration,
+}
+
+impl Default for EscalationThresholds {
+    fn default() -> Self {
+        Self {
+            warning: chrono::Duration::minutes(30),
+            critical: chrono::Duration::minutes(120),
+        }
+    }
+}
+
+impl EscalationThresholds {
+    /// Defaults, overridden by `PP_INCIDENT_ESCALATE_WARNING_MINUTES` and
+    /// `PP_INCIDENT_ESCALATE_CRITICAL_MINUTES`.
+    pub fn from_env() -> Self {
+        let minutes = |key: &str| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.parse::<i64>().ok())
+                .filter(|m| *m > 0)
+                .map(chrono::Duration::minutes)
+        };
+        let defaults = Self::default();
+        Self {
+            warning: minutes("PP_INCIDENT_ESCALATE_WARNING_MINUTES").unwrap_or(defaults.warning),
+            critical: minutes("PP_INCIDENT_ESCALATE_CRITICAL_MINUTES").unwrap_or(defaults.critical),
+        }
+    }
+
+    /// Least severity an incident that has persisted for `duration` warrants.
+    pub fn severity_for(&self, duration: chrono::Duration) -> Severity {
+        if duration >= self.critical {
+            Severity::Critical
+        } else if duration >= self.warning {
+            Severity::Warning
+        } else {
+            Severity::Info
+        }
+    }
+}
+
+/// Minutes a metric must stay within baseline before its incident resolves.
+pub const DEFAULT_RECOVERY_MINUTES: u32 = 15;
+
+/// Recovery period: `PP_INCIDENT_RECOVERY_MINUTES`, or the default.
+pub fn recovery_minutes() -> u32 {
+    std::env::var("PP_INCIDENT_RECOVERY_MINUTES")
+        .ok()
+        .and_then(|v| v.parse::<u32>().ok())
+        .filter(|m| *m > 0)
+        .unwrap_or(DEFAULT_RECOVERY_MINUTES)
+}
+
 /// A detected incident with verdict and evidence.
 #[derive(Debug, serde::Serialize)]
 pub struct Incident {
@@ -28,4 +88,13 @@ pub struct Incident {
     pub verdict: String,
     pub evidence: serde_json::Value,
     pub created_at: chrono::DateTime<chrono::Utc>,
+    /// Set once the condition has recovered; `None` while ongoing.
+    pub resolved_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl Incident {
+    /// Measured impact window (onset to resolution), once resolved.
+    pub fn impact_duration(&self) -> Option<chrono::Duration> {
+        self.resolved_at.map(|r| r - self.created_at)
+    }
 }
@@ -0,0 +1,426 @@
+//! Link saturation: speed tests read together with latency under load.
+//!
+//! A slow speed test alone can't tell "someone else is using all your
+//! bandwidth" from "your ISP is slow". The idle latency measured just
+//! before the test can: when other traffic fills the link, the router's
+//! queue is already full, so idle RTT sits well above its usual level while
+//! the test only gets a share of its usual rate. When the ISP is slow the
+//! test falls just as short, but idle RTT is normal. (RTT under the test's
+//! own load says nothing here: the test inflates it either way.)
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rusqlite::{params, OptionalExtension};
+use serde::Serialize;
+
+use crate::analysis::qos::BUFFERBLOAT_PROBE_TYPE;
+use crate::detect::engine::CORRELATION_WINDOW_MINUTES;
+use crate::storage::Pool;
+use crate::throughput::provider::{SpeedTestResult, SPEED_PROBE_TYPE};
+
+/// A direction counts as slow below this share of its usual rate.
+pub const SHORTFALL_RATIO: f64 = 0.7;
+
+/// Idle RTT this far above its usual level means the local queue was
+/// already filling before the test started.
+pub const BUSY_IDLE_RTT_MS: f64 = 30.0;
+
+/// Earlier tests (or idle RTT readings) needed before "usual" means anything.
+pub const MIN_BASELINE_TESTS: usize = 3;
+
+/// How far back the usual rate is taken from.
+const BASELINE_DAYS: i64 = 7;
+
+/// Link direction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Download,
+    Upload,
+}
+
+impl Direction {
+    fn as_str(self) -> &'static str {
+        match self {
+            Direction::Download => "download",
+            Direction::Upload => "upload",
+        }
+    }
+}
+
+/// What a slow speed test most likely means.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Diagnosis {
+    /// Slow and idle latency already raised: other traffic is filling the link.
+    Saturated,
+    /// Slow with normal idle latency: the bottleneck is upstream, at the ISP.
+    IspSlow,
+}
+
+/// The slowest direction of a speed test against its usual rate.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Shortfall {
+    pub direction: Direction,
+    pub measured_mbps: f64,
+    pub usual_mbps: f64,
+}
+
+/// A speed test paired with a concurrent latency-under-load measurement.
+#[derive(Debug, Clone, Serialize)]
+pub struct Episode {
+    /// `probe_results` row of the latency measurement.
+    pub latency_id: i64,
+    pub at: DateTime<Utc>,
+    /// Speed test source (provider ID or engine).
+    pub source: String,
+    /// iperf3/native test mode (`lan`/`wan`); `None` for providers.
+    pub mode: Option<String>,
+    pub diagnosis: Diagnosis,
+    pub shortfall: Shortfall,
+    /// Idle RTT measured just before the test.
+    pub idle_rtt_ms: f64,
+    /// Median idle RTT of earlier runs against the same target.
+    pub usual_idle_rtt_ms: f64,
+    /// Latency the test's own load added, for context only.
+    pub bufferbloat_ms: f64,
+}
+
+impl Episode {
+    pub fn verdict(&self) -> String {
+        let direction = self.shortfall.direction.as_str();
+        match self.diagnosis {
+            Diagnosis::Saturated => format!("Link Saturated: {}", direction),
+            Diagnosis::IspSlow => format!("ISP Slow: {}", direction),
+        }
+    }
+
+    pub fn evidence(&self) -> serde_json::Value {
+        let explanation = match self.diagnosis {
+            Diagnosis::Saturated => format!(
+                "{} ran at {:.0} of a usual {:.0} Mbps and idle latency before the test was \
+                 {:.0} ms against a usual {:.0} ms; another device is likely using the {} bandwidth",
+                self.shortfall.direction.as_str(),
+                self.shortfall.measured_mbps,
+                self.shortfall.usual_mbps,
+                self.idle_rtt_ms,
+                self.usual_idle_rtt_ms,
+                self.shortfall.direction.as_str(),
+            ),
+            Diagnosis::IspSlow => format!(
+                "{} ran at {:.0} of a usual {:.0} Mbps with idle latency at its usual level \
+                 ({:.0} ms against {:.0} ms); the slowdown is upstream, not local traffic",
+                self.shortfall.direction.as_str(),
+                self.shortfall.measured_mbps,
+                self.shortfall.usual_mbps,
+                self.idle_rtt_ms,
+                self.usual_idle_rtt_ms,
+            ),
+        };
+        serde_json::json!({
+            "latency_id": self.latency_id,
+            "source": self.source,
+            "mode": self.mode,
+            "at": self.at.to_rfc3339(),
+            "diagnosis": self.diagnosis,
+            "direction": self.shortfall.direction,
+            "measured_mbps": self.shortfall.measured_mbps,
+            "usual_mbps": self.shortfall.usual_mbps,
+            "idle_rtt_ms": self.idle_rtt_ms,
+            "usual_idle_rtt_ms": self.usual_idle_rtt_ms,
+            "bufferbloat_ms": self.bufferbloat_ms,
+            "explanation": explanation,
+        })
+    }
+}
+
+/// The direction furthest below its usual rate, if it is below
+/// [`SHORTFALL_RATIO`]. Directions without a usual rate are ignored.
+pub fn shortfall(speed: &SpeedTestResult, usual_down: Option<f64>, usual_up: Option<f64>) -> Option<Shortfall> {
+    [
+        (Direction::Download, speed.download_mbps, usual_down),
+        (Direction::Upload, speed.upload_mbps, usual_up),
+    ]
+    .into_iter()
+    .filter_map(|(direction, measured, usual)| match (measured, usual) {
+        (Some(measured_mbps), Some(usual_mbps)) if usual_mbps > 0.0 => Some(Shortfall {
+            direction,
+            measured_mbps,
+            usual_mbps,
+        }),
+        _ => None,
+    })
+    .filter(|s| s.measured_mbps < s.usual_mbps * SHORTFALL_RATIO)
+    .min_by(|a, b| {
+        (a.measured_mbps / a.usual_mbps).total_cmp(&(b.measured_mbps / b.usual_mbps))
+    })
+}
+
+/// Saturated if idle latency was already well above usual, otherwise the ISP.
+pub fn diagnose(idle_rtt_ms: f64, usual_idle_rtt_ms: f64) -> Diagnosis {
+    if idle_rtt_ms - usual_idle_rtt_ms >= BUSY_IDLE_RTT_MS {
+        Diagnosis::Saturated
+    } else {
+        Diagnosis::IspSlow
+    }
+}
+
+/// Latency measurements from the last `hours`, each paired with the
+/// nearest speed test within [`CORRELATION_WINDOW_MINUTES`], that show a
+/// shortfall. Measurements already cited by an incident, and those without
+/// enough idle RTT history to judge, are skipped.
+pub fn find_episodes(pool: &Pool, hours: u32) -> Result<Vec<Episode>> {
+    let conn = pool.get()?;
+    let mut stmt = conn.prepare(
+        "SELECT p.id, p.target, p.result_json, p.created_at FROM probe_results p
+         WHERE p.probe_type = ?1 AND p.created_at > datetime('now', ?2)
+         AND NOT EXISTS (
+             SELECT 1 FROM incidents i
+             WHERE json_extract(i.evidence_json, '$.latency_id') = p.id
+         )
+         ORDER BY p.created_at ASC",
+    )?;
+    let latency: Vec<(i64, String, String, String)> = stmt
+        .query_map(params![BUFFERBLOAT_PROBE_TYPE, format!("-{} hours", hours)], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+
+    let mut episodes = Vec::new();
+    for (latency_id, target, json, created_at) in latency {
+        let qos: serde_json::Value = serde_json::from_str(&json)?;
+        let (Some(idle_rtt_ms), Some(bufferbloat_ms)) =
+            (qos["baseline_rtt_ms"].as_f64(), qos["bufferbloat_ms"].as_f64())
+        else {
+            continue;
+        };
+
+        // UDP runs send at a fixed rate, so they can't show a shortfall.
+        let paired: Option<(i64, String, String)> = conn
+            .query_row(
+                "SELECT id, target, result_json FROM probe_results
+                 WHERE probe_type = ?1
+                 AND json_extract(result_json, '$.raw_json.directions[0].protocol') IS NOT 'udp'
+                 AND abs(julianday(created_at) - julianday(?2)) * 1440 <= ?3
+                 ORDER BY abs(julianday(created_at) - julianday(?2)) ASC, id DESC LIMIT 1",
+                params![SPEED_PROBE_TYPE, created_at, CORRELATION_WINDOW_MINUTES],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()?;
+        let Some((speed_id, source, speed_json)) = paired else {
+            continue;
+        };
+        let speed: SpeedTestResult = serde_json::from_str(&speed_json)?;
+        let mode = test_mode(&speed);
+
+        let baseline = Baseline { source: &source, mode: mode.as_deref(), exclude_id: speed_id };
+        let usual_down = usual_mbps(&conn, &baseline, "$.download_mbps")?;
+        let usual_up = usual_mbps(&conn, &baseline, "$.upload_mbps")?;
+        let Some(shortfall) = shortfall(&speed, usual_down, usual_up) else {
+            continue;
+        };
+        let Some(usual_idle_rtt_ms) = usual_idle_rtt_ms(&conn, &target, latency_id)? else {
+            continue;
+        };
+
+        episodes.push(Episode {
+            latency_id,
+            at: crate::detect::incident::parse_timestamp(&created_at),
+            source,
+            mode,
+            diagnosis: diagnose(idle_rtt_ms, usual_idle_rtt_ms),
+            shortfall,
+            idle_rtt_ms,
+            usual_idle_rtt_ms,
+            bufferbloat_ms,
+        });
+    }
+    Ok(episodes)
+}
+
+/// iperf3/native test mode (`lan`/`wan`) of a normalized speed test.
+fn test_mode(speed: &SpeedTestResult) -> Option<String> {
+    speed.raw_json.as_ref()?["directions"][0]["mode"]
+        .as_str()
+        .map(str::to_string)
+}
+
+/// Which speed tests a result is compared with: same source and, for
+/// iperf3/native, same mode, so LAN and WAN runs never share a baseline.
+struct Baseline<'a> {
+    source: &'a str,
+    mode: Option<&'a str>,
+    exclude_id: i64,
+}
+
+/// Median of `field` over the baseline's other speed tests from the last
+/// [`BASELINE_DAYS`], leaving out rate-capped UDP runs; `None` with fewer
+/// than [`MIN_BASELINE_TESTS`].
+fn usual_mbps(conn: &rusqlite::Connection, baseline: &Baseline, field: &str) -> Result<Option<f64>> {
+    let mut stmt = conn.prepare(
+        "SELECT json_extract(result_json, ?1) FROM probe_results
+         WHERE probe_type = ?2 AND target = ?3 AND id != ?4
+         AND json_extract(result_json, '$.raw_json.directions[0].mode') IS ?6
+         AND json_extract(result_json, '$.raw_json.directions[0].protocol') IS NOT 'udp'
+         AND created_at > datetime('now', ?5) AND json_extract(result_json, ?1) IS NOT NULL",
+    )?;
+    let values: Vec<f64> = stmt
+        .query_map(
+            params![
+                field,
+                SPEED_PROBE_TYPE,
+                baseline.source,
+                baseline.exclude_id,
+                format!("-{} days", BASELINE_DAYS),
+                baseline.mode,
+            ],
+            |row| row.get(0),
+        )?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(median(values))
+}
+
+/// Median idle RTT of the other latency-under-load runs against `target`
+/// from the last [`BASELINE_DAYS`]; `None` with fewer than
+/// [`MIN_BASELINE_TESTS`].
+fn usual_idle_rtt_ms(conn: &rusqlite::Connection, target: &str, exclude_id: i64) -> Result<Option<f64>> {
+    let mut stmt = conn.prepare(
+        "SELECT json_extract(result_json, '$.baseline_rtt_ms') FROM probe_results
+         WHERE probe_type = ?1 AND target = ?2 AND id != ?3
+         AND created_at > datetime('now', ?4)
+         AND json_extract(result_json, '$.baseline_rtt_ms') IS NOT NULL",
+    )?;
+    let values: Vec<f64> = stmt
+        .query_map(
+            params![BUFFERBLOAT_PROBE_TYPE, target, exclude_id, format!("-{} days", BASELINE_DAYS)],
+            |row| row.get(0),
+        )?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(median(values))
+}
+
+fn median(mut values: Vec<f64>) -> Option<f64> {
+    if values.len() < MIN_BASELINE_TESTS {
+        return None;
+    }
+    values.sort_by(|a, b| a.total_cmp(b));
+    let mid = values.len() / 2;
+    Some(if values.len() % 2 == 0 {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn speed(down: f64, up: f64) -> SpeedTestResult {
+        iperf3(down, up, None)
+    }
+
+    /// An iperf3 run in `mode`, as `SpeedTestResult::from_throughput` stores it.
+    fn iperf3(down: f64, up: f64, mode: Option<&str>) -> SpeedTestResult {
+        SpeedTestResult {
+            provider_id: "iperf3".into(),
+            download_mbps: Some(down),
+            upload_mbps: Some(up),
+            latency_ms: None,
+            jitter_ms: None,
+            packet_loss_pct: None,
+            bufferbloat_ms: None,
+            server: None,
+            raw_json: mode.map(|m| serde_json::json!({ "directions": [{ "mode": m }] })),
+            timestamp: Utc::now(),
+            truncated: false,
+        }
+    }
+
+    /// A rate-capped iperf3 UDP run in `mode`.
+    fn udp(mbps: f64, mode: &str) -> SpeedTestResult {
+        SpeedTestResult {
+            raw_json: Some(serde_json::json!({ "directions": [{ "mode": mode, "protocol": "udp" }] })),
+            ..iperf3(mbps, mbps, None)
+        }
+    }
+
+    fn insert(pool: &Pool, probe_type: &str, target: &str, json: String, ago: &str) {
+        pool.get()
+            .unwrap()
+            .execute(
+                "INSERT INTO probe_results (probe_type, target, result_json, created_at)
+                 VALUES (?1, ?2, ?3, datetime('now', ?4))",
+                params![probe_type, target, json, ago],
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_shortfall_picks_worst_direction() {
+        let s = shortfall(&speed(300.0, 15.0), Some(900.0), Some(20.0)).unwrap();
+        assert_eq!(s.direction, Direction::Download);
+        assert_eq!(s.usual_mbps, 900.0);
+
+        // Within 70% of usual, or no usual rate to compare with: not slow.
+        assert!(shortfall(&speed(700.0, 18.0), Some(900.0), Some(20.0)).is_none());
+        assert!(shortfall(&speed(300.0, 15.0), None, None).is_none());
+
+        // Idle RTT 80 ms over its usual 10 ms: the link was busy before the test.
+        assert_eq!(diagnose(90.0, 10.0), Diagnosis::Saturated);
+        assert_eq!(diagnose(15.0, 10.0), Diagnosis::IspSlow);
+    }
+
+    #[test]
+    fn test_find_episodes_pairs_latency_with_speed() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = crate::storage::open_pool(dir.path().join("t.db").to_str().unwrap()).unwrap();
+
+        let qos = |idle: f64| serde_json::json!({ "baseline_rtt_ms": idle, "bufferbloat_ms": 40.0 }).to_string();
+        for day in 1..=3 {
+            let ago = format!("-{} days", day);
+            let json = serde_json::to_string(&iperf3(900.0, 40.0, Some("wan"))).unwrap();
+            insert(&pool, SPEED_PROBE_TYPE, "iperf3", json, &ago);
+            insert(&pool, BUFFERBLOAT_PROBE_TYPE, "8.8.8.8", qos(12.0), &ago);
+        }
+        // Multi-gig LAN runs of the same engine stay out of the WAN baseline.
+        for _ in 0..4 {
+            let json = serde_json::to_string(&iperf3(9000.0, 9000.0, Some("lan"))).unwrap();
+            insert(&pool, SPEED_PROBE_TYPE, "iperf3", json, "-2 hours");
+        }
+        // So do UDP runs, which send at a fixed 100 Mbps.
+        for _ in 0..4 {
+            let json = serde_json::to_string(&udp(100.0, "wan")).unwrap();
+            insert(&pool, SPEED_PROBE_TYPE, "iperf3", json, "-3 hours");
+        }
+
+        // No bufferbloat run without enough idle RTT history is judged.
+        let json = serde_json::to_string(&iperf3(880.0, 8.0, Some("wan"))).unwrap();
+        insert(&pool, SPEED_PROBE_TYPE, "iperf3", json, "-30 minutes");
+        insert(&pool, BUFFERBLOAT_PROBE_TYPE, "1.1.1.1", qos(90.0), "-30 minutes");
+        assert!(find_episodes(&pool, 1).unwrap().is_empty());
+
+        // Upload crawled and the link was already queueing before the test.
+        let json = serde_json::to_string(&iperf3(880.0, 8.0, Some("wan"))).unwrap();
+        insert(&pool, SPEED_PROBE_TYPE, "iperf3", json, "-5 minutes");
+        // A UDP run just as close is never the one paired.
+        let json = serde_json::to_string(&udp(100.0, "wan")).unwrap();
+        insert(&pool, SPEED_PROBE_TYPE, "iperf3", json, "-5 minutes");
+        insert(&pool, BUFFERBLOAT_PROBE_TYPE, "8.8.8.8", qos(90.0), "-5 minutes");
+
+        let episodes = find_episodes(&pool, 1).unwrap();
+        assert_eq!(episodes.len(), 1);
+        let e = &episodes[0];
+        assert_eq!(e.diagnosis, Diagnosis::Saturated);
+        assert_eq!(e.mode.as_deref(), Some("wan"));
+        assert_eq!(e.shortfall.direction, Direction::Upload);
+        assert_eq!(e.shortfall.usual_mbps, 40.0);
+        assert_eq!(e.usual_idle_rtt_ms, 12.0);
+        assert_eq!(e.verdict(), "Link Saturated: upload");
+
+        // Once an incident cites it, the measurement is not reported again.
+        crate::detect::incident::IncidentManager::new(pool.clone())
+            .record_incident(&e.verdict(), crate::detect::Severity::Warning, e.evidence())
+            .unwrap();
+        assert!(find_episodes(&pool, 1).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,152 @@
+//! Loss under load: throughput runs whose TCP retransmits point at a lossy
+//! or congested path.
+//!
+//! Average throughput can look healthy while the path drops packets; TCP
+//! hides the loss by retransmitting. A run that retransmitted more than
+//! [`LOSSY_RETRANSMIT_PCT`] of its segments is reported even when its rate
+//! is fine, since the same loss hurts latency-sensitive traffic.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rusqlite::params;
+use serde::Serialize;
+
+use crate::storage::Pool;
+use crate::throughput::report::{is_lossy_under_load, retransmit_pct, LOSSY_RETRANSMIT_PCT};
+use crate::throughput::ThroughputResult;
+
+/// A stored throughput direction that retransmitted too much.
+#[derive(Debug, Clone, Serialize)]
+pub struct LossyRun {
+    /// `throughput_results` row of the run.
+    pub throughput_id: i64,
+    pub at: DateTime<Utc>,
+    pub mode: String,
+    pub direction: String,
+    pub throughput_mbps: f64,
+    pub retransmits: u64,
+    pub retransmit_pct: f64,
+}
+
+impl LossyRun {
+    pub fn verdict(&self) -> String {
+        format!("Loss Under Load: {} {}", self.mode.to_uppercase(), self.direction)
+    }
+
+    pub fn evidence(&self) -> serde_json::Value {
+        serde_json::json!({
+            "throughput_id": self.throughput_id,
+            "at": self.at.to_rfc3339(),
+            "mode": self.mode,
+            "direction": self.direction,
+            "throughput_mbps": self.throughput_mbps,
+            "retransmits": self.retransmits,
+            "retransmit_pct": self.retransmit_pct,
+            "explanation": format!(
+                "{} retransmitted {:.2}% of segments (threshold {:.1}%) at {:.0} Mbps; \
+                 the path is dropping packets under load",
+                self.direction, self.retransmit_pct, LOSSY_RETRANSMIT_PCT, self.throughput_mbps,
+            ),
+        })
+    }
+}
+
+/// TCP throughput directions from the last `hours` that were lossy under
+/// load. Runs already cited by an incident are skipped.
+pub fn find_lossy_runs(pool: &Pool, hours: u32) -> Result<Vec<LossyRun>> {
+    let conn = pool.get()?;
+    let mut stmt = conn.prepare(
+        "SELECT t.id, t.result_json, t.created_at FROM throughput_results t
+         WHERE t.retransmits IS NOT NULL AND t.created_at > datetime('now', ?1)
+         AND NOT EXISTS (
+             SELECT 1 FROM incidents i
+             WHERE json_extract(i.evidence_json, '$.throughput_id') = t.id
+         )
+         ORDER BY t.created_at ASC",
+    )?;
+    let rows: Vec<(i64, String, String)> = stmt
+        .query_map(params![format!("-{} hours", hours)], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+
+    let mut runs = Vec::new();
+    for (throughput_id, json, created_at) in rows {
+        let Ok(result) = serde_json::from_str::<ThroughputResult>(&json) else {
+            continue;
+        };
+        if !is_lossy_under_load(&result) {
+            continue;
+        }
+        let (Some(retransmits), Some(pct)) =
+            (result.tcp.as_ref().and_then(|t| t.retransmits), retransmit_pct(&result))
+        else {
+            continue;
+        };
+        runs.push(LossyRun {
+            throughput_id,
+            at: crate::detect::incident::parse_timestamp(&created_at),
+            mode: result.mode,
+            direction: result.direction,
+            throughput_mbps: result.throughput_mbps,
+            retransmits,
+            retransmit_pct: pct,
+        });
+    }
+    Ok(runs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::throughput::iperf::Protocol;
+    use crate::throughput::TcpStats;
+
+    fn run(direction: &str, retransmits: u64) -> ThroughputResult {
+        ThroughputResult {
+            mode: "wan".to_string(),
+            direction: direction.to_string(),
+            protocol: Protocol::Tcp,
+            throughput_mbps: 941.5,
+            omit_secs: None,
+            raw_throughput_mbps: None,
+            jitter_ms: None,
+            loss_percent: None,
+            streams: 4,
+            streams_mbps: Vec::new(),
+            stream_balance: None,
+            duration_secs: 10.0,
+            link_speed_mbps: Some(1000),
+            bytes_transferred: 1_176_875_000,
+            efficiency_pct: None,
+            grade: None,
+            tcp: Some(TcpStats {
+                retransmits: Some(retransmits),
+                rtt_ms: None,
+                cwnd_bytes: None,
+            }),
+            buffers: None,
+            engine: "iperf3".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_find_lossy_runs_reports_each_run_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = crate::storage::open_pool(dir.path().join("t.db").to_str().unwrap()).unwrap();
+        crate::storage::save_throughput(&pool, &run("upload", 12)).unwrap();
+        crate::storage::save_throughput(&pool, &run("download", 20_000)).unwrap();
+
+        let runs = find_lossy_runs(&pool, 1).unwrap();
+        assert_eq!(runs.len(), 1);
+        let r = &runs[0];
+        assert_eq!(r.direction, "download");
+        assert!(r.retransmit_pct >= LOSSY_RETRANSMIT_PCT);
+        assert_eq!(r.verdict(), "Loss Under Load: WAN download");
+
+        crate::detect::incident::IncidentManager::new(pool.clone())
+            .record_incident(&r.verdict(), crate::detect::Severity::Warning, r.evidence())
+            .unwrap();
+        assert!(find_lossy_runs(&pool, 1).unwrap().is_empty());
+    }
+}
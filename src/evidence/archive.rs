@@ -0,0 +1,269 @@
+//! Archive formats for evidence bundles.
+//!
+//! ZIP stays the default because every OS opens it. For shipping bundles
+//! over a slow uplink, `tar.zst` (and `tar.gz` where zstd isn't available)
+//! compress the measurement and incident JSON far better.
+//!
+//! Large entries are written through [`BundleWriter::add_streamed`] so they
+//! never sit in memory whole. ZIP takes them straight into the deflate
+//! stream; tar needs each entry's size up front, so they are spooled to an
+//! anonymous temp file first.
+
+use std::fs::File;
+use std::io::{BufWriter, Read, Seek, Write};
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+
+/// zstd level: well past gzip's ratio while staying quick on a Pi.
+const ZSTD_LEVEL: i32 = 9;
+
+/// Container and compression of a bundle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    #[default]
+    Zip,
+    Zstd,
+    Gzip,
+}
+
+impl Compression {
+    /// File extension for bundles in this format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Compression::Zip => "zip",
+            Compression::Zstd => "tar.zst",
+            Compression::Gzip => "tar.gz",
+        }
+    }
+}
+
+impl std::fmt::Display for Compression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Compression::Zip => write!(f, "zip"),
+            Compression::Zstd => write!(f, "zstd"),
+            Compression::Gzip => write!(f, "gzip"),
+        }
+    }
+}
+
+impl std::str::FromStr for Compression {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "zip" => Ok(Self::Zip),
+            "zstd" | "zst" => Ok(Self::Zstd),
+            "gzip" | "gz" => Ok(Self::Gzip),
+            other => bail!("unknown compression '{}' (expected zip, zstd or gzip)", other),
+        }
+    }
+}
+
+enum Inner {
+    Zip(zip::ZipWriter<File>),
+    Zstd(tar::Builder<zstd::Encoder<'static, File>>),
+    Gzip(tar::Builder<flate2::write::GzEncoder<File>>),
+}
+
+/// Writes named entries into a bundle archive.
+pub struct BundleWriter {
+    inner: Inner,
+    mtime: u64,
+}
+
+impl BundleWriter {
+    /// Create (or truncate) the archive at `path`.
+    pub fn create(path: &Path, compression: Compression) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("failed to create bundle {}", path.display()))?;
+        let inner = match compression {
+            Compression::Zip => Inner::Zip(zip::ZipWriter::new(file)),
+            Compression::Zstd => Inner::Zstd(tar::Builder::new(
+                zstd::Encoder::new(file, ZSTD_LEVEL).context("failed to start zstd stream")?,
+            )),
+            Compression::Gzip => Inner::Gzip(tar::Builder::new(flate2::write::GzEncoder::new(
+                file,
+                flate2::Compression::default(),
+            ))),
+        };
+        Ok(Self {
+            inner,
+            mtime: chrono::Utc::now().timestamp().max(0) as u64,
+        })
+    }
+
+    /// Add a file named `name` (relative to the archive root).
+    pub fn add(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        match &mut self.inner {
+            Inner::Zip(zip) => {
+                let options = zip::write::SimpleFileOptions::default()
+                    .compression_method(zip::CompressionMethod::Deflated);
+                zip.start_file(name, options)?;
+                zip.write_all(data)?;
+            }
+            Inner::Zstd(tar) => append_tar(tar, name, data, data.len() as u64, self.mtime)?,
+            Inner::Gzip(tar) => append_tar(tar, name, data, data.len() as u64, self.mtime)?,
+        }
+        Ok(())
+    }
+
+    /// Add a file whose contents `write` produces incrementally.
+    pub fn add_streamed<F>(&mut self, name: &str, write: F) -> Result<()>
+    where
+        F: FnOnce(&mut dyn Write) -> Result<()>,
+    {
+        match &mut self.inner {
+            Inner::Zip(zip) => {
+                let options = zip::write::SimpleFileOptions::default()
+                    .compression_method(zip::CompressionMethod::Deflated)
+                    .large_file(true);
+                zip.start_file(name, options)?;
+                write(zip)
+            }
+            Inner::Zstd(tar) => append_tar_spooled(tar, name, write, self.mtime),
+            Inner::Gzip(tar) => append_tar_spooled(tar, name, write, self.mtime),
+        }
+    }
+
+    /// Write the archive trailer and flush the compressor.
+    pub fn finish(self) -> Result<()> {
+        match self.inner {
+            Inner::Zip(zip) => {
+                zip.finish()?;
+            }
+            Inner::Zstd(tar) => {
+                tar.into_inner()?.finish()?;
+            }
+            Inner::Gzip(tar) => {
+                tar.into_inner()?.finish()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn append_tar<W: Write, R: Read>(
+    tar: &mut tar::Builder<W>,
+    name: &str,
+    data: R,
+    size: u64,
+    mtime: u64,
+) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(size);
+    header.set_mode(0o644);
+    header.set_mtime(mtime);
+    tar.append_data(&mut header, name, data)
+        .with_context(|| format!("failed to add {} to bundle", name))
+}
+
+fn append_tar_spooled<W, F>(tar: &mut tar::Builder<W>, name: &str, write: F, mtime: u64) -> Result<()>
+where
+    W: Write,
+    F: FnOnce(&mut dyn Write) -> Result<()>,
+{
+    let mut spool = tempfile::tempfile().context("failed to create bundle spool file")?;
+    {
+        let mut out = BufWriter::new(&mut spool);
+        write(&mut out)?;
+        out.flush()?;
+    }
+    let size = spool.stream_position()?;
+    spool.rewind()?;
+    append_tar(tar, name, spool, size, mtime)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Read every entry of a bundle back as `(name, contents)`.
+    pub(crate) fn read_entries(path: &Path, compression: Compression) -> Vec<(String, Vec<u8>)> {
+        let file = File::open(path).unwrap();
+        let mut entries = Vec::new();
+        match compression {
+            Compression::Zip => {
+                let mut zip = zip::ZipArchive::new(file).unwrap();
+                for i in 0..zip.len() {
+                    let mut entry = zip.by_index(i).unwrap();
+                    let mut data = Vec::new();
+                    entry.read_to_end(&mut data).unwrap();
+                    entries.push((entry.name().to_string(), data));
+                }
+            }
+            Compression::Zstd => read_tar(zstd::Decoder::new(file).unwrap(), &mut entries),
+            Compression::Gzip => read_tar(flate2::read::GzDecoder::new(file), &mut entries),
+        }
+        entries
+    }
+
+    fn read_tar<R: Read>(reader: R, entries: &mut Vec<(String, Vec<u8>)>) {
+        let mut archive = tar::Archive::new(reader);
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let name = entry.path().unwrap().to_string_lossy().into_owned();
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data).unwrap();
+            entries.push((name, data));
+        }
+    }
+
+    #[test]
+    fn test_every_format_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let payload = b"{\"probe_type\":\"icmp\",\"value\":12.5}\n".repeat(500);
+        for compression in [Compression::Zip, Compression::Zstd, Compression::Gzip] {
+            let path = dir.path().join(format!("bundle.{}", compression.extension()));
+            let mut writer = BundleWriter::create(&path, compression).unwrap();
+            writer.add("measurements.jsonl", &payload).unwrap();
+            writer.add("notes.txt", b"hello").unwrap();
+            writer.finish().unwrap();
+
+            // Repetitive JSON must actually shrink.
+            assert!(std::fs::metadata(&path).unwrap().len() < payload.len() as u64 / 4);
+            let entries = read_entries(&path, compression);
+            assert_eq!(entries.len(), 2, "{}", compression);
+            assert_eq!(entries[0], ("measurements.jsonl".to_string(), payload.clone()));
+            assert_eq!(entries[1], ("notes.txt".to_string(), b"hello".to_vec()));
+        }
+    }
+
+    #[test]
+    fn test_streamed_entries_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        for compression in [Compression::Zip, Compression::Zstd, Compression::Gzip] {
+            let path = dir.path().join(format!("bundle.{}", compression.extension()));
+            let mut writer = BundleWriter::create(&path, compression).unwrap();
+            writer.add("manifest.json", b"{}").unwrap();
+            writer
+                .add_streamed("rows.jsonl", |out| {
+                    for i in 0..1000 {
+                        writeln!(out, "{{\"row\":{}}}", i)?;
+                    }
+                    Ok(())
+                })
+                .unwrap();
+            writer.add_streamed("empty.jsonl", |_| Ok(())).unwrap();
+            writer.finish().unwrap();
+
+            let entries = read_entries(&path, compression);
+            assert_eq!(entries.len(), 3, "{}", compression);
+            let rows = String::from_utf8(entries[1].1.clone()).unwrap();
+            assert_eq!(rows.lines().count(), 1000);
+            assert_eq!(rows.lines().last(), Some("{\"row\":999}"));
+            assert_eq!(entries[2], ("empty.jsonl".to_string(), Vec::new()));
+        }
+    }
+
+    #[test]
+    fn test_compression_parse() {
+        assert_eq!("ZSTD".parse::<Compression>().unwrap(), Compression::Zstd);
+        assert_eq!("gz".parse::<Compression>().unwrap(), Compression::Gzip);
+        assert_eq!(Compression::default(), Compression::Zip);
+        assert!("rar".parse::<Compression>().is_err());
+    }
+}
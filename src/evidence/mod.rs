@@ -1,12 +1,360 @@
 //! Evidence bundle generation and export.
+//!
+//! A bundle is an archive (see [`archive::Compression`]) with a
+//! `manifest.json` at its root listing the other entries, the bundle schema
+//! version, and the compression used, so consumers know how to open it.
+//! Next to it, `summary.json` (see [`summary`]) gives the verdicts at a glance.
+//! Entry contents are scrubbed per the bundle's [`redact::RedactionLevel`]
+//! before they are written.
+//!
+//! Measurements are streamed row by row from SQLite into `measurements.jsonl`
+//! rather than collected first, so exporting a busy day stays within a
+//! fixed memory budget on the 2GB Pi.
+
+pub mod archive;
+pub mod redact;
+pub mod summary;
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
 use anyhow::Result;
+use serde::Serialize;
+
+use crate::selftest::SelfTestReport;
+use crate::storage::Pool;
+
+pub use archive::Compression;
+pub use redact::RedactionLevel;
+
+/// Entry streamed from the `measurements` table, one JSON object per line.
+pub const MEASUREMENTS_ENTRY: &str = "measurements.jsonl";
+
+/// Incidents raised in the bundle window, plus any still open.
+pub const INCIDENTS_ENTRY: &str = "incidents.json";
+
+/// Configured schedules, enabled or not.
+pub const SCHEDULES_ENTRY: &str = "schedules.json";
+
+/// The self-test report, when the caller ran one.
+pub const SELF_TEST_ENTRY: &str = "selftest.json";
+
+/// Version of the bundle layout described by the manifest.
+pub const BUNDLE_SCHEMA_VERSION: u32 = 1;
+
+/// `manifest.json` at the root of every bundle.
+#[derive(Debug, Clone, Serialize)]
+pub struct Manifest {
+    pub schema_version: u32,
+    pub generator: String,
+    pub created_at: String,
+    /// `zip`, `zstd` (`.tar.zst`) or `gzip` (`.tar.gz`).
+    pub compression: Compression,
+    /// `none`, `internal` or `full`; see [`redact`].
+    pub redaction: RedactionLevel,
+    /// Entries other than the manifest itself.
+    pub files: Vec<String>,
+}
+
+/// Export a support/evidence bundle to the specified path. `self_test`
+/// feeds the summary's self-test verdict when the caller ran one.
+pub async fn export_bundle(
+    pool: &Pool,
+    output: &str,
+    compression: Compression,
+    redaction: RedactionLevel,
+    self_test: Option<&SelfTestReport>,
+) -> Result<()> {
+    let summary = summary::build(pool, self_test, summary::SUMMARY_WINDOW_HOURS)?;
+    let mut collected: Vec<(String, Vec<u8>)> = vec![
+        ("summary.json".to_string(), serde_json::to_vec_pretty(&summary)?),
+        (
+            INCIDENTS_ENTRY.to_string(),
+            serde_json::to_vec_pretty(&collect_incidents(pool, summary::SUMMARY_WINDOW_HOURS)?)?,
+        ),
+        (SCHEDULES_ENTRY.to_string(), serde_json::to_vec_pretty(&collect_schedules(pool)?)?),
+    ];
+    if let Some(report) = self_test {
+        collected.push((SELF_TEST_ENTRY.to_string(), serde_json::to_vec_pretty(report)?));
+    }
+
+    let mut redactor = redact::Redactor::new(redaction);
+    let entries: Vec<(String, Vec<u8>)> = collected
+        .into_iter()
+        .map(|(name, data)| {
+            let data = redact_entry(&mut redactor, &name, &data);
+            (name, data)
+        })
+        .collect();
+
+    let mut files: Vec<String> = entries.iter().map(|(name, _)| name.clone()).collect();
+    files.push(MEASUREMENTS_ENTRY.to_string());
+    let manifest = Manifest {
+        schema_version: BUNDLE_SCHEMA_VERSION,
+        generator: format!("packetparamedic {}", env!("CARGO_PKG_VERSION")),
+        created_at: chrono::Utc::now().to_rfc3339(),
+        compression,
+        redaction,
+        files,
+    };
+    let path = PathBuf::from(output);
+    let pool = pool.clone();
+    tokio::task::spawn_blocking(move || write_bundle(&pool, &path, &manifest, &entries, &mut redactor)).await??;
+
+    tracing::info!(%output, %compression, %redaction, "Exported bundle");
+    Ok(())
+}
 
-/// Export a support/evidence bundle to the specified path.
-pub async fn export_bundle(output: &str) -> Result<()> {
-    // TODO: Collect last 24h of probe results, incidents, config, self-test report
-    // TODO: Redact MAC addresses, internal IPs, SSIDs
-    // TODO: Package as ZIP
-    tracing::info!(%output, "Export bundle (stub)");
+/// Write the manifest, the in-memory `entries`, then the streamed tables.
+fn write_bundle(
+    pool: &Pool,
+    path: &Path,
+    manifest: &Manifest,
+    entries: &[(String, Vec<u8>)],
+    redactor: &mut redact::Redactor,
+) -> Result<()> {
+    let mut writer = archive::BundleWriter::create(path, manifest.compression)?;
+    writer.add("manifest.json", &serde_json::to_vec_pretty(manifest)?)?;
+    for (name, data) in entries {
+        writer.add(name, data)?;
+    }
+    writer.add_streamed(MEASUREMENTS_ENTRY, |out| {
+        stream_measurements(pool, summary::SUMMARY_WINDOW_HOURS, redactor, out)
+    })?;
+    writer.finish()
+}
+
+/// Write the last `hours` of measurements to `out` as JSON Lines, one row
+/// at a time.
+fn stream_measurements(
+    pool: &Pool,
+    hours: u32,
+    redactor: &mut redact::Redactor,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let conn = pool.get()?;
+    let mut stmt = conn.prepare(
+        "SELECT probe_type, target, value, unit, backend, duration_us, dscp, created_at
+         FROM measurements WHERE datetime(created_at) > datetime('now', ?1)
+         ORDER BY created_at, id",
+    )?;
+    let mut rows = stmt.query([format!("-{} hours", hours)])?;
+    let mut line = Vec::new();
+    while let Some(row) = rows.next()? {
+        let mut doc = serde_json::json!({
+            "probe_type": row.get::<_, String>(0)?,
+            "target": row.get::<_, String>(1)?,
+            "value": row.get::<_, f64>(2)?,
+            "unit": row.get::<_, String>(3)?,
+            "backend": row.get::<_, String>(4)?,
+            "duration_us": row.get::<_, Option<i64>>(5)?,
+            "dscp": row.get::<_, Option<i64>>(6)?,
+            "created_at": row.get::<_, String>(7)?,
+        });
+        redactor.redact_json(&mut doc);
+        line.clear();
+        serde_json::to_writer(&mut line, &doc)?;
+        line.push(b'\n');
+        out.write_all(&line)?;
+    }
     Ok(())
 }
+
+/// Incidents raised in the last `hours`, and older ones not yet resolved,
+/// newest first.
+fn collect_incidents(pool: &Pool, hours: u32) -> Result<Vec<serde_json::Value>> {
+    let conn = pool.get()?;
+    let mut stmt = conn.prepare(
+        "SELECT id, severity, verdict, evidence_json, created_at, resolved_at
+         FROM incidents
+         WHERE datetime(created_at) > datetime('now', ?1) OR resolved_at IS NULL
+         ORDER BY created_at DESC",
+    )?;
+    let rows = stmt.query_map([format!("-{} hours", hours)], |row| {
+        let evidence: String = row.get(3)?;
+        Ok(serde_json::json!({
+            "id": row.get::<_, String>(0)?,
+            "severity": row.get::<_, String>(1)?,
+            "verdict": row.get::<_, String>(2)?,
+            "evidence": serde_json::from_str::<serde_json::Value>(&evidence).unwrap_or_default(),
+            "created_at": row.get::<_, String>(4)?,
+            "resolved_at": row.get::<_, Option<String>>(5)?,
+        }))
+    })?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+/// Every configured schedule, as stored.
+fn collect_schedules(pool: &Pool) -> Result<Vec<serde_json::Value>> {
+    let conn = pool.get()?;
+    let mut stmt = conn.prepare(
+        "SELECT name, cron_expr, test_type, params, enabled, last_run_at, next_run_at
+         FROM schedules ORDER BY name",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        let params: Option<String> = row.get(3)?;
+        Ok(serde_json::json!({
+            "name": row.get::<_, String>(0)?,
+            "cron_expr": row.get::<_, String>(1)?,
+            "test_type": row.get::<_, String>(2)?,
+            "params": params.and_then(|p| serde_json::from_str::<serde_json::Value>(&p).ok()),
+            "enabled": row.get::<_, bool>(4)?,
+            "last_run_at": row.get::<_, Option<String>>(5)?,
+            "next_run_at": row.get::<_, Option<String>>(6)?,
+        }))
+    })?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+/// Scrub one entry: JSON (and JSON Lines) structurally, anything else as text.
+fn redact_entry(redactor: &mut redact::Redactor, name: &str, data: &[u8]) -> Vec<u8> {
+    if redactor.level() == RedactionLevel::None {
+        return data.to_vec();
+    }
+    let text = String::from_utf8_lossy(data);
+    if name.ends_with(".json") {
+        if let Ok(mut doc) = serde_json::from_str::<serde_json::Value>(&text) {
+            redactor.redact_json(&mut doc);
+            return serde_json::to_vec_pretty(&doc).unwrap_or_default();
+        }
+    } else if name.ends_with(".jsonl") {
+        let mut out = Vec::with_capacity(data.len());
+        for line in text.lines() {
+            match serde_json::from_str::<serde_json::Value>(line) {
+                Ok(mut doc) => {
+                    redactor.redact_json(&mut doc);
+                    out.extend_from_slice(doc.to_string().as_bytes());
+                }
+                Err(_) => out.extend_from_slice(redactor.redact_text(line).as_bytes()),
+            }
+            out.push(b'\n');
+        }
+        return out;
+    }
+    redactor.redact_text(&text).into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_manifest_records_compression() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = crate::storage::open_pool(dir.path().join("t.db").to_str().unwrap()).unwrap();
+        for compression in [Compression::Zip, Compression::Zstd] {
+            let path = dir.path().join(format!("bundle.{}", compression.extension()));
+            export_bundle(&pool, path.to_str().unwrap(), compression, RedactionLevel::Internal, None)
+                .await
+                .unwrap();
+
+            let entries = archive::tests::read_entries(&path, compression);
+            let (name, data) = &entries[0];
+            assert_eq!(name, "manifest.json");
+            let manifest: serde_json::Value = serde_json::from_slice(data).unwrap();
+            assert_eq!(manifest["schema_version"], BUNDLE_SCHEMA_VERSION);
+            assert_eq!(manifest["compression"], compression.to_string());
+            assert_eq!(manifest["redaction"], "internal");
+            assert_eq!(
+                manifest["files"],
+                serde_json::json!(["summary.json", INCIDENTS_ENTRY, SCHEDULES_ENTRY, MEASUREMENTS_ENTRY])
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bundle_includes_redacted_summary() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = crate::storage::open_pool(dir.path().join("t.db").to_str().unwrap()).unwrap();
+        crate::detect::incident::IncidentManager::new(pool.clone())
+            .record_incident(
+                "Gateway Unreachable: 192.168.1.1",
+                crate::detect::Severity::Critical,
+                serde_json::json!({}),
+            )
+            .unwrap();
+        pool.get()
+            .unwrap()
+            .execute(
+                "INSERT INTO blame_checks (verdict, confidence, details_json)
+                 VALUES ('Local Network Issue: 192.168.1.1', 80, '[]')",
+                [],
+            )
+            .unwrap();
+
+        let path = dir.path().join("bundle.zip");
+        export_bundle(&pool, path.to_str().unwrap(), Compression::Zip, RedactionLevel::Internal, None)
+            .await
+            .unwrap();
+
+        let entries = archive::tests::read_entries(&path, Compression::Zip);
+        let (name, data) = &entries[1];
+        assert_eq!(name, "summary.json");
+        let summary: serde_json::Value = serde_json::from_slice(data).unwrap();
+        assert_eq!(summary["blame"]["verdict"], "Local Network Issue: IP_1");
+        assert_eq!(summary["incidents"]["critical"], 1);
+        assert!(summary["self_test"]["status"].is_null());
+        assert!(summary["time_range"]["from"].is_string());
+
+        let (name, data) = &entries[2];
+        assert_eq!(name, INCIDENTS_ENTRY);
+        let incidents: serde_json::Value = serde_json::from_slice(data).unwrap();
+        assert_eq!(incidents[0]["verdict"], "Gateway Unreachable: IP_1");
+        assert_eq!(incidents[0]["severity"], "Critical");
+    }
+
+    #[tokio::test]
+    async fn test_bundle_includes_schedules_and_self_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = crate::storage::open_pool(dir.path().join("t.db").to_str().unwrap()).unwrap();
+        pool.get()
+            .unwrap()
+            .execute(
+                "INSERT INTO schedules (name, cron_expr, test_type, params)
+                 VALUES ('gw-ping', '*/5 * * * *', 'icmp', '{\"target\":\"192.168.1.1\"}')",
+                [],
+            )
+            .unwrap();
+        let report = crate::selftest::SelfTestReport {
+            results: Vec::new(),
+            compatibility: Default::default(),
+            recommendations: vec!["High Performance: connect a 2.5GbE+ NIC".to_string()],
+        };
+
+        let path = dir.path().join("bundle.zip");
+        export_bundle(&pool, path.to_str().unwrap(), Compression::Zip, RedactionLevel::Internal, Some(&report))
+            .await
+            .unwrap();
+
+        let entries = archive::tests::read_entries(&path, Compression::Zip);
+        let names: Vec<&str> = entries.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["manifest.json", "summary.json", INCIDENTS_ENTRY, SCHEDULES_ENTRY, SELF_TEST_ENTRY, MEASUREMENTS_ENTRY]
+        );
+        let schedules: serde_json::Value = serde_json::from_slice(&entries[3].1).unwrap();
+        assert_eq!(schedules[0]["name"], "gw-ping");
+        assert_eq!(schedules[0]["params"]["target"], "IP_1");
+        assert_eq!(schedules[0]["enabled"], true);
+        let self_test: serde_json::Value = serde_json::from_slice(&entries[4].1).unwrap();
+        assert_eq!(self_test["recommendations"][0], "High Performance: connect a 2.5GbE+ NIC");
+    }
+
+    #[test]
+    fn test_redact_entry_by_type() {
+        let mut r = redact::Redactor::new(RedactionLevel::Internal);
+        let json = redact_entry(&mut r, "wifi.json", br#"{"ssid":"Home","ip":"192.168.1.9"}"#);
+        let doc: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(doc["ssid"], "SSID_1");
+        assert_eq!(doc["ip"], "IP_1");
+
+        let lines = redact_entry(&mut r, "m.jsonl", b"{\"target\":\"192.168.1.9\"}\nnot json 10.0.0.1\n");
+        assert_eq!(String::from_utf8(lines).unwrap(), "{\"target\":\"IP_1\"}\nnot json IP_2\n");
+
+        let text = redact_entry(&mut r, "log.txt", b"gw 192.168.1.9");
+        assert_eq!(text, b"gw IP_1");
+
+        let mut keep = redact::Redactor::new(RedactionLevel::None);
+        assert_eq!(redact_entry(&mut keep, "log.txt", b"gw 192.168.1.9"), b"gw 192.168.1.9");
+    }
+}
@@ -0,0 +1,351 @@
+//! Redaction of identifying details in evidence bundles.
+//!
+//! Different recipients need different levels ([`RedactionLevel`]): an ISP
+//! support rep shouldn't see the LAN layout, while a personal archive should
+//! keep everything. Each scrubbed value is replaced by a stable pseudonym
+//! (`MAC_1`, `IP_2`, `SSID_1`, `HOST_3`): one [`Redactor`] is used for the
+//! whole bundle, so the same address gets the same pseudonym in every file
+//! and correlations survive redaction.
+//!
+//! | Level      | MACs | Private IPs | SSIDs | Local hostnames | Public IPs / hostnames |
+//! |------------|------|-------------|-------|-----------------|------------------------|
+//! | `none`     |      |             |       |                 |                        |
+//! | `internal` | yes  | yes         | yes   | yes             |                        |
+//! | `full`     | yes  | yes         | yes   | yes             | yes                    |
+//!
+//! Private IPs are RFC 1918, CGNAT, link-local and IPv6 ULA addresses;
+//! loopback and unspecified addresses identify nothing and are kept. Local
+//! hostnames are those under `.local`, `.lan`, `.home`, `.internal` or
+//! `.home.arpa`. SSIDs only appear in structured data, so they are found by
+//! key in [`Redactor::redact_json`].
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use anyhow::bail;
+use serde::Serialize;
+use serde_json::Value;
+
+/// Suffixes that mark a hostname as local to the LAN.
+const LOCAL_SUFFIXES: &[&str] = &[".local", ".lan", ".home", ".internal", ".home.arpa"];
+
+/// JSON keys whose string value is an SSID.
+const SSID_KEYS: &[&str] = &["ssid", "bssid_name", "network_name"];
+
+/// JSON keys whose string value is a hostname, whatever it looks like.
+const HOSTNAME_KEYS: &[&str] = &["hostname", "host_name", "nickname"];
+
+/// How much to scrub from a bundle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RedactionLevel {
+    /// Keep everything (personal archive).
+    None,
+    /// Scrub LAN details: MACs, private IPs, SSIDs, local hostnames.
+    #[default]
+    Internal,
+    /// Also scrub public IPs and hostnames.
+    Full,
+}
+
+impl std::fmt::Display for RedactionLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RedactionLevel::None => write!(f, "none"),
+            RedactionLevel::Internal => write!(f, "internal"),
+            RedactionLevel::Full => write!(f, "full"),
+        }
+    }
+}
+
+impl std::str::FromStr for RedactionLevel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "none" => Ok(Self::None),
+            "internal" => Ok(Self::Internal),
+            "full" => Ok(Self::Full),
+            other => bail!("unknown redaction level '{}' (expected none, internal or full)", other),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Kind {
+    Mac,
+    Ip,
+    Ssid,
+    Host,
+}
+
+impl Kind {
+    fn prefix(&self) -> &'static str {
+        match self {
+            Kind::Mac => "MAC",
+            Kind::Ip => "IP",
+            Kind::Ssid => "SSID",
+            Kind::Host => "HOST",
+        }
+    }
+}
+
+/// Scrubs values at one level, handing out stable pseudonyms.
+#[derive(Debug, Default)]
+pub struct Redactor {
+    level: RedactionLevel,
+    pseudonyms: HashMap<(Kind, String), String>,
+    counters: HashMap<Kind, usize>,
+}
+
+impl Redactor {
+    pub fn new(level: RedactionLevel) -> Self {
+        Self {
+            level,
+            ..Self::default()
+        }
+    }
+
+    pub fn level(&self) -> RedactionLevel {
+        self.level
+    }
+
+    /// Redact identifiers in free text (log lines, CSV, command output).
+    pub fn redact_text(&mut self, text: &str) -> String {
+        if self.level == RedactionLevel::None {
+            return text.to_string();
+        }
+        let mut out = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find(is_token_char) {
+            out.push_str(&rest[..start]);
+            let after = &rest[start..];
+            let len = after.find(|c: char| !is_token_char(c)).unwrap_or(after.len());
+            let token = &after[..len];
+            // Sentence punctuation isn't part of an address or name.
+            let core = token.trim_end_matches(['.', ':', '-']);
+            match self.redact_token(core) {
+                Some(replacement) => {
+                    out.push_str(&replacement);
+                    out.push_str(&token[core.len()..]);
+                }
+                None => out.push_str(token),
+            }
+            rest = &after[len..];
+        }
+        out.push_str(rest);
+        out
+    }
+
+    /// Redact a JSON document in place: SSID and hostname fields by key,
+    /// every other string (and object key) as free text.
+    pub fn redact_json(&mut self, value: &mut Value) {
+        if self.level == RedactionLevel::None {
+            return;
+        }
+        match value {
+            Value::String(s) => *s = self.redact_text(s),
+            Value::Array(items) => items.iter_mut().for_each(|v| self.redact_json(v)),
+            Value::Object(map) => {
+                let fields = std::mem::take(map);
+                for (key, mut v) in fields {
+                    let lower = key.to_ascii_lowercase();
+                    match &mut v {
+                        Value::String(s) if SSID_KEYS.contains(&lower.as_str()) && !s.is_empty() => {
+                            *s = self.pseudonym(Kind::Ssid, s);
+                        }
+                        Value::String(s)
+                            if HOSTNAME_KEYS.contains(&lower.as_str())
+                                && (self.level == RedactionLevel::Full || is_local_hostname(s)) =>
+                        {
+                            *s = self.pseudonym(Kind::Host, s);
+                        }
+                        _ => self.redact_json(&mut v),
+                    }
+                    map.insert(self.redact_text(&key), v);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Replacement for one token, if it should be scrubbed at this level.
+    fn redact_token(&mut self, token: &str) -> Option<String> {
+        if is_mac(token) {
+            return Some(self.pseudonym(Kind::Mac, &token.to_ascii_lowercase()));
+        }
+        if let Some((ip, port)) = parse_ip(token) {
+            if !self.scrubs_ip(ip) {
+                return None;
+            }
+            let name = self.pseudonym(Kind::Ip, &ip.to_string());
+            return Some(match port {
+                Some(port) => format!("{}:{}", name, port),
+                None => name,
+            });
+        }
+        if is_hostname(token) && (self.level == RedactionLevel::Full || is_local_hostname(token)) {
+            return Some(self.pseudonym(Kind::Host, &token.to_ascii_lowercase()));
+        }
+        None
+    }
+
+    fn scrubs_ip(&self, ip: IpAddr) -> bool {
+        if ip.is_loopback() || ip.is_unspecified() {
+            return false;
+        }
+        match self.level {
+            RedactionLevel::None => false,
+            RedactionLevel::Internal => is_private_ip(ip),
+            RedactionLevel::Full => true,
+        }
+    }
+
+    fn pseudonym(&mut self, kind: Kind, value: &str) -> String {
+        if let Some(existing) = self.pseudonyms.get(&(kind, value.to_string())) {
+            return existing.clone();
+        }
+        let n = self.counters.entry(kind).or_insert(0);
+        *n += 1;
+        let name = format!("{}_{}", kind.prefix(), n);
+        self.pseudonyms.insert((kind, value.to_string()), name.clone());
+        name
+    }
+}
+
+fn is_token_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '.' | ':' | '-' | '_')
+}
+
+/// `aa:bb:cc:dd:ee:ff` or `aa-bb-cc-dd-ee-ff`.
+fn is_mac(token: &str) -> bool {
+    let bytes = token.as_bytes();
+    bytes.len() == 17
+        && (bytes[2] == b':' || bytes[2] == b'-')
+        && bytes.iter().enumerate().all(|(i, b)| {
+            if i % 3 == 2 {
+                *b == bytes[2]
+            } else {
+                b.is_ascii_hexdigit()
+            }
+        })
+}
+
+/// An IP address, optionally followed by `:port` (IPv4 only; bare IPv6
+/// colons are ambiguous).
+fn parse_ip(token: &str) -> Option<(IpAddr, Option<u16>)> {
+    if let Ok(ip) = token.parse::<IpAddr>() {
+        return Some((ip, None));
+    }
+    let (host, port) = token.rsplit_once(':')?;
+    let ip = host.parse::<Ipv4Addr>().ok()?;
+    Some((IpAddr::V4(ip), Some(port.parse().ok()?)))
+}
+
+fn is_private_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            v4.is_private() || v4.is_link_local() || (a == 100 && (64..128).contains(&b))
+        }
+        IpAddr::V6(v6) => is_ula(&v6) || is_v6_link_local(&v6),
+    }
+}
+
+fn is_ula(ip: &Ipv6Addr) -> bool {
+    ip.segments()[0] & 0xfe00 == 0xfc00
+}
+
+fn is_v6_link_local(ip: &Ipv6Addr) -> bool {
+    ip.segments()[0] & 0xffc0 == 0xfe80
+}
+
+/// A dotted name with at least two labels and an alphabetic last label,
+/// e.g. `nas.local` or `speedtest.example.net`.
+fn is_hostname(token: &str) -> bool {
+    let labels: Vec<&str> = token.split('.').collect();
+    labels.len() >= 2
+        && labels.iter().all(|l| {
+            !l.is_empty()
+                && !l.starts_with('-')
+                && l.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+        && labels
+            .last()
+            .is_some_and(|tld| tld.len() >= 2 && tld.chars().all(|c| c.is_ascii_alphabetic()))
+}
+
+fn is_local_hostname(name: &str) -> bool {
+    let lower = name.to_ascii_lowercase();
+    LOCAL_SUFFIXES.iter().any(|s| lower.ends_with(s))
+        || (!lower.is_empty() && !lower.contains('.') && lower.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const LOG: &str = "eth0 dc:a6:32:01:02:03 got 192.168.1.23 from gw 192.168.1.1; \
+                       nas.local at fd00::5, cgnat 100.72.1.9, probing 8.8.8.8:53 and speedtest.example.net. \
+                       Again: DC:A6:32:01:02:03 192.168.1.23 127.0.0.1";
+
+    #[test]
+    fn test_level_none_keeps_everything() {
+        let mut r = Redactor::new(RedactionLevel::None);
+        assert_eq!(r.redact_text(LOG), LOG);
+        let mut doc = json!({ "ssid": "HomeWiFi", "ip": "10.0.0.2" });
+        let before = doc.clone();
+        r.redact_json(&mut doc);
+        assert_eq!(doc, before);
+    }
+
+    #[test]
+    fn test_level_internal_scrubs_lan_details() {
+        let mut r = Redactor::new(RedactionLevel::Internal);
+        let out = r.redact_text(LOG);
+        assert_eq!(
+            out,
+            "eth0 MAC_1 got IP_1 from gw IP_2; \
+             HOST_1 at IP_3, cgnat IP_4, probing 8.8.8.8:53 and speedtest.example.net. \
+             Again: MAC_1 IP_1 127.0.0.1"
+        );
+    }
+
+    #[test]
+    fn test_level_full_scrubs_public_too() {
+        let mut r = Redactor::new(RedactionLevel::Full);
+        let out = r.redact_text(LOG);
+        assert!(out.contains("probing IP_5:53 and HOST_2."), "{}", out);
+        assert!(out.ends_with("MAC_1 IP_1 127.0.0.1"));
+        assert!(!out.contains("8.8.8.8") && !out.contains("example.net"));
+    }
+
+    #[test]
+    fn test_json_pseudonyms_are_stable_across_documents() {
+        let mut r = Redactor::new(RedactionLevel::Internal);
+        let mut wifi = json!([{ "interface": "wlan0", "ssid": "HomeWiFi", "hostname": "pi5" }]);
+        let mut incident = json!({
+            "verdict": "Local Network Issue",
+            "evidence": { "gateway": "192.168.1.1", "target": "1.1.1.1" },
+            "network": { "SSID": "HomeWiFi" }
+        });
+        r.redact_json(&mut wifi);
+        r.redact_json(&mut incident);
+
+        assert_eq!(wifi[0]["ssid"], "SSID_1");
+        assert_eq!(wifi[0]["hostname"], "HOST_1");
+        assert_eq!(wifi[0]["interface"], "wlan0");
+        assert_eq!(incident["network"]["SSID"], "SSID_1");
+        assert_eq!(incident["evidence"]["gateway"], "IP_1");
+        assert_eq!(incident["evidence"]["target"], "1.1.1.1");
+        assert_eq!(incident["verdict"], "Local Network Issue");
+    }
+
+    #[test]
+    fn test_redaction_level_parse() {
+        assert_eq!("FULL".parse::<RedactionLevel>().unwrap(), RedactionLevel::Full);
+        assert_eq!(RedactionLevel::default(), RedactionLevel::Internal);
+        assert!("partial".parse::<RedactionLevel>().is_err());
+    }
+}
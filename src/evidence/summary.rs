@@ -0,0 +1,239 @@
+//! `summary.json`: the bundle's verdict at a glance.
+//!
+//! Support engineers triage from this file and only open the raw
+//! measurement and incident files when it points somewhere interesting.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rusqlite::OptionalExtension;
+use serde::Serialize;
+
+use crate::analysis::blame_history;
+use crate::selftest::{SelfTestReport, TestStatus};
+use crate::storage::Pool;
+use crate::throughput::report::{efficiency_pct, EfficiencyGrade};
+
+/// Hours of history the summary (and the bundle) covers.
+pub const SUMMARY_WINDOW_HOURS: u32 = 24;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BundleSummary {
+    pub time_range: TimeRange,
+    /// Most recent blame check in the window, if any ran.
+    pub blame: Option<BlameVerdict>,
+    pub incidents: IncidentCounts,
+    pub self_test: SelfTestVerdict,
+    /// Most recent throughput test in the window, if any ran.
+    pub link: Option<LinkSummary>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TimeRange {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BlameVerdict {
+    pub verdict: String,
+    pub confidence: u8,
+    pub checked_at: DateTime<Utc>,
+}
+
+/// Incidents raised in the window, by severity.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct IncidentCounts {
+    pub info: usize,
+    pub warning: usize,
+    pub critical: usize,
+    /// Of those, still open.
+    pub open: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SelfTestVerdict {
+    /// Worst component status, or `None` if no self-test was included.
+    pub status: Option<TestStatus>,
+    /// Components that failed.
+    pub failed: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LinkSummary {
+    pub link_speed_mbps: Option<u64>,
+    pub throughput_mbps: f64,
+    pub efficiency_pct: Option<f64>,
+    pub grade: Option<EfficiencyGrade>,
+}
+
+/// Summarize the last `hours` of stored data plus `self_test`, if one ran.
+pub fn build(pool: &Pool, self_test: Option<&SelfTestReport>, hours: u32) -> Result<BundleSummary> {
+    let to = Utc::now();
+    let from = to - chrono::Duration::hours(hours as i64);
+
+    let blame = blame_history::load_history(pool, hours)?
+        .pop()
+        .map(|r| BlameVerdict {
+            verdict: r.verdict,
+            confidence: r.confidence,
+            checked_at: r.created_at,
+        });
+
+    Ok(BundleSummary {
+        time_range: TimeRange { from, to },
+        blame,
+        incidents: incident_counts(pool, hours)?,
+        self_test: self_test_verdict(self_test),
+        link: latest_link(pool, hours)?,
+    })
+}
+
+fn incident_counts(pool: &Pool, hours: u32) -> Result<IncidentCounts> {
+    let conn = pool.get()?;
+    let mut stmt = conn.prepare(
+        "SELECT severity, status = 'Open' FROM incidents
+         WHERE datetime(created_at) > datetime('now', ?1)",
+    )?;
+    let rows = stmt.query_map([format!("-{} hours", hours)], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, bool>(1)?))
+    })?;
+
+    let mut counts = IncidentCounts::default();
+    for r in rows {
+        let (severity, open) = r?;
+        match severity.as_str() {
+            "Critical" => counts.critical += 1,
+            "Warning" => counts.warning += 1,
+            _ => counts.info += 1,
+        }
+        if open {
+            counts.open += 1;
+        }
+    }
+    Ok(counts)
+}
+
+fn self_test_verdict(report: Option<&SelfTestReport>) -> SelfTestVerdict {
+    let Some(report) = report else {
+        return SelfTestVerdict { status: None, failed: Vec::new() };
+    };
+    let has = |status: TestStatus| report.results.iter().any(|r| r.status == status);
+    let status = if has(TestStatus::Fail) {
+        TestStatus::Fail
+    } else if has(TestStatus::Warning) {
+        TestStatus::Warning
+    } else {
+        TestStatus::Pass
+    };
+    SelfTestVerdict {
+        status: Some(status),
+        failed: report
+            .results
+            .iter()
+            .filter(|r| r.status == TestStatus::Fail)
+            .map(|r| r.component.clone())
+            .collect(),
+    }
+}
+
+/// The latest TCP run; UDP runs are rate-capped and say nothing about the link.
+fn latest_link(pool: &Pool, hours: u32) -> Result<Option<LinkSummary>> {
+    let conn = pool.get()?;
+    let row = conn
+        .query_row(
+            "SELECT link_speed_mbps, throughput_mbps FROM throughput_results
+             WHERE throughput_mbps IS NOT NULL AND protocol = 'tcp' AND datetime(created_at) > datetime('now', ?1)
+             ORDER BY created_at DESC, id DESC LIMIT 1",
+            [format!("-{} hours", hours)],
+            |row| Ok((row.get::<_, Option<i64>>(0)?, row.get::<_, f64>(1)?)),
+        )
+        .optional()?;
+    Ok(row.map(|(link, throughput_mbps)| {
+        let link_speed_mbps = link.and_then(|l| u64::try_from(l).ok());
+        let efficiency_pct = efficiency_pct(throughput_mbps, link_speed_mbps);
+        LinkSummary {
+            link_speed_mbps,
+            throughput_mbps,
+            efficiency_pct,
+            grade: efficiency_pct.map(EfficiencyGrade::from_pct),
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detect::incident::IncidentManager;
+    use crate::detect::Severity;
+    use crate::selftest::ComponentResult;
+
+    fn component(name: &str, status: TestStatus) -> ComponentResult {
+        ComponentResult {
+            component: name.to_string(),
+            status,
+            details: String::new(),
+            remediation: None,
+        }
+    }
+
+    #[test]
+    fn test_summary_from_stored_data() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = crate::storage::open_pool(dir.path().join("t.db").to_str().unwrap()).unwrap();
+        pool.get()
+            .unwrap()
+            .execute_batch(
+            "INSERT INTO blame_checks (verdict, confidence, details_json, created_at)
+                 VALUES ('Local Network Issue', 70, '[]', datetime('now', '-2 hours')),
+                        ('ISP Issue', 85, '[]', datetime('now', '-1 hours')),
+                        ('Healthy', 90, '[]', datetime('now', '-3 days'));
+             INSERT INTO throughput_results (mode, direction, link_speed_mbps, throughput_mbps, result_json, created_at)
+                 VALUES ('wan', 'download', 1000, 450.0, '{}', datetime('now', '-3 hours')),
+                        ('wan', 'download', 1000, 920.0, '{}', datetime('now', '-1 hours'));",
+        )
+        .unwrap();
+        let im = IncidentManager::new(pool.clone());
+        im.record_incident("ISP Issue", Severity::Critical, serde_json::json!({})).unwrap();
+        let resolved = im.record_incident("DNS Anomaly", Severity::Warning, serde_json::json!({})).unwrap();
+        im.resolve_incident(resolved).unwrap();
+
+        let report = SelfTestReport {
+            results: vec![
+                component("Board", TestStatus::Pass),
+                component("Storage", TestStatus::Warning),
+                component("Power Supply", TestStatus::Fail),
+            ],
+            compatibility: Default::default(),
+            recommendations: Vec::new(),
+        };
+        let summary = build(&pool, Some(&report), SUMMARY_WINDOW_HOURS).unwrap();
+
+        let blame = summary.blame.unwrap();
+        assert_eq!(blame.verdict, "ISP Issue");
+        assert_eq!(blame.confidence, 85);
+        assert_eq!(
+            summary.incidents,
+            IncidentCounts { info: 0, warning: 1, critical: 1, open: 1 }
+        );
+        assert_eq!(summary.self_test.status, Some(TestStatus::Fail));
+        assert_eq!(summary.self_test.failed, vec!["Power Supply".to_string()]);
+        let link = summary.link.unwrap();
+        assert_eq!(link.throughput_mbps, 920.0);
+        assert_eq!(link.grade, Some(EfficiencyGrade::A));
+        assert_eq!(
+            summary.time_range.to - summary.time_range.from,
+            chrono::Duration::hours(SUMMARY_WINDOW_HOURS as i64)
+        );
+    }
+
+    #[test]
+    fn test_empty_summary() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = crate::storage::open_pool(dir.path().join("t.db").to_str().unwrap()).unwrap();
+        let summary = build(&pool, None, SUMMARY_WINDOW_HOURS).unwrap();
+        assert!(summary.blame.is_none());
+        assert!(summary.link.is_none());
+        assert_eq!(summary.incidents, IncidentCounts::default());
+        assert_eq!(summary.self_test.status, None);
+    }
+}
@@ -8,6 +8,7 @@ pub mod analysis;
 pub mod api;
 pub mod detect;
 pub mod evidence;
+pub mod onboarding;
 pub mod probes;
 pub mod scheduler;
 pub mod selftest;
@@ -55,11 +56,47 @@ pub async fn serve(bind: &str, db_path: &str) -> Result<()> {
         scheduler: scheduler.clone(),
     };
 
-    let app = api::router(app_state);
+    let app = api::router(app_state, &api::ApiConfig::from_env());
 
     tracing::info!(%addr, "PacketParamedic listening");
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown_signal())
+        .await?;
+
+    // Leave a truncated WAL behind so the next start doesn't replay it.
+    tracing::info!("Shutting down; checkpointing database");
+    storage::wal::checkpoint_logged(&pool).await;
 
     Ok(())
 }
+
+/// Resolve on Ctrl-C or (on Unix) SIGTERM from systemd.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::warn!("Failed to listen for Ctrl-C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sig) => {
+                sig.recv().await;
+            }
+            Err(e) => {
+                tracing::warn!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
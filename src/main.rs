@@ -18,7 +18,7 @@ enum Commands {
     /// Start the daemon (API server + scheduler + probes)
     Serve {
         /// Bind address
-        #[arg(long, default_value = "0.0.0.0:8080")]
+        #[arg(long, env = "PP_BIND_ADDR", default_value = "0.0.0.0:8080")]
         bind: String,
     },
 
@@ -27,6 +27,10 @@ enum Commands {
         /// JSON output for machine parsing
         #[arg(long)]
         json: bool,
+
+        /// Skip the slow checks (acceleration init, Wi-Fi scan)
+        #[arg(long)]
+        quick: bool,
     },
 
     /// Run a blame check ("Is it me or my ISP?")
@@ -38,7 +42,7 @@ enum Commands {
         #[arg(long, default_value = "wan")]
         mode: String,
 
-        /// Provider to use: ookla, ndt7, fast (overrides mode/peer)
+        /// Provider to use: ookla, ndt7, fast, web (overrides mode/peer)
         #[arg(long)]
         provider: Option<String>,
 
@@ -46,6 +50,19 @@ enum Commands {
         #[arg(long)]
         peer: Option<String>,
 
+        /// Pin a provider server (ndt7: M-Lab site such as lga03, or a server FQDN;
+        /// ookla: server ID from --list-servers)
+        #[arg(long)]
+        server: Option<String>,
+
+        /// List the provider's servers instead of testing (ookla)
+        #[arg(long)]
+        list_servers: bool,
+
+        /// Run every installed provider in turn and print a comparison table
+        #[arg(long)]
+        compare: bool,
+
         /// Test duration
         #[arg(long, default_value = "30s")]
         duration: String,
@@ -53,6 +70,33 @@ enum Commands {
         /// Number of parallel TCP streams
         #[arg(long, default_value = "1")]
         streams: u32,
+
+        /// iperf3 TCP window size, e.g. 4M (defaults by link speed)
+        #[arg(long)]
+        window: Option<String>,
+
+        /// iperf3 read/write buffer length, e.g. 1M (defaults by link speed)
+        #[arg(long)]
+        len: Option<String>,
+
+        /// Seconds of TCP warm-up to leave out of the average (iperf3 -O);
+        /// run on top of --duration. Also applies to --provider reflector
+        #[arg(long)]
+        omit: Option<u32>,
+
+        /// iperf3 transport: tcp (default), or udp to measure jitter and loss
+        #[arg(long)]
+        protocol: Option<String>,
+
+        /// iperf3 target bandwidth per stream, e.g. 500M (UDP default 100M;
+        /// caps TCP)
+        #[arg(long)]
+        bandwidth: Option<String>,
+
+        /// Seconds to wait for a speed test already in progress (e.g. a
+        /// scheduled one) before giving up; 0 fails immediately
+        #[arg(long, default_value = "300")]
+        lock_wait: u64,
     },
 
     /// Run a trace (MTR) to a target
@@ -76,9 +120,45 @@ enum Commands {
 
     /// Export a support/evidence bundle
     ExportBundle {
-        /// Output file path
-        #[arg(long, default_value = "bundle.zip")]
-        output: String,
+        /// Output file path (default: bundle.zip, bundle.tar.zst or bundle.tar.gz)
+        #[arg(long)]
+        output: Option<String>,
+
+        /// Archive format: zip (default), zstd (.tar.zst) or gzip (.tar.gz)
+        #[arg(long, default_value = "zip")]
+        compression: String,
+
+        /// What to scrub: none (own archive), internal (MACs, private IPs,
+        /// SSIDs, local hostnames) or full (also public IPs and hostnames)
+        #[arg(long, default_value = "internal")]
+        redact: String,
+
+        /// Don't run the hardware self-test for the bundle summary
+        #[arg(long)]
+        skip_self_test: bool,
+    },
+
+    /// Import historical latency samples (e.g. old ping logs) as measurements
+    Import {
+        /// Input format (csv: `timestamp,latency_ms` per line)
+        #[arg(long, default_value = "csv")]
+        format: String,
+
+        /// Probe type the samples belong to (icmp, dns, http, tcp)
+        #[arg(long, default_value = "icmp")]
+        probe: String,
+
+        /// Target the samples were measured against (e.g. 8.8.8.8)
+        #[arg(long)]
+        target: String,
+
+        /// File to import
+        #[arg(long)]
+        file: String,
+
+        /// Parse and report without writing anything
+        #[arg(long)]
+        dry_run: bool,
     },
 
     /// Pair with a Paramedic Reflector
@@ -88,8 +168,72 @@ enum Commands {
         host: String,
 
         /// Pairing token from reflector
+        #[arg(long, required_unless_present = "generate")]
+        token: Option<String>,
+
+        /// Generate the code here instead, to enter on the reflector with
+        /// `reflector pair --code` (this side owns the code and its expiry)
+        #[arg(long, conflicts_with = "token")]
+        generate: bool,
+
+        /// Lifetime of a generated code in seconds
+        #[arg(long, default_value_t = packetparamedic::reflector_proto::pairing::DEFAULT_TTL.as_secs(), requires = "generate")]
+        ttl: u64,
+
+        /// Nickname for this reflector in fleet commands (defaults to the address)
         #[arg(long)]
-        token: String,
+        name: Option<String>,
+
+        /// Expected reflector endpoint ID (as shown by `reflector show-id`);
+        /// pairing aborts if the reflector presents a different one
+        #[arg(long)]
+        endpoint_id: Option<String>,
+
+        /// Pair without asking to confirm the reflector's endpoint ID
+        #[arg(long, short = 'y')]
+        yes: bool,
+    },
+
+    /// Query all paired reflectors
+    ReflectorFleet {
+        #[command(subcommand)]
+        action: FleetAction,
+    },
+
+    /// First-run wizard: self-test, pick a schedule profile, pair a
+    /// reflector, and write a starter config
+    Init {
+        /// Accept all defaults without prompting (for scripting)
+        #[arg(long, short = 'y')]
+        yes: bool,
+
+        /// Schedule profile to apply (minimal, standard, aggressive); default is suggested from self-test
+        #[arg(long)]
+        profile: Option<String>,
+
+        /// Skip the hardware self-test
+        #[arg(long)]
+        skip_self_test: bool,
+
+        /// Reflector address to pair with (e.g. 1.2.3.4:4000)
+        #[arg(long, requires = "token")]
+        reflector: Option<String>,
+
+        /// Pairing token from the reflector
+        #[arg(long, requires = "reflector")]
+        token: Option<String>,
+
+        /// Daily data budget for scheduled throughput tests, in GB
+        #[arg(long)]
+        budget_gb: Option<f64>,
+
+        /// Where to write the starter environment file
+        #[arg(long, default_value = packetparamedic::onboarding::DEFAULT_CONFIG_PATH)]
+        config: String,
+
+        /// Overwrite an existing config file
+        #[arg(long)]
+        force: bool,
     },
 
     /// Check Wi-Fi status (Phase 7.5)
@@ -100,6 +244,16 @@ enum Commands {
     },
 }
 
+#[derive(Subcommand)]
+enum FleetAction {
+    /// Show up/down/busy status of every paired reflector
+    Status {
+        /// JSON output
+        #[arg(long)]
+        json: bool,
+    },
+}
+
 #[derive(Subcommand)]
 enum DiagnosticCommand {
     /// Measure Bufferbloat (Latency Under Load)
@@ -119,6 +273,32 @@ enum DiagnosticCommand {
         #[arg(long, default_value = "icmp")]
         probe: String,
     },
+
+    /// Show how blame-check verdicts changed over time
+    BlameHistory {
+        /// Look-back window in hours
+        #[arg(long, default_value = "24")]
+        hours: u32,
+
+        /// JSON output
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Benchmark acceleration backends (Vulkan, GLES, NEON, scalar)
+    AccelBench {
+        /// Dataset sizes in samples (comma-separated)
+        #[arg(long, value_delimiter = ',', default_value = "1024,65536,1048576")]
+        sizes: Vec<usize>,
+
+        /// Timed iterations per op/backend/size
+        #[arg(long, default_value = "10")]
+        iterations: u32,
+
+        /// JSON output
+        #[arg(long)]
+        json: bool,
+    },
 }
 
 #[derive(Subcommand)]
@@ -132,13 +312,24 @@ enum ScheduleAction {
         #[arg(long)]
         name: String,
 
-        /// Cron expression (5-field)
+        /// Cron expression: 5-field (minute hour dom month dow), or 6-field
+        /// with seconds first for sub-minute schedules
         #[arg(long)]
         cron: String,
 
         /// Test type to run
         #[arg(long)]
         test: String,
+
+        /// Test parameters as JSON, e.g. '{"reflector": "office"}' for
+        /// reflector_throughput / reflector_udp_echo
+        #[arg(long)]
+        params: Option<String>,
+
+        /// A single test parameter as key=value (repeatable; overrides
+        /// --params), e.g. --param provider=ndt7 --param duration_sec=15
+        #[arg(long = "param")]
+        param: Vec<String>,
     },
 
     /// Remove a schedule
@@ -167,6 +358,53 @@ enum ScheduleAction {
     },
 }
 
+/// Process exit codes, so cron/monit can react to an outcome without parsing
+/// stdout. Any other failure exits 1 via the `anyhow::Error` from `main`.
+/// Keep in sync with the "Exit codes" table in the README.
+mod exit_code {
+    pub const OK: i32 = 0;
+    pub const ERROR: i32 = 1;
+    pub const ISP_ISSUE: i32 = 2;
+    pub const LOCAL_ISSUE: i32 = 3;
+    pub const SELF_TEST_FAILED: i32 = 4;
+    pub const DNS_ISSUE: i32 = 5;
+    pub const SERVICE_ISSUE: i32 = 6;
+}
+
+/// Exit code for a blame-check outcome (see `probes::run_blame_check`).
+fn blame_exit_code(fault: packetparamedic::probes::BlameFault) -> i32 {
+    use packetparamedic::probes::BlameFault;
+    match fault {
+        BlameFault::Healthy => exit_code::OK,
+        BlameFault::Isp => exit_code::ISP_ISSUE,
+        BlameFault::Local => exit_code::LOCAL_ISSUE,
+        BlameFault::Dns => exit_code::DNS_ISSUE,
+        BlameFault::Service => exit_code::SERVICE_ISSUE,
+        BlameFault::Unknown => exit_code::ERROR,
+    }
+}
+
+/// Store a provider result with the other speed tests; best effort.
+fn record_speed_test(result: &packetparamedic::throughput::provider::SpeedTestResult) {
+    let saved = packetparamedic::storage::open_pool("data/packetparamedic.db").and_then(|pool| result.save(&pool));
+    if let Err(e) = saved {
+        tracing::warn!("Failed to record speed test: {:#}", e);
+    }
+}
+
+/// Self-test fails if any component failed; warnings and skips still pass.
+fn selftest_exit_code(report: &packetparamedic::selftest::SelfTestReport) -> i32 {
+    if report
+        .results
+        .iter()
+        .any(|r| r.status == packetparamedic::selftest::TestStatus::Fail)
+    {
+        exit_code::SELF_TEST_FAILED
+    } else {
+        exit_code::OK
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     // Initialize tracing
@@ -187,9 +425,15 @@ async fn main() -> Result<()> {
             tracing::info!(%bind, "Starting PacketParamedic daemon");
             packetparamedic::serve(&bind, "data/packetparamedic.db").await?;
         }
-        Commands::SelfTest { json } => {
+        Commands::SelfTest { json, quick } => {
             tracing::info!("Running hardware self-test");
-            let report = packetparamedic::selftest::run().await?;
+            let report = packetparamedic::selftest::run_with(quick).await?;
+            if let Ok(pool) = packetparamedic::storage::open_pool("data/packetparamedic.db") {
+                if let Err(e) = packetparamedic::selftest::save_report(&pool, &report, quick) {
+                    tracing::warn!("Failed to record self-test: {:#}", e);
+                }
+            }
+            let code = selftest_exit_code(&report);
             if json {
                 let json_output = serde_json::to_string_pretty(&report)?;
                 println!("{}", json_output);
@@ -221,15 +465,44 @@ async fn main() -> Result<()> {
                     };
                     println!("{:<25} : {}", persona, check);
                 }
+                if !report.recommendations.is_empty() {
+                    println!("\n=== Next Steps ===");
+                    for rec in &report.recommendations {
+                        println!("  - {}", rec);
+                    }
+                }
                 println!("(See BUYERS_GUIDE.md for details on requirements)");
                 println!();
             }
+            if code != exit_code::OK {
+                std::process::exit(code);
+            }
         }
         Commands::BlameCheck => {
             tracing::info!("Running blame check");
             // CLI immediate mode
-            let report = packetparamedic::probes::run_blame_check().await?;
+            let timeouts = packetparamedic::probes::ProbeTimeouts::from_env();
+            let retry = packetparamedic::probes::ProbeRetry::from_env()?;
+            // Baselines sharpen confidence; without the DB the check still runs.
+            let pool = packetparamedic::storage::open_pool("data/packetparamedic.db").ok();
+            let lan = packetparamedic::probes::lan::LanReflector::discover();
+            let report =
+                packetparamedic::probes::run_blame_check(
+                    &timeouts,
+                    &retry,
+                    pool.as_ref(),
+                    lan.as_ref(),
+                    &tokio_util::sync::CancellationToken::new(),
+                )
+                .await?;
+
+            if let Some(pool) = &pool {
+                if let Err(e) = packetparamedic::analysis::blame_history::save_blame_check(pool, &report) {
+                    tracing::warn!("Failed to record blame check: {:#}", e);
+                }
+            }
 
+            let code = blame_exit_code(report.fault);
             println!("\n=== PacketParamedic Diagnostic Report ===");
             println!("Verdict:    {}", report.verdict);
             println!("Confidence: {}%", report.confidence);
@@ -238,14 +511,70 @@ async fn main() -> Result<()> {
                 println!(" - {}", detail);
             }
             println!("=========================================\n");
+            if code != exit_code::OK {
+                std::process::exit(code);
+            }
         }
         Commands::SpeedTest {
             mode,
             provider,
             peer,
+            server,
+            list_servers,
+            compare,
             duration,
             streams,
+            window,
+            len,
+            omit,
+            protocol,
+            bandwidth,
+            lock_wait,
         } => {
+            if list_servers {
+                match provider.as_deref() {
+                    Some("ookla" | "ookla-cli") => {
+                        let servers =
+                            packetparamedic::throughput::provider::ookla::OoklaProvider::list_servers()
+                                .await?;
+                        for s in servers {
+                            println!("{:>6}  {} - {}, {} ({})", s.id, s.name, s.location, s.country, s.host);
+                        }
+                        return Ok(());
+                    }
+                    _ => anyhow::bail!("--list-servers is supported for --provider ookla"),
+                }
+            }
+            if protocol.is_some() && (compare || provider.is_some()) {
+                anyhow::bail!("--protocol only applies to iperf3 tests, not --provider or --compare");
+            }
+            // The reflector runs iperf3 and honours --omit; other providers have no warm-up to cut.
+            if omit.is_some() && (compare || provider.as_deref().is_some_and(|p| p != "reflector")) {
+                anyhow::bail!("--omit only applies to iperf3 tests and --provider reflector");
+            }
+            let _lock = speed_test_lock(lock_wait).await?;
+            if compare {
+                use packetparamedic::throughput::{compare, provider};
+                // Manual runs count against the daily budget too, so a
+                // comparison can't blow through a metered plan.
+                let pool = packetparamedic::storage::open_pool("data/packetparamedic.db").ok();
+                let budget = packetparamedic::scheduler::budget::DataBudget::from_env();
+                let registry = provider::get_all_providers();
+                let request = provider::SpeedTestRequest {
+                    timeout: provider::DEFAULT_TIMEOUT,
+                    prefer_ipv6: false,
+                    server_hint: None,
+                };
+                let results = compare::run_all(&registry, &request, pool.as_ref().map(|p| (p, &budget))).await;
+                if results.is_empty() {
+                    anyhow::bail!("No provider produced a result (see log for skipped/failed providers)");
+                }
+                for res in &results {
+                    record_speed_test(res);
+                }
+                print!("{}", compare::format_table(&results));
+                return Ok(());
+            }
             if let Some(prov_id) = provider {
                 tracing::info!(%prov_id, "Running provider speed test");
                 // Dispatch to provider framework
@@ -257,11 +586,15 @@ async fn main() -> Result<()> {
                         use packetparamedic::throughput::provider::SpeedTestProvider;
                         if p.is_available() {
                             let res = p.run(packetparamedic::throughput::provider::SpeedTestRequest {
-                                timeout: std::time::Duration::from_secs(30),
+                                timeout: packetparamedic::throughput::provider::DEFAULT_TIMEOUT,
                                 prefer_ipv6: false,
-                                server_hint: None,
+                                server_hint: server.clone(),
                             }).await?; // Added await
                             println!("{}", serde_json::to_string_pretty(&res)?);
+                            record_speed_test(&res);
+                            if res.truncated {
+                                eprintln!("Warning: provider timed out; only completed phases are reported.");
+                            }
                         } else {
                             anyhow::bail!("Ookla CLI not found. {}", p.meta().install_hint);
                         }
@@ -271,11 +604,15 @@ async fn main() -> Result<()> {
                          use packetparamedic::throughput::provider::SpeedTestProvider;
                          if p.is_available() {
                             let res = p.run(packetparamedic::throughput::provider::SpeedTestRequest {
-                                timeout: std::time::Duration::from_secs(30),
+                                timeout: packetparamedic::throughput::provider::DEFAULT_TIMEOUT,
                                 prefer_ipv6: false,
-                                server_hint: None,
+                                server_hint: server.clone(),
                             }).await?; // Added await
                             println!("{}", serde_json::to_string_pretty(&res)?);
+                            record_speed_test(&res);
+                            if res.truncated {
+                                eprintln!("Warning: provider timed out; only completed phases are reported.");
+                            }
                          } else {
                             anyhow::bail!("NDT7 Client not found. {}", p.meta().install_hint);
                          }
@@ -285,26 +622,75 @@ async fn main() -> Result<()> {
                          use packetparamedic::throughput::provider::SpeedTestProvider;
                          if p.is_available() {
                             let res = p.run(packetparamedic::throughput::provider::SpeedTestRequest {
-                                timeout: std::time::Duration::from_secs(30),
+                                timeout: packetparamedic::throughput::provider::DEFAULT_TIMEOUT,
                                 prefer_ipv6: false,
                                 server_hint: None,
                             }).await?; // Added await
                             println!("{}", serde_json::to_string_pretty(&res)?);
+                            record_speed_test(&res);
                          } else {
                             anyhow::bail!("Fast CLI not found. {}", p.meta().install_hint);
                          }
                     },
+                    "web" => {
+                        let p = packetparamedic::throughput::provider::web::WebProvider;
+                        use packetparamedic::throughput::provider::SpeedTestProvider;
+                        if p.is_available() {
+                            let res = p.run(packetparamedic::throughput::provider::SpeedTestRequest {
+                                timeout: packetparamedic::throughput::provider::DEFAULT_TIMEOUT,
+                                prefer_ipv6: false,
+                                server_hint: None,
+                            }).await?;
+                            println!("{}", serde_json::to_string_pretty(&res)?);
+                            record_speed_test(&res);
+                        } else {
+                            anyhow::bail!("No headless browser found. {}", p.meta().install_hint);
+                        }
+                    },
                     "reflector" => {
                         let p = packetparamedic::throughput::provider::reflector::ReflectorProvider;
                         use packetparamedic::throughput::provider::SpeedTestProvider;
                         if p.is_available() {
                            // Use --peer as server_hint for Reflector
-                           let res = p.run(packetparamedic::throughput::provider::SpeedTestRequest {
+                           let (res, sessions) = p.run_sessions(packetparamedic::throughput::provider::SpeedTestRequest {
                                timeout: std::time::Duration::from_secs(30), // Should parse duration arg if possible, but struct hardcoded here
                                prefer_ipv6: false,
                                server_hint: peer.clone(),
-                           }).await?;
+                           }, omit.unwrap_or(0)).await?;
                            println!("{}", serde_json::to_string_pretty(&res)?);
+
+                           // Storage is best-effort, as for every other speed test.
+                           record_speed_test(&res);
+                           // Keep both sides of each session for discrepancy analysis.
+                           let pool = packetparamedic::storage::open_pool("data/packetparamedic.db")
+                               .map_err(|e| tracing::warn!("Failed to record reflector sessions: {:#}", e))
+                               .ok();
+                           let addr = peer.as_deref().unwrap_or_default();
+                           for s in &sessions {
+                               if let Some(Err(e)) = pool.as_ref().map(|pool| s.save(pool, addr)) {
+                                   tracing::warn!("Failed to record reflector session: {:#}", e);
+                               }
+                               if let (Some(r), Some(d)) = (s.reflector_mbps(), s.discrepancy_pct()) {
+                                   println!(
+                                       "{}: client {:.1} Mbps, reflector {:.1} Mbps ({:+.1}% not seen by client)",
+                                       s.direction, s.client_mbps, r, d
+                                   );
+                               }
+                               if let Some(balance) = s.stream_balance() {
+                                   let streams = &s.reflector.as_ref().unwrap().streams_mbps;
+                                   let rates: Vec<String> = streams.iter().map(|m| format!("{:.0}", m)).collect();
+                                   println!(
+                                       "  streams: {} Mbps, balance {:.2}{}",
+                                       rates.join(" / "),
+                                       balance,
+                                       if balance < packetparamedic::throughput::report::UNBALANCED_STREAMS {
+                                           " -- uneven streams"
+                                       } else {
+                                           ""
+                                       }
+                                   );
+                               }
+                           }
                         } else {
                            anyhow::bail!("iperf3 not found (required for reflector).");
                         }
@@ -312,9 +698,51 @@ async fn main() -> Result<()> {
                     _ => anyhow::bail!("Unknown provider: {}", prov_id),
                 }
             } else {
-                tracing::info!(%mode, ?peer, %duration, %streams, "Running iperf3 speed test");
-                packetparamedic::throughput::run_test(&mode, peer.as_deref(), &duration, streams)
-                    .await?;
+                let protocol: packetparamedic::throughput::iperf::Protocol =
+                    protocol.as_deref().unwrap_or("tcp").parse()?;
+                tracing::info!(%mode, ?peer, %duration, %streams, %protocol, "Running iperf3 speed test");
+                let tuning = packetparamedic::throughput::iperf::Tuning {
+                    window,
+                    len,
+                    omit_secs: omit,
+                    bandwidth,
+                };
+                use packetparamedic::system::thermal;
+                // Thermal samples around the run flag results skewed by a throttled SoC.
+                let pool = packetparamedic::storage::open_pool("data/packetparamedic.db").ok();
+                let started = chrono::Utc::now();
+                if let Some(pool) = &pool {
+                    thermal::sample_logged(pool).await;
+                }
+                let results = packetparamedic::throughput::run_test_tuned(
+                    pool.as_ref(),
+                    &mode,
+                    peer.as_deref(),
+                    &duration,
+                    streams,
+                    protocol,
+                    tuning,
+                )
+                .await?;
+                if let Some(pool) = &pool {
+                    thermal::sample_logged(pool).await;
+                    use packetparamedic::throughput::provider::SpeedTestResult;
+                    let window = thermal::during(pool, started, chrono::Utc::now()).ok();
+                    if let Some(note) = window.as_ref().and_then(|w| w.annotation()) {
+                        eprintln!("Warning: {}; the result may reflect the Pi, not the network.", note);
+                    }
+                    if let Some(mut result) = SpeedTestResult::from_throughput(results) {
+                        if let Some(window) = &window {
+                            result = result.with_thermal(window);
+                        }
+                        if let Err(e) = result.save(pool) {
+                            tracing::warn!("Failed to record speed test: {:#}", e);
+                        }
+                    }
+                    if let Some(window) = window {
+                        packetparamedic::system::power::record_undervoltage_logged(pool, format!("speed:{}", mode), window).await;
+                    }
+                }
             }
         }
         Commands::Trace { target } => {
@@ -330,6 +758,12 @@ async fn main() -> Result<()> {
                     println!("\n--- Bufferbloat Grade: {} ---", result.grade);
                     println!("Baseline RTT: {:.2} ms", result.baseline_rtt_ms);
                     println!("Loaded RTT:   {:.2} ms (+{:.2} ms)", result.loaded_rtt_ms, result.bufferbloat_ms);
+                    // Stored with its load phase so the anomaly scan can tell
+                    // a saturated link from a slow ISP.
+                    let saved = packetparamedic::storage::open_pool("data/packetparamedic.db").and_then(|pool| result.save(&pool));
+                    if let Err(e) = saved {
+                        tracing::warn!("Failed to record bufferbloat result: {:#}", e);
+                    }
                     
                     if result.grade == 'D' || result.grade == 'F' {
                         println!("⚠️  High Bufferbloat detected! Your router may need AQM/SQM enabled.");
@@ -349,6 +783,78 @@ async fn main() -> Result<()> {
                          println!("No data available for last 24h.");
                      }
                 }
+                DiagnosticCommand::BlameHistory { hours, json } => {
+                    use packetparamedic::analysis::blame_history;
+                    let pool = packetparamedic::storage::open_pool("data/packetparamedic.db")?;
+                    let records = blame_history::load_history(&pool, hours)?;
+                    let summary = blame_history::summarize(&records);
+                    let changes = blame_history::verdict_changes(&records);
+
+                    if json {
+                        println!(
+                            "{}",
+                            serde_json::to_string_pretty(&serde_json::json!({
+                                "hours": hours,
+                                "runs": records.len(),
+                                "verdicts": summary,
+                                "changes": changes,
+                            }))?
+                        );
+                    } else if records.is_empty() {
+                        println!("No blame checks recorded in the last {}h.", hours);
+                    } else {
+                        let local = |t: &chrono::DateTime<chrono::Utc>| {
+                            t.with_timezone(&chrono::Local).format("%m-%d %H:%M").to_string()
+                        };
+                        println!("--- Blame History (last {}h, {} runs) ---", hours, records.len());
+                        for s in &summary {
+                            println!(
+                                "{}: {} time{} between {} and {} (avg confidence {:.0}%)",
+                                s.verdict,
+                                s.count,
+                                if s.count == 1 { "" } else { "s" },
+                                local(&s.first),
+                                local(&s.last),
+                                s.avg_confidence
+                            );
+                        }
+                        if !changes.is_empty() {
+                            println!("\nVerdict changes:");
+                            for c in &changes {
+                                println!("  {}  {} -> {}", local(&c.at), c.from, c.to);
+                            }
+                        }
+                    }
+                }
+                DiagnosticCommand::AccelBench { sizes, iterations, json } => {
+                    let manager = packetparamedic::accel::AccelerationManager::new();
+                    let report = packetparamedic::accel::bench::run(&manager, &sizes, iterations);
+
+                    if json {
+                        println!("{}", serde_json::to_string_pretty(&report)?);
+                    } else {
+                        println!("--- Acceleration Benchmark ({} iterations) ---", report.iterations);
+                        println!("Backends: {:?}", report.backends);
+                        println!(
+                            "{:<10} | {:<7} | {:>9} | {:>10} | {:>10} | {:>10} | {:>10}",
+                            "Op", "Backend", "Samples", "Min (us)", "Mean (us)", "Max (us)", "MSamples/s"
+                        );
+                        println!("{:-<10}-|-{:-<7}-|-{:-<9}-|-{:-<10}-|-{:-<10}-|-{:-<10}-|-{:-<10}", "", "", "", "", "", "", "");
+                        for e in &report.entries {
+                            let backend = format!("{:?}", e.backend);
+                            match &e.error {
+                                Some(err) => println!(
+                                    "{:<10} | {:<7} | {:>9} | unavailable: {}",
+                                    e.op, backend, e.samples, err
+                                ),
+                                None => println!(
+                                    "{:<10} | {:<7} | {:>9} | {:>10} | {:>10} | {:>10} | {:>10.1}",
+                                    e.op, backend, e.samples, e.min_us, e.mean_us, e.max_us, e.msamples_per_sec
+                                ),
+                            }
+                        }
+                    }
+                }
             }
         }
         Commands::Schedule { action } => {
@@ -363,28 +869,56 @@ async fn main() -> Result<()> {
                     } else {
                         println!("{:<20} | {:<15} | {:<10} | Enabled", "Name", "Cron", "Test");
                         println!("{:-<20}-|-{:-<15}-|-{:-<10}-|-{:-<7}", "", "", "", "");
-                        for (name, cron, test, enabled) in list {
-                            println!("{:<20} | {:<15} | {:<10} | {}", name, cron, test, enabled);
+                        for (name, cron, test, enabled, params) in list {
+                            match params {
+                                Some(p) => println!("{:<20} | {:<15} | {:<10} | {} {}", name, cron, test, enabled, p),
+                                None => println!("{:<20} | {:<15} | {:<10} | {}", name, cron, test, enabled),
+                            }
                         }
                     }
                 }
-                ScheduleAction::Add { name, cron, test } => {
-                    scheduler.add_schedule(&name, &cron, &test).await?;
-                    println!("Schedule '{}' added.", name);
+                ScheduleAction::Add { name, cron, test, params, param } => {
+                    use anyhow::Context;
+                    let params = params
+                        .map(|p| serde_json::from_str::<serde_json::Value>(&p))
+                        .transpose()
+                        .context("--params must be a JSON object")?;
+                    let params = packetparamedic::scheduler::params::merge_pairs(params, &param)?;
+                    use packetparamedic::scheduler::cron as sched_cron;
+                    // Fails fast, naming the offending field.
+                    let description = sched_cron::describe(&cron)?;
+                    scheduler.add_schedule_with_params(&name, &cron, &test, params.as_ref()).await?;
+                    println!("Schedule '{}' added: {} (UTC).", name, description);
+                    println!("Next runs:");
+                    for t in sched_cron::next_runs(&cron, chrono::Utc::now(), 3)? {
+                        println!("  {}", t.to_rfc3339());
+                    }
                 }
                 ScheduleAction::Remove { name } => {
                     scheduler.remove(&name).await?;
                     println!("Schedule '{}' removed.", name);
                 }
                 ScheduleAction::DryRun { hours } => {
-                    let preview = scheduler.preview_next_runs(hours).await?;
+                    use packetparamedic::scheduler::budget::format_bytes;
+                    let (preview, usage) = scheduler.preview_data_usage(hours).await?;
                     if preview.is_empty() {
                         println!("No runs scheduled in next {} hours.", hours);
                     } else {
                         println!("Upcoming runs (next {} hours):", hours);
-                        for (time, name, test) in preview {
-                            println!("{} : {} ({})", time, name, test);
+                        for (time, name, test, bytes) in preview {
+                            if bytes > 0 {
+                                println!("{} : {} ({}) ~{}", time, name, test, format_bytes(bytes));
+                            } else {
+                                println!("{} : {} ({})", time, name, test);
+                            }
                         }
+                        println!();
+                        println!(
+                            "Estimated data usage: ~{} in this window, ~{}/day, ~{}/month",
+                            format_bytes(usage.window_bytes),
+                            format_bytes(usage.daily_bytes),
+                            format_bytes(usage.monthly_bytes)
+                        );
                     }
                 }
                 ScheduleAction::ApplyProfile { profile, force } => {
@@ -396,21 +930,7 @@ async fn main() -> Result<()> {
                              return Ok(());
                          }
                          
-                         // Delete all
-                         let list = scheduler.list().await?;
-                         for (n, _, _, _) in list {
-                             scheduler.remove(&n).await?;
-                         }
-                         
-                         // Apply profile
-                         let scheds = packetparamedic::scheduler::profiles::get_profile_schedules(p);
-                         for s in scheds {
-                             scheduler.add_schedule(&s.name, &s.cron_expr, &s.test_type).await?;
-                             if !s.enabled {
-                                 // toggle disable if logic existed, but add_schedule default enables.
-                                 // For now we assume enabled.
-                             }
-                         }
+                         scheduler.apply_profile(p).await?;
                          println!("Profile '{}' applied successfully.", profile);
                     } else {
                         anyhow::bail!("Unknown profile: {}. Options: minimal, standard, aggressive", profile);
@@ -418,37 +938,125 @@ async fn main() -> Result<()> {
                 }
             }
         }
-        Commands::ExportBundle { output } => {
-            tracing::info!(%output, "Exporting support bundle");
-            packetparamedic::evidence::export_bundle(&output).await?;
+        Commands::ExportBundle { output, compression, redact, skip_self_test } => {
+            let compression: packetparamedic::evidence::Compression = compression.parse()?;
+            let redaction: packetparamedic::evidence::RedactionLevel = redact.parse()?;
+            let output = output.unwrap_or_else(|| format!("bundle.{}", compression.extension()));
+            let pool = packetparamedic::storage::open_pool("data/packetparamedic.db")?;
+            let self_test = if skip_self_test {
+                None
+            } else {
+                Some(packetparamedic::selftest::run().await?)
+            };
+            tracing::info!(%output, %compression, %redaction, "Exporting support bundle");
+            packetparamedic::evidence::export_bundle(&pool, &output, compression, redaction, self_test.as_ref())
+                .await?;
         }
-        Commands::PairReflector { host, token } => {
+        Commands::Import { format, probe, target, file, dry_run } => {
             use anyhow::Context;
-            let addr: std::net::SocketAddr = host.parse()
-                .with_context(|| format!("invalid reflector address: {}", host))?;
-            
-            let home = std::env::var("HOME").unwrap_or_else(|_| ".".into());
-            let pathbuf = std::path::Path::new(&home).join(".packetparamedic");
-            let identity_path = pathbuf.join("identity.key");
+            use packetparamedic::probes::ProbeType;
+            use packetparamedic::storage::import::{self, ImportFormat};
 
-            if !pathbuf.exists() {
-                tokio::fs::create_dir_all(&pathbuf).await?;
+            let format: ImportFormat = format.parse()?;
+            let probe: ProbeType = probe.parse()?;
+            let input = std::fs::File::open(&file).with_context(|| format!("failed to open {}", file))?;
+            let parsed = import::parse(std::io::BufReader::new(input), format, &probe, &target)?;
+
+            const MAX_REPORTED: usize = 20;
+            for row in parsed.skipped.iter().take(MAX_REPORTED) {
+                println!("  skipped line {}: {}", row.line, row.reason);
             }
-            
-            let identity = packetparamedic::reflector_proto::identity::Identity::load_or_generate(&identity_path)?;
-            
-            println!("Paramedic Identity: {}", identity.endpoint_id());
-            println!("Connecting to {}...", addr);
-            
-            let mut client = packetparamedic::reflector_proto::client::ReflectorClient::connect(addr, &identity).await?;
-            let resp = client.pair(token).await?;
-            
-            if resp.success {
-                println!("✅ Successfully paired with Reflector!");
-                println!("Reflector ID: {}", resp.endpoint_id.unwrap_or_default());
+            if parsed.skipped.len() > MAX_REPORTED {
+                println!("  ... and {} more", parsed.skipped.len() - MAX_REPORTED);
+            }
+            if parsed.measurements.is_empty() {
+                anyhow::bail!("no valid samples in {} ({} row(s) skipped)", file, parsed.skipped.len());
+            }
+
+            if dry_run {
+                println!(
+                    "Would import {} {} sample(s) for {} ({} skipped).",
+                    parsed.measurements.len(), probe, target, parsed.skipped.len()
+                );
             } else {
-                println!("❌ Pairing failed: {}", resp.message);
+                let pool = packetparamedic::storage::open_pool("data/packetparamedic.db")?;
+                let written = packetparamedic::storage::save_measurements(&pool, &parsed.measurements)?;
+                println!(
+                    "Imported {} {} sample(s) for {} ({} skipped, {} already stored).",
+                    written, probe, target, parsed.skipped.len(), parsed.measurements.len() - written
+                );
+            }
+        }
+        Commands::PairReflector { host, token, generate, ttl, name, endpoint_id, yes } => {
+            let expected = endpoint_id.as_deref();
+            match token {
+                Some(token) if !generate => pair_reflector(&host, token, name, expected, yes).await?,
+                _ => pair_reflector_generated(&host, std::time::Duration::from_secs(ttl), name, expected, yes).await?,
+            };
+        }
+        Commands::ReflectorFleet { action } => match action {
+            FleetAction::Status { json } => {
+                use packetparamedic::reflector_proto::fleet::{status_all, FleetState};
+                use packetparamedic::reflector_proto::peers::PeerStore;
+
+                let (dir, identity) = load_paramedic_identity().await?;
+                let store = PeerStore::load(&dir.join(packetparamedic::reflector_proto::peers::PEER_STORE_FILE))?;
+                if store.is_empty() {
+                    println!("No paired reflectors. Use 'pair-reflector' first.");
+                    return Ok(());
+                }
+
+                let results = status_all(&store, &identity).await;
+                if json {
+                    let out: Vec<serde_json::Value> = results
+                        .iter()
+                        .map(|(name, r)| match r {
+                            Ok(s) => serde_json::json!({ "nickname": name, "state": FleetState::of(r), "status": s }),
+                            Err(e) => serde_json::json!({ "nickname": name, "state": FleetState::of(r), "error": format!("{:#}", e) }),
+                        })
+                        .collect();
+                    println!("{}", serde_json::to_string_pretty(&out)?);
+                } else {
+                    println!("{:<20} | {:<5} | {:<10} | {:<12} | Details", "Reflector", "State", "Tests", "Bytes today");
+                    println!("{:-<20}-|-{:-<5}-|-{:-<10}-|-{:-<12}-|-{:-<30}", "", "", "", "", "");
+                    for (name, r) in &results {
+                        let state = match FleetState::of(r) {
+                            FleetState::Up => "UP",
+                            FleetState::Busy => "BUSY",
+                            FleetState::Down => "DOWN",
+                        };
+                        match r {
+                            Ok(s) => println!(
+                                "{:<20} | {:<5} | {:<10} | {:<12} | up {}s, {}",
+                                name, state, s.tests_today, s.bytes_today, s.uptime_sec, s.endpoint_id
+                            ),
+                            Err(e) => println!("{:<20} | {:<5} | {:<10} | {:<12} | {:#}", name, state, "-", "-", e),
+                        }
+                    }
+                }
             }
+        },
+        Commands::Init {
+            yes,
+            profile,
+            skip_self_test,
+            reflector,
+            token,
+            budget_gb,
+            config,
+            force,
+        } => {
+            run_init(InitOptions {
+                yes,
+                profile,
+                skip_self_test,
+                reflector,
+                token,
+                budget_gb,
+                config,
+                force,
+            })
+            .await?;
         }
         Commands::WifiStatus { json } => {
             tracing::info!("Scanning Wi-Fi status");
@@ -476,3 +1084,371 @@ async fn main() -> Result<()> {
     }
     Ok(())
 }
+
+/// Paramedic data directory (`~/.packetparamedic`) and its identity,
+/// creating both on first use.
+async fn load_paramedic_identity() -> Result<(
+    std::path::PathBuf,
+    packetparamedic::reflector_proto::identity::Identity,
+)> {
+    let home = std::env::var("HOME").unwrap_or_else(|_| ".".into());
+    let pathbuf = std::path::Path::new(&home).join(".packetparamedic");
+    let identity_path = pathbuf.join("identity.key");
+
+    if !pathbuf.exists() {
+        tokio::fs::create_dir_all(&pathbuf).await?;
+    }
+
+    let identity = packetparamedic::reflector_proto::identity::Identity::load_or_generate(&identity_path)?;
+    Ok((pathbuf, identity))
+}
+
+/// Pair this device with a reflector using a one-time token, and record it
+/// in the local reflector store on success.
+///
+/// The token is only sent once the reflector's endpoint ID has been checked
+/// against `expected`, or confirmed by the operator (skipped with `assume_yes`).
+async fn pair_reflector(
+    host: &str,
+    token: String,
+    name: Option<String>,
+    expected: Option<&str>,
+    assume_yes: bool,
+) -> Result<bool> {
+    use anyhow::Context;
+
+    let addr: std::net::SocketAddr = host.parse()
+        .with_context(|| format!("invalid reflector address: {}", host))?;
+    if !packetparamedic::reflector_proto::pairing::is_valid_code(token.trim()) {
+        anyhow::bail!(
+            "invalid pairing token '{}': expected {} characters as shown by `reflector pair`",
+            token.trim(),
+            packetparamedic::reflector_proto::pairing::CODE_LEN
+        );
+    }
+
+    let (pathbuf, identity) = load_paramedic_identity().await?;
+
+    println!("Paramedic Identity: {}", identity.endpoint_id());
+    println!("Connecting to {}...", addr);
+
+    let mut client = packetparamedic::reflector_proto::client::ReflectorClient::connect(addr, &identity).await?;
+    if !verify_reflector_identity(client.server_endpoint_id(), expected, assume_yes)? {
+        println!("Pairing cancelled.");
+        return Ok(false);
+    }
+    let resp = client.pair(token.trim().to_string()).await?;
+
+    if resp.success {
+        remember_reflector(&pathbuf, host, name, resp)?;
+        Ok(true)
+    } else {
+        println!("❌ Pairing failed: {}", resp.message);
+        Ok(false)
+    }
+}
+
+/// Reverse pairing: generate the code here, show the command that makes the
+/// reflector adopt it, and keep presenting it until the reflector accepts it
+/// or it expires. This side is authoritative for the code's expiry.
+async fn pair_reflector_generated(
+    host: &str,
+    ttl: std::time::Duration,
+    name: Option<String>,
+    expected: Option<&str>,
+    assume_yes: bool,
+) -> Result<bool> {
+    use anyhow::Context;
+    use packetparamedic::reflector_proto::client::ReflectorClient;
+    use packetparamedic::reflector_proto::pairing::{PendingCode, RETRY_INTERVAL};
+    use std::io::Write;
+
+    let addr: std::net::SocketAddr = host.parse()
+        .with_context(|| format!("invalid reflector address: {}", host))?;
+
+    let (pathbuf, identity) = load_paramedic_identity().await?;
+    let pending = PendingCode::generate(ttl);
+
+    println!("Paramedic Identity: {}", identity.endpoint_id());
+    println!("Pairing Code      : {}", pending.code);
+    println!("Expires At        : {}", pending.expires_at.to_rfc3339());
+    println!();
+    println!("On the reflector, run the command below (its TTL counts down with this one).");
+    println!("Press Ctrl-C to cancel.");
+    println!();
+
+    // The reflector's identity is checked on the first successful connect;
+    // later attempts must present the same ID.
+    let mut verified: Option<Option<String>> = None;
+    loop {
+        if pending.is_expired() {
+            println!();
+            println!("❌ Pairing code expired before the reflector accepted it.");
+            return Ok(false);
+        }
+        print!("\r  {}   ", pending.reflector_command());
+        std::io::stdout().flush()?;
+
+        // Until the operator has entered the code the reflector refuses
+        // unknown peers or rejects the code; both just mean "not yet".
+        let mut client = match tokio::time::timeout(pending.remaining(), ReflectorClient::connect(addr, &identity)).await {
+            Ok(Ok(client)) => client,
+            Ok(Err(e)) => {
+                tracing::debug!("Pairing attempt failed: {:#}", e);
+                tokio::time::sleep(RETRY_INTERVAL.min(pending.remaining())).await;
+                continue;
+            }
+            Err(_) => continue,
+        };
+        let presented = client.server_endpoint_id().map(str::to_string);
+        match &verified {
+            None => {
+                println!();
+                if !verify_reflector_identity(presented.as_deref(), expected, assume_yes)? {
+                    println!("Pairing cancelled.");
+                    return Ok(false);
+                }
+                verified = Some(presented);
+            }
+            Some(id) if *id != presented => {
+                println!();
+                anyhow::bail!(
+                    "reflector at {} changed endpoint ID during pairing ({} -> {})",
+                    host,
+                    id.as_deref().unwrap_or("none"),
+                    presented.as_deref().unwrap_or("none")
+                );
+            }
+            Some(_) => {}
+        }
+
+        match tokio::time::timeout(pending.remaining(), client.pair(pending.code.clone())).await {
+            Ok(Ok(resp)) if resp.success => {
+                println!();
+                remember_reflector(&pathbuf, host, name, resp)?;
+                return Ok(true);
+            }
+            Ok(Ok(resp)) => tracing::debug!(message = %resp.message, "Reflector has not adopted the code yet"),
+            Ok(Err(e)) => tracing::debug!("Pairing attempt failed: {:#}", e),
+            Err(_) => continue,
+        }
+        tokio::time::sleep(RETRY_INTERVAL.min(pending.remaining())).await;
+    }
+}
+
+/// Show the reflector's endpoint ID and decide whether to pair with it: an
+/// `expected` ID must match exactly (mismatches abort), otherwise the operator
+/// is asked unless `assume_yes` is set.
+fn verify_reflector_identity(presented: Option<&str>, expected: Option<&str>, assume_yes: bool) -> Result<bool> {
+    use packetparamedic::reflector_proto::identity::short_fingerprint;
+
+    match presented {
+        Some(id) => println!("Reflector ID      : {} (fingerprint {})", id, short_fingerprint(id)),
+        None => println!("Reflector ID      : (not presented)"),
+    }
+    if let Some(expected) = expected {
+        packetparamedic::reflector_proto::pairing::check_endpoint_id(expected, presented)?;
+        println!("Reflector ID matches the expected endpoint ID.");
+        return Ok(true);
+    }
+    if assume_yes {
+        return Ok(true);
+    }
+    println!("Compare the fingerprint with the one `reflector status` shows on the reflector.");
+    confirm("Pair with this reflector?", false)
+}
+
+/// Report a successful pairing and record the reflector in the local store.
+fn remember_reflector(
+    dir: &std::path::Path,
+    host: &str,
+    name: Option<String>,
+    resp: packetparamedic::reflector_proto::rpc::PairResponse,
+) -> Result<()> {
+    use packetparamedic::reflector_proto::peers::{PeerStore, StoredReflector, PEER_STORE_FILE};
+
+    println!("✅ Successfully paired with Reflector!");
+    println!("Reflector ID: {}", resp.endpoint_id.clone().unwrap_or_default());
+
+    let mut store = PeerStore::load(&dir.join(PEER_STORE_FILE))?;
+    store.upsert(StoredReflector {
+        nickname: name.unwrap_or_else(|| host.to_string()),
+        address: host.to_string(),
+        endpoint_id: resp.endpoint_id,
+        paired_at: chrono::Utc::now().to_rfc3339(),
+    });
+    store.save()?;
+    Ok(())
+}
+
+struct InitOptions {
+    yes: bool,
+    profile: Option<String>,
+    skip_self_test: bool,
+    reflector: Option<String>,
+    token: Option<String>,
+    budget_gb: Option<f64>,
+    config: String,
+    force: bool,
+}
+
+/// Take the cross-process speed-test lock, queueing behind a test already in
+/// progress for up to `wait_secs`. The lock is advisory: if the database
+/// can't be opened the test runs without it.
+async fn speed_test_lock(
+    wait_secs: u64,
+) -> Result<Option<packetparamedic::scheduler::speed_lock::SpeedTestLock>> {
+    use packetparamedic::scheduler::speed_lock::SpeedTestLock;
+
+    let pool = match packetparamedic::storage::open_pool("data/packetparamedic.db") {
+        Ok(pool) => pool,
+        Err(e) => {
+            tracing::warn!("Speed-test lock unavailable, running anyway: {:#}", e);
+            return Ok(None);
+        }
+    };
+    let wait = std::time::Duration::from_secs(wait_secs);
+    let acquired = SpeedTestLock::acquire_waiting(&pool, "cli", wait, |holder| {
+        eprintln!(
+            "Another speed test is in progress: {}. Waiting up to {}s for it to finish...",
+            holder, wait_secs
+        );
+    })
+    .await;
+    match acquired {
+        Ok(Ok(lock)) => Ok(Some(lock)),
+        Ok(Err(holder)) => anyhow::bail!(
+            "Another speed test is still running: {}. Two tests at once split the link and \
+             both under-report; retry later or raise --lock-wait.",
+            holder
+        ),
+        Err(e) => {
+            tracing::warn!("Speed-test lock unavailable, running anyway: {:#}", e);
+            Ok(None)
+        }
+    }
+}
+
+/// Ask a question on stdin; an empty answer returns `default`.
+fn prompt(question: &str, default: &str) -> Result<String> {
+    use std::io::Write;
+    if default.is_empty() {
+        print!("{}: ", question);
+    } else {
+        print!("{} [{}]: ", question, default);
+    }
+    std::io::stdout().flush()?;
+    let mut line = String::new();
+    std::io::stdin().read_line(&mut line)?;
+    let answer = line.trim();
+    Ok(if answer.is_empty() { default.to_string() } else { answer.to_string() })
+}
+
+fn confirm(question: &str, default: bool) -> Result<bool> {
+    let answer = prompt(question, if default { "Y/n" } else { "y/N" })?;
+    Ok(match answer.to_lowercase().as_str() {
+        "y" | "yes" => true,
+        "n" | "no" => false,
+        _ => default,
+    })
+}
+
+/// First-run wizard. With `--yes`, every question takes its default and
+/// pairing only happens when `--reflector`/`--token` are given.
+async fn run_init(opts: InitOptions) -> Result<()> {
+    use packetparamedic::onboarding::{suggest_profile, StarterConfig};
+    use packetparamedic::scheduler::profiles::Profile;
+
+    println!("\n=== PacketParamedic first-run setup ===\n");
+
+    // 1. Self-test
+    let mut suggested = Profile::Standard;
+    if opts.skip_self_test {
+        println!("[1/4] Self-test skipped.");
+    } else {
+        println!("[1/4] Running hardware self-test...");
+        let report = packetparamedic::selftest::run().await?;
+        let failed = report
+            .results
+            .iter()
+            .filter(|r| r.status == packetparamedic::selftest::TestStatus::Fail)
+            .count();
+        println!("      {} check(s), {} failed.", report.results.len(), failed);
+        for (persona, ready) in &report.compatibility {
+            println!("      {:<25} : {}", persona, if *ready { "ready" } else { "not ready" });
+        }
+        suggested = suggest_profile(&report.compatibility);
+    }
+
+    // 2. Schedule profile
+    let profile = match &opts.profile {
+        Some(name) => Profile::from_str(name).ok_or_else(|| {
+            anyhow::anyhow!("Unknown profile: {}. Options: minimal, standard, aggressive", name)
+        })?,
+        None if opts.yes => suggested,
+        None => {
+            let name = prompt("[2/4] Schedule profile (minimal, standard, aggressive)", suggested.name())?;
+            Profile::from_str(&name)
+                .ok_or_else(|| anyhow::anyhow!("Unknown profile: {}", name))?
+        }
+    };
+    if opts.yes || opts.profile.is_some() || confirm(&format!("      Replace existing schedules with '{}'?", profile.name()), true)? {
+        let pool = packetparamedic::storage::open_pool("data/packetparamedic.db")?;
+        let scheduler = packetparamedic::scheduler::Scheduler::new(pool);
+        scheduler.apply_profile(profile).await?;
+        println!("[2/4] Applied '{}' schedule profile.", profile.name());
+    } else {
+        println!("[2/4] Kept existing schedules.");
+    }
+
+    // 3. Reflector pairing
+    let mut paired = None;
+    let pairing = match (opts.reflector, opts.token) {
+        (Some(host), Some(token)) => Some((host, token)),
+        _ if opts.yes => None,
+        _ => {
+            if confirm("[3/4] Pair with a Paramedic Reflector now?", false)? {
+                let host = prompt("      Reflector address (host:port)", "")?;
+                let token = prompt("      Pairing token", "")?;
+                Some((host, token))
+            } else {
+                None
+            }
+        }
+    };
+    match pairing {
+        // A failed pairing shouldn't cost the rest of the setup; it can be
+        // retried on its own later.
+        Some((host, token)) => match pair_reflector(&host, token, None, None, opts.yes).await {
+            Ok(true) => paired = Some(host),
+            Ok(false) => {}
+            Err(e) => {
+                println!("[3/4] Pairing with {} failed: {:#}", host, e);
+                println!("      Continuing without a reflector; retry with: packetparamedic pair-reflector --host {}", host);
+            }
+        },
+        None => println!("[3/4] Reflector pairing skipped."),
+    }
+
+    // 4. Starter config
+    let budget_gb = match opts.budget_gb {
+        Some(gb) => Some(gb),
+        None if opts.yes => None,
+        None => {
+            let answer = prompt("[4/4] Daily data budget for speed tests in GB (blank = unlimited)", "")?;
+            if answer.is_empty() { None } else { Some(answer.parse()?) }
+        }
+    };
+    let starter = StarterConfig {
+        profile,
+        reflector: paired,
+        daily_budget_gb: budget_gb,
+        ..Default::default()
+    };
+    let path = std::path::Path::new(&opts.config);
+    starter.write(path, opts.force)?;
+    println!("[4/4] Wrote starter config to {}", path.display());
+
+    println!("\nDone. Start the daemon with: packetparamedic serve");
+    Ok(())
+}
@@ -0,0 +1,140 @@
+//! First-run onboarding: profile suggestion and starter configuration.
+//!
+//! The interactive flow lives in the `init` CLI command; this module holds
+//! the decisions it makes so they can be tested and scripted.
+
+use crate::scheduler::profiles::Profile;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Default location of the starter environment file.
+pub const DEFAULT_CONFIG_PATH: &str = "config/packetparamedic.env";
+
+/// Pick a schedule profile from the self-test persona readiness map.
+///
+/// Hardware ready for high-performance testing gets the aggressive profile,
+/// a reliable always-on box gets the standard profile, and anything else
+/// starts minimal so a marginal device is not overloaded.
+pub fn suggest_profile(compatibility: &HashMap<String, bool>) -> Profile {
+    let ready = |persona: &str| compatibility.get(persona).copied().unwrap_or(false);
+
+    if ready("High Performance") && ready("Reliability & Uptime") {
+        Profile::Aggressive
+    } else if ready("Reliability & Uptime") || ready("Simple Troubleshooting") {
+        Profile::Standard
+    } else {
+        Profile::Minimal
+    }
+}
+
+/// Settings captured by the first-run wizard, written as a systemd-style
+/// environment file (`EnvironmentFile=`) of `PP_*` variables.
+#[derive(Debug, Clone)]
+pub struct StarterConfig {
+    pub bind_addr: String,
+    pub profile: Profile,
+    /// Paired reflector address, if any.
+    pub reflector: Option<String>,
+    /// Daily data budget for scheduled throughput tests (GB).
+    pub daily_budget_gb: Option<f64>,
+}
+
+impl Default for StarterConfig {
+    fn default() -> Self {
+        Self {
+            bind_addr: "0.0.0.0:8080".to_string(),
+            profile: Profile::Standard,
+            reflector: None,
+            daily_budget_gb: None,
+        }
+    }
+}
+
+impl StarterConfig {
+    /// Render the environment file contents.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        out.push_str("# PacketParamedic starter configuration (written by `packetparamedic init`).\n");
+        out.push_str("# Load with systemd `EnvironmentFile=` or `set -a; . ./packetparamedic.env`.\n\n");
+        out.push_str(&format!("PP_BIND_ADDR={}\n", self.bind_addr));
+        out.push_str(&format!("# Schedule profile applied at init: {}\n", self.profile.name()));
+
+        match self.daily_budget_gb {
+            Some(gb) => out.push_str(&format!("PP_DAILY_BW_BUDGET_GB={}\n", gb)),
+            None => out.push_str("# PP_DAILY_BW_BUDGET_GB=10\n"),
+        }
+        match &self.reflector {
+            Some(addr) => out.push_str(&format!("# Paired reflector: {}\n", addr)),
+            None => out.push_str("# No reflector paired; run `packetparamedic pair-reflector` later.\n"),
+        }
+
+        out.push_str("\n# Uncomment to require a bearer token on the API:\n");
+        out.push_str("# PP_API_TOKEN=\n");
+        out
+    }
+
+    /// Write the environment file, refusing to overwrite unless `force`.
+    pub fn write(&self, path: &Path, force: bool) -> Result<()> {
+        if path.exists() && !force {
+            anyhow::bail!(
+                "{} already exists (pass --force to overwrite)",
+                path.display()
+            );
+        }
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+        std::fs::write(path, self.render())
+            .with_context(|| format!("failed to write {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compat(pairs: &[(&str, bool)]) -> HashMap<String, bool> {
+        pairs.iter().map(|(k, v)| (k.to_string(), *v)).collect()
+    }
+
+    #[test]
+    fn test_suggest_profile() {
+        let all = compat(&[
+            ("Simple Troubleshooting", true),
+            ("Reliability & Uptime", true),
+            ("High Performance", true),
+        ]);
+        assert_eq!(suggest_profile(&all).name(), "aggressive");
+
+        let basic = compat(&[
+            ("Simple Troubleshooting", true),
+            ("Reliability & Uptime", false),
+            ("High Performance", true),
+        ]);
+        assert_eq!(suggest_profile(&basic).name(), "standard");
+
+        assert_eq!(suggest_profile(&HashMap::new()).name(), "minimal");
+    }
+
+    #[test]
+    fn test_starter_config_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("conf").join("pp.env");
+        let config = StarterConfig {
+            daily_budget_gb: Some(5.0),
+            reflector: Some("10.0.0.2:4000".to_string()),
+            ..Default::default()
+        };
+
+        config.write(&path, false).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.contains("PP_BIND_ADDR=0.0.0.0:8080"));
+        assert!(text.contains("PP_DAILY_BW_BUDGET_GB=5"));
+        assert!(text.contains("10.0.0.2:4000"));
+
+        assert!(config.write(&path, false).is_err());
+        assert!(config.write(&path, true).is_ok());
+    }
+}
@@ -1,11 +1,26 @@
-use super::{Measurement, Probe, ProbeType};
-use anyhow::Result;
+use super::{dscp, Measurement, Probe, ProbeType};
+use anyhow::{bail, Context, Result};
+use std::net::SocketAddr;
 use std::time::{Duration, Instant, SystemTime};
+use tokio::net::UdpSocket;
 use trust_dns_resolver::TokioAsyncResolver;
 
 /// DNS Resolution Probe
 pub struct DnsProbe {
     resolver: TokioAsyncResolver,
+    /// Mark queries with this DSCP. The resolver library owns its sockets,
+    /// so marked probes send a single A query to the first system nameserver
+    /// over their own UDP socket instead.
+    dscp: Option<u8>,
+}
+
+impl DnsProbe {
+    pub fn with_dscp(dscp: Option<u8>) -> Self {
+        Self {
+            dscp,
+            ..Self::default()
+        }
+    }
 }
 
 impl Default for DnsProbe {
@@ -13,26 +28,33 @@ impl Default for DnsProbe {
         // Use system config (from /etc/resolv.conf)
         let resolver =
             TokioAsyncResolver::tokio_from_system_conf().expect("Failed to create DNS resolver");
-        Self { resolver }
+        Self {
+            resolver,
+            dscp: None,
+        }
     }
 }
 
 #[async_trait::async_trait]
 impl Probe for DnsProbe {
-    async fn run(&self, target: &str, _timeout: Duration) -> Result<Measurement> {
+    async fn run(&self, target: &str, timeout: Duration) -> Result<Measurement> {
         let start = Instant::now();
 
-        let result = self.resolver.lookup_ip(target).await;
+        let result = match self.dscp {
+            Some(dscp) => query_marked(target, timeout, dscp).await?.then_some(true).ok_or(()),
+            None => match tokio::time::timeout(timeout, self.resolver.lookup_ip(target)).await {
+                // If we got IP addresses, success.
+                // We don't necessarily care about the IPs themselves for availability, just that it resolved.
+                Ok(r) => r.map(|lookup| lookup.iter().count() > 0).map_err(|_| ()),
+                Err(_) => Err(()),
+            },
+        };
 
         let duration = start.elapsed();
         let timestamp = SystemTime::now();
 
         match result {
-            Ok(lookup) => {
-                // If we got IP addresses, success.
-                // We don't necessarily care about the IPs themselves for availability, just that it resolved.
-                let success = lookup.iter().count() > 0;
-
+            Ok(success) => {
                 Ok(Measurement {
                     probe_type: ProbeType::Dns,
                     target: target.to_string(),
@@ -40,6 +62,7 @@ impl Probe for DnsProbe {
                     unit: "ms".to_string(),
                     success,
                     timestamp,
+                    dscp: self.dscp,
                 })
             }
             Err(_) => {
@@ -51,8 +74,96 @@ impl Probe for DnsProbe {
                     unit: "ms".to_string(),
                     success: false,
                     timestamp,
+                    dscp: self.dscp,
                 })
             }
         }
     }
 }
+
+/// Resolve `name` with one DSCP-marked A query to the system's first
+/// nameserver. `Ok(false)` is a failed lookup (timeout, error rcode, no
+/// answers); `Err` means the query could not be sent as requested.
+async fn query_marked(name: &str, timeout: Duration, dscp: u8) -> Result<bool> {
+    let (config, _) = trust_dns_resolver::system_conf::read_system_conf()
+        .context("failed to read system resolver config")?;
+    let Some(server) = config.name_servers().first().map(|ns| ns.socket_addr) else {
+        bail!("no nameserver configured");
+    };
+    let bind: SocketAddr = if server.is_ipv6() {
+        "[::]:0".parse()?
+    } else {
+        "0.0.0.0:0".parse()?
+    };
+    let socket = UdpSocket::bind(bind).await.context("failed to open DNS socket")?;
+    dscp::apply(socket2::SockRef::from(&socket), server.is_ipv6(), dscp)?;
+
+    let id: u16 = rand::random();
+    let query = build_query(id, name)?;
+    let exchange = async {
+        socket.connect(server).await?;
+        socket.send(&query).await?;
+        let mut buf = [0u8; 512];
+        loop {
+            let n = socket.recv(&mut buf).await?;
+            // Ignore stray datagrams that aren't our response.
+            if n >= 4 && buf[..2] == id.to_be_bytes() {
+                return std::io::Result::Ok(has_answers(&buf[..n]));
+            }
+        }
+    };
+    Ok(matches!(tokio::time::timeout(timeout, exchange).await, Ok(Ok(true))))
+}
+
+/// A recursive A/IN query for `name`.
+fn build_query(id: u16, name: &str) -> Result<Vec<u8>> {
+    let mut query = Vec::with_capacity(18 + name.len());
+    query.extend_from_slice(&id.to_be_bytes());
+    // Flags: RD. QDCOUNT 1, no other records.
+    query.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            bail!("invalid DNS name '{}'", name);
+        }
+        query.push(label.len() as u8);
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+    query.extend_from_slice(&[0, 1, 0, 1]);
+    Ok(query)
+}
+
+/// Whether a response is a successful answer with at least one record.
+fn has_answers(response: &[u8]) -> bool {
+    response.len() >= 12
+        && response[2] & 0x80 != 0
+        && response[3] & 0x0f == 0
+        && u16::from_be_bytes([response[6], response[7]]) > 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_query() {
+        let q = build_query(0xbeef, "example.com.").unwrap();
+        assert_eq!(&q[..4], &[0xbe, 0xef, 0x01, 0x00]);
+        assert_eq!(&q[12..], b"\x07example\x03com\x00\x00\x01\x00\x01");
+        assert!(build_query(1, "a..b").is_err());
+    }
+
+    #[test]
+    fn test_has_answers() {
+        let mut reply = [0u8; 12];
+        reply[2] = 0x81;
+        reply[3] = 0x80;
+        reply[7] = 1;
+        assert!(has_answers(&reply));
+        reply[3] = 0x83; // NXDOMAIN
+        assert!(!has_answers(&reply));
+        reply[3] = 0x80;
+        reply[7] = 0;
+        assert!(!has_answers(&reply));
+    }
+}
@@ -0,0 +1,123 @@
+//! DSCP (Differentiated Services) marking for probe traffic.
+//!
+//! To check whether an ISP honors QoS marking, ICMP and DNS (UDP) probes can
+//! carry a DSCP value in the IPv4 ToS byte / IPv6 traffic class. The value is
+//! stored with each measurement (`measurements.dscp`) and marked samples are
+//! kept out of baselines, detection, rollups, trends and the dashboard series,
+//! which all describe the unmarked path. Compare the two by querying the
+//! `dscp` column directly.
+//!
+//! Scheduled probes take it as a suffix on the target, e.g.
+//! `icmp:8.8.8.8?dscp=46` or `dns:example.com?dscp=af41`.
+
+use anyhow::{bail, Context, Result};
+use socket2::SockRef;
+
+/// Largest valid DSCP (six bits).
+pub const MAX_DSCP: u8 = 63;
+
+/// Target suffix carrying the DSCP for scheduled probes.
+const SPEC_SUFFIX: &str = "?dscp=";
+
+/// Parse a DSCP given as a number (`46`) or class name (`ef`, `af41`, `cs1`).
+pub fn parse(raw: &str) -> Result<u8> {
+    let raw = raw.trim().to_ascii_lowercase();
+    let dscp = match raw.as_str() {
+        "ef" => 46,
+        "be" | "default" => 0,
+        s if s.starts_with("cs") => match s[2..].parse::<u8>() {
+            Ok(n) if n <= 7 => n << 3,
+            _ => bail!("invalid DSCP class '{}' (cs0-cs7)", raw),
+        },
+        s if s.starts_with("af") => {
+            let digits: Vec<u8> = s[2..].bytes().map(|b| b.wrapping_sub(b'0')).collect();
+            match digits.as_slice() {
+                [class @ 1..=4, precedence @ 1..=3] => (class << 3) | (precedence << 1),
+                _ => bail!("invalid DSCP class '{}' (af11-af43)", raw),
+            }
+        }
+        s => s
+            .parse::<u8>()
+            .with_context(|| format!("invalid DSCP '{}' (0-{} or a class name such as ef)", raw, MAX_DSCP))?,
+    };
+    if dscp > MAX_DSCP {
+        bail!("DSCP {} out of range (0-{})", dscp, MAX_DSCP);
+    }
+    Ok(dscp)
+}
+
+/// Split a scheduled probe target into the host and its optional DSCP.
+pub fn split_target(target: &str) -> Result<(&str, Option<u8>)> {
+    match target.split_once(SPEC_SUFFIX) {
+        Some((host, dscp)) => Ok((host, Some(parse(dscp)?))),
+        None => Ok((target, None)),
+    }
+}
+
+/// The ToS / traffic-class byte for `dscp` (ECN bits left clear).
+pub fn tos(dscp: u8) -> u8 {
+    dscp << 2
+}
+
+/// Mark all packets sent on `socket` with `dscp`.
+///
+/// Fails with an explicit message if the kernel (or a sandbox) refuses the
+/// socket option, rather than letting the probe run unmarked.
+pub fn apply(socket: SockRef<'_>, ipv6: bool, dscp: u8) -> Result<()> {
+    let tos = u32::from(tos(dscp));
+    let result = if ipv6 {
+        socket.set_tclass_v6(tos)
+    } else {
+        socket.set_tos(tos)
+    };
+    result.map_err(|e| {
+        let option = if ipv6 { "IPV6_TCLASS" } else { "IP_TOS" };
+        if e.kind() == std::io::ErrorKind::PermissionDenied {
+            anyhow::anyhow!("setting DSCP {} ({}) is not permitted on this system", dscp, option)
+        } else {
+            anyhow::anyhow!("failed to set DSCP {} ({}): {}", dscp, option, e)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_numbers_and_classes() {
+        assert_eq!(parse("0").unwrap(), 0);
+        assert_eq!(parse("46").unwrap(), 46);
+        assert_eq!(parse("EF").unwrap(), 46);
+        assert_eq!(parse("af41").unwrap(), 34);
+        assert_eq!(parse("af11").unwrap(), 10);
+        assert_eq!(parse("cs1").unwrap(), 8);
+        assert_eq!(parse("cs7").unwrap(), 56);
+        assert!(parse("64").is_err());
+        assert!(parse("af51").is_err());
+        assert!(parse("cs8").is_err());
+        assert!(parse("fast").is_err());
+    }
+
+    #[test]
+    fn test_split_target() {
+        assert_eq!(split_target("8.8.8.8").unwrap(), ("8.8.8.8", None));
+        assert_eq!(split_target("8.8.8.8?dscp=46").unwrap(), ("8.8.8.8", Some(46)));
+        assert_eq!(split_target("example.com?dscp=af41").unwrap(), ("example.com", Some(34)));
+        assert!(split_target("8.8.8.8?dscp=99").is_err());
+    }
+
+    #[test]
+    fn test_tos_byte() {
+        assert_eq!(tos(46), 0xb8);
+        assert_eq!(tos(0), 0);
+        assert_eq!(tos(MAX_DSCP), 0xfc);
+    }
+
+    #[test]
+    fn test_apply_marks_udp_socket() {
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        apply(SockRef::from(&socket), false, 46).unwrap();
+        assert_eq!(SockRef::from(&socket).tos().unwrap(), 0xb8);
+    }
+}
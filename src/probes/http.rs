@@ -13,6 +13,7 @@ impl Default for HttpProbe {
         Self {
             client: Client::builder()
                 .timeout(Duration::from_secs(5))
+                .dns_resolver(std::sync::Arc::new(crate::system::network::CachedResolver))
                 .build()
                 .expect("Failed to build HTTP client"),
         }
@@ -21,7 +22,7 @@ impl Default for HttpProbe {
 
 #[async_trait::async_trait]
 impl Probe for HttpProbe {
-    async fn run(&self, target: &str, _timeout: Duration) -> Result<Measurement> {
+    async fn run(&self, target: &str, timeout: Duration) -> Result<Measurement> {
         let url = if target.starts_with("http") {
             target.to_string()
         } else {
@@ -29,7 +30,8 @@ impl Probe for HttpProbe {
         };
 
         let start = Instant::now();
-        let result = self.client.get(&url).send().await;
+        // Per-request timeout overrides the client's 5s default.
+        let result = self.client.get(&url).timeout(timeout).send().await;
         let duration = start.elapsed();
         let timestamp = SystemTime::now();
 
@@ -49,6 +51,7 @@ impl Probe for HttpProbe {
                     unit: "ms".to_string(),
                     success,
                     timestamp,
+                    dscp: None,
                 })
             }
             Err(_) => Ok(Measurement {
@@ -58,6 +61,7 @@ impl Probe for HttpProbe {
                 unit: "ms".to_string(),
                 success: false,
                 timestamp,
+                dscp: None,
             }),
         }
     }
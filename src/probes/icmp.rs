@@ -1,12 +1,16 @@
-use super::{Measurement, Probe, ProbeType};
-use anyhow::{Context, Result};
+use super::{dscp, Measurement, Probe, ProbeType};
+use anyhow::{bail, Context, Result};
 use std::time::{Duration, Instant, SystemTime};
 // Temporary until we use ICMP raw socket crate
 use tracing::warn;
 
 /// Simple ICMP probe wrapper (uses system ping for now)
 /// Future: Use `pnet` or `socket2` for raw sockets to avoid fork/exec overhead.
-pub struct IcmpProbe;
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IcmpProbe {
+    /// Mark echo requests with this DSCP (`ping -Q`).
+    pub dscp: Option<u8>,
+}
 
 #[async_trait::async_trait]
 impl Probe for IcmpProbe {
@@ -23,17 +27,54 @@ impl Probe for IcmpProbe {
 
         let timeout_secs = timeout.as_secs_f64().max(1.0);
 
-        let output = tokio::process::Command::new("ping")
-            .arg("-c")
+        // Resolve through the shared cache rather than ping's own lookup;
+        // if that fails, let ping try (and report) the name itself. The
+        // lookup counts against the probe's timeout, so a slow resolver
+        // fails the probe instead of stalling it.
+        let destination =
+            match tokio::time::timeout(timeout, crate::system::network::resolve(target)).await {
+                Ok(Ok(ips)) => ips[0].to_string(),
+                Ok(Err(_)) => target.to_string(),
+                Err(_) => {
+                    return Ok(Measurement {
+                        probe_type: ProbeType::Icmp,
+                        target: target.to_string(),
+                        value: -1.0,
+                        unit: "ms".to_string(),
+                        success: false,
+                        timestamp: SystemTime::now(),
+                        dscp: self.dscp,
+                    })
+                }
+            };
+
+        let mut cmd = tokio::process::Command::new("ping");
+        cmd.arg("-c")
             .arg("1")
             .arg("-W")
             .arg(timeout_secs.to_string())
-            .arg("-q")
-            .arg(target)
+            .arg("-q");
+        if let Some(dscp) = self.dscp {
+            // -Q takes the whole ToS byte; ping applies it to IPv6 traffic class too.
+            cmd.arg("-Q").arg(dscp::tos(dscp).to_string());
+        }
+        let output = cmd
+            .arg(&destination)
             .output()
             .await
             .context("Failed to execute ping")?;
 
+        // ping exits 1 when no reply came back and 2 on any other error. A
+        // refused ToS option must not be recorded as plain packet loss, but
+        // other exit-2 errors (unreachable network, unknown host) are
+        // ordinary failures, so only the ToS complaint is raised.
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if let (Some(dscp), Some(2)) = (self.dscp, output.status.code()) {
+            if is_tos_error(&stderr) {
+                bail!("ping could not send with DSCP {} to {}: {}", dscp, target, stderr.trim());
+            }
+        }
+
         let duration = start.elapsed();
         let timestamp = SystemTime::now();
 
@@ -57,6 +98,7 @@ impl Probe for IcmpProbe {
                 unit: "ms".to_string(),
                 success: true,
                 timestamp,
+                dscp: self.dscp,
             })
         } else {
             // Timeout or unreachable
@@ -67,11 +109,21 @@ impl Probe for IcmpProbe {
                 unit: "ms".to_string(),
                 success: false,
                 timestamp,
+                dscp: self.dscp,
             })
         }
     }
 }
 
+/// Whether ping's stderr is about the ToS/QoS option (`-Q`) rather than the
+/// network, e.g. "setsockopt(IP_TOS): Operation not permitted".
+fn is_tos_error(stderr: &str) -> bool {
+    let stderr = stderr.to_ascii_lowercase();
+    ["tos", "qos", "tclass", "traffic class"]
+        .iter()
+        .any(|needle| stderr.contains(needle))
+}
+
 fn extract_rtt(output: &str) -> Option<f64> {
     // Try to find "time=12.3 ms"
     if let Some(pos) = output.find("time=") {
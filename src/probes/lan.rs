@@ -0,0 +1,118 @@
+//! LAN reflector stage for blame check.
+//!
+//! A gateway ping only proves the router answers. With a reflector paired on
+//! the local network, blame check can run a short throughput test to it and
+//! tell "LAN fine, WAN slow" from a genuinely degraded LAN.
+
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::reflector_proto::identity::Identity;
+use crate::reflector_proto::peers::{PeerStore, StoredReflector, PEER_STORE_FILE};
+use crate::throughput::provider::reflector::{QuickCheck, ReflectorProvider};
+
+/// Default throughput floor for the LAN stage to pass, in Mbps.
+pub const DEFAULT_MIN_MBPS: f64 = 100.0;
+
+/// Default length of the LAN throughput test, in seconds.
+pub const DEFAULT_DURATION_SECS: u64 = 3;
+
+/// A LAN reflector blame check can test against.
+#[derive(Debug, Clone)]
+pub struct LanReflector {
+    pub nickname: String,
+    pub address: SocketAddr,
+    /// Download rate below which the LAN is blamed.
+    pub min_mbps: f64,
+    pub duration_secs: u64,
+    identity_path: PathBuf,
+}
+
+impl LanReflector {
+    /// Find the reflector to use from `~/.packetparamedic`.
+    ///
+    /// `PP_BLAME_REFLECTOR` names one (nickname or address) or disables the
+    /// stage with `off`; otherwise the first paired reflector with a LAN
+    /// address is used. `None` when nothing suitable is paired.
+    pub fn discover() -> Option<Self> {
+        let home = std::env::var("HOME").unwrap_or_else(|_| ".".into());
+        let dir = Path::new(&home).join(".packetparamedic");
+        let choice = std::env::var("PP_BLAME_REFLECTOR").ok();
+        let min_mbps = std::env::var("PP_BLAME_LAN_MIN_MBPS")
+            .ok()
+            .and_then(|v| v.parse::<f64>().ok())
+            .filter(|v| *v > 0.0)
+            .unwrap_or(DEFAULT_MIN_MBPS);
+
+        let identity_path = dir.join("identity.key");
+        if !identity_path.exists() {
+            return None;
+        }
+        let store = match PeerStore::load(&dir.join(PEER_STORE_FILE)) {
+            Ok(store) => store,
+            Err(e) => {
+                tracing::warn!("Ignoring reflector store: {:#}", e);
+                return None;
+            }
+        };
+
+        let stored = select(&store, choice.as_deref())?;
+        let address = match stored.address.parse() {
+            Ok(a) => a,
+            Err(_) => {
+                tracing::warn!(address = %stored.address, "Invalid reflector address; LAN stage skipped");
+                return None;
+            }
+        };
+        Some(Self {
+            nickname: stored.nickname.clone(),
+            address,
+            min_mbps,
+            duration_secs: DEFAULT_DURATION_SECS,
+            identity_path,
+        })
+    }
+
+    /// Run the short throughput test.
+    pub async fn check(&self) -> Result<QuickCheck> {
+        let identity = Identity::load(&self.identity_path).context("Failed to load identity")?;
+        ReflectorProvider::quick_check(self.address, &identity, self.duration_secs).await
+    }
+}
+
+/// Pick the reflector per `PP_BLAME_REFLECTOR` (`choice`).
+fn select<'a>(store: &'a PeerStore, choice: Option<&str>) -> Option<&'a StoredReflector> {
+    match choice.map(str::trim) {
+        Some("off") | Some("none") => None,
+        Some(c) if !c.is_empty() => store.find(c),
+        _ => store.first_lan(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = PeerStore::load(&dir.path().join(PEER_STORE_FILE)).unwrap();
+        for (nickname, address) in [("cloud", "203.0.113.10:4000"), ("home", "10.0.0.2:4000")] {
+            store.upsert(StoredReflector {
+                nickname: nickname.to_string(),
+                address: address.to_string(),
+                endpoint_id: None,
+                paired_at: "2026-01-01T00:00:00Z".to_string(),
+            });
+        }
+
+        assert_eq!(select(&store, None).unwrap().nickname, "home");
+        assert_eq!(select(&store, Some("")).unwrap().nickname, "home");
+        // An explicit choice wins even if it is not on the LAN.
+        assert_eq!(select(&store, Some("cloud")).unwrap().nickname, "cloud");
+        assert!(select(&store, Some("off")).is_none());
+        assert!(select(&store, Some("missing")).is_none());
+    }
+}
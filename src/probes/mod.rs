@@ -1,10 +1,13 @@
 pub mod trace;
 use anyhow::Result;
 use std::time::Duration;
+use tokio_util::sync::CancellationToken;
 
 pub mod dns;
+pub mod dscp;
 pub mod http;
 pub mod icmp;
+pub mod lan;
 pub mod tcp;
 pub mod wifi;
 
@@ -27,6 +30,20 @@ impl std::fmt::Display for ProbeType {
     }
 }
 
+impl std::str::FromStr for ProbeType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "icmp" => Ok(Self::Icmp),
+            "dns" => Ok(Self::Dns),
+            "http" => Ok(Self::Http),
+            "tcp" => Ok(Self::Tcp),
+            other => anyhow::bail!("unknown probe type '{}' (expected icmp, dns, http or tcp)", other),
+        }
+    }
+}
+
 pub struct Measurement {
     pub probe_type: ProbeType,
     pub target: String,
@@ -34,6 +51,8 @@ pub struct Measurement {
     pub unit: String,
     pub success: bool,
     pub timestamp: std::time::SystemTime,
+    /// DSCP the probe marked its packets with, if any (see [`dscp`]).
+    pub dscp: Option<u8>,
 }
 
 /// Trait for all active probes
@@ -46,91 +65,594 @@ pub trait Probe: Send + Sync {
 
 use serde::{Deserialize, Serialize};
 
+use crate::analysis::confidence::{
+    healthy_confidence, verdict_confidence, StageEvidence, MIN_BASELINE_SAMPLES,
+};
+use crate::analysis::stats::{calculate_baseline, Baseline};
+use crate::storage::Pool;
+
+/// Per-probe-type timeouts.
+///
+/// ICMP should fail fast, while HTTP legitimately needs longer: a slow but
+/// working web endpoint must not be declared down because it missed a
+/// ping-sized deadline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProbeTimeouts {
+    pub icmp: Duration,
+    pub dns: Duration,
+    pub http: Duration,
+    pub tcp: Duration,
+}
+
+impl Default for ProbeTimeouts {
+    fn default() -> Self {
+        Self {
+            icmp: Duration::from_secs(1),
+            dns: Duration::from_secs(2),
+            http: Duration::from_secs(5),
+            tcp: Duration::from_secs(2),
+        }
+    }
+}
+
+/// Optional per-type overrides in milliseconds, as read from the
+/// environment or an API request body. Unset or zero keeps the current value.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TimeoutOverrides {
+    pub icmp_ms: Option<u64>,
+    pub dns_ms: Option<u64>,
+    pub http_ms: Option<u64>,
+    pub tcp_ms: Option<u64>,
+}
+
+impl ProbeTimeouts {
+    /// Defaults overridden by `PP_PROBE_TIMEOUT_{ICMP,DNS,HTTP,TCP}_MS`.
+    pub fn from_env() -> Self {
+        let env_ms = |name: &str| std::env::var(name).ok()?.trim().parse::<u64>().ok();
+        Self::default().with_overrides(&TimeoutOverrides {
+            icmp_ms: env_ms("PP_PROBE_TIMEOUT_ICMP_MS"),
+            dns_ms: env_ms("PP_PROBE_TIMEOUT_DNS_MS"),
+            http_ms: env_ms("PP_PROBE_TIMEOUT_HTTP_MS"),
+            tcp_ms: env_ms("PP_PROBE_TIMEOUT_TCP_MS"),
+        })
+    }
+
+    /// Apply the set fields of `overrides`.
+    pub fn with_overrides(self, overrides: &TimeoutOverrides) -> Self {
+        let pick = |current: Duration, ms: Option<u64>| match ms {
+            Some(ms) if ms > 0 => Duration::from_millis(ms),
+            _ => current,
+        };
+        Self {
+            icmp: pick(self.icmp, overrides.icmp_ms),
+            dns: pick(self.dns, overrides.dns_ms),
+            http: pick(self.http, overrides.http_ms),
+            tcp: pick(self.tcp, overrides.tcp_ms),
+        }
+    }
+
+    /// Timeout for a probe of the given type.
+    pub fn for_probe(&self, probe: &ProbeType) -> Duration {
+        match probe {
+            ProbeType::Icmp => self.icmp,
+            ProbeType::Dns => self.dns,
+            ProbeType::Http => self.http,
+            ProbeType::Tcp => self.tcp,
+        }
+    }
+}
+
+/// How many attempts a probe gets and how many must succeed.
+///
+/// A single dropped ICMP packet should not flip the blame check to a
+/// confident "Local Network Issue"; requiring e.g. 2 of 3 replies rides out
+/// transient loss while still catching a link that is mostly down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct ProbeRetry {
+    pub attempts: u32,
+    pub required_successes: u32,
+}
+
+impl Default for ProbeRetry {
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            required_successes: 2,
+        }
+    }
+}
+
+/// Upper bound on `attempts`, so one API request can't keep probing for
+/// minutes.
+pub const MAX_ATTEMPTS: u32 = 10;
+
+/// Pause between attempts so they don't all land in the same loss burst.
+const RETRY_GAP: Duration = Duration::from_millis(200);
+
+impl ProbeRetry {
+    /// One attempt that must succeed (no retries).
+    pub fn single() -> Self {
+        Self {
+            attempts: 1,
+            required_successes: 1,
+        }
+    }
+
+    /// Defaults overridden by `PP_PROBE_ATTEMPTS` / `PP_PROBE_REQUIRED_SUCCESSES`.
+    pub fn from_env() -> Result<Self> {
+        let env_u32 = |name: &str| std::env::var(name).ok()?.trim().parse::<u32>().ok();
+        let defaults = Self::default();
+        let retry = Self {
+            attempts: env_u32("PP_PROBE_ATTEMPTS").unwrap_or(defaults.attempts),
+            required_successes: env_u32("PP_PROBE_REQUIRED_SUCCESSES")
+                .unwrap_or(defaults.required_successes),
+        };
+        retry.validate()?;
+        Ok(retry)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.attempts > MAX_ATTEMPTS {
+            anyhow::bail!(
+                "invalid probe retry: attempts must be at most {}, got {}",
+                MAX_ATTEMPTS,
+                self.attempts
+            );
+        }
+        if self.required_successes == 0 || self.required_successes > self.attempts {
+            anyhow::bail!(
+                "invalid probe retry: required_successes must be between 1 and attempts ({}), got {}",
+                self.attempts,
+                self.required_successes
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Result of running one probe under a [`ProbeRetry`] policy.
+pub struct RetryOutcome {
+    /// Every attempt made, in order. Stops early once the verdict is settled.
+    pub attempts: Vec<Measurement>,
+    pub successes: u32,
+    pub passed: bool,
+}
+
+impl RetryOutcome {
+    /// Mean value (latency) of the successful attempts.
+    pub fn value(&self) -> Option<f64> {
+        let ok: Vec<f64> = self.attempts.iter().filter(|m| m.success).map(|m| m.value).collect();
+        if ok.is_empty() {
+            None
+        } else {
+            Some(ok.iter().sum::<f64>() / ok.len() as f64)
+        }
+    }
+
+    /// Per-attempt evidence, e.g. "2/3 ok [1.2 ms, timeout, 1.4 ms]".
+    pub fn summary(&self) -> String {
+        let each: Vec<String> = self
+            .attempts
+            .iter()
+            .map(|m| {
+                if m.success {
+                    format!("{:.1} {}", m.value, m.unit)
+                } else {
+                    "failed".to_string()
+                }
+            })
+            .collect();
+        format!("{}/{} ok [{}]", self.successes, self.attempts.len(), each.join(", "))
+    }
+
+    /// Evidence for confidence calibration, paired with the target's learned
+    /// baseline (if any).
+    fn evidence(&self, baseline: Option<Baseline>) -> StageEvidence {
+        StageEvidence {
+            attempts: self.attempts.len() as u32,
+            successes: self.successes,
+            latency_ms: self.value(),
+            baseline,
+            corroborated: false,
+        }
+    }
+}
+
+/// Run `fut` unless `cancel` fires first.
+async fn until_cancelled<T>(
+    cancel: &CancellationToken,
+    fut: impl std::future::Future<Output = Result<T>>,
+) -> Result<T> {
+    tokio::select! {
+        biased;
+        _ = cancel.cancelled() => anyhow::bail!("cancelled"),
+        res = fut => res,
+    }
+}
+
+/// Run `probe` up to `retry.attempts` times, passing once
+/// `retry.required_successes` attempts succeed. Stops with an error as soon
+/// as `cancel` fires.
+pub async fn run_with_retry(
+    probe: &dyn Probe,
+    target: &str,
+    timeout: Duration,
+    retry: &ProbeRetry,
+    cancel: &CancellationToken,
+) -> Result<RetryOutcome> {
+    let mut attempts = Vec::new();
+    let mut successes = 0;
+
+    for n in 0..retry.attempts {
+        if n > 0 {
+            until_cancelled(cancel, async {
+                tokio::time::sleep(RETRY_GAP).await;
+                Ok(())
+            })
+            .await?;
+        }
+        let m = until_cancelled(cancel, probe.run(target, timeout)).await?;
+        if m.success {
+            successes += 1;
+        }
+        attempts.push(m);
+
+        let remaining = retry.attempts - n - 1;
+        if successes >= retry.required_successes || successes + remaining < retry.required_successes {
+            break;
+        }
+    }
+
+    Ok(RetryOutcome {
+        passed: successes >= retry.required_successes,
+        attempts,
+        successes,
+    })
+}
+
+/// Which layer a blame check faulted, for callers that act on the outcome
+/// (exit codes, alerts) rather than display `verdict`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlameFault {
+    Healthy,
+    Local,
+    Isp,
+    Dns,
+    Service,
+    /// Reports stored before the fault was recorded.
+    #[default]
+    Unknown,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct BlameReport {
     pub verdict: String,
+    #[serde(default)]
+    pub fault: BlameFault,
     pub confidence: u8,
     pub details: Vec<String>,
 }
 
-/// Run an immediate blame check sequence
-pub async fn run_blame_check() -> Result<BlameReport> {
-    let timeout = Duration::from_secs(2);
+/// Learned baseline for `target`, if `pool` is available and has enough
+/// recent samples. Lookup errors only cost calibration, not the check.
+fn lookup_baseline(pool: Option<&Pool>, probe_type: &ProbeType, target: &str) -> Option<Baseline> {
+    let pool = pool?;
+    match calculate_baseline(pool, &probe_type.to_string(), target) {
+        Ok(b) if b.sample_count >= MIN_BASELINE_SAMPLES => Some(b),
+        Ok(_) => None,
+        Err(e) => {
+            tracing::debug!(%target, "Baseline lookup failed: {:#}", e);
+            None
+        }
+    }
+}
+
+/// Run an immediate blame check sequence.
+///
+/// Confidence is calibrated from the retry evidence of each stage and, when
+/// `pool` is given, from the learned 24h baselines of the probed targets.
+/// With a LAN reflector, a short throughput test to it runs after the
+/// gateway check; without one the sequence is unchanged.
+pub async fn run_blame_check(
+    timeouts: &ProbeTimeouts,
+    retry: &ProbeRetry,
+    pool: Option<&Pool>,
+    lan: Option<&lan::LanReflector>,
+    cancel: &CancellationToken,
+) -> Result<BlameReport> {
+    retry.validate()?;
     let mut details = Vec::new();
+    let mut passed: Vec<StageEvidence> = Vec::new();
 
     // 1. Check Gateway (Local Network)
     // TODO: Use system::network::get_default_gateway(). For now, try detection or fallback.
     let gateway =
         crate::system::network::get_default_gateway().unwrap_or_else(|_| "192.168.1.1".to_string());
-    let icmp = icmp::IcmpProbe;
+    let icmp = icmp::IcmpProbe::default();
 
-    let gw_res = icmp.run(&gateway, timeout).await?;
-    if !gw_res.success {
-        details.push(format!("Gateway ({}) unreachable.", gateway));
+    let gw_res = run_with_retry(&icmp, &gateway, timeouts.icmp, retry, cancel).await?;
+    let gw_ev = gw_res.evidence(lookup_baseline(pool, &ProbeType::Icmp, &gateway));
+    if !gw_res.passed {
+        details.push(format!(
+            "Gateway ({}) unreachable: {} (needed {}, timeout {} ms).",
+            gateway,
+            gw_res.summary(),
+            retry.required_successes,
+            timeouts.icmp.as_millis()
+        ));
         return Ok(BlameReport {
             verdict: "Local Network Issue".to_string(),
-            confidence: 90,
+            fault: BlameFault::Local,
+            confidence: verdict_confidence(&gw_ev, &passed),
             details,
         });
     }
     details.push(format!(
-        "Gateway ({}) ping: {:.1} ms (OK)",
-        gateway, gw_res.value
+        "Gateway ({}) ping: {:.1} ms (OK, {}){}",
+        gateway,
+        gw_res.value().unwrap_or_default(),
+        gw_res.summary(),
+        baseline_note(&gw_ev)
     ));
+    passed.push(gw_ev);
+
+    // 1b. LAN throughput to a paired reflector (optional)
+    if let Some(lan) = lan {
+        match until_cancelled(cancel, lan.check()).await {
+            Ok(check) => {
+                let ok = check.download_mbps >= lan.min_mbps;
+                let lan_ev = StageEvidence {
+                    attempts: 1,
+                    successes: ok as u32,
+                    latency_ms: Some(check.rtt_ms),
+                    baseline: None,
+                    corroborated: true,
+                };
+                if !ok {
+                    details.push(format!(
+                        "LAN reflector {} ({}) download {:.0} Mbps, below the {:.0} Mbps floor (rtt {:.1} ms).",
+                        lan.nickname, lan.address, check.download_mbps, lan.min_mbps, check.rtt_ms
+                    ));
+                    return Ok(BlameReport {
+                        verdict: "Local Network Issue".to_string(),
+                        fault: BlameFault::Local,
+                        confidence: verdict_confidence(&lan_ev, &passed),
+                        details,
+                    });
+                }
+                details.push(format!(
+                    "LAN reflector {} ({}) download {:.0} Mbps, rtt {:.1} ms (OK)",
+                    lan.nickname, lan.address, check.download_mbps, check.rtt_ms
+                ));
+                // Real throughput across the LAN is stronger evidence for it
+                // than a gateway ping, so it stands in for the gateway stage.
+                passed = vec![lan_ev];
+            }
+            Err(e) => details.push(format!(
+                "LAN reflector {} ({}) unavailable, stage skipped: {:#}",
+                lan.nickname, lan.address, e
+            )),
+        }
+    }
 
     // 2. Check WAN (ISP)
     let wan_target = "8.8.8.8";
-    let wan_res = icmp.run(wan_target, timeout).await?;
-    if !wan_res.success {
-        details.push(format!("WAN target ({}) unreachable.", wan_target));
+    let wan_res = run_with_retry(&icmp, wan_target, timeouts.icmp, retry, cancel).await?;
+    let wan_ev = wan_res.evidence(lookup_baseline(pool, &ProbeType::Icmp, wan_target));
+    if !wan_res.passed {
+        details.push(format!(
+            "WAN target ({}) unreachable: {} (needed {}, timeout {} ms).",
+            wan_target,
+            wan_res.summary(),
+            retry.required_successes,
+            timeouts.icmp.as_millis()
+        ));
         return Ok(BlameReport {
             verdict: "ISP / Internet Connection Issue".to_string(),
-            confidence: 80,
+            fault: BlameFault::Isp,
+            confidence: verdict_confidence(&wan_ev, &passed),
             details,
         });
     }
     details.push(format!(
-        "WAN ({}) ping: {:.1} ms (OK)",
-        wan_target, wan_res.value
+        "WAN ({}) ping: {:.1} ms (OK, {}){}",
+        wan_target,
+        wan_res.value().unwrap_or_default(),
+        wan_res.summary(),
+        baseline_note(&wan_ev)
     ));
+    passed.push(wan_ev);
 
     // 3. Check DNS
     let dns = dns::DnsProbe::default();
     let dns_target = "google.com";
-    let dns_res = dns.run(dns_target, timeout).await?;
-    if !dns_res.success {
-        details.push("DNS Resolution failed.".to_string());
+    let dns_res = run_with_retry(&dns, dns_target, timeouts.dns, retry, cancel).await?;
+    let dns_ev = dns_res.evidence(lookup_baseline(pool, &ProbeType::Dns, dns_target));
+    if !dns_res.passed {
+        details.push(format!(
+            "DNS Resolution failed: {} (needed {}, timeout {} ms).",
+            dns_res.summary(),
+            retry.required_successes,
+            timeouts.dns.as_millis()
+        ));
         return Ok(BlameReport {
             verdict: "DNS Configuration Issue".to_string(),
-            confidence: 75,
+            fault: BlameFault::Dns,
+            confidence: verdict_confidence(&dns_ev, &passed),
             details,
         });
     }
     details.push(format!(
-        "DNS check ({}) resolved in {:.1} ms (OK)",
-        dns_target, dns_res.value
+        "DNS check ({}) resolved in {:.1} ms (OK, {}){}",
+        dns_target,
+        dns_res.value().unwrap_or_default(),
+        dns_res.summary(),
+        baseline_note(&dns_ev)
     ));
+    passed.push(dns_ev);
 
     // 4. Check HTTP (Service)
     let http = http::HttpProbe::default();
     let http_target = "http://google.com";
-    let http_res = http.run(http_target, timeout).await?;
-    if !http_res.success {
-        details.push("HTTP Request failed.".to_string());
+    let http_res = run_with_retry(&http, http_target, timeouts.http, retry, cancel).await?;
+    let http_ev = http_res.evidence(lookup_baseline(pool, &ProbeType::Http, http_target));
+    if !http_res.passed {
+        details.push(format!(
+            "HTTP Request failed: {} (needed {}, timeout {} ms).",
+            http_res.summary(),
+            retry.required_successes,
+            timeouts.http.as_millis()
+        ));
         return Ok(BlameReport {
             verdict: "Service / Application Layer Issue".to_string(),
-            confidence: 60,
+            fault: BlameFault::Service,
+            confidence: verdict_confidence(&http_ev, &passed),
             details,
         });
     }
     details.push(format!(
-        "HTTP check ({}) took {:.1} ms (OK)",
-        http_target, http_res.value
+        "HTTP check ({}) took {:.1} ms (OK, {}){}",
+        http_target,
+        http_res.value().unwrap_or_default(),
+        http_res.summary(),
+        baseline_note(&http_ev)
     ));
+    passed.push(http_ev);
 
     Ok(BlameReport {
         verdict: "Healthy".to_string(),
-        confidence: 95,
+        fault: BlameFault::Healthy,
+        confidence: healthy_confidence(&passed),
         details,
     })
 }
+
+/// " vs baseline 1.2 ± 0.3 ms" suffix for a passing stage, empty without one.
+fn baseline_note(e: &StageEvidence) -> String {
+    match &e.baseline {
+        Some(b) => format!(" vs baseline {:.1} ± {:.1} ms", b.mean, b.std_dev),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_timeouts_per_type() {
+        let t = ProbeTimeouts::default();
+        assert!(t.icmp < t.dns);
+        assert!(t.dns < t.http);
+        assert_eq!(t.for_probe(&ProbeType::Http), Duration::from_secs(5));
+        assert_eq!(t.for_probe(&ProbeType::Icmp), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_timeout_overrides() {
+        let overrides: TimeoutOverrides =
+            serde_json::from_str(r#"{"http_ms": 10000, "icmp_ms": 0}"#).unwrap();
+        let t = ProbeTimeouts::default().with_overrides(&overrides);
+        assert_eq!(t.http, Duration::from_secs(10));
+        // Zero and unset fields keep the defaults.
+        assert_eq!(t.icmp, ProbeTimeouts::default().icmp);
+        assert_eq!(t.dns, ProbeTimeouts::default().dns);
+    }
+
+    /// Replays a fixed success/failure sequence.
+    struct ScriptedProbe {
+        script: Vec<bool>,
+        next: std::sync::atomic::AtomicUsize,
+    }
+
+    impl ScriptedProbe {
+        fn new(script: &[bool]) -> Self {
+            Self {
+                script: script.to_vec(),
+                next: std::sync::atomic::AtomicUsize::new(0),
+            }
+        }
+
+        fn calls(&self) -> usize {
+            self.next.load(std::sync::atomic::Ordering::SeqCst)
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Probe for ScriptedProbe {
+        async fn run(&self, target: &str, _timeout: Duration) -> Result<Measurement> {
+            let i = self.next.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            let success = self.script[i];
+            Ok(Measurement {
+                probe_type: ProbeType::Icmp,
+                target: target.to_string(),
+                value: if success { 1.5 } else { -1.0 },
+                unit: "ms".to_string(),
+                success,
+                timestamp: std::time::SystemTime::now(),
+                dscp: None,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_rides_out_single_loss() {
+        let probe = ScriptedProbe::new(&[false, true, true]);
+        let out = run_with_retry(&probe, "gw", Duration::from_secs(1), &ProbeRetry::default(), &CancellationToken::new())
+            .await
+            .unwrap();
+        assert!(out.passed);
+        assert_eq!(out.successes, 2);
+        assert_eq!(out.value(), Some(1.5));
+        assert_eq!(out.summary(), "2/3 ok [failed, 1.5 ms, 1.5 ms]");
+
+        // A single attempt with the same first packet fails.
+        let probe = ScriptedProbe::new(&[false]);
+        let out = run_with_retry(&probe, "gw", Duration::from_secs(1), &ProbeRetry::single(), &CancellationToken::new())
+            .await
+            .unwrap();
+        assert!(!out.passed);
+    }
+
+    #[tokio::test]
+    async fn test_retry_stops_once_settled() {
+        let probe = ScriptedProbe::new(&[true, true, true]);
+        let out = run_with_retry(&probe, "gw", Duration::from_secs(1), &ProbeRetry::default(), &CancellationToken::new())
+            .await
+            .unwrap();
+        assert!(out.passed);
+        assert_eq!(probe.calls(), 2);
+
+        // Two failures out of three: the threshold can no longer be met.
+        let probe = ScriptedProbe::new(&[false, false, true]);
+        let out = run_with_retry(&probe, "gw", Duration::from_secs(1), &ProbeRetry::default(), &CancellationToken::new())
+            .await
+            .unwrap();
+        assert!(!out.passed);
+        assert_eq!(probe.calls(), 2);
+        assert!(out.value().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_retry_stops_when_cancelled() {
+        let probe = ScriptedProbe::new(&[true, true, true]);
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let res = run_with_retry(&probe, "gw", Duration::from_secs(1), &ProbeRetry::default(), &cancel).await;
+        assert!(res.is_err());
+        assert_eq!(probe.calls(), 0);
+    }
+
+    #[test]
+    fn test_retry_validate() {
+        assert!(ProbeRetry::default().validate().is_ok());
+        assert!(ProbeRetry::single().validate().is_ok());
+        let zero = ProbeRetry { attempts: 3, required_successes: 0 };
+        assert!(zero.validate().is_err());
+        let too_many = ProbeRetry { attempts: 2, required_successes: 3 };
+        assert!(too_many.validate().is_err());
+        let unbounded = ProbeRetry { attempts: u32::MAX, required_successes: 1 };
+        assert!(unbounded.validate().is_err());
+    }
+}
@@ -1,5 +1,6 @@
 use super::{Measurement, Probe, ProbeType};
-use anyhow::Result;
+use anyhow::{Context, Result};
+use std::net::{IpAddr, SocketAddr};
 use std::time::{Duration, Instant, SystemTime};
 use tokio::net::TcpStream;
 
@@ -11,16 +12,19 @@ impl Probe for TcpProbe {
     async fn run(&self, target: &str, timeout: Duration) -> Result<Measurement> {
         let start = Instant::now();
 
-        // Target format: "host:port", e.g. "google.com:80"
-        // If no port, default to 80? Or error?
-        // Let's assume input includes port for now.
-        let addr = if target.contains(':') {
-            target.to_string()
-        } else {
-            format!("{}:80", target)
-        };
+        let (host, port) = split_target(target)?;
 
-        let connect_future = TcpStream::connect(&addr);
+        // Resolved through the shared cache, inside the probe's timeout so a
+        // slow resolver fails the probe instead of stalling it. A failed
+        // lookup leaves no addresses and the connect fails like any other
+        // error.
+        let connect_future = async {
+            let addrs: Vec<SocketAddr> = crate::system::network::resolve(host)
+                .await
+                .map(|ips| ips.into_iter().map(|ip| SocketAddr::new(ip, port)).collect())
+                .unwrap_or_default();
+            TcpStream::connect(&addrs[..]).await
+        };
         let result = tokio::time::timeout(timeout, connect_future).await;
 
         let duration = start.elapsed();
@@ -34,6 +38,7 @@ impl Probe for TcpProbe {
                 unit: "ms".to_string(),
                 success: true,
                 timestamp,
+                dscp: None,
             }),
             Ok(Err(_)) => {
                 // Connection refused or other IO error
@@ -44,6 +49,7 @@ impl Probe for TcpProbe {
                     unit: "ms".to_string(),
                     success: false,
                     timestamp,
+                    dscp: None,
                 })
             }
             Err(_) => {
@@ -55,8 +61,52 @@ impl Probe for TcpProbe {
                     unit: "ms".to_string(),
                     success: false,
                     timestamp,
+                    dscp: None,
                 })
             }
         }
     }
 }
+
+/// Split a TCP target into host and port, e.g. `"google.com:80"`; port 80 if
+/// omitted. IPv6 addresses are given bare (`2001:db8::1`) or bracketed
+/// with a port (`[2001:db8::1]:443`).
+fn split_target(target: &str) -> Result<(&str, u16)> {
+    let port = |p: &str| {
+        p.parse::<u16>()
+            .with_context(|| format!("invalid port in TCP target '{}'", target))
+    };
+    if target.parse::<IpAddr>().is_ok() {
+        return Ok((target, 80));
+    }
+    if let Some(rest) = target.strip_prefix('[') {
+        let (host, after) = rest
+            .split_once(']')
+            .with_context(|| format!("unclosed '[' in TCP target '{}'", target))?;
+        return match after.strip_prefix(':') {
+            Some(p) => Ok((host, port(p)?)),
+            None if after.is_empty() => Ok((host, 80)),
+            None => anyhow::bail!("invalid TCP target '{}'", target),
+        };
+    }
+    match target.rsplit_once(':') {
+        Some((host, p)) => Ok((host, port(p)?)),
+        None => Ok((target, 80)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_target() {
+        assert_eq!(split_target("google.com:443").unwrap(), ("google.com", 443));
+        assert_eq!(split_target("google.com").unwrap(), ("google.com", 80));
+        assert_eq!(split_target("2001:db8::1").unwrap(), ("2001:db8::1", 80));
+        assert_eq!(split_target("[2001:db8::1]:443").unwrap(), ("2001:db8::1", 443));
+        assert_eq!(split_target("[2001:db8::1]").unwrap(), ("2001:db8::1", 80));
+        assert!(split_target("host:http").is_err());
+        assert!(split_target("[2001:db8::1").is_err());
+    }
+}
@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
 use anyhow::Result;
-use std::process::Command;
+use std::process::{Command, Output};
+use tokio_util::sync::CancellationToken;
 use tracing::{info, warn};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -44,27 +45,51 @@ pub struct Hop {
 /// Executes a trace to the target using `mtr` in JSON mode.
 /// Requires `mtr` (or `mtr-tiny`) to be installed and properly capacitied (CAP_NET_RAW).
 pub fn run_trace(target: &str) -> Result<MtrReport> {
+    check_target(target)?;
+    info!(target, "Starting MTR trace...");
+    report_from_output(target, mtr_command(target).output())
+}
+
+/// [`run_trace`] that kills `mtr` as soon as `cancel` fires, so a trace
+/// abandoned by its caller (an API request that timed out, say) stops too.
+pub async fn run_trace_cancellable(target: &str, cancel: &CancellationToken) -> Result<MtrReport> {
+    check_target(target)?;
+    info!(target, "Starting MTR trace...");
+    let mut cmd = tokio::process::Command::from(mtr_command(target));
+    cmd.kill_on_drop(true);
+    let output = tokio::select! {
+        biased;
+        () = cancel.cancelled() => anyhow::bail!("trace to {} cancelled", target),
+        out = cmd.output() => out,
+    };
+    report_from_output(target, output)
+}
+
+fn check_target(target: &str) -> Result<()> {
     // Validate target lightly to avoid injection (though Command protects mostly)
     if target.chars().any(|c| !c.is_alphanumeric() && c != '.' && c != ':' && c != '-') {
         anyhow::bail!("Invalid target format");
     }
+    Ok(())
+}
 
-    info!(target, "Starting MTR trace...");
-
+fn mtr_command(target: &str) -> Command {
     // Build command: mtr --json --report -c 10 <target>
     // --report is implied by --json in modern versions but explicit is safer.
     // -c 10 sends 10 packets per hop.
     // -z reports ASN (optional, might break JSON schema if unexpected fields? No, extra fields ignored by default serde)
     
-    let output = Command::new("mtr")
-        .arg("--json")
+    let mut cmd = Command::new("mtr");
+    cmd.arg("--json")
         .arg("--report")
         .arg("-c")
         .arg("10")
         .arg("--report-wide") // Use wide report for easier parsing if JSON fails
-        .arg(target)
-        .output();
+        .arg(target);
+    cmd
+}
 
+fn report_from_output(target: &str, output: std::io::Result<Output>) -> Result<MtrReport> {
     match output {
         Ok(out) => {
             if out.status.success() {
@@ -98,6 +123,26 @@ pub fn run_trace(target: &str) -> Result<MtrReport> {
     }
 }
 
+/// Persist a trace to `trace_results` with its hop count, worst hop latency
+/// and average loss.
+pub fn save(pool: &crate::storage::Pool, report: &MtrReport) -> Result<()> {
+    let conn = pool.get()?;
+    let hubs = &report.report.mtr.hubs;
+    let hop_count = hubs.len();
+    let max_lat = hubs.iter().map(|h| h.worst).fold(0.0, f32::max);
+    let avg_loss = if hop_count > 0 {
+        hubs.iter().map(|h| h.loss_percent).sum::<f32>() / hop_count as f32
+    } else {
+        0.0
+    };
+    conn.execute(
+        "INSERT INTO trace_results (target, hop_count, max_latency_ms, avg_loss_percent, result_json)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        rusqlite::params![report.report.mtr.dst, hop_count, max_lat, avg_loss, serde_json::to_string(report)?],
+    )?;
+    Ok(())
+}
+
 fn parse_mtr_text(output: &str, target: &str) -> Result<MtrReport> {
     let mut hubs = Vec::new();
     let mut lines = output.lines();
@@ -1,35 +1,119 @@
 
-use std::net::SocketAddr;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use anyhow::{anyhow, Context, Result};
+use bytes::Bytes;
 use futures::{SinkExt, StreamExt};
 use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
 use rustls::{ClientConfig, RootCertStore};
 use rustls_pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer, ServerName, UnixTime};
-use tokio::net::TcpStream;
-use tokio_rustls::{client::TlsStream, TlsConnector};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_rustls::TlsConnector;
 use tokio_util::codec::Framed;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use crate::reflector_proto::{
     cert,
     identity::Identity,
     rpc::{self, LinkMessage, MessagePayload},
-    wire::LinkCodec,
+    tunnel::{self, Tunnel},
+    udp_echo,
+    wire::{Frame, LinkCodec},
 };
 
+/// Request ID of the Hello that opens every connection.
+const HELLO_REQUEST_ID: &str = "init-0";
+
+/// ALPN protocol identifier offered to reflectors; must match their `network.alpn`.
+pub const ALPN_PP_LINK: &[u8] = b"pp-link/1";
+
+/// Paramedic Link protocol versions we speak, oldest first. The oldest goes
+/// in `Hello.version` for reflectors that predate negotiation.
+pub const PROTOCOL_VERSIONS: &[&str] = &["1.0", "1.1"];
+
+/// First protocol version with `Ping`/`Pong`.
+const KEEPALIVE_VERSION: &str = "1.1";
+
+/// Default keepalive interval: well under the 30 s UDP and few-minute TCP
+/// idle timeouts common on NAT gateways.
+pub const DEFAULT_KEEPALIVE: Duration = Duration::from_secs(15);
+
+/// Keepalive intervals a `Ping` may go unanswered before the connection is
+/// declared dead.
+const KEEPALIVE_MISSES: u32 = 2;
+
+/// Control-plane transport to a reflector; must be one the reflector's
+/// `network.transport` listens on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Transport {
+    /// mTLS over TCP.
+    #[default]
+    Tcp,
+    /// mTLS over QUIC (UDP, same port).
+    Quic,
+}
+
+impl std::str::FromStr for Transport {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "tcp" => Ok(Self::Tcp),
+            "quic" => Ok(Self::Quic),
+            other => Err(anyhow!("unknown reflector transport '{}' (expected tcp or quic)", other)),
+        }
+    }
+}
+
+impl Transport {
+    /// `PP_REFLECTOR_TRANSPORT`, defaulting to TCP.
+    pub fn from_env() -> Result<Self> {
+        match std::env::var("PP_REFLECTOR_TRANSPORT") {
+            Ok(v) if !v.trim().is_empty() => v.parse(),
+            _ => Ok(Self::Tcp),
+        }
+    }
+}
+
+/// Byte stream carrying the framed control plane, whatever the transport.
+trait LinkIo: AsyncRead + AsyncWrite + Send + Unpin {}
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> LinkIo for T {}
+
 /// A client for the Paramedic Link protocol that talks to a Reflector.
 pub struct ReflectorClient {
-    framed: Framed<TlsStream<TcpStream>, LinkCodec>,
+    framed: Framed<Box<dyn LinkIo>, LinkCodec>,
     request_counter: u64,
+    /// The reflector's endpoint ID, as presented in its certificate.
+    server_endpoint_id: Option<String>,
+    /// Protocol version the reflector chose for this connection.
+    protocol_version: String,
+    /// Interval between keepalive pings while the connection is otherwise
+    /// idle; zero disables them.
+    keepalive: Duration,
+    /// Requests we stopped waiting for (tunnel channels still opening when
+    /// the data plane finished); their late replies are skipped.
+    abandoned: HashSet<String>,
+    /// Keeps the QUIC endpoint and connection alive for the stream's lifetime.
+    _quic: Option<(quinn::Endpoint, quinn::Connection)>,
 }
 
 impl ReflectorClient {
-    /// Connect to a reflector at `addr` using the given `identity`.
+    /// Connect to a reflector at `addr` using the given `identity`, over the
+    /// transport chosen by `PP_REFLECTOR_TRANSPORT` (TCP by default).
     ///
-    /// This performs the TCP connect, mTLS handshake, and Paramedic Link Hello exchange.
+    /// This performs the connect, mTLS handshake, and Paramedic Link Hello exchange.
     pub async fn connect(addr: SocketAddr, identity: &Identity) -> Result<Self> {
+        Self::connect_with(addr, identity, Transport::from_env()?).await
+    }
+
+    /// Connect over an explicit control-plane transport.
+    pub async fn connect_with(addr: SocketAddr, identity: &Identity, transport: Transport) -> Result<Self> {
         // 1. Generate self-signed client certificate.
         let (cert_der, key_der) = cert::generate_self_signed_cert(identity)
             .context("failed to generate client certificate")?;
@@ -42,55 +126,171 @@ impl ReflectorClient {
         // In a real implementation, we should pin the server ID after pairing.
         let verifier = Arc::new(BlindVerifier);
         
-        let config = ClientConfig::builder()
+        let mut config = ClientConfig::builder()
             .dangerous()
             .with_custom_certificate_verifier(verifier)
             .with_client_auth_cert(cert_chain, private_key)?;
+        config.alpn_protocols = vec![ALPN_PP_LINK.to_vec()];
 
-        let connector = TlsConnector::from(Arc::new(config));
+        // 3. Connect and upgrade to TLS.
+        // Use "reflector" as the server name for SNI (it's ignored by our verifier but required by API).
+        info!(address = %addr, ?transport, "connecting to reflector");
+        let (io, quic, peer_cert): (Box<dyn LinkIo>, _, Option<Vec<u8>>) = match transport {
+            Transport::Tcp => {
+                let connector = TlsConnector::from(Arc::new(config));
+                let tcp_stream = TcpStream::connect(addr).await
+                    .context("failed to connect TCP")?;
+                let domain = ServerName::try_from("reflector").unwrap();
+                let tls_stream = connector.connect(domain, tcp_stream).await
+                    .context("failed TLS handshake")?;
+                let peer_cert = tls_stream.get_ref().1.peer_certificates()
+                    .and_then(|certs| certs.first())
+                    .map(|c| c.as_ref().to_vec());
+                (Box::new(tls_stream), None, peer_cert)
+            }
+            Transport::Quic => {
+                let crypto = quinn::crypto::rustls::QuicClientConfig::try_from(config)
+                    .context("TLS config is not usable for QUIC")?;
+                let bind: SocketAddr = if addr.is_ipv6() { "[::]:0" } else { "0.0.0.0:0" }.parse()?;
+                let mut endpoint = quinn::Endpoint::client(bind).context("failed to bind QUIC client")?;
+                let mut client = quinn::ClientConfig::new(Arc::new(crypto));
+                // QUIC keep-alives hold the connection (and NAT mapping) open
+                // while the control stream sits idle during a long test.
+                let mut transport = quinn::TransportConfig::default();
+                transport.keep_alive_interval(Some(DEFAULT_KEEPALIVE));
+                client.transport_config(Arc::new(transport));
+                endpoint.set_default_client_config(client);
+                let conn = endpoint.connect(addr, "reflector")?.await
+                    .context("failed QUIC handshake")?;
+                let peer_cert = conn.peer_identity()
+                    .and_then(|id| id.downcast::<Vec<CertificateDer<'static>>>().ok())
+                    .and_then(|certs| certs.first().map(|c| c.as_ref().to_vec()));
+                // The reflector serves the first bidirectional stream.
+                let (send, recv) = conn.open_bi().await.context("failed to open control stream")?;
+                (Box::new(tokio::io::join(recv, send)), Some((endpoint, conn)), peer_cert)
+            }
+        };
 
-        // 3. Connect TCP.
-        info!(address = %addr, "connecting to reflector");
-        let tcp_stream = TcpStream::connect(addr).await
-            .context("failed to connect TCP")?;
+        let cert_id = match peer_cert {
+            Some(der) => Some(cert::extract_peer_id_from_cert(&der)
+                .context("reflector certificate carries no endpoint ID")?),
+            None => None,
+        };
 
-        // 4. Upgrade to TLS.
-        // Use "reflector" as the server name for SNI (it's ignored by our verifier but required by API).
-        let domain = ServerName::try_from("reflector").unwrap();
-        let tls_stream = connector.connect(domain, tcp_stream).await
-            .context("failed TLS handshake")?;
+        Self::handshake(io, cert_id, quic).await
+    }
 
-        // 5. Wrap in codec.
-        let mut framed = Framed::new(tls_stream, LinkCodec::new());
+    /// Run the Hello exchange over an established control stream.
+    async fn handshake(
+        io: Box<dyn LinkIo>,
+        cert_id: Option<String>,
+        quic: Option<(quinn::Endpoint, quinn::Connection)>,
+    ) -> Result<Self> {
+        // 4. Wrap in codec.
+        let mut framed = Framed::new(io, LinkCodec::new());
 
-        // 6. Send Hello.
+        // 5. Send Hello.
         let hello = LinkMessage {
-            request_id: "init-0".to_string(),
+            request_id: HELLO_REQUEST_ID.to_string(),
             payload: MessagePayload::Hello(rpc::Hello {
-                version: "1.0".to_string(),
+                version: PROTOCOL_VERSIONS[0].to_string(),
+                versions: PROTOCOL_VERSIONS.iter().map(|v| v.to_string()).collect(),
                 features: vec!["throughput".to_string(), "udp_echo".to_string()],
             }),
         };
         framed.send(hello).await.context("failed to send Hello")?;
 
-        // 7. Receive ServerHello.
+        // 6. Receive ServerHello.
         let response = framed.next().await
             .ok_or_else(|| anyhow!("connection closed before ServerHello"))?
             .context("failed to decode ServerHello frame")?;
 
-        match response.payload {
-            MessagePayload::ServerHello(sh) => {
+        if let Frame::Message(msg) = &response {
+            if msg.request_id != HELLO_REQUEST_ID {
+                anyhow::bail!(
+                    "reflector answered Hello with request ID {:?}, expected {:?}",
+                    msg.request_id, HELLO_REQUEST_ID
+                );
+            }
+        }
+
+        let (server_endpoint_id, protocol_version) = match response {
+            Frame::Message(LinkMessage { payload: MessagePayload::ServerHello(sh), .. }) => {
+                // The certificate is authoritative; a ServerHello claiming a
+                // different identity means something is relaying the session.
+                if let (Some(cert_id), Some(hello_id)) = (&cert_id, &sh.endpoint_id) {
+                    if !cert_id.eq_ignore_ascii_case(hello_id) {
+                        anyhow::bail!(
+                            "reflector announced endpoint ID {} but its certificate is for {}",
+                            hello_id, cert_id
+                        );
+                    }
+                }
+                if !PROTOCOL_VERSIONS.contains(&sh.version.as_str()) {
+                    anyhow::bail!("reflector chose unsupported protocol version {}", sh.version);
+                }
                 info!(server_version = %sh.version, "handshake complete");
+                (cert_id.or(sh.endpoint_id), sh.version)
+            }
+            Frame::Message(LinkMessage { payload: MessagePayload::Error(e), .. }) => {
+                anyhow::bail!("reflector rejected Hello ({}): {}", e.code, e.message)
             }
             other => anyhow::bail!("expected ServerHello, got {:?}", other),
-        }
+        };
 
         Ok(Self {
             framed,
             request_counter: 1,
+            server_endpoint_id,
+            protocol_version,
+            keepalive: keepalive_from_env(),
+            abandoned: HashSet::new(),
+            _quic: quic,
         })
     }
 
+    /// The reflector's endpoint ID, if it presented one during the handshake.
+    ///
+    /// Callers pairing with a new reflector should show this to the operator
+    /// (or compare it against an expected value) before sending the token.
+    pub fn server_endpoint_id(&self) -> Option<&str> {
+        self.server_endpoint_id.as_deref()
+    }
+
+    /// The protocol version negotiated with the reflector, e.g. `"1.0"`.
+    pub fn protocol_version(&self) -> &str {
+        &self.protocol_version
+    }
+
+    /// Whether the reflector answers `Ping` (it negotiated 1.1 or later).
+    pub fn supports_keepalive(&self) -> bool {
+        version_at_least(&self.protocol_version, KEEPALIVE_VERSION)
+    }
+
+    /// Set the keepalive interval (`PP_REFLECTOR_KEEPALIVE_SECS` by
+    /// default); zero disables keepalives.
+    pub fn set_keepalive(&mut self, interval: Duration) {
+        self.keepalive = interval;
+    }
+
+    /// Ping the reflector and return the round-trip time.
+    pub async fn ping(&mut self) -> Result<Duration> {
+        if !self.supports_keepalive() {
+            anyhow::bail!("reflector speaks protocol {}, ping needs {}", self.protocol_version, KEEPALIVE_VERSION);
+        }
+        let req_id = self.next_id();
+        let sent = Instant::now();
+        self.framed
+            .send(LinkMessage { request_id: req_id.clone(), payload: MessagePayload::Ping })
+            .await
+            .context("failed to send Ping")?;
+        match self.expect_response(&req_id).await? {
+            MessagePayload::Pong => Ok(sent.elapsed()),
+            MessagePayload::Error(e) => Err(anyhow!("reflector error {}: {}", e.code, e.message)),
+            other => Err(anyhow!("expected Pong, got {:?}", other)),
+        }
+    }
+
     /// Send a PairRequest and await the response.
     pub async fn pair(&mut self, token: String) -> Result<rpc::PairResponse> {
         let req_id = self.next_id();
@@ -112,18 +312,40 @@ impl ReflectorClient {
 
     /// Request a throughput session.
     pub async fn request_throughput_session(&mut self, duration_sec: u64, streams: u32, reverse: bool) -> Result<rpc::SessionGrant> {
+        self.request_session(rpc::SessionRequest {
+            test_type: rpc::TestType::Throughput,
+            params: rpc::TestParams {
+                duration_sec,
+                protocol: Some("tcp".to_string()),
+                streams: Some(streams),
+                reverse: Some(reverse),
+                mtu_probe: None,
+            },
+        })
+        .await
+    }
+
+    /// Request a UDP echo session. `mtu_probe` asks the reflector to send its
+    /// echoes with don't-fragment set, for [`udp_echo::UdpEchoMode::SizeRamp`].
+    pub async fn request_udp_echo_session(&mut self, duration_sec: u64, mtu_probe: bool) -> Result<rpc::SessionGrant> {
+        self.request_session(rpc::SessionRequest {
+            test_type: rpc::TestType::UdpEcho,
+            params: rpc::TestParams {
+                duration_sec,
+                protocol: Some("udp".to_string()),
+                streams: None,
+                reverse: None,
+                mtu_probe: Some(mtu_probe),
+            },
+        })
+        .await
+    }
+
+    async fn request_session(&mut self, request: rpc::SessionRequest) -> Result<rpc::SessionGrant> {
         let req_id = self.next_id();
         let msg = LinkMessage {
             request_id: req_id.clone(),
-            payload: MessagePayload::SessionRequest(rpc::SessionRequest {
-                test_type: rpc::TestType::Throughput,
-                params: rpc::TestParams {
-                    duration_sec,
-                    protocol: Some("tcp".to_string()),
-                    streams: Some(streams),
-                    reverse: Some(reverse),
-                },
-            }),
+            payload: MessagePayload::SessionRequest(request),
         };
 
         self.framed.send(msg).await.context("failed to send SessionRequest")?;
@@ -137,6 +359,188 @@ impl ReflectorClient {
         }
     }
 
+    /// Run a UDP echo test against a granted `UdpEcho` session.
+    ///
+    /// Datagrams go straight to the granted port even for tunneled grants;
+    /// UDP can't be carried over the control connection, so this needs only
+    /// the grant, not the client, and can run while the client is idle.
+    pub async fn run_udp_echo(
+        grant: &rpc::SessionGrant,
+        reflector_ip: IpAddr,
+        mode: &udp_echo::UdpEchoMode,
+    ) -> Result<udp_echo::UdpEchoReport> {
+        udp_echo::run(SocketAddr::new(reflector_ip, grant.port), mode).await
+    }
+
+    /// Close a test session, asking for the reflector's side of the result.
+    ///
+    /// Returns `None` if the reflector has no engine result for the session
+    /// (or predates `SessionSummary` and replies with a plain `Ok`).
+    pub async fn close_session(&mut self, test_id: &str) -> Result<Option<rpc::SessionSummary>> {
+        let req_id = self.next_id();
+        let msg = LinkMessage {
+            request_id: req_id.clone(),
+            payload: MessagePayload::SessionClose(rpc::SessionClose {
+                test_id: test_id.to_string(),
+                want_summary: true,
+            }),
+        };
+
+        self.framed.send(msg).await.context("failed to send SessionClose")?;
+
+        let resp = self.expect_response(&req_id).await?;
+        match resp {
+            MessagePayload::SessionSummary(s) => Ok(Some(s)),
+            MessagePayload::Ok => Ok(None),
+            MessagePayload::Error(e) => Err(anyhow!("reflector error {}: {}", e.code, e.message)),
+            other => Err(anyhow!("expected SessionSummary, got {:?}", other)),
+        }
+    }
+
+    /// Fetch the reflector's current status.
+    pub async fn get_status(&mut self) -> Result<rpc::StatusSnapshot> {
+        let req_id = self.next_id();
+        let msg = LinkMessage {
+            request_id: req_id.clone(),
+            payload: MessagePayload::GetStatus,
+        };
+
+        self.framed.send(msg).await.context("failed to send GetStatus")?;
+
+        let resp = self.expect_response(&req_id).await?;
+        match resp {
+            MessagePayload::StatusSnapshot(s) => Ok(s),
+            MessagePayload::Error(e) => Err(anyhow!("reflector error {}: {}", e.code, e.message)),
+            other => Err(anyhow!("expected StatusSnapshot, got {:?}", other)),
+        }
+    }
+
+    /// Run `work` against a granted session's data plane.
+    ///
+    /// `work` gets the address iperf3 should connect to: the reflector's data
+    /// port for `direct_ephemeral` grants, or a loopback listener whose
+    /// connections are tunneled over this control connection for `tunneled`
+    /// grants (pumped here until `work` finishes). When the grant asks for a
+    /// token preamble, direct connections also go through a loopback relay
+    /// that opens each one with the session token.
+    pub async fn run_data_plane<T, F, Fut>(
+        &mut self,
+        grant: &rpc::SessionGrant,
+        reflector_ip: IpAddr,
+        work: F,
+    ) -> Result<T>
+    where
+        F: FnOnce(SocketAddr) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        if !grant.is_tunneled() {
+            let remote = SocketAddr::new(reflector_ip, grant.port);
+            if !grant.token_preamble {
+                return self.with_keepalive(work(remote)).await;
+            }
+            let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+                .await
+                .context("failed to bind token relay listener")?;
+            let local_addr = listener.local_addr()?;
+            let relay = tokio::spawn(token_relay(listener, remote, grant.token.clone()));
+            let result = self.with_keepalive(work(local_addr)).await;
+            relay.abort();
+            return result;
+        }
+
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .await
+            .context("failed to bind tunnel listener")?;
+        let local_addr = listener.local_addr()?;
+        let (outbound, mut outbound_rx) = mpsc::channel::<Bytes>(tunnel::OUTBOUND_QUEUE);
+        let mut tunnel = Tunnel::new(outbound);
+        // TunnelOpen request IDs awaiting a reply, by channel.
+        let mut pending: HashMap<String, u32> = HashMap::new();
+        let mut next_channel = 1u32;
+
+        let mut keepalive = Keepalive::new(self);
+        let work = work(local_addr);
+        tokio::pin!(work);
+        loop {
+            tokio::select! {
+                result = &mut work => {
+                    self.abandoned.extend(pending.into_keys());
+                    self.abandoned.extend(keepalive.outstanding.take().map(|(id, _)| id));
+                    return result;
+                }
+
+                _ = keepalive.tick() => keepalive.ping(self).await?,
+
+                accepted = listener.accept() => {
+                    let (stream, _) = accepted.context("tunnel listener failed")?;
+                    let channel = next_channel;
+                    next_channel += 1;
+                    let req_id = self.next_id();
+                    let msg = LinkMessage {
+                        request_id: req_id.clone(),
+                        payload: MessagePayload::TunnelOpen(rpc::TunnelOpen {
+                            test_id: grant.test_id.clone(),
+                            channel,
+                        }),
+                    };
+                    self.framed.send(msg).await.context("failed to send TunnelOpen")?;
+                    tunnel.open(channel, &grant.test_id, stream);
+                    pending.insert(req_id, channel);
+                }
+
+                Some(payload) = outbound_rx.recv() => {
+                    self.framed.send(payload).await.context("failed to send tunnel data")?;
+                }
+
+                frame = self.framed.next() => {
+                    match frame.ok_or_else(|| anyhow!("connection closed"))?.context("failed to decode frame")? {
+                        Frame::Data { channel, data } => tunnel.deliver(channel, data).await,
+                        Frame::Message(msg) if keepalive.is_pong(&msg) => {}
+                        Frame::Message(msg) => match (pending.remove(&msg.request_id), msg.payload) {
+                            (Some(channel), MessagePayload::Error(e)) => {
+                                warn!(channel, code = e.code, "reflector refused tunnel channel: {}", e.message);
+                                tunnel.close(channel);
+                            }
+                            (Some(_), _) => {}
+                            (None, _) => debug!("ignoring message with id {} during tunnel", msg.request_id),
+                        },
+                    }
+                }
+            }
+        }
+    }
+
+    /// Run `work` while keeping the otherwise idle control connection alive.
+    async fn with_keepalive<T>(&mut self, work: impl Future<Output = Result<T>>) -> Result<T> {
+        let mut keepalive = Keepalive::new(self);
+        if keepalive.timer.is_none() {
+            return work.await;
+        }
+        tokio::pin!(work);
+        loop {
+            tokio::select! {
+                result = &mut work => {
+                    self.abandoned.extend(keepalive.outstanding.take().map(|(id, _)| id));
+                    return result;
+                }
+
+                _ = keepalive.tick() => keepalive.ping(self).await?,
+
+                frame = self.framed.next() => {
+                    match frame.ok_or_else(|| anyhow!("connection closed"))?.context("failed to decode frame")? {
+                        Frame::Data { .. } => {}
+                        Frame::Message(msg) if keepalive.is_pong(&msg) => {}
+                        Frame::Message(msg) if self.abandoned.remove(&msg.request_id) => {}
+                        Frame::Message(msg) => anyhow::bail!(
+                            "reflector sent unsolicited message {:?} during a test",
+                            msg.request_id
+                        ),
+                    }
+                }
+            }
+        }
+    }
+
     fn next_id(&mut self) -> String {
         let id = format!("req-{}", self.request_counter);
         self.request_counter += 1;
@@ -145,19 +549,139 @@ impl ReflectorClient {
 
     async fn expect_response(&mut self, req_id: &str) -> Result<MessagePayload> {
         loop {
-            let frame = self.framed.next().await
+            let frame = match self.framed.next().await
                 .ok_or_else(|| anyhow!("connection closed"))?
-                .context("failed to decode frame")?;
+                .context("failed to decode frame")?
+            {
+                Frame::Message(m) => m,
+                // Late data for a tunnel channel that has already finished.
+                Frame::Data { .. } => continue,
+            };
 
             if frame.request_id == req_id {
                 return Ok(frame.payload);
             }
-            // Ignore other messages (like heartbeats if we had them)
-            debug!("ignoring message with id {} (waiting for {})", frame.request_id, req_id);
+            if self.abandoned.remove(&frame.request_id) {
+                debug!("ignoring late reply to {} (waiting for {})", frame.request_id, req_id);
+                continue;
+            }
+            // Requests are answered in order: anything else is a stray frame,
+            // and accepting it could pair a reply with the wrong request.
+            anyhow::bail!(
+                "reflector sent a reply to request {:?} while {:?} was outstanding",
+                frame.request_id, req_id
+            );
         }
     }
 }
 
+/// Keepalive pings sent while waiting on a test.
+struct Keepalive {
+    timer: Option<tokio::time::Interval>,
+    interval: Duration,
+    /// The unanswered ping, if any, and when it was sent.
+    outstanding: Option<(String, Instant)>,
+}
+
+impl Keepalive {
+    /// Disabled when the interval is zero or the reflector predates `Ping`.
+    fn new(client: &ReflectorClient) -> Self {
+        let interval = client.keepalive;
+        let timer = (!interval.is_zero() && client.supports_keepalive()).then(|| {
+            let mut timer = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+            timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            timer
+        });
+        Self { timer, interval, outstanding: None }
+    }
+
+    /// Wait for the next ping; never fires when disabled.
+    async fn tick(&mut self) {
+        match self.timer.as_mut() {
+            Some(timer) => {
+                timer.tick().await;
+            }
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Send a ping, unless one is still unanswered: fail once it has been
+    /// for [`KEEPALIVE_MISSES`] intervals.
+    async fn ping(&mut self, client: &mut ReflectorClient) -> Result<()> {
+        if let Some((_, sent)) = &self.outstanding {
+            if sent.elapsed() >= self.interval * KEEPALIVE_MISSES {
+                anyhow::bail!(
+                    "reflector stopped answering keepalives (no pong for {:.0?}); connection lost",
+                    sent.elapsed()
+                );
+            }
+            return Ok(());
+        }
+        let request_id = client.next_id();
+        client
+            .framed
+            .send(LinkMessage { request_id: request_id.clone(), payload: MessagePayload::Ping })
+            .await
+            .context("failed to send keepalive")?;
+        self.outstanding = Some((request_id, Instant::now()));
+        Ok(())
+    }
+
+    /// Consume `msg` if it answers the outstanding ping.
+    fn is_pong(&mut self, msg: &LinkMessage) -> bool {
+        let answers = matches!(msg.payload, MessagePayload::Pong)
+            && self.outstanding.as_ref().is_some_and(|(id, _)| *id == msg.request_id);
+        if answers {
+            self.outstanding = None;
+        }
+        answers
+    }
+}
+
+/// `PP_REFLECTOR_KEEPALIVE_SECS` (default [`DEFAULT_KEEPALIVE`], `0` disables).
+fn keepalive_from_env() -> Duration {
+    match std::env::var("PP_REFLECTOR_KEEPALIVE_SECS") {
+        Ok(v) => match v.trim().parse::<u64>() {
+            Ok(secs) => Duration::from_secs(secs),
+            Err(_) => {
+                warn!(value = %v, "Ignoring invalid PP_REFLECTOR_KEEPALIVE_SECS");
+                DEFAULT_KEEPALIVE
+            }
+        },
+        Err(_) => DEFAULT_KEEPALIVE,
+    }
+}
+
+/// Compare `major.minor` versions; unparsable ones are never at least `min`.
+fn version_at_least(version: &str, min: &str) -> bool {
+    let parse = |v: &str| -> Option<(u16, u16)> {
+        let (major, minor) = v.trim().split_once('.')?;
+        Some((major.parse().ok()?, minor.parse().ok()?))
+    };
+    matches!((parse(version), parse(min)), (Some(v), Some(m)) if v >= m)
+}
+
+/// Accept local data connections and forward each to the reflector's gated
+/// data port, opening it with the session token.
+async fn token_relay(listener: TcpListener, remote: SocketAddr, token: String) {
+    while let Ok((mut local, _)) = listener.accept().await {
+        let token = token.clone();
+        tokio::spawn(async move {
+            let result = async {
+                let mut upstream = TcpStream::connect(remote)
+                    .await
+                    .with_context(|| format!("failed to connect to data port {}", remote))?;
+                upstream.write_all(token.as_bytes()).await?;
+                tokio::io::copy_bidirectional(&mut local, &mut upstream).await?;
+                anyhow::Ok(())
+            };
+            if let Err(e) = result.await {
+                debug!("data-plane relay connection ended: {:#}", e);
+            }
+        });
+    }
+}
+
 /// A verifier that accepts any server certificate (dangerous!).
 /// Used for pairing when we don't know the server's ID yet.
 #[derive(Debug)]
@@ -201,3 +725,148 @@ impl ServerCertVerifier for BlindVerifier {
         ]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Answer the Hello on `server` with `version`, then return the framed stream.
+    async fn fake_reflector(server: tokio::io::DuplexStream, version: &str) -> Framed<tokio::io::DuplexStream, LinkCodec> {
+        let mut framed = Framed::new(server, LinkCodec::new());
+        let hello = match framed.next().await.unwrap().unwrap() {
+            Frame::Message(m) => m,
+            other => panic!("expected Hello, got {:?}", other),
+        };
+        let reply: MessagePayload = serde_json::from_value(serde_json::json!({
+            "type": "server_hello",
+            "version": version,
+            "features": [],
+            "policy_summary": {
+                "max_test_duration_sec": 60,
+                "max_concurrent_tests": 1,
+                "max_tests_per_hour": 10,
+                "allowed_test_types": ["throughput"]
+            }
+        }))
+        .unwrap();
+        framed.send(LinkMessage { request_id: hello.request_id, payload: reply }).await.unwrap();
+        framed
+    }
+
+    fn pair_response() -> MessagePayload {
+        MessagePayload::PairResponse(rpc::PairResponse {
+            success: true,
+            message: "paired".into(),
+            endpoint_id: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_mismatched_response_id_rejected() {
+        let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+        let server = tokio::spawn(async move {
+            let mut framed = fake_reflector(server_io, "1.0").await;
+            let request = match framed.next().await.unwrap().unwrap() {
+                Frame::Message(m) => m,
+                other => panic!("expected PairRequest, got {:?}", other),
+            };
+            // Answer under someone else's ID.
+            let stray = LinkMessage { request_id: format!("{}-other", request.request_id), payload: pair_response() };
+            framed.send(stray).await.unwrap();
+        });
+
+        let mut client = ReflectorClient::handshake(Box::new(client_io), None, None).await.unwrap();
+        let err = client.pair("ABCD1234".into()).await.unwrap_err();
+        assert!(err.to_string().contains("while \"req-1\" was outstanding"), "{err}");
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_late_reply_to_abandoned_request_skipped() {
+        let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+        let server = tokio::spawn(async move {
+            let mut framed = fake_reflector(server_io, "1.0").await;
+            let request = match framed.next().await.unwrap().unwrap() {
+                Frame::Message(m) => m,
+                other => panic!("expected PairRequest, got {:?}", other),
+            };
+            framed.send(LinkMessage { request_id: "req-0".into(), payload: MessagePayload::Ok }).await.unwrap();
+            framed.send(LinkMessage { request_id: request.request_id, payload: pair_response() }).await.unwrap();
+        });
+
+        let mut client = ReflectorClient::handshake(Box::new(client_io), None, None).await.unwrap();
+        client.abandoned.insert("req-0".into());
+        assert!(client.pair("ABCD1234".into()).await.unwrap().success);
+        assert!(client.abandoned.is_empty());
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_ping_needs_1_1() {
+        let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+        let server = tokio::spawn(async move {
+            let mut framed = fake_reflector(server_io, "1.1").await;
+            let ping = match framed.next().await.unwrap().unwrap() {
+                Frame::Message(m) => m,
+                other => panic!("expected Ping, got {:?}", other),
+            };
+            assert!(matches!(ping.payload, MessagePayload::Ping));
+            framed.send(LinkMessage { request_id: ping.request_id, payload: MessagePayload::Pong }).await.unwrap();
+        });
+        let mut client = ReflectorClient::handshake(Box::new(client_io), None, None).await.unwrap();
+        assert!(client.supports_keepalive());
+        client.ping().await.unwrap();
+        server.await.unwrap();
+
+        // A 1.0 reflector would drop the connection on an unknown message.
+        let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+        let server = tokio::spawn(async move { fake_reflector(server_io, "1.0").await });
+        let mut client = ReflectorClient::handshake(Box::new(client_io), None, None).await.unwrap();
+        assert!(!client.supports_keepalive());
+        assert!(client.ping().await.is_err());
+        drop(server.await.unwrap());
+
+        assert!(version_at_least("1.10", "1.1"));
+        assert!(!version_at_least("bogus", "1.1"));
+    }
+
+    #[tokio::test]
+    async fn test_keepalive_detects_dead_connection() {
+        let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+        let server = tokio::spawn(async move {
+            // Reads pings but never answers, like a connection lost upstream.
+            let mut framed = fake_reflector(server_io, "1.1").await;
+            let mut pings = 0;
+            while let Some(Ok(Frame::Message(msg))) = framed.next().await {
+                assert!(matches!(msg.payload, MessagePayload::Ping));
+                pings += 1;
+            }
+            pings
+        });
+
+        let mut client = ReflectorClient::handshake(Box::new(client_io), None, None).await.unwrap();
+        client.set_keepalive(Duration::from_millis(50));
+        let started = Instant::now();
+        let err = client
+            .with_keepalive(async {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+                Ok(())
+            })
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("stopped answering keepalives"), "{err}");
+        assert!(started.elapsed() < Duration::from_secs(2));
+
+        // One ping, never repeated while it is outstanding.
+        drop(client);
+        assert_eq!(server.await.unwrap(), 1);
+    }
+
+    #[test]
+    fn test_transport_parse() {
+        assert_eq!("tcp".parse::<Transport>().unwrap(), Transport::Tcp);
+        assert_eq!(" QUIC ".parse::<Transport>().unwrap(), Transport::Quic);
+        assert!("sctp".parse::<Transport>().is_err());
+        assert_eq!(Transport::default(), Transport::Tcp);
+    }
+}
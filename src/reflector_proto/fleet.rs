@@ -0,0 +1,93 @@
+//! Fleet-wide queries across all paired reflectors.
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::reflector_proto::{
+    client::ReflectorClient, identity::Identity, peers::PeerStore, rpc::StatusSnapshot,
+};
+
+/// Upper bound on connect + handshake + `GetStatus` per reflector.
+pub const FLEET_QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Coarse health of one reflector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FleetState {
+    /// Reachable and idle.
+    Up,
+    /// Reachable but running a test.
+    Busy,
+    /// Unreachable, handshake failed, or errored.
+    Down,
+}
+
+impl FleetState {
+    pub fn of(result: &Result<StatusSnapshot>) -> Self {
+        match result {
+            Ok(s) if s.active_test.is_some() => Self::Busy,
+            Ok(_) => Self::Up,
+            Err(_) => Self::Down,
+        }
+    }
+}
+
+/// Query `GetStatus` on every stored reflector concurrently.
+///
+/// Each entry carries its own result; one unreachable reflector never
+/// aborts the sweep. Results are in store order.
+pub async fn status_all(
+    store: &PeerStore,
+    identity: &Identity,
+) -> Vec<(String, Result<StatusSnapshot>)> {
+    let queries = store.reflectors().iter().map(|r| async move {
+        let result = query_status(&r.address, identity).await;
+        (r.nickname.clone(), result)
+    });
+    futures::future::join_all(queries).await
+}
+
+async fn query_status(address: &str, identity: &Identity) -> Result<StatusSnapshot> {
+    let addr: SocketAddr = address
+        .parse()
+        .with_context(|| format!("invalid reflector address: {}", address))?;
+
+    tokio::time::timeout(FLEET_QUERY_TIMEOUT, async {
+        let mut client = ReflectorClient::connect(addr, identity).await?;
+        client.get_status().await
+    })
+    .await
+    .with_context(|| format!("timed out after {}s", FLEET_QUERY_TIMEOUT.as_secs()))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reflector_proto::peers::StoredReflector;
+
+    #[tokio::test]
+    async fn test_unreachable_reflectors_do_not_abort_sweep() {
+        // The binary installs this in main; tests have to do it themselves.
+        rustls::crypto::ring::default_provider().install_default().ok();
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = PeerStore::load(&dir.path().join("reflectors.json")).unwrap();
+        for (name, addr) in [("bad-addr", "not-an-address"), ("closed", "127.0.0.1:1")] {
+            store.upsert(StoredReflector {
+                nickname: name.to_string(),
+                address: addr.to_string(),
+                endpoint_id: None,
+                paired_at: "2026-01-01T00:00:00Z".to_string(),
+            });
+        }
+
+        let identity = Identity::generate();
+        let results = status_all(&store, &identity).await;
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].0, "bad-addr");
+        assert!(results.iter().all(|(_, r)| FleetState::of(r) == FleetState::Down));
+    }
+}
@@ -147,6 +147,28 @@ fn crockford_luhn_validate(encoded_with_check: &str) -> bool {
 // EndpointId
 // ---------------------------------------------------------------------------
 
+/// Number of 4-character Crockford groups kept in a short fingerprint.
+///
+/// Three groups are 60 bits of the public key, plus the full ID's check
+/// digit (5 bits that depend on the whole key). Accidental collisions only
+/// become likely around 2^32 (~4 billion) endpoints, but an attacker who can
+/// grind ~2^65 keys could forge a matching fingerprint, so fingerprints are
+/// for eyeball comparison and never replace the full ID.
+pub const FINGERPRINT_GROUPS: usize = 3;
+
+/// Short fingerprint of a formatted endpoint ID string, such as one a peer
+/// presented (see [`EndpointId::short_fingerprint`]).
+pub fn short_fingerprint(id: &str) -> String {
+    let parts: Vec<&str> = id.split('-').collect();
+    if parts.len() <= FINGERPRINT_GROUPS + 2 {
+        return id.to_string();
+    }
+    let mut out = parts[..=FINGERPRINT_GROUPS].join("-");
+    out.push('-');
+    out.push_str(parts[parts.len() - 1]);
+    out
+}
+
 /// A human-readable identifier for a PacketParamedic endpoint, derived from
 /// the Ed25519 public key.
 ///
@@ -180,6 +202,16 @@ impl EndpointId {
         &self.0
     }
 
+    /// Short fingerprint for humans to compare: the first
+    /// [`FINGERPRINT_GROUPS`] groups plus the check digit, e.g.
+    /// `PP-5R6Q-2M1K-9D3F-C`.
+    ///
+    /// Only for display and verbal comparison; the full ID stays the
+    /// canonical identifier everywhere it is stored or checked.
+    pub fn short_fingerprint(&self) -> String {
+        short_fingerprint(&self.0)
+    }
+
     /// Validate the Luhn check digit of this endpoint ID.
     pub fn validate(&self) -> bool {
         // Strip the "PP-" prefix, remove dashes, then validate.
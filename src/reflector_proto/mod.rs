@@ -3,3 +3,8 @@ pub mod wire;
 pub mod identity;
 pub mod cert;
 pub mod client;
+pub mod fleet;
+pub mod pairing;
+pub mod peers;
+pub mod tunnel;
+pub mod udp_echo;
@@ -0,0 +1,140 @@
+//! Pairing codes generated on the Paramedic side (reverse pairing).
+//!
+//! Normally the reflector generates the code (`reflector pair`) and the
+//! operator types it into `pair-reflector --token`. With
+//! `pair-reflector --generate` the roles flip for an operator standing at the
+//! Paramedic: this side generates the code, the operator enters it on the
+//! reflector with `reflector pair --code`, and this side keeps presenting it
+//! until the reflector accepts it.
+//!
+//! The side that generated a code is authoritative for it. Its TTL starts at
+//! generation and is never extended: the adopting side is told the
+//! *remaining* TTL (see [`PendingCode::reflector_command`]), so both windows
+//! close at the same moment, and this side stops presenting the code then.
+//!
+//! Either way, the operator gets to see the reflector's endpoint ID before the
+//! pairing completes, or passes the expected one up front; see
+//! [`check_endpoint_id`].
+
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use rand::Rng;
+
+/// Code alphabet, identical to the reflector's: uppercase alphanumeric
+/// without the ambiguous 0/O and 1/I/L, so codes survive being read aloud.
+pub const CODE_CHARSET: &[u8] = b"ABCDEFGHJKMNPQRSTUVWXYZ23456789";
+
+/// Length of a pairing code.
+pub const CODE_LEN: usize = 8;
+
+/// Default lifetime of a generated code (the reflector's default too).
+pub const DEFAULT_TTL: Duration = Duration::from_secs(600);
+
+/// How often the code is re-presented while waiting for the reflector.
+pub const RETRY_INTERVAL: Duration = Duration::from_secs(3);
+
+/// A locally generated code waiting to be adopted by a reflector.
+#[derive(Debug, Clone)]
+pub struct PendingCode {
+    pub code: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl PendingCode {
+    /// Generate a fresh code valid for `ttl` from now.
+    pub fn generate(ttl: Duration) -> Self {
+        let mut rng = rand::thread_rng();
+        let code = (0..CODE_LEN)
+            .map(|_| CODE_CHARSET[rng.gen_range(0..CODE_CHARSET.len())] as char)
+            .collect();
+        Self {
+            code,
+            expires_at: Utc::now()
+                + chrono::Duration::from_std(ttl)
+                    .unwrap_or_else(|_| chrono::Duration::from_std(DEFAULT_TTL).unwrap()),
+        }
+    }
+
+    /// Time left before the code expires (zero once expired).
+    pub fn remaining(&self) -> Duration {
+        (self.expires_at - Utc::now()).to_std().unwrap_or_default()
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.remaining().is_zero()
+    }
+
+    /// The command that makes the reflector adopt this code for exactly the
+    /// time it has left here.
+    pub fn reflector_command(&self) -> String {
+        format!(
+            "reflector pair --code {} --ttl {}s",
+            self.code,
+            self.remaining().as_secs()
+        )
+    }
+}
+
+/// Whether `code` has the shape both sides accept (case-insensitive).
+pub fn is_valid_code(code: &str) -> bool {
+    code.len() == CODE_LEN
+        && code
+            .to_ascii_uppercase()
+            .bytes()
+            .all(|b| CODE_CHARSET.contains(&b))
+}
+
+/// Compare the endpoint ID a reflector presented against the one the
+/// operator expects (case-insensitive, since IDs are often typed by hand).
+/// A reflector that presents no ID at all fails the check.
+pub fn check_endpoint_id(expected: &str, presented: Option<&str>) -> Result<()> {
+    match presented {
+        Some(id) if id.trim().eq_ignore_ascii_case(expected.trim()) => Ok(()),
+        Some(id) => bail!("reflector endpoint ID mismatch: expected {}, got {}", expected.trim(), id),
+        None => bail!("reflector did not present an endpoint ID (expected {})", expected.trim()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_code_shape() {
+        let pending = PendingCode::generate(Duration::from_secs(300));
+        assert!(is_valid_code(&pending.code));
+        assert!(is_valid_code(&pending.code.to_lowercase()));
+        assert!(!is_valid_code("ABCD123"));
+        assert!(!is_valid_code("ABCD1234")); // '1' is excluded
+    }
+
+    #[test]
+    fn test_remaining_ttl_is_handed_over() {
+        let pending = PendingCode::generate(Duration::from_secs(300));
+        let left = pending.remaining().as_secs();
+        assert!((298..=300).contains(&left));
+        assert!(!pending.is_expired());
+
+        let cmd = pending.reflector_command();
+        assert!(cmd.starts_with(&format!("reflector pair --code {} --ttl ", pending.code)));
+        assert!(cmd.ends_with('s'));
+
+        let expired = PendingCode::generate(Duration::ZERO);
+        assert!(expired.is_expired());
+        assert!(expired.reflector_command().ends_with("--ttl 0s"));
+    }
+
+    #[test]
+    fn test_check_endpoint_id() {
+        let id = "PP-ABCD-EFGH-JKLM-NPQR-STUV-WXYZ-2345-6789";
+        assert!(check_endpoint_id(id, Some(id)).is_ok());
+        assert!(check_endpoint_id(&id.to_lowercase(), Some(id)).is_ok());
+        assert!(check_endpoint_id(" PP-ABCD-EFGH-JKLM-NPQR-STUV-WXYZ-2345-6789\n", Some(id)).is_ok());
+
+        let err = check_endpoint_id(id, Some("PP-ZZZZ-ZZZZ-ZZZZ-ZZZZ-ZZZZ-ZZZZ-ZZZZ-ZZZZ")).unwrap_err();
+        assert!(err.to_string().contains("mismatch"));
+        assert!(check_endpoint_id(id, None).is_err());
+    }
+}
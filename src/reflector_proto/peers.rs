@@ -0,0 +1,167 @@
+//! Paired reflector store on the Paramedic side.
+//!
+//! Reflectors are recorded on successful pairing so fleet-wide commands can
+//! reach all of them without re-entering addresses. Stored as JSON next to
+//! the identity key in `~/.packetparamedic/`.
+
+use std::fs;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// File name of the store inside the Paramedic data directory.
+pub const PEER_STORE_FILE: &str = "reflectors.json";
+
+/// A reflector this device has paired with.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct StoredReflector {
+    /// Operator-chosen label; defaults to the address.
+    pub nickname: String,
+    /// `host:port` of the reflector's control port.
+    pub address: String,
+    /// Reflector endpoint ID reported during pairing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint_id: Option<String>,
+    /// RFC 3339 time of pairing.
+    pub paired_at: String,
+}
+
+/// JSON-backed list of paired reflectors.
+#[derive(Debug)]
+pub struct PeerStore {
+    path: PathBuf,
+    reflectors: Vec<StoredReflector>,
+}
+
+impl PeerStore {
+    /// Load the store from `path`; a missing file is an empty store.
+    pub fn load(path: &Path) -> Result<Self> {
+        let reflectors = if path.exists() {
+            let text = fs::read_to_string(path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            serde_json::from_str(&text)
+                .with_context(|| format!("failed to parse {}", path.display()))?
+        } else {
+            Vec::new()
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            reflectors,
+        })
+    }
+
+    /// Write the store back to disk.
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+        let text = serde_json::to_string_pretty(&self.reflectors)?;
+        fs::write(&self.path, text)
+            .with_context(|| format!("failed to write {}", self.path.display()))
+    }
+
+    /// Add a reflector, replacing any entry with the same nickname or address.
+    pub fn upsert(&mut self, reflector: StoredReflector) {
+        self.reflectors
+            .retain(|r| r.nickname != reflector.nickname && r.address != reflector.address);
+        self.reflectors.push(reflector);
+    }
+
+    pub fn reflectors(&self) -> &[StoredReflector] {
+        &self.reflectors
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.reflectors.is_empty()
+    }
+
+    /// Look up a reflector by nickname or address.
+    pub fn find(&self, name_or_addr: &str) -> Option<&StoredReflector> {
+        self.reflectors
+            .iter()
+            .find(|r| r.nickname == name_or_addr || r.address == name_or_addr)
+    }
+
+    /// First paired reflector whose address is on a local network
+    /// (RFC 1918, link-local or IPv6 unique-local).
+    pub fn first_lan(&self) -> Option<&StoredReflector> {
+        self.reflectors.iter().find(|r| {
+            r.address
+                .parse::<SocketAddr>()
+                .map(|a| is_lan_ip(a.ip()))
+                .unwrap_or(false)
+        })
+    }
+}
+
+fn is_lan_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => v4.is_private() || v4.is_link_local(),
+        IpAddr::V6(v6) => {
+            let first = v6.segments()[0];
+            (first & 0xfe00) == 0xfc00 || (first & 0xffc0) == 0xfe80
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reflector(nickname: &str, address: &str) -> StoredReflector {
+        StoredReflector {
+            nickname: nickname.to_string(),
+            address: address.to_string(),
+            endpoint_id: None,
+            paired_at: "2026-01-01T00:00:00Z".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_missing_store_is_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = PeerStore::load(&dir.path().join(PEER_STORE_FILE)).unwrap();
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn test_roundtrip_and_upsert() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(PEER_STORE_FILE);
+
+        let mut store = PeerStore::load(&path).unwrap();
+        store.upsert(reflector("home", "10.0.0.2:4000"));
+        store.upsert(reflector("office", "10.1.0.2:4000"));
+        // Re-pairing the same address under a new name replaces it.
+        store.upsert(reflector("lab", "10.0.0.2:4000"));
+        store.save().unwrap();
+
+        let loaded = PeerStore::load(&path).unwrap();
+        let names: Vec<&str> = loaded.reflectors().iter().map(|r| r.nickname.as_str()).collect();
+        assert_eq!(names, vec!["office", "lab"]);
+    }
+
+    #[test]
+    fn test_find_and_first_lan() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = PeerStore::load(&dir.path().join(PEER_STORE_FILE)).unwrap();
+        store.upsert(reflector("cloud", "203.0.113.10:4000"));
+        store.upsert(reflector("bad", "not-an-addr"));
+        store.upsert(reflector("home", "192.168.1.20:4000"));
+        store.upsert(reflector("v6", "[fd00::20]:4000"));
+
+        assert_eq!(store.first_lan().unwrap().nickname, "home");
+        assert_eq!(store.find("203.0.113.10:4000").unwrap().nickname, "cloud");
+        assert_eq!(store.find("v6").unwrap().address, "[fd00::20]:4000");
+        assert!(store.find("missing").is_none());
+
+        let mut wan_only = PeerStore::load(&dir.path().join("other.json")).unwrap();
+        wan_only.upsert(reflector("cloud", "203.0.113.10:4000"));
+        assert!(wan_only.first_lan().is_none());
+        assert!(is_lan_ip("fe80::1".parse().unwrap()));
+    }
+}
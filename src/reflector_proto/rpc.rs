@@ -16,12 +16,17 @@ pub enum MessagePayload {
     SessionGrant(SessionGrant),
     SessionDeny(SessionDeny),
     SessionClose(SessionClose),
+    SessionSummary(SessionSummary),
+    TunnelOpen(TunnelOpen),
     GetStatus,
     StatusSnapshot(StatusSnapshot),
     PairRequest(PairRequest),
     PairResponse(PairResponse),
     GetPathMeta,
     PathMeta(PathMeta),
+    /// Keepalive, protocol 1.1 and later.
+    Ping,
+    Pong,
     Ok,
     Error(ErrorResponse),
 }
@@ -29,16 +34,29 @@ pub enum MessagePayload {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Hello {
     pub version: String,
+    /// Every protocol version we speak; the reflector picks the highest
+    /// one it shares (older reflectors ignore this and answer `version`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub versions: Vec<String>,
     pub features: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServerHello {
+    /// Protocol version negotiated for this connection.
     pub version: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub versions: Vec<String>,
     pub features: Vec<String>,
     pub policy_summary: PolicySummary,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub network_position: Option<String>,
+    /// Public address families (`ipv4`, `ipv6`, `dual_stack`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reachable_via: Option<String>,
+    /// The reflector's endpoint ID (older reflectors don't send it).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint_id: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -68,6 +86,9 @@ pub struct TestParams {
     pub protocol: Option<String>,
     pub streams: Option<u32>,
     pub reverse: Option<bool>,
+    /// Ask for don't-fragment echoes (UDP echo size ramp).
+    #[serde(default)]
+    pub mtu_probe: Option<bool>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -77,6 +98,39 @@ pub struct SessionGrant {
     pub port: u16,
     pub token: String,
     pub expires_at: String,
+    /// Data connections must open with `token` (direct-ephemeral mode).
+    #[serde(default)]
+    pub token_preamble: bool,
+    /// Granted duration, possibly shortened by the reflector (older
+    /// reflectors don't send it).
+    #[serde(default)]
+    pub duration_sec: Option<u64>,
+    /// Streams to run (throughput): ours, or the reflector's default.
+    #[serde(default)]
+    pub streams: Option<u32>,
+}
+
+impl SessionGrant {
+    /// Data plane is carried over the control connection rather than `port`.
+    pub fn is_tunneled(&self) -> bool {
+        self.mode == "tunneled"
+    }
+
+    /// The duration and stream count to run with: what the reflector
+    /// granted, falling back to what was requested.
+    pub fn effective(&self, duration_sec: u64, streams: u32) -> (u64, u32) {
+        (
+            self.duration_sec.unwrap_or(duration_sec),
+            self.streams.unwrap_or(streams),
+        )
+    }
+}
+
+/// Opens a data channel of a tunneled session (see [`super::tunnel`]).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TunnelOpen {
+    pub test_id: String,
+    pub channel: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -99,6 +153,39 @@ pub enum DenyReason {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SessionClose {
     pub test_id: String,
+    #[serde(default)]
+    pub want_summary: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionOutcome {
+    Completed,
+    TimedOut,
+    Error,
+}
+
+/// The reflector's own view of a closed session.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionSummary {
+    pub test_id: String,
+    pub outcome: SessionOutcome,
+    pub bytes_transferred: u64,
+    pub duration_sec: f64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub streams_mbps: Vec<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl SessionSummary {
+    /// Average data-plane rate seen by the reflector, in Mbps.
+    pub fn throughput_mbps(&self) -> f64 {
+        if self.duration_sec <= 0.0 {
+            return 0.0;
+        }
+        self.bytes_transferred as f64 * 8.0 / self.duration_sec / 1_000_000.0
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
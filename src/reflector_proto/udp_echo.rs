@@ -0,0 +1,320 @@
+//! Client side of the reflector's UDP echo test.
+//!
+//! [`UdpEchoMode::Fixed`] sends equal-size datagrams one at a time for loss
+//! and round-trip time. [`UdpEchoMode::SizeRamp`] walks payload sizes upward
+//! with don't-fragment set (the reflector sets it on its echoes too when the
+//! session was requested with `mtu_probe`) until echoes stop coming back,
+//! then bisects the last step. The largest payload echoed intact is what the
+//! whole path, there and back, carries without fragmentation: path-MTU
+//! discovery through the reflector rather than just to the local gateway.
+
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use tokio::net::UdpSocket;
+use tokio::time::Instant;
+
+/// How long to wait for each echo.
+pub const ECHO_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Probes per size before a ramp step counts as failed, so one lost
+/// datagram isn't mistaken for the MTU.
+pub const RAMP_ATTEMPTS: u32 = 3;
+
+/// Sequence number at the start of every payload.
+const MIN_PAYLOAD: usize = 4;
+
+/// Largest IPv4 UDP payload.
+const MAX_PAYLOAD: usize = 65_507;
+
+/// What a UDP echo run sends.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UdpEchoMode {
+    /// `count` datagrams of `size` payload bytes.
+    Fixed { size: usize, count: u32 },
+    /// Payload sizes from `start` to `end` bytes in `step`s.
+    SizeRamp { start: usize, end: usize, step: usize },
+}
+
+impl UdpEchoMode {
+    /// 64 to 1472 bytes (a 1500-byte MTU minus IPv4 and UDP headers).
+    pub fn default_ramp() -> Self {
+        Self::SizeRamp { start: 64, end: 1472, step: 64 }
+    }
+
+    fn validate(&self) -> Result<()> {
+        let check = |size: usize| {
+            if !(MIN_PAYLOAD..=MAX_PAYLOAD).contains(&size) {
+                bail!("payload size {} outside {}..={}", size, MIN_PAYLOAD, MAX_PAYLOAD);
+            }
+            Ok(())
+        };
+        match *self {
+            Self::Fixed { size, .. } => check(size),
+            Self::SizeRamp { start, end, step } => {
+                check(start)?;
+                check(end)?;
+                if start > end || step == 0 {
+                    bail!("invalid size ramp {}..={} step {}", start, end, step);
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Outcome of a UDP echo run.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct UdpEchoReport {
+    pub sent: u32,
+    pub received: u32,
+    pub avg_rtt_ms: Option<f64>,
+    /// Largest payload echoed intact; for a ramp, the path-MTU payload.
+    pub max_payload: Option<usize>,
+    /// Smallest payload that never came back (ramp only).
+    pub first_failed: Option<usize>,
+}
+
+/// Run `mode` against the echo engine at `remote`.
+pub async fn run(remote: SocketAddr, mode: &UdpEchoMode) -> Result<UdpEchoReport> {
+    run_with_timeout(remote, mode, ECHO_TIMEOUT).await
+}
+
+async fn run_with_timeout(remote: SocketAddr, mode: &UdpEchoMode, timeout: Duration) -> Result<UdpEchoReport> {
+    mode.validate()?;
+    let local: SocketAddr = if remote.is_ipv4() {
+        (Ipv4Addr::UNSPECIFIED, 0).into()
+    } else {
+        (Ipv6Addr::UNSPECIFIED, 0).into()
+    };
+    let socket = UdpSocket::bind(local).await.context("failed to bind UDP echo socket")?;
+    socket
+        .connect(remote)
+        .await
+        .with_context(|| format!("failed to reach UDP echo port {}", remote))?;
+
+    let mut prober = Prober {
+        socket,
+        timeout,
+        seq: 0,
+        rtt_total: Duration::ZERO,
+        report: UdpEchoReport::default(),
+    };
+    match *mode {
+        UdpEchoMode::Fixed { size, count } => {
+            for _ in 0..count {
+                if prober.echo(size).await?.is_some() {
+                    prober.report.max_payload = Some(size);
+                }
+            }
+        }
+        UdpEchoMode::SizeRamp { start, end, step } => {
+            set_dont_fragment(&prober.socket, remote.is_ipv6())
+                .context("failed to set don't-fragment on UDP echo socket")?;
+            prober.ramp(start, end, step).await?;
+        }
+    }
+
+    let mut report = prober.report;
+    if report.received > 0 {
+        report.avg_rtt_ms = Some(prober.rtt_total.as_secs_f64() * 1000.0 / report.received as f64);
+    }
+    Ok(report)
+}
+
+struct Prober {
+    socket: UdpSocket,
+    timeout: Duration,
+    seq: u32,
+    rtt_total: Duration,
+    report: UdpEchoReport,
+}
+
+impl Prober {
+    /// Send one datagram of `size` bytes; the RTT if it came back intact.
+    async fn echo(&mut self, size: usize) -> Result<Option<Duration>> {
+        self.seq = self.seq.wrapping_add(1);
+        let mut payload = vec![self.seq as u8; size];
+        payload[..MIN_PAYLOAD].copy_from_slice(&self.seq.to_be_bytes());
+
+        self.report.sent += 1;
+        let started = Instant::now();
+        match self.socket.send(&payload).await {
+            Ok(_) => {}
+            // Larger than the MTU the kernel already knows for this route.
+            Err(e) if e.raw_os_error() == Some(libc::EMSGSIZE) => return Ok(None),
+            Err(e) => return Err(e).context("failed to send UDP echo"),
+        }
+
+        let deadline = started + self.timeout;
+        let mut buf = vec![0u8; size + 1];
+        loop {
+            match tokio::time::timeout_at(deadline, self.socket.recv(&mut buf)).await {
+                Err(_) => return Ok(None),
+                Ok(Err(e)) if e.raw_os_error() == Some(libc::EMSGSIZE) => return Ok(None),
+                Ok(Err(e)) => return Err(e).context("UDP echo port unreachable"),
+                // Anything else is a late echo of an earlier probe.
+                Ok(Ok(len)) if buf[..len] == payload[..] => {
+                    let rtt = started.elapsed();
+                    self.report.received += 1;
+                    self.rtt_total += rtt;
+                    return Ok(Some(rtt));
+                }
+                Ok(Ok(_)) => continue,
+            }
+        }
+    }
+
+    async fn size_passes(&mut self, size: usize) -> Result<bool> {
+        for _ in 0..RAMP_ATTEMPTS {
+            if self.echo(size).await?.is_some() {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    async fn ramp(&mut self, start: usize, end: usize, step: usize) -> Result<()> {
+        let mut size = start;
+        let mut last_ok = None;
+        let failed = loop {
+            if !self.size_passes(size).await? {
+                break size;
+            }
+            last_ok = Some(size);
+            if size >= end {
+                self.report.max_payload = last_ok;
+                return Ok(());
+            }
+            size = (size + step).min(end);
+        };
+
+        // Bisect between the last size that passed and the one that failed.
+        let (mut ok, mut bad) = match last_ok {
+            Some(ok) => (ok, failed),
+            None => {
+                self.report.first_failed = Some(failed);
+                return Ok(());
+            }
+        };
+        while bad - ok > 1 {
+            let mid = ok + (bad - ok) / 2;
+            if self.size_passes(mid).await? {
+                ok = mid;
+            } else {
+                bad = mid;
+            }
+        }
+        self.report.max_payload = Some(ok);
+        self.report.first_failed = Some(bad);
+        Ok(())
+    }
+}
+
+/// Send with DF set and never fragment locally (`IP(V6)_PMTUDISC_DO`).
+#[cfg(target_os = "linux")]
+fn set_dont_fragment(socket: &UdpSocket, ipv6: bool) -> std::io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let (level, name, value) = if ipv6 {
+        (libc::IPPROTO_IPV6, libc::IPV6_MTU_DISCOVER, libc::IPV6_PMTUDISC_DO)
+    } else {
+        (libc::IPPROTO_IP, libc::IP_MTU_DISCOVER, libc::IP_PMTUDISC_DO)
+    };
+    // SAFETY: `value` outlives the call and its size is passed alongside it.
+    let ret = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            level,
+            name,
+            &value as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of_val(&value) as libc::socklen_t,
+        )
+    };
+    if ret == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_dont_fragment(_socket: &UdpSocket, _ipv6: bool) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "don't-fragment is only supported on Linux",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Echo server that drops payloads over `limit` bytes, like a path with
+    /// a small MTU.
+    async fn echo_server(limit: usize) -> SocketAddr {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = vec![0u8; MAX_PAYLOAD];
+            while let Ok((len, peer)) = socket.recv_from(&mut buf).await {
+                if len <= limit {
+                    let _ = socket.send_to(&buf[..len], peer).await;
+                }
+            }
+        });
+        addr
+    }
+
+    const FAST: Duration = Duration::from_millis(50);
+
+    #[tokio::test]
+    async fn test_ramp_finds_largest_payload() {
+        let addr = echo_server(1000).await;
+        let report = run_with_timeout(addr, &UdpEchoMode::default_ramp(), FAST).await.unwrap();
+        assert_eq!(report.max_payload, Some(1000));
+        assert_eq!(report.first_failed, Some(1001));
+        assert!(report.received > 0 && report.received < report.sent);
+        assert!(report.avg_rtt_ms.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_ramp_passes_whole_range() {
+        let addr = echo_server(MAX_PAYLOAD).await;
+        let mode = UdpEchoMode::SizeRamp { start: 64, end: 1472, step: 512 };
+        let report = run_with_timeout(addr, &mode, FAST).await.unwrap();
+        assert_eq!(report.max_payload, Some(1472));
+        assert_eq!(report.first_failed, None);
+        // 64, 576, 1088, then clamped to 1472.
+        assert_eq!(report.sent, 4);
+    }
+
+    #[tokio::test]
+    async fn test_ramp_fails_from_start() {
+        let addr = echo_server(32).await;
+        let report = run_with_timeout(addr, &UdpEchoMode::default_ramp(), FAST).await.unwrap();
+        assert_eq!(report.max_payload, None);
+        assert_eq!(report.first_failed, Some(64));
+        assert_eq!(report.sent, RAMP_ATTEMPTS);
+    }
+
+    #[tokio::test]
+    async fn test_fixed_mode() {
+        let addr = echo_server(MAX_PAYLOAD).await;
+        let report = run_with_timeout(addr, &UdpEchoMode::Fixed { size: 200, count: 5 }, FAST)
+            .await
+            .unwrap();
+        assert_eq!((report.sent, report.received), (5, 5));
+        assert_eq!(report.max_payload, Some(200));
+    }
+
+    #[test]
+    fn test_mode_validation() {
+        assert!(UdpEchoMode::default_ramp().validate().is_ok());
+        assert!(UdpEchoMode::SizeRamp { start: 1472, end: 64, step: 64 }.validate().is_err());
+        assert!(UdpEchoMode::SizeRamp { start: 64, end: 1472, step: 0 }.validate().is_err());
+        assert!(UdpEchoMode::Fixed { size: 2, count: 1 }.validate().is_err());
+    }
+}
@@ -35,8 +35,17 @@ impl LinkCodec {
 }
 
 use crate::reflector_proto::rpc::LinkMessage;
+use crate::reflector_proto::tunnel;
 use tokio_util::codec::{Decoder, Encoder};
 
+/// A decoded frame: a control message or tunneled data.
+#[derive(Debug)]
+pub enum Frame {
+    Message(LinkMessage),
+    /// Data for a tunnel channel; empty `data` is end-of-stream.
+    Data { channel: u32, data: Bytes },
+}
+
 impl Encoder<LinkMessage> for LinkCodec {
     type Error = anyhow::Error;
 
@@ -47,15 +56,28 @@ impl Encoder<LinkMessage> for LinkCodec {
     }
 }
 
+/// Pre-built payloads, i.e. tunnel data frames.
+impl Encoder<Bytes> for LinkCodec {
+    type Error = anyhow::Error;
+
+    fn encode(&mut self, item: Bytes, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.inner.encode(item, dst).map_err(|e| anyhow::anyhow!(e))
+    }
+}
+
 impl Decoder for LinkCodec {
-    type Item = LinkMessage;
+    type Item = Frame;
     type Error = anyhow::Error;
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
         match self.inner.decode(src).map_err(|e| anyhow::anyhow!(e))? {
+            Some(bytes) if tunnel::is_data_frame(&bytes) => {
+                let (channel, data) = tunnel::parse_data_frame(bytes.freeze())?;
+                Ok(Some(Frame::Data { channel, data }))
+            }
             Some(bytes) => {
                 let msg = serde_json::from_slice(&bytes).context("failed to deserialize message")?;
-                Ok(Some(msg))
+                Ok(Some(Frame::Message(msg)))
             },
             None => Ok(None),
         }
@@ -74,6 +96,7 @@ mod tests {
             request_id: "test-1".to_string(),
             payload: MessagePayload::Hello(Hello {
                 version: "1.0".to_string(),
+                versions: vec!["1.0".to_string()],
                 features: vec![],
             }),
         };
@@ -88,6 +111,24 @@ mod tests {
         let decoded = decode_codec.decode(&mut buf).expect("decode failed").expect("should have frame");
         
         // request_id match?
-        assert_eq!(msg.request_id, decoded.request_id);
+        match decoded {
+            Frame::Message(m) => assert_eq!(msg.request_id, m.request_id),
+            other => panic!("expected message, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_data_frame_decodes() {
+        let mut codec = LinkCodec::new();
+        let mut buf = BytesMut::new();
+        codec.encode(tunnel::data_frame(4, b"abc"), &mut buf).expect("encode failed");
+
+        match codec.decode(&mut buf).expect("decode failed").expect("should have frame") {
+            Frame::Data { channel, data } => {
+                assert_eq!(channel, 4);
+                assert_eq!(&data[..], b"abc");
+            }
+            other => panic!("expected data frame, got {:?}", other),
+        }
     }
 }
@@ -0,0 +1,326 @@
+//! Daily data budget for bandwidth-heavy scheduled tests.
+//!
+//! Throughput jobs record the bytes they move into `data_usage`; once the
+//! day's total reaches the budget, further throughput jobs are skipped until
+//! the next UTC day. Cheap probes are never counted or blocked.
+
+use crate::scheduler::params::JobParams;
+use crate::scheduler::reflector_jobs;
+use crate::storage::Pool;
+use anyhow::Result;
+use chrono::Utc;
+use rusqlite::OptionalExtension;
+
+const BYTES_PER_GB: f64 = 1_000_000_000.0;
+
+/// Assumed WAN rate for speed-test estimates when there is no history yet.
+const FALLBACK_SPEED_TEST_MBPS: f64 = 100.0;
+
+/// How far back historical throughput is averaged for estimates.
+const ESTIMATE_HISTORY_DAYS: u32 = 30;
+
+/// Daily cap on bytes moved by scheduled throughput tests.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DataBudget {
+    /// `None` means unlimited.
+    pub daily_bytes: Option<u64>,
+}
+
+/// Budget state for the current UTC day.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct BudgetStatus {
+    pub day: String,
+    pub limit_bytes: Option<u64>,
+    pub used_bytes: u64,
+    /// `None` when unlimited.
+    pub remaining_bytes: Option<u64>,
+    pub exhausted: bool,
+}
+
+impl DataBudget {
+    pub fn unlimited() -> Self {
+        Self { daily_bytes: None }
+    }
+
+    pub fn from_gb(gb: f64) -> Self {
+        Self {
+            daily_bytes: Some((gb * BYTES_PER_GB) as u64),
+        }
+    }
+
+    /// Read `PP_DAILY_BW_BUDGET_GB`; unset or invalid means unlimited.
+    pub fn from_env() -> Self {
+        match std::env::var("PP_DAILY_BW_BUDGET_GB") {
+            Ok(v) => match v.trim().parse::<f64>() {
+                Ok(gb) if gb > 0.0 => Self::from_gb(gb),
+                _ => {
+                    tracing::warn!(value = %v, "Ignoring invalid PP_DAILY_BW_BUDGET_GB");
+                    Self::unlimited()
+                }
+            },
+            Err(_) => Self::unlimited(),
+        }
+    }
+
+    /// Current usage against this budget.
+    pub fn status(&self, pool: &Pool) -> Result<BudgetStatus> {
+        let used_bytes = used_today(pool)?;
+        Ok(BudgetStatus {
+            day: today(),
+            limit_bytes: self.daily_bytes,
+            used_bytes,
+            remaining_bytes: self.daily_bytes.map(|l| l.saturating_sub(used_bytes)),
+            exhausted: self.daily_bytes.is_some_and(|l| used_bytes >= l),
+        })
+    }
+}
+
+/// Projected data use of upcoming scheduled runs.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct UsageProjection {
+    /// Sum of per-run estimates over the preview window.
+    pub window_bytes: u64,
+    /// Window total scaled to 24 hours.
+    pub daily_bytes: u64,
+    /// Daily figure times 30.
+    pub monthly_bytes: u64,
+}
+
+impl UsageProjection {
+    /// Project from per-run estimates covering `hours` hours.
+    pub fn from_runs(estimates: &[u64], hours: u64) -> Self {
+        let window_bytes: u64 = estimates.iter().sum();
+        let daily_bytes = if hours == 0 {
+            0
+        } else {
+            (window_bytes as f64 * 24.0 / hours as f64) as u64
+        };
+        Self {
+            window_bytes,
+            daily_bytes,
+            monthly_bytes: daily_bytes * 30,
+        }
+    }
+}
+
+/// Estimated bytes one run of `test_type` with `params` will move.
+///
+/// Only throughput jobs are counted; probes are a few KB and reported as 0.
+/// iperf3 speed tests use the average measured rate for that mode and
+/// stream count over the last 30 days (any stream count if none match),
+/// falling back to an assumed 100 Mbps, over both directions of the
+/// scheduled `duration_sec`. Provider runs are costed like
+/// [`compare::estimated_bytes`](crate::throughput::compare::estimated_bytes);
+/// reflector throughput like a LAN test, warm-up included.
+pub fn estimate_run_bytes(pool: &Pool, test_type: &str, params: Option<&serde_json::Value>) -> u64 {
+    let default_secs = crate::scheduler::engine::SCHEDULED_SPEED_TEST_SECS;
+    let parsed = JobParams::parse(test_type, params);
+    let (mode, secs_per_direction, streams) = match &parsed {
+        Ok(JobParams::Speed(p)) => {
+            let mode = match p.mode.as_deref() {
+                Some(mode) => mode,
+                None if test_type == "speed:lan" => "lan",
+                None => "wan",
+            };
+            if p.provider.is_some() {
+                (mode, crate::throughput::compare::PHASE_SECS as u32, None)
+            } else {
+                (mode, p.duration_sec.unwrap_or(default_secs), Some(p.streams.unwrap_or(1)))
+            }
+        }
+        Ok(JobParams::Reflector(p)) if test_type == reflector_jobs::THROUGHPUT => {
+            let secs = p.duration_sec.map_or(default_secs, |s| s.min(u64::from(u32::MAX)) as u32);
+            // The omitted warm-up runs on top of the duration.
+            ("lan", secs.saturating_add(p.omit_sec.unwrap_or(0)), None)
+        }
+        _ => return 0,
+    };
+
+    let avg_mbps = streams
+        .and_then(|n| average_throughput_mbps(pool, mode, Some(n)))
+        .or_else(|| average_throughput_mbps(pool, mode, None))
+        .unwrap_or(FALLBACK_SPEED_TEST_MBPS);
+    (avg_mbps * 1_000_000.0 / 8.0 * f64::from(secs_per_direction) * 2.0) as u64
+}
+
+/// Average throughput of `mode` TCP tests over the estimate history,
+/// limited to tests with `streams` parallel streams when given. UDP runs
+/// send at a fixed rate, so they say nothing about what a TCP test moves.
+fn average_throughput_mbps(pool: &Pool, mode: &str, streams: Option<u32>) -> Option<f64> {
+    let conn = pool.get().ok()?;
+    conn.query_row(
+        "SELECT AVG(throughput_mbps) FROM throughput_results
+         WHERE mode = ?1 AND protocol = 'tcp' AND throughput_mbps IS NOT NULL
+           AND created_at >= datetime('now', ?2)
+           AND (?3 IS NULL OR streams = ?3)",
+        rusqlite::params![mode, format!("-{} days", ESTIMATE_HISTORY_DAYS), streams],
+        |row| row.get::<_, Option<f64>>(0),
+    )
+    .ok()
+    .flatten()
+    .filter(|mbps| *mbps > 0.0)
+}
+
+/// Format a byte count as GB/MB for CLI output.
+pub fn format_bytes(bytes: u64) -> String {
+    let b = bytes as f64;
+    if b >= BYTES_PER_GB {
+        format!("{:.1} GB", b / BYTES_PER_GB)
+    } else {
+        format!("{:.0} MB", b / 1_000_000.0)
+    }
+}
+
+/// Add `bytes` to today's usage.
+pub fn record_usage(pool: &Pool, bytes: u64) -> Result<()> {
+    let conn = pool.get()?;
+    conn.execute(
+        "INSERT INTO data_usage (day, bytes) VALUES (?1, ?2)
+         ON CONFLICT(day) DO UPDATE SET bytes = bytes + excluded.bytes",
+        rusqlite::params![today(), bytes as i64],
+    )?;
+    Ok(())
+}
+
+/// Bytes recorded so far for the current UTC day.
+pub fn used_today(pool: &Pool) -> Result<u64> {
+    let conn = pool.get()?;
+    let used: Option<i64> = conn
+        .query_row(
+            "SELECT bytes FROM data_usage WHERE day = ?1",
+            rusqlite::params![today()],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(used.unwrap_or(0).max(0) as u64)
+}
+
+fn today() -> String {
+    Utc::now().format("%Y-%m-%d").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_pool() -> (tempfile::TempDir, Pool) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("budget.db");
+        let pool = crate::storage::open_pool(path.to_str().unwrap()).unwrap();
+        (dir, pool)
+    }
+
+    #[test]
+    fn test_budget_tracks_usage() {
+        let (_dir, pool) = test_pool();
+        let budget = DataBudget::from_gb(1.0);
+
+        let status = budget.status(&pool).unwrap();
+        assert_eq!(status.used_bytes, 0);
+        assert!(!status.exhausted);
+
+        record_usage(&pool, 600_000_000).unwrap();
+        record_usage(&pool, 300_000_000).unwrap();
+        let status = budget.status(&pool).unwrap();
+        assert_eq!(status.used_bytes, 900_000_000);
+        assert_eq!(status.remaining_bytes, Some(100_000_000));
+        assert!(!status.exhausted);
+
+        record_usage(&pool, 200_000_000).unwrap();
+        let status = budget.status(&pool).unwrap();
+        assert_eq!(status.remaining_bytes, Some(0));
+        assert!(status.exhausted);
+    }
+
+    #[test]
+    fn test_unreadable_usage_is_an_error() {
+        let (_dir, pool) = test_pool();
+        pool.get().unwrap().execute("DROP TABLE data_usage", []).unwrap();
+        assert!(DataBudget::from_gb(1.0).status(&pool).is_err());
+    }
+
+    #[test]
+    fn test_estimate_run_bytes() {
+        let (_dir, pool) = test_pool();
+
+        // Probes are negligible.
+        assert_eq!(estimate_run_bytes(&pool, "icmp-gateway", None), 0);
+        assert_eq!(estimate_run_bytes(&pool, "dns:1.1.1.1", None), 0);
+
+        // No history: assumed 100 Mbps, 10 s each way = 250 MB.
+        assert_eq!(estimate_run_bytes(&pool, "speed-test-light", None), 250_000_000);
+
+        // History: average of measured rates.
+        let conn = pool.get().unwrap();
+        for mbps in [400.0, 600.0] {
+            conn.execute(
+                "INSERT INTO throughput_results (mode, direction, throughput_mbps, result_json)
+                 VALUES ('wan', 'download', ?1, '{}')",
+                rusqlite::params![mbps],
+            )
+            .unwrap();
+        }
+        assert_eq!(estimate_run_bytes(&pool, "speed:wan", None), 1_250_000_000);
+
+        // A rate-capped UDP run doesn't drag the average down.
+        conn.execute(
+            "INSERT INTO throughput_results (mode, direction, protocol, throughput_mbps, result_json)
+             VALUES ('wan', 'upload', 'udp', 100.0, '{}')",
+            [],
+        )
+        .unwrap();
+        assert_eq!(estimate_run_bytes(&pool, "speed:wan", None), 1_250_000_000);
+    }
+
+    #[test]
+    fn test_estimate_run_bytes_uses_params() {
+        let (_dir, pool) = test_pool();
+        let conn = pool.get().unwrap();
+        for (streams, mbps) in [(1, 400.0), (4, 900.0)] {
+            conn.execute(
+                "INSERT INTO throughput_results (mode, direction, streams, throughput_mbps, result_json)
+                 VALUES ('lan', 'download', ?1, ?2, '{}')",
+                rusqlite::params![streams, mbps],
+            )
+            .unwrap();
+        }
+
+        // 900 Mbps at 4 streams, 30 s each way.
+        let params = serde_json::json!({ "duration_sec": 30, "streams": 4 });
+        assert_eq!(estimate_run_bytes(&pool, "speed:lan", Some(&params)), 6_750_000_000);
+        // No history at 2 streams: average of all LAN tests (650 Mbps).
+        let params = serde_json::json!({ "streams": 2 });
+        assert_eq!(estimate_run_bytes(&pool, "speed:lan", Some(&params)), 1_625_000_000);
+        // Providers run their own fixed-length phases; no WAN history here.
+        let params = serde_json::json!({ "provider": "ndt7" });
+        assert_eq!(estimate_run_bytes(&pool, "speed:wan", Some(&params)), 250_000_000);
+
+        // Reflector throughput is costed as LAN; UDP echo is negligible.
+        let params = serde_json::json!({ "reflector": "office", "duration_sec": 20 });
+        assert_eq!(estimate_run_bytes(&pool, reflector_jobs::THROUGHPUT, Some(&params)), 3_250_000_000);
+        assert_eq!(estimate_run_bytes(&pool, reflector_jobs::UDP_ECHO, Some(&params)), 0);
+        // 20 s plus a 4 s warm-up each way.
+        let params = serde_json::json!({ "reflector": "office", "duration_sec": 20, "omit_sec": 4 });
+        assert_eq!(estimate_run_bytes(&pool, reflector_jobs::THROUGHPUT, Some(&params)), 3_900_000_000);
+    }
+
+    #[test]
+    fn test_usage_projection() {
+        // Two 250 MB runs in a 48 h window -> 250 MB/day, 7.5 GB/month.
+        let p = UsageProjection::from_runs(&[250_000_000, 0, 250_000_000], 48);
+        assert_eq!(p.window_bytes, 500_000_000);
+        assert_eq!(p.daily_bytes, 250_000_000);
+        assert_eq!(p.monthly_bytes, 7_500_000_000);
+        assert_eq!(format_bytes(p.monthly_bytes), "7.5 GB");
+        assert_eq!(format_bytes(p.daily_bytes), "250 MB");
+    }
+
+    #[test]
+    fn test_unlimited_budget_never_exhausts() {
+        let (_dir, pool) = test_pool();
+        record_usage(&pool, u32::MAX as u64).unwrap();
+        let status = DataBudget::unlimited().status(&pool).unwrap();
+        assert!(!status.exhausted);
+        assert_eq!(status.remaining_bytes, None);
+    }
+}
@@ -1,26 +1,223 @@
+use crate::scheduler::budget::{self, DataBudget, UsageProjection};
+use crate::scheduler::queue::JobQueue;
+use crate::scheduler::SchedulerError;
 use crate::storage::Pool;
 use anyhow::{Context, Result};
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use cron::Schedule as CronSchedule;
+use std::collections::HashSet;
 use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio::sync::Semaphore;
 
+/// Default shortest allowed gap between a schedule's runs.
+pub const DEFAULT_MIN_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Longest the engine waits between checks for due schedules.
+pub const MAX_TICK: Duration = Duration::from_secs(10);
+
 /// A scheduler that persists tasks in SQLite and checks for runnable tasks.
 #[derive(Clone)]
 pub struct Scheduler {
     pool: Pool,
     bandwidth_permit: Arc<Semaphore>,
+    data_budget: DataBudget,
+    /// Schedules with a run in flight, for the overrun guard.
+    running: Arc<Mutex<HashSet<String>>>,
+    /// Shortest gap between runs a new schedule may have.
+    min_interval: Duration,
+    /// Caps how many scheduled tests run at once.
+    jobs: JobQueue,
+}
+
+/// Marks a schedule as running until dropped.
+pub struct RunGuard {
+    running: Arc<Mutex<HashSet<String>>>,
+    name: String,
+}
+
+impl Drop for RunGuard {
+    fn drop(&mut self) {
+        self.running.lock().unwrap_or_else(|e| e.into_inner()).remove(&self.name);
+    }
+}
+
+/// Names of the fields of a normalized (seconds-first) expression.
+const FIELD_NAMES: [&str; 7] = ["second", "minute", "hour", "day-of-month", "month", "day-of-week", "year"];
+
+/// Normalize and parse a cron expression. Two forms are accepted:
+///
+/// - 5 fields, `minute hour day-of-month month day-of-week` (standard cron),
+///   stored as 6-field with 0 seconds;
+/// - 6 fields with seconds first (an optional 7th is the year), for
+///   sub-minute schedules such as `*/30 * * * * *`.
+///
+/// On error, points at the offending field.
+pub fn parse(expr: &str) -> Result<(String, CronSchedule), SchedulerError> {
+    let fields: Vec<&str> = expr.split_whitespace().collect();
+    let normalized = match fields.len() {
+        5 => format!("0 {}", fields.join(" ")),
+        6 | 7 => fields.join(" "),
+        count => {
+            return Err(SchedulerError::CronFieldCount {
+                expr: expr.to_string(),
+                count,
+            })
+        }
+    };
+    match CronSchedule::from_str(&normalized) {
+        Ok(schedule) => Ok((normalized, schedule)),
+        Err(e) => Err(locate_error(expr, &normalized, e.to_string())),
+    }
+}
+
+/// Find the first field that fails to parse on its own (every other field
+/// `*`), so the error can name it.
+fn locate_error(expr: &str, normalized: &str, reason: String) -> SchedulerError {
+    let fields: Vec<&str> = normalized.split_whitespace().collect();
+    let offset = if expr.split_whitespace().count() == 5 { 1 } else { 0 };
+    for (i, value) in fields.iter().enumerate().skip(offset) {
+        let probe: Vec<&str> = (0..fields.len()).map(|j| if j == i { *value } else { "*" }).collect();
+        if let Err(e) = CronSchedule::from_str(&probe.join(" ")) {
+            return SchedulerError::InvalidCron {
+                expr: expr.to_string(),
+                field: FIELD_NAMES[i],
+                value: value.to_string(),
+                reason: e.to_string(),
+            };
+        }
+    }
+    // Every field parses alone; the combination is at fault.
+    SchedulerError::InvalidCron {
+        expr: expr.to_string(),
+        field: "combined",
+        value: normalized.to_string(),
+        reason,
+    }
+}
+
+/// Human description of a cron expression, e.g. "every day at 02:00".
+pub fn describe(expr: &str) -> Result<String> {
+    let (normalized, _) = parse(expr)?;
+    let f: Vec<&str> = normalized.split_whitespace().collect();
+    let (sec, min, hour, dom, month, dow) = (f[0], f[1], f[2], f[3], f[4], f[5]);
+    let num = |s: &str| s.parse::<u32>().ok();
+    let step = |s: &str| s.strip_prefix("*/").and_then(|n| n.parse::<u32>().ok());
+    let any = |s: &str| s == "*" || s == "?";
+
+    let time = match (num(sec), num(min), num(hour)) {
+        (Some(0), Some(m), Some(h)) => format!("at {:02}:{:02}", h, m),
+        (Some(s), Some(m), Some(h)) => format!("at {:02}:{:02}:{:02}", h, m, s),
+        (Some(0), Some(0), None) if any(hour) => "every hour".to_string(),
+        (Some(0), Some(m), None) if any(hour) => format!("every hour at minute {}", m),
+        (Some(0), Some(m), None) if step(hour).is_some() => {
+            format!("every {} hours at minute {}", step(hour).unwrap_or(1), m)
+        }
+        (Some(0), None, None) if any(min) && any(hour) => "every minute".to_string(),
+        (Some(0), None, None) if step(min).is_some() && any(hour) => {
+            format!("every {} minutes", step(min).unwrap_or(1))
+        }
+        (None, None, None) if any(sec) && any(min) && any(hour) => "every second".to_string(),
+        (None, None, None) if step(sec).is_some() && any(min) && any(hour) => {
+            format!("every {} seconds", step(sec).unwrap_or(1))
+        }
+        _ => format!("at second {}, minute {}, hour {}", sec, min, hour),
+    };
+
+    let mut days = Vec::new();
+    if !any(dom) {
+        days.push(format!("on day {} of the month", dom));
+    }
+    if !any(month) {
+        days.push(format!("in month {}", month));
+    }
+    if !any(dow) {
+        days.push(format!("on day-of-week {}", dow));
+    }
+    if days.is_empty() {
+        // "every day every minute" reads badly; only name the day for
+        // schedules that fire at most hourly.
+        if time.starts_with("at ") {
+            return Ok(format!("every day {}", time));
+        }
+        return Ok(time);
+    }
+    Ok(format!("{} {}", time, days.join(", ")))
+}
+
+/// Shortest gap between consecutive runs of `schedule`, judged over its
+/// next few fire times. `None` if it fires at most once.
+pub fn shortest_interval(schedule: &CronSchedule) -> Option<Duration> {
+    let runs: Vec<DateTime<Utc>> = schedule.upcoming(Utc).take(60).collect();
+    runs.windows(2)
+        .filter_map(|w| (w[1] - w[0]).to_std().ok())
+        .min()
+}
+
+/// Read `PP_SCHEDULE_MIN_INTERVAL_SECS` (default 10 s).
+pub fn min_interval_from_env() -> Duration {
+    match std::env::var("PP_SCHEDULE_MIN_INTERVAL_SECS") {
+        Ok(v) => match v.trim().parse::<u64>() {
+            Ok(secs) if secs > 0 => Duration::from_secs(secs),
+            _ => {
+                tracing::warn!(value = %v, "Ignoring invalid PP_SCHEDULE_MIN_INTERVAL_SECS");
+                DEFAULT_MIN_INTERVAL
+            }
+        },
+        Err(_) => DEFAULT_MIN_INTERVAL,
+    }
+}
+
+/// The next `n` fire times of a cron expression after `after` (UTC).
+pub fn next_runs(expr: &str, after: DateTime<Utc>, n: usize) -> Result<Vec<DateTime<Utc>>> {
+    let (_, schedule) = parse(expr)?;
+    Ok(schedule.after(&after).take(n).collect())
 }
 
 impl Scheduler {
+    /// Create a scheduler with the data budget from `PP_DAILY_BW_BUDGET_GB`.
     pub fn new(pool: Pool) -> Self {
+        Self::with_budget(pool, DataBudget::from_env())
+    }
+
+    pub fn with_budget(pool: Pool, data_budget: DataBudget) -> Self {
         Self {
             pool,
             bandwidth_permit: Arc::new(Semaphore::new(1)), // Only 1 bandwidth-heavy test at a time
+            data_budget,
+            running: Arc::new(Mutex::new(HashSet::new())),
+            min_interval: min_interval_from_env(),
+            jobs: JobQueue::from_env(),
         }
     }
 
+    /// Override the concurrent job limit (`PP_MAX_CONCURRENT_JOBS`).
+    pub fn with_max_concurrent_jobs(mut self, limit: usize) -> Self {
+        self.jobs = JobQueue::new(limit);
+        self
+    }
+
+    pub fn job_queue(&self) -> &JobQueue {
+        &self.jobs
+    }
+
+    /// Override the minimum gap between a new schedule's runs.
+    pub fn with_min_interval(mut self, min_interval: Duration) -> Self {
+        self.min_interval = min_interval;
+        self
+    }
+
+    pub fn min_interval(&self) -> Duration {
+        self.min_interval
+    }
+
+    /// How often the engine should check for due schedules: often enough
+    /// for the most frequent schedule allowed, at most every `MAX_TICK`.
+    pub fn tick_interval(&self) -> Duration {
+        self.min_interval.clamp(Duration::from_secs(1), MAX_TICK)
+    }
+
     pub fn get_pool(&self) -> &Pool {
         &self.pool
     }
@@ -29,6 +226,22 @@ impl Scheduler {
         self.bandwidth_permit.clone()
     }
 
+    pub fn data_budget(&self) -> DataBudget {
+        self.data_budget
+    }
+
+    /// Mark `name` as running, or `None` if its previous run hasn't finished.
+    pub fn try_begin_run(&self, name: &str) -> Option<RunGuard> {
+        let mut running = self.running.lock().unwrap_or_else(|e| e.into_inner());
+        if !running.insert(name.to_string()) {
+            return None;
+        }
+        Some(RunGuard {
+            running: self.running.clone(),
+            name: name.to_string(),
+        })
+    }
+
     /// Ensure default schedules exist (idempotent).
     pub async fn ensure_defaults(&self) -> Result<()> {
         let defaults = crate::scheduler::profiles::defaults();
@@ -45,22 +258,37 @@ impl Scheduler {
 
     /// Add a new schedule to the database
     pub async fn add_schedule(&self, name: &str, cron_expr: &str, test_type: &str) -> Result<()> {
-        // Normalize Cron: parse 5-field (standard) as 6-field (quartz with 0 seconds)
-        let parts: Vec<&str> = cron_expr.split_whitespace().collect();
-        let effective_cron = if parts.len() == 5 {
-            format!("0 {}", cron_expr)
-        } else {
-            cron_expr.to_string()
-        };
-
-        // Validate Cron
-        let _ = CronSchedule::from_str(&effective_cron)
-            .map_err(|e| anyhow::anyhow!("Invalid cron expression '{}': {}", effective_cron, e))?;
+        self.add_schedule_with_params(name, cron_expr, test_type, None).await
+    }
+
+    /// Add a schedule carrying test parameters (e.g. which reflector a
+    /// `reflector_throughput` test runs against), stored as JSON.
+    pub async fn add_schedule_with_params(
+        &self,
+        name: &str,
+        cron_expr: &str,
+        test_type: &str,
+        params: Option<&serde_json::Value>,
+    ) -> Result<()> {
+        let (effective_cron, schedule) = parse(cron_expr)?;
+        if let Some(interval) = shortest_interval(&schedule) {
+            if interval < self.min_interval {
+                return Err(SchedulerError::TooFrequent {
+                    expr: cron_expr.to_string(),
+                    interval_secs: interval.as_secs(),
+                    min_secs: self.min_interval.as_secs(),
+                }
+                .into());
+            }
+        }
+
+        // Check the params against the test type now rather than at run time.
+        crate::scheduler::params::JobParams::parse(test_type, params)?;
 
         let conn = self.pool.get()?;
         conn.execute(
-            "INSERT INTO schedules (name, cron_expr, test_type, enabled) VALUES (?1, ?2, ?3, 1)",
-            rusqlite::params![name, effective_cron, test_type],
+            "INSERT INTO schedules (name, cron_expr, test_type, params, enabled) VALUES (?1, ?2, ?3, ?4, 1)",
+            rusqlite::params![name, effective_cron, test_type, params.map(|p| p.to_string())],
         )
         .context("Failed to insert schedule")?;
 
@@ -104,10 +332,48 @@ impl Scheduler {
         Ok(preview)
     }
 
-    /// List all schedules
-    pub async fn list(&self) -> Result<Vec<(String, String, String, bool)>> {
+    /// Dry-run preview with an estimated data cost per run and the
+    /// projected daily/monthly total. Returns (time, name, test, est_bytes).
+    pub async fn preview_data_usage(
+        &self,
+        hours: u64,
+    ) -> Result<(Vec<(String, String, String, u64)>, UsageProjection)> {
+        let preview = self.preview_next_runs(hours).await?;
+        let params: std::collections::HashMap<String, Option<serde_json::Value>> = self
+            .list()
+            .await?
+            .into_iter()
+            .map(|(name, _, _, _, params)| (name, params))
+            .collect();
+        let runs: Vec<(String, String, String, u64)> = preview
+            .into_iter()
+            .map(|(time, name, test)| {
+                let params = params.get(&name).and_then(Option::as_ref);
+                let bytes = budget::estimate_run_bytes(&self.pool, &test, params);
+                (time, name, test, bytes)
+            })
+            .collect();
+
+        let estimates: Vec<u64> = runs.iter().map(|r| r.3).collect();
+        Ok((runs, UsageProjection::from_runs(&estimates, hours)))
+    }
+
+    /// Replace all existing schedules with those of `profile`.
+    pub async fn apply_profile(&self, profile: crate::scheduler::profiles::Profile) -> Result<()> {
+        for (name, ..) in self.list().await? {
+            self.remove(&name).await?;
+        }
+        for s in crate::scheduler::profiles::get_profile_schedules(profile) {
+            self.add_schedule(&s.name, &s.cron_expr, &s.test_type).await?;
+        }
+        Ok(())
+    }
+
+    /// List all schedules as (name, cron, test_type, enabled, params)
+    #[allow(clippy::type_complexity)]
+    pub async fn list(&self) -> Result<Vec<(String, String, String, bool, Option<serde_json::Value>)>> {
         let conn = self.pool.get()?;
-        let mut stmt = conn.prepare("SELECT name, cron_expr, test_type, enabled FROM schedules")?;
+        let mut stmt = conn.prepare("SELECT name, cron_expr, test_type, enabled, params FROM schedules")?;
 
         let rows = stmt.query_map([], |row| {
             Ok((
@@ -115,12 +381,21 @@ impl Scheduler {
                 row.get::<_, String>(1)?,
                 row.get::<_, String>(2)?,
                 row.get::<_, i64>(3)? != 0,
+                row.get::<_, Option<String>>(4)?,
             ))
         })?;
 
         let mut list = Vec::new();
         for r in rows {
-            list.push(r?);
+            let (name, cron, test_type, enabled, params) = r?;
+            let params = params.and_then(|p| match serde_json::from_str(&p) {
+                Ok(v) => Some(v),
+                Err(e) => {
+                    tracing::warn!(schedule=%name, "Ignoring unparseable schedule params: {}", e);
+                    None
+                }
+            });
+            list.push((name, cron, test_type, enabled, params));
         }
         Ok(list)
     }
@@ -138,11 +413,11 @@ impl Scheduler {
     }
 
     /// Check for tasks that are due to run.
-    /// Returns list of (name, test_type)
-    pub async fn check_due_tasks(&self) -> Result<Vec<(String, String)>> {
+    /// Returns list of (name, test_type, params)
+    pub async fn check_due_tasks(&self) -> Result<Vec<(String, String, Option<serde_json::Value>)>> {
         let conn = self.pool.get()?;
         let mut stmt = conn.prepare(
-            "SELECT name, cron_expr, last_run_at, test_type FROM schedules WHERE enabled = 1",
+            "SELECT name, cron_expr, last_run_at, test_type, params FROM schedules WHERE enabled = 1",
         )?;
 
         let rows = stmt.query_map([], |row| {
@@ -151,6 +426,7 @@ impl Scheduler {
                 row.get::<_, String>(1)?,
                 row.get::<_, Option<String>>(2)?,
                 row.get::<_, String>(3)?,
+                row.get::<_, Option<String>>(4)?,
             ))
         })?;
 
@@ -158,7 +434,7 @@ impl Scheduler {
         let mut due_tasks = Vec::new();
 
         for r in rows {
-            let (name, cron_expr, last_run_at, test_type) = r?;
+            let (name, cron_expr, last_run_at, test_type, params) = r?;
 
             let should_run = match last_run_at {
                 Some(last_run_str) => {
@@ -184,7 +460,14 @@ impl Scheduler {
             };
 
             if should_run {
-                due_tasks.push((name, test_type));
+                let params = params.and_then(|p| match serde_json::from_str(&p) {
+                    Ok(v) => Some(v),
+                    Err(e) => {
+                        tracing::warn!(schedule=%name, "Ignoring unparseable schedule params: {}", e);
+                        None
+                    }
+                });
+                due_tasks.push((name, test_type, params));
             }
         }
 
@@ -202,3 +485,70 @@ impl Scheduler {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_describe() {
+        assert_eq!(describe("0 2 * * *").unwrap(), "every day at 02:00");
+        assert_eq!(describe("30 14 * * *").unwrap(), "every day at 14:30");
+        assert_eq!(describe("* * * * *").unwrap(), "every minute");
+        assert_eq!(describe("*/15 * * * *").unwrap(), "every 15 minutes");
+        assert_eq!(describe("5 * * * *").unwrap(), "every hour at minute 5");
+        assert_eq!(describe("*/30 * * * * *").unwrap(), "every 30 seconds");
+        assert_eq!(describe("0 3 1 * *").unwrap(), "at 03:00 on day 1 of the month");
+        assert_eq!(describe("0 3 * * Mon-Fri").unwrap(), "at 03:00 on day-of-week Mon-Fri");
+    }
+
+    #[test]
+    fn test_parse_points_at_bad_field() {
+        match parse("0 2 * *") {
+            Err(SchedulerError::CronFieldCount { count, .. }) => assert_eq!(count, 4),
+            other => panic!("expected field count error, got {:?}", other.map(|(n, _)| n)),
+        }
+        match parse("0 25 * * *") {
+            Err(SchedulerError::InvalidCron { field, value, .. }) => {
+                assert_eq!(field, "hour");
+                assert_eq!(value, "25");
+            }
+            other => panic!("expected invalid hour, got {:?}", other.map(|(n, _)| n)),
+        }
+        assert_eq!(parse("0 2 * * *").unwrap().0, "0 0 2 * * *");
+    }
+
+    #[tokio::test]
+    async fn test_min_interval_guard() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = crate::storage::open_pool(dir.path().join("t.db").to_str().unwrap()).unwrap();
+        let scheduler = Scheduler::new(pool).with_min_interval(Duration::from_secs(10));
+
+        // Seconds-first 6-field form alongside classic 5-field.
+        scheduler.add_schedule("half-minute", "*/30 * * * * *", "icmp:1.1.1.1").await.unwrap();
+        scheduler.add_schedule("hourly", "0 * * * *", "icmp:1.1.1.1").await.unwrap();
+
+        let err = scheduler.add_schedule("too-fast", "*/2 * * * * *", "icmp:1.1.1.1").await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<SchedulerError>(),
+            Some(SchedulerError::TooFrequent { interval_secs: 2, min_secs: 10, .. })
+        ));
+        assert_eq!(scheduler.tick_interval(), Duration::from_secs(10));
+        assert_eq!(scheduler.with_min_interval(Duration::from_secs(2)).tick_interval(), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_next_runs() {
+        let after = Utc.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap();
+        let runs = next_runs("0 2 * * *", after, 3).unwrap();
+        assert_eq!(
+            runs,
+            vec![
+                Utc.with_ymd_and_hms(2026, 3, 2, 2, 0, 0).unwrap(),
+                Utc.with_ymd_and_hms(2026, 3, 3, 2, 0, 0).unwrap(),
+                Utc.with_ymd_and_hms(2026, 3, 4, 2, 0, 0).unwrap(),
+            ]
+        );
+    }
+}
@@ -1,170 +1,545 @@
 use crate::probes::{self, Probe};
+use crate::scheduler::budget;
+use crate::scheduler::history::{self, HistoryEntry, RunStatus};
+use crate::scheduler::params::{JobParams, SpeedParams};
+use crate::scheduler::queue::{JobPermit, Priority};
+use crate::scheduler::reflector_jobs::{self, ReflectorJobParams};
 use crate::scheduler::Scheduler;
 use crate::storage::save_measurement;
 use crate::system::network; // Import the network module
+use crate::system::thermal;
 use std::time::Duration;
 use tracing::{error, info, warn};
 
+/// Per-direction duration of scheduled speed tests (kept short to limit load).
+pub const SCHEDULED_SPEED_TEST_SECS: u32 = 10;
+
 /// Main scheduler execution loop.
-/// Spawns a background task that polls for due schedules every 10 seconds.
+/// Spawns a background task that polls for due schedules every
+/// `Scheduler::tick_interval` (at most 10 seconds; sooner when sub-minute
+/// schedules are allowed),
+/// checkpoints the SQLite WAL every `wal::CHECKPOINT_INTERVAL`, rolls up
+/// old raw measurements every `rollup::ROLLUP_INTERVAL` (first pass at startup),
+/// and records a thermal sample every `thermal::SAMPLE_INTERVAL`.
 pub async fn run_scheduler_loop(scheduler: Scheduler) {
     info!("Scheduler engine started");
 
-    let mut interval = tokio::time::interval(Duration::from_secs(10));
+    let mut interval = tokio::time::interval(scheduler.tick_interval());
+    let mut last_checkpoint = std::time::Instant::now();
+    let mut last_rollup: Option<std::time::Instant> = None;
+    let mut last_thermal: Option<std::time::Instant> = None;
 
     loop {
         interval.tick().await;
 
+        // Keep the WAL bounded between write bursts.
+        if last_checkpoint.elapsed() >= crate::storage::wal::CHECKPOINT_INTERVAL {
+            last_checkpoint = std::time::Instant::now();
+            crate::storage::wal::checkpoint_logged(scheduler.get_pool()).await;
+        }
+
+        // Fold raw rows past retention into hourly rollups.
+        if last_rollup.map_or(true, |t| {
+            t.elapsed() >= crate::storage::rollup::ROLLUP_INTERVAL
+        }) {
+            last_rollup = Some(std::time::Instant::now());
+            crate::storage::rollup::rollup_logged(scheduler.get_pool()).await;
+        }
+
+        // Thermal history, so slow results can be traced to a throttled SoC.
+        if last_thermal.map_or(true, |t| t.elapsed() >= thermal::SAMPLE_INTERVAL) {
+            last_thermal = Some(std::time::Instant::now());
+            thermal::sample_logged(scheduler.get_pool()).await;
+        }
+
         match scheduler.check_due_tasks().await {
             Ok(tasks) => {
-                for (name, full_test_string) in tasks {
+                for (name, full_test_string, params) in tasks {
                     info!(schedule=%name, "Task due");
+                    let task = run_task(scheduler.clone(), name.clone(), full_test_string, params);
+                    dispatch(&scheduler, &name, task).await;
+                }
+            }
+            Err(e) => {
+                error!("Failed to check due tasks: {}", e);
+            }
+        }
+    }
+}
 
-                    // clone for async task
-                    let scheduler = scheduler.clone();
-                    let name = name.clone();
-                    let full_test_string = full_test_string.clone();
-
-                    tokio::spawn(async move {
-                        // Apply random jitter (0-30s) to spread load and avoid thundering herds
-                        {
-                            let jitter_secs = rand::random::<u64>() % 30;
-                            if jitter_secs > 0 {
-                                info!(schedule=%name, jitter=%jitter_secs, "Applying schedule jitter");
-                                tokio::time::sleep(Duration::from_secs(jitter_secs)).await;
-                            }
-                        }
+/// Start a due schedule's run in the background, unless its previous run is
+/// still executing: then the fire is skipped (and recorded in the run
+/// history) so a run longer than its interval can't pile up overlapping runs.
+/// Either way the fire is consumed by marking the schedule as run.
+pub async fn dispatch<F>(scheduler: &Scheduler, name: &str, task: F) -> Option<tokio::task::JoinHandle<()>>
+where
+    F: std::future::Future<Output = ()> + Send + 'static,
+{
+    // Mark as run BEFORE execution to prevent double-scheduling
+    if let Err(e) = scheduler.update_last_run(name).await {
+        error!(schedule=%name, "Failed to update last_run: {}", e);
+        return None;
+    }
 
-                        // Mark as run BEFORE execution to prevent double-scheduling (best effort)
-                        if let Err(e) = scheduler.update_last_run(&name).await {
-                            error!(schedule=%name, "Failed to update last_run: {}", e);
-                            return;
-                        }
+    let Some(guard) = scheduler.try_begin_run(name) else {
+        warn!(schedule=%name, "Previous run still executing, skipped due to overrun");
+        let now = chrono::Utc::now();
+        let entry = HistoryEntry {
+            schedule_name: name.to_string(),
+            status: RunStatus::Skipped,
+            result_summary: Some("skipped due to overrun".to_string()),
+            started_at: now,
+            finished_at: Some(now),
+        };
+        if let Err(e) = history::record(scheduler.get_pool(), &entry) {
+            error!(schedule=%name, "Failed to record skipped run: {}", e);
+        }
+        return None;
+    };
 
-                        // Resolve Aliases first
-                        let resolved_spec = match full_test_string.as_str() {
-                            "icmp-gateway" => match network::get_default_gateway() {
-                                Ok(gw) => format!("icmp:{}", gw),
-                                Err(e) => {
-                                    warn!(schedule=%name, "Failed to resolve gateway: {}. Fallback to 192.168.1.1", e);
-                                    "icmp:192.168.1.1".to_string()
-                                }
-                            },
-                            "dns-check" | "dns-resolver" => "dns:1.1.1.1".to_string(),
-                            "http-check" | "http-reachability" => "http:google.com".to_string(),
-                            "speed-test-light" => "speed:wan".to_string(), // new speed alias
-                            "blame-check" => "blame:full".to_string(),     // explicit blame alias
-                            "anomaly-check" | "anomaly-scan" => "anomaly:scan".to_string(),
-                            other => other.to_string(),
-                        };
-
-                        // Parse "type:target" e.g. "icmp:8.8.8.8"
-                        let parts: Vec<&str> = resolved_spec.splitn(2, ':').collect();
-                        if parts.len() != 2 {
-                            warn!(schedule=%name, spec=%resolved_spec, "Invalid test spec. Expected 'type:target' (or known alias)");
-                            return;
-                        }
-                        let probe_kind = parts[0];
-                        let target = parts[1];
+    Some(tokio::spawn(async move {
+        let _guard = guard;
+        task.await;
+    }))
+}
 
-                        let timeout = Duration::from_secs(5);
+/// Run one scheduled test (`full_test_string` is a `type:target` spec, an
+/// alias, or a reflector test type configured by `params`).
+async fn run_task(
+    scheduler: Scheduler,
+    name: String,
+    full_test_string: String,
+    params: Option<serde_json::Value>,
+) {
+    // Apply random jitter (0-30s) to spread load and avoid thundering herds
+    {
+        let jitter_secs = rand::random::<u64>() % 30;
+        if jitter_secs > 0 {
+            info!(schedule=%name, jitter=%jitter_secs, "Applying schedule jitter");
+            tokio::time::sleep(Duration::from_secs(jitter_secs)).await;
+        }
+    }
 
-                        let result = match probe_kind {
-                            "icmp" => {
-                                let p = probes::icmp::IcmpProbe;
-                                p.run(target, timeout).await
-                            }
-                            "http" => {
-                                let p = probes::http::HttpProbe::default();
-                                p.run(target, timeout).await
-                            }
-                            "dns" => {
-                                let p = probes::dns::DnsProbe::default();
-                                p.run(target, timeout).await
-                            }
-                            "tcp" => {
-                                let p = probes::tcp::TcpProbe;
-                                p.run(target, timeout).await
-                            }
-                            "blame" => {
-                                // Blame check is special: it reads from DB and writes to DB.
-                                match crate::analysis::runner::perform_blame_analysis(
-                                    scheduler.get_pool(),
-                                )
-                                .await
-                                {
-                                    Ok(_) => {
-                                        info!(schedule=%name, "Blame analysis complete");
-                                        return; // Success
-                                    }
-                                    Err(e) => Err(e),
-                                }
-                            }
-                            "anomaly" => {
-                                // Anomaly scan task
-                                let engine = crate::detect::engine::AnomalyEngine::new(scheduler.get_pool().clone());
-                                match engine.run_scan().await {
-                                    Ok(_) => {
-                                        info!(schedule=%name, "Anomaly scan complete");
-                                        // Run Correlation immediately
-                                        let correlator = crate::analysis::correlation::CorrelationEngine::new(scheduler.get_pool().clone());
-                                        if let Err(e) = correlator.correlate().await {
-                                            warn!(schedule=%name, "Correlation failed: {}", e);
-                                        }
-                                        return; 
-                                    }
-                                    Err(e) => Err(e),
-                                }
-                            }
-                            "speed" => {
-                                // "speed:wan" or "speed:lan"
-                                let mode = if target == "lan" { "lan" } else { "wan" };
-                                
-                                info!(schedule=%name, "Waiting for bandwidth permit...");
-                                let sem = scheduler.get_bandwidth_permit();
-                                let _permit = match sem.acquire().await {
-                                    Ok(p) => {
-                                        info!(schedule=%name, "Bandwidth permit acquired");
-                                        p
-                                    },
-                                    Err(e) => {
-                                        error!(schedule=%name, "Failed to acquire bandwidth permit: {}", e);
-                                        return;
-                                    }
-                                };
-
-                                // Default params for scheduled test: 10s, 1 stream (lightweight)
-                                match crate::throughput::run_test(mode, None, "10s", 1).await {
-                                    Ok(_) => {
-                                        info!(schedule=%name, mode=%mode, "Speed test complete");
-                                        return; // Success
-                                    }
-                                    Err(e) => Err(e),
-                                }
-                            }
-                            _ => {
-                                warn!(schedule=%name, kind=%probe_kind, "Unknown probe type");
-                                return;
-                            }
-                        };
-
-                        match result {
-                            Ok(m) => {
-                                info!(schedule=%name, kind=%probe_kind, target=%target, value=%m.value, success=%m.success, "Probe finished");
-                                if let Err(e) = save_measurement(scheduler.get_pool(), &m) {
-                                    error!(schedule=%name, "Failed to save measurement: {}", e);
-                                }
-                            }
-                            Err(e) => {
-                                error!(schedule=%name, "Probe failed: {}", e);
+    let job_params = match JobParams::parse(&full_test_string, params.as_ref()) {
+        Ok(p) => p,
+        Err(e) => {
+            warn!(schedule=%name, "Invalid schedule params: {:#}", e);
+            return;
+        }
+    };
+
+    let Some((_heavy, _slot)) = admit(&scheduler, &name, &full_test_string, params.as_ref()).await else {
+        return;
+    };
+
+    let job_params = match job_params {
+        JobParams::Reflector(reflector_params) => {
+            run_reflector_task(&scheduler, &name, &full_test_string, reflector_params).await;
+            return;
+        }
+        p => p,
+    };
+
+    // Resolve Aliases first
+    let resolved_spec = match full_test_string.as_str() {
+        "icmp-gateway" => match network::get_default_gateway() {
+            Ok(gw) => format!("icmp:{}", gw),
+            Err(e) => {
+                warn!(schedule=%name, "Failed to resolve gateway: {}. Fallback to 192.168.1.1", e);
+                "icmp:192.168.1.1".to_string()
+            }
+        },
+        "dns-check" | "dns-resolver" => "dns:1.1.1.1".to_string(),
+        "http-check" | "http-reachability" => "http:google.com".to_string(),
+        "speed-test-light" => "speed:wan".to_string(), // new speed alias
+        "blame-check" => "blame:full".to_string(),     // explicit blame alias
+        "anomaly-check" | "anomaly-scan" => "anomaly:scan".to_string(),
+        other => other.to_string(),
+    };
+
+    // Parse "type:target" e.g. "icmp:8.8.8.8"; a target param overrides
+    // (or supplies) the spec's target.
+    let (probe_kind, spec_target) = match resolved_spec.split_once(':') {
+        Some((kind, target)) => (kind, Some(target.to_string())),
+        None => (resolved_spec.as_str(), None),
+    };
+    let Some(target) = job_params.target().or(spec_target) else {
+        warn!(schedule=%name, spec=%resolved_spec, "Invalid test spec. Expected 'type:target' (or known alias, or a target param)");
+        return;
+    };
+    let target = target.as_str();
+
+    let timeouts = probes::ProbeTimeouts::from_env();
+
+    let result = match probe_kind {
+        "icmp" => match probes::dscp::split_target(target) {
+            Ok((host, dscp)) => probes::icmp::IcmpProbe { dscp }.run(host, timeouts.icmp).await,
+            Err(e) => Err(e),
+        },
+        "http" => {
+            let p = probes::http::HttpProbe::default();
+            p.run(target, timeouts.http).await
+        }
+        "dns" => match probes::dscp::split_target(target) {
+            Ok((host, dscp)) => probes::dns::DnsProbe::with_dscp(dscp).run(host, timeouts.dns).await,
+            Err(e) => Err(e),
+        },
+        "tcp" => {
+            let p = probes::tcp::TcpProbe;
+            p.run(target, timeouts.tcp).await
+        }
+        "blame" => {
+            // Blame check is special: it reads from DB and writes to DB.
+            match crate::analysis::runner::perform_blame_analysis(
+                scheduler.get_pool(),
+            )
+            .await
+            {
+                Ok(_) => {
+                    info!(schedule=%name, "Blame analysis complete");
+                    return; // Success
+                }
+                Err(e) => Err(e),
+            }
+        }
+        "anomaly" => {
+            // Anomaly scan task
+            let engine = crate::detect::engine::AnomalyEngine::new(scheduler.get_pool().clone());
+            match engine.run_scan().await {
+                Ok(_) => {
+                    info!(schedule=%name, "Anomaly scan complete");
+                    // Run Correlation immediately
+                    let correlator = crate::analysis::correlation::CorrelationEngine::new(scheduler.get_pool().clone());
+                    if let Err(e) = correlator.correlate().await {
+                        warn!(schedule=%name, "Correlation failed: {}", e);
+                    }
+                    return; 
+                }
+                Err(e) => Err(e),
+            }
+        }
+        "trace" => {
+            let host = target.to_string();
+            match tokio::task::spawn_blocking(move || probes::trace::run_trace(&host)).await {
+                Ok(Ok(report)) => {
+                    if let Err(e) = probes::trace::save(scheduler.get_pool(), &report) {
+                        error!(schedule=%name, "Failed to save trace: {}", e);
+                    }
+                    info!(schedule=%name, target=%target, hops=report.report.mtr.hubs.len(), "Trace complete");
+                    return;
+                }
+                Ok(Err(e)) => Err(e),
+                Err(e) => Err(anyhow::anyhow!("trace task failed: {}", e)),
+            }
+        }
+        "speed" => {
+            // "speed:wan" or "speed:lan"
+            let mode = if target == "lan" { "lan" } else { "wan" };
+            let speed = match &job_params {
+                JobParams::Speed(p) => p.clone(),
+                _ => SpeedParams::default(),
+            };
+
+            if let Some(provider) = &speed.provider {
+                run_provider_speed_test(&scheduler, &name, provider).await;
+                return;
+            }
+
+            // Default params for scheduled test: 10s, 1 stream (lightweight)
+            let duration = format!("{}s", speed.duration_sec.unwrap_or(SCHEDULED_SPEED_TEST_SECS));
+            let streams = speed.streams.unwrap_or(1);
+            // Bracket the test with samples so even a short one has thermal
+            // evidence from inside its window.
+            let started = chrono::Utc::now();
+            thermal::sample_logged(scheduler.get_pool()).await;
+            let outcome = crate::throughput::run_test(
+                Some(scheduler.get_pool()),
+                mode,
+                None,
+                &duration,
+                streams,
+                crate::throughput::iperf::Protocol::Tcp,
+            )
+            .await;
+            thermal::sample_logged(scheduler.get_pool()).await;
+            match outcome {
+                Ok(results) => {
+                    let bytes: u64 = results.iter().map(|r| r.bytes_transferred).sum();
+                    if let Err(e) = budget::record_usage(scheduler.get_pool(), bytes) {
+                        error!(schedule=%name, "Failed to record data usage: {}", e);
+                    }
+                    let window = match thermal::during(scheduler.get_pool(), started, chrono::Utc::now()) {
+                        Ok(window) => {
+                            if let Some(note) = window.annotation() {
+                                warn!(schedule=%name, mode=%mode, "Speed test result unreliable: {}", note);
                             }
+                            Some(window)
                         }
-                    });
+                        Err(e) => {
+                            warn!(schedule=%name, "Failed to read thermal history: {:#}", e);
+                            None
+                        }
+                    };
+                    if let Some(mut result) = crate::throughput::provider::SpeedTestResult::from_throughput(results) {
+                        if let Some(window) = &window {
+                            result = result.with_thermal(window);
+                        }
+                        if let Err(e) = result.save(scheduler.get_pool()) {
+                            error!(schedule=%name, "Failed to save speed test result: {}", e);
+                        }
+                    }
+                    if let Some(window) = window {
+                        crate::system::power::record_undervoltage_logged(scheduler.get_pool(), format!("speed:{}", mode), window).await;
+                    }
+                    info!(schedule=%name, mode=%mode, bytes=%bytes, "Speed test complete");
+                    return; // Success
                 }
+                Err(e) => Err(e),
             }
-            Err(e) => {
-                error!("Failed to check due tasks: {}", e);
+        }
+        _ => {
+            warn!(schedule=%name, kind=%probe_kind, "Unknown probe type");
+            return;
+        }
+    };
+
+    match result {
+        Ok(m) => {
+            info!(schedule=%name, kind=%probe_kind, target=%target, value=%m.value, success=%m.success, "Probe finished");
+            if let Err(e) = save_measurement(scheduler.get_pool(), &m) {
+                error!(schedule=%name, "Failed to save measurement: {}", e);
+            }
+        }
+        Err(e) => {
+            error!(schedule=%name, "Probe failed: {}", e);
+        }
+    }
+}
+
+/// Wait until `test_type` may run: bandwidth-heavy tests first pass the
+/// budget and lock checks and take the bandwidth permit ([`begin_heavy_test`]),
+/// then every test waits for a job slot (probes go first). Taking the permit
+/// first keeps a speed test queued behind another one from holding a slot
+/// probes could use, or showing as running. `None` if the run is skipped.
+async fn admit(
+    scheduler: &Scheduler,
+    name: &str,
+    test_type: &str,
+    params: Option<&serde_json::Value>,
+) -> Option<(Option<HeavyTestSlot>, JobPermit)> {
+    let priority = Priority::for_test(test_type);
+    let heavy = if priority == Priority::SpeedTest {
+        let estimate = budget::estimate_run_bytes(scheduler.get_pool(), test_type, params);
+        Some(begin_heavy_test(scheduler, name, estimate).await?)
+    } else {
+        None
+    };
+    let slot = scheduler.job_queue().acquire(priority).await;
+    Some((heavy, slot))
+}
+
+/// Holds the bandwidth permit and the cross-process speed-test lock for
+/// the length of a bandwidth-heavy run.
+struct HeavyTestSlot {
+    _permit: tokio::sync::OwnedSemaphorePermit,
+    _lock: Option<crate::scheduler::speed_lock::SpeedTestLock>,
+}
+
+/// Gate a bandwidth-heavy run: `None` (after logging why) when what is
+/// left of today's data budget can't cover `estimated_bytes` (see
+/// [`budget::estimate_run_bytes`]) or another speed test holds the lock;
+/// otherwise waits for the scheduler's bandwidth permit.
+async fn begin_heavy_test(scheduler: &Scheduler, name: &str, estimated_bytes: u64) -> Option<HeavyTestSlot> {
+    // Bandwidth-safe mode: skip runs today's data budget can't cover.
+    match scheduler.data_budget().status(scheduler.get_pool()) {
+        Ok(status) if status.exhausted || status.remaining_bytes.is_some_and(|r| r < estimated_bytes) => {
+            warn!(
+                schedule=%name,
+                used_bytes=%status.used_bytes,
+                limit_bytes=?status.limit_bytes,
+                estimated_bytes=%estimated_bytes,
+                "Daily data budget can't cover this run, skipping speed test"
+            );
+            return None;
+        }
+        Ok(_) => {}
+        // Fail closed: an unreadable ledger must not lift the cap.
+        Err(e) if scheduler.data_budget().daily_bytes.is_some() => {
+            error!(schedule=%name, "Failed to read data budget, skipping speed test: {}", e);
+            return None;
+        }
+        Err(e) => warn!(schedule=%name, "Failed to read data budget: {}", e),
+    }
+
+    info!(schedule=%name, "Waiting for bandwidth permit...");
+    let permit = match scheduler.get_bandwidth_permit().acquire_owned().await {
+        Ok(p) => {
+            info!(schedule=%name, "Bandwidth permit acquired");
+            p
+        }
+        Err(e) => {
+            error!(schedule=%name, "Failed to acquire bandwidth permit: {}", e);
+            return None;
+        }
+    };
+
+    // A manual CLI test may be running in another process.
+    let lock = match crate::scheduler::speed_lock::SpeedTestLock::try_acquire(
+        scheduler.get_pool(),
+        "scheduler",
+    ) {
+        Ok(Ok(lock)) => Some(lock),
+        Ok(Err(holder)) => {
+            warn!(schedule=%name, %holder, "Another speed test is in progress, skipping");
+            return None;
+        }
+        Err(e) => {
+            warn!(schedule=%name, "Speed-test lock unavailable, running anyway: {:#}", e);
+            None
+        }
+    };
+
+    Some(HeavyTestSlot {
+        _permit: permit,
+        _lock: lock,
+    })
+}
+
+/// Run a speed test with a third-party provider (`provider` param), saving
+/// its normalized result to `probe_results`.
+async fn run_provider_speed_test(scheduler: &Scheduler, name: &str, provider: &str) {
+    let Some(p) = crate::throughput::provider::get_all_providers()
+        .into_iter()
+        .find(|p| p.meta().id == provider)
+    else {
+        warn!(schedule=%name, %provider, "Unknown speed test provider");
+        return;
+    };
+    if !p.is_available() {
+        warn!(schedule=%name, %provider, "Speed test provider not installed: {}", p.meta().install_hint);
+        return;
+    }
+    let req = crate::throughput::provider::SpeedTestRequest {
+        timeout: crate::throughput::provider::DEFAULT_TIMEOUT,
+        prefer_ipv6: false,
+        server_hint: None,
+    };
+    match p.run(req).await {
+        Ok(result) => {
+            // Providers don't report bytes moved; charge what the measured
+            // rates imply, as manual comparisons do.
+            let bytes = crate::throughput::compare::estimated_bytes(&result);
+            if let Err(e) = budget::record_usage(scheduler.get_pool(), bytes) {
+                error!(schedule=%name, "Failed to record data usage: {}", e);
+            }
+            info!(schedule=%name, %provider, download_mbps=?result.download_mbps, upload_mbps=?result.upload_mbps, bytes=%bytes, "Speed test complete");
+            if let Err(e) = result.save(scheduler.get_pool()) {
+                error!(schedule=%name, "Failed to save speed test result: {}", e);
+            }
+        }
+        Err(e) => error!(schedule=%name, %provider, "Speed test failed: {:#}", e),
+    }
+}
+
+/// Run a `reflector_throughput` / `reflector_udp_echo` schedule against the
+/// paired reflector named in its params. The caller has already admitted
+/// it (see [`admit`]), bandwidth permit included for throughput tests.
+async fn run_reflector_task(
+    scheduler: &Scheduler,
+    name: &str,
+    test_type: &str,
+    params: ReflectorJobParams,
+) {
+    let home = std::env::var("HOME").unwrap_or_else(|_| ".".into());
+    let dir = std::path::Path::new(&home).join(".packetparamedic");
+    let target = match reflector_jobs::Target::resolve(&dir, &params.reflector) {
+        Ok(t) => t,
+        Err(e) => {
+            error!(schedule=%name, reflector=%params.reflector, "Reflector unavailable: {:#}", e);
+            return;
+        }
+    };
+
+    if test_type == reflector_jobs::THROUGHPUT {
+        let duration_sec = params.duration_sec.unwrap_or(SCHEDULED_SPEED_TEST_SECS as u64);
+        let omit_sec = params.omit_sec.unwrap_or(0);
+        match reflector_jobs::run_throughput(scheduler.get_pool(), &target, duration_sec, omit_sec).await {
+            Ok(sessions) => {
+                let bytes = reflector_jobs::bytes_moved(&sessions, duration_sec + u64::from(omit_sec));
+                if let Err(e) = budget::record_usage(scheduler.get_pool(), bytes) {
+                    error!(schedule=%name, "Failed to record data usage: {}", e);
+                }
+                for s in &sessions {
+                    info!(schedule=%name, reflector=%target.nickname, direction=%s.direction, mbps=%s.client_mbps, "Reflector throughput complete");
+                }
+            }
+            Err(e) => error!(schedule=%name, reflector=%target.nickname, "Reflector throughput failed: {:#}", e),
+        }
+    } else {
+        match reflector_jobs::run_udp_echo(scheduler.get_pool(), &target, &params).await {
+            Ok(report) => {
+                info!(schedule=%name, reflector=%target.nickname, sent=%report.sent, received=%report.received, avg_rtt_ms=?report.avg_rtt_ms, "Reflector UDP echo complete");
             }
+            Err(e) => error!(schedule=%name, reflector=%target.nickname, "Reflector UDP echo failed: {:#}", e),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_overrunning_schedule_is_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = crate::storage::open_pool(dir.path().join("t.db").to_str().unwrap()).unwrap();
+        let scheduler = Scheduler::new(pool.clone()).with_min_interval(Duration::from_secs(1));
+        // Every second, with a run that takes much longer.
+        scheduler.add_schedule("slow", "* * * * * *", "slow:test").await.unwrap();
+        let slow_run = || tokio::time::sleep(Duration::from_millis(3000));
+
+        let first = dispatch(&scheduler, "slow", slow_run()).await;
+        assert!(first.is_some());
+
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        let due = scheduler.check_due_tasks().await.unwrap();
+        assert!(due.iter().any(|(name, _, _)| name == "slow"));
+        assert!(dispatch(&scheduler, "slow", slow_run()).await.is_none());
+
+        let skipped = history::recent(&pool, "slow", 10).unwrap();
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].status, RunStatus::Skipped);
+        assert_eq!(skipped[0].result_summary.as_deref(), Some("skipped due to overrun"));
+
+        // Once the slow run finishes the next fire runs normally.
+        first.unwrap().await.unwrap();
+        assert!(dispatch(&scheduler, "slow", slow_run()).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_speed_test_waiting_on_permit_holds_no_job_slot() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = crate::storage::open_pool(dir.path().join("t.db").to_str().unwrap()).unwrap();
+        let scheduler = Scheduler::with_budget(pool, budget::DataBudget::default()).with_max_concurrent_jobs(1);
+
+        // Another speed test holds the bandwidth permit.
+        let permit = scheduler.get_bandwidth_permit().acquire_owned().await.unwrap();
+        let waiting = {
+            let scheduler = scheduler.clone();
+            tokio::spawn(async move { admit(&scheduler, "speed", "speed:wan", None).await.is_some() })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(scheduler.job_queue().counts().running, 0);
+
+        // A probe still gets the only slot.
+        let probe = tokio::time::timeout(Duration::from_secs(1), admit(&scheduler, "icmp", "icmp:1.1.1.1", None))
+            .await
+            .expect("probe blocked behind a waiting speed test")
+            .unwrap();
+        assert!(probe.0.is_none());
+        drop(probe);
+
+        drop(permit);
+        assert!(waiting.await.unwrap());
+    }
+}
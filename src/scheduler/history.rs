@@ -1,5 +1,9 @@
 //! Execution history tracking for scheduled runs.
 
+use crate::storage::Pool;
+use anyhow::Result;
+use rusqlite::params;
+
 /// A record of a scheduled test execution.
 #[derive(Debug, serde::Serialize)]
 pub struct HistoryEntry {
@@ -10,10 +14,65 @@ pub struct HistoryEntry {
     pub finished_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
-#[derive(Debug, serde::Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
 pub enum RunStatus {
     Success,
     Failed,
     Aborted,
     Missed,
+    /// Not started because the previous run of the schedule was still going.
+    Skipped,
+}
+
+impl RunStatus {
+    fn parse(s: &str) -> Self {
+        match s {
+            "Success" => Self::Success,
+            "Failed" => Self::Failed,
+            "Aborted" => Self::Aborted,
+            "Skipped" => Self::Skipped,
+            _ => Self::Missed,
+        }
+    }
+}
+
+/// Append an entry to `schedule_history`.
+pub fn record(pool: &Pool, entry: &HistoryEntry) -> Result<()> {
+    let conn = pool.get()?;
+    conn.execute(
+        "INSERT INTO schedule_history (schedule_name, status, result_summary, started_at, finished_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            entry.schedule_name,
+            format!("{:?}", entry.status),
+            entry.result_summary,
+            entry.started_at.to_rfc3339(),
+            entry.finished_at.map(|t| t.to_rfc3339()),
+        ],
+    )?;
+    Ok(())
+}
+
+/// The most recent `limit` entries for `schedule_name`, newest first.
+pub fn recent(pool: &Pool, schedule_name: &str, limit: usize) -> Result<Vec<HistoryEntry>> {
+    let conn = pool.get()?;
+    let mut stmt = conn.prepare(
+        "SELECT status, result_summary, started_at, finished_at FROM schedule_history
+         WHERE schedule_name = ?1 ORDER BY id DESC LIMIT ?2",
+    )?;
+    let parse = |s: String| {
+        chrono::DateTime::parse_from_rfc3339(&s)
+            .map(|t| t.with_timezone(&chrono::Utc))
+            .unwrap_or_default()
+    };
+    let rows = stmt.query_map(params![schedule_name, limit], |row| {
+        Ok(HistoryEntry {
+            schedule_name: schedule_name.to_string(),
+            status: RunStatus::parse(&row.get::<_, String>(0)?),
+            result_summary: row.get(1)?,
+            started_at: parse(row.get(2)?),
+            finished_at: row.get::<_, Option<String>>(3)?.map(parse),
+        })
+    })?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
 }
@@ -1,7 +1,35 @@
+pub mod budget;
 pub mod cron;
 pub mod engine;
+pub mod history;
+pub mod params;
 pub mod profiles;
 pub mod queue;
+pub mod reflector_jobs;
+pub mod speed_lock;
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum SchedulerError {
+    #[error("invalid cron expression '{expr}': {field} field '{value}' {reason}")]
+    InvalidCron {
+        expr: String,
+        field: &'static str,
+        value: String,
+        reason: String,
+    },
+
+    #[error("invalid cron expression '{expr}': expected 5 fields (minute hour day-of-month month day-of-week) or 6 with seconds first, got {count}")]
+    CronFieldCount { expr: String, count: usize },
+
+    #[error("cron expression '{expr}' fires every {interval_secs}s, more often than the {min_secs}s minimum (PP_SCHEDULE_MIN_INTERVAL_SECS)")]
+    TooFrequent {
+        expr: String,
+        interval_secs: u64,
+        min_secs: u64,
+    },
+}
 
 // Re-export common types
 pub use self::cron::Scheduler;
@@ -0,0 +1,202 @@
+//! Per-schedule test parameters.
+//!
+//! A schedule's optional `params` JSON is parsed into the typed parameters
+//! of its test type when it is added (so typos fail fast) and again when it
+//! runs. Unknown keys are rejected.
+
+use anyhow::{bail, Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::scheduler::reflector_jobs::{self, ReflectorJobParams};
+
+/// Params of the cheap probes (`icmp`, `dns`, `http`, `tcp`).
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProbeParams {
+    /// Overrides (or supplies) the target of a `type:target` spec.
+    #[serde(default)]
+    pub target: Option<String>,
+}
+
+/// Params of `speed` tests.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpeedParams {
+    /// `wan` or `lan`; overrides the spec's target.
+    #[serde(default)]
+    pub mode: Option<String>,
+    /// Run a provider (`ookla-cli`, `ndt7`, `fast`, `web`) instead of iperf3.
+    #[serde(default)]
+    pub provider: Option<String>,
+    /// Per-direction iperf3 duration (default `SCHEDULED_SPEED_TEST_SECS`);
+    /// providers run their own fixed-length test.
+    #[serde(default)]
+    pub duration_sec: Option<u32>,
+    /// Parallel iperf3 streams (default 1).
+    #[serde(default)]
+    pub streams: Option<u32>,
+}
+
+impl SpeedParams {
+    /// Refuse a provider that isn't one of
+    /// [`get_all_providers`](crate::throughput::provider::get_all_providers),
+    /// which is all the scheduler can run.
+    fn validate(&self) -> Result<()> {
+        let Some(provider) = &self.provider else {
+            return Ok(());
+        };
+        let known: Vec<&str> = crate::throughput::provider::get_all_providers()
+            .iter()
+            .map(|p| p.meta().id)
+            .collect();
+        if !known.contains(&provider.as_str()) {
+            bail!("unknown speed test provider '{}' (expected one of: {})", provider, known.join(", "));
+        }
+        Ok(())
+    }
+}
+
+/// Params of `trace` tests.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TraceParams {
+    #[serde(default)]
+    pub target: Option<String>,
+}
+
+/// Typed params of a schedule, by test type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JobParams {
+    Probe(ProbeParams),
+    Speed(SpeedParams),
+    Trace(TraceParams),
+    Reflector(ReflectorJobParams),
+    /// Test types that take no params (`blame`, `anomaly`, unknown types).
+    None,
+}
+
+impl JobParams {
+    /// Parse `params` for `test_type` (a `type:target` spec, alias or
+    /// reflector test type).
+    pub fn parse(test_type: &str, params: Option<&Value>) -> Result<Self> {
+        if reflector_jobs::is_reflector_test(test_type) {
+            let params = ReflectorJobParams::parse(params)?;
+            params.validate(test_type)?;
+            return Ok(Self::Reflector(params));
+        }
+        let kind = kind(test_type);
+        let parsed = match kind {
+            "icmp" | "dns" | "http" | "tcp" => Self::Probe(typed(kind, params)?),
+            "speed" => {
+                let params: SpeedParams = typed(kind, params)?;
+                params.validate()?;
+                Self::Speed(params)
+            }
+            "trace" => Self::Trace(typed(kind, params)?),
+            _ => {
+                if params.is_some_and(|p| p.as_object().map_or(true, |o| !o.is_empty())) {
+                    bail!("'{}' tests take no params", kind);
+                }
+                Self::None
+            }
+        };
+        Ok(parsed)
+    }
+
+    /// Target from the params, overriding the spec's (for speed tests, the
+    /// mode).
+    pub fn target(&self) -> Option<String> {
+        match self {
+            Self::Probe(p) => p.target.clone(),
+            Self::Speed(p) => p.mode.clone(),
+            Self::Trace(p) => p.target.clone(),
+            Self::Reflector(_) | Self::None => None,
+        }
+    }
+}
+
+/// Test kind of a spec or alias, e.g. `icmp` for `icmp:8.8.8.8` and
+/// `icmp-gateway`.
+pub fn kind(test_type: &str) -> &str {
+    match test_type {
+        "icmp-gateway" => "icmp",
+        "dns-check" | "dns-resolver" => "dns",
+        "http-check" | "http-reachability" => "http",
+        "speed-test-light" => "speed",
+        "blame-check" => "blame",
+        "anomaly-check" | "anomaly-scan" => "anomaly",
+        other => other.split_once(':').map_or(other, |(kind, _)| kind),
+    }
+}
+
+fn typed<T: DeserializeOwned + Default>(kind: &str, params: Option<&Value>) -> Result<T> {
+    match params {
+        None => Ok(T::default()),
+        Some(p) => serde_json::from_value(p.clone()).with_context(|| format!("invalid params for '{}' test", kind)),
+    }
+}
+
+/// Merge `key=value` pairs (from `--param`) into `base`, which must be a
+/// JSON object if given. Numbers and booleans are stored as such, anything
+/// else as a string. `None` if there is nothing to store.
+pub fn merge_pairs(base: Option<Value>, pairs: &[String]) -> Result<Option<Value>> {
+    let mut map = match base {
+        None => Map::new(),
+        Some(Value::Object(map)) => map,
+        Some(other) => bail!("params must be a JSON object, got {}", other),
+    };
+    for pair in pairs {
+        let (key, value) = pair
+            .split_once('=')
+            .filter(|(k, _)| !k.trim().is_empty())
+            .with_context(|| format!("invalid param '{}', expected key=value", pair))?;
+        let value = match serde_json::from_str::<Value>(value) {
+            Ok(v) if v.is_number() || v.is_boolean() => v,
+            _ => Value::String(value.to_string()),
+        };
+        map.insert(key.trim().to_string(), value);
+    }
+    Ok((!map.is_empty()).then_some(Value::Object(map)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_merge_pairs() {
+        let pairs = ["provider=ndt7".to_string(), "duration_sec=15".to_string(), "target=1.1.1.1".to_string()];
+        let merged = merge_pairs(Some(json!({ "provider": "fast" })), &pairs).unwrap().unwrap();
+        assert_eq!(merged, json!({ "provider": "ndt7", "duration_sec": 15, "target": "1.1.1.1" }));
+
+        assert_eq!(merge_pairs(None, &[]).unwrap(), None);
+        assert!(merge_pairs(None, &["novalue".to_string()]).is_err());
+        assert!(merge_pairs(None, &["=x".to_string()]).is_err());
+        assert!(merge_pairs(Some(json!([1])), &[]).is_err());
+    }
+
+    #[test]
+    fn test_parse_by_test_type() {
+        let speed = JobParams::parse("speed-test-light", Some(&json!({ "provider": "ndt7" }))).unwrap();
+        assert_eq!(
+            speed,
+            JobParams::Speed(SpeedParams { provider: Some("ndt7".into()), ..Default::default() })
+        );
+
+        let trace = JobParams::parse("trace", Some(&json!({ "target": "1.1.1.1" }))).unwrap();
+        assert_eq!(trace.target().as_deref(), Some("1.1.1.1"));
+
+        assert_eq!(JobParams::parse("icmp:8.8.8.8", None).unwrap(), JobParams::Probe(ProbeParams::default()));
+        assert_eq!(JobParams::parse("blame-check", None).unwrap(), JobParams::None);
+
+        // Typos and params on tests that take none fail.
+        assert!(JobParams::parse("speed:wan", Some(&json!({ "providr": "ndt7" }))).is_err());
+        assert!(JobParams::parse("speed:wan", Some(&json!({ "provider": "ndt8" }))).is_err());
+        assert!(JobParams::parse("speed:wan", Some(&json!({ "provider": "ookla-cli" }))).is_ok());
+        assert!(JobParams::parse("blame-check", Some(&json!({ "target": "x" }))).is_err());
+        assert!(JobParams::parse(reflector_jobs::THROUGHPUT, None).is_err());
+    }
+}
@@ -8,6 +8,7 @@ pub struct DefaultSchedule {
     pub enabled: bool,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Profile {
     Minimal,
     Standard,
@@ -15,6 +16,14 @@ pub enum Profile {
 }
 
 impl Profile {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Minimal => "minimal",
+            Self::Standard => "standard",
+            Self::Aggressive => "aggressive",
+        }
+    }
+
     pub fn from_str(s: &str) -> Option<Self> {
         match s.to_lowercase().as_str() {
             "minimal" => Some(Self::Minimal),
@@ -2,6 +2,20 @@
 //!
 //! Priority order: blame-check > probes > speed tests > stress tests.
 //! User-triggered tests preempt scheduled background tests.
+//!
+//! [`JobQueue`] caps how many scheduled tests run at once, whatever their
+//! type, so a burst of schedules due at the same moment can't overload a
+//! small board. Jobs over the limit wait in priority order, oldest first
+//! within a priority.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::oneshot;
+
+/// Default cap on concurrently running scheduled tests.
+pub const DEFAULT_MAX_CONCURRENT_JOBS: usize = 4;
 
 /// Test priority levels (lower number = higher priority).
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize)]
@@ -12,6 +26,17 @@ pub enum Priority {
     StressTest = 3,
 }
 
+impl Priority {
+    /// Priority of a schedule's test type (spec, alias or reflector type).
+    pub fn for_test(test_type: &str) -> Self {
+        match crate::scheduler::params::kind(test_type) {
+            "blame" => Self::BlameCheck,
+            "speed" | crate::scheduler::reflector_jobs::THROUGHPUT => Self::SpeedTest,
+            _ => Self::Probe,
+        }
+    }
+}
+
 /// A queued test job.
 #[derive(Debug)]
 pub struct Job {
@@ -20,3 +45,216 @@ pub struct Job {
     pub priority: Priority,
     pub user_triggered: bool,
 }
+
+/// Running and waiting job counts, for the status endpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub struct QueueCounts {
+    pub running: usize,
+    pub queued: usize,
+    pub limit: usize,
+}
+
+/// Limits concurrently running jobs, admitting waiters by priority.
+#[derive(Debug, Clone)]
+pub struct JobQueue {
+    state: Arc<Mutex<QueueState>>,
+    limit: usize,
+}
+
+#[derive(Debug, Default)]
+struct QueueState {
+    running: usize,
+    waiting: BinaryHeap<Waiter>,
+    next_seq: u64,
+}
+
+#[derive(Debug)]
+struct Waiter {
+    priority: Priority,
+    seq: u64,
+    wake: oneshot::Sender<()>,
+}
+
+// Max-heap: the most urgent priority, then the oldest, pops first.
+impl Ord for Waiter {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .priority
+            .cmp(&self.priority)
+            .then(other.seq.cmp(&self.seq))
+    }
+}
+
+impl PartialOrd for Waiter {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Waiter {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Waiter {}
+
+impl JobQueue {
+    /// A queue running at most `limit` jobs at once (at least one).
+    pub fn new(limit: usize) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(QueueState::default())),
+            limit: limit.max(1),
+        }
+    }
+
+    /// Read `PP_MAX_CONCURRENT_JOBS` (default 4).
+    pub fn from_env() -> Self {
+        let limit = match std::env::var("PP_MAX_CONCURRENT_JOBS") {
+            Ok(v) => match v.trim().parse::<usize>() {
+                Ok(n) if n > 0 => n,
+                _ => {
+                    tracing::warn!(value = %v, "Ignoring invalid PP_MAX_CONCURRENT_JOBS");
+                    DEFAULT_MAX_CONCURRENT_JOBS
+                }
+            },
+            Err(_) => DEFAULT_MAX_CONCURRENT_JOBS,
+        };
+        Self::new(limit)
+    }
+
+    /// Wait for a slot; the job runs until the returned permit is dropped.
+    pub async fn acquire(&self, priority: Priority) -> JobPermit {
+        let rx = {
+            let mut state = self.lock();
+            if state.running < self.limit && state.waiting.is_empty() {
+                state.running += 1;
+                return JobPermit { queue: self.clone() };
+            }
+            let (wake, rx) = oneshot::channel();
+            let seq = state.next_seq;
+            state.next_seq += 1;
+            state.waiting.push(Waiter { priority, seq, wake });
+            rx
+        };
+
+        let mut pending = PendingSlot {
+            rx: Some(rx),
+            queue: self,
+        };
+        if let Some(rx) = pending.rx.as_mut() {
+            // The sender is only dropped after handing over a slot.
+            let _ = rx.await;
+        }
+        pending.rx = None;
+        JobPermit { queue: self.clone() }
+    }
+
+    pub fn counts(&self) -> QueueCounts {
+        let state = self.lock();
+        QueueCounts {
+            running: state.running,
+            queued: state.waiting.len(),
+            limit: self.limit,
+        }
+    }
+
+    /// Hand a finished job's slot to the next waiter, or free it.
+    fn release(&self) {
+        let mut state = self.lock();
+        while let Some(waiter) = state.waiting.pop() {
+            if waiter.wake.send(()).is_ok() {
+                return;
+            }
+        }
+        state.running -= 1;
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A running job's slot in a [`JobQueue`].
+#[derive(Debug)]
+pub struct JobPermit {
+    queue: JobQueue,
+}
+
+impl Drop for JobPermit {
+    fn drop(&mut self) {
+        self.queue.release();
+    }
+}
+
+/// A waiter whose `acquire` may be cancelled: a slot handed over after
+/// cancellation is passed on rather than leaked.
+struct PendingSlot<'a> {
+    rx: Option<oneshot::Receiver<()>>,
+    queue: &'a JobQueue,
+}
+
+impl Drop for PendingSlot<'_> {
+    fn drop(&mut self) {
+        if let Some(mut rx) = self.rx.take() {
+            rx.close();
+            if rx.try_recv().is_ok() {
+                self.queue.release();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_limit_and_priority_order() {
+        let queue = JobQueue::new(1);
+        let first = queue.acquire(Priority::SpeedTest).await;
+        assert_eq!(queue.counts(), QueueCounts { running: 1, queued: 0, limit: 1 });
+
+        let (done_tx, mut done_rx) = tokio::sync::mpsc::unbounded_channel();
+        for (label, priority) in [("speed", Priority::SpeedTest), ("probe", Priority::Probe)] {
+            let queue = queue.clone();
+            let done_tx = done_tx.clone();
+            tokio::spawn(async move {
+                let _permit = queue.acquire(priority).await;
+                done_tx.send(label).unwrap();
+            });
+            // Queue them in a known order.
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(queue.counts().queued, 2);
+
+        // The probe was queued last but runs first.
+        drop(first);
+        assert_eq!(done_rx.recv().await, Some("probe"));
+        assert_eq!(done_rx.recv().await, Some("speed"));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(queue.counts(), QueueCounts { running: 0, queued: 0, limit: 1 });
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_waiter_does_not_leak_slot() {
+        let queue = JobQueue::new(1);
+        let first = queue.acquire(Priority::Probe).await;
+        let waiting = tokio::time::timeout(Duration::from_millis(20), queue.acquire(Priority::Probe)).await;
+        assert!(waiting.is_err());
+
+        drop(first);
+        assert_eq!(queue.counts().running, 0);
+        let _again = queue.acquire(Priority::Probe).await;
+        assert_eq!(queue.counts().running, 1);
+    }
+
+    #[test]
+    fn test_priority_for_test() {
+        assert_eq!(Priority::for_test("blame-check"), Priority::BlameCheck);
+        assert_eq!(Priority::for_test("speed:wan"), Priority::SpeedTest);
+        assert_eq!(Priority::for_test("reflector_throughput"), Priority::SpeedTest);
+        assert_eq!(Priority::for_test("icmp-gateway"), Priority::Probe);
+    }
+}
@@ -0,0 +1,282 @@
+//! Scheduled tests against a paired reflector.
+//!
+//! `reflector_throughput` and `reflector_udp_echo` schedules name the
+//! reflector in their params (e.g. `{"reflector": "office"}`), looked up by
+//! nickname or address in the paired reflector store. Throughput runs are
+//! saved to `reflector_sessions` like a manual `--provider reflector` run;
+//! UDP echo reports go to `probe_results`.
+
+use std::net::SocketAddr;
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+
+use crate::reflector_proto::client::ReflectorClient;
+use crate::reflector_proto::identity::Identity;
+use crate::reflector_proto::peers::{PeerStore, PEER_STORE_FILE};
+use crate::reflector_proto::udp_echo::{UdpEchoMode, UdpEchoReport};
+use crate::storage::Pool;
+use crate::throughput::provider::reflector::{ReflectorProvider, SessionComparison, MIN_TEST_SECS};
+use crate::throughput::provider::SpeedTestRequest;
+
+/// Schedule test type for a throughput test to a reflector.
+pub const THROUGHPUT: &str = "reflector_throughput";
+
+/// Schedule test type for a UDP echo test to a reflector.
+pub const UDP_ECHO: &str = "reflector_udp_echo";
+
+/// Default UDP echo datagrams per run.
+const DEFAULT_ECHO_COUNT: u32 = 100;
+
+/// Default UDP echo payload, in bytes.
+const DEFAULT_ECHO_SIZE: usize = 64;
+
+/// Whether `test_type` is one of the reflector test types.
+pub fn is_reflector_test(test_type: &str) -> bool {
+    test_type == THROUGHPUT || test_type == UDP_ECHO
+}
+
+/// Params of a reflector schedule.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReflectorJobParams {
+    /// Nickname or address of a paired reflector.
+    pub reflector: String,
+    /// Test length per direction (throughput, at least [`MIN_TEST_SECS`]) or
+    /// session length (UDP echo).
+    #[serde(default)]
+    pub duration_sec: Option<u64>,
+    /// Seconds of TCP warm-up to leave out of throughput averages (iperf3
+    /// `-O`), run on top of `duration_sec`.
+    #[serde(default)]
+    pub omit_sec: Option<u32>,
+    /// UDP echo datagrams to send.
+    #[serde(default)]
+    pub count: Option<u32>,
+    /// UDP echo payload size in bytes.
+    #[serde(default)]
+    pub size: Option<usize>,
+}
+
+impl ReflectorJobParams {
+    /// Parse a schedule's params; reflector schedules must name a reflector.
+    pub fn parse(params: Option<&serde_json::Value>) -> Result<Self> {
+        let params = params.ok_or_else(|| anyhow!("reflector schedules need params naming a reflector"))?;
+        serde_json::from_value(params.clone()).context("invalid reflector schedule params")
+    }
+
+    /// Check the params against `test_type`. A throughput `duration_sec`
+    /// under [`MIN_TEST_SECS`] is refused rather than silently raised, and
+    /// `omit_sec` only applies to throughput.
+    pub fn validate(&self, test_type: &str) -> Result<()> {
+        if self.omit_sec.is_some() && test_type != THROUGHPUT {
+            anyhow::bail!("omit_sec only applies to {} schedules", THROUGHPUT);
+        }
+        if let Some(secs) = self.duration_sec.filter(|&s| test_type == THROUGHPUT && s < MIN_TEST_SECS) {
+            anyhow::bail!(
+                "reflector throughput tests run at least {}s per direction (duration_sec {})",
+                MIN_TEST_SECS,
+                secs
+            );
+        }
+        Ok(())
+    }
+}
+
+/// A paired reflector resolved for a scheduled run.
+pub struct Target {
+    pub nickname: String,
+    pub address: SocketAddr,
+    pub identity: Identity,
+}
+
+// Hand-written so the signing key never ends up in logs.
+impl std::fmt::Debug for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Target")
+            .field("nickname", &self.nickname)
+            .field("address", &self.address)
+            .field("identity", &self.identity.endpoint_id().to_string())
+            .finish()
+    }
+}
+
+impl Target {
+    /// Look `name` up in the reflector store under `dir` (`~/.packetparamedic`).
+    pub fn resolve(dir: &Path, name: &str) -> Result<Self> {
+        let store = PeerStore::load(&dir.join(PEER_STORE_FILE))?;
+        let stored = store
+            .find(name)
+            .ok_or_else(|| anyhow!("no paired reflector named '{}'", name))?;
+        let address = stored
+            .address
+            .parse()
+            .with_context(|| format!("invalid reflector address: {}", stored.address))?;
+        let identity = Identity::load(&dir.join("identity.key")).context("Failed to load identity")?;
+        Ok(Self {
+            nickname: stored.nickname.clone(),
+            address,
+            identity,
+        })
+    }
+}
+
+/// Upload and download to the reflector, saving both sides of each session.
+/// `omit_sec` of warm-up run on top of `duration_sec` and are left out of
+/// the averages. Returns the sessions for data budget accounting.
+pub async fn run_throughput(
+    pool: &Pool,
+    target: &Target,
+    duration_sec: u64,
+    omit_sec: u32,
+) -> Result<Vec<SessionComparison>> {
+    let (result, sessions) = ReflectorProvider
+        .run_sessions(
+            SpeedTestRequest {
+                timeout: std::time::Duration::from_secs(duration_sec),
+                prefer_ipv6: false,
+                server_hint: Some(target.address.to_string()),
+            },
+            omit_sec,
+        )
+        .await?;
+    for s in &sessions {
+        s.save(pool, &target.nickname)?;
+    }
+    result.save(pool)?;
+    Ok(sessions)
+}
+
+/// Fixed-size UDP echo to the reflector, saved to `probe_results`.
+pub async fn run_udp_echo(pool: &Pool, target: &Target, params: &ReflectorJobParams) -> Result<UdpEchoReport> {
+    let mode = UdpEchoMode::Fixed {
+        size: params.size.unwrap_or(DEFAULT_ECHO_SIZE),
+        count: params.count.unwrap_or(DEFAULT_ECHO_COUNT),
+    };
+    let duration_sec = params
+        .duration_sec
+        .unwrap_or(crate::scheduler::engine::SCHEDULED_SPEED_TEST_SECS as u64);
+
+    let mut client = ReflectorClient::connect(target.address, &target.identity).await?;
+    let grant = client.request_udp_echo_session(duration_sec, false).await?;
+    let report = ReflectorClient::run_udp_echo(&grant, target.address.ip(), &mode).await;
+    if let Err(e) = client.close_session(&grant.test_id).await {
+        tracing::warn!(test_id = %grant.test_id, error = %e, "failed to close reflector session");
+    }
+    let report = report?;
+    save_udp_echo(pool, &target.nickname, &report)?;
+    Ok(report)
+}
+
+fn save_udp_echo(pool: &Pool, reflector: &str, report: &UdpEchoReport) -> Result<()> {
+    let conn = pool.get()?;
+    conn.execute(
+        "INSERT INTO probe_results (probe_type, target, result_json) VALUES (?1, ?2, ?3)",
+        rusqlite::params![UDP_ECHO, reflector, serde_json::to_string(report)?],
+    )?;
+    Ok(())
+}
+
+/// Bytes the sessions moved, for the daily data budget. Uses the
+/// reflector's count where it sent a summary, else the client's rate.
+pub fn bytes_moved(sessions: &[SessionComparison], duration_sec: u64) -> u64 {
+    sessions
+        .iter()
+        .map(|s| match &s.reflector {
+            Some(summary) => summary.bytes_transferred,
+            None => (s.client_mbps * 1_000_000.0 / 8.0 * duration_sec as f64) as u64,
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reflector_proto::peers::StoredReflector;
+
+    #[test]
+    fn test_params_parse() {
+        let params = serde_json::json!({ "reflector": "office", "duration_sec": 5 });
+        let parsed = ReflectorJobParams::parse(Some(&params)).unwrap();
+        assert_eq!(parsed.reflector, "office");
+        assert_eq!(parsed.duration_sec, Some(5));
+        assert_eq!(parsed.count, None);
+
+        assert!(ReflectorJobParams::parse(None).is_err());
+        assert!(ReflectorJobParams::parse(Some(&serde_json::json!({ "duration_sec": 5 }))).is_err());
+        // A misspelt key is refused, not silently ignored.
+        let typo = serde_json::json!({ "reflector": "office", "duration_secs": 30 });
+        assert!(ReflectorJobParams::parse(Some(&typo)).is_err());
+
+        // Too short for throughput, fine for a UDP echo session.
+        assert!(parsed.validate(THROUGHPUT).is_err());
+        assert!(parsed.validate(UDP_ECHO).is_ok());
+
+        // A warm-up to omit only makes sense for throughput.
+        let params = serde_json::json!({ "reflector": "office", "omit_sec": 3 });
+        let parsed = ReflectorJobParams::parse(Some(&params)).unwrap();
+        assert_eq!(parsed.omit_sec, Some(3));
+        assert!(parsed.validate(THROUGHPUT).is_ok());
+        assert!(parsed.validate(UDP_ECHO).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_schedule_keeps_params() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = crate::storage::open_pool(dir.path().join("t.db").to_str().unwrap()).unwrap();
+        let scheduler = crate::scheduler::Scheduler::new(pool);
+
+        // A reflector test without a reflector is rejected up front.
+        assert!(scheduler.add_schedule("nightly-lan", "0 2 * * *", THROUGHPUT).await.is_err());
+
+        let params = serde_json::json!({ "reflector": "office" });
+        scheduler
+            .add_schedule_with_params("nightly-lan", "0 2 * * *", THROUGHPUT, Some(&params))
+            .await
+            .unwrap();
+        let due = scheduler.check_due_tasks().await.unwrap();
+        assert_eq!(due, vec![("nightly-lan".to_string(), THROUGHPUT.to_string(), Some(params))]);
+    }
+
+    #[test]
+    fn test_resolve_by_nickname() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = PeerStore::load(&dir.path().join(PEER_STORE_FILE)).unwrap();
+        store.upsert(StoredReflector {
+            nickname: "office".to_string(),
+            address: "10.0.0.2:4000".to_string(),
+            endpoint_id: None,
+            paired_at: "2026-01-01T00:00:00Z".to_string(),
+        });
+        store.save().unwrap();
+        Identity::generate().save(&dir.path().join("identity.key")).unwrap();
+
+        let target = Target::resolve(dir.path(), "office").unwrap();
+        assert_eq!(target.address, "10.0.0.2:4000".parse().unwrap());
+        assert!(Target::resolve(dir.path(), "garage").is_err());
+    }
+
+    #[test]
+    fn test_save_udp_echo() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = crate::storage::open_pool(dir.path().join("t.db").to_str().unwrap()).unwrap();
+        let report = UdpEchoReport {
+            sent: 100,
+            received: 98,
+            avg_rtt_ms: Some(0.4),
+            ..Default::default()
+        };
+        save_udp_echo(&pool, "office", &report).unwrap();
+
+        let (probe_type, json): (String, String) = pool
+            .get()
+            .unwrap()
+            .query_row("SELECT probe_type, result_json FROM probe_results", [], |r| {
+                Ok((r.get(0)?, r.get(1)?))
+            })
+            .unwrap();
+        assert_eq!(probe_type, UDP_ECHO);
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&json).unwrap()["received"], 98);
+    }
+}
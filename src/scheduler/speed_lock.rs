@@ -0,0 +1,194 @@
+//! Advisory lock against concurrent speed tests.
+//!
+//! Two speed tests sharing one uplink each measure roughly half of it, and
+//! the result reads as an ISP fault. The scheduler serializes its own tests
+//! with the bandwidth permit, but a CLI `speed-test` is a separate process,
+//! so the lock lives in SQLite: a single `speed_test_lock` row shared by every
+//! process using the database. The row expires after [`LOCK_TTL`] so a holder
+//! that crashed cannot block tests forever.
+
+use std::time::Duration;
+
+use anyhow::Result;
+use rusqlite::{params, OptionalExtension};
+use serde::Serialize;
+
+use crate::storage::Pool;
+
+/// How long a lock is honoured without being released.
+pub const LOCK_TTL: Duration = Duration::from_secs(15 * 60);
+
+/// How often a waiting process re-checks the lock.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Who holds the lock.
+#[derive(Debug, Clone, Serialize)]
+pub struct LockHolder {
+    /// `scheduler` or `cli`.
+    pub holder: String,
+    pub pid: u32,
+    pub acquired_at: String,
+    pub expires_at: String,
+}
+
+impl std::fmt::Display for LockHolder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (pid {}) since {} UTC",
+            self.holder, self.pid, self.acquired_at
+        )
+    }
+}
+
+/// A held speed-test lock; released on drop.
+pub struct SpeedTestLock {
+    pool: Pool,
+    token: String,
+}
+
+impl SpeedTestLock {
+    /// Take the lock for `holder`, or return who has it.
+    pub fn try_acquire(pool: &Pool, holder: &str) -> Result<Result<Self, LockHolder>> {
+        let conn = pool.get()?;
+        conn.execute(
+            "DELETE FROM speed_test_lock WHERE expires_at <= datetime('now')",
+            [],
+        )?;
+
+        let token = format!("{:016x}", rand::random::<u64>());
+        let inserted = conn.execute(
+            "INSERT OR IGNORE INTO speed_test_lock (id, token, holder, pid, expires_at)
+             VALUES (1, ?1, ?2, ?3, datetime('now', ?4))",
+            params![
+                token,
+                holder,
+                std::process::id(),
+                format!("+{} seconds", LOCK_TTL.as_secs())
+            ],
+        )?;
+        if inserted == 1 {
+            return Ok(Ok(Self {
+                pool: pool.clone(),
+                token,
+            }));
+        }
+
+        match current(pool)? {
+            Some(h) => Ok(Err(h)),
+            // Released between the insert and the read; try once more.
+            None => Self::try_acquire(pool, holder),
+        }
+    }
+
+    /// Take the lock, waiting up to `max_wait` for the current holder.
+    /// `on_wait` is called once, with the holder, if the lock is busy.
+    pub async fn acquire_waiting(
+        pool: &Pool,
+        holder: &str,
+        max_wait: Duration,
+        on_wait: impl FnOnce(&LockHolder),
+    ) -> Result<Result<Self, LockHolder>> {
+        let started = std::time::Instant::now();
+        let mut on_wait = Some(on_wait);
+        loop {
+            match Self::try_acquire(pool, holder)? {
+                Ok(lock) => return Ok(Ok(lock)),
+                Err(h) if started.elapsed() >= max_wait => return Ok(Err(h)),
+                Err(h) => {
+                    if let Some(f) = on_wait.take() {
+                        f(&h);
+                    }
+                }
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+impl Drop for SpeedTestLock {
+    fn drop(&mut self) {
+        let released = self
+            .pool
+            .get()
+            .map_err(anyhow::Error::from)
+            .and_then(|conn| {
+                conn.execute(
+                    "DELETE FROM speed_test_lock WHERE token = ?1",
+                    [&self.token],
+                )
+                .map_err(anyhow::Error::from)
+            });
+        if let Err(e) = released {
+            tracing::warn!(
+                "Failed to release speed-test lock (expires on its own): {:#}",
+                e
+            );
+        }
+    }
+}
+
+/// The current, unexpired holder, if any.
+pub fn current(pool: &Pool) -> Result<Option<LockHolder>> {
+    let conn = pool.get()?;
+    let holder = conn
+        .query_row(
+            "SELECT holder, pid, acquired_at, expires_at FROM speed_test_lock
+             WHERE expires_at > datetime('now')",
+            [],
+            |row| {
+                Ok(LockHolder {
+                    holder: row.get(0)?,
+                    pid: row.get(1)?,
+                    acquired_at: row.get(2)?,
+                    expires_at: row.get(3)?,
+                })
+            },
+        )
+        .optional()?;
+    Ok(holder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_excludes_and_releases() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = crate::storage::open_pool(dir.path().join("t.db").to_str().unwrap()).unwrap();
+
+        let lock = SpeedTestLock::try_acquire(&pool, "scheduler")
+            .unwrap()
+            .ok()
+            .unwrap();
+        let busy = SpeedTestLock::try_acquire(&pool, "cli")
+            .unwrap()
+            .err()
+            .unwrap();
+        assert_eq!(busy.holder, "scheduler");
+        assert_eq!(busy.pid, std::process::id());
+
+        drop(lock);
+        assert!(current(&pool).unwrap().is_none());
+        assert!(SpeedTestLock::try_acquire(&pool, "cli").unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_expired_lock_is_taken_over() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = crate::storage::open_pool(dir.path().join("t.db").to_str().unwrap()).unwrap();
+        pool.get()
+            .unwrap()
+            .execute(
+                "INSERT INTO speed_test_lock (id, token, holder, pid, expires_at)
+                 VALUES (1, 'stale', 'cli', 1, datetime('now', '-1 minutes'))",
+                [],
+            )
+            .unwrap();
+
+        assert!(current(&pool).unwrap().is_none());
+        let lock = SpeedTestLock::try_acquire(&pool, "scheduler").unwrap();
+        assert!(lock.is_ok());
+    }
+}
@@ -132,6 +132,46 @@ pub fn check_gpu() -> Result<ComponentResult> {
     }
 }
 
+/// Report which acceleration backends initialized (Vulkan, GLES, NEON).
+///
+/// Must run off the async runtime: GPU init is time-boxed but still blocks.
+pub fn check_accel() -> Result<ComponentResult> {
+    use crate::accel::{AccelerationManager, Backend};
+
+    let manager = AccelerationManager::new();
+    let report = manager.init_report();
+
+    let summary: Vec<String> = report
+        .iter()
+        .map(|b| match &b.reason {
+            None => format!("{:?}: ok", b.backend),
+            Some(reason) => format!("{:?}: unavailable ({})", b.backend, reason),
+        })
+        .collect();
+
+    let gpu_ok = report
+        .iter()
+        .any(|b| matches!(b.backend, Backend::Vulkan | Backend::Gles) && b.available);
+
+    Ok(ComponentResult {
+        component: "Acceleration".to_string(),
+        status: if gpu_ok {
+            TestStatus::Pass
+        } else {
+            TestStatus::Warning
+        },
+        details: summary.join(", "),
+        remediation: if gpu_ok {
+            None
+        } else {
+            Some(
+                "No GPU backend initialized; analysis falls back to NEON. Install mesa-vulkan-drivers for Vulkan compute."
+                    .to_string(),
+            )
+        },
+    })
+}
+
 /// Check Storage Type (NVMe vs SD)
 pub fn check_storage() -> Result<ComponentResult> {
     // Check root device
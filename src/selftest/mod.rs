@@ -1,9 +1,12 @@
 //! Hardware self-test subsystem for Pi 5.
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDateTime, Utc};
 use serde::Serialize;
 use tracing::info;
 
+use crate::storage::Pool;
+
 pub mod hardware;
 pub mod network;
 pub mod thermal;
@@ -14,7 +17,22 @@ use std::collections::HashMap;
 /// Run the full hardware self-test suite.
 /// Returns a list of component results and use case compatibility.
 pub async fn run() -> Result<SelfTestReport> {
-    info!("Self-test: checking Pi 5 hardware...");
+    run_with(false).await
+}
+
+/// Run the self-test suite. `quick` skips the slow checks (acceleration
+/// backend init and Wi-Fi scans), reporting them as `Skipped`.
+///
+/// The checks shell out and read sysfs synchronously, so they run on the
+/// blocking pool rather than stalling a runtime worker (and with it the API).
+pub async fn run_with(quick: bool) -> Result<SelfTestReport> {
+    tokio::task::spawn_blocking(move || run_blocking(quick))
+        .await
+        .context("self-test task failed")?
+}
+
+fn run_blocking(quick: bool) -> Result<SelfTestReport> {
+    info!("Self-test: checking Pi 5 hardware{}...", if quick { " (quick)" } else { "" });
 
     let mut results = Vec::new();
 
@@ -51,6 +69,21 @@ pub async fn run() -> Result<SelfTestReport> {
         }),
     }
 
+    // 3b. Acceleration backends (time-boxed GPU init)
+    if quick {
+        results.push(skipped_in_quick_mode("Acceleration"));
+    } else {
+        match hardware::check_accel() {
+            Ok(res) => results.push(res),
+            Err(e) => results.push(ComponentResult {
+                component: "Acceleration".to_string(),
+                status: TestStatus::Warning,
+                details: format!("Failed to initialize acceleration backends: {}", e),
+                remediation: None,
+            }),
+        }
+    }
+
     // 4. Storage Type
     match hardware::check_storage() {
         Ok(res) => results.push(res),
@@ -73,6 +106,9 @@ pub async fn run() -> Result<SelfTestReport> {
         }),
     }
 
+    // 5b. Power supply (under-voltage flags, same vcgencmd word)
+    results.push(crate::system::power::check_power());
+
     // 6. Network Interfaces (10GbE)
     match network::check_interfaces() {
         Ok(net_results) => results.extend(net_results),
@@ -85,31 +121,152 @@ pub async fn run() -> Result<SelfTestReport> {
     }
 
     // 7. Wi-Fi (Phase 2.2)
-    match wifi::check_wifi() {
-        Ok(wifi_results) => results.extend(wifi_results),
-        Err(e) => results.push(ComponentResult {
-            component: "Wi-Fi".to_string(),
-            status: TestStatus::Warning,
-            details: format!("Failed to check Wi-Fi: {}", e),
-            remediation: Some("Ensure 'iw' is installed.".to_string()),
-        }),
+    if quick {
+        results.push(skipped_in_quick_mode("Wi-Fi"));
+    } else {
+        match wifi::check_wifi() {
+            Ok(wifi_results) => results.extend(wifi_results),
+            Err(e) => results.push(ComponentResult {
+                component: "Wi-Fi".to_string(),
+                status: TestStatus::Warning,
+                details: format!("Failed to check Wi-Fi: {}", e),
+                remediation: Some("Ensure 'iw' is installed.".to_string()),
+            }),
+        }
     }
 
     info!("Self-test complete. {} check(s) run.", results.len());
 
     // Calculate Use Case Compatibility
     let compatibility = calculate_use_case_compatibility(&results);
+    let recommendations = persona_recommendations(&results);
 
     Ok(SelfTestReport {
         results,
         compatibility,
+        recommendations,
     })
 }
 
+fn skipped_in_quick_mode(component: &str) -> ComponentResult {
+    ComponentResult {
+        component: component.to_string(),
+        status: TestStatus::Skipped,
+        details: "Skipped in quick mode".to_string(),
+        remediation: Some("Run the full self-test to check this component.".to_string()),
+    }
+}
+
+/// Persist a self-test run to `selftest_reports`.
+pub fn save_report(pool: &Pool, report: &SelfTestReport, quick: bool) -> Result<()> {
+    let conn = pool.get()?;
+    conn.execute(
+        "INSERT INTO selftest_reports (quick, report_json) VALUES (?1, ?2)",
+        rusqlite::params![quick, serde_json::to_string(report)?],
+    )
+    .context("Failed to save self-test report")?;
+    Ok(())
+}
+
+/// A persisted self-test run, with the report kept as stored JSON.
+#[derive(Debug, Clone, Serialize)]
+pub struct StoredReport {
+    pub quick: bool,
+    pub report: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+/// The most recent persisted self-test run, if any.
+pub fn latest_report(pool: &Pool) -> Result<Option<StoredReport>> {
+    let conn = pool.get()?;
+    let row = conn.query_row(
+        "SELECT quick, report_json, created_at FROM selftest_reports
+         ORDER BY created_at DESC, id DESC LIMIT 1",
+        [],
+        |row| {
+            Ok((
+                row.get::<_, bool>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        },
+    );
+    let (quick, report_json, created_at) = match row {
+        Ok(r) => r,
+        Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(None),
+        Err(e) => return Err(e).context("Failed to load self-test report"),
+    };
+    let created_at = NaiveDateTime::parse_from_str(&created_at, "%Y-%m-%d %H:%M:%S")
+        .with_context(|| format!("Bad selftest_reports timestamp: {}", created_at))?
+        .and_utc();
+    Ok(Some(StoredReport {
+        quick,
+        report: serde_json::from_str(&report_json).context("Bad stored self-test report")?,
+        created_at,
+    }))
+}
+
 #[derive(Debug, Serialize)]
 pub struct SelfTestReport {
     pub results: Vec<ComponentResult>,
     pub compatibility: HashMap<String, bool>, // Use Case Name -> Is Compatible
+    /// Concrete next steps for each persona that is not ready, prefixed
+    /// with the persona name (e.g. "High Performance: connect a 2.5GbE+ NIC").
+    pub recommendations: Vec<String>,
+}
+
+/// Derive next steps from the checks that block each persona.
+/// Mirrors the requirements in `calculate_use_case_compatibility`.
+fn persona_recommendations(results: &[ComponentResult]) -> Vec<String> {
+    let status = |name_part: &str| -> TestStatus {
+        results
+            .iter()
+            .find(|r| r.component.contains(name_part))
+            .map(|r| r.status.clone())
+            .unwrap_or(TestStatus::Fail)
+    };
+    let details = |name_part: &str| -> String {
+        results
+            .iter()
+            .find(|r| r.component.contains(name_part))
+            .map(|r| r.details.clone())
+            .unwrap_or_default()
+    };
+
+    let mut recs = Vec::new();
+    let mut add = |persona: &str, step: &str| recs.push(format!("{}: {}", persona, step));
+
+    // Shared by every persona.
+    let board_pass = status("Board") == TestStatus::Pass;
+    let net_ok = !any_failed(results, "Network") && !any_failed(results, "Interface");
+    if !board_pass {
+        add("All personas", "run on a Raspberry Pi 5 (board check did not pass)");
+    }
+    if !net_ok {
+        add("Simple Troubleshooting", "connect a wired Ethernet interface with link up");
+    }
+
+    if !details("Storage").contains("NVMe") {
+        add("Reliability & Uptime", "boot from NVMe instead of SD card for write endurance");
+    }
+    if status("Thermal") != TestStatus::Pass {
+        add("Reliability & Uptime", "fix throttling: fit the Active Cooler");
+    }
+    if any_failed(results, "Power") {
+        add(
+            "Reliability & Uptime",
+            "fix under-voltage: use the official 27W USB-C supply and a short cable",
+        );
+    }
+
+    let multigig = results
+        .iter()
+        .any(|r| r.details.contains("Multi-Gig") || r.details.contains("10GbE"));
+    if !multigig {
+        add("High Performance", "connect a 2.5GbE+ NIC (PCIe HAT or USB 3) to test beyond 1 Gbps");
+    }
+
+    recs
 }
 
 fn calculate_use_case_compatibility(results: &[ComponentResult]) -> HashMap<String, bool> {
@@ -135,17 +292,20 @@ fn calculate_use_case_compatibility(results: &[ComponentResult]) -> HashMap<Stri
     // Simple Troubleshooting: Needs Pi 5 (Board Pass) + minimal network
     // Board must PASS. Network must not be FAIL.
     let board_pass = get_status("Board") == TestStatus::Pass;
-    let net_ok =
-        get_status("Network") != TestStatus::Fail && get_status("Interface") != TestStatus::Fail;
+    // "Network" only appears when enumeration itself failed, so a missing
+    // component is not a failure here.
+    let net_ok = !any_failed(results, "Network") && !any_failed(results, "Interface");
     map.insert("Simple Troubleshooting".to_string(), board_pass && net_ok);
 
     // Reliability & Uptime: Needs reliability -> NVMe storage preferred (or at least storage pass) + Thermal Pass
     // We check if Storage details contain "NVMe"
     let storage_nvme = get_details("Storage").contains("NVMe");
     let thermal_pass = get_status("Thermal") == TestStatus::Pass;
+    // A missing power check (no vcgencmd) is not a failure.
+    let power_ok = !any_failed(results, "Power");
     map.insert(
         "Reliability & Uptime".to_string(),
-        board_pass && net_ok && storage_nvme && thermal_pass,
+        board_pass && net_ok && storage_nvme && thermal_pass && power_ok,
     );
 
     // High Performance: Needs performance -> 2.5GbE+ (Multi-Gig)
@@ -157,6 +317,13 @@ fn calculate_use_case_compatibility(results: &[ComponentResult]) -> HashMap<Stri
     map
 }
 
+/// True if any component whose name contains `name_part` failed.
+fn any_failed(results: &[ComponentResult], name_part: &str) -> bool {
+    results
+        .iter()
+        .any(|r| r.component.contains(name_part) && r.status == TestStatus::Fail)
+}
+
 /// Self-test result for a single hardware component.
 #[derive(Debug, Serialize, Clone)]
 pub struct ComponentResult {
@@ -173,3 +340,79 @@ pub enum TestStatus {
     Warning,
     Skipped,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(component: &str, status: TestStatus, details: &str) -> ComponentResult {
+        ComponentResult {
+            component: component.to_string(),
+            status,
+            details: details.to_string(),
+            remediation: None,
+        }
+    }
+
+    #[test]
+    fn test_recommendations_for_sd_card_gigabit_pi() {
+        let results = vec![
+            result("Board", TestStatus::Pass, "Raspberry Pi 5 Model B"),
+            result("Storage", TestStatus::Warning, "Root on SD card"),
+            result("Thermal", TestStatus::Pass, "No throttling"),
+            result("Interface: eth0", TestStatus::Warning, "1GbE detected (Link: 1000 Mbps)"),
+        ];
+        let recs = persona_recommendations(&results);
+
+        assert!(recs.iter().any(|r| r.starts_with("Reliability & Uptime: boot from NVMe")));
+        assert!(recs.iter().any(|r| r.starts_with("High Performance: connect a 2.5GbE+ NIC")));
+        assert!(!recs.iter().any(|r| r.starts_with("Simple Troubleshooting")));
+        assert!(!recs.iter().any(|r| r.contains("throttling")));
+    }
+
+    #[test]
+    fn test_no_recommendations_when_all_ready() {
+        let results = vec![
+            result("Board", TestStatus::Pass, "Raspberry Pi 5 Model B"),
+            result("Storage", TestStatus::Pass, "NVMe root"),
+            result("Thermal", TestStatus::Pass, "No throttling"),
+            result("Interface: eth1", TestStatus::Pass, "Multi-Gig detected (Link: 2500 Mbps)"),
+        ];
+        assert!(persona_recommendations(&results).is_empty());
+        assert!(calculate_use_case_compatibility(&results).values().all(|&ok| ok));
+    }
+
+    #[test]
+    fn test_undervoltage_blocks_reliability() {
+        let results = vec![
+            result("Board", TestStatus::Pass, "Raspberry Pi 5 Model B"),
+            result("Storage", TestStatus::Pass, "NVMe root"),
+            result("Thermal Stability", TestStatus::Pass, "No throttling"),
+            result("Power Supply", TestStatus::Fail, "Currently under-voltage"),
+            result("Interface: eth1", TestStatus::Pass, "Multi-Gig detected (Link: 2500 Mbps)"),
+        ];
+        assert!(!calculate_use_case_compatibility(&results)["Reliability & Uptime"]);
+        let recs = persona_recommendations(&results);
+        assert!(recs.iter().any(|r| r.contains("under-voltage")));
+        assert!(!recs.iter().any(|r| r.contains("throttling")));
+    }
+
+    #[test]
+    fn test_save_and_load_latest_report() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = crate::storage::open_pool(dir.path().join("t.db").to_str().unwrap()).unwrap();
+        assert!(latest_report(&pool).unwrap().is_none());
+
+        let report = |details: &str| SelfTestReport {
+            results: vec![result("Board", TestStatus::Pass, details)],
+            compatibility: HashMap::new(),
+            recommendations: vec![],
+        };
+        save_report(&pool, &report("first"), false).unwrap();
+        save_report(&pool, &report("second"), true).unwrap();
+
+        let latest = latest_report(&pool).unwrap().unwrap();
+        assert!(latest.quick);
+        assert_eq!(latest.report["results"][0]["details"], "second");
+    }
+}
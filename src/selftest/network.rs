@@ -18,16 +18,9 @@ pub fn check_interfaces() -> Result<Vec<ComponentResult>> {
         }
 
         // Check speed
-        let speed_path = entry.path().join("speed");
-        let speed_mbps = if speed_path.exists() {
-            fs::read_to_string(speed_path)
-                .unwrap_or_default()
-                .trim()
-                .parse::<i32>()
-                .unwrap_or(-1)
-        } else {
-            -1
-        };
+        let speed_mbps = crate::throughput::link::read_link_speed(&iface)
+            .map(|mbps| mbps as i32)
+            .unwrap_or(-1);
 
         // PCIe check?
         // /sys/class/net/eth0/device -> symlink to ../../../../../0000:01:00.0 etc
@@ -1,4 +1,5 @@
 use crate::selftest::{ComponentResult, TestStatus};
+use crate::system::power::ThrottleFlags;
 use anyhow::{Context, Result};
 use std::fs;
 use std::process::Command;
@@ -11,7 +12,26 @@ pub fn get_cpu_temp() -> Result<f64> {
     Ok(temp_milli / 1000.0)
 }
 
-/// Check thermal throttling status (via vcgencmd)
+/// Parse `vcgencmd get_throttled` output (`throttled=0x50005`).
+pub fn parse_throttled(stdout: &str) -> Option<u32> {
+    let hex_str = stdout.trim().strip_prefix("throttled=0x")?;
+    u32::from_str_radix(hex_str, 16).ok()
+}
+
+/// Read the raw throttle flags (via vcgencmd)
+pub fn read_throttle_mask() -> Result<u32> {
+    let out = Command::new("vcgencmd")
+        .arg("get_throttled")
+        .output()
+        .context("Failed to run vcgencmd")?;
+    parse_throttled(&String::from_utf8_lossy(&out.stdout))
+        .context("Unexpected vcgencmd get_throttled output")
+}
+
+/// Check thermal throttling status (via vcgencmd).
+///
+/// Under-voltage shares the flags word but is reported separately by
+/// [`crate::system::power::check_power`], since it needs a different fix.
 pub fn check_throttling() -> Result<ComponentResult> {
     // Requires 'vcgencmd' be installed (libraspberrypi-bin)
     let output = Command::new("vcgencmd").arg("get_throttled").output();
@@ -19,73 +39,13 @@ pub fn check_throttling() -> Result<ComponentResult> {
     match output {
         Ok(out) => {
             let stdout = String::from_utf8_lossy(&out.stdout);
-            // format: throttled=0x50005
-            // bits: 0=under-voltage, 1=freq-capped, 2=throttled, 3=soft-temp-limit
-            // bits 16-19 are latching (happened since boot)
-
-            let hex_str = stdout.trim().trim_start_matches("throttled=0x");
-            let mask = u32::from_str_radix(hex_str, 16).unwrap_or(0);
-
-            let current_throttle = (mask & 0x07) != 0;
-            let past_throttle = (mask & 0x70000) != 0;
-            let under_voltage = (mask & 0x01) != 0 || (mask & 0x10000) != 0;
-
-            let mut details = Vec::new();
-            if (mask & 0x01) != 0 {
-                details.push("Currently under-voltage");
-            }
-            if (mask & 0x02) != 0 {
-                details.push("Currently frequency capped (CPU)");
-            }
-            if (mask & 0x04) != 0 {
-                details.push("Currently throttled (temp)");
-            }
-            if (mask & 0x10000) != 0 {
-                details.push("Past under-voltage event");
-            }
-            if (mask & 0x20000) != 0 {
-                details.push("Past frequency cap event");
-            }
-            if (mask & 0x40000) != 0 {
-                details.push("Past throttle event");
-            }
-
-            let status = if current_throttle || under_voltage {
-                TestStatus::Fail
-            } else if past_throttle {
-                TestStatus::Warning
-            } else {
-                TestStatus::Pass
-            };
-
-            let details_str = if details.is_empty() {
-                "No throttling detected".to_string()
-            } else {
-                details.join(", ")
-            };
-
-            let remediation = if status == TestStatus::Fail {
-                Some("Check power supply (5V 5A PD recommended) and cooling.".to_string())
-            } else if status == TestStatus::Warning {
-                Some(
-                    "System was throttled previously. Ensure stable power/cooling for long tests."
-                        .to_string(),
-                )
-            } else {
-                None
-            };
-
-            Ok(ComponentResult {
-                component: "Power/Thermal Stability".to_string(),
-                status,
-                details: format!("Mask=0x{:x}. {}", mask, details_str),
-                remediation,
-            })
+            let mask = parse_throttled(&stdout).unwrap_or(0);
+            Ok(throttling_result(ThrottleFlags(mask)))
         }
         Err(_) => {
             // vcgencmd missing? Fallback or skip.
             Ok(ComponentResult {
-                component: "Power/Thermal Stability".to_string(),
+                component: "Thermal Stability".to_string(),
                 status: TestStatus::Skipped,
                 details: "vcgencmd not found. Cannot read throttle flags.".to_string(),
                 remediation: Some("Install libraspberrypi-bin".to_string()),
@@ -93,3 +53,56 @@ pub fn check_throttling() -> Result<ComponentResult> {
         }
     }
 }
+
+fn throttling_result(flags: ThrottleFlags) -> ComponentResult {
+    let status = if flags.throttled_now() {
+        TestStatus::Fail
+    } else if flags.throttled_occurred() {
+        TestStatus::Warning
+    } else {
+        TestStatus::Pass
+    };
+
+    let details = flags.throttle_details();
+    let details_str = if details.is_empty() {
+        "No throttling detected".to_string()
+    } else {
+        details.join(", ")
+    };
+
+    let remediation = match status {
+        TestStatus::Fail => Some("Improve cooling (Active Cooler or a case with a fan).".to_string()),
+        TestStatus::Warning => Some(
+            "System was throttled previously. Ensure adequate cooling for long tests.".to_string(),
+        ),
+        _ => None,
+    };
+
+    ComponentResult {
+        component: "Thermal Stability".to_string(),
+        status,
+        details: format!("Mask=0x{:x}. {}", flags.0, details_str),
+        remediation,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_throttled() {
+        assert_eq!(parse_throttled("throttled=0x50005\n"), Some(0x50005));
+        assert_eq!(parse_throttled("throttled=0x0"), Some(0));
+        assert_eq!(parse_throttled("VCHI initialization failed"), None);
+    }
+
+    #[test]
+    fn test_throttling_ignores_undervoltage() {
+        assert_eq!(throttling_result(ThrottleFlags(0x10001)).status, TestStatus::Pass);
+        assert_eq!(throttling_result(ThrottleFlags(0x40000)).status, TestStatus::Warning);
+        let hot = throttling_result(ThrottleFlags(0x4));
+        assert_eq!(hot.status, TestStatus::Fail);
+        assert!(hot.details.contains("Currently throttled"));
+    }
+}
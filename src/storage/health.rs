@@ -0,0 +1,186 @@
+//! Storage health: degrade instead of failing when the disk is full or
+//! the filesystem is read-only.
+//!
+//! On an appliance a full SD card or a root remounted read-only must not
+//! stop probes. Writes hitting `SQLITE_FULL` / `SQLITE_READONLY` are
+//! absorbed, counted, and reported through `/api/v1/health`; the first
+//! successful write afterwards clears the state.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use rusqlite::ErrorCode;
+use serde::Serialize;
+
+/// Minimum gap between "storage degraded" warnings.
+const WARN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Why writes are currently being dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DegradedReason {
+    DiskFull,
+    ReadOnly,
+}
+
+impl DegradedReason {
+    fn of(err: &rusqlite::Error) -> Option<Self> {
+        match err.sqlite_error_code()? {
+            ErrorCode::DiskFull => Some(Self::DiskFull),
+            ErrorCode::ReadOnly => Some(Self::ReadOnly),
+            _ => None,
+        }
+    }
+}
+
+/// Snapshot for the health endpoint.
+#[derive(Debug, Clone, Serialize)]
+pub struct StorageStatus {
+    pub degraded: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<DegradedReason>,
+    /// Writes dropped since the process started.
+    pub dropped_writes: u64,
+}
+
+pub struct StorageHealth {
+    degraded: AtomicBool,
+    dropped_writes: AtomicU64,
+    reason: Mutex<Option<DegradedReason>>,
+    last_warn: Mutex<Option<Instant>>,
+}
+
+impl StorageHealth {
+    pub const fn new() -> Self {
+        Self {
+            degraded: AtomicBool::new(false),
+            dropped_writes: AtomicU64::new(0),
+            reason: Mutex::new(None),
+            last_warn: Mutex::new(None),
+        }
+    }
+
+    /// Pass a write result through: `Some` on success, `None` when it was
+    /// dropped because storage is degraded, `Err` for any other failure.
+    pub fn absorb<T>(&self, res: rusqlite::Result<T>) -> Result<Option<T>> {
+        match res {
+            Ok(v) => {
+                if self.degraded.swap(false, Ordering::Relaxed) {
+                    tracing::info!("Storage writable again");
+                    *self.reason.lock().unwrap() = None;
+                }
+                Ok(Some(v))
+            }
+            Err(e) => match DegradedReason::of(&e) {
+                Some(reason) => {
+                    self.degraded.store(true, Ordering::Relaxed);
+                    let dropped = self.dropped_writes.fetch_add(1, Ordering::Relaxed) + 1;
+                    *self.reason.lock().unwrap() = Some(reason);
+                    self.warn_throttled(reason, dropped, &e);
+                    Ok(None)
+                }
+                None => Err(e.into()),
+            },
+        }
+    }
+
+    fn warn_throttled(&self, reason: DegradedReason, dropped: u64, err: &rusqlite::Error) {
+        let mut last = self.last_warn.lock().unwrap();
+        if last.map_or(true, |t| t.elapsed() >= WARN_INTERVAL) {
+            *last = Some(Instant::now());
+            tracing::warn!(?reason, dropped, "Storage degraded, dropping writes: {}", err);
+        }
+    }
+
+    pub fn status(&self) -> StorageStatus {
+        StorageStatus {
+            degraded: self.degraded.load(Ordering::Relaxed),
+            reason: *self.reason.lock().unwrap(),
+            dropped_writes: self.dropped_writes.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Default for StorageHealth {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Process-wide storage health, shared by all write paths.
+pub static STORAGE_HEALTH: StorageHealth = StorageHealth::new();
+
+/// Current process-wide storage status.
+pub fn status() -> StorageStatus {
+    STORAGE_HEALTH.status()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use r2d2_sqlite::SqliteConnectionManager;
+
+    #[test]
+    fn test_read_only_write_degrades_then_recovers() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("t.db");
+        // Create the schema, then reopen with writes refused.
+        crate::storage::open_pool(path.to_str().unwrap()).unwrap();
+        let manager = SqliteConnectionManager::file(&path)
+            .with_init(|c| c.execute_batch("PRAGMA query_only = ON;"));
+        let ro = r2d2::Pool::new(manager).unwrap();
+
+        let health = StorageHealth::new();
+        let write = |pool: &crate::storage::Pool| {
+            pool.get()
+                .unwrap()
+                .execute("INSERT INTO data_usage (day, bytes) VALUES ('2026-01-01', 1)", [])
+        };
+
+        assert!(health.absorb(write(&ro)).unwrap().is_none());
+        assert!(health.absorb(write(&ro)).unwrap().is_none());
+        let s = health.status();
+        assert!(s.degraded);
+        assert_eq!(s.reason, Some(DegradedReason::ReadOnly));
+        assert_eq!(s.dropped_writes, 2);
+
+        let rw = crate::storage::open_pool(path.to_str().unwrap()).unwrap();
+        assert_eq!(health.absorb(write(&rw)).unwrap(), Some(1));
+        let s = health.status();
+        assert!(!s.degraded);
+        assert!(s.reason.is_none());
+        assert_eq!(s.dropped_writes, 2);
+    }
+
+    #[test]
+    fn test_other_errors_still_fail() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        let health = StorageHealth::new();
+        let res = conn.execute("INSERT INTO no_such_table VALUES (1)", []);
+        assert!(health.absorb(res).is_err());
+        assert!(!health.status().degraded);
+    }
+
+    #[test]
+    fn test_save_measurement_survives_read_only_db() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("t.db");
+        crate::storage::open_pool(path.to_str().unwrap()).unwrap();
+        let manager = SqliteConnectionManager::file(&path)
+            .with_init(|c| c.execute_batch("PRAGMA query_only = ON;"));
+        let ro = r2d2::Pool::new(manager).unwrap();
+
+        let m = crate::probes::Measurement {
+            probe_type: crate::probes::ProbeType::Icmp,
+            target: "1.1.1.1".to_string(),
+            value: 12.0,
+            unit: "ms".to_string(),
+            success: true,
+            timestamp: std::time::SystemTime::now(),
+            dscp: None,
+        };
+        crate::storage::save_measurement(&ro, &m).unwrap();
+    }
+}
@@ -0,0 +1,201 @@
+//! Import historical measurements from other tools.
+//!
+//! Seeding the `measurements` table with old latency logs gives the baseline
+//! (see [`crate::analysis::stats`]) enough samples on day one instead of
+//! after weeks of probing.
+//!
+//! The CSV format is one sample per line, `timestamp,latency_ms`; further
+//! columns are ignored, as are blank lines, `#` comments, and a header row.
+//! Timestamps may be RFC 3339, `YYYY-MM-DD HH:MM:SS[.fff]` (UTC), or Unix
+//! epoch seconds. Rows that don't parse, carry a negative or non-finite value,
+//! or lie in the future are skipped and reported with their line number.
+
+use std::io::BufRead;
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+
+use crate::probes::{Measurement, ProbeType};
+
+/// Tolerated clock skew before a timestamp counts as "in the future".
+const FUTURE_SKEW_SECS: i64 = 300;
+
+/// Supported input formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    Csv,
+}
+
+impl std::str::FromStr for ImportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "csv" => Ok(Self::Csv),
+            other => bail!("unsupported import format '{}' (expected csv)", other),
+        }
+    }
+}
+
+/// A row that was not imported.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkippedRow {
+    /// 1-based line number in the input.
+    pub line: usize,
+    pub reason: String,
+}
+
+/// Parsed input: the measurements to insert and the rows left out.
+#[derive(Default)]
+pub struct ParsedImport {
+    pub measurements: Vec<Measurement>,
+    pub skipped: Vec<SkippedRow>,
+}
+
+/// Parse latency samples for `probe`/`target` from `input`.
+pub fn parse(
+    input: impl BufRead,
+    format: ImportFormat,
+    probe: &ProbeType,
+    target: &str,
+) -> Result<ParsedImport> {
+    match format {
+        ImportFormat::Csv => parse_csv(input, probe, target, Utc::now()),
+    }
+}
+
+fn parse_csv(
+    input: impl BufRead,
+    probe: &ProbeType,
+    target: &str,
+    now: DateTime<Utc>,
+) -> Result<ParsedImport> {
+    let mut parsed = ParsedImport::default();
+    let mut seen_data = false;
+
+    for (idx, line) in input.lines().enumerate() {
+        let line = line?;
+        let row = line.trim();
+        if row.is_empty() || row.starts_with('#') {
+            continue;
+        }
+        let line_no = idx + 1;
+        match parse_row(row, now) {
+            Ok((ts, value)) => {
+                seen_data = true;
+                parsed.measurements.push(Measurement {
+                    probe_type: probe.clone(),
+                    target: target.to_string(),
+                    value,
+                    unit: "ms".to_string(),
+                    success: true,
+                    timestamp: ts.into(),
+                    dscp: None,
+                });
+            }
+            // A first row whose value isn't numeric is a header, not an error.
+            Err(_) if !seen_data && parsed.skipped.is_empty() && is_header(row) => {}
+            Err(reason) => parsed.skipped.push(SkippedRow {
+                line: line_no,
+                reason,
+            }),
+        }
+    }
+    Ok(parsed)
+}
+
+fn is_header(row: &str) -> bool {
+    row.split(',')
+        .nth(1)
+        .is_some_and(|v| v.trim().parse::<f64>().is_err())
+}
+
+fn parse_row(row: &str, now: DateTime<Utc>) -> std::result::Result<(DateTime<Utc>, f64), String> {
+    let mut cols = row.split(',').map(|c| c.trim().trim_matches('"'));
+    let ts_raw = cols.next().unwrap_or_default();
+    let value_raw = cols
+        .next()
+        .ok_or_else(|| "expected timestamp,latency_ms".to_string())?;
+
+    let ts = parse_timestamp(ts_raw).ok_or_else(|| format!("invalid timestamp '{}'", ts_raw))?;
+    if ts > now + chrono::Duration::seconds(FUTURE_SKEW_SECS) {
+        return Err(format!("timestamp {} is in the future", ts.to_rfc3339()));
+    }
+    let value: f64 = value_raw
+        .parse()
+        .map_err(|_| format!("invalid latency '{}'", value_raw))?;
+    if !value.is_finite() || value < 0.0 {
+        return Err(format!("latency {} out of range", value_raw));
+    }
+    Ok((ts, value))
+}
+
+fn parse_timestamp(raw: &str) -> Option<DateTime<Utc>> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(raw) {
+        return Some(dt.with_timezone(&Utc));
+    }
+    for fmt in ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"] {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(raw, fmt) {
+            return Some(Utc.from_utc_datetime(&naive));
+        }
+    }
+    // Unix epoch seconds, optionally fractional (e.g. `ping -D` output).
+    let secs: f64 = raw.parse().ok()?;
+    if !secs.is_finite() || secs < 0.0 {
+        return None;
+    }
+    DateTime::from_timestamp(secs.trunc() as i64, (secs.fract() * 1e9) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn csv(input: &str) -> ParsedImport {
+        let now = Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap();
+        parse_csv(input.as_bytes(), &ProbeType::Icmp, "8.8.8.8", now).unwrap()
+    }
+
+    #[test]
+    fn test_parse_csv_formats_and_header() {
+        let parsed = csv("timestamp,rtt_ms\n\
+             2025-06-01T12:00:00Z,12.5\n\
+             2025-06-01 12:01:00,13\n\
+             \n\
+             # comment\n\
+             1748779320.5,14.25,extra\n");
+        assert!(parsed.skipped.is_empty(), "{:?}", parsed.skipped);
+        assert_eq!(parsed.measurements.len(), 3);
+
+        let m = &parsed.measurements[2];
+        assert_eq!(m.probe_type, ProbeType::Icmp);
+        assert_eq!(m.target, "8.8.8.8");
+        assert_eq!(m.unit, "ms");
+        assert_eq!(m.value, 14.25);
+        let ts: DateTime<Utc> = m.timestamp.into();
+        assert_eq!(ts.timestamp(), 1_748_779_320);
+    }
+
+    #[test]
+    fn test_parse_csv_reports_malformed_rows() {
+        let parsed = csv("2025-06-01T12:00:00Z,12.5\n\
+             yesterday,10\n\
+             2025-06-01T12:02:00Z,timeout\n\
+             2025-06-01T12:03:00Z,-4\n\
+             2030-01-01T00:00:00Z,9\n\
+             2025-06-01T12:04:00Z\n\
+             2025-06-01T12:05:00Z,11\n");
+        assert_eq!(parsed.measurements.len(), 2);
+        let lines: Vec<usize> = parsed.skipped.iter().map(|s| s.line).collect();
+        assert_eq!(lines, vec![2, 3, 4, 5, 6]);
+        assert!(parsed.skipped[0].reason.contains("invalid timestamp"));
+        assert!(parsed.skipped[1].reason.contains("invalid latency"));
+        assert!(parsed.skipped[3].reason.contains("future"));
+    }
+
+    #[test]
+    fn test_import_format() {
+        assert_eq!("CSV".parse::<ImportFormat>().unwrap(), ImportFormat::Csv);
+        assert!("json".parse::<ImportFormat>().is_err());
+    }
+}
@@ -1,6 +1,12 @@
 //! SQLite storage layer -- schema, queries, migrations.
 
+pub mod health;
+pub mod import;
+pub mod rollup;
 pub mod schema;
+pub mod wal;
+
+use std::time::Duration;
 
 use anyhow::Result;
 use r2d2::Pool as R2D2Pool;
@@ -9,8 +15,59 @@ use r2d2_sqlite::SqliteConnectionManager;
 /// Connection Pool type
 pub type Pool = R2D2Pool<SqliteConnectionManager>;
 
-/// Open (or create) the SQLite database and return a connection pool.
+/// Connection-pool sizing.
+///
+/// Defaults are chosen for a 4-core Pi 5. SQLite allows one writer at a
+/// time, so more connections only help concurrent readers (API handlers,
+/// SSE streams, the anomaly scan) alongside the scheduler's probe writes;
+/// 8 leaves headroom for those without keeping idle connections around for
+/// nothing. The acquire timeout is kept well below r2d2's 30 s default so an
+/// exhausted pool shows up as a prompt error instead of a silent API stall.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoolConfig {
+    pub max_size: u32,
+    pub connection_timeout: Duration,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_size: 8,
+            connection_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+impl PoolConfig {
+    /// Defaults overridden by `PP_DB_POOL_SIZE` / `PP_DB_POOL_TIMEOUT_MS`.
+    pub fn from_env() -> Self {
+        let mut cfg = Self::default();
+        if let Some(n) = std::env::var("PP_DB_POOL_SIZE")
+            .ok()
+            .and_then(|v| v.parse::<u32>().ok())
+            .filter(|n| *n > 0)
+        {
+            cfg.max_size = n;
+        }
+        if let Some(ms) = std::env::var("PP_DB_POOL_TIMEOUT_MS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|ms| *ms > 0)
+        {
+            cfg.connection_timeout = Duration::from_millis(ms);
+        }
+        cfg
+    }
+}
+
+/// Open (or create) the SQLite database and return a connection pool,
+/// sized per [`PoolConfig::from_env`].
 pub fn open_pool(path: &str) -> Result<Pool> {
+    open_pool_with(path, &PoolConfig::from_env())
+}
+
+/// Open (or create) the SQLite database with an explicit pool configuration.
+pub fn open_pool_with(path: &str, config: &PoolConfig) -> Result<Pool> {
     let manager = SqliteConnectionManager::file(path).with_init(|c| {
         c.execute_batch(
             "PRAGMA journal_mode = WAL;
@@ -22,7 +79,10 @@ pub fn open_pool(path: &str) -> Result<Pool> {
         )
     });
 
-    let pool = R2D2Pool::new(manager)?;
+    let pool = R2D2Pool::builder()
+        .max_size(config.max_size)
+        .connection_timeout(config.connection_timeout)
+        .build(manager)?;
 
     // Run migrations on a single connection
     let conn = pool.get()?;
@@ -31,10 +91,15 @@ pub fn open_pool(path: &str) -> Result<Pool> {
     Ok(pool)
 }
 
+use crate::detect::Incident;
 use crate::probes::Measurement;
+use crate::throughput::ThroughputResult;
 use chrono::{DateTime, Utc};
 
 /// Save a probe measurement RESULT to the database.
+///
+/// Returns `Ok` without writing while storage is degraded (disk full or
+/// read-only); see [`health`].
 pub fn save_measurement(pool: &Pool, m: &Measurement) -> Result<()> {
     let conn = pool.get()?;
 
@@ -42,17 +107,368 @@ pub fn save_measurement(pool: &Pool, m: &Measurement) -> Result<()> {
     let dt: DateTime<Utc> = m.timestamp.into();
     let created_at = dt.to_rfc3339();
 
-    conn.execute(
-        "INSERT INTO measurements (probe_type, target, value, unit, created_at)
-         VALUES (?1, ?2, ?3, ?4, ?5)",
+    // On a full or read-only disk the measurement is dropped (and reported
+    // via `health::status`) rather than failing the probe.
+    health::STORAGE_HEALTH.absorb(conn.execute(
+        "INSERT INTO measurements (probe_type, target, value, unit, created_at, dscp)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
         rusqlite::params![
             m.probe_type.to_string(),
             m.target,
             m.value,
             m.unit,
-            created_at
+            created_at,
+            m.dscp
         ],
-    )?;
+    ))?;
+
+    Ok(())
+}
+
+/// Save one direction of a throughput test to `throughput_results`.
+///
+/// Like [`save_measurement`], returns `Ok` without writing while storage is
+/// degraded.
+pub fn save_throughput(pool: &Pool, r: &ThroughputResult) -> Result<()> {
+    let conn = pool.get()?;
+    let tcp = r.tcp.as_ref();
+    health::STORAGE_HEALTH.absorb(conn.execute(
+        "INSERT INTO throughput_results
+            (mode, direction, protocol, link_speed_mbps, streams, throughput_mbps, jitter_ms, loss_percent,
+             retransmits, rtt_ms, cwnd_bytes, duration_secs, engine, result_json)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+        rusqlite::params![
+            r.mode,
+            r.direction,
+            r.protocol.to_string(),
+            r.link_speed_mbps.map(|m| m as i64),
+            r.streams,
+            r.throughput_mbps,
+            r.jitter_ms,
+            r.loss_percent,
+            tcp.and_then(|t| t.retransmits).map(|n| n as i64),
+            tcp.and_then(|t| t.rtt_ms),
+            tcp.and_then(|t| t.cwnd_bytes).map(|n| n as i64),
+            r.duration_secs,
+            r.engine,
+            serde_json::to_string(r)?,
+        ],
+    ))?;
+    Ok(())
+}
+
+/// `datetime('now')` format, which incident timestamps are stored in so
+/// SQLite's `datetime()` comparisons work on them.
+const SQLITE_DATETIME: &str = "%Y-%m-%d %H:%M:%S";
 
+/// Insert an incident, or update it in place if its id is already stored.
+///
+/// Severity is stored by name (`Warning`) and `evidence` as JSON; a set
+/// `resolved_at` closes it. Like [`save_measurement`], returns `Ok` without
+/// writing while storage is degraded.
+pub fn save_incident(pool: &Pool, incident: &Incident) -> Result<()> {
+    let conn = pool.get()?;
+    health::STORAGE_HEALTH.absorb(conn.execute(
+        "INSERT INTO incidents (id, severity, verdict, evidence_json, status, created_at, updated_at, resolved_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, datetime('now'), ?7)
+         ON CONFLICT(id) DO UPDATE SET
+            severity = excluded.severity,
+            verdict = excluded.verdict,
+            evidence_json = excluded.evidence_json,
+            status = excluded.status,
+            updated_at = excluded.updated_at,
+            resolved_at = excluded.resolved_at",
+        rusqlite::params![
+            incident.id.to_string(),
+            format!("{:?}", incident.severity),
+            incident.verdict,
+            serde_json::to_string(&incident.evidence)?,
+            if incident.resolved_at.is_some() { "Resolved" } else { "Open" },
+            incident.created_at.format(SQLITE_DATETIME).to_string(),
+            incident.resolved_at.map(|t| t.format(SQLITE_DATETIME).to_string()),
+        ],
+    ))?;
     Ok(())
 }
+
+/// Incidents active at any point since `since`, oldest first: raised since
+/// then, resolved since then, or still open.
+pub fn list_incidents(pool: &Pool, since: DateTime<Utc>) -> Result<Vec<Incident>> {
+    use crate::detect::incident::{parse_severity, parse_timestamp};
+
+    let conn = pool.get()?;
+    let mut stmt = conn.prepare(
+        "SELECT id, severity, verdict, evidence_json, created_at, resolved_at FROM incidents
+         WHERE datetime(created_at) >= datetime(?1)
+            OR resolved_at IS NULL
+            OR datetime(resolved_at) >= datetime(?1)
+         ORDER BY datetime(created_at), id",
+    )?;
+    let rows = stmt.query_map([since.format(SQLITE_DATETIME).to_string()], |row| {
+        let id: String = row.get(0)?;
+        let evidence: String = row.get(3)?;
+        Ok(Incident {
+            id: uuid::Uuid::parse_str(&id).unwrap_or_default(),
+            severity: parse_severity(&row.get::<_, String>(1)?),
+            verdict: row.get(2)?,
+            evidence: serde_json::from_str(&evidence).unwrap_or_default(),
+            created_at: parse_timestamp(&row.get::<_, String>(4)?),
+            resolved_at: row.get::<_, Option<String>>(5)?.as_deref().map(parse_timestamp),
+        })
+    })?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+/// Insert many measurements in one transaction; returns the number written.
+///
+/// Used for bulk loads such as [`import`], where a half-written batch would
+/// be worse than an error, so degraded storage fails the whole batch.
+/// A sample whose probe type, target and timestamp are already stored, or
+/// whose hour has already been rolled up (see [`rollup`]), is skipped, so
+/// loading the same file twice doesn't double its weight in the baseline.
+pub fn save_measurements(pool: &Pool, measurements: &[Measurement]) -> Result<usize> {
+    let mut conn = pool.get()?;
+    let tx = conn.transaction()?;
+    let mut written = 0;
+    {
+        let mut stmt = tx.prepare(
+            "INSERT INTO measurements (probe_type, target, value, unit, created_at, dscp)
+             SELECT ?1, ?2, ?3, ?4, ?5, ?6
+             WHERE NOT EXISTS (
+                 SELECT 1 FROM measurements
+                 WHERE probe_type = ?1 AND target = ?2 AND created_at = ?5
+             )
+             AND NOT EXISTS (
+                 SELECT 1 FROM measurement_rollups
+                 WHERE probe_type = ?1 AND target = ?2
+                 AND hour = strftime('%Y-%m-%d %H:00:00', ?5)
+             )",
+        )?;
+        for m in measurements {
+            let dt: DateTime<Utc> = m.timestamp.into();
+            written += stmt.execute(rusqlite::params![
+                m.probe_type.to_string(),
+                m.target,
+                m.value,
+                m.unit,
+                dt.to_rfc3339(),
+                m.dscp
+            ])?;
+        }
+    }
+    tx.commit()?;
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pool_config_limits_connections() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = PoolConfig {
+            max_size: 2,
+            connection_timeout: Duration::from_millis(100),
+        };
+        let pool = open_pool_with(dir.path().join("t.db").to_str().unwrap(), &config).unwrap();
+        assert_eq!(pool.max_size(), 2);
+
+        let _a = pool.get().unwrap();
+        let _b = pool.get().unwrap();
+        let started = std::time::Instant::now();
+        assert!(pool.get().is_err());
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_save_and_list_incidents_since() {
+        use crate::detect::Severity;
+
+        let dir = tempfile::tempdir().unwrap();
+        let pool = open_pool(dir.path().join("t.db").to_str().unwrap()).unwrap();
+        let now = Utc::now();
+        let incident = |verdict: &str, days_ago: i64, resolved_days_ago: Option<i64>| Incident {
+            id: uuid::Uuid::new_v4(),
+            severity: Severity::Critical,
+            verdict: verdict.to_string(),
+            evidence: serde_json::json!({ "target": "8.8.8.8" }),
+            created_at: now - chrono::Duration::days(days_ago),
+            resolved_at: resolved_days_ago.map(|d| now - chrono::Duration::days(d)),
+        };
+        let mut recent = incident("recent outage", 2, None);
+        save_incident(&pool, &incident("old, resolved", 30, Some(29))).unwrap();
+        save_incident(&pool, &incident("old, still open", 30, None)).unwrap();
+        save_incident(&pool, &recent).unwrap();
+
+        let week = list_incidents(&pool, now - chrono::Duration::days(7)).unwrap();
+        let verdicts: Vec<&str> = week.iter().map(|i| i.verdict.as_str()).collect();
+        assert_eq!(verdicts, ["old, still open", "recent outage"]);
+        assert_eq!(week[1].severity, Severity::Critical);
+        assert_eq!(week[1].evidence["target"], "8.8.8.8");
+        assert!(week[1].resolved_at.is_none());
+
+        // Saving again updates in place.
+        recent.resolved_at = Some(now);
+        save_incident(&pool, &recent).unwrap();
+        let week = list_incidents(&pool, now - chrono::Duration::days(7)).unwrap();
+        assert_eq!(week.len(), 2);
+        assert!(week[1].resolved_at.is_some());
+    }
+
+    #[test]
+    fn test_save_measurements_batch() {
+        use crate::probes::ProbeType;
+
+        let dir = tempfile::tempdir().unwrap();
+        let pool = open_pool(dir.path().join("t.db").to_str().unwrap()).unwrap();
+        let batch: Vec<Measurement> = (0..50)
+            .map(|i| Measurement {
+                probe_type: ProbeType::Icmp,
+                target: "8.8.8.8".into(),
+                value: 10.0 + i as f64,
+                unit: "ms".into(),
+                success: true,
+                timestamp: std::time::SystemTime::now(),
+                dscp: None,
+            })
+            .collect();
+        assert_eq!(save_measurements(&pool, &batch).unwrap(), 50);
+        // Importing the same samples again adds nothing.
+        assert_eq!(save_measurements(&pool, &batch).unwrap(), 0);
+
+        let count: i64 = pool
+            .get()
+            .unwrap()
+            .query_row(
+                "SELECT COUNT(*) FROM measurements WHERE probe_type = 'icmp' AND target = '8.8.8.8'",
+                [],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!(count, 50);
+
+        // Old samples are rolled up and pruned; importing them again must
+        // not add them to the rollup a second time.
+        let old = Measurement {
+            probe_type: ProbeType::Icmp,
+            target: "8.8.8.8".into(),
+            value: 12.0,
+            unit: "ms".into(),
+            success: true,
+            timestamp: std::time::UNIX_EPOCH + std::time::Duration::from_secs(946_684_800),
+            dscp: None,
+        };
+        assert_eq!(save_measurements(&pool, std::slice::from_ref(&old)).unwrap(), 1);
+        rollup::rollup_and_prune(&pool, 7).unwrap();
+        assert_eq!(save_measurements(&pool, std::slice::from_ref(&old)).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_save_throughput_row() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = open_pool(dir.path().join("t.db").to_str().unwrap()).unwrap();
+        let result = ThroughputResult {
+            mode: "lan".to_string(),
+            direction: "download".to_string(),
+            protocol: crate::throughput::iperf::Protocol::Tcp,
+            throughput_mbps: 941.5,
+            omit_secs: None,
+            raw_throughput_mbps: None,
+            jitter_ms: None,
+            loss_percent: None,
+            streams: 4,
+            streams_mbps: Vec::new(),
+            stream_balance: None,
+            duration_secs: 10.0,
+            link_speed_mbps: Some(1000),
+            bytes_transferred: 1_176_875_000,
+            efficiency_pct: None,
+            grade: None,
+            tcp: Some(crate::throughput::TcpStats {
+                retransmits: Some(12),
+                rtt_ms: Some(0.4),
+                cwnd_bytes: None,
+            }),
+            buffers: Some(crate::throughput::native::SocketBuffers {
+                requested_send_bytes: Some(4 << 20),
+                requested_recv_bytes: None,
+                send_bytes: 425_984,
+                recv_bytes: 131_072,
+                nodelay: false,
+            }),
+            engine: "native".to_string(),
+        };
+        save_throughput(&pool, &result).unwrap();
+
+        let conn = pool.get().unwrap();
+        let (mbps, streams, duration, engine, retransmits, protocol): (f64, u32, f64, String, i64, String) = conn
+            .query_row(
+                "SELECT throughput_mbps, streams, duration_secs, engine, retransmits, protocol
+                 FROM throughput_results WHERE mode = 'lan' AND direction = 'download'",
+                [],
+                |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?, r.get(4)?, r.get(5)?)),
+            )
+            .unwrap();
+        assert_eq!(protocol, "tcp");
+        assert_eq!(mbps, 941.5);
+        assert_eq!(streams, 4);
+        assert_eq!(duration, 10.0);
+        assert_eq!(engine, "native");
+        assert_eq!(retransmits, 12);
+        // A kernel-clamped buffer stays visible next to what was requested.
+        let (requested, granted): (i64, i64) = conn
+            .query_row(
+                "SELECT json_extract(result_json, '$.buffers.requested_send_bytes'),
+                        json_extract(result_json, '$.buffers.send_bytes')
+                 FROM throughput_results",
+                [],
+                |r| Ok((r.get(0)?, r.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((requested, granted), (4 << 20, 425_984));
+
+        // Stored in datetime('now') form so window queries compare correctly.
+        let in_window: bool = conn
+            .query_row(
+                "SELECT created_at = datetime(created_at) AND created_at >= datetime('now', '-1 minute')
+                 FROM throughput_results",
+                [],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert!(in_window);
+    }
+
+    #[test]
+    fn test_save_measurement_records_dscp() {
+        use crate::probes::ProbeType;
+
+        let dir = tempfile::tempdir().unwrap();
+        let pool = open_pool(dir.path().join("t.db").to_str().unwrap()).unwrap();
+        for dscp in [None, Some(46)] {
+            save_measurement(
+                &pool,
+                &Measurement {
+                    probe_type: ProbeType::Icmp,
+                    target: "8.8.8.8".into(),
+                    value: 12.0,
+                    unit: "ms".into(),
+                    success: true,
+                    timestamp: std::time::SystemTime::now(),
+                    dscp,
+                },
+            )
+            .unwrap();
+        }
+
+        let conn = pool.get().unwrap();
+        let mut stmt = conn.prepare("SELECT dscp FROM measurements ORDER BY id").unwrap();
+        let stored: Vec<Option<u8>> = stmt
+            .query_map([], |r| r.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(stored, vec![None, Some(46)]);
+    }
+}
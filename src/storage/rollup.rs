@@ -0,0 +1,326 @@
+//! Hourly rollups of old raw measurements.
+//!
+//! Raw probe rows are kept for [`retention_days`] (7 by default). After that
+//! the scheduler folds them into one `measurement_rollups` row per probe type,
+//! target and hour (count, errors, min/avg/max/p95) and deletes the raw rows,
+//! which keeps the database small on an SD card while multi-month trends stay
+//! queryable through [`crate::analysis::trend`].
+
+use std::time::Duration;
+
+use anyhow::Result;
+use rusqlite::params;
+use serde::Serialize;
+
+use super::Pool;
+
+/// How often the scheduler loop rolls up and prunes.
+pub const ROLLUP_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Raw rows younger than this many days are never rolled up.
+pub const DEFAULT_RETENTION_DAYS: u32 = 7;
+
+/// Hour bucket of a timestamp column, in the format rollups are keyed by.
+pub(crate) const HOUR_BUCKET: &str = "strftime('%Y-%m-%d %H:00:00', created_at)";
+
+/// Raw retention in days: `PP_RAW_RETENTION_DAYS`, or the default.
+pub fn retention_days() -> u32 {
+    std::env::var("PP_RAW_RETENTION_DAYS")
+        .ok()
+        .and_then(|v| v.parse::<u32>().ok())
+        .filter(|d| *d > 0)
+        .unwrap_or(DEFAULT_RETENTION_DAYS)
+}
+
+/// Aggregate of one hour of samples. Negative values are error sentinels:
+/// they are counted in `error_count` and excluded from the statistics.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HourStats {
+    pub count: u64,
+    pub error_count: u64,
+    pub min: Option<f64>,
+    pub avg: Option<f64>,
+    pub max: Option<f64>,
+    pub p95: Option<f64>,
+}
+
+impl HourStats {
+    /// Summarize `sorted` (ascending, error sentinels included).
+    pub fn from_sorted(sorted: &[f64]) -> Self {
+        let split = sorted.partition_point(|v| *v < 0.0);
+        let (errors, valid) = sorted.split_at(split);
+        let n = valid.len();
+        let p95 = (n > 0).then(|| {
+            let rank = ((0.95 * n as f64).ceil() as usize).clamp(1, n);
+            valid[rank - 1]
+        });
+        Self {
+            count: n as u64,
+            error_count: errors.len() as u64,
+            min: valid.first().copied(),
+            avg: (n > 0).then(|| valid.iter().sum::<f64>() / n as f64),
+            max: valid.last().copied(),
+            p95,
+        }
+    }
+
+    /// Combine two aggregates of the same hour. `p95` becomes the larger of
+    /// the two, which can only overstate the tail.
+    pub fn merge(&self, other: &Self) -> Self {
+        let count = self.count + other.count;
+        let avg = match (self.avg, other.avg) {
+            (Some(a), Some(b)) => {
+                Some((a * self.count as f64 + b * other.count as f64) / count as f64)
+            }
+            (a, b) => a.or(b),
+        };
+        Self {
+            count,
+            error_count: self.error_count + other.error_count,
+            min: pick(self.min, other.min, f64::min),
+            avg,
+            max: pick(self.max, other.max, f64::max),
+            p95: pick(self.p95, other.p95, f64::max),
+        }
+    }
+
+    /// `count` synthetic samples that reproduce this hour's min, max, mean and
+    /// (roughly) p95, for consumers that need individual values such as
+    /// histograms.
+    pub fn approximate_samples(&self) -> Vec<f64> {
+        let (Some(min), Some(avg), Some(max)) = (self.min, self.avg, self.max) else {
+            return Vec::new();
+        };
+        let n = self.count as usize;
+        if n == 1 {
+            return vec![avg];
+        }
+        let tail = if n >= 20 { n / 20 } else { 0 };
+        let p95 = self.p95.unwrap_or(max);
+        let rest = n - 2 - tail;
+
+        let mut out = Vec::with_capacity(n);
+        out.push(min);
+        out.push(max);
+        out.extend(std::iter::repeat(p95).take(tail));
+        if rest > 0 {
+            // Whatever keeps the overall mean at `avg`.
+            let fill = (avg * n as f64 - min - max - p95 * tail as f64) / rest as f64;
+            out.extend(std::iter::repeat(fill.clamp(min, max)).take(rest));
+        }
+        out
+    }
+}
+
+fn pick(a: Option<f64>, b: Option<f64>, f: fn(f64, f64) -> f64) -> Option<f64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(f(a, b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Outcome of one rollup pass.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RollupResult {
+    /// Hour buckets written or merged.
+    pub buckets: usize,
+    /// Raw `measurements` rows deleted.
+    pub raw_pruned: usize,
+}
+
+/// Roll up raw measurements from complete hours older than `retention_days`
+/// into `measurement_rollups`, then delete them. DSCP-marked samples are
+/// deleted without being rolled up, so they never mix into the unmarked
+/// trend. Runs in one transaction, so an interrupted pass leaves the raw
+/// rows in place.
+pub fn rollup_and_prune(pool: &Pool, retention_days: u32) -> Result<RollupResult> {
+    let mut conn = pool.get()?;
+    let tx = conn.transaction()?;
+
+    let cutoff: String = tx.query_row(
+        "SELECT strftime('%Y-%m-%d %H:00:00', 'now', ?1)",
+        [format!("-{} days", retention_days)],
+        |row| row.get(0),
+    )?;
+
+    let mut result = RollupResult::default();
+    {
+        let mut select = tx.prepare(&format!(
+            "SELECT probe_type, target, unit, {} AS hour, value FROM measurements
+             WHERE datetime(created_at) < ?1 AND dscp IS NULL
+             ORDER BY probe_type, target, hour, value",
+            HOUR_BUCKET
+        ))?;
+        let mut upsert = tx.prepare(
+            "INSERT INTO measurement_rollups
+                (probe_type, target, unit, hour, count, error_count, min, avg, max, p95)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+             ON CONFLICT (probe_type, target, hour) DO UPDATE SET
+                count = count + excluded.count,
+                error_count = error_count + excluded.error_count,
+                min = COALESCE(MIN(min, excluded.min), min, excluded.min),
+                max = COALESCE(MAX(max, excluded.max), max, excluded.max),
+                p95 = COALESCE(MAX(p95, excluded.p95), p95, excluded.p95),
+                avg = CASE WHEN count + excluded.count = 0 THEN NULL
+                      ELSE (COALESCE(avg, 0) * count + COALESCE(excluded.avg, 0) * excluded.count)
+                           / (count + excluded.count) END",
+        )?;
+
+        let mut flush = |key: &(String, String, String), unit: &str, values: &[f64]| {
+            let s = HourStats::from_sorted(values);
+            upsert.execute(params![
+                key.0,
+                key.1,
+                unit,
+                key.2,
+                s.count as i64,
+                s.error_count as i64,
+                s.min,
+                s.avg,
+                s.max,
+                s.p95
+            ])
+        };
+
+        let mut rows = select.query([&cutoff])?;
+        let mut current: Option<((String, String, String), String)> = None;
+        let mut values = Vec::new();
+        while let Some(row) = rows.next()? {
+            let key: (String, String, String) = (row.get(0)?, row.get(1)?, row.get(3)?);
+            if current.as_ref().map_or(true, |(k, _)| *k != key) {
+                if let Some((k, unit)) = current.take() {
+                    flush(&k, &unit, &values)?;
+                    result.buckets += 1;
+                }
+                values.clear();
+                current = Some((key, row.get(2)?));
+            }
+            values.push(row.get(4)?);
+        }
+        if let Some((k, unit)) = current {
+            flush(&k, &unit, &values)?;
+            result.buckets += 1;
+        }
+    }
+
+    result.raw_pruned = tx.execute(
+        "DELETE FROM measurements WHERE datetime(created_at) < ?1",
+        [&cutoff],
+    )?;
+    tx.commit()?;
+    Ok(result)
+}
+
+/// Roll up with the configured retention and log the result; for the
+/// scheduler loop, where a failure must not stop it.
+pub async fn rollup_logged(pool: &Pool) {
+    let pool = pool.clone();
+    let days = retention_days();
+    match tokio::task::spawn_blocking(move || rollup_and_prune(&pool, days)).await {
+        Ok(Ok(r)) if r.raw_pruned > 0 => tracing::info!(
+            buckets = r.buckets,
+            pruned = r.raw_pruned,
+            retention_days = days,
+            "Rolled up old measurements"
+        ),
+        Ok(Ok(_)) => tracing::debug!("No measurements old enough to roll up"),
+        Ok(Err(e)) => tracing::warn!("Measurement rollup failed: {:#}", e),
+        Err(e) => tracing::warn!("Measurement rollup task failed: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert(pool: &Pool, value: f64, created_at: &str) {
+        pool.get()
+            .unwrap()
+            .execute(
+                "INSERT INTO measurements (probe_type, target, value, unit, created_at)
+                 VALUES ('icmp', '1.1.1.1', ?1, 'ms', ?2)",
+                params![value, created_at],
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_hour_stats() {
+        let values: Vec<f64> = std::iter::once(-1.0)
+            .chain((1..=100).map(f64::from))
+            .collect();
+        let s = HourStats::from_sorted(&values);
+        assert_eq!(s.count, 100);
+        assert_eq!(s.error_count, 1);
+        assert_eq!(s.min, Some(1.0));
+        assert_eq!(s.max, Some(100.0));
+        assert_eq!(s.avg, Some(50.5));
+        assert_eq!(s.p95, Some(95.0));
+
+        let errors_only = HourStats::from_sorted(&[-1.0]);
+        assert_eq!(errors_only.count, 0);
+        assert!(errors_only.avg.is_none());
+
+        let merged = s.merge(&HourStats::from_sorted(&[200.0]));
+        assert_eq!(merged.count, 101);
+        assert_eq!(merged.max, Some(200.0));
+        assert!((merged.avg.unwrap() - 5250.0 / 101.0).abs() < 1e-9);
+
+        let samples = s.approximate_samples();
+        assert_eq!(samples.len(), 100);
+        let mean = samples.iter().sum::<f64>() / 100.0;
+        assert!((mean - 50.5).abs() < 1e-9);
+        assert_eq!(samples.iter().cloned().fold(f64::MAX, f64::min), 1.0);
+        assert_eq!(samples.iter().cloned().fold(f64::MIN, f64::max), 100.0);
+    }
+
+    #[test]
+    fn test_rollup_prunes_old_rows_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = crate::storage::open_pool(dir.path().join("t.db").to_str().unwrap()).unwrap();
+
+        // Two hours ten days ago (both timestamp formats in use), one recent row.
+        insert(&pool, 10.0, "2000-01-01 05:10:00");
+        insert(&pool, 20.0, "2000-01-01T05:40:00+00:00");
+        insert(&pool, -1.0, "2000-01-01 05:50:00");
+        insert(&pool, 30.0, "2000-01-01 06:00:00");
+        let recent = chrono::Utc::now().to_rfc3339();
+        insert(&pool, 40.0, &recent);
+
+        let r = rollup_and_prune(&pool, 7).unwrap();
+        assert_eq!(r.buckets, 2);
+        assert_eq!(r.raw_pruned, 4);
+
+        let conn = pool.get().unwrap();
+        let remaining: i64 = conn
+            .query_row("SELECT count(*) FROM measurements", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(remaining, 1);
+
+        let (count, errors, avg): (i64, i64, f64) = conn
+            .query_row(
+                "SELECT count, error_count, avg FROM measurement_rollups
+                 WHERE hour = '2000-01-01 05:00:00'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!((count, errors, avg), (2, 1, 15.0));
+        drop(conn);
+
+        // A late row for an already rolled-up hour is merged, not duplicated.
+        insert(&pool, 60.0, "2000-01-01 06:30:00");
+        let r = rollup_and_prune(&pool, 7).unwrap();
+        assert_eq!((r.buckets, r.raw_pruned), (1, 1));
+        let (count, avg, max): (i64, f64, f64) = pool
+            .get()
+            .unwrap()
+            .query_row(
+                "SELECT count, avg, max FROM measurement_rollups WHERE hour = '2000-01-01 06:00:00'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!((count, avg, max), (2, 45.0, 60.0));
+    }
+}
@@ -32,11 +32,17 @@ pub fn migrate(conn: &Connection) -> Result<()> {
             id INTEGER PRIMARY KEY,
             mode TEXT NOT NULL,
             direction TEXT NOT NULL,
+            protocol TEXT NOT NULL DEFAULT 'tcp',
             link_speed_mbps INTEGER,
             streams INTEGER NOT NULL DEFAULT 1,
             throughput_mbps REAL,
             jitter_ms REAL,
             loss_percent REAL,
+            retransmits INTEGER,
+            rtt_ms REAL,
+            cwnd_bytes INTEGER,
+            duration_secs REAL,
+            engine TEXT,
             result_json TEXT NOT NULL,
             created_at TEXT NOT NULL DEFAULT (datetime('now'))
         );
@@ -46,6 +52,7 @@ pub fn migrate(conn: &Connection) -> Result<()> {
             name TEXT NOT NULL UNIQUE,
             cron_expr TEXT NOT NULL,
             test_type TEXT NOT NULL,
+            params TEXT,
             enabled INTEGER NOT NULL DEFAULT 1,
             last_run_at TEXT,
             next_run_at TEXT,
@@ -61,9 +68,19 @@ pub fn migrate(conn: &Connection) -> Result<()> {
             unit TEXT NOT NULL,
             backend TEXT NOT NULL DEFAULT 'scalar',
             duration_us INTEGER,
+            dscp INTEGER,
             created_at TEXT NOT NULL DEFAULT (datetime('now'))
         );
 
+        -- SoC temperature / throttle flags sampled by the scheduler (see system::thermal)
+        CREATE TABLE IF NOT EXISTS thermal_samples (
+            id INTEGER PRIMARY KEY,
+            temp_c REAL,
+            throttle_mask INTEGER,
+            created_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+        CREATE INDEX IF NOT EXISTS idx_thermal_samples_created ON thermal_samples(created_at);
+
         CREATE TABLE IF NOT EXISTS spool (
             id INTEGER PRIMARY KEY,
             payload_json TEXT NOT NULL,
@@ -84,6 +101,7 @@ pub fn migrate(conn: &Connection) -> Result<()> {
         );
 
         CREATE INDEX IF NOT EXISTS idx_probe_results_created ON probe_results(created_at);
+        CREATE INDEX IF NOT EXISTS idx_measurements_series ON measurements(probe_type, target, created_at);
         CREATE INDEX IF NOT EXISTS idx_incidents_created ON incidents(created_at);
         CREATE INDEX IF NOT EXISTS idx_throughput_created ON throughput_results(created_at);
         CREATE INDEX IF NOT EXISTS idx_schedule_history_name ON schedule_history(schedule_name);
@@ -110,7 +128,72 @@ pub fn migrate(conn: &Connection) -> Result<()> {
             result_json TEXT NOT NULL,
             created_at TEXT NOT NULL DEFAULT (datetime('now'))
         );
-        CREATE INDEX IF NOT EXISTS idx_trace_results_created ON trace_results(created_at);",
+        CREATE INDEX IF NOT EXISTS idx_trace_results_created ON trace_results(created_at);
+
+        CREATE TABLE IF NOT EXISTS data_usage (
+            day TEXT PRIMARY KEY,
+            bytes INTEGER NOT NULL DEFAULT 0
+        );
+
+        CREATE TABLE IF NOT EXISTS reflector_sessions (
+            id INTEGER PRIMARY KEY,
+            test_id TEXT NOT NULL,
+            reflector TEXT NOT NULL,
+            direction TEXT NOT NULL,
+            client_mbps REAL NOT NULL,
+            client_raw_mbps REAL,
+            reflector_mbps REAL,
+            reflector_bytes INTEGER,
+            reflector_duration_sec REAL,
+            outcome TEXT,
+            created_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+        CREATE INDEX IF NOT EXISTS idx_reflector_sessions_created ON reflector_sessions(created_at);
+
+        CREATE TABLE IF NOT EXISTS blame_checks (
+            id INTEGER PRIMARY KEY,
+            verdict TEXT NOT NULL,
+            confidence INTEGER NOT NULL,
+            details_json TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+        CREATE INDEX IF NOT EXISTS idx_blame_checks_created ON blame_checks(created_at);
+
+        -- Hardware self-test runs (see selftest::save_report)
+        CREATE TABLE IF NOT EXISTS selftest_reports (
+            id INTEGER PRIMARY KEY,
+            quick INTEGER NOT NULL DEFAULT 0,
+            report_json TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+        CREATE INDEX IF NOT EXISTS idx_selftest_reports_created ON selftest_reports(created_at);
+
+        -- Hourly aggregates of raw measurements past retention (see storage::rollup)
+        CREATE TABLE IF NOT EXISTS measurement_rollups (
+            id INTEGER PRIMARY KEY,
+            probe_type TEXT NOT NULL,
+            target TEXT NOT NULL,
+            unit TEXT NOT NULL,
+            hour TEXT NOT NULL,
+            count INTEGER NOT NULL,
+            error_count INTEGER NOT NULL DEFAULT 0,
+            min REAL,
+            avg REAL,
+            max REAL,
+            p95 REAL,
+            UNIQUE (probe_type, target, hour)
+        );
+        CREATE INDEX IF NOT EXISTS idx_rollups_hour ON measurement_rollups(hour);
+
+        -- Cross-process speed-test lock (see scheduler::speed_lock)
+        CREATE TABLE IF NOT EXISTS speed_test_lock (
+            id INTEGER PRIMARY KEY CHECK (id = 1),
+            token TEXT NOT NULL,
+            holder TEXT NOT NULL,
+            pid INTEGER NOT NULL,
+            acquired_at TEXT NOT NULL DEFAULT (datetime('now')),
+            expires_at TEXT NOT NULL
+        );",
     )?;
 
     // Migration: Add 'status' to incidents if missing
@@ -124,6 +207,77 @@ pub fn migrate(conn: &Connection) -> Result<()> {
          conn.execute("ALTER TABLE incidents ADD COLUMN status TEXT NOT NULL DEFAULT 'Open'", [])?;
     }
     
+    // Migration: Add TCP health and run columns to throughput_results if missing
+    for (column, ty) in [
+        ("retransmits", "INTEGER"),
+        ("rtt_ms", "REAL"),
+        ("cwnd_bytes", "INTEGER"),
+        ("duration_secs", "REAL"),
+        ("engine", "TEXT"),
+    ] {
+        let exists: i32 = conn.query_row(
+            "SELECT count(*) FROM pragma_table_info('throughput_results') WHERE name=?1",
+            [column],
+            |row| row.get(0)
+        ).unwrap_or(0);
+        if exists == 0 {
+            conn.execute(&format!("ALTER TABLE throughput_results ADD COLUMN {} {}", column, ty), [])?;
+        }
+    }
+
+    // Migration: Add 'protocol' to throughput_results if missing. Only UDP
+    // runs reported jitter, so older rows can be classified by it
+    let has_protocol: i32 = conn.query_row(
+        "SELECT count(*) FROM pragma_table_info('throughput_results') WHERE name='protocol'",
+        [],
+        |row| row.get(0)
+    ).unwrap_or(0);
+    if has_protocol == 0 {
+        conn.execute("ALTER TABLE throughput_results ADD COLUMN protocol TEXT NOT NULL DEFAULT 'tcp'", [])?;
+        conn.execute(
+            "UPDATE throughput_results SET protocol = 'udp' WHERE json_extract(result_json, '$.jitter_ms') IS NOT NULL",
+            [],
+        )?;
+    }
+
+    // Migration: Add 'client_raw_mbps' (rate including an omitted warm-up)
+    // to reflector_sessions if missing
+    let has_client_raw: i32 = conn.query_row(
+        "SELECT count(*) FROM pragma_table_info('reflector_sessions') WHERE name='client_raw_mbps'",
+        [],
+        |row| row.get(0)
+    ).unwrap_or(0);
+    if has_client_raw == 0 {
+        conn.execute("ALTER TABLE reflector_sessions ADD COLUMN client_raw_mbps REAL", [])?;
+    }
+
+    // Migration: Rewrite RFC 3339 throughput timestamps in datetime('now') form,
+    // so window comparisons against datetime() line up
+    conn.execute(
+        "UPDATE throughput_results SET created_at = datetime(created_at) WHERE created_at LIKE '%T%'",
+        [],
+    )?;
+
+    // Migration: Add 'dscp' (probe QoS marking) to measurements if missing
+    let has_dscp: i32 = conn.query_row(
+        "SELECT count(*) FROM pragma_table_info('measurements') WHERE name='dscp'",
+        [],
+        |row| row.get(0)
+    ).unwrap_or(0);
+    if has_dscp == 0 {
+        conn.execute("ALTER TABLE measurements ADD COLUMN dscp INTEGER", [])?;
+    }
+
+    // Migration: Add 'params' (per-test JSON parameters) to schedules if missing
+    let has_params: i32 = conn.query_row(
+        "SELECT count(*) FROM pragma_table_info('schedules') WHERE name='params'",
+        [],
+        |row| row.get(0)
+    ).unwrap_or(0);
+    if has_params == 0 {
+        conn.execute("ALTER TABLE schedules ADD COLUMN params TEXT", [])?;
+    }
+
     // Migration: Fix incidents.id type if it is INTEGER
     let id_type: String = conn.query_row(
         "SELECT type FROM pragma_table_info('incidents') WHERE name='id'",
@@ -147,6 +301,16 @@ pub fn migrate(conn: &Connection) -> Result<()> {
         )?;
         conn.execute("CREATE INDEX IF NOT EXISTS idx_incidents_created ON incidents(created_at)", [])?;
     }
+
+    // Migration: Add 'resolved_at' to incidents if missing (after the rebuild above)
+    let has_resolved_at: i32 = conn.query_row(
+        "SELECT count(*) FROM pragma_table_info('incidents') WHERE name='resolved_at'",
+        [],
+        |row| row.get(0)
+    ).unwrap_or(0);
+    if has_resolved_at == 0 {
+        conn.execute("ALTER TABLE incidents ADD COLUMN resolved_at TEXT", [])?;
+    }
     
     Ok(())
 }
@@ -172,6 +336,86 @@ mod tests {
         assert_eq!(count, 0);
     }
 
+    #[test]
+    fn test_migrate_adds_tcp_columns_to_old_throughput_table() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE throughput_results (
+                id INTEGER PRIMARY KEY,
+                mode TEXT NOT NULL,
+                direction TEXT NOT NULL,
+                result_json TEXT NOT NULL,
+                created_at TEXT NOT NULL DEFAULT (datetime('now'))
+            );",
+        )
+        .unwrap();
+        migrate(&conn).unwrap();
+
+        let count: i64 = conn
+            .query_row(
+                "SELECT count(*) FROM pragma_table_info('throughput_results')
+                 WHERE name IN ('retransmits', 'rtt_ms', 'cwnd_bytes', 'duration_secs', 'engine')",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(count, 5);
+    }
+
+    #[test]
+    fn test_migrate_classifies_old_throughput_rows_by_protocol() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r#"CREATE TABLE throughput_results (
+                id INTEGER PRIMARY KEY,
+                mode TEXT NOT NULL,
+                direction TEXT NOT NULL,
+                result_json TEXT NOT NULL,
+                created_at TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+            INSERT INTO throughput_results (mode, direction, result_json) VALUES
+                ('lan', 'upload', '{"jitter_ms": null}'),
+                ('lan', 'upload', '{"jitter_ms": 0.3}');"#,
+        )
+        .unwrap();
+        migrate(&conn).unwrap();
+
+        let protocols: Vec<String> = conn
+            .prepare("SELECT protocol FROM throughput_results ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(protocols, ["tcp", "udp"]);
+    }
+
+    #[test]
+    fn test_migrate_adds_params_to_old_schedules_table() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE schedules (
+                id INTEGER PRIMARY KEY,
+                name TEXT NOT NULL UNIQUE,
+                cron_expr TEXT NOT NULL,
+                test_type TEXT NOT NULL,
+                enabled INTEGER NOT NULL DEFAULT 1,
+                last_run_at TEXT
+            );",
+        )
+        .unwrap();
+        migrate(&conn).unwrap();
+
+        let count: i64 = conn
+            .query_row(
+                "SELECT count(*) FROM pragma_table_info('schedules') WHERE name = 'params'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+
     #[test]
     fn test_migrate_is_idempotent() {
         let conn = Connection::open_in_memory().unwrap();
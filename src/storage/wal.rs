@@ -0,0 +1,110 @@
+//! WAL checkpoint management.
+//!
+//! With `journal_mode = WAL`, SQLite only checkpoints automatically when a
+//! commit pushes the log past 1000 pages, and never truncates the file. On a
+//! long-running appliance with bursty writes the `-wal` file can sit large
+//! indefinitely, so the scheduler truncates it periodically and the daemon
+//! does so once more on shutdown.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use super::Pool;
+
+/// How often the scheduler loop checkpoints the WAL.
+pub const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Outcome of `PRAGMA wal_checkpoint(TRUNCATE)`.
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckpointResult {
+    /// A reader or writer blocked the checkpoint from completing.
+    pub busy: bool,
+    /// Frames in the WAL before the checkpoint (-1 if not in WAL mode).
+    pub log_frames: i64,
+    /// Frames copied back into the database.
+    pub checkpointed_frames: i64,
+}
+
+/// Copy the WAL into the database and truncate it to zero bytes.
+pub fn checkpoint(pool: &Pool) -> Result<CheckpointResult> {
+    let conn = pool.get()?;
+    conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |row| {
+        Ok(CheckpointResult {
+            busy: row.get::<_, i64>(0)? != 0,
+            log_frames: row.get(1)?,
+            checkpointed_frames: row.get(2)?,
+        })
+    })
+    .context("WAL checkpoint failed")
+}
+
+/// Current size of the `-wal` file in bytes; `None` for in-memory databases.
+pub fn wal_size_bytes(pool: &Pool) -> Result<Option<u64>> {
+    let Some(path) = wal_path(pool)? else {
+        return Ok(None);
+    };
+    match std::fs::metadata(&path) {
+        Ok(meta) => Ok(Some(meta.len())),
+        // No WAL file yet (or removed after the last connection closed).
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Some(0)),
+        Err(e) => Err(e).with_context(|| format!("failed to stat {}", path.display())),
+    }
+}
+
+fn wal_path(pool: &Pool) -> Result<Option<PathBuf>> {
+    let conn = pool.get()?;
+    let file: String = conn.query_row(
+        "SELECT file FROM pragma_database_list WHERE name = 'main'",
+        [],
+        |row| row.get(0),
+    )?;
+    if file.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(PathBuf::from(format!("{}-wal", file))))
+}
+
+/// Checkpoint and log the result; for background loops, where a failure
+/// should be reported but never stop the loop.
+pub async fn checkpoint_logged(pool: &Pool) {
+    let pool = pool.clone();
+    match tokio::task::spawn_blocking(move || checkpoint(&pool)).await {
+        Ok(Ok(r)) if r.busy => {
+            tracing::debug!(frames = r.log_frames, "WAL checkpoint incomplete (database busy)")
+        }
+        Ok(Ok(r)) => tracing::debug!(frames = r.checkpointed_frames, "WAL checkpointed"),
+        Ok(Err(e)) => tracing::warn!("{:#}", e),
+        Err(e) => tracing::warn!("WAL checkpoint task failed: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checkpoint_truncates_wal() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = crate::storage::open_pool(dir.path().join("t.db").to_str().unwrap()).unwrap();
+
+        {
+            let conn = pool.get().unwrap();
+            for day in 0..200 {
+                conn.execute(
+                    "INSERT INTO data_usage (day, bytes) VALUES (?1, ?2)",
+                    rusqlite::params![format!("day-{}", day), day],
+                )
+                .unwrap();
+            }
+        }
+        assert!(wal_size_bytes(&pool).unwrap().unwrap() > 0);
+
+        let r = checkpoint(&pool).unwrap();
+        assert!(!r.busy);
+        assert_eq!(r.log_frames, r.checkpointed_frames);
+        assert_eq!(wal_size_bytes(&pool).unwrap(), Some(0));
+    }
+}
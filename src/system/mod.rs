@@ -1,3 +1,5 @@
 pub mod disk;
 pub mod network;
 pub mod ntp;
+pub mod power;
+pub mod thermal;
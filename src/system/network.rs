@@ -1,5 +1,10 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::net::IpAddr;
 use std::process::Command;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use trust_dns_resolver::TokioAsyncResolver;
 
 /// diverse implementation for gateway detection
 pub fn get_default_gateway() -> Result<String> {
@@ -41,3 +46,187 @@ pub fn get_default_gateway() -> Result<String> {
     // Fallback?
     Ok("192.168.1.1".to_string())
 }
+
+/// How long a lookup is cached when the resolver reports no TTL.
+pub const DEFAULT_DNS_TTL: Duration = Duration::from_secs(30);
+
+/// Longest a lookup is cached, whatever its TTL, so a long-lived record
+/// can't pin a stale answer for hours.
+pub const MAX_DNS_TTL: Duration = Duration::from_secs(300);
+
+/// A resolved name and when it stops being valid.
+#[derive(Debug, Clone)]
+struct CachedLookup {
+    addrs: Vec<IpAddr>,
+    expires: Instant,
+}
+
+/// TTL-respecting hostname cache shared by the probes.
+///
+/// Concurrent lookups of the same name wait on the first one instead of
+/// each querying the resolver, so a LAN scan and a blame check resolving
+/// `google.com` at the same moment send one query between them.
+#[derive(Default)]
+pub struct DnsCache {
+    entries: Mutex<HashMap<String, Arc<tokio::sync::Mutex<Option<CachedLookup>>>>>,
+}
+
+impl DnsCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resolve `host` with the system resolver, from cache when possible.
+    /// IP literals are returned as-is.
+    pub async fn resolve(&self, host: &str) -> Result<Vec<IpAddr>> {
+        self.resolve_with(host, system_lookup).await
+    }
+
+    /// Like [`resolve`](Self::resolve), with `lookup` returning the
+    /// addresses and, if known, when the answer expires.
+    pub async fn resolve_with<F, Fut>(&self, host: &str, lookup: F) -> Result<Vec<IpAddr>>
+    where
+        F: FnOnce(String) -> Fut,
+        Fut: std::future::Future<Output = Result<(Vec<IpAddr>, Option<Instant>)>>,
+    {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        if let Ok(ip) = host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+            return Ok(vec![ip]);
+        }
+
+        let slot = {
+            let mut entries = self.entries.lock().unwrap();
+            let now = Instant::now();
+            // Drop expired names nobody is resolving right now.
+            entries.retain(|_, slot| {
+                Arc::strong_count(slot) > 1
+                    || slot.try_lock().map_or(true, |c| c.as_ref().is_some_and(|c| c.expires > now))
+            });
+            Arc::clone(entries.entry(host.clone()).or_default())
+        };
+
+        let mut cached = slot.lock().await;
+        if let Some(c) = cached.as_ref().filter(|c| c.expires > Instant::now()) {
+            return Ok(c.addrs.clone());
+        }
+
+        let (addrs, valid_until) = lookup(host.clone()).await?;
+        if addrs.is_empty() {
+            anyhow::bail!("{} resolved to no addresses", host);
+        }
+        let now = Instant::now();
+        let ttl = valid_until
+            .map(|t| t.saturating_duration_since(now))
+            .unwrap_or(DEFAULT_DNS_TTL)
+            .min(MAX_DNS_TTL);
+        *cached = Some(CachedLookup { addrs: addrs.clone(), expires: now + ttl });
+        Ok(addrs)
+    }
+}
+
+/// The process-wide cache used by the probes.
+pub fn dns_cache() -> &'static DnsCache {
+    static CACHE: OnceLock<DnsCache> = OnceLock::new();
+    CACHE.get_or_init(DnsCache::new)
+}
+
+/// Resolve `host` through the shared [`dns_cache`].
+pub async fn resolve(host: &str) -> Result<Vec<IpAddr>> {
+    dns_cache().resolve(host).await
+}
+
+/// Look `host` up with the system resolver config, keeping the record TTL.
+/// Falls back to `getaddrinfo` (no TTL) if the config can't be read.
+async fn system_lookup(host: String) -> Result<(Vec<IpAddr>, Option<Instant>)> {
+    static RESOLVER: OnceLock<Option<TokioAsyncResolver>> = OnceLock::new();
+    let resolver = RESOLVER.get_or_init(|| TokioAsyncResolver::tokio_from_system_conf().ok());
+    match resolver {
+        Some(resolver) => {
+            let lookup = resolver
+                .lookup_ip(host.as_str())
+                .await
+                .with_context(|| format!("failed to resolve {}", host))?;
+            Ok((lookup.iter().collect(), Some(lookup.valid_until())))
+        }
+        None => {
+            let addrs = tokio::net::lookup_host((host.as_str(), 0))
+                .await
+                .with_context(|| format!("failed to resolve {}", host))?;
+            Ok((addrs.map(|a| a.ip()).collect(), None))
+        }
+    }
+}
+
+/// Adapter so `reqwest` clients resolve through the shared [`dns_cache`].
+pub struct CachedResolver;
+
+impl reqwest::dns::Resolve for CachedResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let addrs = resolve(&host).await?;
+            let addrs: reqwest::dns::Addrs =
+                Box::new(addrs.into_iter().map(|ip| std::net::SocketAddr::new(ip, 0)));
+            Ok::<_, Box<dyn std::error::Error + Send + Sync>>(addrs)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    type LookupFuture<'a> = std::pin::Pin<
+        Box<dyn std::future::Future<Output = Result<(Vec<IpAddr>, Option<Instant>)>> + Send + 'a>,
+    >;
+
+    fn counting_lookup<'a>(
+        calls: &'a AtomicUsize,
+        ttl: Option<Duration>,
+    ) -> impl FnOnce(String) -> LookupFuture<'a> + 'a {
+        move |_host| {
+            Box::pin(async move {
+                calls.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                Ok((vec!["192.0.2.7".parse().unwrap()], ttl.map(|t| Instant::now() + t)))
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_lookups_share_one_query() {
+        let cache = DnsCache::new();
+        let calls = AtomicUsize::new(0);
+        let ttl = Some(Duration::from_secs(60));
+        let (a, b) = tokio::join!(
+            cache.resolve_with("Example.com", counting_lookup(&calls, ttl)),
+            cache.resolve_with("example.com.", counting_lookup(&calls, ttl)),
+        );
+        assert_eq!(a.unwrap(), b.unwrap());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        cache.resolve_with("example.com", counting_lookup(&calls, ttl)).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_expired_entry_is_refreshed() {
+        let cache = DnsCache::new();
+        let calls = AtomicUsize::new(0);
+        let ttl = Some(Duration::from_millis(10));
+        cache.resolve_with("example.com", counting_lookup(&calls, ttl)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        cache.resolve_with("example.com", counting_lookup(&calls, ttl)).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_ip_literal_skips_lookup() {
+        let cache = DnsCache::new();
+        let calls = AtomicUsize::new(0);
+        let addrs = cache.resolve_with("[2001:db8::1]", counting_lookup(&calls, None)).await.unwrap();
+        assert_eq!(addrs, vec!["2001:db8::1".parse::<IpAddr>().unwrap()]);
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+    }
+}
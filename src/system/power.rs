@@ -0,0 +1,215 @@
+//! Power supply health from the `vcgencmd get_throttled` bitmask.
+//!
+//! The firmware reports under-voltage in the same word as thermal
+//! throttling. They need different fixes (a PSU or cable versus cooling),
+//! so [`ThrottleFlags`] decodes them separately: the low bits are set while
+//! a condition is active, bits 16-19 latch once it has happened since boot.
+//! A Pi that browns out under load drops USB and Ethernet in ways that look
+//! exactly like a flaky network, hence a self-test check and an incident
+//! when it happens during a test.
+
+use anyhow::Result;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::detect::incident::IncidentManager;
+use crate::detect::Severity;
+use crate::selftest::{thermal, ComponentResult, TestStatus};
+use crate::storage::Pool;
+use crate::system::thermal::ThermalWindow;
+
+/// Verdict of the incident opened for under-voltage during a test.
+pub const UNDERVOLTAGE_VERDICT: &str = "Power Supply Under-voltage";
+
+const PSU_REMEDIATION: &str = "Use the official 27W (5V 5A) USB-C power supply and a short, \
+     thick cable; phone chargers and hubs sag under load. Unpowered USB devices add to the draw.";
+
+/// Decoded `vcgencmd get_throttled` flags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ThrottleFlags(pub u32);
+
+impl ThrottleFlags {
+    const UNDER_VOLTAGE: u32 = 1 << 0;
+    const FREQ_CAPPED: u32 = 1 << 1;
+    const THROTTLED: u32 = 1 << 2;
+    const SOFT_TEMP_LIMIT: u32 = 1 << 3;
+    /// Offset of the "has occurred since boot" copies of the bits above.
+    const OCCURRED_SHIFT: u32 = 16;
+
+    fn now(&self, bit: u32) -> bool {
+        self.0 & bit != 0
+    }
+
+    fn occurred(&self, bit: u32) -> bool {
+        self.0 & (bit << Self::OCCURRED_SHIFT) != 0
+    }
+
+    pub fn under_voltage_now(&self) -> bool {
+        self.now(Self::UNDER_VOLTAGE)
+    }
+
+    pub fn under_voltage_occurred(&self) -> bool {
+        self.occurred(Self::UNDER_VOLTAGE)
+    }
+
+    /// Frequency capped, throttled, or at the soft temperature limit now.
+    pub fn throttled_now(&self) -> bool {
+        self.now(Self::FREQ_CAPPED) || self.now(Self::THROTTLED) || self.now(Self::SOFT_TEMP_LIMIT)
+    }
+
+    pub fn throttled_occurred(&self) -> bool {
+        self.occurred(Self::FREQ_CAPPED)
+            || self.occurred(Self::THROTTLED)
+            || self.occurred(Self::SOFT_TEMP_LIMIT)
+    }
+
+    /// Human-readable list of the throttling (not power) conditions.
+    pub fn throttle_details(&self) -> Vec<&'static str> {
+        let mut details = Vec::new();
+        if self.now(Self::FREQ_CAPPED) {
+            details.push("Currently frequency capped (CPU)");
+        }
+        if self.now(Self::THROTTLED) {
+            details.push("Currently throttled (temp)");
+        }
+        if self.now(Self::SOFT_TEMP_LIMIT) {
+            details.push("Currently at soft temperature limit");
+        }
+        if self.occurred(Self::FREQ_CAPPED) {
+            details.push("Past frequency cap event");
+        }
+        if self.occurred(Self::THROTTLED) {
+            details.push("Past throttle event");
+        }
+        if self.occurred(Self::SOFT_TEMP_LIMIT) {
+            details.push("Past soft temperature limit event");
+        }
+        details
+    }
+}
+
+/// Self-test result for the power supply: `Fail` while under-voltage,
+/// `Warning` if it happened since boot.
+pub fn check_power() -> ComponentResult {
+    match thermal::read_throttle_mask() {
+        Ok(mask) => power_result(ThrottleFlags(mask)),
+        Err(_) => ComponentResult {
+            component: "Power Supply".to_string(),
+            status: TestStatus::Skipped,
+            details: "vcgencmd not found. Cannot read under-voltage flags.".to_string(),
+            remediation: Some("Install libraspberrypi-bin".to_string()),
+        },
+    }
+}
+
+fn power_result(flags: ThrottleFlags) -> ComponentResult {
+    let (status, details) = if flags.under_voltage_now() {
+        (TestStatus::Fail, "Currently under-voltage")
+    } else if flags.under_voltage_occurred() {
+        (TestStatus::Warning, "Under-voltage occurred since boot")
+    } else {
+        (TestStatus::Pass, "No under-voltage detected")
+    };
+    ComponentResult {
+        component: "Power Supply".to_string(),
+        remediation: (status != TestStatus::Pass).then(|| PSU_REMEDIATION.to_string()),
+        status,
+        details: format!("Mask=0x{:x}. {}", flags.0, details),
+    }
+}
+
+/// Open (or refresh) an under-voltage incident if `window` saw one during
+/// `test`. Returns the incident ID when one was recorded.
+pub fn record_if_undervoltage(pool: &Pool, test: &str, window: &ThermalWindow) -> Result<Option<Uuid>> {
+    if !window.under_voltage {
+        return Ok(None);
+    }
+    let evidence = json!({
+        "test": test,
+        "samples": window.samples,
+        "max_temp_c": window.max_temp_c,
+        "throttled": window.throttled,
+        "remediation": PSU_REMEDIATION,
+    });
+    IncidentManager::new(pool.clone())
+        .record_incident(UNDERVOLTAGE_VERDICT, Severity::Warning, evidence)
+        .map(Some)
+}
+
+/// [`record_if_undervoltage`] off the async runtime, logging failures
+/// rather than failing the test that produced `window`.
+pub async fn record_undervoltage_logged(pool: &Pool, test: String, window: ThermalWindow) {
+    let pool = pool.clone();
+    let result = tokio::task::spawn_blocking(move || record_if_undervoltage(&pool, &test, &window)).await;
+    match result {
+        Ok(Ok(_)) => {}
+        Ok(Err(e)) => tracing::warn!("Failed to record under-voltage incident: {:#}", e),
+        Err(e) => tracing::warn!("Under-voltage incident task failed: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flags_separate_power_from_thermal() {
+        let flags = ThrottleFlags(0x50005);
+        assert!(flags.under_voltage_now());
+        assert!(flags.under_voltage_occurred());
+        assert!(flags.throttled_now());
+        assert!(flags.throttled_occurred());
+
+        let past_power_only = ThrottleFlags(0x10000);
+        assert!(!past_power_only.under_voltage_now());
+        assert!(past_power_only.under_voltage_occurred());
+        assert!(!past_power_only.throttled_now());
+        assert!(!past_power_only.throttled_occurred());
+        assert!(past_power_only.throttle_details().is_empty());
+
+        let hot_only = ThrottleFlags(0x40004);
+        assert!(!hot_only.under_voltage_now() && !hot_only.under_voltage_occurred());
+        assert_eq!(
+            hot_only.throttle_details(),
+            vec!["Currently throttled (temp)", "Past throttle event"]
+        );
+    }
+
+    #[test]
+    fn test_power_result_status() {
+        let now = power_result(ThrottleFlags(0x1));
+        assert_eq!(now.status, TestStatus::Fail);
+        assert!(now.remediation.unwrap().contains("power supply"));
+        assert_eq!(power_result(ThrottleFlags(0x10000)).status, TestStatus::Warning);
+        let ok = power_result(ThrottleFlags(0x40000));
+        assert_eq!(ok.status, TestStatus::Pass);
+        assert!(ok.remediation.is_none());
+    }
+
+    #[test]
+    fn test_undervoltage_during_test_opens_incident() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = crate::storage::open_pool(dir.path().join("t.db").to_str().unwrap()).unwrap();
+        let mut window = ThermalWindow {
+            samples: 2,
+            max_temp_c: Some(58.0),
+            throttled: false,
+            under_voltage: false,
+        };
+        assert!(record_if_undervoltage(&pool, "speed:wan", &window).unwrap().is_none());
+
+        window.under_voltage = true;
+        let id = record_if_undervoltage(&pool, "speed:wan", &window).unwrap().unwrap();
+        let (verdict, evidence): (String, String) = pool
+            .get()
+            .unwrap()
+            .query_row(
+                "SELECT verdict, evidence_json FROM incidents WHERE id = ?1",
+                [id.to_string()],
+                |r| Ok((r.get(0)?, r.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(verdict, UNDERVOLTAGE_VERDICT);
+        assert!(evidence.contains("speed:wan"));
+    }
+}
@@ -0,0 +1,209 @@
+//! SoC temperature and throttle history.
+//!
+//! [`crate::selftest::thermal::check_throttling`] only says whether the Pi is
+//! throttled right now. A throughput test that ran while the SoC was
+//! throttled (or under-voltage) measures the Pi, not the network, so the
+//! daemon samples temperature and the `vcgencmd get_throttled` flags into
+//! `thermal_samples` every [`SAMPLE_INTERVAL`], and [`during`] summarizes the
+//! samples inside a test window into an annotation such as
+//! "throttled at 85.0°C during test".
+
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use rusqlite::params;
+use serde::Serialize;
+
+use crate::selftest::thermal;
+use crate::storage::Pool;
+use crate::system::power::ThrottleFlags;
+
+/// How often the scheduler loop records a sample.
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Samples older than this are pruned when new ones are written.
+pub const RETENTION_DAYS: i64 = 7;
+
+const TS_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// One reading. Either half may be missing off a Pi (no thermal zone or no
+/// `vcgencmd`).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ThermalSample {
+    pub timestamp: DateTime<Utc>,
+    pub temp_c: Option<f64>,
+    pub throttle_mask: Option<u32>,
+}
+
+impl ThermalSample {
+    /// Read the current temperature and throttle flags.
+    pub fn read() -> Self {
+        Self {
+            timestamp: Utc::now(),
+            temp_c: thermal::get_cpu_temp().ok(),
+            throttle_mask: thermal::read_throttle_mask().ok(),
+        }
+    }
+
+    /// Frequency capped, throttled, or at the soft temperature limit.
+    pub fn throttled(&self) -> bool {
+        self.throttle_mask.is_some_and(|m| ThrottleFlags(m).throttled_now())
+    }
+
+    pub fn under_voltage(&self) -> bool {
+        self.throttle_mask.is_some_and(|m| ThrottleFlags(m).under_voltage_now())
+    }
+}
+
+/// Thermal conditions over a test window.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ThermalWindow {
+    pub samples: usize,
+    pub max_temp_c: Option<f64>,
+    pub throttled: bool,
+    pub under_voltage: bool,
+}
+
+impl ThermalWindow {
+    fn from_samples(samples: &[ThermalSample]) -> Self {
+        Self {
+            samples: samples.len(),
+            max_temp_c: samples.iter().filter_map(|s| s.temp_c).reduce(f64::max),
+            throttled: samples.iter().any(ThermalSample::throttled),
+            under_voltage: samples.iter().any(ThermalSample::under_voltage),
+        }
+    }
+
+    /// Human-readable note when the hardware may have skewed a result.
+    pub fn annotation(&self) -> Option<String> {
+        let temp = self
+            .max_temp_c
+            .map(|t| format!(" at {:.1}°C", t))
+            .unwrap_or_default();
+        match (self.throttled, self.under_voltage) {
+            (true, true) => Some(format!("throttled{} and under-voltage during test", temp)),
+            (true, false) => Some(format!("throttled{} during test", temp)),
+            (false, true) => Some("under-voltage during test".to_string()),
+            (false, false) => None,
+        }
+    }
+}
+
+/// Store a sample and prune ones past [`RETENTION_DAYS`].
+pub fn record(pool: &Pool, sample: &ThermalSample) -> Result<()> {
+    let conn = pool.get()?;
+    conn.execute(
+        "INSERT INTO thermal_samples (temp_c, throttle_mask, created_at) VALUES (?1, ?2, ?3)",
+        params![
+            sample.temp_c,
+            sample.throttle_mask,
+            sample.timestamp.format(TS_FORMAT).to_string()
+        ],
+    )?;
+    let cutoff = sample.timestamp - chrono::Duration::days(RETENTION_DAYS);
+    conn.execute(
+        "DELETE FROM thermal_samples WHERE created_at < ?1",
+        [cutoff.format(TS_FORMAT).to_string()],
+    )?;
+    Ok(())
+}
+
+/// Take a sample now and store it, logging (not returning) failures.
+pub async fn sample_logged(pool: &Pool) {
+    let pool = pool.clone();
+    let result = tokio::task::spawn_blocking(move || record(&pool, &ThermalSample::read())).await;
+    match result {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => tracing::warn!("Failed to record thermal sample: {:#}", e),
+        Err(e) => tracing::warn!("Thermal sample task failed: {}", e),
+    }
+}
+
+/// Samples taken between `from` and `to` (inclusive), oldest first.
+pub fn samples_between(pool: &Pool, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<ThermalSample>> {
+    let conn = pool.get()?;
+    let mut stmt = conn.prepare(
+        "SELECT temp_c, throttle_mask, created_at FROM thermal_samples
+         WHERE created_at >= ?1 AND created_at <= ?2 ORDER BY created_at",
+    )?;
+    let rows = stmt.query_map(
+        [from.format(TS_FORMAT).to_string(), to.format(TS_FORMAT).to_string()],
+        |row| {
+            let ts: String = row.get(2)?;
+            Ok(ThermalSample {
+                temp_c: row.get(0)?,
+                throttle_mask: row.get(1)?,
+                timestamp: NaiveDateTime::parse_from_str(&ts, TS_FORMAT)
+                    .map(|n| Utc.from_utc_datetime(&n))
+                    .unwrap_or_default(),
+            })
+        },
+    )?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+/// Summarize the thermal samples within a test window.
+pub fn during(pool: &Pool, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<ThermalWindow> {
+    Ok(ThermalWindow::from_samples(&samples_between(pool, start, end)?))
+}
+
+/// Samples from the last `minutes`, for the status endpoint.
+pub fn recent(pool: &Pool, minutes: i64) -> Result<Vec<ThermalSample>> {
+    let now = Utc::now();
+    samples_between(pool, now - chrono::Duration::minutes(minutes), now)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(secs_ago: i64, temp: f64, mask: u32) -> ThermalSample {
+        ThermalSample {
+            timestamp: Utc::now() - chrono::Duration::seconds(secs_ago),
+            temp_c: Some(temp),
+            throttle_mask: Some(mask),
+        }
+    }
+
+    #[test]
+    fn test_window_annotation() {
+        let calm = ThermalWindow::from_samples(&[sample(0, 55.0, 0), sample(0, 61.0, 0x50000)]);
+        assert_eq!(calm.max_temp_c, Some(61.0));
+        assert_eq!(calm.annotation(), None, "past-event bits alone don't flag a test");
+
+        let hot = ThermalWindow::from_samples(&[sample(0, 70.0, 0), sample(0, 85.0, 0x4)]);
+        assert_eq!(hot.annotation().as_deref(), Some("throttled at 85.0°C during test"));
+
+        let brownout = ThermalWindow::from_samples(&[sample(0, 50.0, 0x1)]);
+        assert_eq!(brownout.annotation().as_deref(), Some("under-voltage during test"));
+
+        assert_eq!(ThermalWindow::from_samples(&[]).annotation(), None);
+    }
+
+    #[test]
+    fn test_during_selects_window() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = crate::storage::open_pool(dir.path().join("t.db").to_str().unwrap()).unwrap();
+        record(&pool, &sample(600, 80.0, 0x4)).unwrap();
+        record(&pool, &sample(20, 66.0, 0)).unwrap();
+        record(&pool, &sample(10, 84.5, 0x2)).unwrap();
+
+        let now = Utc::now();
+        let window = during(&pool, now - chrono::Duration::seconds(60), now).unwrap();
+        assert_eq!(window.samples, 2);
+        assert_eq!(window.max_temp_c, Some(84.5));
+        assert!(window.throttled);
+
+        assert_eq!(recent(&pool, 60).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_record_prunes_old_samples() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = crate::storage::open_pool(dir.path().join("t.db").to_str().unwrap()).unwrap();
+        record(&pool, &sample(RETENTION_DAYS * 86_400 + 60, 60.0, 0)).unwrap();
+        record(&pool, &sample(0, 60.0, 0)).unwrap();
+        assert_eq!(recent(&pool, RETENTION_DAYS * 24 * 60 + 10).unwrap().len(), 1);
+    }
+}
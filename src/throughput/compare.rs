@@ -0,0 +1,191 @@
+//! Run every available speed-test provider back to back and compare them.
+//!
+//! Providers disagree ("Ookla says 500, Fast says 300"); seeing them side by
+//! side, measured minutes apart on the same line, shows whether one is an
+//! outlier. Runs are strictly sequential so they never share the link.
+
+use crate::scheduler::budget::{self, DataBudget};
+use crate::storage::Pool;
+use crate::throughput::provider::{SpeedTestProvider, SpeedTestRequest, SpeedTestResult};
+
+/// Assumed length of one download or upload phase, for data-budget
+/// accounting (providers don't report bytes uniformly).
+pub const PHASE_SECS: f64 = 10.0;
+
+/// Estimated bytes a provider run moved.
+pub fn estimated_bytes(result: &SpeedTestResult) -> u64 {
+    let mbps = result.download_mbps.unwrap_or(0.0) + result.upload_mbps.unwrap_or(0.0);
+    (mbps * 1_000_000.0 / 8.0 * PHASE_SECS) as u64
+}
+
+/// Run each available provider in `registry` in turn.
+///
+/// With `data_budget`, each run's estimated bytes are recorded against the daily
+/// data budget and the comparison stops early once it is exhausted.
+/// Providers that are missing or fail are skipped (and logged).
+pub async fn run_all(
+    registry: &[Box<dyn SpeedTestProvider>],
+    request: &SpeedTestRequest,
+    data_budget: Option<(&Pool, &DataBudget)>,
+) -> Vec<SpeedTestResult> {
+    let mut results = Vec::new();
+    for provider in registry {
+        let meta = provider.meta();
+        if !provider.is_available() {
+            tracing::info!(provider = meta.id, "Provider not installed, skipping");
+            continue;
+        }
+        if let Some((pool, b)) = data_budget {
+            match b.status(pool) {
+                Ok(status) if status.exhausted => {
+                    tracing::warn!(
+                        provider = meta.id,
+                        used_bytes = status.used_bytes,
+                        "Daily data budget exhausted, stopping comparison"
+                    );
+                    break;
+                }
+                Ok(_) => {}
+                Err(e) if b.daily_bytes.is_some() => {
+                    tracing::error!("Failed to read data budget, stopping comparison: {:#}", e);
+                    break;
+                }
+                Err(e) => tracing::warn!("Failed to read data budget: {:#}", e),
+            }
+        }
+
+        tracing::info!(provider = meta.id, "Running provider");
+        match provider.run(request.clone()).await {
+            Ok(result) => {
+                if let Some((pool, _)) = data_budget {
+                    if let Err(e) = budget::record_usage(pool, estimated_bytes(&result)) {
+                        tracing::warn!("Failed to record data usage: {:#}", e);
+                    }
+                }
+                results.push(result);
+            }
+            Err(e) => tracing::warn!(provider = meta.id, "Provider failed: {:#}", e),
+        }
+    }
+    results
+}
+
+/// Plain-text comparison table (provider, down, up, latency).
+pub fn format_table(results: &[SpeedTestResult]) -> String {
+    let cell = |v: Option<f64>| v.map_or_else(|| "-".to_string(), |v| format!("{:.1}", v));
+    let mut out = format!(
+        "{:<12} {:>10} {:>10} {:>12}\n",
+        "Provider", "Down Mbps", "Up Mbps", "Latency ms"
+    );
+    for r in results {
+        out.push_str(&format!(
+            "{:<12} {:>10} {:>10} {:>12}{}\n",
+            r.provider_id,
+            cell(r.download_mbps),
+            cell(r.upload_mbps),
+            cell(r.latency_ms),
+            if r.truncated { "  (timed out)" } else { "" }
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::throughput::provider::{
+        MetricsSupported, ProviderKind, ProviderMeta, Recommendation, Stability,
+    };
+    use anyhow::Result;
+
+    struct Fake {
+        id: &'static str,
+        available: bool,
+        mbps: Option<f64>,
+    }
+
+    #[async_trait::async_trait]
+    impl SpeedTestProvider for Fake {
+        fn meta(&self) -> ProviderMeta {
+            ProviderMeta {
+                id: self.id,
+                display_name: self.id,
+                kind: ProviderKind::PublicWAN,
+                recommendation: Recommendation::Optional,
+                description: "",
+                install_hint: "",
+                licensing_note: None,
+                stability: Stability::Stable,
+                metrics: MetricsSupported::default(),
+            }
+        }
+
+        fn is_available(&self) -> bool {
+            self.available
+        }
+
+        async fn run(&self, _req: SpeedTestRequest) -> Result<SpeedTestResult> {
+            let mbps = self.mbps.ok_or_else(|| anyhow::anyhow!("boom"))?;
+            Ok(SpeedTestResult {
+                provider_id: self.id.to_string(),
+                download_mbps: Some(mbps),
+                upload_mbps: Some(mbps / 10.0),
+                latency_ms: Some(10.0),
+                jitter_ms: None,
+                packet_loss_pct: None,
+                bufferbloat_ms: None,
+                server: None,
+                raw_json: None,
+                timestamp: chrono::Utc::now(),
+                truncated: false,
+            })
+        }
+    }
+
+    fn fake(id: &'static str, available: bool, mbps: Option<f64>) -> Box<dyn SpeedTestProvider> {
+        Box::new(Fake {
+            id,
+            available,
+            mbps,
+        })
+    }
+
+    fn request() -> SpeedTestRequest {
+        SpeedTestRequest {
+            timeout: std::time::Duration::from_secs(1),
+            prefer_ipv6: false,
+            server_hint: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_all_skips_missing_and_failed() {
+        let registry = vec![
+            fake("a", true, Some(500.0)),
+            fake("missing", false, Some(1.0)),
+            fake("broken", true, None),
+            fake("b", true, Some(300.0)),
+        ];
+        let results = run_all(&registry, &request(), None).await;
+        let ids: Vec<_> = results.iter().map(|r| r.provider_id.as_str()).collect();
+        assert_eq!(ids, ["a", "b"]);
+
+        let table = format_table(&results);
+        assert!(table.contains("500.0"));
+        assert!(table.contains("30.0"));
+        assert_eq!(table.lines().count(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_run_all_stops_when_budget_exhausted() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = crate::storage::open_pool(dir.path().join("t.db").to_str().unwrap()).unwrap();
+        // 800 Mbps down + 80 up for 10 s is 1.1 GB: the first run spends the budget.
+        let limit = DataBudget::from_gb(1.0);
+        let registry = vec![fake("a", true, Some(800.0)), fake("b", true, Some(800.0))];
+
+        let results = run_all(&registry, &request(), Some((&pool, &limit))).await;
+        assert_eq!(results.len(), 1);
+        assert_eq!(budget::used_today(&pool).unwrap(), 1_100_000_000);
+    }
+}
@@ -3,11 +3,30 @@
 use anyhow::Result;
 use serde::Deserialize;
 
+use super::TcpStats;
+
 /// Parsed iperf3 JSON result (subset of fields we care about).
 #[derive(Debug, Deserialize)]
 pub struct Iperf3Result {
     pub start: Iperf3Start,
     pub end: Iperf3End,
+    /// Per-interval reports, including the warm-up omitted with `-O`.
+    #[serde(default)]
+    pub intervals: Vec<Iperf3Interval>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Iperf3Interval {
+    pub sum: Iperf3IntervalSum,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Iperf3IntervalSum {
+    pub seconds: f64,
+    pub bytes: u64,
+    /// Inside the `-O` warm-up, and so left out of the `end` sums.
+    #[serde(default)]
+    pub omitted: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -26,6 +45,11 @@ pub struct Iperf3TestStart {
 pub struct Iperf3End {
     pub sum_sent: Iperf3Sum,
     pub sum_received: Iperf3Sum,
+    /// UDP-only combined summary carrying the receiver's jitter and loss.
+    #[serde(default)]
+    pub sum: Option<Iperf3Sum>,
+    #[serde(default)]
+    pub streams: Vec<Iperf3StreamEnd>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -36,6 +60,264 @@ pub struct Iperf3Sum {
     pub jitter_ms: Option<f64>,
     #[serde(default)]
     pub lost_percent: Option<f64>,
+    /// TCP sender retransmits (absent for UDP).
+    #[serde(default)]
+    pub retransmits: Option<u64>,
+}
+
+/// Per-stream end-of-test report.
+#[derive(Debug, Deserialize)]
+pub struct Iperf3StreamEnd {
+    #[serde(default)]
+    pub sender: Option<Iperf3StreamSender>,
+    #[serde(default)]
+    pub receiver: Option<Iperf3StreamRate>,
+    /// UDP streams report a single combined side instead.
+    #[serde(default)]
+    pub udp: Option<Iperf3StreamRate>,
+}
+
+/// Throughput of one side of a stream.
+#[derive(Debug, Deserialize)]
+pub struct Iperf3StreamRate {
+    pub bits_per_second: f64,
+}
+
+/// Sender-side TCP_INFO figures iperf3 reports on Linux.
+#[derive(Debug, Deserialize)]
+pub struct Iperf3StreamSender {
+    #[serde(default)]
+    pub bits_per_second: Option<f64>,
+    #[serde(default)]
+    pub max_snd_cwnd: Option<u64>,
+    /// Mean RTT in microseconds.
+    #[serde(default)]
+    pub mean_rtt: Option<u64>,
+}
+
+impl Iperf3Result {
+    /// Throughput over every interval, warm-up included, in Mbps.
+    ///
+    /// `None` unless some intervals were omitted with `-O`; otherwise it
+    /// equals the `end` figure.
+    pub fn raw_mbps(&self) -> Option<f64> {
+        if !self.intervals.iter().any(|i| i.sum.omitted) {
+            return None;
+        }
+        let seconds: f64 = self.intervals.iter().map(|i| i.sum.seconds).sum();
+        let bytes: u64 = self.intervals.iter().map(|i| i.sum.bytes).sum();
+        (seconds > 0.0).then(|| bytes as f64 * 8.0 / seconds / 1_000_000.0)
+    }
+
+    /// Throughput of each parallel stream in Mbps, in stream order.
+    ///
+    /// Uses the receiver's figure where present, like `sum_received`.
+    pub fn streams_mbps(&self) -> Vec<f64> {
+        self.end
+            .streams
+            .iter()
+            .filter_map(|s| {
+                s.receiver
+                    .as_ref()
+                    .map(|r| r.bits_per_second)
+                    .or_else(|| s.sender.as_ref().and_then(|r| r.bits_per_second))
+                    .or_else(|| s.udp.as_ref().map(|r| r.bits_per_second))
+            })
+            .map(|bps| bps / 1_000_000.0)
+            .collect()
+    }
+
+    /// UDP jitter (ms) and loss (%), from `end.sum`, falling back to the
+    /// receiver's summary. Both `None` for TCP.
+    pub fn udp_summary(&self) -> (Option<f64>, Option<f64>) {
+        if !self.start.test_start.protocol.eq_ignore_ascii_case("udp") {
+            return (None, None);
+        }
+        let received = &self.end.sum_received;
+        match &self.end.sum {
+            Some(sum) => (
+                sum.jitter_ms.or(received.jitter_ms),
+                sum.lost_percent.or(received.lost_percent),
+            ),
+            None => (received.jitter_ms, received.lost_percent),
+        }
+    }
+
+    /// TCP retransmits, mean RTT across streams, and largest cwnd.
+    ///
+    /// `None` for UDP tests. RTT and cwnd are only present when the sending
+    /// side ran on Linux.
+    pub fn tcp_stats(&self) -> Option<TcpStats> {
+        if !self.start.test_start.protocol.eq_ignore_ascii_case("tcp") {
+            return None;
+        }
+        let senders: Vec<&Iperf3StreamSender> =
+            self.end.streams.iter().filter_map(|s| s.sender.as_ref()).collect();
+        let rtts: Vec<u64> = senders.iter().filter_map(|s| s.mean_rtt).collect();
+        let rtt_ms = if rtts.is_empty() {
+            None
+        } else {
+            Some(rtts.iter().sum::<u64>() as f64 / rtts.len() as f64 / 1000.0)
+        };
+        Some(TcpStats {
+            retransmits: self.end.sum_sent.retransmits,
+            rtt_ms,
+            cwnd_bytes: senders.iter().filter_map(|s| s.max_snd_cwnd).max(),
+        })
+    }
+}
+
+/// Transport an iperf3 test runs over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    #[default]
+    Tcp,
+    /// `-u`: sent at a fixed rate (`-b`), reporting jitter and loss.
+    Udp,
+}
+
+/// Per-stream target rate for UDP tests when no bandwidth is given. iperf3's own
+/// default of 1 Mbit/s is too low to show loss on any real link.
+pub const DEFAULT_UDP_BANDWIDTH: &str = "100M";
+
+impl std::str::FromStr for Protocol {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "tcp" => Ok(Self::Tcp),
+            "udp" => Ok(Self::Udp),
+            other => anyhow::bail!("unknown protocol '{}' (expected tcp or udp)", other),
+        }
+    }
+}
+
+impl std::fmt::Display for Protocol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Protocol::Tcp => write!(f, "tcp"),
+            Protocol::Udp => write!(f, "udp"),
+        }
+    }
+}
+
+impl Protocol {
+    /// iperf3 arguments selecting this protocol.
+    pub fn args(&self) -> Vec<String> {
+        match self {
+            Protocol::Tcp => Vec::new(),
+            Protocol::Udp => vec!["-u".to_string()],
+        }
+    }
+}
+
+/// TCP window (`-w`), buffer length (`-l`), warm-up omission (`-O`) and
+/// target bandwidth (`-b`) passed to iperf3.
+///
+/// Sizes use iperf3 notation: a number with an optional `K`/`M`/`G` suffix.
+/// `None` leaves the setting to iperf3 and kernel autotuning.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Tuning {
+    pub window: Option<String>,
+    pub len: Option<String>,
+    /// Seconds of TCP slow-start to leave out of the reported average. The
+    /// test runs this much longer than its duration.
+    pub omit_secs: Option<u32>,
+    /// Target rate per stream in bits/s, e.g. `500M`, as iperf3 applies
+    /// `-b` to each of the `-P` streams. UDP tests send at this rate
+    /// ([`DEFAULT_UDP_BANDWIDTH`] if unset); TCP tests are capped by it.
+    pub bandwidth: Option<String>,
+}
+
+impl Tuning {
+    /// Recommended settings for a Pi 5 on a link of the given speed.
+    ///
+    /// Gigabit and below is left alone: autotuning already reaches line
+    /// rate. Multi-gig links need a larger window and buffer to keep the
+    /// single PCIe lane busy from two pinned cores.
+    pub fn pi5_defaults(link_speed_mbps: Option<u64>) -> Self {
+        match link_speed_mbps {
+            Some(mbps) if mbps >= 10_000 => Self {
+                window: Some("4M".to_string()),
+                len: Some("1M".to_string()),
+                omit_secs: None,
+                bandwidth: None,
+            },
+            Some(mbps) if mbps > 1_000 => Self {
+                window: Some("2M".to_string()),
+                len: Some("256K".to_string()),
+                omit_secs: None,
+                bandwidth: None,
+            },
+            _ => Self::default(),
+        }
+    }
+
+    /// Fill any unset field from the Pi 5 defaults for `link_speed_mbps`.
+    pub fn or_defaults(self, link_speed_mbps: Option<u64>) -> Self {
+        let defaults = Self::pi5_defaults(link_speed_mbps);
+        Self {
+            window: self.window.or(defaults.window),
+            len: self.len.or(defaults.len),
+            omit_secs: self.omit_secs,
+            bandwidth: self.bandwidth,
+        }
+    }
+
+    /// Check that every set size is valid iperf3 notation.
+    pub fn validate(&self) -> Result<()> {
+        for (flag, value) in [("window", &self.window), ("len", &self.len), ("bandwidth", &self.bandwidth)] {
+            if let Some(v) = value {
+                if !is_valid_size(v) {
+                    anyhow::bail!("invalid --{} size '{}' (expected e.g. 512K, 4M)", flag, v);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// iperf3 command-line arguments for these settings.
+    pub fn args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(w) = &self.window {
+            args.push("-w".to_string());
+            args.push(w.clone());
+        }
+        if let Some(l) = &self.len {
+            args.push("-l".to_string());
+            args.push(l.clone());
+        }
+        if let Some(omit) = self.omit_secs.filter(|&o| o > 0) {
+            args.push("-O".to_string());
+            args.push(omit.to_string());
+        }
+        if let Some(b) = &self.bandwidth {
+            args.push("-b".to_string());
+            args.push(b.clone());
+        }
+        args
+    }
+}
+
+/// A positive integer with an optional K/M/G suffix (case-insensitive).
+fn is_valid_size(s: &str) -> bool {
+    parse_size(s).is_some()
+}
+
+/// Bytes in an iperf3-notation size: `512K` is 524288. `None` for anything
+/// [`Tuning::validate`] rejects.
+pub(crate) fn parse_size(s: &str) -> Option<u64> {
+    let (digits, scale) = match s.chars().last()? {
+        'K' | 'k' => (&s[..s.len() - 1], 1u64 << 10),
+        'M' | 'm' => (&s[..s.len() - 1], 1 << 20),
+        'G' | 'g' => (&s[..s.len() - 1], 1 << 30),
+        _ => (s, 1),
+    };
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse::<u64>().ok().filter(|&n| n > 0)?.checked_mul(scale)
 }
 
 /// Parse an iperf3 JSON output string into a structured result.
@@ -63,6 +345,84 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_tcp_stats_from_fixture() {
+        let fixture_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join("iperf3")
+            .join("1g-tcp.json");
+        if fixture_path.exists() {
+            let json_str = std::fs::read_to_string(&fixture_path).unwrap();
+            let tcp = parse_output(&json_str).unwrap().tcp_stats().unwrap();
+            assert_eq!(tcp.retransmits, Some(8));
+        }
+    }
+
+    #[test]
+    fn test_tcp_stats_with_stream_info() {
+        let json = r#"{
+            "start": {"test_start": {"protocol": "TCP", "num_streams": 2, "duration": 10}},
+            "end": {
+                "streams": [
+                    {"sender": {"max_snd_cwnd": 524288, "mean_rtt": 12000}},
+                    {"sender": {"max_snd_cwnd": 1048576, "mean_rtt": 14000}}
+                ],
+                "sum_sent": {"bits_per_second": 9.4e8, "bytes": 1175000000, "retransmits": 1204},
+                "sum_received": {"bits_per_second": 9.3e8, "bytes": 1162500000}
+            }
+        }"#;
+        let tcp = parse_output(json).unwrap().tcp_stats().unwrap();
+        assert_eq!(tcp.retransmits, Some(1204));
+        assert_eq!(tcp.rtt_ms, Some(13.0));
+        assert_eq!(tcp.cwnd_bytes, Some(1_048_576));
+    }
+
+    #[test]
+    fn test_streams_mbps_prefers_receiver() {
+        let json = r#"{
+            "start": {"test_start": {"protocol": "TCP", "num_streams": 3, "duration": 10}},
+            "end": {
+                "streams": [
+                    {"sender": {"bits_per_second": 3.2e8}, "receiver": {"bits_per_second": 3.1e8}},
+                    {"sender": {"bits_per_second": 3.0e8}},
+                    {"udp": {"bits_per_second": 2.5e7}}
+                ],
+                "sum_sent": {"bits_per_second": 6.45e8, "bytes": 806250000},
+                "sum_received": {"bits_per_second": 6.35e8, "bytes": 793750000}
+            }
+        }"#;
+        let streams = parse_output(json).unwrap().streams_mbps();
+        assert_eq!(streams, vec![310.0, 300.0, 25.0]);
+    }
+
+    #[test]
+    fn test_tcp_stats_none_for_udp() {
+        let json = r#"{
+            "start": {"test_start": {"protocol": "UDP", "num_streams": 1, "duration": 10}},
+            "end": {
+                "sum_sent": {"bits_per_second": 1e9, "bytes": 1250000000},
+                "sum_received": {"bits_per_second": 9.9e8, "bytes": 1237500000, "lost_percent": 1.0}
+            }
+        }"#;
+        assert!(parse_output(json).unwrap().tcp_stats().is_none());
+    }
+
+    #[test]
+    fn test_udp_summary_jitter_and_loss() {
+        let json = r#"{
+            "start": {"test_start": {"protocol": "UDP", "num_streams": 1, "duration": 10}},
+            "end": {
+                "sum": {"bits_per_second": 9.9e8, "bytes": 1237500000, "jitter_ms": 0.021, "lost_percent": 0.4},
+                "sum_sent": {"bits_per_second": 1e9, "bytes": 1250000000},
+                "sum_received": {"bits_per_second": 9.9e8, "bytes": 1237500000}
+            }
+        }"#;
+        assert_eq!(parse_output(json).unwrap().udp_summary(), (Some(0.021), Some(0.4)));
+
+        let tcp = json.replace("UDP", "TCP");
+        assert_eq!(parse_output(&tcp).unwrap().udp_summary(), (None, None));
+    }
+
     #[test]
     fn test_parse_10g_tcp_fixture() {
         let fixture_path = Path::new(env!("CARGO_MANIFEST_DIR"))
@@ -76,4 +436,100 @@ mod tests {
             assert!(result.end.sum_received.bits_per_second > 5_000_000_000.0);
         }
     }
+
+    #[test]
+    fn test_tuning_pi5_defaults_by_link_speed() {
+        assert_eq!(Tuning::pi5_defaults(None), Tuning::default());
+        assert_eq!(Tuning::pi5_defaults(Some(1000)), Tuning::default());
+
+        let multi_gig = Tuning::pi5_defaults(Some(2500));
+        assert_eq!(multi_gig.window.as_deref(), Some("2M"));
+        assert_eq!(multi_gig.len.as_deref(), Some("256K"));
+
+        let ten_gig = Tuning::pi5_defaults(Some(10_000));
+        assert_eq!(ten_gig.args(), vec!["-w", "4M", "-l", "1M"]);
+    }
+
+    #[test]
+    fn test_tuning_user_values_override_defaults() {
+        let tuning = Tuning {
+            window: Some("8M".to_string()),
+            len: None,
+            omit_secs: Some(3),
+            bandwidth: None,
+        }
+        .or_defaults(Some(10_000));
+        assert_eq!(tuning.window.as_deref(), Some("8M"));
+        assert_eq!(tuning.len.as_deref(), Some("1M"));
+        assert_eq!(tuning.args(), vec!["-w", "8M", "-l", "1M", "-O", "3"]);
+    }
+
+    #[test]
+    fn test_tuning_validate() {
+        let ok = Tuning {
+            window: Some("512K".to_string()),
+            len: Some("131072".to_string()),
+            omit_secs: None,
+            bandwidth: Some("500M".to_string()),
+        };
+        assert!(ok.validate().is_ok());
+
+        for bad in ["", "K", "0", "4MB", "-4M", "4 M"] {
+            let tuning = Tuning {
+                window: Some(bad.to_string()),
+                len: None,
+                omit_secs: None,
+                bandwidth: None,
+            };
+            assert!(tuning.validate().is_err(), "{:?} should be rejected", bad);
+        }
+    }
+
+    #[test]
+    fn test_udp_protocol_args() {
+        let protocol: Protocol = "UDP".parse().unwrap();
+        assert_eq!(protocol, Protocol::Udp);
+        assert_eq!(protocol.args(), vec!["-u"]);
+        assert!(Protocol::Tcp.args().is_empty());
+        assert!("sctp".parse::<Protocol>().is_err());
+
+        let tuning = Tuning {
+            bandwidth: Some("500M".to_string()),
+            ..Tuning::default()
+        };
+        assert_eq!(tuning.args(), vec!["-b", "500M"]);
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("131072"), Some(131_072));
+        assert_eq!(parse_size("512K"), Some(512 * 1024));
+        assert_eq!(parse_size("4m"), Some(4 * 1024 * 1024));
+        assert_eq!(parse_size("1G"), Some(1 << 30));
+        for bad in ["", "M", "0", "4MB", "-4M"] {
+            assert!(parse_size(bad).is_none(), "{:?} should be rejected", bad);
+        }
+    }
+
+    #[test]
+    fn test_raw_mbps_includes_omitted_intervals() {
+        let json = r#"{
+            "start": {"test_start": {"protocol": "TCP", "num_streams": 1, "duration": 2}},
+            "intervals": [
+                {"sum": {"seconds": 1.0, "bytes": 12500000, "omitted": true}},
+                {"sum": {"seconds": 1.0, "bytes": 125000000, "omitted": false}},
+                {"sum": {"seconds": 1.0, "bytes": 125000000, "omitted": false}}
+            ],
+            "end": {
+                "sum_sent": {"bits_per_second": 1000000000, "bytes": 250000000},
+                "sum_received": {"bits_per_second": 1000000000, "bytes": 250000000}
+            }
+        }"#;
+        let result = parse_output(json).unwrap();
+        // (12.5 + 125 + 125) MB over 3 s.
+        assert!((result.raw_mbps().unwrap() - 700.0).abs() < 1e-9);
+
+        let no_omit = json.replace(r#""omitted": true"#, r#""omitted": false"#);
+        assert!(parse_output(&no_omit).unwrap().raw_mbps().is_none());
+    }
 }
@@ -0,0 +1,92 @@
+//! Local link speed discovery via `/sys/class/net`.
+
+use std::net::{IpAddr, ToSocketAddrs};
+use std::path::Path;
+
+const SYS_CLASS_NET: &str = "/sys/class/net";
+
+/// Negotiated link speed of `iface` in Mbps, if the kernel reports one.
+///
+/// Wi-Fi, virtual interfaces, and links that are down report no speed.
+pub fn read_link_speed(iface: &str) -> Option<u64> {
+    let path = Path::new(SYS_CLASS_NET).join(iface).join("speed");
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|s| parse_speed(&s))
+}
+
+/// Interface the kernel would route traffic to `target` through.
+///
+/// `target` may be a hostname or an IP address; the first resolved address
+/// is used.
+pub fn egress_interface(target: &str) -> Option<String> {
+    let ip: IpAddr = match target.parse() {
+        Ok(ip) => ip,
+        Err(_) => (target, 0).to_socket_addrs().ok()?.next()?.ip(),
+    };
+
+    let output = std::process::Command::new("ip")
+        .args(["-o", "route", "get", &ip.to_string()])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_route_dev(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Link speed of the interface used to reach `target`, if known.
+pub fn egress_link_speed_mbps(target: &str) -> Option<u64> {
+    let iface = egress_interface(target)?;
+    let speed = read_link_speed(&iface);
+    tracing::debug!(%target, %iface, ?speed, "Egress link speed");
+    speed
+}
+
+/// Extract the `dev <iface>` field from `ip route get` output.
+fn parse_route_dev(route: &str) -> Option<String> {
+    let mut fields = route.split_whitespace();
+    while let Some(field) = fields.next() {
+        if field == "dev" {
+            return fields.next().map(str::to_string);
+        }
+    }
+    None
+}
+
+/// Parse the contents of a sysfs `speed` file. The kernel writes `-1`
+/// (or an error on read) when the speed is unknown.
+fn parse_speed(raw: &str) -> Option<u64> {
+    match raw.trim().parse::<i64>() {
+        Ok(mbps) if mbps > 0 => Some(mbps as u64),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_speed() {
+        assert_eq!(parse_speed("1000\n"), Some(1000));
+        assert_eq!(parse_speed("10000"), Some(10000));
+        assert_eq!(parse_speed("-1\n"), None);
+        assert_eq!(parse_speed("0"), None);
+        assert_eq!(parse_speed(""), None);
+    }
+
+    #[test]
+    fn test_parse_route_dev() {
+        let route = "1.1.1.1 via 192.168.1.1 dev eth0 src 192.168.1.20 uid 1000 \\    cache ";
+        assert_eq!(parse_route_dev(route), Some("eth0".to_string()));
+        let v6 = "2606:4700::1111 from :: via fe80::1 dev enp1s0 proto ra src 2001:db8::2 metric 100 pref medium";
+        assert_eq!(parse_route_dev(v6), Some("enp1s0".to_string()));
+        assert_eq!(parse_route_dev("RTNETLINK answers: Network is unreachable"), None);
+    }
+
+    #[test]
+    fn test_loopback_has_no_speed() {
+        assert_eq!(read_link_speed("lo"), None);
+    }
+}
@@ -1,15 +1,24 @@
 //! Throughput testing engine: iperf3 wrapper + native Rust fallback.
 
 pub mod provider;
+pub mod compare;
 pub mod iperf;
 pub mod lan;
+pub mod link;
 pub mod native;
 pub mod report;
 pub mod wan;
 
+use std::sync::Mutex;
+use std::time::Duration;
+
 use anyhow::Result;
 use thiserror::Error;
 
+use crate::probes::tcp::TcpProbe;
+use crate::probes::{Measurement, Probe};
+use crate::storage::Pool;
+
 #[derive(Debug, Error)]
 pub enum ThroughputError {
     #[error("iperf3 not found at {path}")]
@@ -28,60 +37,250 @@ pub enum ThroughputError {
     PeerUnreachable { peer: String },
 }
 
+/// TCP-level health of a throughput test.
+///
+/// Goodput alone cannot tell a capacity limit from a lossy path; a high
+/// retransmit count at full speed points at loss or congestion instead.
+/// Each field is `None` when the engine or platform does not report it
+/// (RTT and cwnd come from Linux `TCP_INFO`).
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct TcpStats {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retransmits: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rtt_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cwnd_bytes: Option<u64>,
+}
+
 /// Throughput test result.
-#[derive(Debug, serde::Serialize)]
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct ThroughputResult {
     pub mode: String,
     pub direction: String,
+    /// Transport the test ran over. UDP runs send at a fixed rate, so their
+    /// throughput is not a capacity figure. Results stored before this
+    /// field existed were TCP.
+    #[serde(default)]
+    pub protocol: iperf::Protocol,
+    /// Steady-state throughput: with `omit_secs`, the warm-up is excluded.
     pub throughput_mbps: f64,
+    /// Seconds of warm-up left out of `throughput_mbps`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub omit_secs: Option<u32>,
+    /// Throughput including the warm-up; only set with `omit_secs`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw_throughput_mbps: Option<f64>,
     pub jitter_ms: Option<f64>,
     pub loss_percent: Option<f64>,
     pub streams: u32,
+    /// Throughput of each parallel stream; empty when not reported.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub streams_mbps: Vec<f64>,
+    /// Slowest stream relative to the mean, 0-1. See [`report::stream_balance`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_balance: Option<f64>,
     pub duration_secs: f64,
     pub link_speed_mbps: Option<u64>,
+    /// Bytes moved over the wire by this test, for data-budget accounting.
+    pub bytes_transferred: u64,
+    /// Throughput as a percentage of `link_speed_mbps`; omitted when the
+    /// link speed is unknown. See [`report::apply_efficiency`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub efficiency_pct: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub grade: Option<report::EfficiencyGrade>,
+    /// TCP retransmits / RTT / cwnd; omitted for UDP tests.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tcp: Option<TcpStats>,
+    /// Socket buffers the kernel granted the native engine, next to the
+    /// sizes requested; `None` for iperf3.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub buffers: Option<native::SocketBuffers>,
     pub engine: String, // "iperf3" or "native"
 }
 
 /// Run a throughput test with the given parameters.
-pub async fn run_test(mode: &str, peer: Option<&str>, duration: &str, streams: u32) -> Result<()> {
-    tracing::info!(%mode, ?peer, %duration, %streams, "Running throughput test");
+///
+/// iperf3 window/buffer sizes are picked from the Pi 5 defaults for the
+/// fastest local link; see [`run_test_tuned`] to override them.
+///
+/// Returns one result per direction that completed. With a `pool`, each
+/// direction is also recorded in `throughput_results` (best-effort: a
+/// storage failure is logged, not returned). UDP results carry jitter and
+/// loss.
+pub async fn run_test(
+    pool: Option<&Pool>,
+    mode: &str,
+    peer: Option<&str>,
+    duration: &str,
+    streams: u32,
+    protocol: iperf::Protocol,
+) -> Result<Vec<ThroughputResult>> {
+    run_test_tuned(pool, mode, peer, duration, streams, protocol, iperf::Tuning::default()).await
+}
+
+/// Run a throughput test with explicit iperf3 tuning. Unset fields in
+/// `tuning` fall back to the Pi 5 defaults for the link toward the target (TCP)
+/// or to [`iperf::DEFAULT_UDP_BANDWIDTH`] (UDP).
+pub async fn run_test_tuned(
+    pool: Option<&Pool>,
+    mode: &str,
+    peer: Option<&str>,
+    duration: &str,
+    streams: u32,
+    protocol: iperf::Protocol,
+    tuning: iperf::Tuning,
+) -> Result<Vec<ThroughputResult>> {
+    tuning.validate()?;
 
     // Parse duration ("30s" -> 30)
+    // `omit_secs` runs on top of this, so any warm-up length is fine.
     let dur_secs: u32 = duration.trim_end_matches('s').parse().unwrap_or(30);
 
-    let target = if let Some(p) = peer { p } else { 
-        if mode == "wan" { 
-            // Try to find a working public server
-            find_public_server()
-        } else { 
-            anyhow::bail!("Peer required for LAN test (use --peer <IP>)") 
-        }
+    let target = match peer {
+        Some(p) => p.to_string(),
+        // Probe the public servers for the closest one
+        None if mode == "wan" => find_public_server().await?,
+        None => anyhow::bail!("Peer required for LAN test (use --peer <IP>)"),
+    };
+    let target = target.as_str();
+    let tuning = match protocol {
+        iperf::Protocol::Tcp => tuning.or_defaults(link::egress_link_speed_mbps(target)),
+        // The Pi 5 defaults size TCP windows; a 1M `-l` is no valid datagram.
+        iperf::Protocol::Udp => iperf::Tuning {
+            bandwidth: tuning.bandwidth.or_else(|| Some(iperf::DEFAULT_UDP_BANDWIDTH.to_string())),
+            ..tuning
+        },
     };
+    tracing::info!(%mode, ?peer, %duration, %streams, %protocol, ?tuning, "Running throughput test");
 
-    println!("Running {} throughput test against {} for {}s ({} streams)...", mode.to_uppercase(), target, dur_secs, streams);
+    println!(
+        "Running {} {} throughput test against {} for {}s ({} streams)...",
+        mode.to_uppercase(),
+        protocol.to_string().to_uppercase(),
+        target,
+        dur_secs,
+        streams
+    );
 
-    // Run Upload (Client -> Server)
-    run_iperf_direction(target, dur_secs, streams, false)?;
+    validate_target(target)?;
+    let mut results = Vec::with_capacity(2);
+    let mut failures = Vec::new();
+    // Upload (client -> server), then download (server -> client, -R).
+    for reverse in [false, true] {
+        let label = if reverse { "DOWNLOAD" } else { "UPLOAD" };
+        println!("Starting {} test...", label);
+        let result = match run_iperf_direction(mode, target, dur_secs, streams, reverse, protocol, &tuning) {
+            // The native engine is TCP-only; a UDP test needs iperf3.
+            Err(e)
+                if protocol == iperf::Protocol::Tcp
+                    && matches!(e.downcast_ref(), Some(ThroughputError::Iperf3NotFound { .. })) =>
+            {
+                // Public servers run iperf3, which the native engine can't talk to.
+                if peer.is_none() {
+                    Err(e.context(
+                        "the native engine can't test against public iperf3 servers; \
+                         install iperf3 or pass --peer <IP> of a host running a discard \
+                         (upload) or source (download) service on the native port",
+                    ))
+                } else {
+                    println!("  -> {:#}; falling back to the native engine", e);
+                    run_native_direction(mode, target, dur_secs, streams, reverse, &tuning).await
+                }
+            }
+            other => other,
+        };
+        match result {
+            Ok(r) => {
+                print_direction(label, &r);
+                results.push(r);
+            }
+            Err(e) => {
+                println!("  -> {} failed: {:#}", label, e);
+                failures.push(format!("{}: {:#}", label.to_lowercase(), e));
+            }
+        }
+    }
+    // The server may have gone away since it was picked.
+    if peer.is_none() && !failures.is_empty() {
+        forget_public_server(target);
+    }
+    if results.is_empty() {
+        anyhow::bail!("Throughput test against {} failed ({})", target, failures.join("; "));
+    }
 
-    // Run Download (Server -> Client, -R)
-    run_iperf_direction(target, dur_secs, streams, true)?;
+    if let Some(pool) = pool {
+        for result in &results {
+            if let Err(e) = crate::storage::save_throughput(pool, result) {
+                tracing::warn!("Failed to record throughput result: {:#}", e);
+            }
+        }
+    }
 
-    Ok(())
+    Ok(results)
 }
 
-fn find_public_server() -> &'static str {
-    // List of public iperf3 servers (best effort)
-    // In a real pro app, we'd ping them for lowest latency first.
-    let _servers = [
-        "speedtest.wtnet.de",
-        "ping.online.net",
-        "iperf.biznetnetworks.com",
-        "bouygues.iperf.fr",
-    ];
-    // For now, just return the first one as default, but ideally we'd loop.
-    // Given execution constraints, let's pick a high-availability one.
-    // wtnet is often busy. ping.online.net is robust.
-    "ping.online.net"
+/// Public iperf3 servers tried for WAN tests without `--peer`.
+const PUBLIC_SERVERS: &[&str] = &[
+    "speedtest.wtnet.de",
+    "ping.online.net",
+    "iperf.biznetnetworks.com",
+    "bouygues.iperf.fr",
+];
+
+/// How long each public server gets to accept a TCP connection.
+const PUBLIC_SERVER_PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Public server picked by [`find_public_server`], until a test against it
+/// fails.
+static PUBLIC_SERVER: Mutex<Option<String>> = Mutex::new(None);
+
+/// The lowest-latency public iperf3 server.
+///
+/// Candidates are probed concurrently with a TCP connect to port 5201. The
+/// winner is cached until a test against it fails (see
+/// [`forget_public_server`]); a failed search is not cached, so the next
+/// test probes again.
+async fn find_public_server() -> Result<String> {
+    if let Some(server) = PUBLIC_SERVER.lock().unwrap().clone() {
+        return Ok(server);
+    }
+
+    let probes = PUBLIC_SERVERS.iter().map(|server| async move {
+        let measurement = TcpProbe
+            .run(&format!("{}:5201", server), PUBLIC_SERVER_PROBE_TIMEOUT)
+            .await;
+        (*server, measurement.ok())
+    });
+    let results = futures::future::join_all(probes).await;
+    for (server, m) in &results {
+        tracing::debug!(%server, rtt_ms = ?m.as_ref().filter(|m| m.success).map(|m| m.value), "Public server probe");
+    }
+
+    let server = lowest_latency(results.into_iter().filter_map(|(s, m)| Some((s, m?))))
+        .ok_or_else(|| anyhow::anyhow!("no public iperf3 server reachable (tried {})", PUBLIC_SERVERS.join(", ")))?;
+    *PUBLIC_SERVER.lock().unwrap() = Some(server.to_string());
+    Ok(server.to_string())
+}
+
+/// Drop `server` from the cache so the next WAN test probes again. A no-op
+/// if another server has been picked since.
+fn forget_public_server(server: &str) {
+    let mut cached = PUBLIC_SERVER.lock().unwrap();
+    if cached.as_deref() == Some(server) {
+        tracing::info!(%server, "Public iperf3 server failed; probing again next test");
+        *cached = None;
+    }
+}
+
+/// The server with the fastest successful probe, if any succeeded.
+fn lowest_latency<'a>(probes: impl IntoIterator<Item = (&'a str, Measurement)>) -> Option<&'a str> {
+    probes
+        .into_iter()
+        .filter(|(_, m)| m.success)
+        .min_by(|a, b| a.1.value.total_cmp(&b.1.value))
+        .map(|(server, _)| server)
 }
 
 fn validate_target(target: &str) -> Result<()> {
@@ -98,11 +297,50 @@ fn validate_target(target: &str) -> Result<()> {
     Ok(())
 }
 
-fn run_iperf_direction(target: &str, duration: u32, streams: u32, reverse: bool) -> Result<()> {
+/// Exit status of `taskset` when the command it should run does not exist.
+const TASKSET_EXEC_NOT_FOUND: i32 = 127;
+
+/// One-direction summary lines, as printed after each direction of
+/// [`run_test`].
+fn print_direction(label: &str, r: &ThroughputResult) {
+    println!("  -> {}: {:.2} Mbps", label, r.throughput_mbps);
+    if let (Some(omit), Some(raw)) = (r.omit_secs, r.raw_throughput_mbps) {
+        println!("     {:.2} Mbps including the first {}s of warm-up", raw, omit);
+    }
+    if let (Some(jitter), Some(loss)) = (r.jitter_ms, r.loss_percent) {
+        println!("     jitter {:.2}ms, loss {:.2}%", jitter, loss);
+    }
+    for line in [
+        report::efficiency_line(r),
+        report::tcp_line(r),
+        report::stream_line(r),
+    ]
+    .into_iter()
+    .flatten()
+    {
+        println!("     {}", line);
+    }
+}
+
+/// Run iperf3 in one direction against `target` and parse its report.
+///
+/// `reverse` runs the download (`-R`). Jitter and loss come from the UDP
+/// summary and are `None` for TCP; `link_speed_mbps` is the local egress
+/// interface's speed.
+fn run_iperf_direction(
+    mode: &str,
+    target: &str,
+    duration: u32,
+    streams: u32,
+    reverse: bool,
+    protocol: iperf::Protocol,
+    tuning: &iperf::Tuning,
+) -> Result<ThroughputResult> {
     validate_target(target)?;
 
-    let dir_str = if reverse { "DOWNLOAD" } else { "UPLOAD" };
-    println!("Starting {} test...", dir_str);
+    // Capacity of the local link this test leaves through, so the result
+    // can be read as achieved-vs-capacity.
+    let link_speed_mbps = link::egress_link_speed_mbps(target);
 
     // Build iperf3 arguments
     let mut iperf_args = vec![
@@ -117,6 +355,8 @@ fn run_iperf_direction(target: &str, duration: u32, streams: u32, reverse: bool)
     if reverse {
         iperf_args.push("-R".to_string());
     }
+    iperf_args.extend(protocol.args());
+    iperf_args.extend(tuning.args());
 
     // Optimization: Pin to cores 2,3 on Pi 5 (leave 0,1 for OS/API)
     // Pi 5 guarantees 'taskset' availability (util-linux).
@@ -127,28 +367,146 @@ fn run_iperf_direction(target: &str, duration: u32, streams: u32, reverse: bool)
     cmd.arg("iperf3");
     cmd.args(&iperf_args);
 
-    let output = cmd.output();
-
-    match output {
-        Ok(out) => {
-            if out.status.success() {
-                let json_str = String::from_utf8_lossy(&out.stdout);
-                match crate::throughput::iperf::parse_output(&json_str) {
-                    Ok(res) => {
-                        let mbps = res.end.sum_received.bits_per_second / 1_000_000.0;
-                         println!("  -> {}: {:.2} Mbps", dir_str, mbps);
-                    },
-                    Err(e) => println!("  -> Failed to parse JSON: {}", e),
-                }
-            } else {
-                let err = String::from_utf8_lossy(&out.stderr);
-                println!("  -> iperf3 failed: {}", err.trim());
-            }
-        },
-        Err(e) => {
-            println!("  -> Error executing iperf3: {}", e);
-            println!("     (Is 'iperf3' installed? try 'sudo apt install iperf3')");
+    let out = match cmd.output() {
+        Ok(out) => out,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(ThroughputError::Iperf3NotFound { path: "taskset".to_string() }.into());
         }
+        Err(e) => anyhow::bail!("error executing iperf3: {}", e),
+    };
+    // taskset exits 127 when it cannot exec the command it was given.
+    if out.status.code() == Some(TASKSET_EXEC_NOT_FOUND) {
+        return Err(ThroughputError::Iperf3NotFound { path: "iperf3".to_string() }.into());
+    }
+    if !out.status.success() {
+        let err = String::from_utf8_lossy(&out.stderr);
+        anyhow::bail!("iperf3 failed: {}", err.trim());
+    }
+
+    let json_str = String::from_utf8_lossy(&out.stdout);
+    let res = iperf::parse_output(&json_str)
+        .map_err(|e| anyhow::anyhow!("failed to parse iperf3 JSON: {}", e))?;
+    let (jitter_ms, loss_percent) = res.udp_summary();
+    let mut r = ThroughputResult {
+        mode: mode.to_string(),
+        direction: if reverse { "download" } else { "upload" }.to_string(),
+        protocol,
+        throughput_mbps: res.end.sum_received.bits_per_second / 1_000_000.0,
+        omit_secs: tuning.omit_secs.filter(|&o| o > 0),
+        raw_throughput_mbps: res.raw_mbps(),
+        jitter_ms,
+        loss_percent,
+        streams: res.start.test_start.num_streams,
+        streams_mbps: res.streams_mbps(),
+        stream_balance: None,
+        duration_secs: res.start.test_start.duration,
+        link_speed_mbps,
+        bytes_transferred: res.end.sum_sent.bytes.max(res.end.sum_received.bytes),
+        efficiency_pct: None,
+        grade: None,
+        tcp: res.tcp_stats(),
+        buffers: None,
+        engine: "iperf3".to_string(),
+    };
+    report::apply_efficiency(&mut r);
+    r.stream_balance = report::stream_balance(&r.streams_mbps);
+    Ok(r)
+}
+
+/// [`run_iperf_direction`] on the native engine, for hosts without iperf3.
+///
+/// Needs a cooperating peer on [`native::port_from_env`]: a discard service
+/// for upload, a sender for download. UDP-only fields stay `None`.
+async fn run_native_direction(
+    mode: &str,
+    target: &str,
+    duration: u32,
+    streams: u32,
+    reverse: bool,
+    tuning: &iperf::Tuning,
+) -> Result<ThroughputResult> {
+    validate_target(target)?;
+    let link_speed_mbps = link::egress_link_speed_mbps(target);
+    let socket_tuning = native::SocketTuning::from_env(link_speed_mbps)?;
+    let omit_secs = tuning.omit_secs.unwrap_or(0);
+    let res = native::tcp_throughput(
+        target,
+        native::port_from_env()?,
+        streams,
+        u64::from(duration),
+        omit_secs,
+        reverse,
+        &socket_tuning,
+    )
+    .await?;
+    for warning in &res.warnings {
+        println!("     {}", warning);
+    }
+
+    let mut r = ThroughputResult {
+        mode: mode.to_string(),
+        direction: if reverse { "download" } else { "upload" }.to_string(),
+        protocol: iperf::Protocol::Tcp,
+        throughput_mbps: res.throughput_mbps,
+        omit_secs: tuning.omit_secs.filter(|&o| o > 0),
+        raw_throughput_mbps: res.raw_throughput_mbps,
+        jitter_ms: None,
+        loss_percent: None,
+        streams: res.streams_mbps.len() as u32,
+        streams_mbps: res.streams_mbps,
+        stream_balance: None,
+        duration_secs: res.duration_secs,
+        link_speed_mbps,
+        bytes_transferred: res.bytes_transferred,
+        efficiency_pct: None,
+        grade: None,
+        tcp: res.tcp,
+        buffers: res.buffers,
+        engine: "native".to_string(),
+    };
+    report::apply_efficiency(&mut r);
+    r.stream_balance = report::stream_balance(&r.streams_mbps);
+    Ok(r)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::probes::ProbeType;
+
+    fn probe(value: f64, success: bool) -> Measurement {
+        Measurement {
+            probe_type: ProbeType::Tcp,
+            target: String::new(),
+            value,
+            unit: "ms".to_string(),
+            success,
+            timestamp: std::time::SystemTime::now(),
+            dscp: None,
+        }
+    }
+
+    #[test]
+    fn test_lowest_latency_skips_unreachable() {
+        let probes = vec![
+            ("far.example", probe(48.0, true)),
+            ("down.example", probe(-1.0, false)),
+            ("near.example", probe(12.5, true)),
+        ];
+        assert_eq!(lowest_latency(probes), Some("near.example"));
+        assert_eq!(lowest_latency(vec![("down.example", probe(-1.0, false))]), None);
+    }
+
+    #[tokio::test]
+    async fn test_failed_public_server_is_forgotten() {
+        *PUBLIC_SERVER.lock().unwrap() = Some("cached.example".to_string());
+        assert_eq!(find_public_server().await.unwrap(), "cached.example");
+
+        // A failure against some other server leaves the pick alone.
+        forget_public_server("other.example");
+        assert_eq!(PUBLIC_SERVER.lock().unwrap().as_deref(), Some("cached.example"));
+
+        forget_public_server("cached.example");
+        assert!(PUBLIC_SERVER.lock().unwrap().is_none());
     }
-    Ok(())
 }
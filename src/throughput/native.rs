@@ -1,21 +1,539 @@
 //! Native Rust TCP/UDP throughput engine (fallback when iperf3 unavailable).
 //!
 //! Pure safe Rust using tokio::net primitives. No unsafe.
+//!
+//! Socket buffers follow [`SocketTuning`]: Pi 5 defaults by link speed,
+//! overridable with `PP_NATIVE_SNDBUF` / `PP_NATIVE_RCVBUF` /
+//! `PP_NATIVE_NODELAY`. The kernel caps them at `net.core.wmem_max` /
+//! `rmem_max`, so the effective sizes are reported with each result.
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpSocket, TcpStream};
+use tokio::time::Instant;
+
+use super::TcpStats;
+
+/// Port the native engine connects to unless `PP_NATIVE_PORT` is set;
+/// the iperf3 default, so a peer firewall rule covers both engines.
+pub const DEFAULT_PORT: u16 = 5201;
+
+/// Size of each write to (or read from) the socket.
+const WRITE_CHUNK_BYTES: usize = 128 * 1024;
+
+/// Length of one throughput sample.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
 
-use anyhow::Result;
+/// Socket options the native engine applies before connecting.
+///
+/// Buffer sizes use iperf3 notation in the environment (`4M`, `512K`).
+/// `None` leaves the buffer to kernel autotuning, which an explicit
+/// `SO_RCVBUF` switches off for that socket.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SocketTuning {
+    /// `SO_SNDBUF` to request, in bytes.
+    pub send_buffer_bytes: Option<u32>,
+    /// `SO_RCVBUF` to request, in bytes.
+    pub recv_buffer_bytes: Option<u32>,
+    /// `TCP_NODELAY`: send each write at once instead of coalescing.
+    pub nodelay: bool,
+}
+
+impl SocketTuning {
+    /// Recommended buffers for a Pi 5 on a link of the given speed.
+    ///
+    /// Gigabit and below keeps autotuning, which already reaches line rate.
+    /// Multi-gig needs room for the bandwidth-delay product: 10 Gbps over a
+    /// 5 ms LAN path is ~6 MB in flight.
+    pub fn pi5_defaults(link_speed_mbps: Option<u64>) -> Self {
+        let buffer = match link_speed_mbps {
+            Some(mbps) if mbps >= 10_000 => Some(8 << 20),
+            Some(mbps) if mbps > 1_000 => Some(4 << 20),
+            _ => None,
+        };
+        Self {
+            send_buffer_bytes: buffer,
+            recv_buffer_bytes: buffer,
+            nodelay: true,
+        }
+    }
+
+    /// Defaults for `link_speed_mbps`, overridden by `PP_NATIVE_SNDBUF`,
+    /// `PP_NATIVE_RCVBUF` and `PP_NATIVE_NODELAY`.
+    pub fn from_env(link_speed_mbps: Option<u64>) -> Result<Self> {
+        let env_size = |name: &str| -> Result<Option<u32>> {
+            let Ok(raw) = std::env::var(name) else {
+                return Ok(None);
+            };
+            super::iperf::parse_size(raw.trim())
+                .and_then(|n| u32::try_from(n).ok())
+                .map(Some)
+                .ok_or_else(|| anyhow::anyhow!("invalid {} '{}' (expected e.g. 512K, 4M)", name, raw))
+        };
+        let defaults = Self::pi5_defaults(link_speed_mbps);
+        Ok(Self {
+            send_buffer_bytes: env_size("PP_NATIVE_SNDBUF")?.or(defaults.send_buffer_bytes),
+            recv_buffer_bytes: env_size("PP_NATIVE_RCVBUF")?.or(defaults.recv_buffer_bytes),
+            nodelay: std::env::var("PP_NATIVE_NODELAY")
+                .map(|v| !matches!(v.trim().to_ascii_lowercase().as_str(), "0" | "false" | "no" | "off"))
+                .unwrap_or(defaults.nodelay),
+        })
+    }
+}
+
+/// Socket buffers as requested and as the kernel actually granted them.
+///
+/// Linux reports twice the requested size (the extra half is bookkeeping
+/// overhead) and caps requests at `net.core.wmem_max` / `rmem_max`.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SocketBuffers {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requested_send_bytes: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requested_recv_bytes: Option<u32>,
+    pub send_bytes: u32,
+    pub recv_bytes: u32,
+    pub nodelay: bool,
+}
+
+/// Kernel caps on socket buffers an application may request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferLimits {
+    /// `net.core.wmem_max`, in bytes.
+    pub wmem_max: u64,
+    /// `net.core.rmem_max`, in bytes.
+    pub rmem_max: u64,
+}
+
+impl BufferLimits {
+    /// Read the limits from `/proc/sys/net/core`. `None` off Linux or when
+    /// they can't be read.
+    pub fn read() -> Option<Self> {
+        let read = |name: &str| {
+            std::fs::read_to_string(format!("/proc/sys/net/core/{}", name))
+                .ok()?
+                .trim()
+                .parse::<u64>()
+                .ok()
+        };
+        Some(Self {
+            wmem_max: read("wmem_max")?,
+            rmem_max: read("rmem_max")?,
+        })
+    }
+
+    /// One warning per requested buffer the kernel will clamp, with the
+    /// sysctl that lifts the cap.
+    pub fn warnings(&self, tuning: &SocketTuning) -> Vec<String> {
+        [
+            ("net.core.wmem_max", self.wmem_max, tuning.send_buffer_bytes, "send"),
+            ("net.core.rmem_max", self.rmem_max, tuning.recv_buffer_bytes, "receive"),
+        ]
+        .into_iter()
+        .filter_map(|(sysctl, max, requested, side)| {
+            let requested = u64::from(requested?);
+            (max < requested).then(|| {
+                format!(
+                    "{} is {} bytes, below the {}-byte {} buffer requested; the kernel will clamp it \
+                     (raise with: sysctl -w {}={})",
+                    sysctl, max, requested, side, sysctl, requested
+                )
+            })
+        })
+        .collect()
+    }
+}
+
+/// Native engine port: `PP_NATIVE_PORT`, or [`DEFAULT_PORT`].
+pub fn port_from_env() -> Result<u16> {
+    match std::env::var("PP_NATIVE_PORT") {
+        Ok(raw) => raw
+            .trim()
+            .parse()
+            .map_err(|_| anyhow::anyhow!("invalid PP_NATIVE_PORT '{}'", raw)),
+        Err(_) => Ok(DEFAULT_PORT),
+    }
+}
+
+/// Open a TCP connection to `addr` with `tuning` applied.
+///
+/// Buffers are set before connecting so the window scale negotiated in the
+/// handshake can use them. Returns the sizes the kernel actually granted.
+pub async fn connect_tuned(addr: SocketAddr, tuning: &SocketTuning) -> Result<(TcpStream, SocketBuffers)> {
+    let socket = if addr.is_ipv4() {
+        TcpSocket::new_v4()?
+    } else {
+        TcpSocket::new_v6()?
+    };
+    if let Some(bytes) = tuning.send_buffer_bytes {
+        socket.set_send_buffer_size(bytes).context("failed to set SO_SNDBUF")?;
+    }
+    if let Some(bytes) = tuning.recv_buffer_bytes {
+        socket.set_recv_buffer_size(bytes).context("failed to set SO_RCVBUF")?;
+    }
+    let buffers = SocketBuffers {
+        requested_send_bytes: tuning.send_buffer_bytes,
+        requested_recv_bytes: tuning.recv_buffer_bytes,
+        send_bytes: socket.send_buffer_size()?,
+        recv_bytes: socket.recv_buffer_size()?,
+        nodelay: tuning.nodelay,
+    };
+    let stream = socket
+        .connect(addr)
+        .await
+        .with_context(|| format!("failed to connect to {}", addr))?;
+    stream.set_nodelay(tuning.nodelay)?;
+    Ok((stream, buffers))
+}
 
 /// Run a native TCP throughput test to the specified peer.
-pub async fn tcp_throughput(peer: &str, port: u16, duration_secs: u64) -> Result<NativeResult> {
-    // TODO: Open TCP connection, send data for duration, measure throughput
-    tracing::debug!(%peer, %port, %duration_secs, "Native TCP throughput (stub)");
+///
+/// Opens `streams` parallel connections. Upload writes to the peer, which
+/// only has to accept and read (e.g. a discard service); with `reverse`
+/// (download) the peer is expected to send until the connection closes.
+/// A peer that hangs up early or moves no data is an error, not a slow
+/// result: that is what an iperf3 server on the same port looks like.
+/// The first `omit_secs` seconds (TCP slow-start) run in addition to
+/// `duration_secs` and are left out of `throughput_mbps`, like iperf3 `-O`.
+pub async fn tcp_throughput(
+    peer: &str,
+    port: u16,
+    streams: u32,
+    duration_secs: u64,
+    omit_secs: u32,
+    reverse: bool,
+    tuning: &SocketTuning,
+) -> Result<NativeResult> {
+    tracing::debug!(%peer, %port, %streams, %duration_secs, %omit_secs, %reverse, ?tuning, "Native TCP throughput");
+
+    let warnings = BufferLimits::read()
+        .map(|limits| limits.warnings(tuning))
+        .unwrap_or_default();
+    for warning in &warnings {
+        tracing::warn!("{}", warning);
+    }
+
+    let addr = tokio::net::lookup_host((peer, port))
+        .await
+        .with_context(|| format!("failed to resolve {}", peer))?
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("no address for {}", peer))?;
+    let mut connections = Vec::with_capacity(streams.max(1) as usize);
+    for _ in 0..streams.max(1) {
+        connections.push(connect_tuned(addr, tuning).await?);
+    }
+    let buffers = connections[0].1.clone();
+
+    let deadline = Instant::now() + Duration::from_secs(duration_secs + u64::from(omit_secs));
+    let per_stream = futures::future::try_join_all(
+        connections
+            .iter_mut()
+            .map(|(stream, _)| run_stream(stream, deadline, reverse)),
+    )
+    .await?;
+
+    // Sample before the sockets close, while the kernel still tracks them.
+    let tcp = sample_tcp_info(addr).await;
+    for (stream, _) in &mut connections {
+        let _ = stream.shutdown().await;
+    }
+
+    let rates: Vec<(f64, f64)> = per_stream
+        .iter()
+        .map(|samples| omit_adjusted(samples, omit_secs).unwrap_or((0.0, 0.0)))
+        .collect();
+    let bytes_transferred = per_stream.iter().flatten().map(|s| s.bytes).sum();
+    if bytes_transferred == 0 {
+        anyhow::bail!("no data moved to or from {}; is it running a native peer on port {}?", addr, port);
+    }
     Ok(NativeResult {
-        throughput_mbps: 0.0,
+        throughput_mbps: rates.iter().map(|r| r.0).sum(),
+        raw_throughput_mbps: (omit_secs > 0).then(|| rates.iter().map(|r| r.1).sum()),
+        streams_mbps: rates.iter().map(|r| r.0).collect(),
+        bytes_transferred,
         duration_secs: duration_secs as f64,
+        tcp,
+        buffers: Some(buffers),
+        warnings,
     })
 }
 
+/// Move data on one connection until `deadline`, sampled every
+/// [`SAMPLE_INTERVAL`]. Writes for upload, reads with `reverse`.
+async fn run_stream(stream: &mut TcpStream, deadline: Instant, reverse: bool) -> Result<Vec<IntervalSample>> {
+    let mut buf = vec![0u8; WRITE_CHUNK_BYTES];
+    let mut samples = Vec::new();
+    let mut interval_start = Instant::now();
+    let mut interval_bytes = 0u64;
+    loop {
+        let io = async {
+            if reverse {
+                stream.read(&mut buf).await
+            } else {
+                stream.write(&buf).await
+            }
+        };
+        match tokio::time::timeout_at(deadline, io).await {
+            Ok(Ok(0)) if reverse => anyhow::bail!(
+                "peer closed the connection with {:.1}s of the test left; \
+                 it must keep sending until we close",
+                deadline.saturating_duration_since(Instant::now()).as_secs_f64()
+            ),
+            Ok(moved) => interval_bytes += moved.context("native throughput I/O failed")? as u64,
+            Err(_) => break,
+        }
+        if interval_start.elapsed() >= SAMPLE_INTERVAL {
+            samples.push(IntervalSample {
+                seconds: interval_start.elapsed().as_secs_f64(),
+                bytes: interval_bytes,
+            });
+            interval_start = Instant::now();
+            interval_bytes = 0;
+        }
+    }
+    samples.push(IntervalSample {
+        seconds: interval_start.elapsed().as_secs_f64(),
+        bytes: interval_bytes,
+    });
+    Ok(samples)
+}
+
 #[derive(Debug, serde::Serialize)]
 pub struct NativeResult {
+    /// Steady-state throughput, warm-up excluded.
     pub throughput_mbps: f64,
+    /// Throughput including the warm-up, when one was omitted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw_throughput_mbps: Option<f64>,
+    /// Steady-state throughput of each connection.
+    pub streams_mbps: Vec<f64>,
+    /// Bytes moved across all connections, warm-up included.
+    pub bytes_transferred: u64,
     pub duration_secs: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tcp: Option<TcpStats>,
+    /// Effective socket buffers; compare with the requested sizes to spot
+    /// a kernel clamp.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub buffers: Option<SocketBuffers>,
+    /// Host configuration that will hold the test back, e.g. a low
+    /// `net.core.rmem_max`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// Bytes moved in one sampling interval of a native test.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IntervalSample {
+    pub seconds: f64,
+    pub bytes: u64,
+}
+
+/// Throughput in Mbps over `samples` after dropping the first `omit_secs`
+/// of them, and over all of them: `(steady_state, raw)`.
+///
+/// An interval straddling the cut-off counts as warm-up. Returns `None`
+/// when nothing is left after the warm-up.
+pub fn omit_adjusted(samples: &[IntervalSample], omit_secs: u32) -> Option<(f64, f64)> {
+    let mbps = |samples: &[IntervalSample]| {
+        let seconds: f64 = samples.iter().map(|s| s.seconds).sum();
+        let bytes: u64 = samples.iter().map(|s| s.bytes).sum();
+        (seconds > 0.0).then(|| bytes as f64 * 8.0 / seconds / 1_000_000.0)
+    };
+    let mut elapsed = 0.0;
+    let steady_from = samples
+        .iter()
+        .position(|s| {
+            let starts_at = elapsed;
+            elapsed += s.seconds;
+            starts_at >= omit_secs as f64
+        })
+        .unwrap_or(samples.len());
+    Some((mbps(&samples[steady_from..])?, mbps(samples)?))
+}
+
+/// Sample the kernel's `TCP_INFO` for connections to `peer`.
+///
+/// Reads it through `ss -tin` rather than `getsockopt` so this module stays
+/// free of unsafe. Call before closing the sockets: retransmits are summed,
+/// RTT averaged and cwnd maxed across all matching connections. Linux only;
+/// `None` elsewhere or when no connection matches.
+pub async fn sample_tcp_info(peer: SocketAddr) -> Option<TcpStats> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    let out = tokio::process::Command::new("ss")
+        .args(["-tinH", "dst", &peer.to_string()])
+        .output()
+        .await
+        .ok()?;
+    if !out.status.success() {
+        return None;
+    }
+    parse_ss_info(&String::from_utf8_lossy(&out.stdout))
+}
+
+/// Parse the `-i` detail lines of `ss -tin` output.
+fn parse_ss_info(output: &str) -> Option<TcpStats> {
+    let mut retransmits = 0u64;
+    let mut rtts = Vec::new();
+    let mut cwnd_bytes: Option<u64> = None;
+
+    for line in output.lines().filter(|l| l.contains("rtt:")) {
+        let mut mss = None;
+        let mut cwnd = None;
+        for (key, value) in line.split_whitespace().filter_map(|t| t.split_once(':')) {
+            match key {
+                // "rtt:<avg>/<var>" in milliseconds.
+                "rtt" => {
+                    if let Some(avg) = value.split('/').next().and_then(|v| v.parse::<f64>().ok()) {
+                        rtts.push(avg);
+                    }
+                }
+                "mss" => mss = value.parse::<u64>().ok(),
+                // Congestion window in segments.
+                "cwnd" => cwnd = value.parse::<u64>().ok(),
+                // "retrans:<unacked>/<total>"
+                "retrans" => {
+                    if let Some(total) = value.split('/').nth(1).and_then(|v| v.parse::<u64>().ok()) {
+                        retransmits += total;
+                    }
+                }
+                _ => {}
+            }
+        }
+        if let (Some(mss), Some(cwnd)) = (mss, cwnd) {
+            cwnd_bytes = cwnd_bytes.max(Some(mss * cwnd));
+        }
+    }
+
+    if rtts.is_empty() {
+        return None;
+    }
+    Some(TcpStats {
+        retransmits: Some(retransmits),
+        rtt_ms: Some(rtts.iter().sum::<f64>() / rtts.len() as f64),
+        cwnd_bytes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ss_info() {
+        let output = "\
+ESTAB 0 0 10.0.0.5:50412 10.0.0.2:5201
+\t cubic wscale:7,7 rto:204 rtt:12.5/0.8 ato:40 mss:1448 pmtu:1500 rcvmss:536 advmss:1448 cwnd:362 bytes_sent:1175000000 bytes_retrans:1743392 retrans:0/1204 segs_out:811464
+ESTAB 0 0 10.0.0.5:50414 10.0.0.2:5201
+\t cubic wscale:7,7 rto:204 rtt:13.5/1.1 ato:40 mss:1448 pmtu:1500 cwnd:724 retrans:1/96
+";
+        let tcp = parse_ss_info(output).unwrap();
+        assert_eq!(tcp.retransmits, Some(1300));
+        assert_eq!(tcp.rtt_ms, Some(13.0));
+        assert_eq!(tcp.cwnd_bytes, Some(724 * 1448));
+    }
+
+    #[test]
+    fn test_omit_adjusted() {
+        let sample = |mb: u64| IntervalSample { seconds: 1.0, bytes: mb * 125_000 };
+        // Slow-start: 100 then 500 Mbps, then steady 900 Mbps.
+        let samples = [sample(100), sample(500), sample(900), sample(900)];
+        let (steady, raw) = omit_adjusted(&samples, 2).unwrap();
+        assert!((steady - 900.0).abs() < 1e-9);
+        assert!((raw - 600.0).abs() < 1e-9);
+
+        let (steady, raw) = omit_adjusted(&samples, 0).unwrap();
+        assert_eq!(steady, raw);
+        assert!(omit_adjusted(&samples, 4).is_none());
+    }
+
+    #[test]
+    fn test_socket_tuning_pi5_defaults() {
+        let gigabit = SocketTuning::pi5_defaults(Some(1000));
+        assert_eq!(gigabit.send_buffer_bytes, None);
+        assert!(gigabit.nodelay);
+        assert_eq!(SocketTuning::pi5_defaults(Some(2500)).recv_buffer_bytes, Some(4 << 20));
+        assert_eq!(SocketTuning::pi5_defaults(Some(10_000)).send_buffer_bytes, Some(8 << 20));
+    }
+
+    #[test]
+    fn test_buffer_limit_warnings() {
+        let tuning = SocketTuning::pi5_defaults(Some(10_000));
+        // Stock Raspberry Pi OS: 208 KB caps.
+        let stock = BufferLimits { wmem_max: 212_992, rmem_max: 212_992 };
+        let warnings = stock.warnings(&tuning);
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[1].contains("net.core.rmem_max is 212992 bytes"));
+        assert!(warnings[1].contains("sysctl -w net.core.rmem_max=8388608"));
+
+        let raised = BufferLimits { wmem_max: 16 << 20, rmem_max: 16 << 20 };
+        assert!(raised.warnings(&tuning).is_empty());
+        assert!(stock.warnings(&SocketTuning::pi5_defaults(None)).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_connect_tuned_reports_effective_buffers() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let tuning = SocketTuning {
+            send_buffer_bytes: Some(64 * 1024),
+            recv_buffer_bytes: Some(64 * 1024),
+            nodelay: true,
+        };
+        let (stream, buffers) = connect_tuned(addr, &tuning).await.unwrap();
+        assert!(stream.nodelay().unwrap());
+        assert_eq!(buffers.requested_send_bytes, Some(64 * 1024));
+        // Linux doubles the request; other kernels report it as set.
+        assert!(buffers.send_bytes >= 64 * 1024);
+        assert!(buffers.recv_bytes >= 64 * 1024);
+    }
+
+    #[tokio::test]
+    async fn test_tcp_throughput_parallel_upload() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        // Discard service: read and drop everything on each connection.
+        tokio::spawn(async move {
+            while let Ok((mut conn, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut buf = vec![0u8; 64 * 1024];
+                    while matches!(conn.read(&mut buf).await, Ok(n) if n > 0) {}
+                });
+            }
+        });
+
+        let tuning = SocketTuning::pi5_defaults(None);
+        let result = tcp_throughput("127.0.0.1", port, 2, 1, 0, false, &tuning).await.unwrap();
+        assert_eq!(result.streams_mbps.len(), 2);
+        assert!(result.throughput_mbps > 0.0);
+        assert!(result.bytes_transferred > 0);
+        assert!(result.raw_throughput_mbps.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_tcp_throughput_download_fails_on_early_close() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        // Not a source: hang up without sending, as an iperf3 server would.
+        tokio::spawn(async move {
+            while let Ok((conn, _)) = listener.accept().await {
+                drop(conn);
+            }
+        });
+
+        let tuning = SocketTuning::pi5_defaults(None);
+        let err = tcp_throughput("127.0.0.1", port, 1, 1, 0, true, &tuning).await.unwrap_err();
+        assert!(err.to_string().contains("closed the connection"), "{:#}", err);
+    }
+
+    #[test]
+    fn test_parse_ss_info_no_connections() {
+        assert!(parse_ss_info("").is_none());
+        assert!(parse_ss_info("ESTAB 0 0 10.0.0.5:50412 10.0.0.2:5201\n").is_none());
+    }
 }
@@ -1,4 +1,4 @@
-use super::{SpeedTestProvider, ProviderMeta, SpeedTestRequest, SpeedTestResult, ProviderKind, Stability, MetricsSupported, Recommendation};
+use super::{run_with_timeout, SpeedTestProvider, ProviderMeta, SpeedTestRequest, SpeedTestResult, ProviderKind, Stability, MetricsSupported, Recommendation};
 use anyhow::Result;
 use std::process::Command;
 
@@ -69,7 +69,7 @@ impl SpeedTestProvider for FastProvider {
         Self::detect_variant().is_some()
     }
 
-    async fn run(&self, _req: SpeedTestRequest) -> Result<SpeedTestResult> {
+    async fn run(&self, req: SpeedTestRequest) -> Result<SpeedTestResult> {
         let variant = Self::detect_variant().ok_or_else(|| anyhow::anyhow!("Fast CLI not found"))?;
 
         // Note: detect_variant() uses synchronous Command, which is fine for small checks.
@@ -78,13 +78,16 @@ impl SpeedTestProvider for FastProvider {
         match variant {
             FastVariant::Node(exe) => {
                 // Node Logic (JSON)
-                let output = tokio::process::Command::new(exe)
-                    .arg("--json")
-                    .arg("--upload")
-                    .output()
-                    .await?;
+                // Prints a single JSON object at the end, so a timeout leaves
+                // nothing to salvage.
+                let mut cmd = tokio::process::Command::new(exe);
+                cmd.arg("--json").arg("--upload");
+                let output = run_with_timeout(cmd, req.timeout).await?;
+                if output.timed_out {
+                    anyhow::bail!("Fast CLI (Node) timed out after {:?}", req.timeout);
+                }
         
-                if !output.status.success() {
+                if !output.success {
                      return Err(anyhow::anyhow!("Fast CLI (Node) failed: {}", String::from_utf8_lossy(&output.stderr)));
                 }
         
@@ -108,20 +111,24 @@ impl SpeedTestProvider for FastProvider {
                     jitter_ms: None,
                     packet_loss_pct: None,
                     bufferbloat_ms: bufferbloat,
+                    server: None,
                     raw_json: Some(json),
                     timestamp: chrono::Utc::now(),
+                    truncated: false,
                 })
             },
             FastVariant::Go(exe) => {
                 // Go Logic (Text)
                 // fast-cli --simple
                 // Output: "85.2 Mbps\n"
-                let output = tokio::process::Command::new(exe)
-                    .arg("--simple")
-                    .output()
-                    .await?;
+                let mut cmd = tokio::process::Command::new(exe);
+                cmd.arg("--simple");
+                let output = run_with_timeout(cmd, req.timeout).await?;
+                if output.timed_out {
+                    anyhow::bail!("Fast CLI (Go) timed out after {:?}", req.timeout);
+                }
                 
-                if !output.status.success() {
+                if !output.success {
                      return Err(anyhow::anyhow!("Fast CLI (Go) failed: {}", String::from_utf8_lossy(&output.stderr)));
                 }
                 
@@ -141,8 +148,10 @@ impl SpeedTestProvider for FastProvider {
                     jitter_ms: None,
                     packet_loss_pct: None,
                     bufferbloat_ms: None,
+                    server: None,
                     raw_json: Some(serde_json::json!({ "raw_output": s })),
                     timestamp: chrono::Utc::now(),
+                    truncated: false,
                 })
             }
         }
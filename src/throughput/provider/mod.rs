@@ -1,11 +1,16 @@
+use std::sync::Arc;
 use std::time::Duration;
 use serde::{Deserialize, Serialize};
 use anyhow::Result;
 
+use crate::storage::Pool;
+use crate::throughput::ThroughputResult;
+
 pub mod ookla;
 pub mod ndt7;
 pub mod fast;
 pub mod reflector;
+pub mod web;
 
 /// Metadata describing a speed test provider.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -75,8 +80,192 @@ pub struct SpeedTestResult {
     pub jitter_ms: Option<f64>,
     pub packet_loss_pct: Option<f64>,
     pub bufferbloat_ms: Option<f64>,
+    /// Server actually tested against (provider-specific name / id / host).
+    #[serde(default)]
+    pub server: Option<String>,
     pub raw_json: Option<serde_json::Value>, // keep provider-native detail
     pub timestamp: chrono::DateTime<chrono::Utc>,
+    /// The run hit `SpeedTestRequest::timeout`; only phases that finished
+    /// before it are filled in.
+    #[serde(default)]
+    pub truncated: bool,
+}
+
+/// `probe_results.probe_type` for normalized speed test rows.
+pub const SPEED_PROBE_TYPE: &str = "speed";
+
+impl SpeedTestResult {
+    /// Normalize one iperf3/native run (one result per direction) into a
+    /// single record. `None` if no direction completed.
+    pub fn from_throughput(results: Vec<ThroughputResult>) -> Option<Self> {
+        results
+            .into_iter()
+            .map(SpeedTestResult::from)
+            .reduce(SpeedTestResult::merge)
+    }
+
+    /// Combine two partial results of the same run (e.g. its upload and
+    /// download): the first value set for each metric wins and the
+    /// per-direction detail in `raw_json` is concatenated.
+    fn merge(mut self, other: SpeedTestResult) -> Self {
+        self.download_mbps = self.download_mbps.or(other.download_mbps);
+        self.upload_mbps = self.upload_mbps.or(other.upload_mbps);
+        self.latency_ms = self.latency_ms.or(other.latency_ms);
+        self.jitter_ms = self.jitter_ms.or(other.jitter_ms);
+        self.packet_loss_pct = self.packet_loss_pct.or(other.packet_loss_pct);
+        self.bufferbloat_ms = self.bufferbloat_ms.or(other.bufferbloat_ms);
+        self.server = self.server.or(other.server);
+        self.truncated |= other.truncated;
+        self.timestamp = self.timestamp.min(other.timestamp);
+
+        let directions = |r: Option<serde_json::Value>| match r {
+            Some(serde_json::Value::Object(mut m)) => match m.remove("directions") {
+                Some(serde_json::Value::Array(d)) => d,
+                _ => Vec::new(),
+            },
+            _ => Vec::new(),
+        };
+        let mut merged = directions(self.raw_json.take());
+        merged.extend(directions(other.raw_json));
+        self.raw_json = Some(serde_json::json!({ "directions": merged }));
+        self
+    }
+
+    /// Record the SoC's thermal state during the run under
+    /// `raw_json.thermal`, so a result skewed by throttling or under-voltage
+    /// stays recognisable after the fact.
+    pub fn with_thermal(mut self, window: &crate::system::thermal::ThermalWindow) -> Self {
+        let mut thermal = serde_json::to_value(window).unwrap_or_default();
+        if let (Some(obj), Some(note)) = (thermal.as_object_mut(), window.annotation()) {
+            obj.insert("annotation".into(), note.into());
+        }
+        match &mut self.raw_json {
+            Some(serde_json::Value::Object(m)) => {
+                m.insert("thermal".into(), thermal);
+            }
+            _ => self.raw_json = Some(serde_json::json!({ "thermal": thermal })),
+        }
+        self
+    }
+
+    /// Persist to `probe_results` as a `speed` row, keyed by provider, the
+    /// table every speed test (provider, iperf3, native, reflector) shares.
+    pub fn save(&self, pool: &Pool) -> Result<()> {
+        let conn = pool.get()?;
+        conn.execute(
+            "INSERT INTO probe_results (probe_type, target, result_json) VALUES (?1, ?2, ?3)",
+            rusqlite::params![SPEED_PROBE_TYPE, self.provider_id, serde_json::to_string(self)?],
+        )?;
+        Ok(())
+    }
+}
+
+/// One direction of an iperf3/native test. `provider_id` is the engine
+/// (`iperf3` or `native`); mode, direction, streams and TCP stats are kept
+/// under `raw_json.directions`.
+impl From<ThroughputResult> for SpeedTestResult {
+    fn from(r: ThroughputResult) -> Self {
+        let (download_mbps, upload_mbps) = match r.direction.as_str() {
+            "download" => (Some(r.throughput_mbps), None),
+            _ => (None, Some(r.throughput_mbps)),
+        };
+        SpeedTestResult {
+            provider_id: r.engine.clone(),
+            download_mbps,
+            upload_mbps,
+            latency_ms: None,
+            jitter_ms: r.jitter_ms,
+            packet_loss_pct: r.loss_percent,
+            bufferbloat_ms: None,
+            server: None,
+            raw_json: Some(serde_json::json!({ "directions": [r] })),
+            timestamp: chrono::Utc::now(),
+            truncated: false,
+        }
+    }
+}
+
+/// Timeout for a full provider run (latency + download + upload).
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Output of a provider CLI run under a deadline.
+#[derive(Debug)]
+pub struct CliOutput {
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    /// Exited with status 0 (always false when timed out).
+    pub success: bool,
+    /// Killed at the deadline; `stdout` holds what was printed until then.
+    pub timed_out: bool,
+}
+
+/// How long to keep draining stdout/stderr once the child has exited or
+/// been killed. A grandchild that inherited the pipes can hold them open
+/// indefinitely; past this, whatever was read so far is returned.
+const PIPE_DRAIN_GRACE: Duration = Duration::from_secs(2);
+
+/// Read `pipe` to EOF in the background, into a buffer that stays readable
+/// if the reader is abandoned.
+fn drain<R>(pipe: Option<R>) -> (Arc<std::sync::Mutex<Vec<u8>>>, tokio::task::JoinHandle<()>)
+where
+    R: tokio::io::AsyncRead + Unpin + Send + 'static,
+{
+    use tokio::io::AsyncReadExt;
+
+    let buf = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let sink = buf.clone();
+    let task = tokio::spawn(async move {
+        let Some(mut pipe) = pipe else { return };
+        let mut chunk = [0u8; 8192];
+        while let Ok(n) = pipe.read(&mut chunk).await {
+            if n == 0 {
+                break;
+            }
+            sink.lock().unwrap().extend_from_slice(&chunk[..n]);
+        }
+    });
+    (buf, task)
+}
+
+/// Wait up to [`PIPE_DRAIN_GRACE`] for a [`drain`] task, then take its buffer.
+async fn drained(
+    (buf, mut task): (Arc<std::sync::Mutex<Vec<u8>>>, tokio::task::JoinHandle<()>),
+) -> Vec<u8> {
+    if tokio::time::timeout(PIPE_DRAIN_GRACE, &mut task).await.is_err() {
+        tracing::warn!("Provider output pipe still open after exit; giving up on it");
+        task.abort();
+    }
+    std::mem::take(&mut *buf.lock().unwrap())
+}
+
+/// Run a provider CLI, killing it if it is still running after `timeout`.
+/// Unlike wrapping `output()` in a timeout, whatever the child printed before
+/// the deadline is kept, so streaming providers can report finished phases.
+pub async fn run_with_timeout(mut cmd: tokio::process::Command, timeout: Duration) -> Result<CliOutput> {
+    let mut child = cmd
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+
+    let (success, timed_out) = match tokio::time::timeout(timeout, child.wait()).await {
+        Ok(status) => (status?.success(), false),
+        Err(_) => {
+            tracing::warn!(?timeout, "Provider timed out, killing it");
+            child.kill().await?;
+            (false, true)
+        }
+    };
+
+    Ok(CliOutput {
+        stdout: drained(stdout).await,
+        stderr: drained(stderr).await,
+        success,
+        timed_out,
+    })
 }
 
 /// Trait for all speed test providers (Ookla, NDT7, Fast, iPerf3, Reflector).
@@ -98,5 +287,122 @@ pub fn get_all_providers() -> Vec<Box<dyn SpeedTestProvider>> {
         Box::new(ookla::OoklaProvider),
         Box::new(ndt7::Ndt7Provider),
         Box::new(fast::FastProvider),
+        Box::new(web::WebProvider),
     ]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_run_with_timeout_keeps_partial_output() {
+        let mut cmd = tokio::process::Command::new("sh");
+        cmd.args(["-c", "echo first; exec sleep 5"]);
+        let started = std::time::Instant::now();
+        let out = run_with_timeout(cmd, Duration::from_millis(300)).await.unwrap();
+        assert!(out.timed_out);
+        assert!(!out.success);
+        assert_eq!(String::from_utf8_lossy(&out.stdout), "first\n");
+        assert!(started.elapsed() < Duration::from_secs(4));
+
+        let mut cmd = tokio::process::Command::new("sh");
+        cmd.args(["-c", "echo done"]);
+        let out = run_with_timeout(cmd, Duration::from_secs(5)).await.unwrap();
+        assert!(out.success && !out.timed_out);
+    }
+
+    #[tokio::test]
+    async fn test_run_with_timeout_stops_waiting_on_inherited_pipes() {
+        // The background sleep keeps stdout open after the killed shell.
+        let mut cmd = tokio::process::Command::new("sh");
+        cmd.args(["-c", "echo first; sleep 30 & wait"]);
+        let started = std::time::Instant::now();
+        let out = run_with_timeout(cmd, Duration::from_millis(300)).await.unwrap();
+        assert!(out.timed_out);
+        assert_eq!(String::from_utf8_lossy(&out.stdout), "first\n");
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    fn throughput(direction: &str, mbps: f64) -> ThroughputResult {
+        ThroughputResult {
+            mode: "lan".into(),
+            direction: direction.into(),
+            protocol: crate::throughput::iperf::Protocol::Tcp,
+            throughput_mbps: mbps,
+            omit_secs: None,
+            raw_throughput_mbps: None,
+            jitter_ms: None,
+            loss_percent: None,
+            streams: 4,
+            streams_mbps: Vec::new(),
+            stream_balance: None,
+            duration_secs: 10.0,
+            link_speed_mbps: Some(1000),
+            bytes_transferred: 1_000_000,
+            efficiency_pct: None,
+            grade: None,
+            tcp: None,
+            buffers: None,
+            engine: "iperf3".into(),
+        }
+    }
+
+    #[test]
+    fn test_with_thermal_keeps_annotation() {
+        let window = crate::system::thermal::ThermalWindow {
+            samples: 2,
+            max_temp_c: Some(84.5),
+            throttled: true,
+            under_voltage: false,
+        };
+        let r = SpeedTestResult::from_throughput(vec![throughput("download", 900.0)])
+            .unwrap()
+            .with_thermal(&window);
+        let raw = r.raw_json.unwrap();
+        assert_eq!(raw["directions"].as_array().unwrap().len(), 1);
+        assert_eq!(raw["thermal"]["throttled"], true);
+        assert_eq!(raw["thermal"]["annotation"], "throttled at 84.5°C during test");
+    }
+
+    #[test]
+    fn test_from_throughput_merges_directions() {
+        let r = SpeedTestResult::from_throughput(vec![
+            throughput("upload", 400.0),
+            throughput("download", 900.0),
+        ])
+        .unwrap();
+        assert_eq!(r.provider_id, "iperf3");
+        assert_eq!(r.upload_mbps, Some(400.0));
+        assert_eq!(r.download_mbps, Some(900.0));
+
+        let directions = r.raw_json.unwrap()["directions"].as_array().unwrap().clone();
+        assert_eq!(directions.len(), 2);
+        assert_eq!(directions[1]["direction"], "download");
+        assert_eq!(directions[1]["streams"], 4);
+        assert_eq!(directions[1]["engine"], "iperf3");
+
+        assert!(SpeedTestResult::from_throughput(vec![]).is_none());
+    }
+
+    #[test]
+    fn test_save_writes_speed_row() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = crate::storage::open_pool(dir.path().join("t.db").to_str().unwrap()).unwrap();
+        let r = SpeedTestResult::from(throughput("download", 900.0));
+        r.save(&pool).unwrap();
+
+        let (target, json): (String, String) = pool
+            .get()
+            .unwrap()
+            .query_row(
+                "SELECT target, result_json FROM probe_results WHERE probe_type = 'speed'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(target, "iperf3");
+        let back: SpeedTestResult = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.download_mbps, Some(900.0));
+    }
+}
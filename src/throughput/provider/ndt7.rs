@@ -1,4 +1,4 @@
-use super::{SpeedTestProvider, ProviderMeta, SpeedTestRequest, SpeedTestResult, ProviderKind, Stability, MetricsSupported, Recommendation};
+use super::{run_with_timeout, SpeedTestProvider, ProviderMeta, SpeedTestRequest, SpeedTestResult, ProviderKind, Stability, MetricsSupported, Recommendation};
 use anyhow::Result;
 use std::process::Command;
 
@@ -47,78 +47,236 @@ impl SpeedTestProvider for Ndt7Provider {
         Self::find_executable().is_some()
     }
 
-    async fn run(&self, _req: SpeedTestRequest) -> Result<SpeedTestResult> {
+    async fn run(&self, req: SpeedTestRequest) -> Result<SpeedTestResult> {
         let exe = Self::find_executable().ok_or_else(|| anyhow::anyhow!("NDT7 Client not found"))?;
-        
-        // Run: ndt7-client -format=json
-        let output = tokio::process::Command::new(exe)
-            .arg("-format=json")
-            .output()
-            .await?;
-            
-        if !output.status.success() {
+
+        // Run: ndt7-client -format=json [-server=<fqdn>]
+        let mut cmd = tokio::process::Command::new(exe);
+        cmd.arg("-format=json");
+        if let Some(hint) = req.server_hint.as_deref() {
+            cmd.arg(format!("-server={}", server_for_hint(hint)));
+        }
+        let output = run_with_timeout(cmd, req.timeout).await?;
+
+        if !output.success && !output.timed_out {
              return Err(anyhow::anyhow!("NDT7 Client failed: {}", String::from_utf8_lossy(&output.stderr)));
         }
 
-        // Output allows for streaming JSON objects (one per measurement interval)
-        // We need to aggregate them or pick the final summary if available.
-        // The go client outputs multiple JSON lines. The last one usually contains the summary or we average.
-        // For simpler MVP, let's parse the output as newline-delimited JSON and take the max/average.
-        
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let mut download_accum = 0.0;
-        let mut upload_accum = 0.0;
-        let mut count = 0;
-        let mut max_rtt = 0.0;
-        
-        for line in stdout.lines() {
-            if let Ok(json) = serde_json::from_str::<serde_json::Value>(line) {
-                // Check Test type ("download" or "upload")
-                let test_type = json.get("Value").and_then(|v| v.get("Test")).and_then(|s| s.as_str());
-                
-                // Parse Throughput from AppInfo (NumBytes / ElapsedTime)
-                if let Some(app_info) = json.get("Value").and_then(|v| v.get("AppInfo")) {
-                    let bytes = app_info.get("NumBytes").and_then(|n| n.as_f64()).unwrap_or(0.0);
-                    let elapsed_us = app_info.get("ElapsedTime").and_then(|n| n.as_f64()).unwrap_or(1.0); // avoid div by zero
-                    
-                    if bytes > 0.0 && elapsed_us > 0.0 {
-                        let mbps = (bytes * 8.0) / (elapsed_us / 1_000_000.0) / 1_000_000.0;
-                        match test_type {
-                            Some("download") => download_accum = mbps, // Keep updating, last one is final
-                            Some("upload") => upload_accum = mbps,
-                            _ => {}
-                        }
-                    }
-                }
-                
-                // Parse RTT from TCPInfo (MinRTT)
-                // NDT writes TCPInfo: { "MinRTT": ... } in some messages
-                if let Some(tcp_info) = json.get("Value").and_then(|v| v.get("TCPInfo")) {
-                     if let Some(rtt) = tcp_info.get("MinRTT").and_then(|n| n.as_f64()) {
-                         // rtt is usually microseconds or milliseconds?
-                         // Linux kernel uses microseconds usually? Or verify unit.
-                         // Usually NDT reports microseconds.
-                         let rtt_ms = rtt / 1000.0;
-                         if rtt_ms > 0.0 {
-                             max_rtt = rtt_ms; // Just capture latest capable RTT
-                         }
-                     }
-                }
-                 count += 1;
+        let result = parse_output(&String::from_utf8_lossy(&output.stdout), output.timed_out);
+        if result.download_mbps.is_none() && result.upload_mbps.is_none() {
+            anyhow::bail!("NDT7 Client timed out before completing a subtest");
+        }
+        Ok(result)
+    }
+}
+
+/// Expand an M-Lab site code (e.g. `lga03`) to its first server's FQDN;
+/// anything else is taken as a host name as-is.
+fn server_for_hint(hint: &str) -> String {
+    let hint = hint.trim();
+    let is_site = hint.len() == 5
+        && hint[..3].chars().all(|c| c.is_ascii_lowercase())
+        && hint[3..].chars().all(|c| c.is_ascii_digit());
+    if is_site {
+        format!("ndt-mlab1-{}.mlab-oti.measurement-lab.org", hint)
+    } else {
+        hint.to_string()
+    }
+}
+
+/// Last server-side measurement of one subtest.
+#[derive(Default)]
+struct Subtest {
+    /// A `complete` event was seen.
+    complete: bool,
+    mbps: Option<f64>,
+    tcp_info: Option<serde_json::Value>,
+    bbr_info: Option<serde_json::Value>,
+}
+
+fn value_unit(v: &serde_json::Value, key: &str) -> Option<f64> {
+    v.get(key).and_then(|p| p.get("Value")).and_then(|n| n.as_f64())
+}
+
+/// Parse `ndt7-client -format=json` output.
+///
+/// The client prints one JSON object per line: `starting` / `connected` /
+/// `measurement` / `complete` events (`{"Key": ..., "Value": {...}}`) and,
+/// last, a summary with the server and per-subtest throughput, latency and
+/// retransmission. The summary wins when present; otherwise throughput comes
+/// from the last measurement. A run that never reached the upload subtest
+/// leaves `upload_mbps` empty rather than reporting 0. When `truncated`
+/// (killed at the timeout), a subtest without a `complete` event is dropped
+/// instead of reporting its partial throughput.
+fn parse_output(stdout: &str, truncated: bool) -> SpeedTestResult {
+    let mut download = Subtest::default();
+    let mut upload = Subtest::default();
+    let mut summary = None;
+    let mut lines = 0;
+
+    for line in stdout.lines() {
+        let Ok(json) = serde_json::from_str::<serde_json::Value>(line) else {
+            continue;
+        };
+        lines += 1;
+
+        if json.get("ServerFQDN").is_some() {
+            summary = Some(json);
+            continue;
+        }
+        let key = json.get("Key").and_then(|k| k.as_str());
+        let Some(value) = json.get("Value") else {
+            continue;
+        };
+        let subtest = match value.get("Test").and_then(|s| s.as_str()) {
+            Some("download") => &mut download,
+            Some("upload") => &mut upload,
+            _ => continue,
+        };
+        match key {
+            Some("measurement") => {}
+            Some("complete") => {
+                subtest.complete = true;
+                continue;
+            }
+            _ => continue,
+        }
+
+        // Throughput from AppInfo (NumBytes / ElapsedTime in us); the last
+        // sample covers the whole subtest.
+        if let Some(app_info) = value.get("AppInfo") {
+            let bytes = app_info.get("NumBytes").and_then(|n| n.as_f64()).unwrap_or(0.0);
+            let elapsed_us = app_info.get("ElapsedTime").and_then(|n| n.as_f64()).unwrap_or(0.0);
+            if bytes > 0.0 && elapsed_us > 0.0 {
+                subtest.mbps = Some((bytes * 8.0) / elapsed_us);
+            }
+        }
+        // Kernel TCP_INFO / BBR state as seen by the server.
+        if let Some(tcp) = value.get("TCPInfo") {
+            subtest.tcp_info = Some(tcp.clone());
+        }
+        if let Some(bbr) = value.get("BBRInfo") {
+            subtest.bbr_info = Some(bbr.clone());
+        }
+    }
+
+    if truncated {
+        for subtest in [&mut download, &mut upload] {
+            if !subtest.complete {
+                subtest.mbps = None;
             }
         }
-        
-        // Stub Result
-        Ok(SpeedTestResult {
-            provider_id: "ndt7".to_string(),
-            download_mbps: Some(download_accum), 
-            upload_mbps: Some(upload_accum),
-            latency_ms: if max_rtt > 0.0 { Some(max_rtt) } else { None }, 
-            jitter_ms: None,
-            packet_loss_pct: None,
-            bufferbloat_ms: None,
-            raw_json: Some(serde_json::json!({ "raw_output_lines": count })),
-            timestamp: chrono::Utc::now(),
+    }
+
+    let sum_dl = summary.as_ref().and_then(|s| s.get("Download")).filter(|v| !v.is_null());
+    let sum_ul = summary.as_ref().and_then(|s| s.get("Upload")).filter(|v| !v.is_null());
+
+    let download_mbps = sum_dl.and_then(|d| value_unit(d, "Throughput")).or(download.mbps);
+    let upload_mbps = sum_ul.and_then(|u| value_unit(u, "Throughput")).or(upload.mbps);
+
+    // TCP_INFO times are in microseconds.
+    let tcp_ms = |key: &str| {
+        download
+            .tcp_info
+            .as_ref()
+            .and_then(|t| t.get(key))
+            .and_then(|n| n.as_f64())
+            .filter(|us| *us > 0.0)
+            .map(|us| us / 1000.0)
+    };
+    let latency_ms = sum_dl.and_then(|d| value_unit(d, "Latency")).or_else(|| tcp_ms("MinRTT"));
+    let jitter_ms = tcp_ms("RTTVar");
+    let packet_loss_pct = sum_dl.and_then(|d| value_unit(d, "Retransmission"));
+
+    let subtest_json = |s: &Subtest| {
+        serde_json::json!({
+            "mbps": s.mbps,
+            "tcp_info": s.tcp_info,
+            "bbr_info": s.bbr_info,
         })
+    };
+    let server = summary
+        .as_ref()
+        .and_then(|s| s.get("ServerFQDN"))
+        .and_then(|v| v.as_str())
+        .map(str::to_string);
+    let raw_json = serde_json::json!({
+        "server": server,
+        "summary": summary,
+        "download": subtest_json(&download),
+        "upload": subtest_json(&upload),
+        "raw_output_lines": lines,
+    });
+
+    SpeedTestResult {
+        provider_id: "ndt7".to_string(),
+        download_mbps,
+        upload_mbps,
+        latency_ms,
+        jitter_ms,
+        packet_loss_pct,
+        bufferbloat_ms: None,
+        server,
+        raw_json: Some(raw_json),
+        timestamp: chrono::Utc::now(),
+        truncated,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_hint() {
+        assert_eq!(
+            server_for_hint("lga03"),
+            "ndt-mlab1-lga03.mlab-oti.measurement-lab.org"
+        );
+        assert_eq!(server_for_hint("ndt.example.org"), "ndt.example.org");
+    }
+
+    #[test]
+    fn test_parse_summary_and_detail() {
+        let out = concat!(
+            r#"{"Key":"starting","Value":{"Test":"download"}}"#, "\n",
+            r#"{"Key":"measurement","Value":{"Origin":"server","Test":"download","AppInfo":{"NumBytes":12500000,"ElapsedTime":1000000},"TCPInfo":{"MinRTT":12000,"RTTVar":3000},"BBRInfo":{"BW":12000000,"MinRTT":11800}}}"#, "\n",
+            r#"{"Key":"measurement","Value":{"Origin":"client","Test":"upload","AppInfo":{"NumBytes":2500000,"ElapsedTime":1000000}}}"#, "\n",
+            r#"{"ServerFQDN":"ndt-mlab1-lga03.mlab-oti.measurement-lab.org","Download":{"Throughput":{"Value":95.5,"Unit":"Mbit/s"},"Latency":{"Value":11.9,"Unit":"ms"},"Retransmission":{"Value":0.4,"Unit":"%"}},"Upload":{"Throughput":{"Value":19.8,"Unit":"Mbit/s"}}}"#, "\n",
+        );
+        let r = parse_output(out, false);
+        assert_eq!(r.download_mbps, Some(95.5));
+        assert_eq!(r.upload_mbps, Some(19.8));
+        assert_eq!(r.latency_ms, Some(11.9));
+        assert_eq!(r.jitter_ms, Some(3.0));
+        assert_eq!(r.packet_loss_pct, Some(0.4));
+        assert_eq!(
+            r.server.as_deref(),
+            Some("ndt-mlab1-lga03.mlab-oti.measurement-lab.org")
+        );
+        assert_eq!(r.raw_json.unwrap()["download"]["bbr_info"]["BW"], 12000000);
+    }
+
+    #[test]
+    fn test_download_only_leaves_upload_empty() {
+        let out = r#"{"Key":"measurement","Value":{"Test":"download","AppInfo":{"NumBytes":12500000,"ElapsedTime":1000000},"TCPInfo":{"MinRTT":20000}}}"#;
+        let r = parse_output(out, false);
+        assert_eq!(r.download_mbps, Some(100.0));
+        assert_eq!(r.upload_mbps, None);
+        assert_eq!(r.latency_ms, Some(20.0));
+        assert_eq!(r.packet_loss_pct, None);
+    }
+
+    #[test]
+    fn test_truncated_drops_unfinished_subtest() {
+        let out = concat!(
+            r#"{"Key":"measurement","Value":{"Test":"download","AppInfo":{"NumBytes":12500000,"ElapsedTime":1000000}}}"#, "\n",
+            r#"{"Key":"complete","Value":{"Test":"download"}}"#, "\n",
+            r#"{"Key":"measurement","Value":{"Test":"upload","AppInfo":{"NumBytes":250000,"ElapsedTime":1000000}}}"#, "\n",
+        );
+        let r = parse_output(out, true);
+        assert!(r.truncated);
+        assert_eq!(r.download_mbps, Some(100.0));
+        assert_eq!(r.upload_mbps, None);
     }
 }
@@ -1,9 +1,57 @@
-use super::{SpeedTestProvider, ProviderMeta, SpeedTestRequest, SpeedTestResult, ProviderKind, Stability, MetricsSupported, Recommendation};
+use super::{run_with_timeout, SpeedTestProvider, ProviderMeta, SpeedTestRequest, SpeedTestResult, ProviderKind, Stability, MetricsSupported, Recommendation};
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 use std::process::Command;
 
 pub struct OoklaProvider;
 
+/// One entry of `speedtest --servers`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServerInfo {
+    pub id: u32,
+    pub name: String,
+    #[serde(default)]
+    pub location: String,
+    #[serde(default)]
+    pub country: String,
+    #[serde(default)]
+    pub host: String,
+}
+
+impl OoklaProvider {
+    /// Servers the CLI would pick from (nearest first), for pinning one via
+    /// `SpeedTestRequest::server_hint`.
+    pub async fn list_servers() -> Result<Vec<ServerInfo>> {
+        let output = tokio::process::Command::new("speedtest")
+            .args(["--servers", "--format=json", "--accept-license", "--accept-gdpr"])
+            .output()
+            .await?;
+        if !output.status.success() {
+            return Err(anyhow::anyhow!("Ookla CLI failed: {}", String::from_utf8_lossy(&output.stderr)));
+        }
+        parse_server_list(&output.stdout)
+    }
+}
+
+fn parse_server_list(stdout: &[u8]) -> Result<Vec<ServerInfo>> {
+    #[derive(Deserialize)]
+    struct ServerList {
+        servers: Vec<ServerInfo>,
+    }
+    Ok(serde_json::from_slice::<ServerList>(stdout)?.servers)
+}
+
+/// `"<name> - <location> (id <id>)"` from a result's `server` object.
+fn server_label(json: &serde_json::Value) -> Option<String> {
+    let server = json.get("server")?;
+    let id = server.get("id")?.as_u64()?;
+    let name = server.get("name").and_then(|v| v.as_str()).unwrap_or("unknown");
+    Some(match server.get("location").and_then(|v| v.as_str()) {
+        Some(location) => format!("{} - {} (id {})", name, location, id),
+        None => format!("{} (id {})", name, id),
+    })
+}
+
 #[async_trait::async_trait]
 impl SpeedTestProvider for OoklaProvider {
     fn meta(&self) -> ProviderMeta {
@@ -34,39 +82,157 @@ impl SpeedTestProvider for OoklaProvider {
         std::process::Command::new("speedtest").arg("--version").output().is_ok()
     }
 
-    async fn run(&self, _req: SpeedTestRequest) -> Result<SpeedTestResult> {
-        // Provide a real implementation stub that would fail gracefully but compiles
-        // Run: speedtest --format=json --accept-license --accept-gdpr
-        let output = tokio::process::Command::new("speedtest")
-            .arg("--format=json")
+    async fn run(&self, req: SpeedTestRequest) -> Result<SpeedTestResult> {
+        // Run: speedtest --format=jsonl --progress=yes --accept-license --accept-gdpr [-s <id>]
+        // JSON lines stream per-phase progress, so a run cut off by the
+        // timeout still yields the phases that finished.
+        let mut cmd = tokio::process::Command::new("speedtest");
+        cmd.arg("--format=jsonl")
+            .arg("--progress=yes")
             .arg("--accept-license")
-            .arg("--accept-gdpr")
-            .output()
-            .await?;
-            
-        if !output.status.success() {
+            .arg("--accept-gdpr");
+        if let Some(hint) = req.server_hint.as_deref() {
+            let id: u32 = hint
+                .trim()
+                .parse()
+                .map_err(|_| anyhow::anyhow!("Ookla server hint must be a numeric server ID, got '{}'", hint))?;
+            cmd.arg("-s").arg(id.to_string());
+        }
+        let output = run_with_timeout(cmd, req.timeout).await?;
+
+        if !output.success && !output.timed_out {
              return Err(anyhow::anyhow!("Ookla CLI failed: {}", String::from_utf8_lossy(&output.stderr)));
         }
 
-        let json: serde_json::Value = serde_json::from_slice(&output.stdout)?;
-        
-        // Basic parsing (stub)
-        let download = json.get("download").and_then(|v| v.get("bandwidth")).and_then(|v| v.as_f64()).map(|b| b * 8.0 / 1_000_000.0);
-        let upload = json.get("upload").and_then(|v| v.get("bandwidth")).and_then(|v| v.as_f64()).map(|b| b * 8.0 / 1_000_000.0);
-        let latency = json.get("ping").and_then(|v| v.get("latency")).and_then(|v| v.as_f64());
-        let jitter = json.get("ping").and_then(|v| v.get("jitter")).and_then(|v| v.as_f64());
-        let packet_loss = json.get("packetLoss").and_then(|v| v.as_f64());
+        parse_jsonl(&String::from_utf8_lossy(&output.stdout), output.timed_out)
+    }
+}
+
+fn bandwidth_mbps(phase: &serde_json::Value) -> Option<f64> {
+    phase.get("bandwidth").and_then(|v| v.as_f64()).map(|b| b * 8.0 / 1_000_000.0)
+}
+
+/// Parse `--format=jsonl` output: `testStart`, then `ping` / `download` /
+/// `upload` progress events, then a `result` line identical to
+/// `--format=json`. Without a `result` (timed out), a phase counts as
+/// finished once it reports progress 1 or the next phase has started.
+fn parse_jsonl(stdout: &str, timed_out: bool) -> Result<SpeedTestResult> {
+    let mut last: std::collections::HashMap<String, serde_json::Value> = Default::default();
+    for line in stdout.lines() {
+        let Ok(json) = serde_json::from_str::<serde_json::Value>(line) else {
+            continue;
+        };
+        if let Some(kind) = json.get("type").and_then(|t| t.as_str()) {
+            last.insert(kind.to_string(), json);
+        }
+    }
 
-        Ok(SpeedTestResult {
+    if let Some(json) = last.remove("result") {
+        let ping = json.get("ping");
+        return Ok(SpeedTestResult {
             provider_id: "ookla-cli".to_string(),
-            download_mbps: download,
-            upload_mbps: upload,
-            latency_ms: latency,
-            jitter_ms: jitter,
-            packet_loss_pct: packet_loss,
+            download_mbps: json.get("download").and_then(bandwidth_mbps),
+            upload_mbps: json.get("upload").and_then(bandwidth_mbps),
+            latency_ms: ping.and_then(|v| v.get("latency")).and_then(|v| v.as_f64()),
+            jitter_ms: ping.and_then(|v| v.get("jitter")).and_then(|v| v.as_f64()),
+            packet_loss_pct: json.get("packetLoss").and_then(|v| v.as_f64()),
             bufferbloat_ms: None,
+            server: server_label(&json),
             raw_json: Some(json),
             timestamp: chrono::Utc::now(),
-        })
+            truncated: false,
+        });
+    }
+
+    // No final result: keep only the phases that completed.
+    let phase = |kind: &str, next: Option<&str>| {
+        let event = last.get(kind)?.get(kind)?;
+        let progress = event.get("progress").and_then(|p| p.as_f64()).unwrap_or(0.0);
+        let done = progress >= 1.0 || next.is_some_and(|n| last.contains_key(n));
+        done.then_some(event)
+    };
+    let ping = phase("ping", Some("download"));
+    let download = phase("download", Some("upload"));
+    let upload = phase("upload", None);
+    if ping.is_none() && download.is_none() {
+        anyhow::bail!(
+            "Ookla CLI {} before completing any phase",
+            if timed_out { "timed out" } else { "exited" }
+        );
+    }
+
+    Ok(SpeedTestResult {
+        provider_id: "ookla-cli".to_string(),
+        download_mbps: download.and_then(bandwidth_mbps),
+        upload_mbps: upload.and_then(bandwidth_mbps),
+        latency_ms: ping.and_then(|v| v.get("latency")).and_then(|v| v.as_f64()),
+        jitter_ms: ping.and_then(|v| v.get("jitter")).and_then(|v| v.as_f64()),
+        packet_loss_pct: None,
+        bufferbloat_ms: None,
+        server: last.get("testStart").and_then(server_label),
+        raw_json: Some(serde_json::json!({ "partial": true, "last_events": last })),
+        timestamp: chrono::Utc::now(),
+        truncated: true,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_server_list() {
+        let out = br#"{"type":"serverList","servers":[
+            {"id":1234,"host":"speed.example.net","port":8080,"name":"Example ISP","location":"Chicago, IL","country":"United States"},
+            {"id":99,"name":"Minimal"}
+        ]}"#;
+        let servers = parse_server_list(out).unwrap();
+        assert_eq!(servers.len(), 2);
+        assert_eq!(servers[0].id, 1234);
+        assert_eq!(servers[0].location, "Chicago, IL");
+        assert_eq!(servers[1].host, "");
+    }
+
+    #[test]
+    fn test_parse_jsonl_complete_and_partial() {
+        let start = r#"{"type":"testStart","server":{"id":1234,"name":"Example ISP","location":"Chicago, IL"}}"#;
+        let ping = r#"{"type":"ping","ping":{"jitter":1.5,"latency":12.0,"progress":1.0}}"#;
+        let dl_mid = r#"{"type":"download","download":{"bandwidth":62500000,"progress":0.4}}"#;
+        let dl_end = r#"{"type":"download","download":{"bandwidth":125000000,"progress":1.0}}"#;
+        let ul_mid = r#"{"type":"upload","upload":{"bandwidth":2500000,"progress":0.3}}"#;
+        let result = r#"{"type":"result","ping":{"jitter":1.5,"latency":12.0},"download":{"bandwidth":125000000},"upload":{"bandwidth":5000000},"packetLoss":0.0,"server":{"id":1234,"name":"Example ISP"}}"#;
+
+        let full = [start, ping, dl_end, ul_mid, result].join("\n");
+        let r = parse_jsonl(&full, false).unwrap();
+        assert!(!r.truncated);
+        assert_eq!(r.download_mbps, Some(1000.0));
+        assert_eq!(r.upload_mbps, Some(40.0));
+
+        // Timed out during upload: download is kept, upload is not.
+        let partial = [start, ping, dl_mid, dl_end, ul_mid].join("\n");
+        let r = parse_jsonl(&partial, true).unwrap();
+        assert!(r.truncated);
+        assert_eq!(r.download_mbps, Some(1000.0));
+        assert_eq!(r.upload_mbps, None);
+        assert_eq!(r.latency_ms, Some(12.0));
+        assert_eq!(r.server.as_deref(), Some("Example ISP - Chicago, IL (id 1234)"));
+
+        // Timed out mid-download: only latency survives.
+        let r = parse_jsonl(&[start, ping, dl_mid].join("\n"), true).unwrap();
+        assert_eq!((r.latency_ms, r.download_mbps), (Some(12.0), None));
+
+        assert!(parse_jsonl(start, true).is_err());
+    }
+
+    #[test]
+    fn test_server_label() {
+        let json = serde_json::json!({
+            "server": { "id": 1234, "name": "Example ISP", "location": "Chicago, IL" }
+        });
+        assert_eq!(
+            server_label(&json).as_deref(),
+            Some("Example ISP - Chicago, IL (id 1234)")
+        );
+        assert!(server_label(&serde_json::json!({})).is_none());
     }
 }
@@ -1,39 +1,98 @@
 
 use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
 use std::net::SocketAddr;
+use crate::storage::Pool;
 use crate::throughput::provider::{SpeedTestProvider, SpeedTestRequest, SpeedTestResult, ProviderMeta, ProviderKind, Stability, MetricsSupported, Recommendation};
-use crate::reflector_proto::{client::ReflectorClient, identity::Identity};
+use crate::reflector_proto::{client::ReflectorClient, identity::Identity, rpc::{SessionGrant, SessionSummary}};
 
 pub struct ReflectorProvider;
 
-#[async_trait::async_trait]
-impl SpeedTestProvider for ReflectorProvider {
-    fn meta(&self) -> ProviderMeta {
-        ProviderMeta {
-            id: "reflector",
-            display_name: "PacketParamedic Reflector",
-            kind: ProviderKind::SelfHostedWAN,
-            recommendation: Recommendation::Recommended,
-            description: "Dedicated high-performance endpoint using Paramedic Link protocol.",
-            install_hint: "Deploy a Reflector instance using Docker or the binary.",
-            licensing_note: None,
-            stability: Stability::Stable,
-            metrics: MetricsSupported {
-                download: true,
-                upload: true,
-                latency: false, // Could be derived from iperf3
-                jitter: true,   // iperf3 returns jitter
-                packet_loss: true,
-                bufferbloat: false,
-            },
-        }
+/// Shortest test per direction. A shorter `SpeedTestRequest::timeout` is
+/// raised to this, since iperf3 barely leaves slow-start in less.
+pub const MIN_TEST_SECS: u64 = 10;
+
+/// One direction of a reflector test as seen from both ends.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionComparison {
+    pub test_id: String,
+    /// "upload" or "download".
+    pub direction: String,
+    /// Goodput measured by the local iperf3 client, warm-up excluded.
+    pub client_mbps: f64,
+    /// Client goodput including the warm-up; only set when one was omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_raw_mbps: Option<f64>,
+    /// The reflector's summary, if it returned one on `SessionClose`.
+    pub reflector: Option<SessionSummary>,
+}
+
+impl SessionComparison {
+    pub fn reflector_mbps(&self) -> Option<f64> {
+        self.reflector.as_ref().map(|s| s.throughput_mbps())
     }
 
-    fn is_available(&self) -> bool {
-         std::process::Command::new("iperf3").arg("-v").output().is_ok()
+    /// Share of the reflector-side rate that the client did not see, in percent.
+    ///
+    /// E.g. reflector 950 Mbps vs client 900 Mbps gives ~5.3%, which points at
+    /// loss or retransmission on the path rather than at either endpoint.
+    /// The reflector counts the whole session, warm-up included, so it is
+    /// compared with the client's raw rate when a warm-up was omitted.
+    pub fn discrepancy_pct(&self) -> Option<f64> {
+        let reflector = self.reflector_mbps().filter(|m| *m > 0.0)?;
+        let client = self.client_raw_mbps.unwrap_or(self.client_mbps);
+        Some((reflector - client) / reflector * 100.0)
     }
 
-    async fn run(&self, req: SpeedTestRequest) -> Result<SpeedTestResult> {
+    /// Slowest reflector-side stream relative to the mean; see
+    /// [`crate::throughput::report::stream_balance`]. `None` without
+    /// per-stream figures from the reflector.
+    pub fn stream_balance(&self) -> Option<f64> {
+        crate::throughput::report::stream_balance(&self.reflector.as_ref()?.streams_mbps)
+    }
+
+    /// Persist both sides of the session to `reflector_sessions`.
+    pub fn save(&self, pool: &Pool, reflector_addr: &str) -> Result<()> {
+        let conn = pool.get()?;
+        let outcome = self
+            .reflector
+            .as_ref()
+            .map(|s| serde_json::to_value(&s.outcome))
+            .transpose()?
+            .and_then(|v| v.as_str().map(String::from));
+        conn.execute(
+            "INSERT INTO reflector_sessions
+                (test_id, reflector, direction, client_mbps, client_raw_mbps, reflector_mbps,
+                 reflector_bytes, reflector_duration_sec, outcome)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            rusqlite::params![
+                self.test_id,
+                reflector_addr,
+                self.direction,
+                self.client_mbps,
+                self.client_raw_mbps,
+                self.reflector_mbps(),
+                self.reflector.as_ref().map(|s| s.bytes_transferred as i64),
+                self.reflector.as_ref().map(|s| s.duration_sec),
+                outcome,
+            ],
+        )?;
+        Ok(())
+    }
+}
+
+impl ReflectorProvider {
+    /// Run upload and download against the reflector, closing each session
+    /// with a summary request so both sides of every test are returned.
+    ///
+    /// `omit_secs` of TCP slow-start (iperf3 `-O`) run on top of each
+    /// direction and are left out of the reported rates; the session is
+    /// requested that much longer so the reflector keeps serving.
+    pub async fn run_sessions(
+        &self,
+        req: SpeedTestRequest,
+        omit_secs: u32,
+    ) -> Result<(SpeedTestResult, Vec<SessionComparison>)> {
          // 1. Get Control Plane Address
          let host_str = req.server_hint.ok_or_else(|| anyhow!("Reflector provider requires a host (use --peer)"))?;
          let control_addr: SocketAddr = host_str.parse().context("Invalid reflector address (e.g. 1.2.3.4:4000)")?;
@@ -51,46 +110,208 @@ impl SpeedTestProvider for ReflectorProvider {
          
          // Hardcoded streams/test parameters for now since SpeedTestRequest is limited
          let streams = 4; // Parallel streams used by Reflector usually
-         let duration = 10.max(req.timeout.as_secs()); // Ensure at least 10s
+         let duration = MIN_TEST_SECS.max(req.timeout.as_secs()) + u64::from(omit_secs);
          
-         let data_plane_ip = control_addr.ip().to_string();
+         let data_plane_ip = control_addr.ip();
 
          // 4. Run Upload (Client -> Server)
          // Note: Reflector protocol 'reverse' param in SessionRequest means "Server sends to Client" (Download).
          // So for Upload: reverse = false.
          let up_grant = client.request_throughput_session(duration, streams, false).await?;
          tracing::info!(?up_grant, "Received throughput session grant (Upload)");
-         // Note: up_grant.port is the data plane port on the server.
+         let (up_duration, up_streams) = granted(&up_grant, duration, streams);
+         let (up_duration, up_omit) = split_omit(up_duration, omit_secs);
+         // Note: up_grant.port is the data plane port on the server (unused when tunneled).
          
          // Add a small delay to allow the server's iperf3 process to initialize and bind the port.
          tokio::time::sleep(std::time::Duration::from_millis(500)).await;
 
-         let up_mbps = run_iperf3_async(&data_plane_ip, up_grant.port, duration, streams, false).await?;
+         let up = client
+             .run_data_plane(&up_grant, data_plane_ip, |addr| {
+                 run_iperf3_at(addr, up_duration, up_omit, up_streams, false)
+             })
+             .await?;
+         let up_summary = close_for_summary(&mut client, &up_grant.test_id).await;
 
          // 5. Run Download (Client <- Server)
          // reverse = true.
          let down_grant = client.request_throughput_session(duration, streams, true).await?;
          tracing::info!(?down_grant, "Received throughput session grant (Download)");
+         let (down_duration, down_streams) = granted(&down_grant, duration, streams);
+         let (down_duration, down_omit) = split_omit(down_duration, omit_secs);
          // Add a small delay to allow the server's iperf3 process to initialize and bind the port.
          tokio::time::sleep(std::time::Duration::from_millis(500)).await;
 
-         let down_mbps = run_iperf3_async(&data_plane_ip, down_grant.port, duration, streams, true).await?;
+         let down = client
+             .run_data_plane(&down_grant, data_plane_ip, |addr| {
+                 run_iperf3_at(addr, down_duration, down_omit, down_streams, true)
+             })
+             .await?;
+         let down_summary = close_for_summary(&mut client, &down_grant.test_id).await;
 
-         Ok(SpeedTestResult {
+         let sessions = vec![
+             SessionComparison {
+                 test_id: up_grant.test_id,
+                 direction: "upload".to_string(),
+                 client_mbps: up.mbps,
+                 client_raw_mbps: up.raw_mbps,
+                 reflector: up_summary,
+             },
+             SessionComparison {
+                 test_id: down_grant.test_id,
+                 direction: "download".to_string(),
+                 client_mbps: down.mbps,
+                 client_raw_mbps: down.raw_mbps,
+                 reflector: down_summary,
+             },
+         ];
+
+         let result = SpeedTestResult {
              provider_id: "reflector".to_string(),
-             download_mbps: Some(down_mbps),
-             upload_mbps: Some(up_mbps),
+             download_mbps: Some(down.mbps),
+             upload_mbps: Some(up.mbps),
              latency_ms: None, // todo: extract from iperf json
              jitter_ms: None,
              packet_loss_pct: None,
              bufferbloat_ms: None,
-             raw_json: None,
+             server: Some(control_addr.to_string()),
+             raw_json: Some(serde_json::json!({ "sessions": sessions, "omit_secs": omit_secs })),
              timestamp: chrono::Utc::now(),
-         })
+             truncated: false,
+         };
+         Ok((result, sessions))
+    }
+}
+
+/// Result of a short LAN check against a reflector.
+#[derive(Debug, Clone, Serialize)]
+pub struct QuickCheck {
+    /// Round trip of a status request over the control link.
+    pub rtt_ms: f64,
+    pub download_mbps: f64,
+}
+
+impl ReflectorProvider {
+    /// Short download-only test, used by blame check to prove the LAN path
+    /// with real throughput rather than a ping.
+    pub async fn quick_check(addr: SocketAddr, identity: &Identity, duration_secs: u64) -> Result<QuickCheck> {
+        const STREAMS: u32 = 2;
+
+        let mut client = ReflectorClient::connect(addr, identity).await?;
+        let started = std::time::Instant::now();
+        client.get_status().await?;
+        let rtt_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+        let grant = client.request_throughput_session(duration_secs, STREAMS, true).await?;
+        let (duration_secs, streams) = granted(&grant, duration_secs, STREAMS);
+        // Give the reflector's iperf3 a moment to bind, as in `run_sessions`.
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+        let mbps = client
+            .run_data_plane(&grant, addr.ip(), |data| run_iperf3_at(data, duration_secs, 0, streams, true))
+            .await;
+        close_for_summary(&mut client, &grant.test_id).await;
+
+        Ok(QuickCheck { rtt_ms, download_mbps: mbps?.mbps })
+    }
+}
+
+/// Duration and streams a grant allows, noting when the reflector changed
+/// what we asked for.
+fn granted(grant: &SessionGrant, duration_secs: u64, streams: u32) -> (u64, u32) {
+    let effective = grant.effective(duration_secs, streams);
+    if effective != (duration_secs, streams) {
+        tracing::info!(
+            requested_secs = duration_secs,
+            requested_streams = streams,
+            granted_secs = effective.0,
+            granted_streams = effective.1,
+            "reflector adjusted the test"
+        );
+    }
+    effective
+}
+
+/// Seconds to measure and to omit within a granted session of
+/// `granted_secs`. If the reflector cut the session too short to hold the
+/// warm-up, the whole session is measured instead.
+fn split_omit(granted_secs: u64, omit_secs: u32) -> (u64, u32) {
+    match granted_secs.checked_sub(u64::from(omit_secs)) {
+        Some(measured) if measured > 0 => (measured, omit_secs),
+        _ => {
+            if omit_secs > 0 {
+                tracing::warn!(granted_secs, omit_secs, "granted session too short to omit the warm-up");
+            }
+            (granted_secs, 0)
+        }
+    }
+}
+
+/// Close a session and fetch the reflector's summary; failures only cost the
+/// comparison, not the measurement.
+async fn close_for_summary(client: &mut ReflectorClient, test_id: &str) -> Option<SessionSummary> {
+    match client.close_session(test_id).await {
+        Ok(summary) => summary,
+        Err(e) => {
+            tracing::warn!(%test_id, error = %e, "failed to close reflector session");
+            None
+        }
     }
 }
 
-async fn run_iperf3_async(host: &str, port: u16, duration: u64, streams: u32, reverse: bool) -> Result<f64> {
+#[async_trait::async_trait]
+impl SpeedTestProvider for ReflectorProvider {
+    fn meta(&self) -> ProviderMeta {
+        ProviderMeta {
+            id: "reflector",
+            display_name: "PacketParamedic Reflector",
+            kind: ProviderKind::SelfHostedWAN,
+            recommendation: Recommendation::Recommended,
+            description: "Dedicated high-performance endpoint using Paramedic Link protocol.",
+            install_hint: "Deploy a Reflector instance using Docker or the binary.",
+            licensing_note: None,
+            stability: Stability::Stable,
+            metrics: MetricsSupported {
+                download: true,
+                upload: true,
+                latency: false, // Could be derived from iperf3
+                jitter: true,   // iperf3 returns jitter
+                packet_loss: true,
+                bufferbloat: false,
+            },
+        }
+    }
+
+    fn is_available(&self) -> bool {
+         std::process::Command::new("iperf3").arg("-v").output().is_ok()
+    }
+
+    async fn run(&self, req: SpeedTestRequest) -> Result<SpeedTestResult> {
+        self.run_sessions(req, 0).await.map(|(result, _)| result)
+    }
+}
+
+/// Client-side rates of one iperf3 run.
+#[derive(Debug, Clone, Copy)]
+struct ClientRates {
+    /// Steady-state goodput, warm-up excluded.
+    mbps: f64,
+    /// Goodput including the warm-up; only set when one was omitted.
+    raw_mbps: Option<f64>,
+}
+
+/// `run_iperf3_async` against a data-plane address from `run_data_plane`.
+async fn run_iperf3_at(addr: SocketAddr, duration: u64, omit_secs: u32, streams: u32, reverse: bool) -> Result<ClientRates> {
+    run_iperf3_async(&addr.ip().to_string(), addr.port(), duration, omit_secs, streams, reverse).await
+}
+
+async fn run_iperf3_async(
+    host: &str,
+    port: u16,
+    duration: u64,
+    omit_secs: u32,
+    streams: u32,
+    reverse: bool,
+) -> Result<ClientRates> {
     // Construct arguments for iperf3 itself
     let mut iperf_args = vec![
         "-c".to_string(),
@@ -107,6 +328,10 @@ async fn run_iperf3_async(host: &str, port: u16, duration: u64, streams: u32, re
     if reverse {
         iperf_args.push("-R".to_string());
     }
+    if omit_secs > 0 {
+        iperf_args.push("-O".to_string());
+        iperf_args.push(omit_secs.to_string());
+    }
 
     // Determine executable and final args based on OS/taskset availability
     let (exe, final_args) = if cfg!(target_os = "linux") {
@@ -135,6 +360,14 @@ async fn run_iperf3_async(host: &str, port: u16, duration: u64, streams: u32, re
         ));
     }
     
+    let raw_mbps = if omit_secs > 0 {
+        crate::throughput::iperf::parse_output(&String::from_utf8_lossy(&output.stdout))
+            .ok()
+            .and_then(|r| r.raw_mbps())
+    } else {
+        None
+    };
+
     // Parse JSON
     let json: serde_json::Value = serde_json::from_slice(&output.stdout)?;
     
@@ -147,5 +380,100 @@ async fn run_iperf3_async(host: &str, port: u16, duration: u64, streams: u32, re
     // Prefer received (goodput), fallback to sent if missing (e.g. UDP sender report only)
     let bits_per_second = sum_received.or(sum_sent).ok_or_else(|| anyhow::anyhow!("Could not find throughput data in JSON"))?;
         
-    Ok(bits_per_second / 1_000_000.0)
+    Ok(ClientRates {
+        mbps: bits_per_second / 1_000_000.0,
+        raw_mbps,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reflector_proto::rpc::SessionOutcome;
+
+    fn comparison(client_mbps: f64, reflector_bytes: u64) -> SessionComparison {
+        SessionComparison {
+            test_id: "t-1".to_string(),
+            direction: "download".to_string(),
+            client_mbps,
+            client_raw_mbps: None,
+            reflector: Some(SessionSummary {
+                test_id: "t-1".to_string(),
+                outcome: SessionOutcome::Completed,
+                bytes_transferred: reflector_bytes,
+                duration_sec: 10.0,
+                streams_mbps: Vec::new(),
+                error: None,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_stream_balance_from_reflector_summary() {
+        let mut c = comparison(900.0, 1_187_500_000);
+        assert!(c.stream_balance().is_none());
+
+        c.reflector.as_mut().unwrap().streams_mbps = vec![300.0, 300.0, 300.0, 50.0];
+        let balance = c.stream_balance().unwrap();
+        assert!((balance - 50.0 / 237.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_discrepancy_pct() {
+        // 1_187_500_000 bytes over 10s = 950 Mbps on the reflector side.
+        let c = comparison(900.0, 1_187_500_000);
+        assert!((c.reflector_mbps().unwrap() - 950.0).abs() < 1e-9);
+        assert!((c.discrepancy_pct().unwrap() - 50.0 / 950.0 * 100.0).abs() < 1e-9);
+
+        let missing = SessionComparison { reflector: None, ..c.clone() };
+        assert!(missing.discrepancy_pct().is_none());
+
+        let zero = comparison(900.0, 0);
+        assert!(zero.discrepancy_pct().is_none());
+
+        // With the warm-up omitted, the reflector's whole-session rate is
+        // held against the client's raw rate.
+        let omitted = SessionComparison { client_mbps: 940.0, client_raw_mbps: Some(900.0), ..c };
+        assert!((omitted.discrepancy_pct().unwrap() - 50.0 / 950.0 * 100.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_split_omit() {
+        assert_eq!(split_omit(13, 3), (10, 3));
+        assert_eq!(split_omit(10, 0), (10, 0));
+        // Cut short by the reflector: measure the whole session.
+        assert_eq!(split_omit(3, 3), (3, 0));
+        assert_eq!(split_omit(2, 3), (2, 0));
+    }
+
+    #[test]
+    fn test_save_stores_both_sides() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = crate::storage::open_pool(dir.path().join("t.db").to_str().unwrap()).unwrap();
+
+        SessionComparison { client_raw_mbps: Some(850.0), ..comparison(900.0, 1_187_500_000) }
+            .save(&pool, "192.0.2.1:4000")
+            .unwrap();
+        SessionComparison { reflector: None, ..comparison(400.0, 0) }
+            .save(&pool, "192.0.2.1:4000")
+            .unwrap();
+
+        let conn = pool.get().unwrap();
+        let (client, client_raw, reflector, outcome): (f64, Option<f64>, Option<f64>, Option<String>) = conn
+            .query_row(
+                "SELECT client_mbps, client_raw_mbps, reflector_mbps, outcome FROM reflector_sessions ORDER BY id LIMIT 1",
+                [],
+                |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?)),
+            )
+            .unwrap();
+        assert_eq!(client, 900.0);
+        assert_eq!(client_raw, Some(850.0));
+        assert!((reflector.unwrap() - 950.0).abs() < 1e-9);
+        assert_eq!(outcome.as_deref(), Some("completed"));
+
+        let count: i64 = conn
+            .query_row("SELECT count(*) FROM reflector_sessions WHERE reflector_mbps IS NULL", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(count, 1);
+    }
 }
@@ -0,0 +1,144 @@
+use super::{run_with_timeout, SpeedTestProvider, ProviderMeta, SpeedTestRequest, SpeedTestResult, ProviderKind, Stability, MetricsSupported, Recommendation};
+use anyhow::Result;
+use std::process::Command;
+
+/// Page the browser loads; fast.com starts its download test on load.
+const TEST_URL: &str = "https://fast.com";
+
+/// Browsers that can run headless and dump the rendered DOM.
+const BROWSERS: [&str; 4] = ["chromium", "chromium-browser", "google-chrome", "google-chrome-stable"];
+
+/// Last-resort provider: drives a headless browser through a web-only speed
+/// test when no CLI provider is installed. Only download is read from the
+/// page, and the result depends on the page's markup, hence Experimental.
+pub struct WebProvider;
+
+impl WebProvider {
+    fn find_browser() -> Option<&'static str> {
+        BROWSERS
+            .into_iter()
+            .find(|b| Command::new(b).arg("--version").output().map(|o| o.status.success()).unwrap_or(false))
+    }
+}
+
+#[async_trait::async_trait]
+impl SpeedTestProvider for WebProvider {
+    fn meta(&self) -> ProviderMeta {
+        ProviderMeta {
+            id: "web",
+            display_name: "Web (headless browser)",
+            kind: ProviderKind::BrowserAutomated,
+            recommendation: Recommendation::Fallback,
+            description: "Runs fast.com in headless Chromium. Use only when no CLI provider is available.",
+            install_hint: "Install Chromium: sudo apt-get install -y chromium",
+            licensing_note: None,
+            stability: Stability::Experimental,
+            metrics: MetricsSupported {
+                download: true,
+                upload: false,
+                latency: false,
+                jitter: false,
+                packet_loss: false,
+                bufferbloat: false,
+            },
+        }
+    }
+
+    fn is_available(&self) -> bool {
+        Self::find_browser().is_some()
+    }
+
+    async fn run(&self, req: SpeedTestRequest) -> Result<SpeedTestResult> {
+        let browser = Self::find_browser().ok_or_else(|| anyhow::anyhow!("No headless browser found"))?;
+
+        // The DOM is dumped once the page has run for the time budget; if the
+        // test hasn't finished by then the value is not marked succeeded and
+        // the run fails rather than reporting a ramp-up figure.
+        let budget_ms = req.timeout.as_millis().max(10_000);
+        let mut cmd = tokio::process::Command::new(browser);
+        cmd.arg("--headless=new")
+            .arg("--disable-gpu")
+            .arg("--no-sandbox")
+            .arg(format!("--virtual-time-budget={}", budget_ms))
+            .arg("--dump-dom")
+            .arg(TEST_URL);
+        // Grace on top of the page budget for browser start-up and the dump.
+        let deadline = std::time::Duration::from_millis(budget_ms as u64) + std::time::Duration::from_secs(30);
+        let output = run_with_timeout(cmd, deadline).await?;
+        if output.timed_out {
+            anyhow::bail!("Headless browser timed out after {:?}", deadline);
+        }
+
+        if !output.success {
+            return Err(anyhow::anyhow!("Headless browser failed: {}", String::from_utf8_lossy(&output.stderr)));
+        }
+
+        let dom = String::from_utf8_lossy(&output.stdout);
+        let download = parse_fast_dom(&dom)
+            .ok_or_else(|| anyhow::anyhow!("No speed result found on {}", TEST_URL))?;
+
+        Ok(SpeedTestResult {
+            provider_id: "web".to_string(),
+            download_mbps: Some(download),
+            upload_mbps: None,
+            latency_ms: None,
+            jitter_ms: None,
+            packet_loss_pct: None,
+            bufferbloat_ms: None,
+            server: Some(TEST_URL.to_string()),
+            raw_json: Some(serde_json::json!({ "url": TEST_URL, "browser": browser })),
+            timestamp: chrono::Utc::now(),
+            truncated: false,
+        })
+    }
+}
+
+/// Opening tag and text content of the element with `id`, if it is a leaf.
+fn element<'a>(dom: &'a str, id: &str) -> Option<(&'a str, &'a str)> {
+    let at = dom.find(&format!("id=\"{}\"", id))?;
+    let tag_start = dom[..at].rfind('<')?;
+    let open_end = at + dom[at..].find('>')? + 1;
+    let close = open_end + dom[open_end..].find('<')?;
+    Some((&dom[tag_start..open_end], dom[open_end..close].trim()))
+}
+
+/// Download speed in Mbps from fast.com's rendered page
+/// (`#speed-value` + `#speed-units`). The value only counts once the page
+/// marks it `succeeded`; before that it is a ramp-up reading.
+fn parse_fast_dom(dom: &str) -> Option<f64> {
+    let (tag, text) = element(dom, "speed-value")?;
+    let class = tag.split("class=\"").nth(1)?.split('"').next()?;
+    if !class.split_whitespace().any(|c| c == "succeeded") {
+        return None;
+    }
+    let value: f64 = text.parse().ok()?;
+    let factor = match element(dom, "speed-units").map_or("Mbps", |(_, units)| units) {
+        "Kbps" => 0.001,
+        "Gbps" => 1000.0,
+        _ => 1.0,
+    };
+    (value > 0.0).then_some(value * factor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_fast_dom() {
+        let dom = r#"<div class="speed-results-container succeeded" id="speed-value">940</div>
+            <div class="speed-units-container" id="speed-units">Mbps</div>"#;
+        assert_eq!(parse_fast_dom(dom), Some(940.0));
+
+        let dom = r#"<div class="succeeded" id="speed-value">1.2</div><div id="speed-units">Gbps</div>"#;
+        assert_eq!(parse_fast_dom(dom), Some(1200.0));
+
+        // Still ramping up: the figure is not final yet.
+        let dom = r#"<div class="speed-results-container" id="speed-value">310</div>"#;
+        assert_eq!(parse_fast_dom(dom), None);
+
+        // Page loaded but the test never ran.
+        assert_eq!(parse_fast_dom(r#"<div class="succeeded" id="speed-value">0</div>"#), None);
+        assert_eq!(parse_fast_dom("<html></html>"), None);
+    }
+}
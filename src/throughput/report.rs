@@ -1,6 +1,164 @@
 //! Throughput result formatting and storage.
 
-use super::ThroughputResult;
+use super::{iperf::Protocol, ThroughputResult};
+use serde::{Deserialize, Serialize};
+
+/// How close a result came to the capacity of the local link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EfficiencyGrade {
+    /// >= 90%: the link itself is the limit.
+    A,
+    /// >= 75%: normal protocol and peer overhead.
+    B,
+    /// >= 50%: something upstream is throttling.
+    C,
+    /// >= 25%
+    D,
+    /// < 25%
+    F,
+}
+
+impl EfficiencyGrade {
+    pub fn from_pct(pct: f64) -> Self {
+        if pct >= 90.0 {
+            Self::A
+        } else if pct >= 75.0 {
+            Self::B
+        } else if pct >= 50.0 {
+            Self::C
+        } else if pct >= 25.0 {
+            Self::D
+        } else {
+            Self::F
+        }
+    }
+}
+
+/// Throughput as a percentage of link speed, or `None` if the link speed
+/// is unknown (Wi-Fi, unrecognized NICs).
+pub fn efficiency_pct(throughput_mbps: f64, link_speed_mbps: Option<u64>) -> Option<f64> {
+    let link = link_speed_mbps.filter(|&l| l > 0)?;
+    Some(throughput_mbps / link as f64 * 100.0)
+}
+
+/// Fill `efficiency_pct` and `grade` from the throughput and link speed.
+///
+/// UDP runs are left ungraded: they send at the `-b` rate, not as fast as
+/// the link allows.
+pub fn apply_efficiency(result: &mut ThroughputResult) {
+    if result.protocol == Protocol::Udp {
+        result.efficiency_pct = None;
+        result.grade = None;
+        return;
+    }
+    result.efficiency_pct = efficiency_pct(result.throughput_mbps, result.link_speed_mbps);
+    result.grade = result.efficiency_pct.map(EfficiencyGrade::from_pct);
+}
+
+/// One-line plain-English efficiency summary, e.g.
+/// "You're getting 92% of your 1Gbps link".
+pub fn efficiency_line(result: &ThroughputResult) -> Option<String> {
+    let pct = result.efficiency_pct?;
+    let link = result.link_speed_mbps?;
+    let link_label = if link >= 1000 {
+        format!("{}Gbps", link as f64 / 1000.0)
+    } else {
+        format!("{}Mbps", link)
+    };
+    Some(format!(
+        "You're getting {:.0}% of your {} link",
+        pct, link_label
+    ))
+}
+
+/// Typical Ethernet TCP segment payload, used to turn retransmit counts
+/// into a share of segments sent.
+const TCP_SEGMENT_BYTES: f64 = 1448.0;
+
+/// Retransmitted share of segments above which a link counts as lossy under
+/// load, even when average throughput looks fine.
+pub const LOSSY_RETRANSMIT_PCT: f64 = 1.0;
+
+/// Retransmits as an estimated percentage of TCP segments sent.
+pub fn retransmit_pct(result: &ThroughputResult) -> Option<f64> {
+    let retransmits = result.tcp.as_ref()?.retransmits?;
+    if result.bytes_transferred == 0 {
+        return None;
+    }
+    let segments = result.bytes_transferred as f64 / TCP_SEGMENT_BYTES;
+    Some(retransmits as f64 / segments * 100.0)
+}
+
+/// Whether the test retransmitted enough to indicate loss or congestion
+/// on the path rather than a capacity limit.
+pub fn is_lossy_under_load(result: &ThroughputResult) -> bool {
+    retransmit_pct(result).is_some_and(|pct| pct >= LOSSY_RETRANSMIT_PCT)
+}
+
+/// One-line TCP health summary, e.g.
+/// "TCP: 1204 retransmits (0.15% of segments), RTT 13.0ms, cwnd 1024KB".
+pub fn tcp_line(result: &ThroughputResult) -> Option<String> {
+    let tcp = result.tcp.as_ref()?;
+    let mut parts = Vec::new();
+    if let Some(r) = tcp.retransmits {
+        match retransmit_pct(result) {
+            Some(pct) => parts.push(format!("{} retransmits ({:.2}% of segments)", r, pct)),
+            None => parts.push(format!("{} retransmits", r)),
+        }
+    }
+    if let Some(rtt) = tcp.rtt_ms {
+        parts.push(format!("RTT {:.1}ms", rtt));
+    }
+    if let Some(cwnd) = tcp.cwnd_bytes {
+        parts.push(format!("cwnd {}KB", cwnd / 1024));
+    }
+    if parts.is_empty() {
+        return None;
+    }
+    let mut line = format!("TCP: {}", parts.join(", "));
+    if is_lossy_under_load(result) {
+        line.push_str(" -- lossy under load, check for congestion or a bad cable");
+    }
+    Some(line)
+}
+
+/// Stream balance below which one or more parallel streams lagged well
+/// behind the rest.
+pub const UNBALANCED_STREAMS: f64 = 0.5;
+
+/// Slowest stream relative to the mean: 1.0 when every stream ran at the
+/// same rate, near 0 when one stalled.
+///
+/// `None` with fewer than two streams. A low balance with a good total
+/// points at per-flow shaping, ECMP hashing onto a slow path, or a
+/// saturated CPU core rather than the link itself.
+pub fn stream_balance(streams_mbps: &[f64]) -> Option<f64> {
+    if streams_mbps.len() < 2 {
+        return None;
+    }
+    let mean = streams_mbps.iter().sum::<f64>() / streams_mbps.len() as f64;
+    if mean <= 0.0 {
+        return None;
+    }
+    let slowest = streams_mbps.iter().cloned().fold(f64::INFINITY, f64::min);
+    Some(slowest / mean)
+}
+
+/// One-line per-stream summary, e.g.
+/// "Streams: 236 / 234 / 231 / 12 Mbps, balance 0.07".
+pub fn stream_line(result: &ThroughputResult) -> Option<String> {
+    let balance = result.stream_balance?;
+    let rates: Vec<String> = result
+        .streams_mbps
+        .iter()
+        .map(|m| format!("{:.0}", m))
+        .collect();
+    let mut line = format!("Streams: {} Mbps, balance {:.2}", rates.join(" / "), balance);
+    if balance < UNBALANCED_STREAMS {
+        line.push_str(" -- uneven streams, check for per-flow shaping or a busy CPU core");
+    }
+    Some(line)
+}
 
 /// Format a throughput result as a human-readable summary.
 pub fn format_summary(result: &ThroughputResult) -> String {
@@ -21,12 +179,21 @@ pub fn format_summary(result: &ThroughputResult) -> String {
         result.engine,
     );
 
+    if let (Some(omit), Some(raw)) = (result.omit_secs, result.raw_throughput_mbps) {
+        summary.push_str(&format!(", {:.1} Mbps with {}s warm-up", raw, omit));
+    }
     if let Some(jitter) = result.jitter_ms {
         summary.push_str(&format!(", jitter: {:.2}ms", jitter));
     }
     if let Some(loss) = result.loss_percent {
         summary.push_str(&format!(", loss: {:.2}%", loss));
     }
+    if let (Some(pct), Some(grade)) = (result.efficiency_pct, result.grade) {
+        summary.push_str(&format!(", efficiency: {:.0}% ({:?})", pct, grade));
+    }
+    if let Some(r) = result.tcp.as_ref().and_then(|t| t.retransmits) {
+        summary.push_str(&format!(", retransmits: {}", r));
+    }
 
     summary
 }
@@ -40,12 +207,22 @@ mod tests {
         let result = ThroughputResult {
             mode: "lan".to_string(),
             direction: "download".to_string(),
+            protocol: Protocol::Tcp,
             throughput_mbps: 9412.0,
+            omit_secs: None,
+            raw_throughput_mbps: None,
             jitter_ms: Some(0.05),
             loss_percent: Some(0.01),
             streams: 4,
+            streams_mbps: Vec::new(),
+            stream_balance: None,
             duration_secs: 30.0,
             link_speed_mbps: Some(10000),
+            bytes_transferred: 0,
+            efficiency_pct: None,
+            grade: None,
+            tcp: None,
+            buffers: None,
             engine: "iperf3".to_string(),
         };
         let summary = format_summary(&result);
@@ -59,16 +236,145 @@ mod tests {
         let result = ThroughputResult {
             mode: "wan".to_string(),
             direction: "upload".to_string(),
+            protocol: Protocol::Tcp,
             throughput_mbps: 245.3,
+            omit_secs: None,
+            raw_throughput_mbps: None,
             jitter_ms: None,
             loss_percent: None,
             streams: 1,
+            streams_mbps: Vec::new(),
+            stream_balance: None,
             duration_secs: 10.0,
             link_speed_mbps: Some(1000),
+            bytes_transferred: 0,
+            efficiency_pct: None,
+            grade: None,
+            tcp: None,
+            buffers: None,
             engine: "native".to_string(),
         };
         let summary = format_summary(&result);
         assert!(summary.contains("245.3 Mbps"));
         assert!(summary.contains("1 stream,"));
     }
+
+    fn result_with_link(throughput_mbps: f64, link_speed_mbps: Option<u64>) -> ThroughputResult {
+        let mut result = ThroughputResult {
+            mode: "lan".to_string(),
+            direction: "download".to_string(),
+            protocol: Protocol::Tcp,
+            throughput_mbps,
+            omit_secs: None,
+            raw_throughput_mbps: None,
+            jitter_ms: None,
+            loss_percent: None,
+            streams: 1,
+            streams_mbps: Vec::new(),
+            stream_balance: None,
+            duration_secs: 10.0,
+            link_speed_mbps,
+            bytes_transferred: 0,
+            efficiency_pct: None,
+            grade: None,
+            tcp: None,
+            buffers: None,
+            engine: "iperf3".to_string(),
+        };
+        apply_efficiency(&mut result);
+        result
+    }
+
+    #[test]
+    fn test_efficiency_grading() {
+        let result = result_with_link(920.0, Some(1000));
+        assert!((result.efficiency_pct.unwrap() - 92.0).abs() < 1e-9);
+        assert_eq!(result.grade, Some(EfficiencyGrade::A));
+        assert_eq!(
+            efficiency_line(&result).unwrap(),
+            "You're getting 92% of your 1Gbps link"
+        );
+        assert!(format_summary(&result).contains("efficiency: 92% (A)"));
+
+        let result = result_with_link(1500.0, Some(2500));
+        assert_eq!(result.grade, Some(EfficiencyGrade::C));
+        assert!(efficiency_line(&result).unwrap().contains("2.5Gbps"));
+
+        assert_eq!(EfficiencyGrade::from_pct(80.0), EfficiencyGrade::B);
+        assert_eq!(EfficiencyGrade::from_pct(30.0), EfficiencyGrade::D);
+        assert_eq!(EfficiencyGrade::from_pct(5.0), EfficiencyGrade::F);
+    }
+
+    #[test]
+    fn test_udp_runs_are_not_graded() {
+        let mut result = result_with_link(100.0, Some(1000));
+        result.protocol = Protocol::Udp;
+        apply_efficiency(&mut result);
+        assert_eq!(result.efficiency_pct, None);
+        assert_eq!(result.grade, None);
+        assert_eq!(efficiency_line(&result), None);
+    }
+
+    #[test]
+    fn test_retransmits_flag_lossy_link() {
+        // ~1.2 GB at 1448 B/segment is ~811k segments.
+        let mut result = result_with_link(940.0, Some(1000));
+        result.bytes_transferred = 1_175_000_000;
+        result.tcp = Some(crate::throughput::TcpStats {
+            retransmits: Some(1204),
+            rtt_ms: Some(13.0),
+            cwnd_bytes: Some(1_048_576),
+        });
+        assert!(!is_lossy_under_load(&result));
+        let line = tcp_line(&result).unwrap();
+        assert!(line.contains("1204 retransmits (0.15% of segments)"));
+        assert!(line.contains("RTT 13.0ms"));
+        assert!(line.contains("cwnd 1024KB"));
+        assert!(format_summary(&result).contains("retransmits: 1204"));
+
+        // Full speed on average, but 2% of segments resent.
+        result.tcp.as_mut().unwrap().retransmits = Some(16_230);
+        assert!(is_lossy_under_load(&result));
+        assert!(tcp_line(&result).unwrap().contains("lossy under load"));
+    }
+
+    #[test]
+    fn test_tcp_stats_omitted_when_absent() {
+        let result = result_with_link(450.0, Some(1000));
+        assert!(tcp_line(&result).is_none());
+        assert!(!is_lossy_under_load(&result));
+        assert!(serde_json::to_value(&result).unwrap().get("tcp").is_none());
+    }
+
+    #[test]
+    fn test_stream_balance_flags_stalled_stream() {
+        assert!(stream_balance(&[]).is_none());
+        assert!(stream_balance(&[940.0]).is_none());
+        assert_eq!(stream_balance(&[235.0, 235.0, 235.0, 235.0]), Some(1.0));
+
+        let mut result = result_with_link(713.0, Some(1000));
+        result.streams = 4;
+        result.streams_mbps = vec![236.0, 234.0, 231.0, 12.0];
+        result.stream_balance = stream_balance(&result.streams_mbps);
+        let balance = result.stream_balance.unwrap();
+        assert!(balance < UNBALANCED_STREAMS);
+        let line = stream_line(&result).unwrap();
+        assert!(line.starts_with("Streams: 236 / 234 / 231 / 12 Mbps"));
+        assert!(line.contains("uneven streams"));
+
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["streams_mbps"].as_array().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_efficiency_omitted_without_link_speed() {
+        let result = result_with_link(450.0, None);
+        assert!(result.efficiency_pct.is_none());
+        assert!(result.grade.is_none());
+        assert!(efficiency_line(&result).is_none());
+
+        let json = serde_json::to_value(&result).unwrap();
+        assert!(json.get("grade").is_none());
+        assert!(json.get("efficiency_pct").is_none());
+    }
 }
@@ -12,8 +12,15 @@ use anyhow::{Result, Context};
 #[ignore]
 async fn test_persona_simple_troubleshooting_live() -> Result<()> {
     println!("Step 1: Running Full Blame Check (Gateway + WAN + DNS + HTTP)...");
-    let report = packetparamedic::probes::run_blame_check().await
-        .context("Blame Check failed")?;
+    let report = packetparamedic::probes::run_blame_check(
+        &packetparamedic::probes::ProbeTimeouts::from_env(),
+        &packetparamedic::probes::ProbeRetry::from_env()?,
+        None,
+        None,
+        &tokio_util::sync::CancellationToken::new(),
+    )
+    .await
+    .context("Blame Check failed")?;
     
     assert!(!report.verdict.is_empty(), "Verdict should be present");
     assert!(!report.details.is_empty(), "Evidence details should be present");
@@ -59,7 +66,15 @@ async fn test_persona_simple_troubleshooting_live() -> Result<()> {
 async fn test_persona_high_performance_live() -> Result<()> {
     println!("Step 1: Running WAN Throughput Test (iperf3 public server)...");
     // Force a 5-second test to validate throughput engine end-to-end
-    match packetparamedic::throughput::run_test("wan", None, "5s", 1).await {
+    match packetparamedic::throughput::run_test(
+        None,
+        "wan",
+        None,
+        "5s",
+        1,
+        packetparamedic::throughput::iperf::Protocol::Tcp,
+    )
+    .await {
          Ok(_) => println!(" - WAN Throughput test (iperf3) completed successfully."),
          Err(e) => println!(" - WAN Throughput test (iperf3) failed: {}", e),
     }
@@ -81,8 +96,8 @@ async fn test_persona_high_performance_live() -> Result<()> {
                  server_hint: None,
             };
             
-            // Execute provider synchronously (blocking test thread is acceptable here)
-            match provider.run(req) {
+            // Execute provider inline (one at a time)
+            match provider.run(req).await {
                  Ok(res) => {
                      println!("      => Download: {:.2} Mbps", res.download_mbps.unwrap_or(0.0));
                      println!("      => Upload:   {:.2} Mbps", res.upload_mbps.unwrap_or(0.0));
@@ -142,7 +157,7 @@ async fn test_persona_reliability_soak_live() -> Result<()> {
     let scheduler2 = Scheduler::new(pool2);
     let list = scheduler2.list().await?;
     
-    assert!(list.iter().any(|(n, _, _, _)| n == "nightly-soak"), "Schedule lost after restart!");
+    assert!(list.iter().any(|(n, ..)| n == "nightly-soak"), "Schedule lost after restart!");
     println!(" - Schedule persisted successfully.");
     
     // Cleanup
@@ -182,7 +197,7 @@ async fn test_nat_environment_simulation() -> Result<()> {
     let providers = packetparamedic::throughput::provider::get_all_providers();
     if let Some(provider) = providers.into_iter().find(|p| p.meta().id == "reflector") {
          println!(" -> Found Reflector Provider. Measuring...");
-         match provider.run(req) { // This runs synchronously
+         match provider.run(req).await {
              Ok(res) => {
                  println!("    ✅ Local Reflector Test PASS");
                  println!("      Download: {:.2} Mbps", res.download_mbps.unwrap_or(0.0));
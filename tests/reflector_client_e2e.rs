@@ -0,0 +1,142 @@
+//! End-to-end test of [`ReflectorClient`] against a real reflector binary.
+//!
+//! The reflector is a separate crate, so this drives its built binary rather
+//! than linking it: set `PP_REFLECTOR_BIN` to it (e.g.
+//! `reflector/target/debug/reflector`) and run with `--ignored`. It covers the
+//! client's half of the wiring the reflector's own e2e tests stand in for
+//! with a hand-rolled client.
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use packetparamedic::reflector_proto::client::{ReflectorClient, Transport};
+use packetparamedic::reflector_proto::identity::Identity;
+use packetparamedic::reflector_proto::udp_echo::UdpEchoMode;
+
+/// Pairing code published to the reflector before the client connects.
+const PAIRING_CODE: &str = "E2EPAIR2";
+
+/// A reflector process that is killed when dropped.
+struct ReflectorProcess(Child);
+
+impl Drop for ReflectorProcess {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+/// A loopback port that was free a moment ago.
+fn free_port() -> u16 {
+    std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+/// Write a config into `dir`, start `serve`, and open pairing with
+/// [`PAIRING_CODE`]. `pair` waits for the code to be used, so it keeps
+/// running alongside the server as it would for an operator.
+fn start_reflector(bin: &Path, dir: &Path) -> (ReflectorProcess, ReflectorProcess, SocketAddr) {
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, free_port()));
+    let data_port = free_port();
+    let config = format!(
+        r#"
+[identity]
+private_key_path = "{dir}/identity.key"
+
+[network]
+listen_address = "{addr}"
+listen_address_health = "127.0.0.1:{health}"
+transport = "tcp"
+mode = "tunneled"
+data_port_range_start = {data_port}
+data_port_range_end = {data_port}
+
+[access]
+pairing_enabled = true
+
+[logging]
+audit_log_path = "{dir}/audit.jsonl"
+"#,
+        dir = dir.display(),
+        health = free_port(),
+    );
+    let config_path = dir.join("reflector.toml");
+    std::fs::write(&config_path, config).unwrap();
+
+    let spawn = |args: &[&str]| {
+        let child = Command::new(bin)
+            .arg("--config")
+            .arg(&config_path)
+            .args(args)
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap();
+        ReflectorProcess(child)
+    };
+    let server = spawn(&["serve"]);
+    let pairing = spawn(&["pair", "--ttl", "5m", "--code", PAIRING_CODE]);
+    (server, pairing, addr)
+}
+
+/// Connect, retrying until the reflector is listening.
+async fn connect(addr: SocketAddr) -> ReflectorClient {
+    let identity = Identity::generate();
+    let started = Instant::now();
+    loop {
+        match ReflectorClient::connect_with(addr, &identity, Transport::Tcp).await {
+            Ok(client) => return client,
+            Err(e) if started.elapsed() > Duration::from_secs(10) => {
+                panic!("reflector never accepted a connection: {:#}", e)
+            }
+            Err(_) => tokio::time::sleep(Duration::from_millis(100)).await,
+        }
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs a built reflector binary in PP_REFLECTOR_BIN"]
+async fn test_client_pairs_and_runs_udp_echo() {
+    // The binary installs this in main; tests have to do it themselves.
+    rustls::crypto::ring::default_provider().install_default().ok();
+    let bin = std::env::var("PP_REFLECTOR_BIN").expect("PP_REFLECTOR_BIN is not set");
+    let dir = tempfile::tempdir().unwrap();
+    let (_server, _pairing, addr) = start_reflector(Path::new(&bin), dir.path());
+
+    let mut client = connect(addr).await;
+    assert!(client.server_endpoint_id().is_some());
+    assert!(client.supports_keepalive());
+    client.ping().await.unwrap();
+
+    // Wait for `pair` to publish the code to the server.
+    let started = Instant::now();
+    while !dir.path().join("pairing.json").exists() {
+        assert!(started.elapsed() < Duration::from_secs(10), "pairing code never published");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    let paired = client.pair(PAIRING_CODE.to_string()).await.unwrap();
+    assert!(paired.success, "pairing refused: {:?}", paired);
+
+    let grant = client.request_udp_echo_session(2, false).await.unwrap();
+    let report = ReflectorClient::run_udp_echo(
+        &grant,
+        IpAddr::V4(Ipv4Addr::LOCALHOST),
+        &UdpEchoMode::Fixed { size: 64, count: 10 },
+    )
+    .await
+    .unwrap();
+    assert_eq!(report.sent, 10);
+    assert_eq!(report.received, 10);
+
+    let summary = client
+        .close_session(&grant.test_id)
+        .await
+        .unwrap()
+        .expect("no session summary");
+    assert_eq!(summary.test_id, grant.test_id);
+    assert!(summary.bytes_transferred > 0);
+}
@@ -71,3 +71,13 @@ fn test_live_trace_google_dns() {
         }
     }
 }
+
+#[tokio::test]
+async fn test_cancelled_trace_stops() {
+    let cancel = tokio_util::sync::CancellationToken::new();
+    cancel.cancel();
+    let err = packetparamedic::probes::trace::run_trace_cancellable("8.8.8.8", &cancel)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("cancelled"), "{}", err);
+}